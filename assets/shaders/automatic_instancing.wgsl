@@ -0,0 +1,48 @@
+#import bevy_pbr::mesh_view_bind_group
+
+struct Material {
+    color: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: Material;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+
+    [[location(3)]] i_model_0: vec4<f32>;
+    [[location(4)]] i_model_1: vec4<f32>;
+    [[location(5)]] i_model_2: vec4<f32>;
+    [[location(6)]] i_model_3: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let model = mat4x4<f32>(
+        vertex.i_model_0,
+        vertex.i_model_1,
+        vertex.i_model_2,
+        vertex.i_model_3,
+    );
+    let world_position = model * vec4<f32>(vertex.position, 1.0);
+    let world_normal = normalize((model * vec4<f32>(vertex.normal, 0.0)).xyz);
+    // a fixed light from above, so that the faces of the cubes can be told apart
+    let shade = 0.4 + 0.6 * max(dot(world_normal, normalize(vec3<f32>(0.4, 1.0, 0.6))), 0.0);
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * world_position;
+    out.color = vec4<f32>(material.color.rgb * shade, material.color.a);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
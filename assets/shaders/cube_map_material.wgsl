@@ -0,0 +1,16 @@
+#import bevy_pbr::mesh_view_bind_group
+
+[[group(1), binding(0)]]
+var environment: texture_cube<f32>;
+[[group(1), binding(1)]]
+var environment_sampler: sampler;
+
+struct FragmentInput {
+    [[location(0)]] world_position: vec4<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(input: FragmentInput) -> [[location(0)]] vec4<f32> {
+    let direction = input.world_position.xyz - view.world_position;
+    return textureSample(environment, environment_sampler, direction);
+}
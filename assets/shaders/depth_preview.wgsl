@@ -0,0 +1,26 @@
+[[group(0), binding(0)]]
+var depth_texture: texture_depth_2d;
+[[group(0), binding(1)]]
+var depth_sampler: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// a single triangle covering the whole viewport
+[[stage(vertex)]]
+fn vertex([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // the depth is reversed and non-linear, so it is brightened to make distant geometry visible
+    let depth = textureSample(depth_texture, depth_sampler, in.uv);
+    return vec4<f32>(vec3<f32>(pow(depth, 0.2)), 1.0);
+}
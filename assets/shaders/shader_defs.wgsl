@@ -29,5 +29,5 @@ fn fragment() -> [[location(0)]] vec4<f32> {
 # ifdef IS_RED 
     color = vec4<f32>(1.0, 0.0, 0.0, 1.0);
 # endif
-    return color;
+    return vec4<f32>(color.rgb * #{BRIGHTNESS}, color.a);
 }
@@ -0,0 +1,58 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+
+[[group(1), binding(0)]]
+var<uniform> mesh: Mesh;
+
+struct BaseMaterial {
+    color: vec4<f32>;
+};
+[[group(2), binding(#{MATERIAL})]]
+var<uniform> material: BaseMaterial;
+
+#ifdef WIND
+struct WindParams {
+    strength: f32;
+    frequency: f32;
+    phase: f32;
+};
+[[group(2), binding(#{WIND})]]
+var<uniform> wind: WindParams;
+#endif
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_normal: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var world_position = mesh.model * vec4<f32>(vertex.position, 1.0);
+#ifdef WIND
+    // the higher the vertex, the further it sways
+    let height = max(vertex.position.y + 1.0, 0.0);
+    world_position.x = world_position.x + wind.strength * height * sin(wind.phase + height * wind.frequency);
+#endif
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * world_position;
+    out.world_normal = mat3x3<f32>(
+        mesh.inverse_transpose_model[0].xyz,
+        mesh.inverse_transpose_model[1].xyz,
+        mesh.inverse_transpose_model[2].xyz
+    ) * vertex.normal;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // a little shading from above, so the sides of the pillars can be told apart
+    let light = 0.5 + 0.5 * max(dot(normalize(in.world_normal), normalize(vec3<f32>(0.3, 1.0, 0.5))), 0.0);
+    return vec4<f32>(material.color.rgb * light, material.color.a);
+}
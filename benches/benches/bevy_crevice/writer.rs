@@ -0,0 +1,30 @@
+use bevy_crevice::std140::{self, AsStd140};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use glam::Mat4;
+
+criterion_group!(benches, write_mat4_slice);
+criterion_main!(benches);
+
+fn write_mat4_slice(c: &mut Criterion) {
+    let matrices = (0..10_000)
+        .map(|i| Mat4::from_translation([i as f32, 0.0, 0.0].into()).as_std140())
+        .collect::<Vec<_>>();
+    let mut buffer = vec![0u8; matrices.len() * 64];
+
+    let mut group = c.benchmark_group("write_10k_mat4");
+    group.bench_function("value_by_value", |b| {
+        b.iter(|| {
+            let mut writer = std140::Writer::new(&mut buffer[..]);
+            writer.write(black_box(matrices.as_slice())).unwrap();
+        });
+    });
+    group.bench_function("bulk", |b| {
+        b.iter(|| {
+            let mut writer = std140::Writer::new(&mut buffer[..]);
+            writer
+                .write_std140_slice(black_box(matrices.as_slice()))
+                .unwrap();
+        });
+    });
+    group.finish();
+}
@@ -0,0 +1,82 @@
+use bevy_crevice::std140::{self, AsStd140};
+use bevy_render::render_resource::{coalesce_ranges, write_std140_parallel, MAX_COALESCED_GAP};
+use bevy_tasks::TaskPoolBuilder;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::{Mat4, Vec4};
+
+criterion_group!(benches, write_50k_uniforms, coalesce_uniform_writes);
+criterion_main!(benches);
+
+#[derive(AsStd140)]
+struct EntityUniform {
+    transform: Mat4,
+    color: Vec4,
+}
+
+fn write_50k_uniforms(c: &mut Criterion) {
+    let uniforms = (0..50_000)
+        .map(|i| EntityUniform {
+            transform: Mat4::from_translation([i as f32, 0.0, 0.0].into()),
+            color: Vec4::splat(1.0),
+        })
+        .collect::<Vec<_>>();
+    let item_size = EntityUniform::std140_size_static();
+    let mut buffer = vec![0u8; uniforms.len() * item_size];
+
+    let mut group = c.benchmark_group("write_50k_uniforms");
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            let mut writer = std140::Writer::new(&mut buffer[..]);
+            writer.write(black_box(uniforms.as_slice())).unwrap();
+        });
+    });
+    for thread_count in &[1, 2, 4, 8, 16] {
+        let pool = TaskPoolBuilder::new().num_threads(*thread_count).build();
+        group.bench_with_input(
+            BenchmarkId::new("threads", thread_count),
+            thread_count,
+            |b, _| {
+                b.iter(|| {
+                    write_std140_parallel(
+                        black_box(uniforms.as_slice()),
+                        item_size,
+                        &mut buffer,
+                        &pool,
+                    );
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Coalesces the slots of the changed dynamic uniforms of 50k entities into as few writes as
+/// possible.
+fn coalesce_uniform_writes(c: &mut Criterion) {
+    let item_size = 256;
+    let scenarios: [(&str, fn(usize) -> bool); 3] = [
+        ("every_entity", |_| true),
+        ("every_third_entity", |entity| entity % 3 == 0),
+        // e.g. a few animated entities between static ones
+        ("one_in_a_hundred", |entity| entity % 100 == 0),
+    ];
+
+    let mut group = c.benchmark_group("coalesce_uniform_writes");
+    let mut coalesced = Vec::new();
+    for (name, changed) in scenarios {
+        let ranges = (0..50_000)
+            .filter(|entity| changed(*entity))
+            .map(|entity| entity * item_size..(entity + 1) * item_size)
+            .collect::<Vec<_>>();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                coalesce_ranges(
+                    black_box(ranges.iter().cloned()),
+                    MAX_COALESCED_GAP,
+                    &mut coalesced,
+                );
+            });
+        });
+    }
+    group.finish();
+}
@@ -77,7 +77,10 @@ impl Node for ClearPassNode {
                         load: LoadOp::Clear(0.0),
                         store: true,
                     }),
-                    stencil_ops: None,
+                    stencil_ops: depth.stencil_ops(Operations {
+                        load: LoadOp::Clear(0),
+                        store: true,
+                    }),
                 }),
             };
 
@@ -20,20 +20,21 @@ pub use main_pass_driver::*;
 use std::ops::Range;
 
 use bevy_app::{App, Plugin};
+use bevy_asset::HandleId;
 use bevy_core::FloatOrd;
 use bevy_ecs::prelude::*;
 use bevy_render::{
     camera::{ActiveCamera, Camera2d, Camera3d, RenderTarget},
     color::Color,
-    render_graph::{EmptyNode, RenderGraph, SlotInfo, SlotType},
+    render_graph::{ComputeDispatchNode, EmptyNode, RenderGraph, SlotInfo, SlotType},
     render_phase::{
         batch_phase_system, sort_phase_system, BatchedPhaseItem, CachedRenderPipelinePhaseItem,
-        DrawFunctionId, DrawFunctions, EntityPhaseItem, PhaseItem, RenderPhase,
+        DrawFunctionId, DrawFunctions, DrawStateKey, EntityPhaseItem, PhaseItem, RenderPhase,
     },
     render_resource::*,
     renderer::RenderDevice,
     texture::TextureCache,
-    view::{ExtractedView, Msaa, ViewDepthTexture},
+    view::{ExtractedView, Msaa, ViewDepthFormat, ViewDepthTexture},
     RenderApp, RenderStage, RenderWorld,
 };
 
@@ -142,6 +143,7 @@ impl Plugin for CorePipelinePlugin {
             .add_system_to_stage(RenderStage::PhaseSort, sort_phase_system::<AlphaMask3d>)
             .add_system_to_stage(RenderStage::PhaseSort, sort_phase_system::<Transparent3d>);
 
+        let compute_dispatch_node = ComputeDispatchNode::new(&mut render_app.world);
         let clear_pass_node = ClearPassNode::new(&mut render_app.world);
         let pass_node_2d = MainPass2dNode::new(&mut render_app.world);
         let pass_node_3d = MainPass3dNode::new(&mut render_app.world);
@@ -192,6 +194,11 @@ impl Plugin for CorePipelinePlugin {
         graph
             .add_node_edge(node::CLEAR_PASS_DRIVER, node::MAIN_PASS_DRIVER)
             .unwrap();
+        // compute dispatches run before the main passes, which can read their results
+        graph.add_node(ComputeDispatchNode::NAME, compute_dispatch_node);
+        graph
+            .add_node_edge(ComputeDispatchNode::NAME, node::MAIN_PASS_DEPENDENCIES)
+            .unwrap();
     }
 }
 
@@ -242,19 +249,53 @@ impl BatchedPhaseItem for Transparent2d {
     }
 }
 
+/// An opaque draw of the 3D main pass.
+///
+/// Opaque draws are sorted by the state they bind, so that draws of the same pipeline, material
+/// and mesh are drawn after each other and don't have to rebind it. Draws of the same state are
+/// drawn front-to-back by their `distance`, and ties are broken by their entity, so the order is
+/// deterministic.
 pub struct Opaque3d {
     pub distance: f32,
     pub pipeline: CachedRenderPipelineId,
+    /// The bind group of the material of the draw, if it has one.
+    pub material_bind_group: Option<BindGroupId>,
+    pub mesh: Option<HandleId>,
     pub entity: Entity,
     pub draw_function: DrawFunctionId,
 }
 
+impl Opaque3d {
+    /// Creates a draw without a material bind group or mesh, which is only sorted by its
+    /// `pipeline` and `distance`.
+    pub fn new(
+        distance: f32,
+        pipeline: CachedRenderPipelineId,
+        entity: Entity,
+        draw_function: DrawFunctionId,
+    ) -> Self {
+        Self {
+            distance,
+            pipeline,
+            material_bind_group: None,
+            mesh: None,
+            entity,
+            draw_function,
+        }
+    }
+}
+
 impl PhaseItem for Opaque3d {
-    type SortKey = FloatOrd;
+    type SortKey = (DrawStateKey, FloatOrd, Entity);
 
     #[inline]
     fn sort_key(&self) -> Self::SortKey {
-        FloatOrd(self.distance)
+        let state = DrawStateKey {
+            pipeline: self.pipeline,
+            bind_group: self.material_bind_group,
+            mesh: self.mesh,
+        };
+        (state, FloatOrd(self.distance), self.entity)
     }
 
     #[inline]
@@ -388,6 +429,7 @@ pub fn prepare_core_views_system(
     mut commands: Commands,
     mut texture_cache: ResMut<TextureCache>,
     msaa: Res<Msaa>,
+    depth_format: Res<ViewDepthFormat>,
     render_device: Res<RenderDevice>,
     views_3d: Query<
         (Entity, &ExtractedView),
@@ -411,14 +453,27 @@ pub fn prepare_core_views_system(
                 mip_level_count: 1,
                 sample_count: msaa.samples,
                 dimension: TextureDimension::D2,
-                format: TextureFormat::Depth32Float, /* PERF: vulkan docs recommend using 24
-                                                      * bit depth for better performance */
-                usage: TextureUsages::RENDER_ATTACHMENT,
+                // PERF: vulkan docs recommend using 24 bit depth for better performance
+                format: depth_format.0,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
             },
         );
+        // only the depth aspect can be sampled
+        let depth_view = if depth_format.has_stencil() {
+            cached_texture.texture.create_view(&TextureViewDescriptor {
+                label: Some("view_depth_texture_depth_view"),
+                aspect: TextureAspect::DepthOnly,
+                ..Default::default()
+            })
+        } else {
+            cached_texture.default_view.clone()
+        };
         commands.entity(entity).insert(ViewDepthTexture {
             texture: cached_texture.texture,
             view: cached_texture.default_view,
+            depth_view,
+            format: *depth_format,
+            sample_count: msaa.samples,
         });
     }
 }
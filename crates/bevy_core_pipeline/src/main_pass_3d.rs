@@ -1,6 +1,7 @@
 use crate::{AlphaMask3d, Opaque3d, Transparent3d};
 use bevy_ecs::prelude::*;
 use bevy_render::{
+    camera::ExtractedCamera,
     render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
     render_phase::{DrawFunctions, RenderPhase, TrackedRenderPass},
     render_resource::{LoadOp, Operations, RenderPassDepthStencilAttachment, RenderPassDescriptor},
@@ -18,6 +19,7 @@ pub struct MainPass3dNode {
             &'static RenderPhase<Transparent3d>,
             &'static ViewTarget,
             &'static ViewDepthTexture,
+            Option<&'static ExtractedCamera>,
         ),
         With<ExtractedView>,
     >,
@@ -49,7 +51,7 @@ impl Node for MainPass3dNode {
         world: &World,
     ) -> Result<(), NodeRunError> {
         let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
-        let (opaque_phase, alpha_mask_phase, transparent_phase, target, depth) =
+        let (opaque_phase, alpha_mask_phase, transparent_phase, target, depth, camera) =
             match self.query.get_manual(world, view_entity) {
                 Ok(query) => query,
                 Err(_) => return Ok(()), // No window
@@ -75,7 +77,10 @@ impl Node for MainPass3dNode {
                         load: LoadOp::Load,
                         store: true,
                     }),
-                    stencil_ops: None,
+                    stencil_ops: depth.stencil_ops(Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    }),
                 }),
             };
 
@@ -86,6 +91,9 @@ impl Node for MainPass3dNode {
                 .begin_render_pass(&pass_descriptor);
             let mut draw_functions = draw_functions.write();
             let mut tracked_pass = TrackedRenderPass::new(render_pass);
+            if let Some(camera) = camera {
+                tracked_pass.set_camera_viewport(camera);
+            }
             for item in &opaque_phase.items {
                 let draw_function = draw_functions.get_mut(item.draw_function).unwrap();
                 draw_function.draw(world, &mut tracked_pass, view_entity, item);
@@ -111,7 +119,10 @@ impl Node for MainPass3dNode {
                         load: LoadOp::Load,
                         store: true,
                     }),
-                    stencil_ops: None,
+                    stencil_ops: depth.stencil_ops(Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    }),
                 }),
             };
 
@@ -122,6 +133,9 @@ impl Node for MainPass3dNode {
                 .begin_render_pass(&pass_descriptor);
             let mut draw_functions = draw_functions.write();
             let mut tracked_pass = TrackedRenderPass::new(render_pass);
+            if let Some(camera) = camera {
+                tracked_pass.set_camera_viewport(camera);
+            }
             for item in &alpha_mask_phase.items {
                 let draw_function = draw_functions.get_mut(item.draw_function).unwrap();
                 draw_function.draw(world, &mut tracked_pass, view_entity, item);
@@ -149,7 +163,12 @@ impl Node for MainPass3dNode {
                         load: LoadOp::Load,
                         store: false,
                     }),
-                    stencil_ops: None,
+                    // Unlike depth, the stencil buffer is stored, since transparent materials can
+                    // write stencil values (e.g. a glass portal frame masking what is drawn later).
+                    stencil_ops: depth.stencil_ops(Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    }),
                 }),
             };
 
@@ -160,6 +179,9 @@ impl Node for MainPass3dNode {
                 .begin_render_pass(&pass_descriptor);
             let mut draw_functions = draw_functions.write();
             let mut tracked_pass = TrackedRenderPass::new(render_pass);
+            if let Some(camera) = camera {
+                tracked_pass.set_camera_viewport(camera);
+            }
             for item in &transparent_phase.items {
                 let draw_function = draw_functions.get_mut(item.draw_function).unwrap();
                 draw_function.draw(world, &mut tracked_pass, view_entity, item);
@@ -0,0 +1,142 @@
+use bevy_macro_utils::get_named_struct_fields;
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_quote, DeriveInput, Error, Path};
+
+/// The fields are stored as the bits of a `u64`.
+const MAX_FIELDS: usize = 64;
+
+pub fn emit(input: DeriveInput) -> TokenStream {
+    let bevy_crevice_path = crate::bevy_crevice_path();
+    let mod_path: Path = parse_quote!(#bevy_crevice_path::std140);
+
+    let visibility = input.vis;
+    let input_name = input.ident;
+    if !input.generics.params.is_empty() {
+        return Error::new(
+            Span::call_site(),
+            "DirtyStd140 can't be derived for generic structs",
+        )
+        .into_compile_error();
+    }
+
+    let fields: Vec<_> = match get_named_struct_fields(&input.data) {
+        Ok(fields) => fields.named.iter().collect(),
+        Err(e) => return e.into_compile_error(),
+    };
+    if fields.len() > MAX_FIELDS {
+        return Error::new(
+            Span::call_site(),
+            format!("DirtyStd140 supports at most {} fields", MAX_FIELDS),
+        )
+        .into_compile_error();
+    }
+
+    // the struct generated by `#[derive(AsStd140)]`
+    let std140_name = format_ident!("Std140{}", input_name);
+    let dirty_name = format_ident!("{}Dirty", input_name);
+    let setters_name = format_ident!("{}Setters", input_name);
+
+    let field_names: Vec<_> = fields
+        .iter()
+        .map(|field| field.ident.as_ref().unwrap())
+        .collect();
+    let flag_names: Vec<_> = field_names
+        .iter()
+        .map(|name| format_ident!("{}", name.to_string().to_uppercase()))
+        .collect();
+    let indices: Vec<_> = (0..fields.len()).collect();
+    let count = fields.len();
+    let all_bits = if count == MAX_FIELDS {
+        quote!(u64::MAX)
+    } else {
+        quote!((1u64 << #count) - 1)
+    };
+    let dirty_doc = format!("The set of the fields of [`{}`] that changed.", input_name);
+
+    let flags = flag_names.iter().zip(&indices).map(|(flag, index)| {
+        quote! {
+            pub const #flag: Self = Self(1 << #index);
+        }
+    });
+
+    let write_fields = field_names.iter().zip(&indices).map(|(field, index)| {
+        quote! {
+            #index => {
+                let value = #mod_path::AsStd140::as_std140(&self.#field);
+                let value = #mod_path::Std140::as_bytes(&value);
+                let layout = ::core::mem::MaybeUninit::<#std140_name>::uninit();
+                let base = layout.as_ptr();
+                // SAFETY: only the address of the field is taken, the memory isn't read
+                let offset = unsafe { ::core::ptr::addr_of!((*base).#field) } as usize - base as usize;
+                bytes[offset..offset + value.len()].copy_from_slice(value);
+                offset..offset + value.len()
+            }
+        }
+    });
+
+    let setters = fields.iter().zip(&flag_names).map(|(field, flag)| {
+        let field_name = field.ident.as_ref().unwrap();
+        let field_ty = &field.ty;
+        let setter = format_ident!("set_{}", field_name);
+        quote! {
+            fn #setter(&mut self, #field_name: #field_ty) {
+                self.untracked_mut().#field_name = #field_name;
+                self.mark_dirty(#dirty_name::#flag);
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #dirty_doc]
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+        #visibility struct #dirty_name(u64);
+
+        impl #dirty_name {
+            #(#flags)*
+        }
+
+        impl ::core::ops::BitOr for #dirty_name {
+            type Output = Self;
+
+            #[inline]
+            fn bitor(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+        }
+
+        impl ::core::ops::BitOrAssign for #dirty_name {
+            #[inline]
+            fn bitor_assign(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+        }
+
+        impl #mod_path::DirtyMask for #dirty_name {
+            const ALL: Self = Self(#all_bits);
+
+            #[inline]
+            fn bits(self) -> u64 {
+                self.0
+            }
+        }
+
+        impl #mod_path::DirtyStd140 for #input_name {
+            type Dirty = #dirty_name;
+
+            fn write_std140_field(&self, index: usize, bytes: &mut [u8]) -> ::core::ops::Range<usize> {
+                match index {
+                    #(#write_fields)*
+                    _ => panic!("{} has no field with the index {}", stringify!(#input_name), index),
+                }
+            }
+        }
+
+        /// Setters that mark the fields they set as dirty.
+        #visibility trait #setters_name: #mod_path::DirtyTracker<#input_name> {
+            #(#setters)*
+        }
+
+        impl<T: #mod_path::DirtyTracker<#input_name> + ?Sized> #setters_name for T {}
+    }
+}
@@ -0,0 +1,225 @@
+use bevy_macro_utils::get_named_struct_fields;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    DeriveInput, Error, Field, Ident, Lit, LitStr, Token,
+};
+
+pub fn emit(input: DeriveInput) -> TokenStream {
+    let bevy_crevice_path = crate::bevy_crevice_path();
+
+    let fields = match get_named_struct_fields(&input.data) {
+        Ok(fields) => fields,
+        Err(e) => return e.into_compile_error(),
+    };
+
+    let mut editable_fields = Vec::new();
+    for field in &fields.named {
+        match FieldAttributes::parse(field) {
+            Ok(attributes) if attributes.skip => {}
+            Ok(attributes) => editable_fields.push((field, attributes)),
+            Err(e) => return e.into_compile_error(),
+        }
+    }
+
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let value_trait_path = quote!(#bevy_crevice_path::editor::EditableValue);
+
+    let field_infos = editable_fields.iter().map(|(field, attributes)| {
+        let field_ty = &field.ty;
+        let field_name_str = field.ident.as_ref().unwrap().to_string();
+        let display_name = match &attributes.display_name {
+            Some(display_name) => quote!(::core::option::Option::Some(#display_name)),
+            None => quote!(::core::option::Option::None),
+        };
+        let range = match attributes.range {
+            Some((min, max)) => quote!(::core::option::Option::Some((#min, #max))),
+            None => quote!(::core::option::Option::None),
+        };
+        let semantic = &attributes.semantic;
+
+        quote! {
+            #bevy_crevice_path::editor::FieldEditorInfo {
+                name: #field_name_str,
+                display_name: #display_name,
+                range: #range,
+                semantic: #bevy_crevice_path::editor::FieldSemantic::#semantic,
+                components: <#field_ty as #value_trait_path>::COMPONENTS,
+            },
+        }
+    });
+
+    let set_field_arms = editable_fields
+        .iter()
+        .enumerate()
+        .map(|(index, (field, _))| {
+            let field_name = field.ident.as_ref().unwrap();
+
+            quote! {
+                #index => #value_trait_path::set_components(&mut self.#field_name, components),
+            }
+        });
+
+    let field_index_arms = editable_fields
+        .iter()
+        .enumerate()
+        .map(|(index, (field, _))| {
+            let field_name_str = field.ident.as_ref().unwrap().to_string();
+
+            quote! {
+                #field_name_str => ::core::option::Option::Some(#index),
+            }
+        });
+
+    quote! {
+        impl #impl_generics #bevy_crevice_path::editor::EditableUniform for #name #ty_generics #where_clause {
+            const EDITOR_FIELDS: &'static [#bevy_crevice_path::editor::FieldEditorInfo] = &[
+                #( #field_infos )*
+            ];
+
+            fn field_index(name: &str) -> ::core::option::Option<usize> {
+                match name {
+                    #( #field_index_arms )*
+                    _ => {
+                        #bevy_crevice_path::internal::unknown_field(
+                            stringify!(#name),
+                            name,
+                            Self::EDITOR_FIELDS,
+                        );
+                        ::core::option::Option::None
+                    }
+                }
+            }
+
+            fn set_field(&mut self, index: usize, components: &[f32]) -> bool {
+                match index {
+                    #( #set_field_arms )*
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// The metadata given to a field with `#[uniform(...)]` attributes.
+struct FieldAttributes {
+    skip: bool,
+    display_name: Option<String>,
+    range: Option<(f64, f64)>,
+    semantic: Ident,
+}
+
+impl FieldAttributes {
+    fn parse(field: &Field) -> Result<Self, Error> {
+        let mut skip = false;
+        let mut display_name = None;
+        let mut min = None;
+        let mut max = None;
+        let mut semantic = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("uniform"))
+        {
+            let args =
+                attr.parse_args_with(Punctuated::<FieldArgument, Token![,]>::parse_terminated)?;
+            for arg in args {
+                match arg {
+                    FieldArgument::Skip => skip = true,
+                    FieldArgument::DisplayName(name) => display_name = Some(name.value()),
+                    FieldArgument::Min(value) => min = Some(value),
+                    FieldArgument::Max(value) => max = Some(value),
+                    FieldArgument::Semantic(name) => {
+                        let variant = match name.value().as_str() {
+                            "color" => "Color",
+                            "direction" => "Direction",
+                            "factor" => "Factor",
+                            "raw" => "Raw",
+                            _ => {
+                                return Err(Error::new_spanned(
+                                    name,
+                                    "unknown semantic, expected one of `color`, `direction`, \
+                                     `factor` or `raw`",
+                                ));
+                            }
+                        };
+                        semantic = Some(Ident::new(variant, name.span()));
+                    }
+                }
+            }
+        }
+
+        let range = match (min, max) {
+            (None, None) => None,
+            (Some(min), Some(max)) if min <= max => Some((min, max)),
+            (Some(_), Some(_)) => {
+                return Err(Error::new_spanned(
+                    field,
+                    "the `min` value has to be at most the `max` value",
+                ));
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    field,
+                    "`min` and `max` have to be given together",
+                ));
+            }
+        };
+
+        Ok(Self {
+            skip,
+            display_name,
+            range,
+            semantic: semantic.unwrap_or_else(|| Ident::new("Raw", Span::call_site())),
+        })
+    }
+}
+
+/// A single argument of a `#[uniform(...)]` attribute.
+enum FieldArgument {
+    Skip,
+    DisplayName(LitStr),
+    Min(f64),
+    Max(f64),
+    Semantic(LitStr),
+}
+
+impl Parse for FieldArgument {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        if name == "skip" {
+            return Ok(FieldArgument::Skip);
+        }
+
+        let name_str = name.to_string();
+        if !["display_name", "semantic", "min", "max"].contains(&name_str.as_str()) {
+            return Err(Error::new_spanned(
+                name,
+                "unknown attribute, expected `skip`, `display_name`, `min`, `max` or `semantic`",
+            ));
+        }
+
+        input.parse::<Token![=]>()?;
+        Ok(match name_str.as_str() {
+            "display_name" => FieldArgument::DisplayName(input.parse()?),
+            "semantic" => FieldArgument::Semantic(input.parse()?),
+            "min" => FieldArgument::Min(parse_number(input)?),
+            _ => FieldArgument::Max(parse_number(input)?),
+        })
+    }
+}
+
+/// Parses a possibly negative integer or float literal.
+fn parse_number(input: ParseStream) -> syn::Result<f64> {
+    let negative = input.parse::<Option<Token![-]>>()?.is_some();
+    let value = match input.parse()? {
+        Lit::Float(lit) => lit.base10_parse::<f64>()?,
+        Lit::Int(lit) => lit.base10_parse::<f64>()?,
+        lit => return Err(Error::new_spanned(lit, "expected a number")),
+    };
+    Ok(if negative { -value } else { value })
+}
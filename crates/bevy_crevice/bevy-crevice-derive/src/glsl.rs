@@ -1,7 +1,7 @@
 use bevy_macro_utils::get_named_struct_fields;
 use proc_macro2::{Literal, TokenStream};
 use quote::quote;
-use syn::{parse_quote, DeriveInput, Path};
+use syn::{parse_quote, DeriveInput, Error, Lit, Meta, NestedMeta, Path};
 
 pub fn emit(input: DeriveInput) -> TokenStream {
     let bevy_crevice_path = crate::bevy_crevice_path();
@@ -11,6 +11,12 @@ pub fn emit(input: DeriveInput) -> TokenStream {
         Err(e) => return e.into_compile_error(),
     };
 
+    let include_path = match include_path(&input) {
+        Ok(Some(include_path)) => quote!(::core::option::Option::Some(#include_path)),
+        Ok(None) => quote!(::core::option::Option::None),
+        Err(e) => return e.into_compile_error(),
+    };
+
     let base_trait_path: Path = parse_quote!(#bevy_crevice_path::glsl::Glsl);
     let struct_trait_path: Path = parse_quote!(#bevy_crevice_path::glsl::GlslStruct);
 
@@ -40,9 +46,41 @@ pub fn emit(input: DeriveInput) -> TokenStream {
         }
 
         unsafe impl #impl_generics #struct_trait_path for #name #ty_generics #where_clause {
+            const INCLUDE_PATH: ::core::option::Option<&'static str> = #include_path;
+
             fn enumerate_fields(s: &mut String) {
                 #( #glsl_fields )*
             }
         }
     }
 }
+
+/// Parses the path given by `#[glsl(include_path = "...")]`.
+fn include_path(input: &DeriveInput) -> Result<Option<String>, Error> {
+    let mut include_path = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("glsl")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected `glsl(...)`")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("include_path") => {
+                    match pair.lit {
+                        Lit::Str(path) => include_path = Some(path.value()),
+                        lit => {
+                            return Err(Error::new_spanned(lit, "expected a string literal"));
+                        }
+                    }
+                }
+                nested => {
+                    return Err(Error::new_spanned(
+                        nested,
+                        "unknown attribute, expected `include_path = \"...\"`",
+                    ));
+                }
+            }
+        }
+    }
+    Ok(include_path)
+}
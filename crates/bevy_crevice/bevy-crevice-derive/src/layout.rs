@@ -1,6 +1,6 @@
 use bevy_macro_utils::get_named_struct_fields;
 use proc_macro2::{Span, TokenStream};
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, ToTokens};
 use syn::{parse_quote, DeriveInput, Ident, Path, Type};
 
 pub fn emit(
@@ -21,6 +21,7 @@ pub fn emit(
     let as_trait_path: Path = parse_quote!(#mod_path::#as_trait_name);
     let as_trait_method = format_ident!("as_{}", mod_name);
     let from_trait_method = format_ident!("from_{}", mod_name);
+    let describe_trait_method = format_ident!("describe_{}_layout", mod_name);
 
     let padded_name = format_ident!("{}Padded", trait_name);
     let padded_path: Path = parse_quote!(#mod_path::#padded_name);
@@ -188,6 +189,49 @@ pub fn emit(
         })
         .collect();
 
+    // Computes the offset of the field with the given index, right after the padding of the
+    // previous field.
+    let offset_of_field = |target: usize| {
+        let mut output = vec![quote!(0usize)];
+        for index in 0..target {
+            let layout_ty = layout_version_of_ty(&fields[index].ty);
+            let pad_fn = &pad_fns[index];
+            output.push(quote! {
+                + ::core::mem::size_of::<#layout_ty>() + #pad_fn()
+            });
+        }
+
+        output.into_iter().collect::<TokenStream>()
+    };
+
+    let field_layouts: TokenStream = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let field_name = field.ident.as_ref().unwrap().to_string();
+            let field_ty = &field.ty;
+            // the tokens of the type are separated by spaces, which aren't needed in types
+            // usable in uniforms
+            let field_ty_str = field_ty.to_token_stream().to_string().replace(' ', "");
+            let layout_ty = layout_version_of_ty(field_ty);
+            let alignment = layout_alignment_of_ty(field_ty);
+            let offset = offset_of_field(index);
+            let pad_fn = &pad_fns[index];
+
+            quote! {
+                #bevy_crevice_path::layout::FieldLayoutDescription {
+                    name: #field_name,
+                    ty: #field_ty_str,
+                    offset: #offset,
+                    size: ::core::mem::size_of::<#layout_ty>(),
+                    alignment: #alignment,
+                    padding: #pad_fn(),
+                    describe: <#field_ty as #as_trait_path>::#describe_trait_method,
+                },
+            }
+        })
+        .collect();
+
     let struct_definition = quote! {
         #[derive(Debug, Clone, Copy)]
         #[repr(C)]
@@ -248,6 +292,20 @@ pub fn emit(
         quote!()
     };
 
+    let inventory_submission = if cfg!(feature = "layout-inventory") {
+        quote! {
+            #bevy_crevice_path::internal::inventory::submit! {
+                #bevy_crevice_path::layout::RegisteredLayout {
+                    name: stringify!(#input_name),
+                    standard: #bevy_crevice_path::layout::LayoutStandard::#trait_name,
+                    describe: <#input_name as #as_trait_path>::#describe_trait_method,
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     quote! {
         #pad_fn_impls
         #struct_definition
@@ -255,6 +313,12 @@ pub fn emit(
         unsafe impl #impl_generics #bevy_crevice_path::internal::bytemuck::Zeroable for #generated_name #ty_generics #where_clause {}
         unsafe impl #impl_generics #bevy_crevice_path::internal::bytemuck::Pod for #generated_name #ty_generics #where_clause {}
 
+        impl #impl_generics #generated_name #ty_generics #where_clause {
+            const FIELD_LAYOUTS: &'static [#bevy_crevice_path::layout::FieldLayoutDescription] = &[
+                #field_layouts
+            ];
+        }
+
         unsafe impl #impl_generics #mod_path::#trait_name for #generated_name #ty_generics #where_clause {
             const ALIGNMENT: usize = #struct_alignment;
             const PAD_AT_END: bool = true;
@@ -280,8 +344,18 @@ pub fn emit(
                     #input_struct_field_init
                 }
             }
+
+            fn #describe_trait_method() -> #bevy_crevice_path::layout::UniformLayoutDescription {
+                #bevy_crevice_path::layout::UniformLayoutDescription {
+                    name: stringify!(#input_name),
+                    size: ::core::mem::size_of::<#generated_name>(),
+                    alignment: <#generated_name as #trait_path>::ALIGNMENT,
+                    fields: <#generated_name #ty_generics>::FIELD_LAYOUTS,
+                }
+            }
         }
 
         #debug_methods
+        #inventory_submission
     }
 }
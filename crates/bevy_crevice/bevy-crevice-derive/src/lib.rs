@@ -1,3 +1,5 @@
+mod dirty;
+mod editor;
 mod glsl;
 mod layout;
 
@@ -22,7 +24,23 @@ pub fn derive_as_std430(input: CompilerTokenStream) -> CompilerTokenStream {
     CompilerTokenStream::from(expanded)
 }
 
-#[proc_macro_derive(GlslStruct)]
+#[proc_macro_derive(DirtyStd140)]
+pub fn derive_dirty_std140(input: CompilerTokenStream) -> CompilerTokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let expanded = dirty::emit(input);
+
+    CompilerTokenStream::from(expanded)
+}
+
+#[proc_macro_derive(EditableUniform, attributes(uniform))]
+pub fn derive_editable_uniform(input: CompilerTokenStream) -> CompilerTokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let expanded = editor::emit(input);
+
+    CompilerTokenStream::from(expanded)
+}
+
+#[proc_macro_derive(GlslStruct, attributes(glsl))]
 pub fn derive_glsl_struct(input: CompilerTokenStream) -> CompilerTokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let expanded = glsl::emit(input);
@@ -0,0 +1,234 @@
+//! Defines traits and types for editing the fields of uniform structs generically, e.g. in an
+//! in-game material editor.
+
+pub use bevy_crevice_derive::EditableUniform;
+
+/// What the value of a field represents, which tells editors how to present it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FieldSemantic {
+    /// A color, usually edited with a color picker.
+    Color,
+    /// A direction, usually edited as a normalized vector.
+    Direction,
+    /// A factor, usually edited with a slider between `0.0` and `1.0`.
+    Factor,
+    /// A value without any special meaning.
+    Raw,
+}
+
+impl Default for FieldSemantic {
+    fn default() -> Self {
+        FieldSemantic::Raw
+    }
+}
+
+/// The metadata of an editable field of a struct, as part of
+/// [`EditableUniform::EDITOR_FIELDS`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldEditorInfo {
+    /// The name of the field.
+    pub name: &'static str,
+    /// The name to show in editors instead of the name of the field.
+    pub display_name: Option<&'static str>,
+    /// The inclusive range of values editors should allow for each component.
+    pub range: Option<(f64, f64)>,
+    /// What the value of the field represents.
+    pub semantic: FieldSemantic,
+    /// The number of components of the value, which is `1` for scalars.
+    pub components: usize,
+}
+
+impl FieldEditorInfo {
+    /// Returns the name editors should show for the field, which is the display name if there is
+    /// one and the name of the field otherwise.
+    pub fn label(&self) -> &'static str {
+        self.display_name.unwrap_or(self.name)
+    }
+}
+
+/// Trait for structs whose fields can be listed and written by editors.
+///
+/// This trait should not generally be implemented by hand, but can be derived. The metadata of
+/// each field is given with `#[uniform(...)]` attributes, which accept a `display_name`, a
+/// `min` and `max` value, and a `semantic` out of `"color"`, `"direction"`, `"factor"` and
+/// `"raw"`. All fields have to implement [`EditableValue`], unless they are skipped with
+/// `#[uniform(skip)]`:
+///
+/// ```rust
+/// use bevy_crevice::editor::{EditableUniform, FieldSemantic};
+///
+/// #[derive(EditableUniform)]
+/// struct StandardMaterial {
+///     #[uniform(display_name = "Base Color", semantic = "color")]
+///     base_color: mint::Vector4<f32>,
+///     #[uniform(min = 0.089, max = 1.0, semantic = "factor")]
+///     roughness: f32,
+///     #[uniform(skip)]
+///     flags: [u32; 2],
+/// }
+///
+/// let fields = StandardMaterial::EDITOR_FIELDS;
+/// assert_eq!(fields.len(), 2);
+/// assert_eq!(fields[0].label(), "Base Color");
+/// assert_eq!(fields[1].range, Some((0.089, 1.0)));
+/// assert_eq!(fields[1].semantic, FieldSemantic::Factor);
+///
+/// let mut material = StandardMaterial {
+///     base_color: [1.0, 1.0, 1.0, 1.0].into(),
+///     roughness: 0.5,
+///     flags: [0; 2],
+/// };
+/// assert!(material.set_field(0, &[1.0, 0.0, 0.0, 1.0]));
+/// assert!(material.set_field_f32(1, 0.25));
+/// assert_eq!(material.roughness, 0.25);
+/// // the base color has four components
+/// assert!(!material.set_field_f32(0, 0.5));
+/// assert!(material.set_field_by_name("roughness", &[0.5]));
+/// assert_eq!(StandardMaterial::field_index("roughness"), Some(1));
+/// ```
+pub trait EditableUniform {
+    /// The metadata of the editable fields, in declaration order. The index of a field in this
+    /// slice is passed to the mutators.
+    const EDITOR_FIELDS: &'static [FieldEditorInfo];
+
+    /// Returns the index of the editable field with the given name.
+    ///
+    /// With the `strict-names` feature, this panics for unknown names instead of returning
+    /// `None`, listing the names of the editable fields and suggesting the closest one, which
+    /// catches typos in code looking fields up by strings.
+    fn field_index(name: &str) -> Option<usize>;
+
+    /// Writes the components of the editable field with the given index.
+    ///
+    /// Returns `false` without writing anything if there is no such field or if the number of
+    /// components doesn't match the field.
+    fn set_field(&mut self, index: usize, components: &[f32]) -> bool;
+
+    /// Writes the editable scalar field with the given index.
+    ///
+    /// Returns `false` without writing anything if there is no such field or if it isn't a
+    /// scalar.
+    fn set_field_f32(&mut self, index: usize, value: f32) -> bool {
+        self.set_field(index, &[value])
+    }
+
+    /// Writes the components of the editable field with the given name, see
+    /// [`field_index`](EditableUniform::field_index) and
+    /// [`set_field`](EditableUniform::set_field).
+    fn set_field_by_name(&mut self, name: &str, components: &[f32]) -> bool {
+        match Self::field_index(name) {
+            Some(index) => self.set_field(index, components),
+            None => false,
+        }
+    }
+}
+
+/// Trait for the types of fields of [`EditableUniform`] structs, which are written from `f32`
+/// components.
+pub trait EditableValue {
+    /// The number of components of the type, which is `1` for scalars.
+    const COMPONENTS: usize;
+
+    /// Writes the components of the value, converting them to the type of the components.
+    ///
+    /// Returns `false` without writing anything if the number of components doesn't match.
+    fn set_components(&mut self, components: &[f32]) -> bool;
+}
+
+macro_rules! editable_scalars {
+    ( $( $ty:ty, )* ) => {
+        $(
+            impl EditableValue for $ty {
+                const COMPONENTS: usize = 1;
+
+                fn set_components(&mut self, components: &[f32]) -> bool {
+                    match *components {
+                        [value] => {
+                            *self = value as $ty;
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! editable_vectors {
+    ( $( $ty:ty { $( $field:ident ),* }, )* ) => {
+        $(
+            impl EditableValue for $ty {
+                const COMPONENTS: usize = [$( stringify!($field) ),*].len();
+
+                fn set_components(&mut self, components: &[f32]) -> bool {
+                    match *components {
+                        [$( $field ),*] => {
+                            $( self.$field = $field as _; )*
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "glam")]
+macro_rules! editable_minty {
+    ( $( $mint_ty:ty => $imp_ty:ty, )* ) => {
+        $(
+            impl EditableValue for $imp_ty {
+                const COMPONENTS: usize = <$mint_ty as EditableValue>::COMPONENTS;
+
+                fn set_components(&mut self, components: &[f32]) -> bool {
+                    let mut mint: $mint_ty = (*self).into();
+                    if !mint.set_components(components) {
+                        return false;
+                    }
+                    *self = mint.into();
+                    true
+                }
+            }
+        )*
+    };
+}
+
+editable_scalars! {
+    f32,
+    f64,
+    i32,
+    u32,
+}
+
+editable_vectors! {
+    mint::Vector2<f32> { x, y },
+    mint::Vector3<f32> { x, y, z },
+    mint::Vector4<f32> { x, y, z, w },
+    mint::Vector2<f64> { x, y },
+    mint::Vector3<f64> { x, y, z },
+    mint::Vector4<f64> { x, y, z, w },
+    mint::Vector2<i32> { x, y },
+    mint::Vector3<i32> { x, y, z },
+    mint::Vector4<i32> { x, y, z, w },
+    mint::Vector2<u32> { x, y },
+    mint::Vector3<u32> { x, y, z },
+    mint::Vector4<u32> { x, y, z, w },
+}
+
+#[cfg(feature = "glam")]
+editable_minty! {
+    mint::Vector2<f32> => glam::Vec2,
+    mint::Vector3<f32> => glam::Vec3,
+    mint::Vector4<f32> => glam::Vec4,
+    mint::Vector2<f64> => glam::DVec2,
+    mint::Vector3<f64> => glam::DVec3,
+    mint::Vector4<f64> => glam::DVec4,
+    mint::Vector2<i32> => glam::IVec2,
+    mint::Vector3<i32> => glam::IVec3,
+    mint::Vector4<i32> => glam::IVec4,
+    mint::Vector2<u32> => glam::UVec2,
+    mint::Vector3<u32> => glam::UVec3,
+    mint::Vector4<u32> => glam::UVec4,
+}
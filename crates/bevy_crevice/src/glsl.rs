@@ -39,7 +39,30 @@ pub unsafe trait Glsl {
 /// Trait for types that can be represented as a struct in GLSL.
 ///
 /// This trait should not generally be implemented by hand, but can be derived.
+///
+/// The derive accepts a path under which shaders can include the definition, which a shader
+/// preprocessor may use to resolve `#include` directives:
+///
+/// ```rust
+/// use bevy_crevice::glsl::GlslStruct;
+///
+/// #[derive(GlslStruct)]
+/// #[glsl(include_path = "materials/standard_material.glsl")]
+/// struct StandardMaterial {
+///     base_color: mint::Vector4<f32>,
+///     roughness: f32,
+/// }
+///
+/// assert_eq!(
+///     StandardMaterial::INCLUDE_PATH,
+///     Some("materials/standard_material.glsl")
+/// );
+/// ```
 pub unsafe trait GlslStruct: Glsl {
+    /// The path under which shaders include the [`GlslStruct::glsl_definition`] of this struct,
+    /// set with `#[glsl(include_path = "...")]`.
+    const INCLUDE_PATH: Option<&'static str> = None;
+
     /// The fields contained in this struct.
     fn enumerate_fields(s: &mut String);
 
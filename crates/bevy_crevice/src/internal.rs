@@ -3,6 +3,89 @@
 
 pub use bytemuck;
 
+#[cfg(feature = "layout-inventory")]
+pub use inventory;
+
+use crate::editor::FieldEditorInfo;
+
+/// Called by derived [`EditableUniform::field_index`](crate::editor::EditableUniform::field_index)
+/// implementations for names without a field, which panics with the `strict-names` feature.
+#[cfg(feature = "strict-names")]
+#[cold]
+#[track_caller]
+pub fn unknown_field(type_name: &str, name: &str, fields: &[FieldEditorInfo]) {
+    let names: Vec<_> = fields.iter().map(|field| field.name).collect();
+    let suggestion = names
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| format!(", did you mean `{}`?", candidate))
+        .unwrap_or_default();
+    panic!(
+        "`{}` has no editable field named `{}`{} The editable fields are: {}",
+        type_name,
+        name,
+        if suggestion.is_empty() {
+            "."
+        } else {
+            suggestion.as_str()
+        },
+        names.join(", ")
+    );
+}
+
+/// Without the `strict-names` feature, unknown names are only reported by returning `None`.
+#[cfg(not(feature = "strict-names"))]
+#[inline(always)]
+pub fn unknown_field(_type_name: &str, _name: &str, _fields: &[FieldEditorInfo]) {}
+
+/// The Levenshtein distance between the strings, counted in chars.
+#[cfg(feature = "strict-names")]
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // the distances between the processed prefix of `a` and each prefix of `b`
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + (a_char != *b_char) as usize;
+            diagonal = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(diagonal + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+/// Called in debug builds after a value was written with a `Writer`, which panics if it wrote a
+/// different number of bytes than its size reports. Such a mismatch shifts everything written
+/// after the value, so members of tuples and items of slices are checked on their own to name the
+/// value that caused it.
+#[cfg(debug_assertions)]
+#[track_caller]
+pub fn check_written_size<T: ?Sized>(
+    standard: &str,
+    member: Option<(&str, usize)>,
+    written: usize,
+    size: usize,
+) {
+    if written == size {
+        return;
+    }
+    let name = core::any::type_name::<T>();
+    match member {
+        Some((outer, index)) => panic!(
+            "`{}` wrote {} bytes as member {} of `{}`, but its `{}_size` is {}",
+            name, written, index, outer, standard, size
+        ),
+        None => panic!(
+            "`{}` wrote {} bytes, but its `{}_size` is {}",
+            name, written, standard, size
+        ),
+    }
+}
+
 /// Gives the number of bytes needed to make `offset` be aligned to `alignment`.
 pub const fn align_offset(offset: usize, alignment: usize) -> usize {
     if alignment == 0 || offset % alignment == 0 {
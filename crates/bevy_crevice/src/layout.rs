@@ -0,0 +1,150 @@
+//! Defines types describing how values are laid out in `std140` and `std430` buffers, which is
+//! useful for debugging data uploaded to the GPU and for tooling.
+
+use core::fmt;
+
+/// The layout of a type in a `std140` or `std430` buffer, returned by
+/// [`AsStd140::describe_std140_layout`](crate::std140::AsStd140::describe_std140_layout) and
+/// [`AsStd430::describe_std430_layout`](crate::std430::AsStd430::describe_std430_layout).
+///
+/// Its [`Display`](fmt::Display) implementation lists the fields in declaration order, including
+/// the fields of nested structs and the padding inserted after each field:
+///
+/// ```rust
+/// use bevy_crevice::std140::AsStd140;
+///
+/// #[derive(AsStd140)]
+/// struct Light {
+///     color: mint::Vector3<f32>,
+///     intensity: f32,
+///     range: f32,
+/// }
+///
+/// let description = Light::describe_std140_layout();
+/// assert_eq!(description.size, 32);
+/// assert_eq!(
+///     description.to_string(),
+///     "Light (size 32, alignment 16)
+///    0  color: mint::Vector3<f32> (size 12, alignment 16)
+///   12  intensity: f32 (size 4, alignment 4)
+///   16  range: f32 (size 4, alignment 4)
+///   20  padding (12 bytes)"
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct UniformLayoutDescription {
+    /// The name of the type. Derived structs use their name as written, other types their
+    /// [`type_name`](core::any::type_name).
+    pub name: &'static str,
+    /// The size of the type in bytes, including the padding at its end.
+    pub size: usize,
+    /// The alignment of the type in bytes.
+    pub alignment: usize,
+    /// The fields of derived structs, in declaration order. Empty for all other types.
+    pub fields: &'static [FieldLayoutDescription],
+}
+
+/// The layout of a field of a struct, as part of a [`UniformLayoutDescription`].
+#[derive(Clone, Copy, Debug)]
+pub struct FieldLayoutDescription {
+    /// The name of the field.
+    pub name: &'static str,
+    /// The type of the field, as written in the struct definition.
+    pub ty: &'static str,
+    /// The offset of the field from the start of the struct in bytes.
+    pub offset: usize,
+    /// The size of the field in bytes.
+    pub size: usize,
+    /// The alignment of the field in bytes.
+    pub alignment: usize,
+    /// The number of bytes of padding following the field.
+    pub padding: usize,
+    /// Describes the layout of the type of the field.
+    pub describe: fn() -> UniformLayoutDescription,
+}
+
+impl UniformLayoutDescription {
+    fn fmt_fields(&self, f: &mut fmt::Formatter, base_offset: usize, depth: usize) -> fmt::Result {
+        let indent = 2 * depth;
+        for field in self.fields {
+            let offset = base_offset + field.offset;
+            write!(
+                f,
+                "\n{:>4}{:indent$}{}: {} (size {}, alignment {})",
+                offset,
+                "",
+                field.name,
+                field.ty,
+                field.size,
+                field.alignment,
+                indent = indent
+            )?;
+            (field.describe)().fmt_fields(f, offset, depth + 1)?;
+            if field.padding > 0 {
+                write!(
+                    f,
+                    "\n{:>4}{:indent$}padding ({} bytes)",
+                    offset + field.size,
+                    "",
+                    field.padding,
+                    indent = indent
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for UniformLayoutDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (size {}, alignment {})",
+            self.name, self.size, self.alignment
+        )?;
+        self.fmt_fields(f, 0, 1)
+    }
+}
+
+/// The layout standards a type can be derived for.
+#[cfg(feature = "layout-inventory")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LayoutStandard {
+    /// The layout of [`AsStd140`](crate::std140::AsStd140) types.
+    Std140,
+    /// The layout of [`AsStd430`](crate::std430::AsStd430) types.
+    Std430,
+}
+
+/// The layout of a type deriving [`AsStd140`](crate::std140::AsStd140) or
+/// [`AsStd430`](crate::std430::AsStd430), which the derive registers at link time with the
+/// `layout-inventory` feature.
+///
+/// Generic types aren't registered, because their layout depends on the type parameters.
+#[cfg(feature = "layout-inventory")]
+#[derive(Clone, Copy, Debug)]
+pub struct RegisteredLayout {
+    /// The name of the type as written.
+    pub name: &'static str,
+    /// The standard the layout follows.
+    pub standard: LayoutStandard,
+    /// Describes the layout of the type.
+    pub describe: fn() -> UniformLayoutDescription,
+}
+
+#[cfg(feature = "layout-inventory")]
+inventory::collect!(RegisteredLayout);
+
+/// Iterates over the layouts of all types in the binary deriving
+/// [`AsStd140`](crate::std140::AsStd140) or [`AsStd430`](crate::std430::AsStd430), in no
+/// particular order. A type deriving both is listed once for each standard.
+///
+/// ```ignore
+/// for layout in iter_registered_layouts() {
+///     println!("{:?} {}", layout.standard, (layout.describe)());
+/// }
+/// ```
+#[cfg(feature = "layout-inventory")]
+pub fn iter_registered_layouts() -> impl Iterator<Item = &'static RegisteredLayout> {
+    inventory::iter::<RegisteredLayout>.into_iter()
+}
@@ -150,6 +150,10 @@ unmap_gpu_buffer();
 * `cgmath`: Enables support for types from cgmath.
 * `nalgebra`: Enables support for types from nalgebra.
 * `glam`: Enables support for types from glam.
+* `layout-inventory`: Registers the layouts of all derived types, see
+  `layout::iter_registered_layouts`.
+* `strict-names`: Panics when fields of derived types are looked up by names they don't have,
+  see `editor::EditableUniform::field_index`.
 
 ## Minimum Supported Rust Version (MSRV)
 
@@ -164,7 +168,9 @@ Crevice supports Rust 1.52.1 and newer due to use of new `const fn` features.
 #[macro_use]
 mod util;
 
+pub mod editor;
 pub mod glsl;
+pub mod layout;
 pub mod std140;
 pub mod std430;
 
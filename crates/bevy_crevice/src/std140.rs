@@ -1,6 +1,7 @@
 //! Defines traits and types for working with data adhering to GLSL's `std140`
 //! layout specification.
 
+mod dirty;
 mod dynamic_uniform;
 mod primitives;
 mod sizer;
@@ -8,6 +9,7 @@ mod traits;
 #[cfg(feature = "std")]
 mod writer;
 
+pub use self::dirty::*;
 pub use self::dynamic_uniform::*;
 pub use self::primitives::*;
 pub use self::sizer::*;
@@ -15,4 +17,4 @@ pub use self::traits::*;
 #[cfg(feature = "std")]
 pub use self::writer::*;
 
-pub use bevy_crevice_derive::AsStd140;
+pub use bevy_crevice_derive::{AsStd140, DirtyStd140};
@@ -0,0 +1,86 @@
+use core::ops::{BitOr, BitOrAssign, Range};
+
+use crate::std140::AsStd140;
+
+/// A set of the fields of a struct, generated as `{Struct}Dirty` by
+/// `#[derive(DirtyStd140)]` with a constant for every field, e.g. `MaterialDirty::COLOR`.
+pub trait DirtyMask:
+    Copy + Default + Eq + BitOr<Output = Self> + BitOrAssign + Send + Sync + 'static
+{
+    /// All fields of the struct.
+    const ALL: Self;
+
+    /// Returns the fields as bits, where the bit `i` is set for the field with index `i`.
+    fn bits(self) -> u64;
+
+    /// Returns `true` if no field is in the set.
+    #[inline]
+    fn is_empty(self) -> bool {
+        self.bits() == 0
+    }
+
+    /// Returns `true` if every field of `other` is in the set as well.
+    #[inline]
+    fn contains(self, other: Self) -> bool {
+        self.bits() & other.bits() == other.bits()
+    }
+}
+
+/// Types whose fields can be written to their `std140` representation one at a time, so that only
+/// the fields that changed have to be uploaded.
+///
+/// Deriving `DirtyStd140` requires deriving [`AsStd140`] as well:
+///
+/// ```rust
+/// use bevy_crevice::std140::{AsStd140, DirtyMask, DirtyStd140};
+///
+/// #[derive(AsStd140, DirtyStd140)]
+/// struct Material {
+///     roughness: f32,
+///     metallic: f32,
+/// }
+///
+/// let material = Material { roughness: 0.5, metallic: 1.0 };
+/// let mut bytes = [0; 16];
+/// let mut written = Vec::new();
+/// material.write_dirty_std140(MaterialDirty::METALLIC, &mut bytes, &mut |range| {
+///     written.push(range)
+/// });
+/// assert_eq!(written, [4..8]);
+/// assert_eq!(bytes[4..8], 1.0f32.to_ne_bytes());
+/// ```
+pub trait DirtyStd140: AsStd140 {
+    /// The set of the fields of the type.
+    type Dirty: DirtyMask;
+
+    /// Writes the `std140` representation of the field with the `index` to its offset in the
+    /// `bytes`, which start with the `std140` representation of the whole value, and returns the
+    /// range of the written bytes.
+    fn write_std140_field(&self, index: usize, bytes: &mut [u8]) -> Range<usize>;
+
+    /// Writes only the `dirty` fields to the `bytes` like [`DirtyStd140::write_std140_field`] and
+    /// passes the range of every written field to `written`, in field order.
+    fn write_dirty_std140(
+        &self,
+        dirty: Self::Dirty,
+        bytes: &mut [u8],
+        written: &mut dyn FnMut(Range<usize>),
+    ) {
+        let mut bits = dirty.bits();
+        while bits != 0 {
+            let index = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            written(self.write_std140_field(index, bytes));
+        }
+    }
+}
+
+/// Wraps a value and records which of its fields were changed, through the setters generated by
+/// `#[derive(DirtyStd140)]` as the `{Struct}Setters` trait.
+pub trait DirtyTracker<T: DirtyStd140> {
+    /// Returns the value without marking any fields as dirty.
+    fn untracked_mut(&mut self) -> &mut T;
+
+    /// Marks the `dirty` fields as changed.
+    fn mark_dirty(&mut self, dirty: T::Dirty);
+}
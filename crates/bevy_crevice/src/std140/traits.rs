@@ -4,6 +4,8 @@ use std::io::{self, Write};
 
 use bytemuck::{bytes_of, Pod, Zeroable};
 
+use crate::layout::UniformLayoutDescription;
+
 #[cfg(feature = "std")]
 use crate::std140::Writer;
 
@@ -123,10 +125,27 @@ pub trait AsStd140 {
         size_of::<Self::Output>()
     }
 
+    /// Describes the `std140` layout of this type, including the offsets and sizes of the fields
+    /// of derived structs.
+    fn describe_std140_layout() -> UniformLayoutDescription {
+        UniformLayoutDescription {
+            name: core::any::type_name::<Self>(),
+            size: size_of::<Self::Output>(),
+            alignment: <Self::Output as Std140>::ALIGNMENT,
+            fields: &[],
+        }
+    }
+
     /// Converts from `std140` version of self to self.
     fn from_std140(val: Self::Output) -> Self;
 }
 
+/// Returns the size and the alignment of the `std140` version of `T`, which allows inspecting
+/// layouts without creating a value of `T`.
+pub fn layout_of<T: AsStd140>() -> (usize, usize) {
+    (size_of::<T::Output>(), <T::Output as Std140>::ALIGNMENT)
+}
+
 impl<T> AsStd140 for T
 where
     T: Std140,
@@ -190,6 +209,10 @@ impl<T: Std140, const N: usize> Std140Array<T, N> {
     }
 }
 
+/// Arrays follow the `std140` array rules: every element is padded to a multiple of 16 bytes,
+/// so e.g. a `[f32; 4]` occupies 64 bytes. Nested arrays like `[[f32; 4]; 4]` pad the elements of
+/// the inner arrays as well. Tightly packed bytes of an array of `Pod` values can be obtained
+/// with `bytemuck::cast_slice` instead.
 impl<T: AsStd140, const N: usize> AsStd140 for [T; N]
 where
     <T::Output as Std140>::Padded: Pod,
@@ -214,6 +237,40 @@ where
     }
 }
 
+/// Booleans are written as a 4 byte `uint` with the value `0` or `1`, like GLSL's `bool`. WGSL
+/// doesn't allow `bool`s in buffers, so declare the field as `u32` in WGSL shaders instead.
+impl AsStd140 for bool {
+    type Output = u32;
+
+    fn as_std140(&self) -> Self::Output {
+        *self as u32
+    }
+
+    fn from_std140(val: Self::Output) -> Self {
+        val != 0
+    }
+}
+
+/// Optional values occupy the space of the value in either case, and `None` is written as zeroes.
+/// Since a shader can't tell a zeroed value apart from a present one, usages of optional fields
+/// should be gated behind a shader def which is only enabled if the value is `Some`.
+///
+/// Converting back from `std140` always returns `Some`.
+impl<T: AsStd140> AsStd140 for Option<T> {
+    type Output = T::Output;
+
+    fn as_std140(&self) -> Self::Output {
+        match self {
+            Some(value) => value.as_std140(),
+            None => Zeroable::zeroed(),
+        }
+    }
+
+    fn from_std140(val: Self::Output) -> Self {
+        Some(T::from_std140(val))
+    }
+}
+
 /// Trait implemented for all types that can be written into a buffer as
 /// `std140` bytes. This type is more general than [`AsStd140`]: all `AsStd140`
 /// types implement `WriteStd140`, but not the other way around.
@@ -266,11 +323,11 @@ where
         let mut iter = self.iter();
 
         if let Some(item) = iter.next() {
-            offset = item.write_std140(writer)?;
+            offset = write_member::<Self, _, _>(item, 0, writer)?;
         }
 
-        for item in iter {
-            item.write_std140(writer)?;
+        for (index, item) in iter.enumerate() {
+            write_member::<Self, _, _>(item, index + 1, writer)?;
         }
 
         Ok(offset)
@@ -282,3 +339,74 @@ where
         writer.len()
     }
 }
+
+#[cfg(feature = "std")]
+impl<T> WriteStd140 for Vec<T>
+where
+    T: WriteStd140,
+{
+    fn write_std140<W: Write>(&self, writer: &mut Writer<W>) -> io::Result<usize> {
+        self.as_slice().write_std140(writer)
+    }
+
+    fn std140_size(&self) -> usize {
+        self.as_slice().std140_size()
+    }
+}
+
+/// Writes a member of a tuple or an item of a slice, checking in debug builds that it writes as
+/// many bytes as [`WriteStd140::std140_size`] reports.
+#[cfg(feature = "std")]
+#[inline]
+fn write_member<Outer, T, W>(value: &T, index: usize, writer: &mut Writer<W>) -> io::Result<usize>
+where
+    Outer: ?Sized,
+    T: WriteStd140 + ?Sized,
+    W: Write,
+{
+    let offset = value.write_std140(writer)?;
+    #[cfg(debug_assertions)]
+    crate::internal::check_written_size::<T>(
+        "std140",
+        Some((core::any::type_name::<Outer>(), index)),
+        writer.len() - offset,
+        value.std140_size(),
+    );
+    #[cfg(not(debug_assertions))]
+    let _ = index;
+    Ok(offset)
+}
+
+/// Tuples are written member by member, like the consecutive fields of a struct. Unlike structs,
+/// tuples are neither aligned nor padded to the alignment of their largest member, so padding
+/// the data following a tuple is the caller's responsibility.
+macro_rules! impl_write_std140_for_tuples {
+    ($(($($name:ident),+)),+ $(,)?) => {
+        $(
+            #[cfg(feature = "std")]
+            impl<$($name: WriteStd140),+> WriteStd140 for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn write_std140<W: Write>(&self, writer: &mut Writer<W>) -> io::Result<usize> {
+                    let ($($name,)+) = self;
+                    let mut index = 0;
+                    let offsets = [$({
+                        index += 1;
+                        write_member::<Self, _, _>($name, index - 1, writer)?
+                    }),+];
+                    Ok(offsets[0])
+                }
+            }
+        )+
+    };
+}
+
+impl_write_std140_for_tuples!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+);
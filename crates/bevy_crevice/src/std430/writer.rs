@@ -1,7 +1,7 @@
 use std::io::{self, Write};
-use std::mem::size_of;
+use std::mem::{size_of, size_of_val};
 
-use bytemuck::bytes_of;
+use bytemuck::{bytes_of, cast_slice};
 
 use crate::internal::align_offset;
 use crate::std430::{AsStd430, Std430, WriteStd430};
@@ -91,11 +91,35 @@ impl<W: Write> Writer<W> {
     /// necessary.
     ///
     /// Returns the offset into the buffer that the value was written to.
+    ///
+    /// In debug builds, this checks that the number of written bytes matches
+    /// [`WriteStd430::std430_size`], which catches implementations whose size
+    /// drifted from what they actually write, and panics with the name of the
+    /// type otherwise.
     pub fn write<T>(&mut self, value: &T) -> io::Result<usize>
     where
         T: WriteStd430 + ?Sized,
     {
-        value.write_std430(self)
+        let offset = value.write_std430(self)?;
+        #[cfg(debug_assertions)]
+        crate::internal::check_written_size::<T>(
+            "std430",
+            None,
+            self.offset - offset,
+            value.std430_size(),
+        );
+        Ok(offset)
+    }
+
+    /// Write a new value to the underlying buffer like [`Writer::write`], but
+    /// return the number of bytes written for the value, excluding the padding
+    /// inserted before it.
+    pub fn write_sized<T>(&mut self, value: &T) -> io::Result<usize>
+    where
+        T: WriteStd430 + ?Sized,
+    {
+        let offset = self.write(value)?;
+        Ok(self.offset - offset)
     }
 
     /// Write an iterator of values to the underlying buffer.
@@ -112,11 +136,11 @@ impl<W: Write> Writer<W> {
         let mut iter = iter.into_iter();
 
         if let Some(item) = iter.next() {
-            offset = item.write_std430(self)?;
+            offset = self.write(&item)?;
         }
 
         for item in iter {
-            item.write_std430(self)?;
+            self.write(&item)?;
         }
 
         Ok(offset)
@@ -143,8 +167,96 @@ impl<W: Write> Writer<W> {
         Ok(write_here)
     }
 
+    /// Write a slice of `Std430` values to the underlying buffer.
+    ///
+    /// This writes the same bytes as [`Writer::write`], but when the size of `T`
+    /// is a multiple of its alignment, like for scalars, 4-component vectors and
+    /// 4x4 matrices, no padding is needed between the values and the whole slice
+    /// is copied at once instead of value by value.
+    ///
+    /// Returns the offset into the buffer that the first value was written to.
+    /// If no values were written, returns the `len()`.
+    pub fn write_std430_slice<T>(&mut self, slice: &[T]) -> io::Result<usize>
+    where
+        T: Std430,
+    {
+        if slice.is_empty() || size_of::<T>() % T::ALIGNMENT != 0 {
+            return self.write(slice);
+        }
+
+        let padding = align_offset(self.offset, T::ALIGNMENT);
+
+        for _ in 0..padding {
+            self.writer.write_all(&[0])?;
+        }
+        self.offset += padding;
+
+        self.writer.write_all(cast_slice(slice))?;
+
+        let write_here = self.offset;
+        self.offset += size_of_val(slice);
+
+        Ok(write_here)
+    }
+
     /// Returns the amount of data written by this `Writer`.
     pub fn len(&self) -> usize {
         self.offset
     }
 }
+
+/// Asserts that a hand-written [`WriteStd430`] implementation is consistent, for use in the unit
+/// tests of custom types: the value has to write as many bytes as
+/// [`WriteStd430::std430_size`] reports, return the offset of its first byte and write the
+/// same bytes regardless of what was written before it.
+///
+/// Unlike the checks of [`Writer::write`], this also works in release builds.
+///
+/// ```
+/// use bevy_crevice::std430::assert_std430_conformance;
+///
+/// assert_std430_conformance(&1.0f32);
+/// assert_std430_conformance(&(mint::Vector3 { x: 1.0f32, y: 2.0, z: 3.0 }, 4u32));
+/// assert_std430_conformance([1u32, 2, 3].as_slice());
+/// ```
+#[track_caller]
+pub fn assert_std430_conformance<T: WriteStd430 + ?Sized>(value: &T) {
+    let name = std::any::type_name::<T>();
+    let size = value.std430_size();
+
+    let mut bytes = Vec::new();
+    let mut writer = Writer::new(&mut bytes);
+    let offset = value.write_std430(&mut writer).unwrap();
+    assert_eq!(
+        offset, 0,
+        "`{}` returned {} as its offset, but was written at 0",
+        name, offset
+    );
+    assert_eq!(
+        writer.len(),
+        size,
+        "`{}` wrote {} bytes, but its `std430_size` is {}",
+        name,
+        writer.len(),
+        size
+    );
+
+    // write the value again after a value of another alignment
+    let mut preceded = Vec::new();
+    let mut writer = Writer::new(&mut preceded);
+    writer.write(&1.0f32).unwrap();
+    let offset = value.write_std430(&mut writer).unwrap();
+    assert_eq!(
+        writer.len() - offset,
+        size,
+        "`{}` wrote {} bytes after a `f32`, but its `std430_size` is {}",
+        name,
+        writer.len() - offset,
+        size
+    );
+    assert!(
+        preceded[offset..] == bytes[..],
+        "`{}` wrote different bytes after a `f32`",
+        name
+    );
+}
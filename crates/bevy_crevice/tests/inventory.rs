@@ -0,0 +1,42 @@
+#![cfg(feature = "layout-inventory")]
+
+use bevy_crevice::layout::{iter_registered_layouts, LayoutStandard};
+use bevy_crevice::std140::AsStd140;
+use bevy_crevice::std430::AsStd430;
+
+#[allow(dead_code)]
+#[derive(AsStd140)]
+struct PointLight {
+    color: mint::Vector4<f32>,
+    range: f32,
+}
+
+#[allow(dead_code)]
+#[derive(AsStd140)]
+struct Fog {
+    color: mint::Vector3<f32>,
+    density: f32,
+}
+
+#[allow(dead_code)]
+#[derive(AsStd430)]
+struct Particle {
+    position: mint::Vector3<f32>,
+    lifetime: f32,
+}
+
+#[test]
+fn derived_layouts_are_registered() {
+    let mut layouts: Vec<_> = iter_registered_layouts()
+        .map(|layout| (layout.name, layout.standard, (layout.describe)().size))
+        .collect();
+    layouts.sort_by_key(|(name, _, _)| *name);
+    assert_eq!(
+        layouts,
+        [
+            ("Fog", LayoutStandard::Std140, 16),
+            ("Particle", LayoutStandard::Std430, 16),
+            ("PointLight", LayoutStandard::Std140, 32),
+        ]
+    );
+}
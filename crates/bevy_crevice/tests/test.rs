@@ -1,5 +1,5 @@
 use bevy_crevice::glsl::GlslStruct;
-use bevy_crevice::std140::AsStd140;
+use bevy_crevice::std140::{AsStd140, Std140};
 
 #[test]
 fn there_and_back_again() {
@@ -37,6 +37,596 @@ fn there_and_back_again() {
     assert_eq!(<ThereAndBackAgain as AsStd140>::from_std140(x_as), x);
 }
 
+#[test]
+fn array_fields() {
+    #[derive(AsStd140, Debug, PartialEq)]
+    struct ArrayFields {
+        weights: [f32; 4],
+        matrix: [[f32; 4]; 4],
+        colors: [mint::Vector4<f32>; 8],
+        indices: [u32; 4],
+    }
+
+    // every array element is padded to 16 bytes
+    assert_eq!(<[f32; 4] as AsStd140>::std140_size_static(), 64);
+    assert_eq!(<[[f32; 4]; 4] as AsStd140>::std140_size_static(), 256);
+    assert_eq!(
+        <[mint::Vector4<f32>; 8] as AsStd140>::std140_size_static(),
+        128
+    );
+    assert_eq!(ArrayFields::std140_size_static(), 64 + 256 + 128 + 64);
+
+    let x = ArrayFields {
+        weights: [0.1, 0.2, 0.3, 0.4],
+        matrix: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [4.0, 5.0, 6.0, 1.0],
+        ],
+        colors: [mint::Vector4 {
+            x: 1.0,
+            y: 0.5,
+            z: 0.25,
+            w: 1.0,
+        }; 8],
+        indices: [1, 2, 3, 4],
+    };
+    let x_as = x.as_std140();
+    assert_eq!(<ArrayFields as AsStd140>::from_std140(x_as), x);
+}
+
+#[test]
+fn optional_fields_are_zeroed() {
+    #[derive(AsStd140)]
+    struct ClipPlane {
+        plane: Option<mint::Vector4<f32>>,
+        distance: f32,
+    }
+
+    let none = ClipPlane {
+        plane: None,
+        distance: 1.0,
+    };
+    let some = ClipPlane {
+        plane: Some(mint::Vector4 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+            w: 2.0,
+        }),
+        distance: 1.0,
+    };
+    let none_std140 = none.as_std140();
+    let some_std140 = some.as_std140();
+    let (none_bytes, some_bytes) = (none_std140.as_bytes(), some_std140.as_bytes());
+    assert_eq!(none_bytes.len(), some_bytes.len());
+    assert!(none_bytes[..16].iter().all(|byte| *byte == 0));
+    assert_eq!(none_bytes[16..], some_bytes[16..]);
+
+    assert_eq!(
+        <Option<mint::Vector4<f32>> as AsStd140>::std140_size_static(),
+        16
+    );
+    assert!(None::<f32>
+        .as_std140()
+        .as_bytes()
+        .iter()
+        .all(|byte| *byte == 0));
+}
+
+#[test]
+fn write_vecs() {
+    use bevy_crevice::std430::{WriteStd430, Writer};
+
+    fn write(values: &Vec<mint::Vector3<f32>>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        Writer::new(&mut bytes).write(values).unwrap();
+        assert_eq!(bytes.len(), values.std430_size());
+        bytes
+    }
+
+    let vector = |x| mint::Vector3 { x, y: 0.0, z: 1.0 };
+    assert!(write(&Vec::new()).is_empty());
+    assert_eq!(write(&vec![vector(1.0)]).len(), 12);
+
+    // elements are aligned to 16 bytes, the size of a `vec3` is rounded up
+    let large = (0..1000).map(|i| vector(i as f32)).collect::<Vec<_>>();
+    let bytes = write(&large);
+    assert_eq!(bytes.len(), 999 * 16 + 12);
+    assert_eq!(bytes[..], write(&large)[..]);
+    assert_eq!(&bytes[16 * 999..16 * 999 + 4], &999.0f32.to_ne_bytes());
+    assert_eq!(large.as_slice().std430_size(), large.std430_size());
+}
+
+#[test]
+fn write_tuples() {
+    use bevy_crevice::std140::{WriteStd140, Writer};
+
+    #[derive(AsStd140)]
+    struct Equivalent {
+        color: mint::Vector4<f32>,
+        intensity: f32,
+        direction: mint::Vector3<f32>,
+    }
+
+    let color = mint::Vector4 {
+        x: 1.0,
+        y: 0.5,
+        z: 0.0,
+        w: 1.0,
+    };
+    let direction = mint::Vector3 {
+        x: 0.0,
+        y: -1.0,
+        z: 0.0,
+    };
+    let tuple = (color, 2.0f32, direction);
+    let mut bytes = Vec::new();
+    Writer::new(&mut bytes).write(&tuple).unwrap();
+    assert_eq!(bytes.len(), tuple.std140_size());
+
+    let equivalent = Equivalent {
+        color,
+        intensity: 2.0,
+        direction,
+    }
+    .as_std140();
+    // the struct is padded to a multiple of 16 bytes, the tuple isn't
+    assert_eq!(bytes.len(), 44);
+    assert_eq!(bytes[..], equivalent.as_bytes()[..44]);
+}
+
+#[test]
+fn bool_fields() {
+    #[derive(AsStd140, Debug, PartialEq)]
+    struct Flags {
+        unlit: bool,
+        alpha_cutoff: f32,
+        double_sided: bool,
+    }
+
+    assert_eq!(<bool as AsStd140>::std140_size_static(), 4);
+    let flags = Flags {
+        unlit: true,
+        alpha_cutoff: 0.5,
+        double_sided: false,
+    };
+    let flags_std140 = flags.as_std140();
+    let bytes = flags_std140.as_bytes();
+    assert_eq!(bytes[..4], 1u32.to_ne_bytes());
+    assert_eq!(bytes[4..8], 0.5f32.to_ne_bytes());
+    assert_eq!(bytes[8..12], 0u32.to_ne_bytes());
+    assert_eq!(<Flags as AsStd140>::from_std140(flags_std140), flags);
+}
+
+#[test]
+fn dirty_fields_are_written_at_their_offsets() {
+    use bevy_crevice::std140::{DirtyMask, DirtyStd140, DirtyTracker};
+
+    #[derive(AsStd140, DirtyStd140)]
+    struct Material {
+        color: mint::Vector4<f32>,
+        roughness: f32,
+        offset: mint::Vector3<f32>,
+    }
+
+    struct Tracker {
+        material: Material,
+        dirty: MaterialDirty,
+    }
+
+    impl DirtyTracker<Material> for Tracker {
+        fn untracked_mut(&mut self) -> &mut Material {
+            &mut self.material
+        }
+
+        fn mark_dirty(&mut self, dirty: MaterialDirty) {
+            self.dirty |= dirty;
+        }
+    }
+
+    let mut tracker = Tracker {
+        material: Material {
+            color: [1.0, 0.0, 0.0, 1.0].into(),
+            roughness: 0.5,
+            offset: [0.0, 1.0, 2.0].into(),
+        },
+        dirty: MaterialDirty::default(),
+    };
+    tracker.set_roughness(0.25);
+    tracker.set_offset([3.0, 4.0, 5.0].into());
+    assert_eq!(
+        tracker.dirty,
+        MaterialDirty::ROUGHNESS | MaterialDirty::OFFSET
+    );
+    assert!(!tracker.dirty.contains(MaterialDirty::COLOR));
+    assert!(MaterialDirty::ALL.contains(tracker.dirty));
+
+    // only the dirty fields are written, and they match a complete write
+    let mut bytes = vec![0xff; Material::std140_size_static()];
+    let mut written = Vec::new();
+    tracker
+        .material
+        .write_dirty_std140(tracker.dirty, &mut bytes, &mut |range| written.push(range));
+    assert_eq!(written, [16..20, 32..44]);
+    let std140 = tracker.material.as_std140();
+    let all = std140.as_bytes();
+    for range in &written {
+        assert_eq!(bytes[range.clone()], all[range.clone()]);
+    }
+    assert!(bytes[..16].iter().all(|byte| *byte == 0xff));
+    assert!(bytes[20..32].iter().all(|byte| *byte == 0xff));
+}
+
+#[test]
+fn write_std140_conformance() {
+    use bevy_crevice::std140::assert_std140_conformance;
+
+    let vector = mint::Vector4 {
+        x: 1.0f32,
+        y: 2.0,
+        z: 3.0,
+        w: 4.0,
+    };
+    assert_std140_conformance(&1u32);
+    assert_std140_conformance(&true);
+    assert_std140_conformance(&vector);
+    assert_std140_conformance(&[[0.5f32; 4]; 4]);
+    assert_std140_conformance(&Some(vector));
+    assert_std140_conformance(&None::<mint::Vector4<f32>>);
+    assert_std140_conformance(&vec![vector; 3]);
+    assert_std140_conformance(&Vec::<f32>::new());
+    assert_std140_conformance([1.0f32, 2.0, 3.0].as_slice());
+    assert_std140_conformance(&(vector, 2.0f32));
+}
+
+/// A hand-written implementation whose size drifted from what it writes.
+struct DriftedStd140;
+
+impl bevy_crevice::std140::WriteStd140 for DriftedStd140 {
+    fn write_std140<W: std::io::Write>(
+        &self,
+        writer: &mut bevy_crevice::std140::Writer<W>,
+    ) -> std::io::Result<usize> {
+        writer.write(&1u32)
+    }
+
+    fn std140_size(&self) -> usize {
+        8
+    }
+}
+
+#[test]
+#[should_panic(expected = "`test::DriftedStd140` wrote 4 bytes")]
+fn conformance_catches_drifted_sizes() {
+    bevy_crevice::std140::assert_std140_conformance(&DriftedStd140);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "`test::DriftedStd140` wrote 4 bytes as member 1 of")]
+fn writes_check_sizes_in_debug_builds() {
+    let mut bytes = Vec::new();
+    let mut writer = bevy_crevice::std140::Writer::new(&mut bytes);
+    let _ = writer.write(&(1.0f32, DriftedStd140, 2.0f32));
+}
+
+#[test]
+fn hand_computed_layouts() {
+    use bevy_crevice::std430::{AsStd430, Std430};
+
+    #[derive(AsStd140, AsStd430)]
+    struct Light {
+        position: mint::Vector3<f32>,
+        intensity: f32,
+        basis: mint::ColumnMatrix3<f32>,
+        weights: [f32; 2],
+        scale: f32,
+    }
+
+    let vector = |x, y, z| mint::Vector3 { x, y, z };
+    let light = Light {
+        position: vector(1.0, 2.0, 3.0),
+        intensity: 4.0,
+        basis: mint::ColumnMatrix3 {
+            x: vector(5.0, 6.0, 7.0),
+            y: vector(8.0, 9.0, 10.0),
+            z: vector(11.0, 12.0, 13.0),
+        },
+        weights: [14.0, 15.0],
+        scale: 16.0,
+    };
+    let floats = |bytes: &[u8]| {
+        bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_ne_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    // the float fills the padding after the vec3, the columns of the mat3 and the elements of
+    // the array are aligned to 16 bytes, and the struct is padded to a multiple of 16 bytes
+    #[rustfmt::skip]
+    let std140 = [
+        1.0, 2.0, 3.0, 4.0,
+        5.0, 6.0, 7.0, 0.0,
+        8.0, 9.0, 10.0, 0.0,
+        11.0, 12.0, 13.0, 0.0,
+        14.0, 0.0, 0.0, 0.0,
+        15.0, 0.0, 0.0, 0.0,
+        16.0, 0.0, 0.0, 0.0,
+    ];
+    assert_eq!(floats(light.as_std140().as_bytes()), std140);
+
+    // std430 packs the elements of scalar arrays tightly
+    #[rustfmt::skip]
+    let std430 = [
+        1.0, 2.0, 3.0, 4.0,
+        5.0, 6.0, 7.0, 0.0,
+        8.0, 9.0, 10.0, 0.0,
+        11.0, 12.0, 13.0, 0.0,
+        14.0, 15.0, 16.0, 0.0,
+    ];
+    assert_eq!(floats(light.as_std430().as_bytes()), std430);
+}
+
+#[test]
+fn nested_arrays_and_matrix_arrays() {
+    use bevy_crevice::{std140, std430};
+
+    #[derive(AsStd140)]
+    struct Cascades {
+        view_projections: [mint::ColumnMatrix4<f32>; 4],
+        splits: [[f32; 4]; 2],
+    }
+
+    assert_eq!(std140::layout_of::<[[f32; 4]; 4]>(), (256, 16));
+    assert_eq!(std430::layout_of::<[[f32; 4]; 4]>(), (64, 4));
+    assert_eq!(
+        std140::layout_of::<[mint::ColumnMatrix4<f32>; 4]>(),
+        (256, 16)
+    );
+    assert_eq!(std140::layout_of::<Cascades>(), (256 + 128, 16));
+
+    let column = |x| mint::Vector4 {
+        x,
+        y: x + 0.25,
+        z: x + 0.5,
+        w: x + 0.75,
+    };
+    let matrix = |i: usize| mint::ColumnMatrix4 {
+        x: column(i as f32 * 4.0),
+        y: column(i as f32 * 4.0 + 1.0),
+        z: column(i as f32 * 4.0 + 2.0),
+        w: column(i as f32 * 4.0 + 3.0),
+    };
+    let cascades = Cascades {
+        view_projections: [matrix(0), matrix(1), matrix(2), matrix(3)],
+        splits: [[1.0, 2.0, 3.0, 4.0], [5.0, 6.0, 7.0, 8.0]],
+    };
+    let cascades_std140 = cascades.as_std140();
+    let floats: Vec<f32> = cascades_std140
+        .as_bytes()
+        .chunks_exact(4)
+        .map(|chunk| f32::from_ne_bytes(chunk.try_into().unwrap()))
+        .collect();
+
+    // the matrices are contiguous and column-major
+    for (index, value) in floats[..64].iter().enumerate() {
+        assert_eq!(*value, index as f32 / 4.0);
+    }
+    // every element of the inner arrays is padded to 16 bytes
+    assert_eq!(floats[64..68], [1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(floats[92..96], [8.0, 0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn bulk_slice_writes_match_value_writes() {
+    use bevy_crevice::{std140, std430};
+
+    fn check_std140<T: Std140>(values: &[T]) {
+        let mut expected = Vec::new();
+        let mut writer = std140::Writer::new(&mut expected);
+        writer.write(&1u32).unwrap();
+        let expected_offset = writer.write(values).unwrap();
+
+        let mut bulk = Vec::new();
+        let mut writer = std140::Writer::new(&mut bulk);
+        writer.write(&1u32).unwrap();
+        assert_eq!(writer.write_std140_slice(values).unwrap(), expected_offset);
+        assert_eq!(bulk, expected);
+    }
+
+    fn check_std430<T: std430::Std430>(values: &[T]) {
+        let mut expected = Vec::new();
+        let mut writer = std430::Writer::new(&mut expected);
+        writer.write(&1u32).unwrap();
+        let expected_offset = writer.write(values).unwrap();
+
+        let mut bulk = Vec::new();
+        let mut writer = std430::Writer::new(&mut bulk);
+        writer.write(&1u32).unwrap();
+        assert_eq!(writer.write_std430_slice(values).unwrap(), expected_offset);
+        assert_eq!(bulk, expected);
+    }
+
+    let matrices: Vec<_> = (0..100)
+        .map(|i| {
+            let column = |x: f32| mint::Vector4 {
+                x: i as f32 + x,
+                y: -x,
+                z: x * 0.5,
+                w: 1.0,
+            };
+            mint::ColumnMatrix4 {
+                x: column(0.0),
+                y: column(1.0),
+                z: column(2.0),
+                w: column(3.0),
+            }
+        })
+        .collect();
+    let vectors: Vec<_> = (0..100)
+        .map(|i| mint::Vector3 {
+            x: i as f32,
+            y: 2.0,
+            z: 3.0,
+        })
+        .collect();
+
+    check_std140(&matrices.iter().map(AsStd140::as_std140).collect::<Vec<_>>());
+    check_std140(&vectors.iter().map(AsStd140::as_std140).collect::<Vec<_>>());
+    check_std140::<std140::Vec4>(&[]);
+    check_std430(
+        &matrices
+            .iter()
+            .map(std430::AsStd430::as_std430)
+            .collect::<Vec<_>>(),
+    );
+    check_std430(
+        &vectors
+            .iter()
+            .map(std430::AsStd430::as_std430)
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn editable_fields() {
+    use bevy_crevice::editor::{EditableUniform, FieldEditorInfo, FieldSemantic};
+
+    #[derive(AsStd140, EditableUniform, Debug, PartialEq)]
+    struct Light {
+        #[uniform(display_name = "Light Color", semantic = "color")]
+        color: mint::Vector3<f32>,
+        #[uniform(min = -1, max = 1.0, semantic = "direction")]
+        direction: mint::Vector3<f32>,
+        #[uniform(skip)]
+        view: mint::ColumnMatrix4<f32>,
+        #[uniform(min = 0.0, max = 100.0)]
+        #[uniform(display_name = "Intensity")]
+        intensity: f32,
+        shadow_samples: u32,
+    }
+
+    assert_eq!(
+        Light::EDITOR_FIELDS,
+        [
+            FieldEditorInfo {
+                name: "color",
+                display_name: Some("Light Color"),
+                range: None,
+                semantic: FieldSemantic::Color,
+                components: 3,
+            },
+            FieldEditorInfo {
+                name: "direction",
+                display_name: None,
+                range: Some((-1.0, 1.0)),
+                semantic: FieldSemantic::Direction,
+                components: 3,
+            },
+            FieldEditorInfo {
+                name: "intensity",
+                display_name: Some("Intensity"),
+                range: Some((0.0, 100.0)),
+                semantic: FieldSemantic::Raw,
+                components: 1,
+            },
+            FieldEditorInfo {
+                name: "shadow_samples",
+                display_name: None,
+                range: None,
+                semantic: FieldSemantic::Raw,
+                components: 1,
+            },
+        ]
+    );
+    assert_eq!(Light::EDITOR_FIELDS[1].label(), "direction");
+
+    let view = mint::ColumnMatrix4::from([[0.0; 4]; 4]);
+    let mut light = Light {
+        color: [1.0, 1.0, 1.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        view,
+        intensity: 1.0,
+        shadow_samples: 1,
+    };
+    assert!(light.set_field(0, &[1.0, 0.5, 0.0]));
+    assert!(light.set_field(1, &[0.0, 0.0, -1.0]));
+    assert!(light.set_field_f32(2, 40.0));
+    assert!(light.set_field_f32(3, 16.0));
+    // wrong component counts and indices past the editable fields are rejected
+    assert!(!light.set_field_f32(0, 0.0));
+    assert!(!light.set_field(2, &[1.0, 2.0]));
+    assert!(!light.set_field_f32(4, 0.0));
+    assert_eq!(
+        light,
+        Light {
+            color: [1.0, 0.5, 0.0].into(),
+            direction: [0.0, 0.0, -1.0].into(),
+            view,
+            intensity: 40.0,
+            shadow_samples: 16,
+        }
+    );
+}
+
+#[derive(bevy_crevice::editor::EditableUniform)]
+struct NamedFields {
+    intensity: f32,
+    #[uniform(skip)]
+    _skipped: u32,
+    range: f32,
+}
+
+#[test]
+fn editable_fields_by_name() {
+    use bevy_crevice::editor::EditableUniform;
+
+    assert_eq!(NamedFields::field_index("intensity"), Some(0));
+    assert_eq!(NamedFields::field_index("range"), Some(1));
+
+    let mut fields = NamedFields {
+        intensity: 1.0,
+        _skipped: 0,
+        range: 10.0,
+    };
+    assert!(fields.set_field_by_name("range", &[20.0]));
+    assert_eq!(fields.range, 20.0);
+    assert!(!fields.set_field_by_name("range", &[1.0, 2.0]));
+}
+
+#[cfg(not(feature = "strict-names"))]
+#[test]
+fn unknown_field_names_are_missing() {
+    use bevy_crevice::editor::EditableUniform;
+
+    assert_eq!(NamedFields::field_index("_skipped"), None);
+    assert_eq!(NamedFields::field_index("intesity"), None);
+}
+
+#[cfg(feature = "strict-names")]
+#[test]
+#[should_panic(
+    expected = "`NamedFields` has no editable field named `intesity`, did you mean `intensity`? \
+                The editable fields are: intensity, range"
+)]
+fn unknown_field_names_panic_in_strict_mode() {
+    use bevy_crevice::editor::EditableUniform;
+
+    NamedFields::field_index("intesity");
+}
+
+#[cfg(feature = "strict-names")]
+#[test]
+#[should_panic(expected = "`NamedFields` has no editable field named `shadows`. The editable")]
+fn unknown_field_names_without_suggestions_panic_in_strict_mode() {
+    use bevy_crevice::editor::EditableUniform;
+
+    NamedFields::field_index("shadows");
+}
+
 #[test]
 fn generate_struct_glsl() {
     #[allow(dead_code)]
@@ -59,3 +649,39 @@ fn generate_struct_array_glsl() {
 
     insta::assert_display_snapshot!(TestGlsl::glsl_definition());
 }
+
+#[allow(dead_code)]
+#[derive(AsStd140, bevy_crevice::std430::AsStd430)]
+struct UvTransform {
+    offset: mint::Vector2<f32>,
+    scale: mint::Vector2<f32>,
+}
+
+#[allow(dead_code)]
+#[derive(AsStd140, bevy_crevice::std430::AsStd430)]
+struct Material {
+    base_color: mint::Vector4<f32>,
+    emissive: mint::Vector4<f32>,
+    roughness: f32,
+    metallic: f32,
+    reflectance: f32,
+    flags: u32,
+    uv_transform: UvTransform,
+    alpha_cutoff: f32,
+}
+
+#[test]
+fn describe_material_std140_layout() {
+    let description = Material::describe_std140_layout();
+    assert_eq!(description.size, Material::std140_size_static());
+    insta::assert_display_snapshot!(description);
+}
+
+#[test]
+fn describe_material_std430_layout() {
+    use bevy_crevice::std430::AsStd430;
+
+    let description = Material::describe_std430_layout();
+    assert_eq!(description.size, Material::std430_size_static());
+    insta::assert_display_snapshot!(description);
+}
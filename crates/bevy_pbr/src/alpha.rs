@@ -1,5 +1,6 @@
 use bevy_ecs::{component::Component, reflect::ReflectComponent};
 use bevy_reflect::Reflect;
+use bevy_render::render_resource::{BlendComponent, BlendFactor, BlendOperation, BlendState};
 
 // FIXME: This should probably be part of bevy_render2!
 /// Alpha mode
@@ -20,3 +21,95 @@ impl Default for AlphaMode {
         AlphaMode::Opaque
     }
 }
+
+/// How the output of a material's fragment shader is blended into the render target.
+///
+/// Every setting except [`BlendSetting::Opaque`] renders the material in the transparent pass.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum BlendSetting {
+    #[default]
+    /// The output replaces the render target.
+    Opaque,
+    /// The output is blended over the render target, weighted by its alpha.
+    Alpha,
+    /// The output, weighted by its alpha, is added to the render target.
+    Additive,
+    /// The render target is multiplied by the output.
+    Multiply,
+    Custom(BlendState),
+}
+
+impl BlendSetting {
+    pub fn blend_state(&self) -> BlendState {
+        match self {
+            BlendSetting::Opaque => BlendState::REPLACE,
+            BlendSetting::Alpha => BlendState::ALPHA_BLENDING,
+            BlendSetting::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            },
+            BlendSetting::Multiply => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::Dst,
+                    dst_factor: BlendFactor::Zero,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+            BlendSetting::Custom(blend_state) => *blend_state,
+        }
+    }
+
+    /// Returns `true` if the material has to be drawn in the transparent pass.
+    pub fn is_transparent(&self) -> bool {
+        *self != BlendSetting::Opaque
+    }
+}
+
+impl From<AlphaMode> for BlendSetting {
+    fn from(alpha_mode: AlphaMode) -> Self {
+        match alpha_mode {
+            AlphaMode::Opaque | AlphaMode::Mask(_) => BlendSetting::Opaque,
+            AlphaMode::Blend => BlendSetting::Alpha,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlphaMode, BlendSetting};
+    use bevy_render::render_resource::BlendState;
+
+    #[test]
+    fn blend_settings() {
+        assert_eq!(BlendSetting::from(AlphaMode::Opaque), BlendSetting::Opaque);
+        assert_eq!(
+            BlendSetting::from(AlphaMode::Mask(0.5)),
+            BlendSetting::Opaque
+        );
+        assert_eq!(BlendSetting::from(AlphaMode::Blend), BlendSetting::Alpha);
+
+        assert!(!BlendSetting::Opaque.is_transparent());
+        assert!(BlendSetting::Custom(BlendState::REPLACE).is_transparent());
+
+        let settings = [
+            BlendSetting::Opaque,
+            BlendSetting::Alpha,
+            BlendSetting::Additive,
+            BlendSetting::Multiply,
+        ];
+        for (i, a) in settings.iter().enumerate() {
+            for b in &settings[i + 1..] {
+                assert_ne!(a.blend_state(), b.blend_state());
+            }
+        }
+    }
+}
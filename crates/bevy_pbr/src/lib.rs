@@ -5,6 +5,7 @@ mod bundle;
 mod light;
 mod material;
 mod pbr_material;
+mod pipeline_settings;
 mod render;
 
 pub use alpha::*;
@@ -12,6 +13,7 @@ pub use bundle::*;
 pub use light::*;
 pub use material::*;
 pub use pbr_material::*;
+pub use pipeline_settings::*;
 pub use render::*;
 
 pub mod prelude {
@@ -38,9 +40,12 @@ use bevy_ecs::prelude::*;
 use bevy_reflect::TypeUuid;
 use bevy_render::{
     prelude::Color,
+    render_asset::RenderAssetDependencyPlugin,
+    render_component::ExtractComponentPlugin,
     render_graph::RenderGraph,
     render_phase::{sort_phase_system, AddRenderCommand, DrawFunctions},
     render_resource::{Shader, SpecializedMeshPipelines},
+    texture::{Image, ImageSampler},
     view::VisibilitySystems,
     RenderApp, RenderStage,
 };
@@ -70,6 +75,10 @@ impl Plugin for PbrPlugin {
             .register_type::<PointLight>()
             .add_plugin(MeshRenderPlugin)
             .add_plugin(MaterialPlugin::<StandardMaterial>::default())
+            .add_plugin(RenderAssetDependencyPlugin::<StandardMaterial, Image>::default())
+            .add_plugin(RenderAssetDependencyPlugin::<StandardMaterial, ImageSampler>::default())
+            .add_plugin(ExtractComponentPlugin::<FaceCulling>::default())
+            .add_plugin(ExtractComponentPlugin::<StencilReference>::default())
             .init_resource::<AmbientLight>()
             .init_resource::<GlobalVisiblePointLights>()
             .init_resource::<DirectionalLightShadowMap>()
@@ -780,7 +780,16 @@ pub(crate) fn assign_lights_to_clusters(
         }
 
         let clusters = clusters.into_inner();
-        let screen_size = camera.target.get_physical_size(&windows, &images);
+        // clusters cover the camera viewport
+        let screen_size = camera
+            .target
+            .get_physical_size(&windows, &images)
+            .map(|size| match &camera.viewport {
+                Some(viewport) => viewport
+                    .clamp_to(size)
+                    .map_or(UVec2::ZERO, |viewport| viewport.physical_size),
+                None => size,
+            });
 
         clusters.aabbs.clear();
         clusters.lights.clear();
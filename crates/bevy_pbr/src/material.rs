@@ -1,6 +1,7 @@
 use crate::{
-    AlphaMode, DrawMesh, MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup,
-    SetMeshViewBindGroup,
+    wireframe::{polygon_mode_line_supported, Wireframe, WireframeConfig},
+    AlphaMode, BlendSetting, DepthSetting, DrawMesh, FaceCulling, MeshPipeline, MeshPipelineKey,
+    MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup, StencilReference, StencilSetting,
 };
 use bevy_app::{App, Plugin};
 use bevy_asset::{AddAsset, Asset, AssetServer, Handle};
@@ -10,7 +11,7 @@ use bevy_ecs::{
     prelude::World,
     system::{
         lifetimeless::{Read, SQuery, SRes},
-        Query, Res, ResMut, SystemParamItem,
+        Local, Query, Res, ResMut, SystemParamItem,
     },
     world::FromWorld,
 };
@@ -23,14 +24,15 @@ use bevy_render::{
         SetItemPipeline, TrackedRenderPass,
     },
     render_resource::{
-        BindGroup, BindGroupLayout, PipelineCache, RenderPipelineDescriptor, Shader,
-        SpecializedMeshPipeline, SpecializedMeshPipelineError, SpecializedMeshPipelines,
+        BindGroup, BindGroupLayout, PipelineCache, PolygonMode, PrimitiveTopology,
+        RenderPipelineDescriptor, Shader, ShaderStages, SpecializedMeshPipeline,
+        SpecializedMeshPipelineError, SpecializedMeshPipelines,
     },
     renderer::RenderDevice,
-    view::{ExtractedView, Msaa, VisibleEntities},
+    view::{ExtractedView, Msaa, ViewDepthFormat, VisibleEntities},
     RenderApp, RenderStage,
 };
-use bevy_utils::tracing::error;
+use bevy_utils::tracing::{error, warn};
 use std::hash::Hash;
 use std::marker::PhantomData;
 
@@ -60,12 +62,65 @@ pub trait Material: Asset + RenderAsset {
         None
     }
 
+    /// Returns the fragment shader used for this specific `material`, which takes precedence over
+    /// the shader returned by [`Material::fragment_shader`]. This allows instances of the same
+    /// material type to be rendered with different shaders. Defaults to [`None`].
+    #[allow(unused_variables)]
+    fn fragment_shader_override(
+        material: &<Self as RenderAsset>::PreparedAsset,
+    ) -> Option<&Handle<Shader>> {
+        None
+    }
+
     /// Returns this material's [`AlphaMode`]. Defaults to [`AlphaMode::Opaque`].
     #[allow(unused_variables)]
     fn alpha_mode(material: &<Self as RenderAsset>::PreparedAsset) -> AlphaMode {
         AlphaMode::Opaque
     }
 
+    /// Returns how this material is blended into the render target. Materials that don't use
+    /// [`BlendSetting::Opaque`] are drawn in the transparent pass. Defaults to the blending implied
+    /// by [`Material::alpha_mode`].
+    fn blend_setting(material: &<Self as RenderAsset>::PreparedAsset) -> BlendSetting {
+        <Self as Material>::alpha_mode(material).into()
+    }
+
+    /// Returns how this material tests against and writes to the depth buffer.
+    /// Defaults to [`DepthSetting::DEFAULT`].
+    #[allow(unused_variables)]
+    fn depth_setting(material: &<Self as RenderAsset>::PreparedAsset) -> DepthSetting {
+        DepthSetting::DEFAULT
+    }
+
+    /// Returns which faces of this material are culled. Entities can override this with a
+    /// [`FaceCulling`] component. Defaults to [`FaceCulling::BACK`].
+    #[allow(unused_variables)]
+    fn face_culling(material: &<Self as RenderAsset>::PreparedAsset) -> FaceCulling {
+        FaceCulling::BACK
+    }
+
+    /// Returns how this material tests against and writes to the stencil buffer.
+    /// Defaults to [`StencilSetting::IGNORE`].
+    #[allow(unused_variables)]
+    fn stencil_setting(material: &<Self as RenderAsset>::PreparedAsset) -> StencilSetting {
+        StencilSetting::IGNORE
+    }
+
+    /// Returns the [`PrimitiveTopology`] this material is rendered with, which takes precedence
+    /// over the [`Mesh::primitive_topology`]. If [`None`] is returned, the topology of the mesh is
+    /// used. Defaults to [`None`].
+    ///
+    /// The vertices (or indices, for indexed meshes) of the mesh are interpreted according to the
+    /// topology: lists use every 2 (lines) or 3 (triangles) consecutive vertices as a separate
+    /// primitive, while strips connect each vertex to the previous one(s). Strips of indexed meshes
+    /// are restarted by the maximum index value of the mesh's index format.
+    #[allow(unused_variables)]
+    fn primitive_topology(
+        material: &<Self as RenderAsset>::PreparedAsset,
+    ) -> Option<PrimitiveTopology> {
+        None
+    }
+
     /// The dynamic uniform indices to set for the given `material`'s [`BindGroup`].
     /// Defaults to an empty array / no dynamic uniform indices.
     #[allow(unused_variables)]
@@ -115,6 +170,33 @@ impl<M: Material> SpecializedMaterial for M {
         <M as Material>::alpha_mode(material)
     }
 
+    #[inline]
+    fn blend_setting(material: &<Self as RenderAsset>::PreparedAsset) -> BlendSetting {
+        <M as Material>::blend_setting(material)
+    }
+
+    #[inline]
+    fn depth_setting(material: &<Self as RenderAsset>::PreparedAsset) -> DepthSetting {
+        <M as Material>::depth_setting(material)
+    }
+
+    #[inline]
+    fn face_culling(material: &<Self as RenderAsset>::PreparedAsset) -> FaceCulling {
+        <M as Material>::face_culling(material)
+    }
+
+    #[inline]
+    fn stencil_setting(material: &<Self as RenderAsset>::PreparedAsset) -> StencilSetting {
+        <M as Material>::stencil_setting(material)
+    }
+
+    #[inline]
+    fn primitive_topology(
+        material: &<Self as RenderAsset>::PreparedAsset,
+    ) -> Option<PrimitiveTopology> {
+        <M as Material>::primitive_topology(material)
+    }
+
     #[inline]
     fn vertex_shader(asset_server: &AssetServer) -> Option<Handle<Shader>> {
         <M as Material>::vertex_shader(asset_server)
@@ -125,6 +207,13 @@ impl<M: Material> SpecializedMaterial for M {
         <M as Material>::fragment_shader(asset_server)
     }
 
+    #[inline]
+    fn fragment_shader_override(
+        material: &<Self as RenderAsset>::PreparedAsset,
+    ) -> Option<&Handle<Shader>> {
+        <M as Material>::fragment_shader_override(material)
+    }
+
     #[allow(unused_variables)]
     #[inline]
     fn dynamic_uniform_indices(material: &<Self as RenderAsset>::PreparedAsset) -> &[u32] {
@@ -173,12 +262,65 @@ pub trait SpecializedMaterial: Asset + RenderAsset {
         None
     }
 
+    /// Returns the fragment shader used for this specific `material`, which takes precedence over
+    /// the shader returned by [`SpecializedMaterial::fragment_shader`]. This allows instances of
+    /// the same material type to be rendered with different shaders. Defaults to [`None`].
+    #[allow(unused_variables)]
+    fn fragment_shader_override(
+        material: &<Self as RenderAsset>::PreparedAsset,
+    ) -> Option<&Handle<Shader>> {
+        None
+    }
+
     /// Returns this material's [`AlphaMode`]. Defaults to [`AlphaMode::Opaque`].
     #[allow(unused_variables)]
     fn alpha_mode(material: &<Self as RenderAsset>::PreparedAsset) -> AlphaMode {
         AlphaMode::Opaque
     }
 
+    /// Returns how this material is blended into the render target. Materials that don't use
+    /// [`BlendSetting::Opaque`] are drawn in the transparent pass. Defaults to the blending implied
+    /// by [`SpecializedMaterial::alpha_mode`].
+    fn blend_setting(material: &<Self as RenderAsset>::PreparedAsset) -> BlendSetting {
+        <Self as SpecializedMaterial>::alpha_mode(material).into()
+    }
+
+    /// Returns how this material tests against and writes to the depth buffer.
+    /// Defaults to [`DepthSetting::DEFAULT`].
+    #[allow(unused_variables)]
+    fn depth_setting(material: &<Self as RenderAsset>::PreparedAsset) -> DepthSetting {
+        DepthSetting::DEFAULT
+    }
+
+    /// Returns which faces of this material are culled. Entities can override this with a
+    /// [`FaceCulling`] component. Defaults to [`FaceCulling::BACK`].
+    #[allow(unused_variables)]
+    fn face_culling(material: &<Self as RenderAsset>::PreparedAsset) -> FaceCulling {
+        FaceCulling::BACK
+    }
+
+    /// Returns how this material tests against and writes to the stencil buffer.
+    /// Defaults to [`StencilSetting::IGNORE`].
+    #[allow(unused_variables)]
+    fn stencil_setting(material: &<Self as RenderAsset>::PreparedAsset) -> StencilSetting {
+        StencilSetting::IGNORE
+    }
+
+    /// Returns the [`PrimitiveTopology`] this material is rendered with, which takes precedence
+    /// over the [`Mesh::primitive_topology`]. If [`None`] is returned, the topology of the mesh is
+    /// used. Defaults to [`None`].
+    ///
+    /// The vertices (or indices, for indexed meshes) of the mesh are interpreted according to the
+    /// topology: lists use every 2 (lines) or 3 (triangles) consecutive vertices as a separate
+    /// primitive, while strips connect each vertex to the previous one(s). Strips of indexed meshes
+    /// are restarted by the maximum index value of the mesh's index format.
+    #[allow(unused_variables)]
+    fn primitive_topology(
+        material: &<Self as RenderAsset>::PreparedAsset,
+    ) -> Option<PrimitiveTopology> {
+        None
+    }
+
     /// The dynamic uniform indices to set for the given `material`'s [`BindGroup`].
     /// Defaults to an empty array / no dynamic uniform indices.
     #[allow(unused_variables)]
@@ -219,6 +361,18 @@ impl<M: SpecializedMaterial> Plugin for MaterialPlugin<M> {
 pub struct MaterialPipelineKey<T> {
     mesh_key: MeshPipelineKey,
     material_key: T,
+    /// A weak handle to the fragment shader override of the material, see
+    /// [`SpecializedMaterial::fragment_shader_override`].
+    fragment_shader: Option<Handle<Shader>>,
+    blend: BlendSetting,
+    depth: DepthSetting,
+    face_culling: FaceCulling,
+    /// The [`FaceCulling`] component of the entity, which takes precedence over the culling of the
+    /// material.
+    entity_face_culling: Option<FaceCulling>,
+    /// Renders the material with [`PolygonMode::Line`], see [`WireframeConfig::materials`].
+    wireframe: bool,
+    stencil: StencilSetting,
 }
 
 pub struct MaterialPipeline<M: SpecializedMaterial> {
@@ -229,6 +383,48 @@ pub struct MaterialPipeline<M: SpecializedMaterial> {
     marker: PhantomData<M>,
 }
 
+impl<M: SpecializedMaterial> MaterialPipeline<M> {
+    /// Applies `key` to a descriptor specialized by [`MeshPipeline::specialize_descriptor`], using
+    /// the `vertex_shader` and `fragment_shader` of the material type if they are set.
+    ///
+    /// This doesn't need the bind group layouts, which
+    /// [`MaterialPipeline::specialize`](SpecializedMeshPipeline::specialize) adds afterwards,
+    /// before specializing the descriptor for the material itself.
+    pub fn specialize_descriptor(
+        descriptor: &mut RenderPipelineDescriptor,
+        key: &MaterialPipelineKey<M::Key>,
+        vertex_shader: Option<&Handle<Shader>>,
+        fragment_shader: Option<&Handle<Shader>>,
+        depth_format: ViewDepthFormat,
+    ) {
+        if let Some(vertex_shader) = vertex_shader {
+            descriptor.vertex.shader = vertex_shader.clone();
+        }
+
+        if let Some(fragment_shader) = key.fragment_shader.as_ref().or(fragment_shader) {
+            descriptor.fragment.as_mut().unwrap().shader = fragment_shader.clone();
+        }
+        descriptor.fragment.as_mut().unwrap().targets[0].blend = Some(key.blend.blend_state());
+        if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
+            key.depth.apply(depth_stencil);
+            if !key.stencil.is_enabled() || depth_format.has_stencil() {
+                key.stencil.apply(depth_stencil);
+            } else {
+                warn!(
+                    "{} uses stencil operations, but the view depth format {:?} has no stencil aspect. Insert a ViewDepthFormat with a stencil aspect to enable them.",
+                    std::any::type_name::<M>(),
+                    depth_format.0
+                );
+            }
+        }
+        key.face_culling.apply(&mut descriptor.primitive);
+        if key.wireframe {
+            descriptor.primitive.polygon_mode = PolygonMode::Line;
+            descriptor.push_shader_def("WIREFRAME", ShaderStages::VERTEX_FRAGMENT);
+        }
+    }
+}
+
 impl<M: SpecializedMaterial> SpecializedMeshPipeline for MaterialPipeline<M> {
     type Key = MaterialPipelineKey<M::Key>;
 
@@ -238,13 +434,13 @@ impl<M: SpecializedMaterial> SpecializedMeshPipeline for MaterialPipeline<M> {
         layout: &MeshVertexBufferLayout,
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
         let mut descriptor = self.mesh_pipeline.specialize(key.mesh_key, layout)?;
-        if let Some(vertex_shader) = &self.vertex_shader {
-            descriptor.vertex.shader = vertex_shader.clone();
-        }
-
-        if let Some(fragment_shader) = &self.fragment_shader {
-            descriptor.fragment.as_mut().unwrap().shader = fragment_shader.clone();
-        }
+        Self::specialize_descriptor(
+            &mut descriptor,
+            &key,
+            self.vertex_shader.as_ref(),
+            self.fragment_shader.as_ref(),
+            self.mesh_pipeline.depth_format,
+        );
 
         // MeshPipeline::specialize's current implementation guarantees that the returned
         // specialized descriptor has a populated layout
@@ -252,6 +448,9 @@ impl<M: SpecializedMaterial> SpecializedMeshPipeline for MaterialPipeline<M> {
         descriptor_layout.insert(1, self.material_layout.clone());
 
         M::specialize(&mut descriptor, key.material_key, layout)?;
+        if let Some(face_culling) = key.entity_face_culling {
+            face_culling.apply(&mut descriptor.primitive);
+        }
         Ok(descriptor)
     }
 }
@@ -277,9 +476,27 @@ type DrawMaterial<M> = (
     SetMeshViewBindGroup<0>,
     SetMaterialBindGroup<M, 1>,
     SetMeshBindGroup<2>,
+    SetStencilReference,
     DrawMesh,
 );
 
+/// Sets the stencil reference value to the entity's [`StencilReference`], or `0` if it has none.
+pub struct SetStencilReference;
+impl EntityRenderCommand for SetStencilReference {
+    type Param = SQuery<Option<Read<StencilReference>>>;
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        query: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let reference = query.get(item).ok().flatten().copied().unwrap_or_default();
+        pass.set_stencil_reference(reference.0 as u32);
+        RenderCommandResult::Success
+    }
+}
+
 pub struct SetMaterialBindGroup<M: SpecializedMaterial, const I: usize>(PhantomData<M>);
 impl<M: SpecializedMaterial, const I: usize> EntityRenderCommand for SetMaterialBindGroup<M, I> {
     type Param = (SRes<RenderAssets<M>>, SQuery<Read<Handle<M>>>);
@@ -309,9 +526,18 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
     mut pipelines: ResMut<SpecializedMeshPipelines<MaterialPipeline<M>>>,
     mut pipeline_cache: ResMut<PipelineCache>,
     msaa: Res<Msaa>,
+    render_device: Res<RenderDevice>,
+    wireframe_config: Option<Res<WireframeConfig>>,
+    mut warned_unsupported_wireframes: Local<bool>,
     render_meshes: Res<RenderAssets<Mesh>>,
     render_materials: Res<RenderAssets<M>>,
-    material_meshes: Query<(&Handle<M>, &Handle<Mesh>, &MeshUniform)>,
+    material_meshes: Query<(
+        &Handle<M>,
+        &Handle<Mesh>,
+        &MeshUniform,
+        Option<&FaceCulling>,
+        Option<&Wireframe>,
+    )>,
     mut views: Query<(
         &ExtractedView,
         &VisibleEntities,
@@ -339,22 +565,51 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
         let inverse_view_matrix = view.transform.compute_matrix().inverse();
         let inverse_view_row_2 = inverse_view_matrix.row(2);
         let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples);
+        let wireframe_materials = wireframe_config
+            .as_ref()
+            .map_or(false, |config| config.materials);
+        let global_wireframe = wireframe_config
+            .as_ref()
+            .map_or(false, |config| config.global);
 
         for visible_entity in &visible_entities.entities {
-            if let Ok((material_handle, mesh_handle, mesh_uniform)) =
-                material_meshes.get(*visible_entity)
+            if let Ok((
+                material_handle,
+                mesh_handle,
+                mesh_uniform,
+                entity_face_culling,
+                entity_wireframe,
+            )) = material_meshes.get(*visible_entity)
             {
                 if let Some(material) = render_materials.get(material_handle) {
                     if let Some(mesh) = render_meshes.get(mesh_handle) {
+                        let primitive_topology =
+                            M::primitive_topology(material).unwrap_or(mesh.primitive_topology);
                         let mut mesh_key =
-                            MeshPipelineKey::from_primitive_topology(mesh.primitive_topology)
+                            MeshPipelineKey::from_primitive_topology(primitive_topology)
+                                | MeshPipelineKey::from_strip_index_format(
+                                    mesh.strip_index_format(primitive_topology),
+                                )
                                 | msaa_key;
                         let alpha_mode = M::alpha_mode(material);
-                        if let AlphaMode::Blend = alpha_mode {
+                        let blend = M::blend_setting(material);
+                        let transparent = alpha_mode == AlphaMode::Blend || blend.is_transparent();
+                        if transparent {
                             mesh_key |= MeshPipelineKey::TRANSPARENT_MAIN_PASS;
                         }
 
+                        let wireframe = wireframe_materials
+                            && (global_wireframe || entity_wireframe.is_some())
+                            && polygon_mode_line_supported(
+                                &render_device,
+                                &mut warned_unsupported_wireframes,
+                            );
+
                         let material_key = M::key(material);
+                        // a weak handle is enough to tell pipelines apart, and doesn't keep
+                        // replaced shaders alive
+                        let fragment_shader =
+                            M::fragment_shader_override(material).map(Handle::clone_weak);
 
                         let pipeline_id = pipelines.specialize(
                             &mut pipeline_cache,
@@ -362,6 +617,13 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
                             MaterialPipelineKey {
                                 mesh_key,
                                 material_key,
+                                fragment_shader,
+                                blend,
+                                depth: M::depth_setting(material),
+                                face_culling: M::face_culling(material),
+                                entity_face_culling: entity_face_culling.copied(),
+                                wireframe,
+                                stencil: M::stencil_setting(material),
                             },
                             &mesh.layout,
                         );
@@ -376,12 +638,29 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
                         // NOTE: row 2 of the inverse view matrix dotted with column 3 of the model matrix
                         // gives the z component of translation of the mesh in view space
                         let mesh_z = inverse_view_row_2.dot(mesh_uniform.transform.col(3));
-                        match alpha_mode {
-                            AlphaMode::Opaque => {
+                        match (alpha_mode, transparent) {
+                            // materials blended into the render target are drawn in the
+                            // transparent pass, even if their alpha mode is opaque or masked
+                            (AlphaMode::Blend, _)
+                            | (AlphaMode::Opaque | AlphaMode::Mask(_), true) => {
+                                transparent_phase.add(Transparent3d {
+                                    entity: *visible_entity,
+                                    draw_function: draw_transparent_pbr,
+                                    pipeline: pipeline_id,
+                                    // NOTE: Back-to-front ordering for transparent with ascending sort means far should have the
+                                    // lowest sort key and getting closer should increase. As we have
+                                    // -z in front of the camera, the largest distance is -far with values increasing toward the
+                                    // camera. As such we can just use mesh_z as the distance
+                                    distance: mesh_z,
+                                });
+                            }
+                            (AlphaMode::Opaque, false) => {
                                 opaque_phase.add(Opaque3d {
                                     entity: *visible_entity,
                                     draw_function: draw_opaque_pbr,
                                     pipeline: pipeline_id,
+                                    material_bind_group: Some(M::bind_group(material).id()),
+                                    mesh: Some(mesh_handle.id),
                                     // NOTE: Front-to-back ordering for opaque with ascending sort means near should have the
                                     // lowest sort key and getting further away should increase. As we have
                                     // -z in front of the camera, values in view space decrease away from the
@@ -389,7 +668,7 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
                                     distance: -mesh_z,
                                 });
                             }
-                            AlphaMode::Mask(_) => {
+                            (AlphaMode::Mask(_), false) => {
                                 alpha_mask_phase.add(AlphaMask3d {
                                     entity: *visible_entity,
                                     draw_function: draw_alpha_mask_pbr,
@@ -401,18 +680,6 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
                                     distance: -mesh_z,
                                 });
                             }
-                            AlphaMode::Blend => {
-                                transparent_phase.add(Transparent3d {
-                                    entity: *visible_entity,
-                                    draw_function: draw_transparent_pbr,
-                                    pipeline: pipeline_id,
-                                    // NOTE: Back-to-front ordering for transparent with ascending sort means far should have the
-                                    // lowest sort key and getting closer should increase. As we have
-                                    // -z in front of the camera, the largest distance is -far with values increasing toward the
-                                    // camera. As such we can just use mesh_z as the distance
-                                    distance: mesh_z,
-                                });
-                            }
                         }
                     }
                 }
@@ -420,3 +687,98 @@ pub fn queue_material_meshes<M: SpecializedMaterial>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{MaterialPipeline, MaterialPipelineKey};
+    use crate::{
+        BlendSetting, DepthSetting, FaceCulling, Material, MeshPipeline, MeshPipelineKey,
+        StencilSetting, MESH_SHADER_HANDLE,
+    };
+    use bevy_asset::HandleUntyped;
+    use bevy_reflect::TypeUuid;
+    use bevy_render::{
+        mesh::{shape, Mesh},
+        render_asset::{PrepareAssetError, RenderAsset},
+        render_resource::{BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Shader},
+        renderer::RenderDevice,
+        view::ViewDepthFormat,
+    };
+
+    #[derive(Clone, TypeUuid)]
+    #[uuid = "5f0c8d2e-7a3b-4e91-b6d4-2c9e81f0a347"]
+    struct OutlineMaterial;
+
+    impl RenderAsset for OutlineMaterial {
+        type ExtractedAsset = Self;
+        type PreparedAsset = ();
+        type Param = ();
+
+        fn extract_asset(&self) -> Self {
+            self.clone()
+        }
+
+        fn prepare_asset(
+            _extracted_asset: Self,
+            _param: &mut (),
+        ) -> Result<(), PrepareAssetError<Self>> {
+            Ok(())
+        }
+    }
+
+    impl Material for OutlineMaterial {
+        fn bind_group(_material: &()) -> &BindGroup {
+            unreachable!("the material is only specialized, not drawn")
+        }
+
+        fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[],
+            })
+        }
+    }
+
+    #[test]
+    fn fragment_shader_override_replaces_the_fragment_shader() {
+        let layout = Mesh::from(shape::Cube::default()).get_mesh_vertex_buffer_layout();
+        let specialize = |fragment_shader| {
+            let key = MaterialPipelineKey {
+                mesh_key: MeshPipelineKey::from_msaa_samples(1),
+                material_key: (),
+                fragment_shader,
+                blend: BlendSetting::Opaque,
+                depth: DepthSetting::DEFAULT,
+                face_culling: FaceCulling::BACK,
+                entity_face_culling: None,
+                wireframe: false,
+                stencil: StencilSetting::IGNORE,
+            };
+            let mut descriptor = MeshPipeline::specialize_descriptor(
+                key.mesh_key,
+                &layout,
+                ViewDepthFormat::default(),
+                &Default::default(),
+            )
+            .unwrap();
+            MaterialPipeline::<OutlineMaterial>::specialize_descriptor(
+                &mut descriptor,
+                &key,
+                None,
+                None,
+                ViewDepthFormat::default(),
+            );
+            descriptor
+        };
+        let mesh_shader = MESH_SHADER_HANDLE.typed::<Shader>();
+        let outline_shader = HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 1).typed::<Shader>();
+
+        let descriptor = specialize(None);
+        assert_eq!(descriptor.fragment.unwrap().shader, mesh_shader);
+
+        // only the fragment shader is replaced
+        let descriptor = specialize(Some(outline_shader.clone()));
+        assert_eq!(descriptor.fragment.unwrap().shader, outline_shader);
+        assert_eq!(descriptor.vertex.shader, mesh_shader);
+    }
+}
@@ -1,26 +1,36 @@
-use crate::{AlphaMode, MaterialPipeline, SpecializedMaterial, PBR_SHADER_HANDLE};
+use crate::{AlphaMode, FaceCulling, MaterialPipeline, SpecializedMaterial, PBR_SHADER_HANDLE};
 use bevy_asset::{AssetServer, Handle};
-use bevy_ecs::system::{lifetimeless::SRes, SystemParamItem};
+use bevy_ecs::system::{
+    lifetimeless::{SRes, SResMut},
+    SystemParamItem,
+};
 use bevy_math::Vec4;
 use bevy_reflect::TypeUuid;
 use bevy_render::{
     color::Color,
     mesh::MeshVertexBufferLayout,
     prelude::Shader,
-    render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+    render_asset::{DependentRenderAsset, PrepareAssetError, RenderAsset, RenderAssets},
     render_resource::{
         std140::{AsStd140, Std140},
         *,
     },
-    renderer::RenderDevice,
-    texture::Image,
+    renderer::{RenderDevice, RenderQueue},
+    texture::{
+        srgb_shader_def, FallbackTexture, FallbackTextures, GpuImage, Image, ImageSampler,
+        SamplerCache, SamplerOverride,
+    },
 };
+use bevy_utils::tracing::error;
 
 /// A material with "standard" properties used in PBR lighting
 /// Standard property values with pictures here
 /// <https://google.github.io/filament/Material%20Properties.pdf>.
 ///
 /// May be created directly from a [`Color`] or an [`Image`].
+///
+/// The textures may be [weak handles](Handle::clone_weak), which don't keep the images loaded.
+/// While such an image isn't loaded, the material binds a fallback texture in its place.
 #[derive(Debug, Clone, TypeUuid)]
 #[uuid = "7494888b-c082-457b-aacf-517228cc0c22"]
 pub struct StandardMaterial {
@@ -29,10 +39,14 @@ pub struct StandardMaterial {
     /// base color as `base_color * base_color_texture_value`
     pub base_color: Color,
     pub base_color_texture: Option<Handle<Image>>,
+    /// Overrides the sampler of the `base_color_texture`, which defaults to the sampler of the
+    /// image itself. The same applies to the samplers of the other textures.
+    pub base_color_sampler: Option<Handle<ImageSampler>>,
     // Use a color for user friendliness even though we technically don't use the alpha channel
     // Might be used in the future for exposure correction in HDR
     pub emissive: Color,
     pub emissive_texture: Option<Handle<Image>>,
+    pub emissive_sampler: Option<Handle<ImageSampler>>,
     /// Linear perceptual roughness, clamped to [0.089, 1.0] in the shader
     /// Defaults to minimum of 0.089
     /// If used together with a roughness/metallic texture, this is factored into the final base
@@ -43,11 +57,16 @@ pub struct StandardMaterial {
     /// color as `metallic * metallic_texture_value`
     pub metallic: f32,
     pub metallic_roughness_texture: Option<Handle<Image>>,
+    pub metallic_roughness_sampler: Option<Handle<ImageSampler>>,
     /// Specular intensity for non-metals on a linear scale of [0.0, 1.0]
     /// defaults to 0.5 which is mapped to 4% reflectance in the shader
     pub reflectance: f32,
     pub normal_map_texture: Option<Handle<Image>>,
+    pub normal_map_sampler: Option<Handle<ImageSampler>>,
     pub occlusion_texture: Option<Handle<Image>>,
+    pub occlusion_sampler: Option<Handle<ImageSampler>>,
+    /// Overrides the samplers of all textures that don't set their own sampler asset.
+    pub sampler_override: Option<SamplerOverride>,
     /// Support two-sided lighting by automatically flipping the normals for "back" faces
     /// within the PBR lighting shader.
     /// Defaults to false.
@@ -66,8 +85,10 @@ impl Default for StandardMaterial {
         StandardMaterial {
             base_color: Color::rgb(1.0, 1.0, 1.0),
             base_color_texture: None,
+            base_color_sampler: None,
             emissive: Color::BLACK,
             emissive_texture: None,
+            emissive_sampler: None,
             // This is the minimum the roughness is clamped to in shader code
             // See <https://google.github.io/filament/Filament.html#materialsystem/parameterization/>
             // It's the minimum floating point value that won't be rounded down to 0 in the
@@ -78,12 +99,16 @@ impl Default for StandardMaterial {
             // This is just a default for mostly-dielectric
             metallic: 0.01,
             metallic_roughness_texture: None,
+            metallic_roughness_sampler: None,
             // Minimum real-world reflectance is 2%, most materials between 2-5%
             // Expressed in a linear scale and equivalent to 4% reflectance see
             // <https://google.github.io/filament/Material%20Properties.pdf>
             reflectance: 0.5,
             occlusion_texture: None,
+            occlusion_sampler: None,
+            sampler_override: None,
             normal_map_texture: None,
+            normal_map_sampler: None,
             double_sided: false,
             cull_mode: Some(Face::Back),
             unlit: false,
@@ -155,12 +180,17 @@ pub struct StandardMaterialUniformData {
 /// The GPU representation of a [`StandardMaterial`].
 #[derive(Debug, Clone)]
 pub struct GpuStandardMaterial {
-    /// A buffer containing the [`StandardMaterialUniformData`] of the material.
-    pub buffer: Buffer,
+    /// The range of a uniform buffer containing the [`StandardMaterialUniformData`] of the
+    /// material.
+    pub uniform: UniformAllocation,
     /// The bind group specifying how the [`StandardMaterialUniformData`] and
     /// all the textures of the material are bound.
     pub bind_group: BindGroup,
+    /// The textures and samplers bound by the `bind_group`.
+    pub binding_key: StandardMaterialBindingKey,
     pub has_normal_map: bool,
+    /// Which of the textures use an sRGB format, in the order of [`STANDARD_MATERIAL_TEXTURES`].
+    pub srgb_textures: [bool; 5],
     pub flags: StandardMaterialFlags,
     pub base_color_texture: Option<Handle<Image>>,
     pub alpha_mode: AlphaMode,
@@ -174,6 +204,11 @@ impl RenderAsset for StandardMaterial {
         SRes<RenderDevice>,
         SRes<MaterialPipeline<StandardMaterial>>,
         SRes<RenderAssets<Image>>,
+        SRes<RenderAssets<ImageSampler>>,
+        SRes<FallbackTextures>,
+        SResMut<SamplerCache>,
+        SRes<RenderQueue>,
+        SResMut<UniformAllocator>,
     );
 
     fn extract_asset(&self) -> Self::ExtractedAsset {
@@ -182,181 +217,474 @@ impl RenderAsset for StandardMaterial {
 
     fn prepare_asset(
         material: Self::ExtractedAsset,
-        (render_device, pbr_pipeline, gpu_images): &mut SystemParamItem<Self::Param>,
+        param: &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
-        let (base_color_texture_view, base_color_sampler) = if let Some(result) = pbr_pipeline
-            .mesh_pipeline
-            .get_image_texture(gpu_images, &material.base_color_texture)
-        {
-            result
-        } else {
-            return Err(PrepareAssetError::RetryNextUpdate(material));
-        };
+        match prepare_standard_material(&material, param, false) {
+            Some(prepared) => Ok(prepared),
+            None => Err(PrepareAssetError::RetryNextUpdate(material)),
+        }
+    }
 
-        let (emissive_texture_view, emissive_sampler) = if let Some(result) = pbr_pipeline
-            .mesh_pipeline
-            .get_image_texture(gpu_images, &material.emissive_texture)
-        {
-            result
-        } else {
-            return Err(PrepareAssetError::RetryNextUpdate(material));
-        };
+    /// Binds [`FallbackTextures`] in place of the textures that haven't been loaded yet. Normal
+    /// maps fall back to flat normals, all other textures to white.
+    fn prepare_fallback_asset(
+        material: &Self::ExtractedAsset,
+        param: &mut SystemParamItem<Self::Param>,
+    ) -> Option<Self::PreparedAsset> {
+        prepare_standard_material(material, param, true)
+    }
 
-        let (metallic_roughness_texture_view, metallic_roughness_sampler) = if let Some(result) =
-            pbr_pipeline
-                .mesh_pipeline
-                .get_image_texture(gpu_images, &material.metallic_roughness_texture)
-        {
-            result
-        } else {
-            return Err(PrepareAssetError::RetryNextUpdate(material));
-        };
-        let (normal_map_texture_view, normal_map_sampler) = if let Some(result) = pbr_pipeline
-            .mesh_pipeline
-            .get_image_texture(gpu_images, &material.normal_map_texture)
-        {
-            result
-        } else {
-            return Err(PrepareAssetError::RetryNextUpdate(material));
-        };
-        let (occlusion_texture_view, occlusion_sampler) = if let Some(result) = pbr_pipeline
-            .mesh_pipeline
-            .get_image_texture(gpu_images, &material.occlusion_texture)
-        {
-            result
-        } else {
-            return Err(PrepareAssetError::RetryNextUpdate(material));
-        };
-        let mut flags = StandardMaterialFlags::NONE;
-        if material.base_color_texture.is_some() {
-            flags |= StandardMaterialFlags::BASE_COLOR_TEXTURE;
-        }
-        if material.emissive_texture.is_some() {
-            flags |= StandardMaterialFlags::EMISSIVE_TEXTURE;
+    /// Only writes the new uniform data if the textures and samplers of the material are
+    /// unchanged, see [`StandardMaterialBindingKey`].
+    fn update_prepared_asset(
+        material: &Self::ExtractedAsset,
+        prepared: &mut Self::PreparedAsset,
+        param: &mut SystemParamItem<Self::Param>,
+    ) -> bool {
+        update_standard_material(material, prepared, param)
+    }
+}
+
+impl DependentRenderAsset<Image> for StandardMaterial {
+    fn dependencies(&self) -> Vec<Handle<Image>> {
+        let (textures, _) = standard_material_bound_handles(self);
+        textures.into_iter().flatten().cloned().collect()
+    }
+}
+
+impl DependentRenderAsset<ImageSampler> for StandardMaterial {
+    fn dependencies(&self) -> Vec<Handle<ImageSampler>> {
+        let (_, samplers) = standard_material_bound_handles(self);
+        samplers.into_iter().flatten().cloned().collect()
+    }
+}
+
+/// The texture fields of a [`StandardMaterial`], in the order of their bindings. Textures with an
+/// sRGB format define the [`srgb_shader_def`] of their field, e.g. `BASE_COLOR_TEXTURE_SRGB`.
+pub const STANDARD_MATERIAL_TEXTURES: [&str; 5] = [
+    "base_color_texture",
+    "emissive_texture",
+    "metallic_roughness_texture",
+    "occlusion_texture",
+    "normal_map_texture",
+];
+
+/// Returns the textures and samplers bound by the `material`, in the order of
+/// [`STANDARD_MATERIAL_TEXTURES`]. Apart from its `sampler_override`, these are the only fields of
+/// the material whose changes require a new bind group, the others are uniform data.
+fn standard_material_bound_handles(
+    material: &StandardMaterial,
+) -> (
+    [&Option<Handle<Image>>; 5],
+    [&Option<Handle<ImageSampler>>; 5],
+) {
+    (
+        [
+            &material.base_color_texture,
+            &material.emissive_texture,
+            &material.metallic_roughness_texture,
+            &material.occlusion_texture,
+            &material.normal_map_texture,
+        ],
+        [
+            &material.base_color_sampler,
+            &material.emissive_sampler,
+            &material.metallic_roughness_sampler,
+            &material.occlusion_sampler,
+            &material.normal_map_sampler,
+        ],
+    )
+}
+
+/// The textures and samplers bound by a [`GpuStandardMaterial`], in the order of the bindings.
+struct StandardMaterialBindings<'a> {
+    images: [&'a GpuImage; 5],
+    samplers: [&'a Sampler; 5],
+}
+
+impl StandardMaterialBindings<'_> {
+    /// Identifies the bound resources. Reloading an image or sampler creates new resources, so
+    /// materials with the same key can keep their bind group.
+    fn key(&self) -> StandardMaterialBindingKey {
+        StandardMaterialBindingKey {
+            texture_views: self.images.map(|image| image.texture_view.id()),
+            samplers: self.samplers.map(Sampler::id),
         }
-        if material.metallic_roughness_texture.is_some() {
-            flags |= StandardMaterialFlags::METALLIC_ROUGHNESS_TEXTURE;
+    }
+
+    /// Which of the textures use an sRGB format. Textures the `material` doesn't set are never
+    /// sRGB, as the shader doesn't sample them.
+    fn srgb_textures(&self, material: &StandardMaterial) -> [bool; 5] {
+        let (textures, _) = standard_material_bound_handles(material);
+        let mut srgb_textures = [false; 5];
+        for ((srgb, image), texture) in srgb_textures.iter_mut().zip(self.images).zip(textures) {
+            *srgb = texture.is_some() && image.is_srgb();
         }
-        if material.occlusion_texture.is_some() {
-            flags |= StandardMaterialFlags::OCCLUSION_TEXTURE;
+        srgb_textures
+    }
+}
+
+/// The texture views and samplers bound by a [`GpuStandardMaterial`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StandardMaterialBindingKey {
+    texture_views: [TextureViewId; 5],
+    samplers: [SamplerId; 5],
+}
+
+/// Resolves the textures and samplers of the `material` and passes them to `f`. Returns `None`
+/// if a texture hasn't been loaded yet or can't be bound, unless `use_fallbacks` is set, in which
+/// case its fallback texture is bound instead.
+fn with_standard_material_bindings<T>(
+    material: &StandardMaterial,
+    (
+        render_device,
+        pbr_pipeline,
+        gpu_images,
+        gpu_samplers,
+        fallback_textures,
+        sampler_cache,
+        render_queue,
+        uniform_allocator,
+    ): &mut SystemParamItem<<StandardMaterial as RenderAsset>::Param>,
+    use_fallbacks: bool,
+    f: impl FnOnce(
+        &RenderDevice,
+        (&RenderQueue, &mut UniformAllocator),
+        &MaterialPipeline<StandardMaterial>,
+        StandardMaterialBindings,
+    ) -> T,
+) -> Option<T> {
+    let image = |field: &'static str, handle: &Option<Handle<Image>>, fallback: FallbackTexture| {
+        let handle = match handle {
+            Some(handle) => handle,
+            None => return Some(&pbr_pipeline.mesh_pipeline.dummy_white_gpu_image),
+        };
+        match gpu_images.get(handle) {
+            Some(gpu_image) => match gpu_image.check_usage(field, TextureUsages::TEXTURE_BINDING) {
+                Ok(()) => Some(gpu_image),
+                Err(err) => {
+                    // only reported once, the preparation without fallbacks is retried every frame
+                    if use_fallbacks {
+                        error!(
+                            "StandardMaterial can't bind the image {:?}: {}",
+                            handle, err
+                        );
+                    }
+                    use_fallbacks.then(|| fallback_textures.get(fallback))
+                }
+            },
+            None => use_fallbacks.then(|| fallback_textures.get(fallback)),
         }
-        if material.double_sided {
-            flags |= StandardMaterialFlags::DOUBLE_SIDED;
+    };
+    let base_color_image = image(
+        "base_color_texture",
+        &material.base_color_texture,
+        FallbackTexture::White,
+    )?;
+    let emissive_image = image(
+        "emissive_texture",
+        &material.emissive_texture,
+        FallbackTexture::White,
+    )?;
+    let metallic_roughness_image = image(
+        "metallic_roughness_texture",
+        &material.metallic_roughness_texture,
+        FallbackTexture::White,
+    )?;
+    let occlusion_image = image(
+        "occlusion_texture",
+        &material.occlusion_texture,
+        FallbackTexture::White,
+    )?;
+    let normal_map_image = image(
+        "normal_map_texture",
+        &material.normal_map_texture,
+        FallbackTexture::FlatNormal,
+    )?;
+
+    let sampler_override = material
+        .sampler_override
+        .map(|sampler_override| sampler_cache.get(render_device, sampler_override).clone());
+    let base_color_sampler = sampler(
+        gpu_samplers,
+        &material.base_color_sampler,
+        sampler_override.as_ref(),
+        base_color_image,
+        use_fallbacks,
+    )?;
+    let emissive_sampler = sampler(
+        gpu_samplers,
+        &material.emissive_sampler,
+        sampler_override.as_ref(),
+        emissive_image,
+        use_fallbacks,
+    )?;
+    let metallic_roughness_sampler = sampler(
+        gpu_samplers,
+        &material.metallic_roughness_sampler,
+        sampler_override.as_ref(),
+        metallic_roughness_image,
+        use_fallbacks,
+    )?;
+    let occlusion_sampler = sampler(
+        gpu_samplers,
+        &material.occlusion_sampler,
+        sampler_override.as_ref(),
+        occlusion_image,
+        use_fallbacks,
+    )?;
+    let normal_map_sampler = sampler(
+        gpu_samplers,
+        &material.normal_map_sampler,
+        sampler_override.as_ref(),
+        normal_map_image,
+        use_fallbacks,
+    )?;
+
+    Some(f(
+        render_device,
+        (&**render_queue, &mut **uniform_allocator),
+        pbr_pipeline,
+        StandardMaterialBindings {
+            images: [
+                base_color_image,
+                emissive_image,
+                metallic_roughness_image,
+                occlusion_image,
+                normal_map_image,
+            ],
+            samplers: [
+                base_color_sampler,
+                emissive_sampler,
+                metallic_roughness_sampler,
+                occlusion_sampler,
+                normal_map_sampler,
+            ],
+        },
+    ))
+}
+
+/// Returns the flags and uniform data of the `material`. The flags depend on the
+/// `normal_map_format` of its normal map texture.
+fn standard_material_uniform_data(
+    material: &StandardMaterial,
+    normal_map_format: TextureFormat,
+) -> (StandardMaterialFlags, StandardMaterialUniformData) {
+    let mut flags = StandardMaterialFlags::NONE;
+    if material.base_color_texture.is_some() {
+        flags |= StandardMaterialFlags::BASE_COLOR_TEXTURE;
+    }
+    if material.emissive_texture.is_some() {
+        flags |= StandardMaterialFlags::EMISSIVE_TEXTURE;
+    }
+    if material.metallic_roughness_texture.is_some() {
+        flags |= StandardMaterialFlags::METALLIC_ROUGHNESS_TEXTURE;
+    }
+    if material.occlusion_texture.is_some() {
+        flags |= StandardMaterialFlags::OCCLUSION_TEXTURE;
+    }
+    if material.double_sided {
+        flags |= StandardMaterialFlags::DOUBLE_SIDED;
+    }
+    if material.unlit {
+        flags |= StandardMaterialFlags::UNLIT;
+    }
+    if material.normal_map_texture.is_some() {
+        match normal_map_format {
+            // All 2-component unorm formats
+            TextureFormat::Rg8Unorm
+            | TextureFormat::Rg16Unorm
+            | TextureFormat::Bc5RgUnorm
+            | TextureFormat::EacRg11Unorm => {
+                flags |= StandardMaterialFlags::TWO_COMPONENT_NORMAL_MAP
+            }
+            _ => {}
         }
-        if material.unlit {
-            flags |= StandardMaterialFlags::UNLIT;
+    }
+    // NOTE: 0.5 is from the glTF default - do we want this?
+    let mut alpha_cutoff = 0.5;
+    match material.alpha_mode {
+        AlphaMode::Opaque => flags |= StandardMaterialFlags::ALPHA_MODE_OPAQUE,
+        AlphaMode::Mask(c) => {
+            alpha_cutoff = c;
+            flags |= StandardMaterialFlags::ALPHA_MODE_MASK;
         }
-        let has_normal_map = material.normal_map_texture.is_some();
-        if has_normal_map {
-            match gpu_images
-                .get(material.normal_map_texture.as_ref().unwrap())
-                .unwrap()
-                .texture_format
-            {
-                // All 2-component unorm formats
-                TextureFormat::Rg8Unorm
-                | TextureFormat::Rg16Unorm
-                | TextureFormat::Bc5RgUnorm
-                | TextureFormat::EacRg11Unorm => {
-                    flags |= StandardMaterialFlags::TWO_COMPONENT_NORMAL_MAP
-                }
-                _ => {}
+        AlphaMode::Blend => flags |= StandardMaterialFlags::ALPHA_MODE_BLEND,
+    };
+
+    let value = StandardMaterialUniformData {
+        base_color: material.base_color.as_linear_rgba_f32().into(),
+        emissive: material.emissive.into(),
+        roughness: material.perceptual_roughness,
+        metallic: material.metallic,
+        reflectance: material.reflectance,
+        flags: flags.bits(),
+        alpha_cutoff,
+    };
+    (flags, value)
+}
+
+/// Prepares the `material` for the GPU. Returns `None` if a texture hasn't been loaded yet or
+/// can't be bound, unless `use_fallbacks` is set, in which case its fallback texture is bound
+/// instead.
+fn prepare_standard_material(
+    material: &StandardMaterial,
+    param: &mut SystemParamItem<<StandardMaterial as RenderAsset>::Param>,
+    use_fallbacks: bool,
+) -> Option<GpuStandardMaterial> {
+    with_standard_material_bindings(
+        material,
+        param,
+        use_fallbacks,
+        |render_device, (render_queue, uniform_allocator), pbr_pipeline, bindings| {
+            let [base_color_image, emissive_image, metallic_roughness_image, occlusion_image, normal_map_image] =
+                bindings.images;
+            let [base_color_sampler, emissive_sampler, metallic_roughness_sampler, occlusion_sampler, normal_map_sampler] =
+                bindings.samplers;
+            let (flags, value) =
+                standard_material_uniform_data(material, normal_map_image.texture_format);
+            let value_std140 = value.as_std140();
+
+            let uniform = uniform_allocator.allocate_with_data(
+                render_device,
+                render_queue,
+                value_std140.as_bytes(),
+            );
+            let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: uniform.binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(&base_color_image.texture_view),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(base_color_sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(&emissive_image.texture_view),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: BindingResource::Sampler(emissive_sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 5,
+                        resource: BindingResource::TextureView(
+                            &metallic_roughness_image.texture_view,
+                        ),
+                    },
+                    BindGroupEntry {
+                        binding: 6,
+                        resource: BindingResource::Sampler(metallic_roughness_sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 7,
+                        resource: BindingResource::TextureView(&occlusion_image.texture_view),
+                    },
+                    BindGroupEntry {
+                        binding: 8,
+                        resource: BindingResource::Sampler(occlusion_sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 9,
+                        resource: BindingResource::TextureView(&normal_map_image.texture_view),
+                    },
+                    BindGroupEntry {
+                        binding: 10,
+                        resource: BindingResource::Sampler(normal_map_sampler),
+                    },
+                ],
+                label: Some("pbr_standard_material_bind_group"),
+                layout: &pbr_pipeline.material_layout,
+            });
+
+            let binding_key = bindings.key();
+            let srgb_textures = bindings.srgb_textures(material);
+
+            GpuStandardMaterial {
+                uniform,
+                bind_group,
+                binding_key,
+                flags,
+                has_normal_map: material.normal_map_texture.is_some(),
+                srgb_textures,
+                base_color_texture: material.base_color_texture.clone(),
+                alpha_mode: material.alpha_mode,
+                cull_mode: material.cull_mode,
             }
-        }
-        // NOTE: 0.5 is from the glTF default - do we want this?
-        let mut alpha_cutoff = 0.5;
-        match material.alpha_mode {
-            AlphaMode::Opaque => flags |= StandardMaterialFlags::ALPHA_MODE_OPAQUE,
-            AlphaMode::Mask(c) => {
-                alpha_cutoff = c;
-                flags |= StandardMaterialFlags::ALPHA_MODE_MASK;
+        },
+    )
+}
+
+/// Updates the `prepared` material in place if the `material` still binds the same textures and
+/// samplers, which only requires writing the new uniform data to its buffer. Returns `false` if
+/// the bind group has to be recreated instead.
+fn update_standard_material(
+    material: &StandardMaterial,
+    prepared: &mut GpuStandardMaterial,
+    param: &mut SystemParamItem<<StandardMaterial as RenderAsset>::Param>,
+) -> bool {
+    with_standard_material_bindings(
+        material,
+        param,
+        false,
+        |_, (render_queue, _), _, bindings| {
+            if bindings.key() != prepared.binding_key {
+                return false;
             }
-            AlphaMode::Blend => flags |= StandardMaterialFlags::ALPHA_MODE_BLEND,
-        };
+            // the normal map is bound last
+            let (flags, value) =
+                standard_material_uniform_data(material, bindings.images[4].texture_format);
+            prepared
+                .uniform
+                .write(render_queue, value.as_std140().as_bytes());
+            prepared.flags = flags;
+            prepared.srgb_textures = bindings.srgb_textures(material);
+            prepared.base_color_texture = material.base_color_texture.clone();
+            prepared.alpha_mode = material.alpha_mode;
+            prepared.cull_mode = material.cull_mode;
+            true
+        },
+    )
+    .unwrap_or(false)
+}
 
-        let value = StandardMaterialUniformData {
-            base_color: material.base_color.as_linear_rgba_f32().into(),
-            emissive: material.emissive.into(),
-            roughness: material.perceptual_roughness,
-            metallic: material.metallic,
-            reflectance: material.reflectance,
-            flags: flags.bits(),
-            alpha_cutoff,
-        };
-        let value_std140 = value.as_std140();
-
-        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("pbr_standard_material_uniform_buffer"),
-            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-            contents: value_std140.as_bytes(),
-        });
-        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::TextureView(base_color_texture_view),
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: BindingResource::Sampler(base_color_sampler),
-                },
-                BindGroupEntry {
-                    binding: 3,
-                    resource: BindingResource::TextureView(emissive_texture_view),
-                },
-                BindGroupEntry {
-                    binding: 4,
-                    resource: BindingResource::Sampler(emissive_sampler),
-                },
-                BindGroupEntry {
-                    binding: 5,
-                    resource: BindingResource::TextureView(metallic_roughness_texture_view),
-                },
-                BindGroupEntry {
-                    binding: 6,
-                    resource: BindingResource::Sampler(metallic_roughness_sampler),
-                },
-                BindGroupEntry {
-                    binding: 7,
-                    resource: BindingResource::TextureView(occlusion_texture_view),
-                },
-                BindGroupEntry {
-                    binding: 8,
-                    resource: BindingResource::Sampler(occlusion_sampler),
-                },
-                BindGroupEntry {
-                    binding: 9,
-                    resource: BindingResource::TextureView(normal_map_texture_view),
-                },
-                BindGroupEntry {
-                    binding: 10,
-                    resource: BindingResource::Sampler(normal_map_sampler),
-                },
-            ],
-            label: Some("pbr_standard_material_bind_group"),
-            layout: &pbr_pipeline.material_layout,
-        });
-
-        Ok(GpuStandardMaterial {
-            buffer,
-            bind_group,
-            flags,
-            has_normal_map,
-            base_color_texture: material.base_color_texture,
-            alpha_mode: material.alpha_mode,
-            cull_mode: material.cull_mode,
-        })
+/// Returns the culling of a material culling the `cull_mode` faces. This is independent of
+/// [`StandardMaterial::double_sided`], which only affects the lighting of the faces that are drawn.
+fn standard_material_face_culling(cull_mode: Option<Face>) -> FaceCulling {
+    FaceCulling {
+        cull_mode,
+        ..FaceCulling::BACK
+    }
+}
+
+/// Returns the sampler of `handle`. If the material doesn't set one, this is the sampler of its
+/// `sampler_override` or otherwise the sampler of the `image`. Samplers that haven't been prepared
+/// yet are handled like textures that haven't been loaded yet.
+fn sampler<'a>(
+    gpu_samplers: &'a RenderAssets<ImageSampler>,
+    handle: &Option<Handle<ImageSampler>>,
+    sampler_override: Option<&'a Sampler>,
+    image: &'a GpuImage,
+    use_fallbacks: bool,
+) -> Option<&'a Sampler> {
+    let default = sampler_override.unwrap_or(&image.sampler);
+    match handle {
+        Some(handle) if use_fallbacks => Some(gpu_samplers.get(handle).unwrap_or(default)),
+        Some(handle) => gpu_samplers.get(handle),
+        None => Some(default),
     }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct StandardMaterialKey {
     normal_map: bool,
-    cull_mode: Option<Face>,
+    srgb_textures: [bool; 5],
 }
 
 impl SpecializedMaterial for StandardMaterial {
@@ -365,7 +693,7 @@ impl SpecializedMaterial for StandardMaterial {
     fn key(render_asset: &<Self as RenderAsset>::PreparedAsset) -> Self::Key {
         StandardMaterialKey {
             normal_map: render_asset.has_normal_map,
-            cull_mode: render_asset.cull_mode,
+            srgb_textures: render_asset.srgb_textures,
         }
     }
 
@@ -375,14 +703,16 @@ impl SpecializedMaterial for StandardMaterial {
         _layout: &MeshVertexBufferLayout,
     ) -> Result<(), SpecializedMeshPipelineError> {
         if key.normal_map {
-            descriptor
-                .fragment
-                .as_mut()
-                .unwrap()
-                .shader_defs
-                .push(String::from("STANDARDMATERIAL_NORMAL_MAP"));
+            descriptor.push_shader_def("STANDARDMATERIAL_NORMAL_MAP", ShaderStages::FRAGMENT);
+        }
+        for (field, srgb) in STANDARD_MATERIAL_TEXTURES
+            .into_iter()
+            .zip(key.srgb_textures)
+        {
+            if srgb {
+                descriptor.push_shader_def(srgb_shader_def(field), ShaderStages::FRAGMENT);
+            }
         }
-        descriptor.primitive.cull_mode = key.cull_mode;
         if let Some(label) = &mut descriptor.label {
             *label = format!("pbr_{}", *label).into();
         }
@@ -514,4 +844,122 @@ impl SpecializedMaterial for StandardMaterial {
     fn alpha_mode(render_asset: &<Self as RenderAsset>::PreparedAsset) -> AlphaMode {
         render_asset.alpha_mode
     }
+
+    #[inline]
+    fn face_culling(render_asset: &<Self as RenderAsset>::PreparedAsset) -> FaceCulling {
+        standard_material_face_culling(render_asset.cull_mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        standard_material_bound_handles, standard_material_face_culling,
+        standard_material_uniform_data, StandardMaterialFlags, StandardMaterialKey,
+    };
+    use crate::{SpecializedMaterial, StandardMaterial};
+    use bevy_asset::{Handle, HandleId};
+    use bevy_render::{
+        color::Color,
+        mesh::Mesh,
+        render_resource::{
+            Face, FragmentState, FrontFace, PrimitiveState, PrimitiveTopology,
+            RenderPipelineDescriptor, ShaderDefs, TextureFormat, VertexState,
+        },
+        texture::Image,
+    };
+
+    #[test]
+    fn srgb_textures_define_shader_defs() {
+        let mut descriptor = RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            push_constant_ranges: Vec::new(),
+            vertex: VertexState {
+                shader: Handle::default(),
+                shader_defs: ShaderDefs::new(),
+                entry_point: "vertex".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
+                buffers: Vec::new(),
+                allow_unused_attributes: false,
+            },
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            fragment: Some(FragmentState {
+                shader: Handle::default(),
+                shader_defs: ShaderDefs::new(),
+                entry_point: "fragment".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
+                targets: Vec::new(),
+            }),
+        };
+        let key = StandardMaterialKey {
+            normal_map: true,
+            srgb_textures: [true, false, false, false, false],
+        };
+        let layout = Mesh::new(PrimitiveTopology::TriangleList).get_mesh_vertex_buffer_layout();
+        StandardMaterial::specialize(&mut descriptor, key, &layout).unwrap();
+
+        assert_eq!(
+            *descriptor.fragment.unwrap().shader_defs,
+            ["STANDARDMATERIAL_NORMAL_MAP", "BASE_COLOR_TEXTURE_SRGB"]
+        );
+        assert!(descriptor.vertex.shader_defs.is_empty());
+    }
+
+    #[test]
+    fn double_sided_composes_with_face_culling() {
+        for double_sided in [false, true] {
+            for cull_mode in [Some(Face::Back), Some(Face::Front), None] {
+                let material = StandardMaterial {
+                    double_sided,
+                    cull_mode,
+                    ..Default::default()
+                };
+                let (flags, _) =
+                    standard_material_uniform_data(&material, TextureFormat::Rgba8Unorm);
+                assert_eq!(
+                    flags.contains(StandardMaterialFlags::DOUBLE_SIDED),
+                    double_sided
+                );
+
+                // the lighting of double sided materials doesn't change which faces are culled
+                let mut primitive = PrimitiveState::default();
+                standard_material_face_culling(material.cull_mode).apply(&mut primitive);
+                assert_eq!(primitive.cull_mode, cull_mode);
+                assert_eq!(primitive.front_face, FrontFace::Ccw);
+            }
+        }
+    }
+
+    #[test]
+    fn only_texture_changes_rebuild_bind_groups() {
+        let textures = [(); 2].map(|_| Handle::<Image>::weak(HandleId::random::<Image>()));
+        let mut material = StandardMaterial {
+            base_color_texture: Some(textures[0].clone_weak()),
+            ..Default::default()
+        };
+        let bound_ids = |material: &StandardMaterial| {
+            let (textures, samplers) = standard_material_bound_handles(material);
+            (
+                textures.map(|texture| texture.as_ref().map(|texture| texture.id)),
+                samplers.map(|sampler| sampler.as_ref().map(|sampler| sampler.id)),
+                material.sampler_override,
+            )
+        };
+        let bound = bound_ids(&material);
+
+        for i in 0..100 {
+            material.perceptual_roughness = i as f32 / 100.0;
+            material.base_color = Color::rgb(i as f32 / 100.0, 0.0, 0.0);
+            assert_eq!(bound_ids(&material), bound);
+        }
+
+        // a different texture has to be bound by a new bind group
+        material.base_color_texture = Some(textures[1].clone_weak());
+        assert_ne!(bound_ids(&material), bound);
+    }
 }
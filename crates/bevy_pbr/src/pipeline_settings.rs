@@ -0,0 +1,268 @@
+use bevy_ecs::{component::Component, query::QueryItem, system::lifetimeless::Read};
+use bevy_render::{
+    render_component::ExtractComponent,
+    render_resource::{
+        CompareFunction, DepthStencilState, Face, FrontFace, PrimitiveState, StencilFaceState,
+        StencilOperation, StencilState,
+    },
+};
+
+/// How a material tests against and writes to the depth buffer.
+///
+/// Bevy uses a reversed depth buffer, in which closer fragments have greater depth values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DepthSetting {
+    /// Writes the depth of the material's fragments to the depth buffer. Materials drawn in the
+    /// transparent pass never write depth.
+    pub write_enabled: bool,
+    /// The comparison against the depth buffer a fragment has to pass to be drawn.
+    pub compare: CompareFunction,
+}
+
+impl DepthSetting {
+    /// Tests against and writes to the depth buffer.
+    pub const DEFAULT: Self = Self {
+        write_enabled: true,
+        compare: CompareFunction::Greater,
+    };
+
+    /// Tests against the depth buffer without writing to it, e.g. for decals.
+    pub const TEST_ONLY: Self = Self {
+        write_enabled: false,
+        compare: CompareFunction::Greater,
+    };
+
+    /// Ignores the depth buffer, e.g. for overlays that are always drawn on top.
+    pub const IGNORE: Self = Self {
+        write_enabled: false,
+        compare: CompareFunction::Always,
+    };
+
+    /// Applies this setting to the `depth_stencil` state of a pipeline.
+    pub fn apply(&self, depth_stencil: &mut DepthStencilState) {
+        depth_stencil.depth_write_enabled &= self.write_enabled;
+        depth_stencil.depth_compare = self.compare;
+    }
+}
+
+impl Default for DepthSetting {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Which faces of a mesh are culled, and which winding makes a face the front face.
+///
+/// Materials define their culling with [`SpecializedMaterial::face_culling`](crate::SpecializedMaterial::face_culling),
+/// which can be overridden for individual entities by adding this as a component.
+///
+/// Culling only decides which faces are rasterized. Lighting both sides of a mesh additionally
+/// requires flipping the normals of back faces in the shader (like
+/// [`StandardMaterial::double_sided`](crate::StandardMaterial::double_sided) does), which respects
+/// the configured `front_face`. The two are independent and can be combined freely.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FaceCulling {
+    pub cull_mode: Option<Face>,
+    pub front_face: FrontFace,
+}
+
+impl FaceCulling {
+    /// Culls back faces, with counter-clockwise front faces.
+    pub const BACK: Self = Self {
+        cull_mode: Some(Face::Back),
+        front_face: FrontFace::Ccw,
+    };
+
+    /// Renders both faces, e.g. for foliage.
+    pub const NONE: Self = Self {
+        cull_mode: None,
+        front_face: FrontFace::Ccw,
+    };
+
+    /// Applies this setting to the `primitive` state of a pipeline.
+    pub fn apply(&self, primitive: &mut PrimitiveState) {
+        primitive.cull_mode = self.cull_mode;
+        primitive.front_face = self.front_face;
+    }
+}
+
+impl Default for FaceCulling {
+    fn default() -> Self {
+        Self::BACK
+    }
+}
+
+impl ExtractComponent for FaceCulling {
+    type Query = Read<FaceCulling>;
+    type Filter = ();
+
+    #[inline]
+    fn extract_component(face_culling: QueryItem<Self::Query>) -> Self {
+        *face_culling
+    }
+}
+
+/// How a material tests against and writes to the stencil buffer. The reference value is set per
+/// entity with a [`StencilReference`] component.
+///
+/// Stencil operations require a view depth format with a stencil aspect, see
+/// [`ViewDepthFormat`](bevy_render::view::ViewDepthFormat).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StencilSetting(pub StencilState);
+
+impl StencilSetting {
+    /// Ignores the stencil buffer.
+    pub const IGNORE: Self = Self(StencilState {
+        front: StencilFaceState::IGNORE,
+        back: StencilFaceState::IGNORE,
+        read_mask: 0,
+        write_mask: 0,
+    });
+
+    /// Writes the [`StencilReference`] of the entity wherever the material is drawn, e.g. to mark
+    /// the area of a portal.
+    pub fn write() -> Self {
+        let face = StencilFaceState {
+            compare: CompareFunction::Always,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op: StencilOperation::Replace,
+        };
+        Self(StencilState {
+            front: face,
+            back: face,
+            read_mask: !0,
+            write_mask: !0,
+        })
+    }
+
+    /// Only draws the material where the stencil buffer passes the `compare` function against the
+    /// [`StencilReference`] of the entity, without changing the stencil buffer.
+    pub fn test(compare: CompareFunction) -> Self {
+        let face = StencilFaceState {
+            compare,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op: StencilOperation::Keep,
+        };
+        Self(StencilState {
+            front: face,
+            back: face,
+            read_mask: !0,
+            write_mask: 0,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_enabled()
+    }
+
+    /// Applies this setting to the `depth_stencil` state of a pipeline.
+    pub fn apply(&self, depth_stencil: &mut DepthStencilState) {
+        depth_stencil.stencil = self.0.clone();
+    }
+}
+
+impl Default for StencilSetting {
+    fn default() -> Self {
+        Self::IGNORE
+    }
+}
+
+/// The reference value stencil operations of an entity's material compare against and write, see
+/// [`StencilSetting`]. Defaults to `0` for entities without this component.
+#[derive(Component, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct StencilReference(pub u8);
+
+impl ExtractComponent for StencilReference {
+    type Query = Read<StencilReference>;
+    type Filter = ();
+
+    #[inline]
+    fn extract_component(reference: QueryItem<Self::Query>) -> Self {
+        *reference
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DepthSetting, FaceCulling, StencilSetting};
+    use bevy_render::render_resource::{
+        CompareFunction, DepthBiasState, DepthStencilState, Face, FrontFace, PrimitiveState,
+        PrimitiveTopology, StencilOperation, StencilState, TextureFormat,
+    };
+
+    fn depth_stencil(depth_write_enabled: bool) -> DepthStencilState {
+        DepthStencilState {
+            format: TextureFormat::Depth32Float,
+            depth_write_enabled,
+            depth_compare: CompareFunction::Greater,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }
+    }
+
+    #[test]
+    fn apply_depth_setting() {
+        let mut state = depth_stencil(true);
+        DepthSetting::default().apply(&mut state);
+        assert_eq!(state, depth_stencil(true));
+
+        DepthSetting::TEST_ONLY.apply(&mut state);
+        assert!(!state.depth_write_enabled);
+        assert_eq!(state.depth_compare, CompareFunction::Greater);
+
+        let mut state = depth_stencil(true);
+        DepthSetting::IGNORE.apply(&mut state);
+        assert!(!state.depth_write_enabled);
+        assert_eq!(state.depth_compare, CompareFunction::Always);
+
+        // the transparent pass doesn't write depth, even if the material asks for it
+        let mut state = depth_stencil(false);
+        DepthSetting::DEFAULT.apply(&mut state);
+        assert!(!state.depth_write_enabled);
+    }
+
+    #[test]
+    fn apply_face_culling() {
+        let mut primitive = PrimitiveState {
+            topology: PrimitiveTopology::LineList,
+            ..Default::default()
+        };
+        FaceCulling {
+            cull_mode: Some(Face::Front),
+            front_face: FrontFace::Cw,
+        }
+        .apply(&mut primitive);
+        assert_eq!(primitive.cull_mode, Some(Face::Front));
+        assert_eq!(primitive.front_face, FrontFace::Cw);
+        assert_eq!(primitive.topology, PrimitiveTopology::LineList);
+
+        FaceCulling::NONE.apply(&mut primitive);
+        assert_eq!(primitive.cull_mode, None);
+        assert_eq!(primitive.front_face, FrontFace::Ccw);
+    }
+
+    #[test]
+    fn stencil_mask_settings() {
+        // a portal frame writes its reference value, and the world behind it is drawn where the
+        // stencil buffer equals the same value
+        let mask = StencilSetting::write();
+        let masked = StencilSetting::test(CompareFunction::Equal);
+        assert!(mask.is_enabled());
+        assert!(masked.is_enabled());
+        assert!(!StencilSetting::default().is_enabled());
+        assert_ne!(mask, masked);
+
+        let mut state = depth_stencil(true);
+        mask.apply(&mut state);
+        assert_eq!(state.stencil.front.compare, CompareFunction::Always);
+        assert_eq!(state.stencil.front.pass_op, StencilOperation::Replace);
+        assert_eq!(state.stencil.write_mask, 0xff_ff_ff_ff);
+
+        masked.apply(&mut state);
+        assert_eq!(state.stencil.back.compare, CompareFunction::Equal);
+        assert_eq!(state.stencil.back.pass_op, StencilOperation::Keep);
+        assert_eq!(state.stencil.write_mask, 0);
+    }
+}
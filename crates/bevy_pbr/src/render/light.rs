@@ -14,6 +14,7 @@ use bevy_math::{const_vec3, Mat4, UVec3, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles};
 use bevy_render::{
     camera::{Camera, CameraProjection},
     color::Color,
+    diagnostic::RenderStatistics,
     mesh::{Mesh, MeshVertexBufferLayout},
     render_asset::RenderAssets,
     render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
@@ -23,7 +24,7 @@ use bevy_render::{
         TrackedRenderPass,
     },
     render_resource::{std140::AsStd140, *},
-    renderer::{RenderContext, RenderDevice, RenderQueue},
+    renderer::{FramesInFlight, RenderContext, RenderDevice, RenderQueue, RenderResourceLimits},
     texture::*,
     view::{
         ExtractedView, ViewUniform, ViewUniformOffset, ViewUniforms, Visibility, VisibleEntities,
@@ -223,12 +224,15 @@ bitflags::bitflags! {
     pub struct ShadowPipelineKey: u32 {
         const NONE               = 0;
         const PRIMITIVE_TOPOLOGY_RESERVED_BITS = ShadowPipelineKey::PRIMITIVE_TOPOLOGY_MASK_BITS << ShadowPipelineKey::PRIMITIVE_TOPOLOGY_SHIFT_BITS;
+        const STRIP_INDEX_FORMAT_RESERVED_BITS = ShadowPipelineKey::STRIP_INDEX_FORMAT_MASK_BITS << ShadowPipelineKey::STRIP_INDEX_FORMAT_SHIFT_BITS;
     }
 }
 
 impl ShadowPipelineKey {
     const PRIMITIVE_TOPOLOGY_MASK_BITS: u32 = 0b111;
     const PRIMITIVE_TOPOLOGY_SHIFT_BITS: u32 = 32 - 3;
+    const STRIP_INDEX_FORMAT_MASK_BITS: u32 = 0b11;
+    const STRIP_INDEX_FORMAT_SHIFT_BITS: u32 = Self::PRIMITIVE_TOPOLOGY_SHIFT_BITS - 2;
 
     pub fn from_primitive_topology(primitive_topology: PrimitiveTopology) -> Self {
         let primitive_topology_bits = ((primitive_topology as u32)
@@ -249,6 +253,25 @@ impl ShadowPipelineKey {
             _ => PrimitiveTopology::default(),
         }
     }
+
+    pub fn from_strip_index_format(strip_index_format: Option<IndexFormat>) -> Self {
+        let strip_index_format_bits = match strip_index_format {
+            None => 0,
+            Some(IndexFormat::Uint16) => 1,
+            Some(IndexFormat::Uint32) => 2,
+        } << Self::STRIP_INDEX_FORMAT_SHIFT_BITS;
+        Self::from_bits(strip_index_format_bits).unwrap()
+    }
+
+    pub fn strip_index_format(&self) -> Option<IndexFormat> {
+        let strip_index_format_bits =
+            (self.bits >> Self::STRIP_INDEX_FORMAT_SHIFT_BITS) & Self::STRIP_INDEX_FORMAT_MASK_BITS;
+        match strip_index_format_bits {
+            1 => Some(IndexFormat::Uint16),
+            2 => Some(IndexFormat::Uint32),
+            _ => None,
+        }
+    }
 }
 
 impl SpecializedMeshPipeline for ShadowPipeline {
@@ -262,12 +285,12 @@ impl SpecializedMeshPipeline for ShadowPipeline {
         let mut vertex_attributes = vec![Mesh::ATTRIBUTE_POSITION.at_shader_location(0)];
 
         let mut bind_group_layout = vec![self.view_layout.clone()];
-        let mut shader_defs = Vec::new();
+        let mut shader_defs = ShaderDefs::new();
 
         if layout.contains(Mesh::ATTRIBUTE_JOINT_INDEX)
             && layout.contains(Mesh::ATTRIBUTE_JOINT_WEIGHT)
         {
-            shader_defs.push(String::from("SKINNED"));
+            shader_defs.push("SKINNED");
             vertex_attributes.push(Mesh::ATTRIBUTE_JOINT_INDEX.at_shader_location(4));
             vertex_attributes.push(Mesh::ATTRIBUTE_JOINT_WEIGHT.at_shader_location(5));
             bind_group_layout.push(self.skinned_mesh_layout.clone());
@@ -281,14 +304,18 @@ impl SpecializedMeshPipeline for ShadowPipeline {
             vertex: VertexState {
                 shader: SHADOW_SHADER_HANDLE.typed::<Shader>(),
                 entry_point: "vertex".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 shader_defs,
                 buffers: vec![vertex_buffer_layout],
+                allow_unused_attributes: false,
             },
             fragment: None,
             layout: Some(bind_group_layout),
+            push_constant_ranges: Vec::new(),
             primitive: PrimitiveState {
                 topology: key.primitive_topology(),
-                strip_index_format: None,
+                strip_index_format: key.strip_index_format(),
                 front_face: FrontFace::Ccw,
                 cull_mode: None,
                 unclipped_depth: false,
@@ -534,12 +561,22 @@ pub struct GlobalLightMeta {
     pub entity_to_index: HashMap<Entity, usize>,
 }
 
-#[derive(Default)]
 pub struct LightMeta {
     pub view_gpu_lights: DynamicUniformVec<GpuLights>,
     pub shadow_view_bind_group: Option<BindGroup>,
 }
 
+impl FromWorld for LightMeta {
+    fn from_world(world: &mut World) -> Self {
+        let limits = world.resource::<RenderResourceLimits>();
+        let frames = world.resource::<FramesInFlight>();
+        Self {
+            view_gpu_lights: DynamicUniformVec::new(limits).with_frames_in_flight(frames),
+            shadow_view_bind_group: None,
+        }
+    }
+}
+
 #[derive(Component)]
 pub enum LightEntity {
     Directional {
@@ -573,6 +610,7 @@ pub fn prepare_lights(
     mut texture_cache: ResMut<TextureCache>,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
+    statistics: Res<RenderStatistics>,
     mut global_light_meta: ResMut<GlobalLightMeta>,
     mut light_meta: ResMut<LightMeta>,
     views: Query<
@@ -645,9 +683,11 @@ pub fn prepare_lights(
     global_light_meta.gpu_point_lights.push(GpuPointLights {
         data: gpu_point_lights,
     });
-    global_light_meta
-        .gpu_point_lights
-        .write_buffer(&render_device, &render_queue);
+    statistics.record_uniform_writes(
+        global_light_meta
+            .gpu_point_lights
+            .write_buffer(&render_device, &render_queue),
+    );
 
     // set up light data for each view
     for (entity, extracted_view, clusters) in views.iter() {
@@ -900,9 +940,11 @@ pub fn prepare_lights(
         ));
     }
 
-    light_meta
-        .view_gpu_lights
-        .write_buffer(&render_device, &render_queue);
+    statistics.record_uniform_writes(
+        light_meta
+            .view_gpu_lights
+            .write_buffer(&render_device, &render_queue),
+    );
 }
 
 // this must match CLUSTER_COUNT_SIZE in pbr.wgsl
@@ -990,6 +1032,7 @@ pub fn prepare_clusters(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
+    statistics: Res<RenderStatistics>,
     global_light_meta: Res<GlobalLightMeta>,
     views: Query<
         (
@@ -1036,12 +1079,16 @@ pub fn prepare_clusters(
             }
         }
 
-        view_clusters_bindings
-            .cluster_light_index_lists
-            .write_buffer(&render_device, &render_queue);
-        view_clusters_bindings
-            .cluster_offsets_and_counts
-            .write_buffer(&render_device, &render_queue);
+        statistics.record_uniform_writes(
+            view_clusters_bindings
+                .cluster_light_index_lists
+                .write_buffer(&render_device, &render_queue),
+        );
+        statistics.record_uniform_writes(
+            view_clusters_bindings
+                .cluster_offsets_and_counts
+                .write_buffer(&render_device, &render_queue),
+        );
 
         commands.get_or_spawn(entity).insert(view_clusters_bindings);
     }
@@ -1105,7 +1152,10 @@ pub fn queue_shadows(
                 if let Ok(mesh_handle) = casting_meshes.get(entity) {
                     if let Some(mesh) = render_meshes.get(mesh_handle) {
                         let key =
-                            ShadowPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                            ShadowPipelineKey::from_primitive_topology(mesh.primitive_topology)
+                                | ShadowPipelineKey::from_strip_index_format(
+                                    mesh.strip_index_format(mesh.primitive_topology),
+                                );
                         let pipeline_id = pipelines.specialize(
                             &mut pipeline_cache,
                             &shadow_pipeline,
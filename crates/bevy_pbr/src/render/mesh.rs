@@ -11,9 +11,11 @@ use bevy_ecs::{
 use bevy_math::{Mat4, Size};
 use bevy_reflect::TypeUuid;
 use bevy_render::{
+    diagnostic::RenderStatistics,
     mesh::{
         skinning::{SkinnedMesh, SkinnedMeshInverseBindposes},
-        GpuBufferInfo, Mesh, MeshVertexBufferLayout,
+        FallbackVertexBuffer, GpuBufferInfo, Mesh, MeshVertexBufferLayout,
+        MissingVertexAttributeReports, FALLBACK_VERTEX_BUFFER_SLOT,
     },
     render_asset::RenderAssets,
     render_component::{ComponentUniforms, DynamicUniformIndex, UniformComponentPlugin},
@@ -21,12 +23,12 @@ use bevy_render::{
     render_resource::{std140::AsStd140, *},
     renderer::{RenderDevice, RenderQueue},
     texture::{BevyDefault, GpuImage, Image, TextureFormatPixelInfo},
-    view::{ComputedVisibility, ViewUniform, ViewUniformOffset, ViewUniforms},
+    view::{ComputedVisibility, ViewDepthFormat, ViewUniform, ViewUniformOffset, ViewUniforms},
     RenderApp, RenderStage,
 };
 use bevy_transform::components::GlobalTransform;
 use smallvec::SmallVec;
-use std::num::NonZeroU64;
+use std::{any::type_name, num::NonZeroU64};
 
 #[derive(Default)]
 pub struct MeshRenderPlugin;
@@ -61,7 +63,7 @@ impl Plugin for MeshRenderPlugin {
         );
         load_internal_asset!(app, SKINNING_HANDLE, "skinning.wgsl", Shader::from_wgsl);
 
-        app.add_plugin(UniformComponentPlugin::<MeshUniform>::default());
+        app.add_plugin(UniformComponentPlugin::<MeshUniform>::default().skip_unchanged());
 
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
@@ -76,7 +78,7 @@ impl Plugin for MeshRenderPlugin {
     }
 }
 
-#[derive(Component, AsStd140, Clone)]
+#[derive(Component, AsStd140, Clone, PartialEq)]
 pub struct MeshUniform {
     pub transform: Mat4,
     pub inverse_transpose_model: Mat4,
@@ -258,6 +260,8 @@ pub struct MeshPipeline {
     pub skinned_mesh_layout: BindGroupLayout,
     // This dummy white texture is to be used in place of optional StandardMaterial textures
     pub dummy_white_gpu_image: GpuImage,
+    pub depth_format: ViewDepthFormat,
+    pub missing_vertex_attribute_reports: MissingVertexAttributeReports,
 }
 
 impl FromWorld for MeshPipeline {
@@ -445,7 +449,9 @@ impl FromWorld for MeshPipeline {
             GpuImage {
                 texture,
                 texture_view,
+                texture_view_dimension: TextureViewDimension::D2,
                 texture_format: image.texture_descriptor.format,
+                texture_usage: image.texture_descriptor.usage,
                 sampler,
                 size: Size::new(
                     image.texture_descriptor.size.width as f32,
@@ -458,6 +464,8 @@ impl FromWorld for MeshPipeline {
             mesh_layout,
             skinned_mesh_layout,
             dummy_white_gpu_image,
+            depth_format: *world.resource::<ViewDepthFormat>(),
+            missing_vertex_attribute_reports: MissingVertexAttributeReports::default(),
         }
     }
 }
@@ -489,6 +497,7 @@ bitflags::bitflags! {
         const TRANSPARENT_MAIN_PASS       = (1 << 0);
         const MSAA_RESERVED_BITS          = MeshPipelineKey::MSAA_MASK_BITS << MeshPipelineKey::MSAA_SHIFT_BITS;
         const PRIMITIVE_TOPOLOGY_RESERVED_BITS = MeshPipelineKey::PRIMITIVE_TOPOLOGY_MASK_BITS << MeshPipelineKey::PRIMITIVE_TOPOLOGY_SHIFT_BITS;
+        const STRIP_INDEX_FORMAT_RESERVED_BITS = MeshPipelineKey::STRIP_INDEX_FORMAT_MASK_BITS << MeshPipelineKey::STRIP_INDEX_FORMAT_SHIFT_BITS;
     }
 }
 
@@ -497,6 +506,8 @@ impl MeshPipelineKey {
     const MSAA_SHIFT_BITS: u32 = 32 - 6;
     const PRIMITIVE_TOPOLOGY_MASK_BITS: u32 = 0b111;
     const PRIMITIVE_TOPOLOGY_SHIFT_BITS: u32 = Self::MSAA_SHIFT_BITS - 3;
+    const STRIP_INDEX_FORMAT_MASK_BITS: u32 = 0b11;
+    const STRIP_INDEX_FORMAT_SHIFT_BITS: u32 = Self::PRIMITIVE_TOPOLOGY_SHIFT_BITS - 2;
 
     pub fn from_msaa_samples(msaa_samples: u32) -> Self {
         let msaa_bits = ((msaa_samples - 1) & Self::MSAA_MASK_BITS) << Self::MSAA_SHIFT_BITS;
@@ -526,15 +537,35 @@ impl MeshPipelineKey {
             _ => PrimitiveTopology::default(),
         }
     }
-}
 
-impl SpecializedMeshPipeline for MeshPipeline {
-    type Key = MeshPipelineKey;
+    pub fn from_strip_index_format(strip_index_format: Option<IndexFormat>) -> Self {
+        let strip_index_format_bits = match strip_index_format {
+            None => 0,
+            Some(IndexFormat::Uint16) => 1,
+            Some(IndexFormat::Uint32) => 2,
+        } << Self::STRIP_INDEX_FORMAT_SHIFT_BITS;
+        Self::from_bits(strip_index_format_bits).unwrap()
+    }
 
-    fn specialize(
-        &self,
-        key: Self::Key,
+    pub fn strip_index_format(&self) -> Option<IndexFormat> {
+        let strip_index_format_bits =
+            (self.bits >> Self::STRIP_INDEX_FORMAT_SHIFT_BITS) & Self::STRIP_INDEX_FORMAT_MASK_BITS;
+        match strip_index_format_bits {
+            1 => Some(IndexFormat::Uint16),
+            2 => Some(IndexFormat::Uint32),
+            _ => None,
+        }
+    }
+}
+
+impl MeshPipeline {
+    /// Specializes the descriptor of a mesh pipeline for `key` and `layout`, without its bind group
+    /// layouts. [`MeshPipeline::specialize`](SpecializedMeshPipeline::specialize) adds them.
+    pub fn specialize_descriptor(
+        key: MeshPipelineKey,
         layout: &MeshVertexBufferLayout,
+        depth_format: ViewDepthFormat,
+        missing_vertex_attribute_reports: &MissingVertexAttributeReports,
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
         let mut vertex_attributes = vec![
             Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
@@ -542,25 +573,24 @@ impl SpecializedMeshPipeline for MeshPipeline {
             Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
         ];
 
-        let mut shader_defs = Vec::new();
+        let mut shader_defs = ShaderDefs::new();
         if layout.contains(Mesh::ATTRIBUTE_TANGENT) {
-            shader_defs.push(String::from("VERTEX_TANGENTS"));
+            shader_defs.push("VERTEX_TANGENTS");
             vertex_attributes.push(Mesh::ATTRIBUTE_TANGENT.at_shader_location(3));
         }
 
-        let mut bind_group_layout = vec![self.view_layout.clone()];
-        if layout.contains(Mesh::ATTRIBUTE_JOINT_INDEX)
-            && layout.contains(Mesh::ATTRIBUTE_JOINT_WEIGHT)
-        {
-            shader_defs.push(String::from("SKINNED"));
+        if is_skinned(layout) {
+            shader_defs.push("SKINNED");
             vertex_attributes.push(Mesh::ATTRIBUTE_JOINT_INDEX.at_shader_location(4));
             vertex_attributes.push(Mesh::ATTRIBUTE_JOINT_WEIGHT.at_shader_location(5));
-            bind_group_layout.push(self.skinned_mesh_layout.clone());
-        } else {
-            bind_group_layout.push(self.mesh_layout.clone());
-        };
+        }
 
-        let vertex_buffer_layout = layout.get_layout(&vertex_attributes)?;
+        // meshes lacking normals or uvs are still drawn, reading zeros for them
+        let vertex_buffer_layouts = layout.get_layout_with_fallback(
+            type_name::<Self>(),
+            &vertex_attributes,
+            missing_vertex_attribute_reports,
+        )?;
 
         let (label, blend, depth_write_enabled);
         if key.contains(MeshPipelineKey::TRANSPARENT_MAIN_PASS) {
@@ -579,26 +609,32 @@ impl SpecializedMeshPipeline for MeshPipeline {
         }
 
         #[cfg(feature = "webgl")]
-        shader_defs.push(String::from("NO_ARRAY_TEXTURES_SUPPORT"));
+        shader_defs.push("NO_ARRAY_TEXTURES_SUPPORT");
 
         Ok(RenderPipelineDescriptor {
             vertex: VertexState {
                 shader: MESH_SHADER_HANDLE.typed::<Shader>(),
                 entry_point: "vertex".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 shader_defs: shader_defs.clone(),
-                buffers: vec![vertex_buffer_layout],
+                buffers: vertex_buffer_layouts.into_buffers(),
+                allow_unused_attributes: false,
             },
             fragment: Some(FragmentState {
                 shader: MESH_SHADER_HANDLE.typed::<Shader>(),
                 shader_defs,
                 entry_point: "fragment".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 targets: vec![ColorTargetState {
                     format: TextureFormat::bevy_default(),
                     blend,
                     write_mask: ColorWrites::ALL,
                 }],
             }),
-            layout: Some(bind_group_layout),
+            layout: None,
+            push_constant_ranges: Vec::new(),
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
                 cull_mode: Some(Face::Back),
@@ -606,10 +642,10 @@ impl SpecializedMeshPipeline for MeshPipeline {
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
                 topology: key.primitive_topology(),
-                strip_index_format: None,
+                strip_index_format: key.strip_index_format(),
             },
             depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
+                format: depth_format.0,
                 depth_write_enabled,
                 depth_compare: CompareFunction::Greater,
                 stencil: StencilState {
@@ -634,6 +670,34 @@ impl SpecializedMeshPipeline for MeshPipeline {
     }
 }
 
+fn is_skinned(layout: &MeshVertexBufferLayout) -> bool {
+    layout.contains(Mesh::ATTRIBUTE_JOINT_INDEX) && layout.contains(Mesh::ATTRIBUTE_JOINT_WEIGHT)
+}
+
+impl SpecializedMeshPipeline for MeshPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = Self::specialize_descriptor(
+            key,
+            layout,
+            self.depth_format,
+            &self.missing_vertex_attribute_reports,
+        )?;
+        let mesh_layout = if is_skinned(layout) {
+            &self.skinned_mesh_layout
+        } else {
+            &self.mesh_layout
+        };
+        descriptor.layout = Some(vec![self.view_layout.clone(), mesh_layout.clone()]);
+        Ok(descriptor)
+    }
+}
+
 pub struct MeshBindGroup {
     pub normal: BindGroup,
     pub skinned: Option<BindGroup>,
@@ -691,6 +755,7 @@ pub struct SkinnedMeshUniform {
 pub fn prepare_skinned_meshes(
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
+    statistics: Res<RenderStatistics>,
     extracted_joints: Res<ExtractedJoints>,
     mut skinned_mesh_uniform: ResMut<SkinnedMeshUniform>,
 ) {
@@ -701,13 +766,16 @@ pub fn prepare_skinned_meshes(
     skinned_mesh_uniform.buffer.clear();
     skinned_mesh_uniform
         .buffer
-        .reserve(extracted_joints.buffer.len(), &render_device);
+        .reserve(extracted_joints.buffer.len(), &render_device)
+        .expect("joint matrices fit into a uniform buffer binding");
     for joint in extracted_joints.buffer.iter() {
         skinned_mesh_uniform.buffer.push(*joint);
     }
-    skinned_mesh_uniform
-        .buffer
-        .write_buffer(&render_device, &render_queue);
+    statistics.record_uniform_writes(
+        skinned_mesh_uniform
+            .buffer
+            .write_buffer(&render_device, &render_queue),
+    );
 }
 
 #[derive(Component)]
@@ -855,17 +923,27 @@ impl<const I: usize> EntityRenderCommand for SetMeshBindGroup<I> {
 
 pub struct DrawMesh;
 impl EntityRenderCommand for DrawMesh {
-    type Param = (SRes<RenderAssets<Mesh>>, SQuery<Read<Handle<Mesh>>>);
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SRes<FallbackVertexBuffer>,
+        SQuery<Read<Handle<Mesh>>>,
+    );
     #[inline]
     fn render<'w>(
         _view: Entity,
         item: Entity,
-        (meshes, mesh_query): SystemParamItem<'w, '_, Self::Param>,
+        (meshes, fallback_vertex_buffer, mesh_query): SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
         let mesh_handle = mesh_query.get(item).unwrap();
         if let Some(gpu_mesh) = meshes.into_inner().get(mesh_handle) {
             pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+            // read by pipelines for the attributes the mesh lacks. The pass skips binding it again
+            // while it is still bound by a previous draw
+            pass.set_vertex_buffer(
+                FALLBACK_VERTEX_BUFFER_SLOT,
+                fallback_vertex_buffer.into_inner().buffer.slice(..),
+            );
             match &gpu_mesh.buffer_info {
                 GpuBufferInfo::Indexed {
                     buffer,
@@ -888,11 +966,74 @@ impl EntityRenderCommand for DrawMesh {
 
 #[cfg(test)]
 mod tests {
-    use super::MeshPipelineKey;
+    use super::{MeshPipeline, MeshPipelineKey};
+    use bevy_render::{
+        mesh::{shape, Mesh, MissingVertexAttributeReports},
+        render_resource::{IndexFormat, PrimitiveTopology},
+        view::{Msaa, ViewDepthFormat},
+    };
+
     #[test]
     fn mesh_key_msaa_samples() {
         for i in 1..=64 {
             assert_eq!(MeshPipelineKey::from_msaa_samples(i).msaa_samples(), i);
         }
     }
+
+    #[test]
+    fn mesh_key_msaa_samples_change_specialization() {
+        let key = |samples| {
+            MeshPipelineKey::from_msaa_samples(samples)
+                | MeshPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleStrip)
+                | MeshPipelineKey::TRANSPARENT_MAIN_PASS
+        };
+        // changing the `Msaa` resource must produce a different key, so that new pipelines are
+        // specialized with the new sample count
+        assert_ne!(key(1), key(4));
+        assert_eq!(key(4).msaa_samples(), 4);
+        assert_eq!(
+            key(4).primitive_topology(),
+            PrimitiveTopology::TriangleStrip
+        );
+        assert!(key(4).contains(MeshPipelineKey::TRANSPARENT_MAIN_PASS));
+    }
+
+    #[test]
+    fn msaa_change_specializes_new_pipelines() {
+        let mesh = Mesh::from(shape::Cube::default());
+        let layout = mesh.get_mesh_vertex_buffer_layout();
+        let reports = MissingVertexAttributeReports::default();
+
+        // the key built by `queue_material_meshes` for an existing mesh
+        let key = |msaa: &Msaa| {
+            MeshPipelineKey::from_msaa_samples(msaa.samples)
+                | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology())
+        };
+        let sample_count = |key| {
+            MeshPipeline::specialize_descriptor(key, &layout, ViewDepthFormat::default(), &reports)
+                .unwrap()
+                .multisample
+                .count
+        };
+        let mut msaa = Msaa { samples: 1 };
+        let single_sampled = key(&msaa);
+        msaa.samples = 4;
+        let multisampled = key(&msaa);
+        // pipelines are cached per key, so a new key specializes a new pipeline
+        assert_ne!(multisampled, single_sampled);
+        assert_eq!(sample_count(single_sampled), 1);
+        assert_eq!(sample_count(multisampled), 4);
+    }
+
+    #[test]
+    fn mesh_key_strip_index_format() {
+        for strip_index_format in [None, Some(IndexFormat::Uint16), Some(IndexFormat::Uint32)] {
+            let key = MeshPipelineKey::from_msaa_samples(4)
+                | MeshPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleStrip)
+                | MeshPipelineKey::from_strip_index_format(strip_index_format);
+            assert_eq!(key.strip_index_format(), strip_index_format);
+            assert_eq!(key.primitive_topology(), PrimitiveTopology::TriangleStrip);
+            assert_eq!(key.msaa_samples(), 4);
+        }
+    }
 }
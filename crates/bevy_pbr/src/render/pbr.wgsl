@@ -576,7 +576,9 @@ fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
             view.inverse_view[2].z,
             view.inverse_view[3].z
         ), in.world_position);
-        let cluster_index = fragment_cluster_index(in.frag_coord.xy, view_z, is_orthographic);
+        // clusters are relative to the camera viewport
+        let viewport_coord = in.frag_coord.xy - view.viewport_origin;
+        let cluster_index = fragment_cluster_index(viewport_coord, view_z, is_orthographic);
         let offset_and_count = unpack_offset_and_count(cluster_index);
         for (var i: u32 = offset_and_count.offset; i < offset_and_count.offset + offset_and_count.count; i = i + 1u) {
             let light_id = get_light_id(i);
@@ -653,5 +655,10 @@ fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
         // output_color.rgb = pow(output_color.rgb, vec3(1.0 / 2.2));
     }
 
+#ifdef WIREFRAME
+    // draw the edges of wireframe materials in their unlit base color
+    output_color = vec4<f32>(material.base_color.rgb, 1.0);
+#endif
+
     return output_color;
 }
@@ -11,12 +11,13 @@ use bevy_render::{
     render_phase::{AddRenderCommand, DrawFunctions, RenderPhase, SetItemPipeline},
     render_resource::{
         PipelineCache, PolygonMode, RenderPipelineDescriptor, Shader, SpecializedMeshPipeline,
-        SpecializedMeshPipelineError, SpecializedMeshPipelines,
+        SpecializedMeshPipelineError, SpecializedMeshPipelines, WgpuFeatures,
     },
+    renderer::RenderDevice,
     view::{ExtractedView, Msaa},
     RenderApp, RenderStage,
 };
-use bevy_utils::tracing::error;
+use bevy_utils::tracing::{error, warn};
 
 pub const WIREFRAME_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 192598014480025766);
@@ -68,6 +69,22 @@ pub struct Wireframe;
 pub struct WireframeConfig {
     /// Whether to show wireframes for all meshes. If `false`, only meshes with a [Wireframe] component will be rendered.
     pub global: bool,
+    /// Whether to also render the materials of wireframe meshes with [`PolygonMode::Line`]. Their
+    /// shaders get the `WIREFRAME` shader def, e.g. to output a flat color.
+    pub materials: bool,
+}
+
+/// Returns `true` if the `render_device` supports [`PolygonMode::Line`]. Otherwise, logs a
+/// warning the first time it is called with `warned` set to `false`.
+pub(crate) fn polygon_mode_line_supported(render_device: &RenderDevice, warned: &mut bool) -> bool {
+    let supported = render_device
+        .features()
+        .contains(WgpuFeatures::POLYGON_MODE_LINE);
+    if !supported && !*warned {
+        warn!("Wireframes are not supported by the current backend, as it lacks WgpuFeatures::POLYGON_MODE_LINE. Falling back to filled rendering.");
+        *warned = true;
+    }
+    supported
 }
 
 pub struct WireframePipeline {
@@ -93,6 +110,8 @@ impl SpecializedMeshPipeline for WireframePipeline {
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
         let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
         descriptor.vertex.shader = self.shader.clone_weak();
+        // the wireframe shader only reads the vertex positions
+        descriptor.vertex.allow_unused_attributes = true;
         descriptor.fragment.as_mut().unwrap().shader = self.shader.clone_weak();
         descriptor.primitive.polygon_mode = PolygonMode::Line;
         descriptor.depth_stencil.as_mut().unwrap().bias.slope_scale = 1.0;
@@ -110,12 +129,17 @@ fn queue_wireframes(
     mut pipelines: ResMut<SpecializedMeshPipelines<WireframePipeline>>,
     mut pipeline_cache: ResMut<PipelineCache>,
     msaa: Res<Msaa>,
+    render_device: Res<RenderDevice>,
+    mut warned_unsupported: Local<bool>,
     mut material_meshes: ParamSet<(
         Query<(Entity, &Handle<Mesh>, &MeshUniform)>,
         Query<(Entity, &Handle<Mesh>, &MeshUniform), With<Wireframe>>,
     )>,
     mut views: Query<(&ExtractedView, &mut RenderPhase<Opaque3d>)>,
 ) {
+    if !polygon_mode_line_supported(&render_device, &mut warned_unsupported) {
+        return;
+    }
     let draw_custom = opaque_3d_draw_functions
         .read()
         .get_id::<DrawWireframes>()
@@ -129,7 +153,10 @@ fn queue_wireframes(
             |(entity, mesh_handle, mesh_uniform): (Entity, &Handle<Mesh>, &MeshUniform)| {
                 if let Some(mesh) = render_meshes.get(mesh_handle) {
                     let key = msaa_key
-                        | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                        | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology)
+                        | MeshPipelineKey::from_strip_index_format(
+                            mesh.strip_index_format(mesh.primitive_topology),
+                        );
                     let pipeline_id = pipelines.specialize(
                         &mut pipeline_cache,
                         &wireframe_pipeline,
@@ -146,6 +173,8 @@ fn queue_wireframes(
                     transparent_phase.add(Opaque3d {
                         entity,
                         pipeline: pipeline_id,
+                        material_bind_group: None,
+                        mesh: Some(mesh_handle.id),
                         draw_function: draw_custom,
                         distance: view_row_2.dot(mesh_uniform.transform.col(3)),
                     });
@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, ops::Range};
 
 use crate::{
     camera::CameraProjection,
@@ -16,14 +16,14 @@ use bevy_ecs::{
     entity::Entity,
     event::EventReader,
     prelude::With,
-    query::Added,
+    query::{Added, ChangeTrackers},
     reflect::ReflectComponent,
     system::{Commands, ParamSet, Query, Res, ResMut},
 };
 use bevy_math::{Mat4, UVec2, Vec2, Vec3};
 use bevy_reflect::{Reflect, ReflectDeserialize};
 use bevy_transform::components::GlobalTransform;
-use bevy_utils::HashSet;
+use bevy_utils::{tracing::warn, HashSet};
 use bevy_window::{WindowCreated, WindowId, WindowResized, Windows};
 use serde::{Deserialize, Serialize};
 use wgpu::Extent3d;
@@ -38,6 +38,91 @@ pub struct Camera {
     pub depth_calculation: DepthCalculation,
     pub near: f32,
     pub far: f32,
+    /// The region of the [`RenderTarget`] this camera renders to. Renders to the whole target if
+    /// `None`.
+    #[reflect(ignore)]
+    pub viewport: Option<Viewport>,
+    /// Discards all fragments outside of this region of the [`RenderTarget`].
+    #[reflect(ignore)]
+    pub scissor_rect: Option<ScissorRect>,
+}
+
+/// A region of a [`RenderTarget`] a [`Camera`] renders to, in physical pixels.
+///
+/// Viewports are dynamic state of render passes, so changing them doesn't require any pipeline to
+/// be specialized again. This allows e.g. animating the layout of a split-screen game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    /// The top left corner of the viewport.
+    pub physical_position: UVec2,
+    pub physical_size: UVec2,
+    /// The range of the depth buffer the viewport maps to, within `0.0..=1.0`.
+    pub depth: Range<f32>,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            physical_position: Default::default(),
+            physical_size: Default::default(),
+            depth: 0.0..1.0,
+        }
+    }
+}
+
+impl Viewport {
+    /// Clamps the viewport to a render target of `target_size`, so that it can be set on a render
+    /// pass without causing validation errors. Returns `None` if no part of the viewport lies
+    /// within the target.
+    pub fn clamp_to(&self, target_size: UVec2) -> Option<Viewport> {
+        let (physical_position, physical_size) =
+            clamp_rect(self.physical_position, self.physical_size, target_size)?;
+        let min_depth = self.depth.start.clamp(0.0, 1.0);
+        let max_depth = self.depth.end.clamp(min_depth, 1.0);
+        Some(Viewport {
+            physical_position,
+            physical_size,
+            depth: min_depth..max_depth,
+        })
+    }
+}
+
+/// A scissor region of a [`RenderTarget`], in physical pixels.
+///
+/// Like [`Viewport`]s, scissor rects are dynamic state of render passes and can be changed
+/// without specializing pipelines again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScissorRect {
+    /// The top left corner of the scissor rect.
+    pub physical_position: UVec2,
+    pub physical_size: UVec2,
+}
+
+impl ScissorRect {
+    /// Clamps the scissor rect to a render target of `target_size`. Returns `None` if no part of
+    /// the scissor rect lies within the target.
+    pub fn clamp_to(&self, target_size: UVec2) -> Option<ScissorRect> {
+        let (physical_position, physical_size) =
+            clamp_rect(self.physical_position, self.physical_size, target_size)?;
+        Some(ScissorRect {
+            physical_position,
+            physical_size,
+        })
+    }
+}
+
+fn clamp_rect(position: UVec2, size: UVec2, target_size: UVec2) -> Option<(UVec2, UVec2)> {
+    let min = position.min(target_size);
+    let max = UVec2::new(
+        position.x.saturating_add(size.x),
+        position.y.saturating_add(size.y),
+    )
+    .min(target_size);
+    let size = max - min;
+    if size.x == 0 || size.y == 0 {
+        return None;
+    }
+    Some((min, size))
 }
 
 #[derive(Debug, Clone, Reflect, PartialEq, Eq, Hash)]
@@ -128,7 +213,7 @@ impl Camera {
         camera_transform: &GlobalTransform,
         world_position: Vec3,
     ) -> Option<Vec2> {
-        let window_size = self.target.get_logical_size(windows, images)?;
+        let (viewport_position, viewport_size) = self.logical_viewport_rect(windows, images)?;
         // Build a transform to convert from world to NDC using camera data
         let world_to_ndc: Mat4 =
             self.projection_matrix * camera_transform.compute_matrix().inverse();
@@ -138,13 +223,34 @@ impl Camera {
             return None;
         }
         // Once in NDC space, we can discard the z element and rescale x/y to fit the screen
-        let screen_space_coords = (ndc_space_coords.truncate() + Vec2::ONE) / 2.0 * window_size;
+        let screen_space_coords =
+            (ndc_space_coords.truncate() + Vec2::ONE) / 2.0 * viewport_size + viewport_position;
         if !screen_space_coords.is_nan() {
             Some(screen_space_coords)
         } else {
             None
         }
     }
+
+    /// Returns the position and size of the region of the [`RenderTarget`] this camera renders to,
+    /// in logical pixels.
+    pub fn logical_viewport_rect(
+        &self,
+        windows: &Windows,
+        images: &Assets<Image>,
+    ) -> Option<(Vec2, Vec2)> {
+        let logical_size = self.target.get_logical_size(windows, images)?;
+        let viewport = match &self.viewport {
+            Some(viewport) => viewport,
+            None => return Some((Vec2::ZERO, logical_size)),
+        };
+        let physical_size = self.target.get_physical_size(windows, images)?;
+        let scale = logical_size / physical_size.max(UVec2::ONE).as_vec2();
+        Some((
+            viewport.physical_position.as_vec2() * scale,
+            viewport.physical_size.as_vec2() * scale,
+        ))
+    }
 }
 
 #[allow(clippy::type_complexity)]
@@ -201,8 +307,9 @@ pub fn camera_system<T: CameraProjection + Component>(
             .is_changed(&changed_window_ids, &changed_image_handles)
             || added_cameras.contains(&entity)
             || camera_projection.is_changed()
+            || camera.is_changed()
         {
-            if let Some(size) = camera.target.get_logical_size(&windows, &images) {
+            if let Some((_, size)) = camera.logical_viewport_rect(&windows, &images) {
                 camera_projection.update(size.x, size.y);
                 camera.projection_matrix = camera_projection.get_projection_matrix();
                 camera.depth_calculation = camera_projection.depth_calculation();
@@ -284,6 +391,39 @@ pub fn set_active_camera<T: Component>(
 pub struct ExtractedCamera {
     pub target: RenderTarget,
     pub physical_size: Option<UVec2>,
+    /// The [`Camera::viewport`], clamped to the size of the target.
+    pub viewport: Option<Viewport>,
+    /// The [`Camera::scissor_rect`], clamped to the size of the target.
+    pub scissor_rect: Option<ScissorRect>,
+}
+
+impl ExtractedCamera {
+    /// The viewport [`TrackedRenderPass::set_camera_viewport`] sets for the camera. This is the
+    /// whole target if the camera has no viewport, so that cameras sharing a render pass don't
+    /// inherit the viewport of the previous camera. Returns `None` if the target size is unknown.
+    ///
+    /// [`TrackedRenderPass::set_camera_viewport`]: crate::render_phase::TrackedRenderPass::set_camera_viewport
+    pub fn pass_viewport(&self) -> Option<Viewport> {
+        self.viewport.clone().or_else(|| {
+            self.physical_size.map(|physical_size| Viewport {
+                physical_size,
+                ..Default::default()
+            })
+        })
+    }
+
+    /// The scissor rect [`TrackedRenderPass::set_camera_viewport`] sets for the camera. Like
+    /// [`ExtractedCamera::pass_viewport`], this is the whole target if the camera has none.
+    ///
+    /// [`TrackedRenderPass::set_camera_viewport`]: crate::render_phase::TrackedRenderPass::set_camera_viewport
+    pub fn pass_scissor_rect(&self) -> Option<ScissorRect> {
+        self.scissor_rect.or_else(|| {
+            self.physical_size.map(|physical_size| ScissorRect {
+                physical_position: UVec2::ZERO,
+                physical_size,
+            })
+        })
+    }
 }
 
 pub fn extract_cameras<M: Component + Default>(
@@ -291,30 +431,216 @@ pub fn extract_cameras<M: Component + Default>(
     windows: Res<Windows>,
     images: Res<Assets<Image>>,
     active_camera: Res<ActiveCamera<M>>,
-    query: Query<(&Camera, &GlobalTransform, &VisibleEntities), With<M>>,
+    query: Query<
+        (
+            &Camera,
+            ChangeTrackers<Camera>,
+            &GlobalTransform,
+            &VisibleEntities,
+        ),
+        With<M>,
+    >,
 ) {
     if let Some(entity) = active_camera.get() {
-        if let Ok((camera, transform, visible_entities)) = query.get(entity) {
+        if let Ok((camera, camera_changes, transform, visible_entities)) = query.get(entity) {
             if let Some(size) = camera.target.get_physical_size(&windows, &images) {
-                commands.get_or_spawn(entity).insert_bundle((
-                    ExtractedCamera {
-                        target: camera.target.clone(),
-                        physical_size: camera.target.get_physical_size(&windows, &images),
-                    },
-                    ExtractedView {
-                        projection: camera.projection_matrix,
-                        transform: *transform,
-                        width: size.x.max(1),
-                        height: size.y.max(1),
-                        near: camera.near,
-                        far: camera.far,
-                    },
-                    visible_entities.clone(),
-                    M::default(),
+                // only warn when the camera changed, to not flood the log every frame
+                let warn = camera_changes.is_changed();
+                let rects = clamp_camera_rect(
+                    camera.viewport.as_ref(),
+                    Viewport::clamp_to,
+                    "viewport",
+                    entity,
+                    size,
+                    warn,
+                )
+                .zip(clamp_camera_rect(
+                    camera.scissor_rect.as_ref(),
+                    ScissorRect::clamp_to,
+                    "scissor rect",
+                    entity,
+                    size,
+                    warn,
                 ));
+                if let Some((viewport, scissor_rect)) = rects {
+                    let view_size = viewport
+                        .as_ref()
+                        .map_or(size, |viewport| viewport.physical_size);
+
+                    commands.get_or_spawn(entity).insert_bundle((
+                        ExtractedCamera {
+                            target: camera.target.clone(),
+                            physical_size: Some(size),
+                            viewport,
+                            scissor_rect,
+                        },
+                        ExtractedView {
+                            projection: camera.projection_matrix,
+                            transform: *transform,
+                            width: view_size.x.max(1),
+                            height: view_size.y.max(1),
+                            near: camera.near,
+                            far: camera.far,
+                        },
+                        visible_entities.clone(),
+                        M::default(),
+                    ));
+                }
             }
         }
     }
 
     commands.insert_resource(active_camera.clone())
 }
+
+/// Clamps the viewport or scissor `rect` of a camera to its render target of `target_size`,
+/// warning about clamped rects if `warn` is set. Returns `None` if the camera can't be rendered
+/// because the rect is empty or outside of the target.
+fn clamp_camera_rect<T: PartialEq + std::fmt::Debug>(
+    rect: Option<&T>,
+    clamp_to: impl FnOnce(&T, UVec2) -> Option<T>,
+    name: &str,
+    entity: Entity,
+    target_size: UVec2,
+    warn: bool,
+) -> Option<Option<T>> {
+    let rect = match rect {
+        Some(rect) => rect,
+        None => return Some(None),
+    };
+    match clamp_to(rect, target_size) {
+        Some(clamped) => {
+            if warn && clamped != *rect {
+                warn!(
+                    "The {} {:?} of camera {:?} exceeds its render target of size {}, clamping it to {:?}.",
+                    name, rect, entity, target_size, clamped
+                );
+            }
+            Some(Some(clamped))
+        }
+        None => {
+            if warn {
+                warn!(
+                    "The {} {:?} of camera {:?} is empty or outside of its render target of size {}, skipping the camera.",
+                    name, rect, entity, target_size
+                );
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExtractedCamera, RenderTarget, ScissorRect, Viewport};
+    use bevy_math::UVec2;
+    use bevy_window::WindowId;
+
+    #[test]
+    fn clamp_viewport_to_target() {
+        let target_size = UVec2::new(1280, 720);
+        let left = Viewport {
+            physical_position: UVec2::ZERO,
+            physical_size: UVec2::new(640, 720),
+            ..Default::default()
+        };
+        assert_eq!(left.clamp_to(target_size), Some(left.clone()));
+
+        let overflowing = Viewport {
+            physical_position: UVec2::new(640, 360),
+            physical_size: UVec2::new(1280, 720),
+            depth: -1.0..2.0,
+        };
+        assert_eq!(
+            overflowing.clamp_to(target_size),
+            Some(Viewport {
+                physical_position: UVec2::new(640, 360),
+                physical_size: UVec2::new(640, 360),
+                depth: 0.0..1.0,
+            })
+        );
+
+        let outside = Viewport {
+            physical_position: UVec2::new(1280, 0),
+            physical_size: UVec2::new(100, 100),
+            ..Default::default()
+        };
+        assert_eq!(outside.clamp_to(target_size), None);
+        assert_eq!(Viewport::default().clamp_to(target_size), None);
+    }
+
+    #[test]
+    fn clamp_scissor_rect_to_target() {
+        let target_size = UVec2::new(800, 600);
+        let scissor_rect = ScissorRect {
+            physical_position: UVec2::new(700, 10),
+            physical_size: UVec2::new(u32::MAX, 20),
+        };
+        assert_eq!(
+            scissor_rect.clamp_to(target_size),
+            Some(ScissorRect {
+                physical_position: UVec2::new(700, 10),
+                physical_size: UVec2::new(100, 20),
+            })
+        );
+        assert_eq!(
+            ScissorRect {
+                physical_position: UVec2::new(10, 10),
+                physical_size: UVec2::new(0, 20),
+            }
+            .clamp_to(target_size),
+            None
+        );
+    }
+
+    #[test]
+    fn cameras_share_pass_with_their_own_viewports() {
+        let camera = |position, size, scissor_rect| ExtractedCamera {
+            target: RenderTarget::Window(WindowId::primary()),
+            physical_size: Some(UVec2::new(4, 2)),
+            viewport: Some(Viewport {
+                physical_position: position,
+                physical_size: size,
+                ..Default::default()
+            }),
+            scissor_rect,
+        };
+        // a split screen, where the right camera only renders the top row of its half
+        let right_scissor_rect = ScissorRect {
+            physical_position: UVec2::new(2, 0),
+            physical_size: UVec2::new(2, 1),
+        };
+        let right = camera(UVec2::new(2, 0), UVec2::new(2, 2), Some(right_scissor_rect));
+        let left = camera(UVec2::ZERO, UVec2::new(2, 2), None);
+
+        // the state set by `TrackedRenderPass::set_camera_viewport` for each camera
+        let commands =
+            [&right, &left].map(|camera| (camera.pass_viewport(), camera.pass_scissor_rect()));
+        assert_eq!(
+            commands,
+            [
+                (right.viewport.clone(), Some(right_scissor_rect)),
+                // the scissor rect of the right camera doesn't apply to the left one
+                (
+                    left.viewport.clone(),
+                    Some(ScissorRect {
+                        physical_position: UVec2::ZERO,
+                        physical_size: UVec2::new(4, 2),
+                    })
+                ),
+            ]
+        );
+
+        let full_screen = ExtractedCamera {
+            viewport: None,
+            ..camera(UVec2::ZERO, UVec2::ZERO, None)
+        };
+        assert_eq!(
+            full_screen.pass_viewport(),
+            Some(Viewport {
+                physical_size: UVec2::new(4, 2),
+                ..Default::default()
+            })
+        );
+    }
+}
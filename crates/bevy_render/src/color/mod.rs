@@ -2,7 +2,11 @@ mod colorspace;
 
 pub use colorspace::*;
 
-use crate::color::{HslRepresentation, SrgbColorSpace};
+use crate::render_resource::{
+    editor::EditableValue,
+    std140::{self, AsStd140},
+    std430::{self, AsStd430},
+};
 use bevy_math::{Vec3, Vec4};
 use bevy_reflect::{FromReflect, Reflect, ReflectDeserialize};
 use serde::{Deserialize, Serialize};
@@ -766,6 +770,51 @@ impl From<Vec4> for Color {
     }
 }
 
+/// Colors are written to uniform buffers as a linear RGBA `vec4<f32>`, because shaders operate
+/// on linear colors. sRGB colors are converted. To upload the sRGB components as they are, use
+/// a `Vec4` created from [`Color::as_rgba_f32`] instead.
+impl AsStd140 for Color {
+    type Output = std140::Vec4;
+
+    fn as_std140(&self) -> Self::Output {
+        let [x, y, z, w] = self.as_linear_rgba_f32();
+        std140::Vec4 { x, y, z, w }
+    }
+
+    fn from_std140(val: Self::Output) -> Self {
+        Color::rgba_linear(val.x, val.y, val.z, val.w)
+    }
+}
+
+/// Colors are written to storage buffers as a linear RGBA `vec4<f32>`, like [`AsStd140`].
+impl AsStd430 for Color {
+    type Output = std430::Vec4;
+
+    fn as_std430(&self) -> Self::Output {
+        let [x, y, z, w] = self.as_linear_rgba_f32();
+        std430::Vec4 { x, y, z, w }
+    }
+
+    fn from_std430(val: Self::Output) -> Self {
+        Color::rgba_linear(val.x, val.y, val.z, val.w)
+    }
+}
+
+/// Colors are edited as their linear RGBA components, like they are written by [`AsStd140`].
+impl EditableValue for Color {
+    const COMPONENTS: usize = 4;
+
+    fn set_components(&mut self, components: &[f32]) -> bool {
+        match *components {
+            [r, g, b, a] => {
+                *self = Color::rgba_linear(r, g, b, a);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
 impl From<Color> for wgpu::Color {
     fn from(color: Color) -> Self {
         if let Color::RgbaLinear {
@@ -1207,6 +1256,33 @@ fn decode_rgba(data: &[u8]) -> Result<Color, HexColorError> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn uniform_colors_are_linear() {
+        #[derive(AsStd140)]
+        struct ColorUniform {
+            color: Color,
+            intensity: f32,
+        }
+
+        let uniform = ColorUniform {
+            color: Color::rgba(0.5, 1.0, 0.0, 0.5),
+            intensity: 2.0,
+        }
+        .as_std140();
+        let floats: &[f32] = bevy_core::cast_slice(std140::Std140::as_bytes(&uniform));
+        assert!((floats[0] - 0.214_041_14).abs() < 1e-6);
+        assert_eq!(floats[1..], [1.0, 0.0, 0.5, 2.0, 0.0, 0.0, 0.0]);
+
+        // linear colors are written as they are
+        let linear = Color::rgba_linear(0.25, 0.5, 0.75, 1.0);
+        let std430 = linear.as_std430();
+        assert_eq!(
+            [std430.x, std430.y, std430.z, std430.w],
+            [0.25, 0.5, 0.75, 1.0]
+        );
+        assert_eq!(Color::from_std140(linear.as_std140()), linear);
+    }
+
     #[test]
     fn hex_color() {
         assert_eq!(Color::hex("FFF").unwrap(), Color::rgb(1.0, 1.0, 1.0));
@@ -0,0 +1,5 @@
+mod render_diagnostics_plugin;
+mod render_statistics;
+
+pub use render_diagnostics_plugin::RenderDiagnosticsPlugin;
+pub use render_statistics::*;
@@ -0,0 +1,91 @@
+use crate::{diagnostic::RenderStatistics, RenderApp};
+use bevy_app::{App, Plugin};
+use bevy_diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy_ecs::system::{Res, ResMut};
+
+/// Adds diagnostics of the uploads and resources created by the renderer to an App, e.g. to find
+/// out whether static scenes upload their uniforms every frame.
+///
+/// The measurements of a frame are taken in the next frame, since the render world is updated
+/// after the app world.
+#[derive(Default)]
+pub struct RenderDiagnosticsPlugin;
+
+impl Plugin for RenderDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let statistics = match app.get_sub_app(RenderApp) {
+            Ok(render_app) => render_app.world.resource::<RenderStatistics>().clone(),
+            Err(_) => return,
+        };
+        app.insert_resource(statistics)
+            .add_startup_system(Self::setup_system)
+            .add_system(Self::diagnostic_system);
+    }
+}
+
+impl RenderDiagnosticsPlugin {
+    pub const UNIFORM_BYTES_WRITTEN: DiagnosticId =
+        DiagnosticId::from_u128(302656256897523044181365188321280191689);
+    pub const DIRTY_UNIFORMS: DiagnosticId =
+        DiagnosticId::from_u128(45871729092954198607687417468348037096);
+    pub const UNIFORM_BUFFER_WRITES: DiagnosticId =
+        DiagnosticId::from_u128(118180620432118684479234482506625077899);
+    pub const ASSET_UNIFORMS: DiagnosticId =
+        DiagnosticId::from_u128(99712614081808290127192184846276740456);
+    pub const ASSET_UNIFORM_ENTITIES: DiagnosticId =
+        DiagnosticId::from_u128(217719916617923956176400675076873895865);
+    pub const UNIFORM_SLOTS_ALLOCATED: DiagnosticId =
+        DiagnosticId::from_u128(327452892229512358955971277798251839033);
+    pub const UNIFORM_SLOTS_RELEASED: DiagnosticId =
+        DiagnosticId::from_u128(283899989627089962332918255009928799945);
+    pub const BIND_GROUPS_CREATED: DiagnosticId =
+        DiagnosticId::from_u128(221094339522918186447225367264281807606);
+    pub const BIND_GROUPS_DESTROYED: DiagnosticId =
+        DiagnosticId::from_u128(14917626392414146746270898509500807230);
+    pub const PIPELINES_SPECIALIZED: DiagnosticId =
+        DiagnosticId::from_u128(278614617186961168356714016218852212428);
+    pub const INSTANCE_BUFFER_BYTES: DiagnosticId =
+        DiagnosticId::from_u128(169750406838024765208375636130205719012);
+
+    pub fn setup_system(mut diagnostics: ResMut<Diagnostics>) {
+        let names = [
+            (Self::UNIFORM_BYTES_WRITTEN, "uniform_bytes_written"),
+            (Self::DIRTY_UNIFORMS, "dirty_uniforms"),
+            (Self::UNIFORM_BUFFER_WRITES, "uniform_buffer_writes"),
+            (Self::ASSET_UNIFORMS, "asset_uniforms"),
+            (Self::ASSET_UNIFORM_ENTITIES, "asset_uniform_entities"),
+            (Self::UNIFORM_SLOTS_ALLOCATED, "uniform_slots_allocated"),
+            (Self::UNIFORM_SLOTS_RELEASED, "uniform_slots_released"),
+            (Self::BIND_GROUPS_CREATED, "bind_groups_created"),
+            (Self::BIND_GROUPS_DESTROYED, "bind_groups_destroyed"),
+            (Self::PIPELINES_SPECIALIZED, "pipelines_specialized"),
+            (Self::INSTANCE_BUFFER_BYTES, "instance_buffer_bytes"),
+        ];
+        for (id, name) in names {
+            diagnostics.add(Diagnostic::new(id, name, 20));
+        }
+    }
+
+    pub fn diagnostic_system(
+        mut diagnostics: ResMut<Diagnostics>,
+        statistics: Res<RenderStatistics>,
+    ) {
+        let frame = statistics.take();
+        let measurements = [
+            (Self::UNIFORM_BYTES_WRITTEN, frame.uniform_bytes_written),
+            (Self::DIRTY_UNIFORMS, frame.dirty_uniforms),
+            (Self::UNIFORM_BUFFER_WRITES, frame.uniform_buffer_writes),
+            (Self::ASSET_UNIFORMS, frame.asset_uniforms),
+            (Self::ASSET_UNIFORM_ENTITIES, frame.asset_uniform_entities),
+            (Self::UNIFORM_SLOTS_ALLOCATED, frame.uniform_slots_allocated),
+            (Self::UNIFORM_SLOTS_RELEASED, frame.uniform_slots_released),
+            (Self::BIND_GROUPS_CREATED, frame.bind_groups_created),
+            (Self::BIND_GROUPS_DESTROYED, frame.bind_groups_destroyed),
+            (Self::PIPELINES_SPECIALIZED, frame.pipelines_specialized),
+            (Self::INSTANCE_BUFFER_BYTES, frame.instance_buffer_bytes),
+        ];
+        for (id, value) in measurements {
+            diagnostics.add_measurement(id, value as f64);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::render_resource::UniformWrites;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// The work done by the renderer since the statistics were last taken, see
+/// [`RenderStatistics::take`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderFrameStatistics {
+    /// The number of bytes written to uniform buffers.
+    pub uniform_bytes_written: u64,
+    /// The number of uniform values that changed and were uploaded, e.g. of dirty entities.
+    pub dirty_uniforms: u64,
+    /// The number of writes to uniform buffers, each of which copies a range of a buffer.
+    pub uniform_buffer_writes: u64,
+    /// The number of uniforms of assets like materials, each of which is shared by all entities
+    /// using the asset.
+    pub asset_uniforms: u64,
+    /// The number of entities binding the uniform of an asset instead of a uniform of their own.
+    pub asset_uniform_entities: u64,
+    /// The number of uniform buffer slots handed out to entities, e.g. to newly spawned ones.
+    pub uniform_slots_allocated: u64,
+    /// The number of uniform buffer slots released by entities that despawned or lost their
+    /// component.
+    pub uniform_slots_released: u64,
+    /// The number of bind groups created for the uniforms of components and by the
+    /// [`BindGroupCache`](crate::render_resource::BindGroupCache).
+    pub bind_groups_created: u64,
+    /// The number of bind groups freed after their buffers were reallocated or they weren't
+    /// used for a while.
+    pub bind_groups_destroyed: u64,
+    /// The number of pipelines that were created, e.g. for newly specialized materials.
+    pub pipelines_specialized: u64,
+    /// The size of the buffers handed out by the [`BufferPool`](crate::render_resource::BufferPool)
+    /// for per-frame data like instance buffers, in bytes.
+    pub instance_buffer_bytes: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    uniform_bytes_written: AtomicU64,
+    dirty_uniforms: AtomicU64,
+    uniform_buffer_writes: AtomicU64,
+    asset_uniforms: AtomicU64,
+    asset_uniform_entities: AtomicU64,
+    uniform_slots_allocated: AtomicU64,
+    uniform_slots_released: AtomicU64,
+    bind_groups_created: AtomicU64,
+    bind_groups_destroyed: AtomicU64,
+    pipelines_specialized: AtomicU64,
+    instance_buffer_bytes: AtomicU64,
+}
+
+/// Counts the work done by the render systems, which is reported by the
+/// [`RenderDiagnosticsPlugin`](super::RenderDiagnosticsPlugin).
+///
+/// The counters are atomic, so render systems running in parallel record into them through a
+/// shared reference. Clones share their counters, so a clone can be read from the app world.
+#[derive(Clone, Default)]
+pub struct RenderStatistics {
+    counters: Arc<Counters>,
+}
+
+impl RenderStatistics {
+    /// Records the `writes` returned by
+    /// [`UniformVec::write_buffer`](crate::render_resource::UniformVec::write_buffer).
+    pub fn record_uniform_writes(&self, writes: UniformWrites) {
+        let counters = &self.counters;
+        counters
+            .uniform_bytes_written
+            .fetch_add(writes.bytes, Ordering::Relaxed);
+        counters
+            .dirty_uniforms
+            .fetch_add(writes.values, Ordering::Relaxed);
+        counters
+            .uniform_buffer_writes
+            .fetch_add(writes.writes, Ordering::Relaxed);
+    }
+
+    /// Records the number of uniforms of assets of a type in this frame, and the number of
+    /// entities sharing them.
+    pub fn record_asset_uniforms(&self, assets: u64, entities: u64) {
+        let counters = &self.counters;
+        counters.asset_uniforms.fetch_add(assets, Ordering::Relaxed);
+        counters
+            .asset_uniform_entities
+            .fetch_add(entities, Ordering::Relaxed);
+    }
+
+    pub fn record_uniform_slots(&self, allocated: u64, released: u64) {
+        let counters = &self.counters;
+        counters
+            .uniform_slots_allocated
+            .fetch_add(allocated, Ordering::Relaxed);
+        counters
+            .uniform_slots_released
+            .fetch_add(released, Ordering::Relaxed);
+    }
+
+    pub fn record_bind_groups(&self, created: u64, destroyed: u64) {
+        let counters = &self.counters;
+        counters
+            .bind_groups_created
+            .fetch_add(created, Ordering::Relaxed);
+        counters
+            .bind_groups_destroyed
+            .fetch_add(destroyed, Ordering::Relaxed);
+    }
+
+    pub fn record_pipelines_specialized(&self, count: u64) {
+        self.counters
+            .pipelines_specialized
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_instance_buffer_bytes(&self, bytes: u64) {
+        self.counters
+            .instance_buffer_bytes
+            .fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics recorded since the last call and resets them.
+    pub fn take(&self) -> RenderFrameStatistics {
+        let counters = &self.counters;
+        let take = |counter: &AtomicU64| counter.swap(0, Ordering::Relaxed);
+        RenderFrameStatistics {
+            uniform_bytes_written: take(&counters.uniform_bytes_written),
+            dirty_uniforms: take(&counters.dirty_uniforms),
+            uniform_buffer_writes: take(&counters.uniform_buffer_writes),
+            asset_uniforms: take(&counters.asset_uniforms),
+            asset_uniform_entities: take(&counters.asset_uniform_entities),
+            uniform_slots_allocated: take(&counters.uniform_slots_allocated),
+            uniform_slots_released: take(&counters.uniform_slots_released),
+            bind_groups_created: take(&counters.bind_groups_created),
+            bind_groups_destroyed: take(&counters.bind_groups_destroyed),
+            pipelines_specialized: take(&counters.pipelines_specialized),
+            instance_buffer_bytes: take(&counters.instance_buffer_bytes),
+        }
+    }
+}
@@ -2,6 +2,9 @@ extern crate core;
 
 pub mod camera;
 pub mod color;
+#[cfg(test)]
+mod counting_allocator;
+pub mod diagnostic;
 pub mod mesh;
 pub mod primitives;
 pub mod render_asset;
@@ -11,6 +14,8 @@ pub mod render_phase;
 pub mod render_resource;
 pub mod renderer;
 pub mod settings;
+#[cfg(test)]
+mod test_util;
 pub mod texture;
 pub mod view;
 
@@ -22,6 +27,7 @@ pub mod prelude {
             PerspectiveProjection,
         },
         color::Color,
+        diagnostic::RenderStatistics,
         mesh::{shape, Mesh},
         render_resource::Shader,
         texture::Image,
@@ -35,11 +41,16 @@ pub use once_cell;
 use crate::{
     camera::CameraPlugin,
     color::Color,
+    diagnostic::RenderStatistics,
     mesh::MeshPlugin,
     primitives::{CubemapFrusta, Frustum},
     render_graph::RenderGraph,
-    render_resource::{PipelineCache, Shader, ShaderLoader},
-    renderer::render_system,
+    render_resource::{
+        BindGroupCache, BufferPool, BufferReadback, FrameArena, PipelineCache, Shader,
+        ShaderDiskCache, ShaderLoader, UniformAllocator, UniformAllocatorSettings,
+        UniformTypeRegistry,
+    },
+    renderer::{render_system, FramesInFlight, RenderResourceLimits},
     texture::ImagePlugin,
     view::{ViewPlugin, WindowRenderPlugin},
 };
@@ -115,6 +126,11 @@ impl Plugin for RenderPlugin {
             .get_resource::<settings::WgpuSettings>()
             .cloned()
             .unwrap_or_default();
+        let allocator_settings = app
+            .world
+            .get_resource::<UniformAllocatorSettings>()
+            .cloned()
+            .unwrap_or_default();
 
         app.add_asset::<Shader>()
             .add_debug_asset::<Shader>()
@@ -142,14 +158,23 @@ impl Plugin for RenderPlugin {
             );
             debug!("Configured wgpu adapter Limits: {:#?}", device.limits());
             debug!("Configured wgpu adapter Features: {:#?}", device.features());
+            let limits = RenderResourceLimits::from(&device.limits());
             app.insert_resource(device.clone())
+                .insert_resource(limits)
                 .insert_resource(queue.clone())
                 .insert_resource(adapter_info.clone())
                 .init_resource::<ScratchRenderWorld>()
                 .register_type::<Frustum>()
                 .register_type::<CubemapFrusta>();
 
-            let pipeline_cache = PipelineCache::new(device.clone());
+            let mut pipeline_cache = PipelineCache::new(device.clone());
+            if let Some(disk_cache) = app.world.get_resource::<ShaderDiskCache>() {
+                pipeline_cache.set_shader_disk_cache(disk_cache.clone());
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(task_pool) = app.world.get_resource::<bevy_tasks::AsyncComputeTaskPool>() {
+                pipeline_cache.set_task_pool(task_pool.0.clone());
+            }
             let asset_server = app.world.resource::<AssetServer>().clone();
 
             let mut render_app = App::empty();
@@ -160,7 +185,16 @@ impl Plugin for RenderPlugin {
             extract_stage.set_apply_buffers(false);
             render_app
                 .add_stage(RenderStage::Extract, extract_stage)
-                .add_stage(RenderStage::Prepare, SystemStage::parallel())
+                .add_stage(
+                    RenderStage::Prepare,
+                    SystemStage::parallel()
+                        .with_system(FramesInFlight::wait_system.exclusive_system().at_start())
+                        .with_system(
+                            UniformTypeRegistry::prepare_system
+                                .exclusive_system()
+                                .at_start(),
+                        ),
+                )
                 .add_stage(RenderStage::Queue, SystemStage::parallel())
                 .add_stage(RenderStage::PhaseSort, SystemStage::parallel())
                 .add_stage(
@@ -169,13 +203,35 @@ impl Plugin for RenderPlugin {
                         .with_system(PipelineCache::process_pipeline_queue_system)
                         .with_system(render_system.exclusive_system().at_end()),
                 )
-                .add_stage(RenderStage::Cleanup, SystemStage::parallel())
+                .add_stage(
+                    RenderStage::Cleanup,
+                    SystemStage::parallel()
+                        .with_system(
+                            BufferReadback::readback_system
+                                .exclusive_system()
+                                .at_start(),
+                        )
+                        .with_system(BufferPool::recycle_system)
+                        .with_system(UniformAllocator::recycle_system)
+                        .with_system(BindGroupCache::evict_system)
+                        .with_system(FrameArena::reset_system)
+                        .with_system(FramesInFlight::advance_system.exclusive_system().at_end()),
+                )
                 .insert_resource(instance)
                 .insert_resource(device)
+                .insert_resource(limits)
+                .insert_resource(FramesInFlight::new(options.frames_in_flight))
                 .insert_resource(queue)
                 .insert_resource(adapter_info)
                 .insert_resource(pipeline_cache)
                 .insert_resource(asset_server)
+                .init_resource::<BufferPool>()
+                .init_resource::<BufferReadback>()
+                .insert_resource(UniformAllocator::new(&limits, allocator_settings.page_size))
+                .init_resource::<BindGroupCache>()
+                .init_resource::<FrameArena>()
+                .init_resource::<UniformTypeRegistry>()
+                .init_resource::<RenderStatistics>()
                 .init_resource::<RenderGraph>();
 
             app.add_sub_app(RenderApp, render_app, move |app_world, render_app| {
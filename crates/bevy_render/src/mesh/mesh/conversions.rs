@@ -24,7 +24,7 @@
 //! assert!(error.is_err());
 //! ```
 
-use crate::mesh::VertexAttributeValues;
+use crate::mesh::{HalfFloat, VertexAttributeValues};
 use bevy_utils::EnumVariantMeta;
 use thiserror::Error;
 
@@ -130,6 +130,52 @@ impl From<Vec<[u8; 4]>> for VertexAttributeValues {
     }
 }
 
+impl From<Vec<[i16; 2]>> for VertexAttributeValues {
+    fn from(vec: Vec<[i16; 2]>) -> Self {
+        VertexAttributeValues::Sint16x2(vec)
+    }
+}
+
+impl From<Vec<[i16; 4]>> for VertexAttributeValues {
+    fn from(vec: Vec<[i16; 4]>) -> Self {
+        VertexAttributeValues::Sint16x4(vec)
+    }
+}
+
+impl From<Vec<[HalfFloat; 2]>> for VertexAttributeValues {
+    fn from(vec: Vec<[HalfFloat; 2]>) -> Self {
+        VertexAttributeValues::Float16x2(vec)
+    }
+}
+
+impl From<Vec<[HalfFloat; 4]>> for VertexAttributeValues {
+    fn from(vec: Vec<[HalfFloat; 4]>) -> Self {
+        VertexAttributeValues::Float16x4(vec)
+    }
+}
+
+impl TryFrom<VertexAttributeValues> for Vec<[HalfFloat; 2]> {
+    type Error = FromVertexAttributeError;
+
+    fn try_from(value: VertexAttributeValues) -> Result<Self, Self::Error> {
+        match value {
+            VertexAttributeValues::Float16x2(value) => Ok(value),
+            _ => Err(FromVertexAttributeError::new::<Self>(value)),
+        }
+    }
+}
+
+impl TryFrom<VertexAttributeValues> for Vec<[HalfFloat; 4]> {
+    type Error = FromVertexAttributeError;
+
+    fn try_from(value: VertexAttributeValues) -> Result<Self, Self::Error> {
+        match value {
+            VertexAttributeValues::Float16x4(value) => Ok(value),
+            _ => Err(FromVertexAttributeError::new::<Self>(value)),
+        }
+    }
+}
+
 impl TryFrom<VertexAttributeValues> for Vec<[u8; 4]> {
     type Error = FromVertexAttributeError;
 
@@ -361,6 +407,8 @@ impl TryFrom<VertexAttributeValues> for Vec<f32> {
 #[cfg(test)]
 mod tests {
     use super::VertexAttributeValues;
+    use crate::mesh::{HalfFloat, VertexFormatSize};
+    use wgpu::VertexFormat;
     #[test]
     fn f32() {
         let buffer = vec![0.0; 10];
@@ -505,6 +553,61 @@ mod tests {
         assert!(error.is_err());
     }
 
+    #[test]
+    fn i16_2() {
+        let buffer = vec![[-1_i16, 1]; 10];
+        let values = VertexAttributeValues::from(buffer.clone());
+        assert_eq!(VertexFormat::from(&values), VertexFormat::Sint16x2);
+        let result_into: Vec<[i16; 2]> = values.clone().try_into().unwrap();
+        let error: Result<Vec<[u16; 2]>, _> = values.try_into();
+        assert_eq!(buffer, result_into);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn i16_4() {
+        let buffer = vec![[-1_i16, 1, 2, 3]; 10];
+        let values = VertexAttributeValues::from(buffer.clone());
+        assert_eq!(VertexFormat::from(&values), VertexFormat::Sint16x4);
+        let result_into: Vec<[i16; 4]> = values.clone().try_into().unwrap();
+        let error: Result<Vec<[u16; 4]>, _> = values.try_into();
+        assert_eq!(buffer, result_into);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn half_4() {
+        let buffer = vec![[HalfFloat::from_f32(0.5); 4]; 10];
+        let values = VertexAttributeValues::from(buffer.clone());
+        assert_eq!(VertexFormat::from(&values), VertexFormat::Float16x4);
+        assert_eq!(
+            values.get_bytes().len(),
+            10 * VertexFormat::Float16x4.get_size() as usize
+        );
+        let result_into: Vec<[HalfFloat; 4]> = values.clone().try_into().unwrap();
+        let error: Result<Vec<[HalfFloat; 2]>, _> = values.try_into();
+        assert_eq!(buffer, result_into);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn half_float_conversion() {
+        for value in [0.0, -0.0, 1.0, -2.5, 0.333_251_95, 65504.0] {
+            assert_eq!(HalfFloat::from_f32(value).to_f32(), value);
+        }
+        assert_eq!(HalfFloat::from_f32(1.0), HalfFloat(0x3c00));
+        // smallest subnormal half
+        assert_eq!(HalfFloat::from_f32(2.0f32.powi(-24)), HalfFloat(0x0001));
+        assert_eq!(HalfFloat(0x0001).to_f32(), 2.0f32.powi(-24));
+        // 1.0 + 2^-11 lies exactly between two halfs and rounds to the even one
+        assert_eq!(
+            HalfFloat::from_f32(1.0 + 2.0f32.powi(-11)),
+            HalfFloat(0x3c00)
+        );
+        assert_eq!(HalfFloat::from_f32(1e6), HalfFloat(0x7c00));
+        assert!(HalfFloat::from_f32(f32::NAN).to_f32().is_nan());
+    }
+
     #[test]
     fn correct_message() {
         let buffer = vec![[0_u32; 4]; 3];
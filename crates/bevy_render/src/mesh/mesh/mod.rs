@@ -8,12 +8,19 @@ use crate::{
     render_resource::{Buffer, VertexBufferLayout},
     renderer::RenderDevice,
 };
-use bevy_core::cast_slice;
-use bevy_ecs::system::{lifetimeless::SRes, SystemParamItem};
+use bevy_core::{cast_slice, Pod, Zeroable};
+use bevy_ecs::{
+    system::{lifetimeless::SRes, SystemParamItem},
+    world::{FromWorld, World},
+};
 use bevy_math::*;
 use bevy_reflect::TypeUuid;
-use bevy_utils::{EnumVariantMeta, Hashed};
-use std::{collections::BTreeMap, hash::Hash};
+use bevy_utils::{tracing::warn, EnumVariantMeta, HashSet, Hashed};
+use std::{
+    collections::BTreeMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
 use thiserror::Error;
 use wgpu::{
     util::BufferInitDescriptor, BufferUsages, IndexFormat, VertexAttribute, VertexFormat,
@@ -285,6 +292,8 @@ impl Mesh {
                 VertexAttributeValues::Snorm16x4(vec) => *vec = duplicate(vec, indices),
                 VertexAttributeValues::Uint16x4(vec) => *vec = duplicate(vec, indices),
                 VertexAttributeValues::Unorm16x4(vec) => *vec = duplicate(vec, indices),
+                VertexAttributeValues::Float16x2(vec) => *vec = duplicate(vec, indices),
+                VertexAttributeValues::Float16x4(vec) => *vec = duplicate(vec, indices),
                 VertexAttributeValues::Sint8x2(vec) => *vec = duplicate(vec, indices),
                 VertexAttributeValues::Snorm8x2(vec) => *vec = duplicate(vec, indices),
                 VertexAttributeValues::Uint8x2(vec) => *vec = duplicate(vec, indices),
@@ -445,6 +454,173 @@ impl InnerMeshVertexBufferLayout {
             attributes,
         })
     }
+
+    /// Like [`get_layout`](Self::get_layout), but instead of failing for attributes this mesh
+    /// lacks, reads them from the zero-filled [`FallbackVertexBuffer`], so that the mesh is still
+    /// drawn. The `pipeline_type` is named in the warning about the missing attributes, which is
+    /// only logged the first time this mesh layout is used with the pipeline type, as recorded in
+    /// the `reports` of the pipeline.
+    ///
+    /// This only fails if the missing attributes don't fit into the fallback buffer.
+    pub fn get_layout_with_fallback(
+        &self,
+        pipeline_type: &'static str,
+        attribute_descriptors: &[VertexAttributeDescriptor],
+        reports: &MissingVertexAttributeReports,
+    ) -> Result<MeshVertexBufferLayouts, MissingVertexAttributeError> {
+        let mut attributes = Vec::with_capacity(attribute_descriptors.len());
+        let mut fallback_attributes = Vec::new();
+        let mut missing = Vec::new();
+        let mut fallback_offset = 0;
+        for attribute_descriptor in attribute_descriptors.iter() {
+            if let Some(index) = self
+                .attribute_ids
+                .iter()
+                .position(|id| *id == attribute_descriptor.id)
+            {
+                let layout_attribute = &self.layout.attributes[index];
+                attributes.push(VertexAttribute {
+                    format: layout_attribute.format,
+                    offset: layout_attribute.offset,
+                    shader_location: attribute_descriptor.shader_location,
+                });
+                continue;
+            }
+
+            // the fallback buffer uses a stride of zero, so every vertex reads the same zeros
+            let format = fallback_format(attribute_descriptor.id);
+            if fallback_offset + format.size() > FALLBACK_VERTEX_BUFFER_SIZE {
+                return Err(MissingVertexAttributeError {
+                    id: attribute_descriptor.id,
+                    name: attribute_descriptor.name,
+                    pipeline_type: Some(pipeline_type),
+                });
+            }
+            fallback_attributes.push(VertexAttribute {
+                format,
+                offset: fallback_offset,
+                shader_location: attribute_descriptor.shader_location,
+            });
+            fallback_offset += align_to_4(format.size());
+            missing.push(attribute_descriptor.name);
+        }
+
+        if !missing.is_empty() && reports.insert(self, pipeline_type) {
+            warn!(
+                "A mesh rendered by {} is missing the vertex attributes {:?}, which read zeros \
+                 instead",
+                pipeline_type, missing
+            );
+        }
+
+        Ok(MeshVertexBufferLayouts {
+            mesh: VertexBufferLayout {
+                array_stride: self.layout.array_stride,
+                step_mode: self.layout.step_mode,
+                attributes,
+            },
+            fallback: (!fallback_attributes.is_empty()).then(|| VertexBufferLayout {
+                array_stride: 0,
+                step_mode: VertexStepMode::Vertex,
+                attributes: fallback_attributes,
+            }),
+            missing,
+        })
+    }
+}
+
+/// The mesh layouts and pipeline types missing attributes were reported for, see
+/// [`InnerMeshVertexBufferLayout::get_layout_with_fallback`]. Mesh pipelines keep one of these,
+/// and clones of it share the reports.
+#[derive(Clone, Default)]
+pub struct MissingVertexAttributeReports {
+    reported: Arc<Mutex<HashSet<(Vec<MeshVertexAttributeId>, &'static str)>>>,
+}
+
+impl MissingVertexAttributeReports {
+    /// Records that the mesh `layout` lacks attributes read by the `pipeline_type`, returning
+    /// whether this wasn't recorded before.
+    pub fn insert(
+        &self,
+        layout: &InnerMeshVertexBufferLayout,
+        pipeline_type: &'static str,
+    ) -> bool {
+        self.reported
+            .lock()
+            .unwrap()
+            .insert((layout.attribute_ids.clone(), pipeline_type))
+    }
+}
+
+/// The size of the [`FallbackVertexBuffer`] in bytes, which limits how many missing attributes
+/// can be read from it.
+pub const FALLBACK_VERTEX_BUFFER_SIZE: u64 = 256;
+
+/// The vertex buffer slot [`FallbackVertexBuffer`] is bound to by the mesh draw commands.
+pub const FALLBACK_VERTEX_BUFFER_SLOT: usize = 1;
+
+/// Returns the format the fallback of a missing attribute is read with, which is the format
+/// of the builtin attribute with the `id`, or four floats for custom attributes.
+fn fallback_format(id: MeshVertexAttributeId) -> VertexFormat {
+    [
+        Mesh::ATTRIBUTE_POSITION,
+        Mesh::ATTRIBUTE_NORMAL,
+        Mesh::ATTRIBUTE_UV_0,
+        Mesh::ATTRIBUTE_TANGENT,
+        Mesh::ATTRIBUTE_COLOR,
+        Mesh::ATTRIBUTE_JOINT_WEIGHT,
+        Mesh::ATTRIBUTE_JOINT_INDEX,
+    ]
+    .iter()
+    .find(|attribute| attribute.id == id)
+    .map_or(VertexFormat::Float32x4, |attribute| attribute.format)
+}
+
+const fn align_to_4(size: u64) -> u64 {
+    (size + 3) & !3
+}
+
+/// The vertex buffer layouts of a pipeline drawing a mesh which may lack some of the attributes
+/// the pipeline reads, see [`InnerMeshVertexBufferLayout::get_layout_with_fallback`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshVertexBufferLayouts {
+    /// The layout of the vertex buffer of the mesh, with the attributes the mesh has.
+    pub mesh: VertexBufferLayout,
+    /// The layout of the [`FallbackVertexBuffer`], with the attributes the mesh lacks, if there
+    /// are any.
+    pub fallback: Option<VertexBufferLayout>,
+    /// The names of the attributes the mesh lacks.
+    pub missing: Vec<&'static str>,
+}
+
+impl MeshVertexBufferLayouts {
+    /// Returns the layouts in the order of their vertex buffer slots, starting with the mesh at
+    /// slot `0` and followed by the fallback at [`FALLBACK_VERTEX_BUFFER_SLOT`].
+    pub fn into_buffers(self) -> Vec<VertexBufferLayout> {
+        let mut buffers = vec![self.mesh];
+        buffers.extend(self.fallback);
+        buffers
+    }
+}
+
+/// A zero-filled vertex buffer, from which pipelines read the attributes missing from a mesh.
+///
+/// The layouts returned by [`InnerMeshVertexBufferLayout::get_layout_with_fallback`] expect this
+/// buffer to be bound at [`FALLBACK_VERTEX_BUFFER_SLOT`].
+pub struct FallbackVertexBuffer {
+    pub buffer: Buffer,
+}
+
+impl FromWorld for FallbackVertexBuffer {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            usage: BufferUsages::VERTEX,
+            label: Some("Fallback Vertex Buffer"),
+            contents: &[0; FALLBACK_VERTEX_BUFFER_SIZE as usize],
+        });
+        Self { buffer }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -530,6 +706,93 @@ impl VertexFormatSize for wgpu::VertexFormat {
     }
 }
 
+/// A 16 bit (half precision) floating point number, used for compact
+/// [`VertexFormat::Float16x2`] and [`VertexFormat::Float16x4`] vertex attributes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct HalfFloat(pub u16);
+
+// SAFETY: `HalfFloat` is a transparent wrapper around a `u16`
+unsafe impl Zeroable for HalfFloat {}
+unsafe impl Pod for HalfFloat {}
+
+impl HalfFloat {
+    /// Converts `value` to the nearest half precision number. Values too large to be represented
+    /// become infinity.
+    pub fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x007f_ffff;
+
+        if exponent == 0xff {
+            // infinity stays infinity, NaN stays NaN
+            let nan = if mantissa != 0 { 0x0200 } else { 0 };
+            return Self(sign | 0x7c00 | nan);
+        }
+
+        let exponent = exponent - 127 + 15;
+        if exponent >= 0x1f {
+            return Self(sign | 0x7c00);
+        }
+        let (half, mantissa, shift) = if exponent <= 0 {
+            // subnormal numbers include the implicit leading bit in their mantissa
+            if exponent < -10 {
+                return Self(sign);
+            }
+            let mantissa = mantissa | 0x0080_0000;
+            let shift = (14 - exponent) as u32;
+            (mantissa >> shift, mantissa, shift)
+        } else {
+            (((exponent as u32) << 10) | (mantissa >> 13), mantissa, 13)
+        };
+
+        // round to nearest, ties to even. A carry into the exponent is intended.
+        let round_bit = 1 << (shift - 1);
+        let half = if mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0 {
+            half + 1
+        } else {
+            half
+        };
+        Self(sign | half as u16)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        let sign = ((self.0 & 0x8000) as u32) << 16;
+        let exponent = ((self.0 >> 10) & 0x1f) as u32;
+        let mantissa = (self.0 & 0x03ff) as u32;
+
+        let bits = match exponent {
+            0 if mantissa == 0 => sign,
+            0 => {
+                // normalize the subnormal number
+                let mut exponent = 127 - 15 + 1;
+                let mut mantissa = mantissa;
+                while mantissa & 0x0400 == 0 {
+                    mantissa <<= 1;
+                    exponent -= 1;
+                }
+                sign | (exponent << 23) | ((mantissa & 0x03ff) << 13)
+            }
+            0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+            _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+        };
+        f32::from_bits(bits)
+    }
+}
+
+impl From<f32> for HalfFloat {
+    fn from(value: f32) -> Self {
+        Self::from_f32(value)
+    }
+}
+
+impl From<HalfFloat> for f32 {
+    fn from(value: HalfFloat) -> Self {
+        value.to_f32()
+    }
+}
+
 /// Contains an array where each entry describes a property of a single vertex.
 /// Matches the [`VertexFormats`](VertexFormat).
 #[derive(Clone, Debug, EnumVariantMeta)]
@@ -554,6 +817,8 @@ pub enum VertexAttributeValues {
     Snorm16x4(Vec<[i16; 4]>),
     Uint16x4(Vec<[u16; 4]>),
     Unorm16x4(Vec<[u16; 4]>),
+    Float16x2(Vec<[HalfFloat; 2]>),
+    Float16x4(Vec<[HalfFloat; 4]>),
     Sint8x2(Vec<[i8; 2]>),
     Snorm8x2(Vec<[i8; 2]>),
     Uint8x2(Vec<[u8; 2]>),
@@ -589,6 +854,8 @@ impl VertexAttributeValues {
             VertexAttributeValues::Snorm16x4(ref values) => values.len(),
             VertexAttributeValues::Uint16x4(ref values) => values.len(),
             VertexAttributeValues::Unorm16x4(ref values) => values.len(),
+            VertexAttributeValues::Float16x2(ref values) => values.len(),
+            VertexAttributeValues::Float16x4(ref values) => values.len(),
             VertexAttributeValues::Sint8x2(ref values) => values.len(),
             VertexAttributeValues::Snorm8x2(ref values) => values.len(),
             VertexAttributeValues::Uint8x2(ref values) => values.len(),
@@ -638,6 +905,8 @@ impl VertexAttributeValues {
             VertexAttributeValues::Snorm16x4(values) => cast_slice(&values[..]),
             VertexAttributeValues::Uint16x4(values) => cast_slice(&values[..]),
             VertexAttributeValues::Unorm16x4(values) => cast_slice(&values[..]),
+            VertexAttributeValues::Float16x2(values) => cast_slice(&values[..]),
+            VertexAttributeValues::Float16x4(values) => cast_slice(&values[..]),
             VertexAttributeValues::Sint8x2(values) => cast_slice(&values[..]),
             VertexAttributeValues::Snorm8x2(values) => cast_slice(&values[..]),
             VertexAttributeValues::Uint8x2(values) => cast_slice(&values[..]),
@@ -673,6 +942,8 @@ impl From<&VertexAttributeValues> for VertexFormat {
             VertexAttributeValues::Snorm16x4(_) => VertexFormat::Snorm16x4,
             VertexAttributeValues::Uint16x4(_) => VertexFormat::Uint16x4,
             VertexAttributeValues::Unorm16x4(_) => VertexFormat::Unorm16x4,
+            VertexAttributeValues::Float16x2(_) => VertexFormat::Float16x2,
+            VertexAttributeValues::Float16x4(_) => VertexFormat::Float16x4,
             VertexAttributeValues::Sint8x2(_) => VertexFormat::Sint8x2,
             VertexAttributeValues::Snorm8x2(_) => VertexFormat::Snorm8x2,
             VertexAttributeValues::Uint8x2(_) => VertexFormat::Uint8x2,
@@ -756,6 +1027,31 @@ pub struct GpuMesh {
     pub layout: MeshVertexBufferLayout,
 }
 
+impl GpuMesh {
+    /// Returns the index format to use as the `strip_index_format` of pipelines rendering this mesh
+    /// with the given `primitive_topology`. This is [`Some`] for indexed meshes rendered with strip
+    /// topologies, in which the maximum index value of the format restarts the strip.
+    pub fn strip_index_format(&self, primitive_topology: PrimitiveTopology) -> Option<IndexFormat> {
+        let index_format = match &self.buffer_info {
+            GpuBufferInfo::Indexed { index_format, .. } => Some(*index_format),
+            GpuBufferInfo::NonIndexed { .. } => None,
+        };
+        strip_index_format(primitive_topology, index_format)
+    }
+}
+
+fn strip_index_format(
+    primitive_topology: PrimitiveTopology,
+    index_format: Option<IndexFormat>,
+) -> Option<IndexFormat> {
+    match primitive_topology {
+        PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip => index_format,
+        PrimitiveTopology::PointList
+        | PrimitiveTopology::LineList
+        | PrimitiveTopology::TriangleList => None,
+    }
+}
+
 /// The index/vertex buffer info of a [`GpuMesh`].
 #[derive(Debug, Clone)]
 pub enum GpuBufferInfo {
@@ -817,3 +1113,109 @@ impl RenderAsset for Mesh {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_index_format, Indices, Mesh, MissingVertexAttributeReports};
+    use wgpu::{IndexFormat, PrimitiveTopology, VertexAttribute, VertexFormat};
+
+    #[test]
+    fn u16_indices() {
+        let mut quad = Mesh::new(PrimitiveTopology::TriangleList);
+        quad.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+        );
+        quad.set_indices(Some(Indices::U16(vec![0, 1, 2, 0, 2, 3])));
+
+        // u16 indices are uploaded as is, without being converted to u32
+        assert_eq!(quad.get_index_buffer_bytes().unwrap().len(), 6 * 2);
+        assert_eq!(
+            IndexFormat::from(quad.indices().unwrap()),
+            IndexFormat::Uint16
+        );
+    }
+
+    #[test]
+    fn strip_index_formats() {
+        assert_eq!(
+            strip_index_format(PrimitiveTopology::TriangleStrip, Some(IndexFormat::Uint16)),
+            Some(IndexFormat::Uint16)
+        );
+        assert_eq!(
+            strip_index_format(PrimitiveTopology::LineStrip, Some(IndexFormat::Uint32)),
+            Some(IndexFormat::Uint32)
+        );
+        assert_eq!(
+            strip_index_format(PrimitiveTopology::TriangleStrip, None),
+            None
+        );
+        assert_eq!(
+            strip_index_format(PrimitiveTopology::TriangleList, Some(IndexFormat::Uint16)),
+            None
+        );
+    }
+
+    #[test]
+    fn missing_attributes_read_from_fallback() {
+        const LIT_PIPELINE: &str = "tests::LitMeshPipeline";
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0, 0.0, 0.0]; 3]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; 3]);
+        let layout = mesh.get_mesh_vertex_buffer_layout();
+        let lit_attributes = [
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
+        ];
+        assert!(layout.get_layout(&lit_attributes).is_err());
+
+        // specializing for several keys checks the same mesh layout repeatedly
+        let reports = MissingVertexAttributeReports::default();
+        for _ in 0..3 {
+            let layouts = layout
+                .get_layout_with_fallback(LIT_PIPELINE, &lit_attributes, &reports)
+                .unwrap();
+            assert_eq!(layouts.missing, ["Vertex_Normal"]);
+            let fallback = layouts.fallback.as_ref().unwrap();
+            assert_eq!(fallback.array_stride, 0);
+            assert_eq!(
+                fallback.attributes,
+                [VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 1,
+                }]
+            );
+            let locations: Vec<_> = layouts
+                .mesh
+                .attributes
+                .iter()
+                .map(|attribute| attribute.shader_location)
+                .collect();
+            assert_eq!(locations, [0, 2]);
+            assert_eq!(layouts.into_buffers().len(), 2);
+        }
+
+        // the missing normals are only reported once
+        assert_eq!(reports.reported.lock().unwrap().len(), 1);
+        assert!(!reports.insert(&layout, LIT_PIPELINE));
+        // other pipelines keep their own reports
+        assert!(MissingVertexAttributeReports::default().insert(&layout, LIT_PIPELINE));
+
+        // meshes with all attributes don't need the fallback
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 3]);
+        let layouts = mesh
+            .get_mesh_vertex_buffer_layout()
+            .get_layout_with_fallback(LIT_PIPELINE, &lit_attributes, &reports)
+            .unwrap();
+        assert!(layouts.fallback.is_none());
+        assert!(layouts.missing.is_empty());
+    }
+}
@@ -5,7 +5,7 @@ pub mod shape;
 
 pub use mesh::*;
 
-use crate::render_asset::RenderAssetPlugin;
+use crate::{render_asset::RenderAssetPlugin, RenderApp};
 use bevy_app::{App, Plugin};
 use bevy_asset::AddAsset;
 
@@ -18,5 +18,9 @@ impl Plugin for MeshPlugin {
             .add_asset::<skinning::SkinnedMeshInverseBindposes>()
             .register_type::<skinning::SkinnedMesh>()
             .add_plugin(RenderAssetPlugin::<Mesh>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<FallbackVertexBuffer>();
+        }
     }
 }
@@ -1,7 +1,8 @@
 use crate::{RenderApp, RenderStage};
-use bevy_app::{App, Plugin};
+use bevy_app::{App, CoreStage, Plugin};
 use bevy_asset::{Asset, AssetEvent, Assets, Handle};
 use bevy_ecs::{
+    event::{Events, ManualEventReader},
     prelude::*,
     system::{StaticSystemParam, SystemParam, SystemParamItem},
 };
@@ -37,6 +38,50 @@ pub trait RenderAsset: Asset {
         extracted_asset: Self::ExtractedAsset,
         param: &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>>;
+    /// Prepares a placeholder for an `extracted_asset` whose [`RenderAsset::prepare_asset`]
+    /// asked to be retried, for example a material binding
+    /// [`FallbackTextures`](crate::texture::FallbackTextures) in place of textures that haven't
+    /// been loaded yet. The placeholder is used until the retried preparation succeeds.
+    ///
+    /// Returns `None` by default, in which case the asset isn't available until then.
+    fn prepare_fallback_asset(
+        _extracted_asset: &Self::ExtractedAsset,
+        _param: &mut SystemParamItem<Self::Param>,
+    ) -> Option<Self::PreparedAsset> {
+        None
+    }
+    /// Updates the `prepared_asset` of a modified asset in place from its new `extracted_asset`,
+    /// for example by writing the new uniform data of a material to its existing buffer instead
+    /// of recreating its bind group.
+    ///
+    /// Returns `false` by default, in which case the asset is prepared again with
+    /// [`RenderAsset::prepare_asset`]. Return `false` as well if the update requires new GPU
+    /// resources, like when a material binds a different texture.
+    fn update_prepared_asset(
+        _extracted_asset: &Self::ExtractedAsset,
+        _prepared_asset: &mut Self::PreparedAsset,
+        _param: &mut SystemParamItem<Self::Param>,
+    ) -> bool {
+        false
+    }
+}
+
+/// A [`RenderAsset`] that is prepared from assets of type `D`, like a material from its textures
+/// and samplers. Its dependencies are tracked by the [`RenderAssetDependencyPlugin`].
+pub trait DependentRenderAsset<D: Asset>: RenderAsset {
+    /// Returns the assets of type `D` this asset is prepared from.
+    ///
+    /// The asset is marked as modified whenever one of them is modified or removed, so that it is
+    /// prepared again instead of binding an outdated GPU resource, for example when a texture is
+    /// hot reloaded.
+    ///
+    /// Only the handles stored in the asset itself keep the dependencies alive, they are tracked
+    /// with weak handles. An asset can therefore hold a
+    /// [weak handle](bevy_asset::Handle::clone_weak) to an image it should use only while
+    /// something else keeps it loaded. Once the image is removed, the asset is treated as if the
+    /// image hadn't been loaded yet, which for materials means binding a
+    /// [fallback texture](crate::texture::FallbackTextures).
+    fn dependencies(&self) -> Vec<Handle<D>>;
 }
 
 #[derive(Clone, Hash, Debug, PartialEq, Eq, SystemLabel)]
@@ -82,7 +127,7 @@ impl<A: RenderAsset> Default for RenderAssetPlugin<A> {
 
 impl<A: RenderAsset> Plugin for RenderAssetPlugin<A> {
     fn build(&self, app: &mut App) {
-        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+        if app.get_sub_app(RenderApp).is_ok() {
             let prepare_asset_system = prepare_assets::<A>.label(self.prepare_asset_label.clone());
 
             let prepare_asset_system = match self.prepare_asset_label {
@@ -95,6 +140,7 @@ impl<A: RenderAsset> Plugin for RenderAssetPlugin<A> {
                 }
             };
 
+            let render_app = app.sub_app_mut(RenderApp);
             render_app
                 .init_resource::<ExtractedAssets<A>>()
                 .init_resource::<RenderAssets<A>>()
@@ -105,6 +151,119 @@ impl<A: RenderAsset> Plugin for RenderAssetPlugin<A> {
     }
 }
 
+/// This plugin prepares the render assets of type `A` again whenever one of the assets of type `D`
+/// they depend on is modified or removed, see [`DependentRenderAsset`].
+///
+/// Render assets only opt into the tracking with this plugin, e.g. the materials that bind
+/// textures add it for [`Image`](crate::texture::Image)s and
+/// [`ImageSampler`](crate::texture::ImageSampler)s.
+pub struct RenderAssetDependencyPlugin<A, D>(PhantomData<fn() -> (A, D)>);
+
+impl<A, D> Default for RenderAssetDependencyPlugin<A, D> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<A: DependentRenderAsset<D>, D: Asset> Plugin for RenderAssetDependencyPlugin<A, D> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RenderAssetDependencies<A, D>>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_render_asset_dependencies::<A, D>,
+            );
+    }
+}
+
+/// Tracks which assets of type `A` depend on which assets of type `D`, as returned by
+/// [`DependentRenderAsset::dependencies`].
+pub struct RenderAssetDependencies<A: RenderAsset, D: Asset> {
+    dependents: HashMap<Handle<D>, HashSet<Handle<A>>>,
+    dependencies: HashMap<Handle<A>, Vec<Handle<D>>>,
+}
+
+impl<A: RenderAsset, D: Asset> Default for RenderAssetDependencies<A, D> {
+    fn default() -> Self {
+        Self {
+            dependents: Default::default(),
+            dependencies: Default::default(),
+        }
+    }
+}
+
+impl<A: RenderAsset, D: Asset> RenderAssetDependencies<A, D> {
+    /// Sets the dependencies of the asset of `handle`, replacing its previous dependencies.
+    pub fn set(&mut self, handle: &Handle<A>, dependencies: Vec<Handle<D>>) {
+        self.remove(handle);
+        if dependencies.is_empty() {
+            return;
+        }
+        let dependencies = dependencies
+            .iter()
+            .map(Handle::clone_weak)
+            .collect::<Vec<_>>();
+        for dependency in &dependencies {
+            self.dependents
+                .entry(dependency.clone_weak())
+                .or_default()
+                .insert(handle.clone_weak());
+        }
+        self.dependencies.insert(handle.clone_weak(), dependencies);
+    }
+
+    /// Removes all dependencies of the asset of `handle`.
+    pub fn remove(&mut self, handle: &Handle<A>) {
+        for dependency in self.dependencies.remove(handle).into_iter().flatten() {
+            if let Some(dependents) = self.dependents.get_mut(&dependency) {
+                dependents.remove(handle);
+                if dependents.is_empty() {
+                    self.dependents.remove(&dependency);
+                }
+            }
+        }
+    }
+
+    /// Returns the assets that depend on the `dependency`.
+    pub fn dependents(&self, dependency: &Handle<D>) -> impl Iterator<Item = &Handle<A>> {
+        self.dependents.get(dependency).into_iter().flatten()
+    }
+}
+
+/// This system keeps the [`RenderAssetDependencies`] up to date and marks the assets depending
+/// on modified or removed assets as modified, so that they are extracted and prepared again.
+fn update_render_asset_dependencies<A: DependentRenderAsset<D>, D: Asset>(
+    mut dependencies: ResMut<RenderAssetDependencies<A, D>>,
+    mut asset_reader: Local<ManualEventReader<AssetEvent<A>>>,
+    mut asset_events: ResMut<Events<AssetEvent<A>>>,
+    mut dependency_events: EventReader<AssetEvent<D>>,
+    assets: Res<Assets<A>>,
+) {
+    for event in asset_reader.iter(&asset_events) {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if let Some(asset) = assets.get(handle) {
+                    dependencies.set(handle, asset.dependencies());
+                }
+            }
+            AssetEvent::Removed { handle } => dependencies.remove(handle),
+        }
+    }
+
+    let mut modified = HashSet::default();
+    for event in dependency_events.iter() {
+        match event {
+            AssetEvent::Modified { handle } | AssetEvent::Removed { handle } => {
+                modified.extend(dependencies.dependents(handle).map(Handle::clone_weak));
+            }
+            AssetEvent::Created { .. } => {}
+        }
+    }
+    // the modified events extract the assets again
+    for handle in modified {
+        asset_events.send(AssetEvent::Modified { handle });
+    }
+}
+
 /// Temporarily stores the extracted and removed assets of the current frame.
 pub struct ExtractedAssets<A: RenderAsset> {
     extracted: Vec<(Handle<A>, A::ExtractedAsset)>,
@@ -184,7 +343,19 @@ fn prepare_assets<R: RenderAsset>(
     param: StaticSystemParam<<R as RenderAsset>::Param>,
 ) {
     let mut param = param.into_inner();
+    let removed_assets = std::mem::take(&mut extracted_assets.removed);
+    let changed_assets = std::mem::take(&mut extracted_assets.extracted);
+
+    // queued assets that were removed or extracted again since are outdated, preparing them would
+    // overwrite the new version or keep the GPU resources of a removed asset alive
     let mut queued_assets = std::mem::take(&mut prepare_next_frame.assets);
+    {
+        let outdated = removed_assets
+            .iter()
+            .chain(changed_assets.iter().map(|(handle, _)| handle))
+            .collect::<HashSet<_>>();
+        queued_assets.retain(|(handle, _)| !outdated.contains(handle));
+    }
     for (handle, extracted_asset) in queued_assets.drain(..) {
         match R::prepare_asset(extracted_asset, &mut param) {
             Ok(prepared_asset) => {
@@ -196,18 +367,438 @@ fn prepare_assets<R: RenderAsset>(
         }
     }
 
-    for removed in std::mem::take(&mut extracted_assets.removed) {
-        render_assets.remove(&removed);
+    for removed in &removed_assets {
+        render_assets.remove(removed);
     }
 
-    for (handle, extracted_asset) in std::mem::take(&mut extracted_assets.extracted) {
+    for (handle, extracted_asset) in changed_assets {
+        if let Some(prepared_asset) = render_assets.get_mut(&handle) {
+            if R::update_prepared_asset(&extracted_asset, prepared_asset, &mut param) {
+                continue;
+            }
+        }
         match R::prepare_asset(extracted_asset, &mut param) {
             Ok(prepared_asset) => {
                 render_assets.insert(handle, prepared_asset);
             }
             Err(PrepareAssetError::RetryNextUpdate(extracted_asset)) => {
+                if let Some(fallback_asset) =
+                    R::prepare_fallback_asset(&extracted_asset, &mut param)
+                {
+                    render_assets.insert(handle.clone_weak(), fallback_asset);
+                }
                 prepare_next_frame.assets.push((handle, extracted_asset));
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        extract_render_asset, prepare_assets, DependentRenderAsset, ExtractedAssets,
+        PrepareAssetError, PrepareNextFrameAssets, RenderAsset, RenderAssetDependencies,
+        RenderAssetDependencyPlugin, RenderAssets,
+    };
+    use crate::texture::Image;
+    use bevy_app::{App, CoreStage};
+    use bevy_asset::{AddAsset, AssetEvent, AssetPlugin, Assets, Handle, HandleId};
+    use bevy_core::CorePlugin;
+    use bevy_ecs::{
+        event::Events,
+        prelude::*,
+        system::{
+            lifetimeless::{SRes, SResMut},
+            SystemParamItem,
+        },
+    };
+    use bevy_reflect::TypeUuid;
+
+    #[derive(TypeUuid)]
+    #[uuid = "b3c2a9d6-6a0e-4b8f-9d57-6c1b1cb4a5e1"]
+    struct TexturedAsset;
+
+    struct TextureLoaded(bool);
+
+    impl RenderAsset for TexturedAsset {
+        type ExtractedAsset = u32;
+        type PreparedAsset = &'static str;
+        type Param = SRes<TextureLoaded>;
+
+        fn extract_asset(&self) -> Self::ExtractedAsset {
+            0
+        }
+
+        fn prepare_asset(
+            extracted_asset: Self::ExtractedAsset,
+            texture_loaded: &mut SystemParamItem<Self::Param>,
+        ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+            if texture_loaded.0 {
+                Ok("texture")
+            } else {
+                Err(PrepareAssetError::RetryNextUpdate(extracted_asset))
+            }
+        }
+
+        fn prepare_fallback_asset(
+            _extracted_asset: &Self::ExtractedAsset,
+            _texture_loaded: &mut SystemParamItem<Self::Param>,
+        ) -> Option<Self::PreparedAsset> {
+            Some("fallback")
+        }
+    }
+
+    #[derive(TypeUuid)]
+    #[uuid = "1f0e6a42-8d7b-4c3e-a5f9-2b8c7d6e5a40"]
+    struct WeakTexturedAsset {
+        texture: Handle<Image>,
+    }
+
+    impl RenderAsset for WeakTexturedAsset {
+        type ExtractedAsset = u32;
+        type PreparedAsset = u32;
+        type Param = ();
+
+        fn extract_asset(&self) -> Self::ExtractedAsset {
+            0
+        }
+
+        fn prepare_asset(
+            extracted_asset: Self::ExtractedAsset,
+            _param: &mut SystemParamItem<Self::Param>,
+        ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+            Ok(extracted_asset)
+        }
+    }
+
+    impl DependentRenderAsset<Image> for WeakTexturedAsset {
+        fn dependencies(&self) -> Vec<Handle<Image>> {
+            vec![self.texture.clone()]
+        }
+    }
+
+    #[test]
+    fn fallback_is_replaced_once_loaded() {
+        let mut world = World::new();
+        let handle = Handle::<TexturedAsset>::weak(HandleId::random::<TexturedAsset>());
+        world.insert_resource(TextureLoaded(false));
+        world.insert_resource(ExtractedAssets::<TexturedAsset> {
+            extracted: vec![(handle.clone_weak(), 0)],
+            removed: Vec::new(),
+        });
+        world.init_resource::<RenderAssets<TexturedAsset>>();
+        world.init_resource::<PrepareNextFrameAssets<TexturedAsset>>();
+
+        let mut stage = SystemStage::single(prepare_assets::<TexturedAsset>);
+        stage.run(&mut world);
+        assert_eq!(
+            world.resource::<RenderAssets<TexturedAsset>>().get(&handle),
+            Some(&"fallback")
+        );
+
+        // the asset keeps its fallback while the texture is loading
+        stage.run(&mut world);
+        assert_eq!(
+            world.resource::<RenderAssets<TexturedAsset>>().get(&handle),
+            Some(&"fallback")
+        );
+
+        world.resource_mut::<TextureLoaded>().0 = true;
+        stage.run(&mut world);
+        assert_eq!(
+            world.resource::<RenderAssets<TexturedAsset>>().get(&handle),
+            Some(&"texture")
+        );
+        assert!(world
+            .resource::<PrepareNextFrameAssets<TexturedAsset>>()
+            .assets
+            .is_empty());
+    }
+
+    /// An asset like a material with a texture and a scalar uniform.
+    #[derive(TypeUuid)]
+    #[uuid = "6d2f4c1e-93a7-4b5e-8f0d-7c3e1a9b2d64"]
+    struct UniformAsset;
+
+    #[derive(Default)]
+    struct BindGroupRebuilds(usize);
+
+    impl RenderAsset for UniformAsset {
+        /// The texture and the scalar.
+        type ExtractedAsset = (u32, f32);
+        type PreparedAsset = (u32, f32);
+        type Param = SResMut<BindGroupRebuilds>;
+
+        fn extract_asset(&self) -> Self::ExtractedAsset {
+            (0, 0.0)
+        }
+
+        fn prepare_asset(
+            extracted_asset: Self::ExtractedAsset,
+            rebuilds: &mut SystemParamItem<Self::Param>,
+        ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+            rebuilds.0 += 1;
+            Ok(extracted_asset)
+        }
+
+        fn update_prepared_asset(
+            (texture, scalar): &Self::ExtractedAsset,
+            prepared_asset: &mut Self::PreparedAsset,
+            _rebuilds: &mut SystemParamItem<Self::Param>,
+        ) -> bool {
+            if *texture != prepared_asset.0 {
+                return false;
+            }
+            prepared_asset.1 = *scalar;
+            true
+        }
+    }
+
+    #[test]
+    fn unchanged_bindings_are_updated_in_place() {
+        let mut world = World::new();
+        let handle = Handle::<UniformAsset>::weak(HandleId::random::<UniformAsset>());
+        world.init_resource::<BindGroupRebuilds>();
+        world.insert_resource(ExtractedAssets::<UniformAsset> {
+            extracted: vec![(handle.clone_weak(), (0, 0.0))],
+            removed: Vec::new(),
+        });
+        world.init_resource::<RenderAssets<UniformAsset>>();
+        world.init_resource::<PrepareNextFrameAssets<UniformAsset>>();
+
+        let mut stage = SystemStage::single(prepare_assets::<UniformAsset>);
+        stage.run(&mut world);
+        world.resource_mut::<BindGroupRebuilds>().0 = 0;
+
+        for i in 1..=100 {
+            world
+                .resource_mut::<ExtractedAssets<UniformAsset>>()
+                .extracted = vec![(handle.clone_weak(), (0, i as f32))];
+            stage.run(&mut world);
+        }
+        assert_eq!(world.resource::<BindGroupRebuilds>().0, 0);
+        assert_eq!(
+            world.resource::<RenderAssets<UniformAsset>>().get(&handle),
+            Some(&(0, 100.0))
+        );
+
+        // swapping the texture requires a new bind group
+        world
+            .resource_mut::<ExtractedAssets<UniformAsset>>()
+            .extracted = vec![(handle.clone_weak(), (1, 100.0))];
+        stage.run(&mut world);
+        assert_eq!(world.resource::<BindGroupRebuilds>().0, 1);
+        assert_eq!(
+            world.resource::<RenderAssets<UniformAsset>>().get(&handle),
+            Some(&(1, 100.0))
+        );
+    }
+
+    #[test]
+    fn outdated_queued_assets_are_dropped() {
+        let mut world = World::new();
+        let handle = Handle::<TexturedAsset>::weak(HandleId::random::<TexturedAsset>());
+        world.insert_resource(TextureLoaded(false));
+        world.insert_resource(ExtractedAssets::<TexturedAsset> {
+            extracted: vec![(handle.clone_weak(), 0)],
+            removed: Vec::new(),
+        });
+        world.init_resource::<RenderAssets<TexturedAsset>>();
+        world.init_resource::<PrepareNextFrameAssets<TexturedAsset>>();
+
+        let mut stage = SystemStage::single(prepare_assets::<TexturedAsset>);
+        stage.run(&mut world);
+
+        // the asset is modified while it is queued, only its new version is prepared
+        world
+            .resource_mut::<ExtractedAssets<TexturedAsset>>()
+            .extracted = vec![(handle.clone_weak(), 1)];
+        stage.run(&mut world);
+        let queued = &world
+            .resource::<PrepareNextFrameAssets<TexturedAsset>>()
+            .assets;
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].1, 1);
+
+        // removing the asset releases its fallback and isn't undone by its queued preparation
+        world
+            .resource_mut::<ExtractedAssets<TexturedAsset>>()
+            .removed = vec![handle.clone_weak()];
+        world.resource_mut::<TextureLoaded>().0 = true;
+        stage.run(&mut world);
+        assert!(world
+            .resource::<RenderAssets<TexturedAsset>>()
+            .get(&handle)
+            .is_none());
+        assert!(world
+            .resource::<PrepareNextFrameAssets<TexturedAsset>>()
+            .assets
+            .is_empty());
+    }
+
+    #[test]
+    fn dependencies_track_dependents() {
+        let image = Handle::<Image>::weak(HandleId::random::<Image>());
+        let other_image = Handle::<Image>::weak(HandleId::random::<Image>());
+        let first = Handle::<TexturedAsset>::weak(HandleId::random::<TexturedAsset>());
+        let second = Handle::<TexturedAsset>::weak(HandleId::random::<TexturedAsset>());
+
+        let mut dependencies = RenderAssetDependencies::<TexturedAsset, Image>::default();
+        dependencies.set(&first, vec![image.clone_weak(), other_image.clone_weak()]);
+        dependencies.set(&second, vec![image.clone_weak()]);
+        let mut dependents = dependencies.dependents(&image).cloned().collect::<Vec<_>>();
+        dependents.sort();
+        let mut expected = vec![first.clone_weak(), second.clone_weak()];
+        expected.sort();
+        assert_eq!(dependents, expected);
+
+        // modifying an asset replaces its dependencies
+        dependencies.set(&first, vec![other_image.clone_weak()]);
+        assert_eq!(
+            dependencies.dependents(&image).collect::<Vec<_>>(),
+            vec![&second]
+        );
+        assert_eq!(
+            dependencies.dependents(&other_image).collect::<Vec<_>>(),
+            vec![&first]
+        );
+
+        dependencies.remove(&second);
+        assert_eq!(dependencies.dependents(&image).count(), 0);
+        assert!(!dependencies.dependents.contains_key(&image));
+    }
+
+    #[test]
+    fn weak_dependencies_dont_keep_images_alive() {
+        let mut app = App::new();
+        app.add_plugin(CorePlugin)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Image>()
+            .add_asset::<WeakTexturedAsset>()
+            .add_plugin(RenderAssetDependencyPlugin::<WeakTexturedAsset, Image>::default());
+
+        let image = app
+            .world
+            .resource_mut::<Assets<Image>>()
+            .add(Image::default());
+        let texture = image.clone_weak();
+        let asset = app
+            .world
+            .resource_mut::<Assets<WeakTexturedAsset>>()
+            .add(WeakTexturedAsset {
+                texture: texture.clone_weak(),
+            });
+        // asset events are sent after `PostUpdate`, so the dependencies are set in the next frame
+        app.update();
+        app.update();
+        assert_eq!(
+            app.world
+                .resource::<RenderAssetDependencies<WeakTexturedAsset, Image>>()
+                .dependents(&texture)
+                .collect::<Vec<_>>(),
+            vec![&asset]
+        );
+
+        // dropping the only strong handle frees the image, which prepares the asset again
+        drop(image);
+        let mut reader = app
+            .world
+            .resource::<Events<AssetEvent<WeakTexturedAsset>>>()
+            .get_reader_current();
+        let mut prepared_again = false;
+        for _ in 0..5 {
+            app.update();
+            let events = app
+                .world
+                .resource::<Events<AssetEvent<WeakTexturedAsset>>>();
+            prepared_again |= reader
+                .iter(events)
+                .any(|event| matches!(event, AssetEvent::Modified { handle } if handle == &asset));
+        }
+        assert!(app
+            .world
+            .resource::<Assets<Image>>()
+            .get(&texture)
+            .is_none());
+        assert!(prepared_again);
+    }
+
+    /// A material whose prepared asset is the id of the bind group created for its texture.
+    #[derive(TypeUuid)]
+    #[uuid = "4a9e2c7b-51d3-4f08-b6e2-9c0d8a3f1e75"]
+    struct TexturedMaterial {
+        texture: Handle<Image>,
+    }
+
+    #[derive(Default)]
+    struct NextBindGroupId(u32);
+
+    impl RenderAsset for TexturedMaterial {
+        type ExtractedAsset = ();
+        type PreparedAsset = u32;
+        type Param = SResMut<NextBindGroupId>;
+
+        fn extract_asset(&self) -> Self::ExtractedAsset {}
+
+        fn prepare_asset(
+            _extracted_asset: Self::ExtractedAsset,
+            next_id: &mut SystemParamItem<Self::Param>,
+        ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+            next_id.0 += 1;
+            Ok(next_id.0)
+        }
+    }
+
+    impl DependentRenderAsset<Image> for TexturedMaterial {
+        fn dependencies(&self) -> Vec<Handle<Image>> {
+            vec![self.texture.clone_weak()]
+        }
+    }
+
+    #[test]
+    fn modified_images_rebuild_dependent_bind_groups() {
+        let mut app = App::new();
+        app.add_plugin(CorePlugin)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Image>()
+            .add_asset::<TexturedMaterial>()
+            .add_plugin(RenderAssetDependencyPlugin::<TexturedMaterial, Image>::default())
+            .init_resource::<NextBindGroupId>()
+            .init_resource::<ExtractedAssets<TexturedMaterial>>()
+            .init_resource::<RenderAssets<TexturedMaterial>>()
+            .init_resource::<PrepareNextFrameAssets<TexturedMaterial>>()
+            .add_system_to_stage(CoreStage::Last, extract_render_asset::<TexturedMaterial>);
+        let mut prepare = SystemStage::single(prepare_assets::<TexturedMaterial>);
+        let mut run_frames = |app: &mut App| {
+            for _ in 0..3 {
+                app.update();
+                prepare.run(&mut app.world);
+            }
+        };
+
+        let mut images = app.world.resource_mut::<Assets<Image>>();
+        let image = images.add(Image::default());
+        let other_image = images.add(Image::default());
+        let mut materials = app.world.resource_mut::<Assets<TexturedMaterial>>();
+        let material = materials.add(TexturedMaterial {
+            texture: image.clone(),
+        });
+        let other_material = materials.add(TexturedMaterial {
+            texture: other_image.clone(),
+        });
+        run_frames(&mut app);
+        let bind_groups = |app: &App| {
+            let render_assets = app.world.resource::<RenderAssets<TexturedMaterial>>();
+            (render_assets[&material], render_assets[&other_material])
+        };
+        let (bind_group, other_bind_group) = bind_groups(&app);
+        assert_ne!(bind_group, other_bind_group);
+
+        // e.g. the image file was hot reloaded
+        app.world.resource_mut::<Assets<Image>>().get_mut(&image);
+        run_frames(&mut app);
+        let (rebuilt_bind_group, unchanged_bind_group) = bind_groups(&app);
+        assert_ne!(rebuilt_bind_group, bind_group);
+        assert_eq!(unchanged_bind_group, other_bind_group);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    render_component::UniformComponentBindings,
+    render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
+};
+use bevy_ecs::{
+    component::Component,
+    prelude::*,
+    system::{
+        lifetimeless::{Read, SQuery, SRes},
+        SystemParamItem,
+    },
+};
+use smallvec::SmallVec;
+use std::marker::PhantomData;
+
+/// How often the uniforms of a component type bound in [`UniformComponentBindings`] are rebound
+/// while drawing, which decides the bind group they are bound in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum UniformFrequency {
+    /// Bound once per frame, e.g. the time or other globals. All entities with the component
+    /// have to share the same uniforms.
+    Frame,
+    /// Bound once per view, from the components of the view entity.
+    View,
+    /// Bound for every drawn entity.
+    #[default]
+    Object,
+}
+
+/// The dynamic offsets of the uniforms of a view in the bind group of the uniforms bound per view
+/// in the [`UniformComponentBindings`] of the pipeline `P`, in binding order.
+#[derive(Component)]
+pub struct UniformComponentViewOffsets<P: Send + Sync + 'static> {
+    pub(super) offsets: SmallVec<[u32; 4]>,
+    pub(super) marker: PhantomData<fn() -> P>,
+}
+
+impl<P: Send + Sync + 'static> UniformComponentViewOffsets<P> {
+    #[inline]
+    pub fn offsets(&self) -> &[u32] {
+        &self.offsets
+    }
+}
+
+/// Sets the bind group of the uniforms bound per frame in the [`UniformComponentBindings`] of the
+/// pipeline `P` at the index `I`.
+pub struct SetUniformComponentFrameBindGroup<P, const I: usize>(PhantomData<fn() -> P>);
+
+impl<P: Send + Sync + 'static, const I: usize> EntityRenderCommand
+    for SetUniformComponentFrameBindGroup<P, I>
+{
+    type Param = SRes<UniformComponentBindings<P>>;
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        _item: Entity,
+        bindings: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let bindings = bindings.into_inner();
+        let (bind_group, offsets) = match (
+            bindings.bind_group_for(UniformFrequency::Frame),
+            bindings.frame_offsets(),
+        ) {
+            (Some(bind_group), Some(offsets)) => (bind_group, offsets),
+            _ => return RenderCommandResult::Failure,
+        };
+        pass.set_bind_group(I, bind_group, offsets);
+        RenderCommandResult::Success
+    }
+}
+
+/// Sets the bind group of the uniforms bound per view in the [`UniformComponentBindings`] of the
+/// pipeline `P` at the index `I`, with the dynamic offsets of the view.
+pub struct SetUniformComponentViewBindGroup<P, const I: usize>(PhantomData<fn() -> P>);
+
+impl<P: Send + Sync + 'static, const I: usize> EntityRenderCommand
+    for SetUniformComponentViewBindGroup<P, I>
+{
+    type Param = (
+        SRes<UniformComponentBindings<P>>,
+        SQuery<Read<UniformComponentViewOffsets<P>>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        view: Entity,
+        _item: Entity,
+        (bindings, offsets): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let bind_group = bindings.into_inner().bind_group_for(UniformFrequency::View);
+        let (bind_group, offsets) = match (bind_group, offsets.get(view)) {
+            (Some(bind_group), Ok(offsets)) => (bind_group, offsets),
+            _ => return RenderCommandResult::Failure,
+        };
+        pass.set_bind_group(I, bind_group, offsets.offsets());
+        RenderCommandResult::Success
+    }
+}
@@ -1,6 +1,19 @@
+mod frequency;
+mod push_constant;
+mod tracked;
+mod uniform;
+mod uniform_asset;
+
+pub use frequency::*;
+pub use push_constant::*;
+pub use tracked::*;
+pub use uniform::*;
+pub use uniform_asset::*;
+
 use crate::{
-    render_resource::{std140::AsStd140, DynamicUniformVec},
-    renderer::{RenderDevice, RenderQueue},
+    diagnostic::RenderStatistics,
+    render_resource::{std140::AsStd140, DynamicUniformVec, UniformTypeRegistry},
+    renderer::{FramesInFlight, RenderDevice, RenderQueue, RenderResourceLimits},
     RenderApp, RenderStage,
 };
 use bevy_app::{App, Plugin};
@@ -11,7 +24,9 @@ use bevy_ecs::{
     query::{FilterFetch, QueryItem, WorldQuery},
     system::{lifetimeless::Read, StaticSystemParam},
 };
-use std::{marker::PhantomData, ops::Deref};
+use bevy_tasks::ComputeTaskPool;
+use bevy_utils::tracing::info_span;
+use std::{any::type_name, marker::PhantomData, ops::Deref};
 
 /// Stores the index of a uniform inside of [`ComponentUniforms`].
 #[derive(Component)]
@@ -49,20 +64,67 @@ pub trait ExtractComponent: Component {
 ///
 /// Therefore it sets up the [`RenderStage::Prepare`](crate::RenderStage::Prepare) step
 /// for the specified [`ExtractComponent`].
-pub struct UniformComponentPlugin<C>(PhantomData<fn() -> C>);
+pub struct UniformComponentPlugin<C> {
+    registry_driven: bool,
+    same_value: Option<fn(&C, &C) -> bool>,
+}
+
+impl<C> UniformComponentPlugin<C> {
+    /// Registers the component type in the [`UniformTypeRegistry`], which prepares its uniforms
+    /// together with all other registered types instead of adding a system for each type.
+    pub fn registry_driven() -> Self {
+        Self {
+            registry_driven: true,
+            same_value: None,
+        }
+    }
+
+    /// Only encodes the components that differ from the component in their uniform slot in the
+    /// last frame, see [`DynamicUniformVec::skip_unchanged`]. This is cheaper for components that
+    /// rarely change, like the transforms of static meshes.
+    pub fn skip_unchanged(mut self) -> Self
+    where
+        C: PartialEq,
+    {
+        self.same_value = Some(C::eq);
+        self
+    }
+}
 
 impl<C> Default for UniformComponentPlugin<C> {
     fn default() -> Self {
-        Self(PhantomData)
+        Self {
+            registry_driven: false,
+            same_value: None,
+        }
     }
 }
 
 impl<C: Component + AsStd140 + Clone> Plugin for UniformComponentPlugin<C> {
     fn build(&self, app: &mut App) {
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
-            render_app
-                .insert_resource(ComponentUniforms::<C>::default())
-                .add_system_to_stage(RenderStage::Prepare, prepare_uniform_components::<C>);
+            let mut component_uniforms = ComponentUniforms::<C>::new(
+                render_app.world.resource::<RenderResourceLimits>(),
+                render_app.world.resource::<FramesInFlight>(),
+            );
+            if let Some(same_value) = self.same_value {
+                component_uniforms.uniforms = component_uniforms
+                    .uniforms
+                    .with_value_comparison(same_value);
+            }
+            render_app.insert_resource(component_uniforms);
+            if self.registry_driven {
+                render_app
+                    .world
+                    .resource_mut::<UniformTypeRegistry>()
+                    .register::<C>()
+                    .prepare_with(Box::new(IntoSystem::into_system(
+                        prepare_uniform_components::<C>,
+                    )));
+            } else {
+                render_app
+                    .add_system_to_stage(RenderStage::Prepare, prepare_uniform_components::<C>);
+            }
         }
     }
 }
@@ -82,31 +144,56 @@ impl<C: Component + AsStd140> Deref for ComponentUniforms<C> {
 }
 
 impl<C: Component + AsStd140> ComponentUniforms<C> {
+    /// Creates the uniforms with slots that are aligned according to the `limits` of the device,
+    /// and a uniform buffer for each of the `frames` in flight.
+    pub fn new(limits: &RenderResourceLimits, frames: &FramesInFlight) -> Self {
+        Self {
+            uniforms: DynamicUniformVec::new(limits).with_frames_in_flight(frames),
+        }
+    }
+
     #[inline]
     pub fn uniforms(&self) -> &DynamicUniformVec<C> {
         &self.uniforms
     }
+
+    /// Uploads all uniforms next frame, not only the ones that changed, see
+    /// [`UniformVec::force_upload`](crate::render_resource::UniformVec::force_upload).
+    #[inline]
+    pub fn force_upload(&mut self) {
+        self.uniforms.force_upload();
+    }
 }
 
 impl<C: Component + AsStd140> Default for ComponentUniforms<C> {
     fn default() -> Self {
-        Self {
-            uniforms: Default::default(),
-        }
+        Self::new(&RenderResourceLimits::default(), &FramesInFlight::default())
     }
 }
 
 /// This system prepares all components of the corresponding component type.
 /// They are transformed into uniforms and stored in the [`ComponentUniforms`] resource.
+/// Only the uniforms that changed since the last frame are uploaded to the GPU. The offsets are
+/// assigned in order, the uniforms of many entities are then encoded in parallel, unless the
+/// plugin [skips unchanged components](UniformComponentPlugin::skip_unchanged), which only
+/// encodes the changed ones.
 fn prepare_uniform_components<C: Component>(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
+    task_pool: Res<ComputeTaskPool>,
+    statistics: Res<RenderStatistics>,
     mut component_uniforms: ResMut<ComponentUniforms<C>>,
     components: Query<(Entity, &C)>,
 ) where
     C: AsStd140 + Clone,
 {
+    let gather_span = info_span!(
+        target: "bevy_render::uniforms",
+        "gather_uniforms",
+        uniform_type = type_name::<C>()
+    )
+    .entered();
     component_uniforms.uniforms.clear();
     let entities = components
         .iter()
@@ -121,10 +208,20 @@ fn prepare_uniform_components<C: Component>(
         })
         .collect::<Vec<_>>();
     commands.insert_or_spawn_batch(entities);
+    gather_span.exit();
 
-    component_uniforms
-        .uniforms
-        .write_buffer(&render_device, &render_queue);
+    let _write_span = info_span!(
+        target: "bevy_render::uniforms",
+        "write_uniforms",
+        uniform_type = type_name::<C>()
+    )
+    .entered();
+    let writes = component_uniforms.uniforms.write_buffer_parallel(
+        &render_device,
+        &render_queue,
+        &task_pool,
+    );
+    statistics.record_uniform_writes(writes);
 }
 
 /// This plugin extracts the components into the "render world".
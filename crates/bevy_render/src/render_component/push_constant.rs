@@ -0,0 +1,295 @@
+use crate::{
+    render_component::{ComponentUniforms, DynamicUniformIndex, UniformComponentPlugin},
+    render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
+    render_resource::{
+        std140::{AsStd140, Std140},
+        BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+        BindGroupLayoutEntry, BindingType, BufferBindingType, BufferSize, PushConstantRange,
+        RenderPipelineDescriptor, ShaderStages,
+    },
+    renderer::RenderDevice,
+    RenderApp, RenderStage,
+};
+use bevy_app::{App, Plugin};
+use bevy_ecs::{
+    component::Component,
+    prelude::*,
+    system::{
+        lifetimeless::{Read, SQuery, SRes},
+        SystemParamItem,
+    },
+};
+use std::{any::type_name, marker::PhantomData};
+
+/// This plugin passes the components of the corresponding type to the draws of their entities as
+/// push constants, e.g. an object index or a tint, which doesn't need a uniform buffer slot or a
+/// dynamic offset per entity.
+///
+/// Push constants require [`wgpu::Features::PUSH_CONSTANTS`] and a large enough
+/// [`max_push_constant_size`](RenderDevice::max_push_constant_size). On devices without them, the
+/// components are prepared by a [`UniformComponentPlugin`](super::UniformComponentPlugin) and
+/// bound as a dynamic uniform instead. Pipelines support both with
+/// [`ComponentPushConstants::specialize`], and draws set the value with
+/// [`SetPushConstantComponent`]. The component has to be extracted into the render world, e.g. by
+/// an [`ExtractComponentPlugin`](super::ExtractComponentPlugin).
+pub struct PushConstantComponentPlugin<C> {
+    stages: ShaderStages,
+    marker: PhantomData<fn() -> C>,
+}
+
+impl<C> PushConstantComponentPlugin<C> {
+    /// Passes the components to the given shader `stages`.
+    pub fn new(stages: ShaderStages) -> Self {
+        Self {
+            stages,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Component + AsStd140 + Clone> Plugin for PushConstantComponentPlugin<C> {
+    fn build(&self, app: &mut App) {
+        let push_constants = match app.get_sub_app_mut(RenderApp) {
+            Ok(render_app) => ComponentPushConstants::<C>::new(
+                render_app.world.resource::<RenderDevice>(),
+                self.stages,
+            ),
+            Err(_) => return,
+        };
+        let fallback = !push_constants.uses_push_constants();
+        if fallback {
+            app.add_plugin(UniformComponentPlugin::<C>::default());
+        }
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.insert_resource(push_constants);
+        if fallback {
+            render_app.add_system_to_stage(
+                RenderStage::Queue,
+                queue_push_constant_fallback_bind_group::<C>,
+            );
+        }
+    }
+}
+
+/// How the [`PushConstantComponentPlugin`] passes the components of type `C` to the shaders.
+///
+/// With push constants, the std140 representation of the component is pushed at offset `0`, so a
+/// pipeline reads push constants of a single component type. Otherwise it's bound as a dynamic
+/// uniform at binding `0` of its own bind group, and [`Self::FALLBACK_SHADER_DEF`] is set, so that
+/// shaders can declare whichever the device supports:
+///
+/// ```wgsl
+/// #ifdef PUSH_CONSTANT_FALLBACK
+/// [[group(2), binding(0)]]
+/// var<uniform> tint: Tint;
+/// #else
+/// var<push_constant> tint: Tint;
+/// #endif
+/// ```
+pub struct ComponentPushConstants<C> {
+    stages: ShaderStages,
+    fallback_layout: Option<BindGroupLayout>,
+    fallback_bind_group: Option<BindGroup>,
+    marker: PhantomData<fn() -> C>,
+}
+
+impl<C: Component + AsStd140> ComponentPushConstants<C> {
+    /// The shader def set for the pipelines reading the components from a uniform buffer.
+    pub const FALLBACK_SHADER_DEF: &'static str = "PUSH_CONSTANT_FALLBACK";
+
+    /// Uses push constants if the device supports enough of them for `C`, and a uniform otherwise.
+    pub fn new(render_device: &RenderDevice, stages: ShaderStages) -> Self {
+        let fallback_layout = (!render_device.supports_push_constants(Self::size())).then(|| {
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some(type_name::<C>()),
+                entries: &[Self::fallback_layout_entry(stages)],
+            })
+        });
+        Self {
+            stages,
+            fallback_layout,
+            fallback_bind_group: None,
+            marker: PhantomData,
+        }
+    }
+
+    fn size() -> u32 {
+        C::std140_size_static() as u32
+    }
+
+    /// The entry of the fallback uniform in its bind group layout.
+    fn fallback_layout_entry(stages: ShaderStages) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding: 0,
+            visibility: stages,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: BufferSize::new(Self::size() as u64),
+            },
+            count: None,
+        }
+    }
+
+    #[inline]
+    pub fn uses_push_constants(&self) -> bool {
+        self.fallback_layout.is_none()
+    }
+
+    /// Declares the push constant range of the components in the `descriptor`, or, if the device
+    /// doesn't support it, inserts the layout of the fallback uniform at the bind group index
+    /// `group` and sets the [`Self::FALLBACK_SHADER_DEF`]. Bind groups from `group` on are moved
+    /// back by one in that case.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `group` is larger than the number of bind groups of the `descriptor`.
+    pub fn specialize(&self, descriptor: &mut RenderPipelineDescriptor, group: usize) {
+        match &self.fallback_layout {
+            None => descriptor.push_constant_ranges.push(PushConstantRange {
+                stages: self.stages,
+                range: 0..Self::size(),
+            }),
+            Some(layout) => {
+                descriptor
+                    .layout
+                    .get_or_insert_with(Vec::new)
+                    .insert(group, layout.clone());
+                descriptor.push_shader_def(Self::FALLBACK_SHADER_DEF, self.stages);
+            }
+        }
+    }
+}
+
+/// Recreates the bind group of the fallback uniforms, whose buffer changes when it grows.
+fn queue_push_constant_fallback_bind_group<C: Component + AsStd140>(
+    render_device: Res<RenderDevice>,
+    mut push_constants: ResMut<ComponentPushConstants<C>>,
+    component_uniforms: Res<ComponentUniforms<C>>,
+) {
+    let push_constants = &mut *push_constants;
+    push_constants.fallback_bind_group = match (
+        &push_constants.fallback_layout,
+        component_uniforms.uniforms().binding(),
+    ) {
+        (Some(layout), Some(binding)) => {
+            Some(render_device.create_bind_group(&BindGroupDescriptor {
+                label: Some(type_name::<C>()),
+                layout,
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: binding,
+                }],
+            }))
+        }
+        _ => None,
+    };
+}
+
+/// Passes the component `C` of the entity to the shaders, as push constants or, on devices that
+/// don't support them, by setting the fallback uniform bind group at the index `I`. This has to
+/// match the `group` the pipeline was specialized with, see [`ComponentPushConstants::specialize`].
+pub struct SetPushConstantComponent<C, const I: usize>(PhantomData<fn() -> C>);
+
+impl<C: Component + AsStd140, const I: usize> EntityRenderCommand
+    for SetPushConstantComponent<C, I>
+{
+    type Param = (
+        SRes<ComponentPushConstants<C>>,
+        SQuery<(Read<C>, Option<Read<DynamicUniformIndex<C>>>)>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (push_constants, query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let push_constants = push_constants.into_inner();
+        let (component, index) = match query.get(item) {
+            Ok(item) => item,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        if push_constants.uses_push_constants() {
+            let value = component.as_std140();
+            pass.set_push_constants(push_constants.stages, 0, value.as_bytes());
+            return RenderCommandResult::Success;
+        }
+        match (&push_constants.fallback_bind_group, index) {
+            (Some(bind_group), Some(index)) => {
+                pass.set_bind_group(I, bind_group, &[index.index()]);
+                RenderCommandResult::Success
+            }
+            _ => RenderCommandResult::Failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ComponentPushConstants;
+    use crate::{
+        render_resource::{
+            std140::AsStd140, BindingType, BufferBindingType, BufferSize, ShaderStages,
+        },
+        renderer::max_push_constant_size,
+        test_util::pipeline_descriptor,
+    };
+    use bevy_ecs::component::Component;
+    use bevy_math::Vec4;
+    use std::marker::PhantomData;
+    use wgpu::{Features, Limits};
+
+    #[derive(Component, AsStd140, Clone)]
+    struct Tint {
+        color: Vec4,
+    }
+
+    #[test]
+    fn push_constant_ranges_are_declared() {
+        let push_constants = ComponentPushConstants::<Tint> {
+            stages: ShaderStages::FRAGMENT,
+            fallback_layout: None,
+            fallback_bind_group: None,
+            marker: PhantomData,
+        };
+        assert!(push_constants.uses_push_constants());
+        let mut descriptor = pipeline_descriptor();
+        push_constants.specialize(&mut descriptor, 1);
+        assert_eq!(descriptor.push_constant_ranges.len(), 1);
+        assert_eq!(
+            descriptor.push_constant_ranges[0].stages,
+            ShaderStages::FRAGMENT
+        );
+        assert_eq!(descriptor.push_constant_ranges[0].range, 0..16);
+        assert!(descriptor.layout.is_none());
+        assert!(descriptor.fragment.unwrap().shader_defs.is_empty());
+    }
+
+    #[test]
+    fn push_constants_fall_back_to_uniforms() {
+        let size = ComponentPushConstants::<Tint>::size();
+        let limits = Limits {
+            max_push_constant_size: 128,
+            ..Default::default()
+        };
+        // devices without the feature don't support any push constants, whatever their limits
+        assert_eq!(max_push_constant_size(Features::empty(), &limits), 0);
+        assert!(size <= max_push_constant_size(Features::PUSH_CONSTANTS, &limits));
+
+        // the fallback binds the std140 representation as a dynamic uniform
+        let entry = ComponentPushConstants::<Tint>::fallback_layout_entry(ShaderStages::FRAGMENT);
+        assert_eq!(entry.binding, 0);
+        assert_eq!(entry.visibility, ShaderStages::FRAGMENT);
+        assert_eq!(
+            entry.ty,
+            BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: BufferSize::new(size as u64),
+            }
+        );
+    }
+}
@@ -0,0 +1,668 @@
+use crate::{
+    diagnostic::RenderStatistics,
+    render_component::DynamicUniformIndex,
+    render_resource::{
+        coalesce_ranges,
+        std140::{AsStd140, DirtyMask, DirtyStd140, DirtyTracker, Std140},
+        BindingResource, Buffer, BufferBinding, BufferDescriptor, BufferGrowthPolicy, BufferSize,
+        BufferUsages, UniformWrites, MAX_COALESCED_GAP,
+    },
+    renderer::{RenderDevice, RenderQueue, RenderResourceLimits},
+    RenderApp, RenderStage,
+};
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::{component::Component, prelude::*};
+use bevy_utils::{tracing::info_span, HashMap};
+use std::{
+    any::type_name,
+    marker::PhantomData,
+    ops::{Deref, Range},
+};
+
+/// A component with a uniform value that records which of its fields changed, so that the
+/// [`TrackedUniformComponentPlugin`] only uploads those fields, e.g. a material with a single
+/// animated parameter.
+///
+/// The fields are set with the `{Struct}Setters` trait generated by `#[derive(DirtyStd140)]`,
+/// which mark them as dirty.
+#[derive(Component)]
+pub struct Tracked<T: DirtyStd140 + Send + Sync + 'static> {
+    value: T,
+    dirty: T::Dirty,
+}
+
+impl<T: DirtyStd140 + Send + Sync + 'static> Tracked<T> {
+    /// Wraps the `value`, all of its fields are uploaded at first.
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            dirty: T::Dirty::ALL,
+        }
+    }
+
+    /// The fields that changed since the value was last extracted.
+    #[inline]
+    pub fn dirty(&self) -> T::Dirty {
+        self.dirty
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: DirtyStd140 + Send + Sync + 'static> Deref for Tracked<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T: DirtyStd140 + Send + Sync + 'static> DirtyTracker<T> for Tracked<T> {
+    #[inline]
+    fn untracked_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    #[inline]
+    fn mark_dirty(&mut self, dirty: T::Dirty) {
+        self.dirty |= dirty;
+    }
+}
+
+/// This plugin prepares the values of the [`Tracked`] components of the corresponding type for the
+/// GPU, like the [`UniformComponentPlugin`](super::UniformComponentPlugin), but only uploads the
+/// fields that were marked as dirty.
+///
+/// Every entity keeps the slot of its value in the [`TrackedUniforms`] resource while it has the
+/// component, so unchanged values aren't written at all. The slot is released when the entity
+/// despawns or the component is removed, and the uniform buffer shrinks again once most of its
+/// slots are unused. A [`DynamicUniformIndex`] of the `Tracked<T>` component is inserted for every
+/// entity.
+pub struct TrackedUniformComponentPlugin<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for TrackedUniformComponentPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: DirtyStd140 + Clone + Send + Sync + 'static> Plugin for TrackedUniformComponentPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if app.get_sub_app(RenderApp).is_err() {
+            return;
+        }
+        // the removals are cleared in `CoreStage::Last`, before the render world is extracted
+        app.init_resource::<RemovedTracked<T>>()
+            .add_system_to_stage(CoreStage::PostUpdate, collect_removed_tracked::<T>);
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            let limits = *render_app.world.resource::<RenderResourceLimits>();
+            render_app
+                .insert_resource(TrackedUniforms::<T>::new(&limits))
+                .init_resource::<RemovedTracked<T>>()
+                .add_system_to_stage(RenderStage::Extract, extract_tracked_uniforms::<T>)
+                .add_system_to_stage(RenderStage::Prepare, prepare_tracked_uniforms::<T>);
+        }
+    }
+}
+
+/// The value of a [`Tracked`] component and the fields that changed, in the "render world".
+#[derive(Component)]
+pub struct ExtractedTracked<T: DirtyStd140 + Send + Sync + 'static> {
+    pub value: T,
+    pub dirty: T::Dirty,
+}
+
+/// The entities that despawned or lost their [`Tracked`] component since the last extraction.
+pub struct RemovedTracked<T> {
+    entities: Vec<Entity>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for RemovedTracked<T> {
+    fn default() -> Self {
+        Self {
+            entities: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// This system collects the entities that lost their [`Tracked`] component in the app world.
+fn collect_removed_tracked<T: DirtyStd140 + Send + Sync + 'static>(
+    mut removed_tracked: ResMut<RemovedTracked<T>>,
+    removed: RemovedComponents<Tracked<T>>,
+) {
+    removed_tracked.entities.extend(removed.iter());
+}
+
+/// Stores the uniforms of all [`Tracked`] components of the type, in a slot per entity.
+pub struct TrackedUniforms<T: DirtyStd140> {
+    slots: HashMap<Entity, usize>,
+    previous_slots: HashMap<Entity, usize>,
+    /// The released slots in descending order, so that the lowest ones are reused first and
+    /// released slots at the end of the buffer can be dropped.
+    free_slots: Vec<usize>,
+    slot_size: usize,
+    allocated_slots: u64,
+    released_slots: u64,
+    /// The contents of the uniform buffer.
+    shadow: Vec<u8>,
+    /// The ranges of the `shadow` that changed since the last upload.
+    dirty_ranges: Vec<Range<usize>>,
+    coalesced_ranges: Vec<Range<usize>>,
+    changed_values: usize,
+    uniform_buffer: Option<Buffer>,
+    /// The number of slots the uniform buffer has room for.
+    capacity: usize,
+    growth_policy: BufferGrowthPolicy,
+    underused_writes: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DirtyStd140> TrackedUniforms<T> {
+    /// Creates the uniforms with slots that are aligned according to the `limits` of the device.
+    pub fn new(limits: &RenderResourceLimits) -> Self {
+        Self {
+            slots: Default::default(),
+            previous_slots: Default::default(),
+            free_slots: Vec::new(),
+            slot_size: limits
+                .uniform_slot_size(T::std140_size_static(), <T as AsStd140>::Output::ALIGNMENT),
+            allocated_slots: 0,
+            released_slots: 0,
+            shadow: Vec::new(),
+            dirty_ranges: Vec::new(),
+            coalesced_ranges: Vec::new(),
+            changed_values: 0,
+            uniform_buffer: None,
+            capacity: 0,
+            growth_policy: BufferGrowthPolicy::default(),
+            underused_writes: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Sets how the capacity of the uniform buffer follows the number of slots in use.
+    pub fn with_growth_policy(mut self, growth_policy: BufferGrowthPolicy) -> Self {
+        self.growth_policy = growth_policy;
+        self
+    }
+
+    #[inline]
+    pub fn uniform_buffer(&self) -> Option<&Buffer> {
+        self.uniform_buffer.as_ref()
+    }
+
+    #[inline]
+    pub fn binding(&self) -> Option<BindingResource> {
+        Some(BindingResource::Buffer(BufferBinding {
+            buffer: self.uniform_buffer()?,
+            offset: 0,
+            size: BufferSize::new(T::std140_size_static() as u64),
+        }))
+    }
+
+    /// Returns the dynamic offset of the uniform of the entity.
+    pub fn offset(&self, entity: Entity) -> Option<u32> {
+        Some((self.slots.get(&entity)? * self.slot_size) as u32)
+    }
+
+    /// The number of entities with a slot.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Releases the slots of the `entities`, which despawned or lost their component.
+    fn release(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        for entity in entities {
+            if let Some(slot) = self.slots.remove(&entity) {
+                self.free_slots.push(slot);
+                self.released_slots += 1;
+            }
+        }
+    }
+
+    /// Returns the number of slots that were allocated and released since the last call.
+    fn take_slot_counts(&mut self) -> (u64, u64) {
+        (
+            std::mem::take(&mut self.allocated_slots),
+            std::mem::take(&mut self.released_slots),
+        )
+    }
+
+    /// Writes the dirty fields of the `values` of this frame into the shadow copy of the uniform
+    /// buffer. Entities without a slot get one and write their whole value, and the slots of the
+    /// entities that are missing are freed.
+    fn update<'a>(&mut self, values: impl Iterator<Item = (Entity, &'a T, T::Dirty)>)
+    where
+        T: 'a,
+    {
+        let _span = info_span!(
+            target: "bevy_render::uniforms",
+            "gather_uniforms",
+            uniform_type = type_name::<T>()
+        )
+        .entered();
+        let Self {
+            slots,
+            previous_slots,
+            free_slots,
+            slot_size,
+            allocated_slots,
+            released_slots,
+            shadow,
+            dirty_ranges,
+            changed_values,
+            ..
+        } = self;
+        std::mem::swap(slots, previous_slots);
+        for (entity, value, dirty) in values {
+            let slot = match previous_slots.remove(&entity) {
+                Some(slot) => {
+                    if !dirty.is_empty() {
+                        *changed_values += 1;
+                    }
+                    let start = slot * *slot_size;
+                    let bytes = &mut shadow[start..start + *slot_size];
+                    value.write_dirty_std140(dirty, bytes, &mut |range| {
+                        dirty_ranges.push(start + range.start..start + range.end);
+                    });
+                    slot
+                }
+                None => {
+                    let slot = free_slots.pop().unwrap_or_else(|| {
+                        shadow.resize(shadow.len() + *slot_size, 0);
+                        shadow.len() / *slot_size - 1
+                    });
+                    *allocated_slots += 1;
+                    *changed_values += 1;
+                    let value = value.as_std140();
+                    let value = value.as_bytes();
+                    let start = slot * *slot_size;
+                    shadow[start..start + value.len()].copy_from_slice(value);
+                    dirty_ranges.push(start..start + value.len());
+                    slot
+                }
+            };
+            slots.insert(entity, slot);
+        }
+        // entities that weren't extracted again lost their component or despawned after the
+        // removals were collected
+        *released_slots += previous_slots.len() as u64;
+        free_slots.extend(previous_slots.drain().map(|(_, slot)| slot));
+
+        free_slots.sort_unstable_by(|a, b| b.cmp(a));
+        let mut len = shadow.len() / *slot_size;
+        let trailing = free_slots
+            .iter()
+            .take_while(|slot| {
+                let is_last = **slot + 1 == len;
+                len -= is_last as usize;
+                is_last
+            })
+            .count();
+        free_slots.drain(..trailing);
+        shadow.truncate(len * *slot_size);
+    }
+
+    /// Lets the capacity of the uniform buffer follow the number of slots in use. Returns `true` if
+    /// the buffer has to be reallocated, in which case the whole shadow copy is uploaded again.
+    fn update_capacity(&mut self) -> bool {
+        let capacity = self.growth_policy.next_capacity(
+            self.capacity,
+            self.shadow.len() / self.slot_size,
+            &mut self.underused_writes,
+        );
+        if capacity == self.capacity {
+            return false;
+        }
+        self.capacity = capacity;
+        self.dirty_ranges.clear();
+        self.dirty_ranges.push(0..self.shadow.len());
+        true
+    }
+
+    /// Uploads the changed ranges of the shadow copy to the uniform buffer. Everything is uploaded
+    /// if the buffer had to be reallocated to make room for more slots.
+    fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) -> UniformWrites {
+        let _span = info_span!(
+            target: "bevy_render::uniforms",
+            "write_uniforms",
+            uniform_type = type_name::<T>()
+        )
+        .entered();
+        if self.update_capacity() {
+            self.uniform_buffer = Some(device.create_buffer(&BufferDescriptor {
+                label: Some("tracked_uniform_buffer"),
+                size: (self.capacity * self.slot_size) as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+                mapped_at_creation: false,
+            }));
+        }
+        // ranges of slots released since they were written aren't uploaded
+        let len = self.shadow.len();
+        self.dirty_ranges.retain(|range| range.end <= len);
+        let mut writes = UniformWrites {
+            values: std::mem::take(&mut self.changed_values) as u64,
+            ..Default::default()
+        };
+        let buffer = match &self.uniform_buffer {
+            Some(buffer) => buffer,
+            None => return writes,
+        };
+        self.dirty_ranges.sort_unstable_by_key(|range| range.start);
+        coalesce_ranges(
+            self.dirty_ranges.drain(..),
+            MAX_COALESCED_GAP,
+            &mut self.coalesced_ranges,
+        );
+        for range in self.coalesced_ranges.drain(..) {
+            queue.write_buffer(buffer, range.start as u64, &self.shadow[range.clone()]);
+            writes.bytes += range.len() as u64;
+            writes.writes += 1;
+        }
+        writes
+    }
+}
+
+/// This system extracts the values of the [`Tracked`] components with the fields that changed,
+/// and resets the dirty fields.
+fn extract_tracked_uniforms<T: DirtyStd140 + Clone + Send + Sync + 'static>(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    mut removed: ResMut<RemovedTracked<T>>,
+    mut query: Query<(Entity, &mut Tracked<T>)>,
+) {
+    commands.insert_resource(RemovedTracked::<T> {
+        entities: std::mem::take(&mut removed.entities),
+        marker: PhantomData,
+    });
+    let mut values = Vec::with_capacity(*previous_len);
+    for (entity, mut tracked) in query.iter_mut() {
+        let dirty = tracked.dirty;
+        // only write to the component if it changed, so it isn't marked as changed every frame
+        if !dirty.is_empty() {
+            tracked.dirty = Default::default();
+        }
+        let value = tracked.value.clone();
+        values.push((entity, (ExtractedTracked { value, dirty },)));
+    }
+    *previous_len = values.len();
+    commands.insert_or_spawn_batch(values);
+}
+
+/// This system writes the dirty fields of the [`Tracked`] components to the [`TrackedUniforms`]
+/// and uploads them.
+fn prepare_tracked_uniforms<T: DirtyStd140 + Send + Sync + 'static>(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    statistics: Res<RenderStatistics>,
+    removed: Res<RemovedTracked<T>>,
+    mut tracked_uniforms: ResMut<TrackedUniforms<T>>,
+    extracted: Query<(Entity, &ExtractedTracked<T>)>,
+) {
+    tracked_uniforms.release(removed.entities.iter().copied());
+    tracked_uniforms.update(
+        extracted
+            .iter()
+            .map(|(entity, extracted)| (entity, &extracted.value, extracted.dirty)),
+    );
+    statistics.record_uniform_writes(tracked_uniforms.write_buffer(&render_device, &render_queue));
+    let (allocated, released) = tracked_uniforms.take_slot_counts();
+    statistics.record_uniform_slots(allocated, released);
+
+    let entities = extracted
+        .iter()
+        .map(|(entity, _)| {
+            let index = DynamicUniformIndex::<Tracked<T>> {
+                index: tracked_uniforms.offset(entity).unwrap(),
+                marker: PhantomData,
+            };
+            (entity, (index,))
+        })
+        .collect::<Vec<_>>();
+    commands.insert_or_spawn_batch(entities);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_removed_tracked, RemovedTracked, Tracked, TrackedUniforms};
+    use crate::{
+        diagnostic::RenderStatistics,
+        render_component::{AssetUniforms, ExtractedUniformAssets},
+        render_resource::{
+            std140::{AsStd140, DirtyStd140, DirtyTracker},
+            BufferGrowthPolicy,
+        },
+        renderer::RenderResourceLimits,
+    };
+    use bevy_ecs::{
+        entity::Entity,
+        schedule::{Stage, SystemStage},
+        world::World,
+    };
+    use bevy_math::Vec4;
+    use bevy_reflect::TypeUuid;
+    use bevy_utils::tracing::{
+        field::{Field, Visit},
+        span, subscriber, Event, Metadata, Subscriber,
+    };
+    use parking_lot::Mutex;
+    use std::{any::type_name, fmt::Debug, ops::Range, sync::Arc};
+
+    #[derive(AsStd140, Clone, TypeUuid)]
+    #[uuid = "5d2f8a61-3c9e-4b07-9a1d-e64b7c0f2d95"]
+    struct ColorMaterial {
+        brightness: f32,
+    }
+
+    #[derive(AsStd140, DirtyStd140, Clone)]
+    struct AnimatedMaterial {
+        base_color: Vec4,
+        roughness: f32,
+        time: f32,
+        emissive: Vec4,
+    }
+
+    fn material() -> Tracked<AnimatedMaterial> {
+        Tracked::new(AnimatedMaterial {
+            base_color: Vec4::splat(0.5),
+            roughness: 0.5,
+            time: 0.0,
+            emissive: Vec4::ZERO,
+        })
+    }
+
+    /// Extracts the `materials` for a frame and returns the ranges that have to be uploaded.
+    fn update(
+        uniforms: &mut TrackedUniforms<AnimatedMaterial>,
+        materials: &mut [(Entity, Tracked<AnimatedMaterial>)],
+    ) -> Vec<Range<usize>> {
+        uniforms.update(
+            materials
+                .iter()
+                .map(|(entity, material)| (*entity, &material.value, material.dirty)),
+        );
+        for (_, material) in materials {
+            material.dirty = Default::default();
+        }
+        std::mem::take(&mut uniforms.dirty_ranges)
+    }
+
+    #[test]
+    fn only_dirty_fields_are_uploaded() {
+        let mut uniforms = TrackedUniforms::new(&RenderResourceLimits::default());
+        let (first, second, third) = (
+            Entity::from_raw(0),
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+        );
+        let mut materials = [(first, material()), (second, material())];
+        // new values are written completely
+        assert_eq!(update(&mut uniforms, &mut materials), [0..48, 256..304]);
+        let before = uniforms.shadow.clone();
+
+        materials[1].1.set_time(1.0);
+        // changes that bypass the setters aren't tracked and aren't written
+        materials[1].1.untracked_mut().roughness = 1.0;
+        let time = 256 + 20..256 + 24;
+        assert_eq!(update(&mut uniforms, &mut materials), [time.clone()]);
+        assert_eq!(uniforms.shadow[time.clone()], 1.0f32.to_ne_bytes());
+        // the untouched bytes are identical
+        assert_eq!(uniforms.shadow[..time.start], before[..time.start]);
+        assert_eq!(uniforms.shadow[time.end..], before[time.end..]);
+
+        // unchanged values aren't written, and the slots of missing entities are reused
+        let [_, (_, second_material)] = materials;
+        let mut materials = [(second, second_material)];
+        assert!(update(&mut uniforms, &mut materials).is_empty());
+        let [(_, second_material)] = materials;
+        let mut materials = [(second, second_material), (third, material())];
+        assert_eq!(update(&mut uniforms, &mut materials), [0..48]);
+        assert_eq!(uniforms.offset(third), Some(0));
+        assert_eq!(uniforms.offset(second), Some(256));
+        assert_eq!(uniforms.offset(first), None);
+    }
+
+    #[test]
+    fn despawned_entities_release_their_slots() {
+        let statistics = RenderStatistics::default();
+        let mut world = World::new();
+        world.init_resource::<RemovedTracked<AnimatedMaterial>>();
+        let mut collect_removed = SystemStage::single(collect_removed_tracked::<AnimatedMaterial>);
+        let mut materials = world.query::<(Entity, &Tracked<AnimatedMaterial>)>();
+        let mut uniforms = TrackedUniforms::new(&RenderResourceLimits::default());
+        let mut capacities = Vec::new();
+        // 1k entities are spawned and despawned again and again
+        for _ in 0..10 {
+            let entities = (0..1000)
+                .map(|_| world.spawn().insert(material()).id())
+                .collect::<Vec<_>>();
+            uniforms.update(
+                materials
+                    .iter(&world)
+                    .map(|(entity, material)| (entity, &material.value, material.dirty)),
+            );
+            uniforms.update_capacity();
+            capacities.push(uniforms.capacity);
+
+            for entity in entities {
+                world.despawn(entity);
+            }
+            collect_removed.run(&mut world);
+            world.clear_trackers();
+            let removed = std::mem::take(
+                &mut world
+                    .resource_mut::<RemovedTracked<AnimatedMaterial>>()
+                    .entities,
+            );
+            uniforms.release(removed);
+            assert!(uniforms.is_empty());
+            for _ in 0..BufferGrowthPolicy::default().shrink_delay {
+                uniforms.update(std::iter::empty());
+                uniforms.update_capacity();
+            }
+            assert!(uniforms.shadow.is_empty());
+            capacities.push(uniforms.capacity);
+
+            let (allocated, released) = uniforms.take_slot_counts();
+            statistics.record_uniform_slots(allocated, released);
+            let frame = statistics.take();
+            assert_eq!(frame.uniform_slots_allocated, 1000);
+            assert_eq!(frame.uniform_slots_released, 1000);
+        }
+        // the uniform buffer shrinks after the entities despawned and doesn't keep growing
+        assert!(capacities
+            .chunks(2)
+            .all(|capacities| capacities == [2000, 1]));
+    }
+
+    /// Records the target, name and fields of every span created while it is the default
+    /// subscriber.
+    #[derive(Clone, Default)]
+    struct CapturedSpans(Arc<Mutex<Vec<(String, String, Vec<(String, String)>)>>>);
+
+    struct FieldRecorder(Vec<(String, String)>);
+
+    impl Visit for FieldRecorder {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    impl Subscriber for CapturedSpans {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &span::Attributes) -> span::Id {
+            let mut fields = FieldRecorder(Vec::new());
+            attributes.record(&mut fields);
+            let metadata = attributes.metadata();
+            let mut spans = self.0.lock();
+            spans.push((
+                metadata.target().to_string(),
+                metadata.name().to_string(),
+                fields.0,
+            ));
+            span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &span::Id, _values: &span::Record) {}
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, _event: &Event) {}
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    #[test]
+    fn uniform_updates_are_traced() {
+        let captured = CapturedSpans::default();
+        let mut uniforms = TrackedUniforms::new(&RenderResourceLimits::default());
+        let mut materials = [(Entity::from_raw(0), material())];
+        update(&mut uniforms, &mut materials);
+        materials[0].1.set_time(1.0);
+        let mut asset_uniforms = AssetUniforms::<ColorMaterial>::default();
+        subscriber::with_default(captured.clone(), || {
+            assert_eq!(update(&mut uniforms, &mut materials).len(), 1);
+            asset_uniforms.update(ExtractedUniformAssets::default());
+        });
+
+        let field = |value: &str| vec![("uniform_type".to_string(), value.to_string())];
+        let spans = captured.0.lock();
+        assert_eq!(
+            *spans,
+            [
+                (
+                    "bevy_render::uniforms".to_string(),
+                    "gather_uniforms".to_string(),
+                    field(type_name::<AnimatedMaterial>()),
+                ),
+                (
+                    "bevy_render::uniforms".to_string(),
+                    "gather_uniforms".to_string(),
+                    field(type_name::<ColorMaterial>()),
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1187 @@
+use crate::{
+    diagnostic::RenderStatistics,
+    render_component::{
+        ComponentUniforms, DynamicUniformIndex, UniformComponentViewOffsets, UniformFrequency,
+    },
+    render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
+    render_resource::{
+        std140::AsStd140, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+        BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+        BufferBinding, BufferBindingType, BufferId, BufferSize, ShaderStages,
+    },
+    renderer::{FramesInFlight, RenderDevice, RenderResourceLimits, UniformBindingSizeError},
+    RenderApp, RenderStage,
+};
+use bevy_app::{App, Plugin};
+use bevy_ecs::{
+    component::Component,
+    prelude::*,
+    system::{
+        lifetimeless::{Read, SQuery, SRes},
+        SystemParamItem,
+    },
+};
+use bevy_utils::{
+    tracing::{error, info_span},
+    HashMap,
+};
+use smallvec::SmallVec;
+use std::{
+    any::{type_name, TypeId},
+    borrow::Cow,
+    marker::PhantomData,
+};
+use thiserror::Error;
+
+/// This plugin binds the uniforms of the component type `C` for the pipeline `P`, in a single bind
+/// group with the uniforms of the other component types bound for it, e.g. a base material and an
+/// add-on component with the parameters of a wind effect.
+///
+/// The uniforms are prepared by the [`UniformComponentPlugin`](super::UniformComponentPlugin) of
+/// the component type. The component types are collected in the [`UniformComponentBindings`]
+/// resource, which provides the merged bind group layout and shader defs for the pipeline, and the
+/// bind group is set with the [`SetUniformComponentBindGroup`] render command. Uniforms only
+/// needed by some passes of the pipeline can be restricted to them with
+/// [`BindUniformComponentPlugin::in_passes`], and uniforms shared by all entities of a frame or
+/// view can be bound separately with [`BindUniformComponentPlugin::with_frequency`].
+///
+/// # Panics
+///
+/// Panics if the name can't be bound, see [`UniformComponentBindingError`].
+pub struct BindUniformComponentPlugin<P, C> {
+    name: Cow<'static, str>,
+    passes: Option<Vec<Cow<'static, str>>>,
+    frequency: UniformFrequency,
+    marker: PhantomData<fn() -> (P, C)>,
+}
+
+impl<P, C> BindUniformComponentPlugin<P, C> {
+    /// Binds the uniforms under the `name`, see [`UniformComponentBindings`].
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            name: name.into(),
+            passes: None,
+            frequency: UniformFrequency::Object,
+            marker: PhantomData,
+        }
+    }
+
+    /// Only binds the uniforms in the named `passes` instead of in all passes, see
+    /// [`UniformComponentBindings::bind_in_passes`].
+    pub fn in_passes<N: Into<Cow<'static, str>>>(
+        mut self,
+        passes: impl IntoIterator<Item = N>,
+    ) -> Self {
+        self.passes = Some(passes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Binds the uniforms with the given `frequency` instead of per entity, see
+    /// [`UniformComponentBindings::bind_with_frequency`].
+    pub fn with_frequency(mut self, frequency: UniformFrequency) -> Self {
+        self.frequency = frequency;
+        self
+    }
+}
+
+impl<P: Send + Sync + 'static, C: Component + AsStd140> Plugin
+    for BindUniformComponentPlugin<P, C>
+{
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            if !render_app
+                .world
+                .contains_resource::<UniformComponentBindings<P>>()
+            {
+                let limits = *render_app.world.resource::<RenderResourceLimits>();
+                render_app
+                    .insert_resource(UniformComponentBindings::<P>::new(limits))
+                    .add_system_to_stage(
+                        RenderStage::Queue,
+                        queue_uniform_component_bindings::<P>
+                            .label(UniformComponentBindingSystem::Queue)
+                            .after(UniformComponentBindingSystem::Collect),
+                    );
+            }
+            render_app.add_system_to_stage(
+                RenderStage::Queue,
+                collect_uniform_component_offsets::<P, C>
+                    .label(UniformComponentBindingSystem::Collect),
+            );
+            let mut bindings = render_app
+                .world
+                .resource_mut::<UniformComponentBindings<P>>();
+            if let Err(err) =
+                bindings.bind_filtered::<C>(self.name.clone(), self.passes.clone(), self.frequency)
+            {
+                panic!("{}", err);
+            }
+        }
+    }
+}
+
+/// The systems queueing the bind groups of the [`UniformComponentBindings`].
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UniformComponentBindingSystem {
+    /// Collects the uniform buffer and dynamic offsets of each bound component type.
+    Collect,
+    /// Creates the bind groups and inserts the offsets of the entities.
+    Queue,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum UniformComponentBindingError {
+    #[error("the uniforms of `{first}` and `{second}` are both bound as `{name}`")]
+    NameCollision {
+        name: String,
+        first: &'static str,
+        second: &'static str,
+    },
+    #[error("the uniforms of `{0}` are already bound")]
+    AlreadyBound(&'static str),
+    #[error("`{0}` is not a valid shader def, only letters, digits and underscores can be used")]
+    InvalidName(String),
+    #[error("the uniforms of `{0}` were bound after the bind group layout was created")]
+    LayoutCreated(&'static str),
+    #[error("the uniforms of `{0}` are restricted to an empty list of passes")]
+    NoPasses(&'static str),
+    #[error(
+        "the uniforms of `{0}` are bound per frame or per view and can't be restricted to passes"
+    )]
+    FrequencyWithPasses(&'static str),
+    #[error(
+        "the uniforms of `{0}` are bound per frame, but differ between the entities with them"
+    )]
+    FrameUniformsDiffer(&'static str),
+    #[error(transparent)]
+    BindingSize(#[from] UniformBindingSizeError),
+}
+
+/// The uniforms of the component types bound in a single bind group for the pipeline `P`, see
+/// [`BindUniformComponentPlugin`].
+///
+/// The uniforms are bound in the order their component types were bound in, each as a uniform
+/// buffer with a dynamic offset. The binding index of each component type is available to the
+/// shader as a shader def with its uppercase name, so the uniforms bound as `"wind"` are declared
+/// with `[[group(2), binding(#{WIND})]]` and can be tested for with `#ifdef WIND`. The entries of
+/// the layouts carry these names too, so the
+/// [`BindingValidation`](crate::render_resource::BindingValidation) of the pipeline cache reports
+/// uniform variables named differently than their component types were bound as, like `winds`.
+///
+/// Only entities with all of the component types are drawn by the
+/// [`SetUniformComponentBindGroup`] render command.
+///
+/// Component types bound with [`bind_in_passes`](Self::bind_in_passes) are left out of the bind
+/// groups of all other passes, e.g. a shadow pass only binding the transform of a material. Each
+/// pass has its own layout, shader defs and bind group, in which the remaining uniforms keep their
+/// order but are renumbered from zero, so a shader compiled with the shader defs of a pass only
+/// declares the uniforms of the pass. A shader that uses a left out binding anyway fails the
+/// binding validation of the pipeline cache. The bind group of a pass is set with the
+/// [`SetUniformComponentPassBindGroup`] render command.
+///
+/// Component types bound with [`bind_with_frequency`](Self::bind_with_frequency) are grouped into
+/// a bind group for each [`UniformFrequency`] instead, numbered from zero like the bind groups of
+/// passes. The bind groups of frames and views are set with the
+/// [`SetUniformComponentFrameBindGroup`](super::SetUniformComponentFrameBindGroup) and
+/// [`SetUniformComponentViewBindGroup`](super::SetUniformComponentViewBindGroup) render commands,
+/// which only rebind them when the view changes, even as part of the draw function of every
+/// entity.
+///
+/// Each pipeline type `P` has bindings of its own, which the [`BindUniformComponentPlugin`] adds
+/// component types to.
+pub struct UniformComponentBindings<P> {
+    bindings: Vec<UniformComponentBinding>,
+    limits: RenderResourceLimits,
+    layouts: HashMap<UniformFrequency, BindGroupLayout>,
+    bind_groups: HashMap<UniformFrequency, BindGroup>,
+    pass_layouts: HashMap<Cow<'static, str>, BindGroupLayout>,
+    pass_bind_groups: HashMap<Cow<'static, str>, BindGroup>,
+    frame_offsets: Option<SmallVec<[u32; 4]>>,
+    /// The bind groups of the recent frames, so that the bind groups of the uniform buffers of
+    /// each frame in flight are only created once.
+    cached_bind_groups: Vec<CachedBindGroup>,
+    marker: PhantomData<fn() -> P>,
+}
+
+struct CachedBindGroup {
+    frequency: UniformFrequency,
+    pass: Option<Cow<'static, str>>,
+    buffers: SmallVec<[BufferId; 4]>,
+    bind_group: BindGroup,
+}
+
+struct UniformComponentBinding {
+    name: Cow<'static, str>,
+    shader_def: String,
+    component_type: &'static str,
+    type_id: TypeId,
+    size: u64,
+    /// The passes the uniforms are bound in, or `None` if they are bound in all passes.
+    passes: Option<Vec<Cow<'static, str>>>,
+    frequency: UniformFrequency,
+    /// The uniform buffer of the component type, if it has been prepared this frame.
+    buffer: Option<Buffer>,
+    /// The dynamic offsets of the entities with the component type this frame.
+    offsets: HashMap<Entity, u32>,
+}
+
+impl UniformComponentBinding {
+    fn is_in_pass(&self, pass: &str) -> bool {
+        self.passes
+            .as_ref()
+            .map_or(true, |passes| passes.iter().any(|bound| bound == pass))
+    }
+}
+
+impl<P> UniformComponentBindings<P> {
+    /// Creates the bindings, which check the size of the uniforms against the `limits` of the
+    /// device.
+    pub fn new(limits: RenderResourceLimits) -> Self {
+        Self {
+            bindings: Vec::new(),
+            limits,
+            layouts: HashMap::default(),
+            bind_groups: HashMap::default(),
+            pass_layouts: HashMap::default(),
+            pass_bind_groups: HashMap::default(),
+            frame_offsets: None,
+            cached_bind_groups: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<P> Default for UniformComponentBindings<P> {
+    fn default() -> Self {
+        Self::new(RenderResourceLimits::default())
+    }
+}
+
+impl<P: Send + Sync + 'static> UniformComponentBindings<P> {
+    /// Binds the uniforms of the component type `C` under the `name`.
+    ///
+    /// The name has to be a valid shader def and mustn't collide with the name of another
+    /// component type, ignoring case. The uniforms have to fit into a uniform buffer binding.
+    pub fn bind<C: Component + AsStd140>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+    ) -> Result<(), UniformComponentBindingError> {
+        self.bind_filtered::<C>(name.into(), None, UniformFrequency::Object)
+    }
+
+    /// Binds the uniforms of the component type `C` under the `name` like [`Self::bind`], but
+    /// only in the named `passes`.
+    pub fn bind_in_passes<C: Component + AsStd140, N: Into<Cow<'static, str>>>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        passes: impl IntoIterator<Item = N>,
+    ) -> Result<(), UniformComponentBindingError> {
+        let passes = passes.into_iter().map(Into::into).collect();
+        self.bind_filtered::<C>(name.into(), Some(passes), UniformFrequency::Object)
+    }
+
+    /// Binds the uniforms of the component type `C` under the `name` like [`Self::bind`], but in
+    /// the bind group of the given `frequency`.
+    pub fn bind_with_frequency<C: Component + AsStd140>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        frequency: UniformFrequency,
+    ) -> Result<(), UniformComponentBindingError> {
+        self.bind_filtered::<C>(name.into(), None, frequency)
+    }
+
+    fn bind_filtered<C: Component + AsStd140>(
+        &mut self,
+        name: Cow<'static, str>,
+        passes: Option<Vec<Cow<'static, str>>>,
+        frequency: UniformFrequency,
+    ) -> Result<(), UniformComponentBindingError> {
+        let component_type = type_name::<C>();
+        if !self.layouts.is_empty() || !self.pass_layouts.is_empty() {
+            return Err(UniformComponentBindingError::LayoutCreated(component_type));
+        }
+        if let Some(passes) = &passes {
+            if passes.is_empty() {
+                return Err(UniformComponentBindingError::NoPasses(component_type));
+            }
+            if frequency != UniformFrequency::Object {
+                return Err(UniformComponentBindingError::FrequencyWithPasses(
+                    component_type,
+                ));
+            }
+        }
+        let mut chars = name.chars();
+        let is_identifier = chars
+            .next()
+            .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(UniformComponentBindingError::InvalidName(name.into_owned()));
+        }
+        let size = C::std140_size_static();
+        self.limits.validate_uniform_binding(component_type, size)?;
+        let shader_def = name.to_ascii_uppercase();
+        for binding in &self.bindings {
+            if binding.type_id == TypeId::of::<C>() {
+                return Err(UniformComponentBindingError::AlreadyBound(component_type));
+            }
+            if binding.shader_def == shader_def {
+                return Err(UniformComponentBindingError::NameCollision {
+                    name: name.into_owned(),
+                    first: binding.component_type,
+                    second: component_type,
+                });
+            }
+        }
+        self.bindings.push(UniformComponentBinding {
+            name,
+            shader_def,
+            component_type,
+            type_id: TypeId::of::<C>(),
+            size: size as u64,
+            passes,
+            frequency,
+            buffer: None,
+            offsets: HashMap::default(),
+        });
+        Ok(())
+    }
+
+    /// Replaces the uniform buffer and the dynamic offsets of the component type `C` with the ones
+    /// of the current frame.
+    fn collect<C: Component>(
+        &mut self,
+        buffer: Option<Buffer>,
+        offsets: impl Iterator<Item = (Entity, u32)>,
+    ) {
+        if let Some(binding) = self
+            .bindings
+            .iter_mut()
+            .find(|binding| binding.type_id == TypeId::of::<C>())
+        {
+            binding.buffer = buffer;
+            binding.offsets.clear();
+            binding.offsets.extend(offsets);
+        }
+    }
+
+    fn bindings_for(
+        &self,
+        frequency: UniformFrequency,
+    ) -> impl Iterator<Item = &UniformComponentBinding> {
+        self.bindings
+            .iter()
+            .filter(move |binding| binding.frequency == frequency)
+    }
+
+    /// The names of the bound component types, in binding order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.bindings.iter().map(|binding| &*binding.name)
+    }
+
+    /// The shader defs of the bound component types, each defined as its binding index in the
+    /// bind group of its [`UniformFrequency`].
+    pub fn shader_defs(&self) -> impl Iterator<Item = String> + '_ {
+        [
+            UniformFrequency::Frame,
+            UniformFrequency::View,
+            UniformFrequency::Object,
+        ]
+        .into_iter()
+        .flat_map(|frequency| self.shader_defs_for(frequency))
+    }
+
+    /// The shader defs of the component types bound with the given `frequency`.
+    pub fn shader_defs_for(
+        &self,
+        frequency: UniformFrequency,
+    ) -> impl Iterator<Item = String> + '_ {
+        shader_defs(self.bindings_for(frequency))
+    }
+
+    /// The entries of the merged bind group layout of the uniforms bound per entity.
+    pub fn layout_entries(&self) -> Vec<BindGroupLayoutEntry> {
+        self.layout_entries_for(UniformFrequency::Object)
+    }
+
+    /// The entries of the bind group layout of the uniforms bound with the given `frequency`.
+    pub fn layout_entries_for(&self, frequency: UniformFrequency) -> Vec<BindGroupLayoutEntry> {
+        layout_entries(self.bindings_for(frequency))
+    }
+
+    /// Returns the merged bind group layout of the uniforms bound per entity, which is created on
+    /// first use. No component types can be bound afterwards.
+    pub fn layout(&mut self, render_device: &RenderDevice) -> BindGroupLayout {
+        self.layout_for(render_device, UniformFrequency::Object)
+    }
+
+    /// Returns the bind group layout of the uniforms bound with the given `frequency`, which is
+    /// created on first use like [`Self::layout`].
+    pub fn layout_for(
+        &mut self,
+        render_device: &RenderDevice,
+        frequency: UniformFrequency,
+    ) -> BindGroupLayout {
+        if let Some(layout) = self.layouts.get(&frequency) {
+            return layout.clone();
+        }
+        let layout = render_device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("uniform_component_layout"),
+                entries: &self.layout_entries_for(frequency),
+            })
+            .with_entry_names(entry_names(self.bindings_for(frequency)));
+        self.layouts.insert(frequency, layout.clone());
+        layout
+    }
+
+    /// The bind group of the uniforms bound per entity in the current frame, if all uniform
+    /// buffers have been created.
+    #[inline]
+    pub fn bind_group(&self) -> Option<&BindGroup> {
+        self.bind_group_for(UniformFrequency::Object)
+    }
+
+    /// The bind group of the uniforms bound with the given `frequency` in the current frame, if
+    /// all uniform buffers have been created.
+    #[inline]
+    pub fn bind_group_for(&self, frequency: UniformFrequency) -> Option<&BindGroup> {
+        self.bind_groups.get(&frequency)
+    }
+
+    /// The dynamic offsets of the uniforms bound per frame, if all of them have been prepared.
+    #[inline]
+    pub fn frame_offsets(&self) -> Option<&[u32]> {
+        self.frame_offsets.as_deref()
+    }
+
+    /// The passes named by [`Self::bind_in_passes`], in the order they were first named in.
+    pub fn passes(&self) -> Vec<Cow<'static, str>> {
+        let mut passes = Vec::<Cow<'static, str>>::new();
+        for pass in self
+            .bindings
+            .iter()
+            .flat_map(|binding| binding.passes.iter().flatten())
+        {
+            if !passes.contains(pass) {
+                passes.push(pass.clone());
+            }
+        }
+        passes
+    }
+
+    fn bindings_in_pass<'a>(
+        &'a self,
+        pass: &'a str,
+    ) -> impl Iterator<Item = &'a UniformComponentBinding> {
+        self.bindings_for(UniformFrequency::Object)
+            .filter(move |binding| binding.is_in_pass(pass))
+    }
+
+    /// The names of the component types bound in the `pass`, in binding order.
+    pub fn names_in_pass<'a>(&'a self, pass: &'a str) -> impl Iterator<Item = &'a str> {
+        self.bindings_in_pass(pass).map(|binding| &*binding.name)
+    }
+
+    /// The shader defs of the component types bound in the `pass`, each defined as its binding
+    /// index in the pass.
+    pub fn shader_defs_in_pass<'a>(&'a self, pass: &'a str) -> impl Iterator<Item = String> + 'a {
+        shader_defs(self.bindings_in_pass(pass))
+    }
+
+    /// The entries of the bind group layout of the `pass`.
+    pub fn layout_entries_in_pass(&self, pass: &str) -> Vec<BindGroupLayoutEntry> {
+        layout_entries(self.bindings_in_pass(pass))
+    }
+
+    /// Returns the bind group layout of the `pass`, which is created on first use like
+    /// [`Self::layout`]. The bind group of the pass is created every frame from then on.
+    pub fn layout_for_pass(&mut self, render_device: &RenderDevice, pass: &str) -> BindGroupLayout {
+        if let Some(layout) = self.pass_layouts.get(pass) {
+            return layout.clone();
+        }
+        let layout = render_device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("uniform_component_pass_layout"),
+                entries: &self.layout_entries_in_pass(pass),
+            })
+            .with_entry_names(entry_names(self.bindings_in_pass(pass)));
+        let pass = self
+            .passes()
+            .into_iter()
+            .find(|named| named == pass)
+            .unwrap_or_else(|| Cow::Owned(pass.to_string()));
+        self.pass_layouts.insert(pass, layout.clone());
+        layout
+    }
+
+    /// The bind group of the `pass` in the current frame, if all uniform buffers have been created.
+    #[inline]
+    pub fn bind_group_for_pass(&self, pass: &str) -> Option<&BindGroup> {
+        self.pass_bind_groups.get(pass)
+    }
+
+    /// Selects the dynamic offsets of the component types bound in the `pass` from the `offsets`
+    /// of an entity.
+    pub fn offsets_in_pass(
+        &self,
+        pass: &str,
+        offsets: &UniformComponentOffsets<P>,
+    ) -> SmallVec<[u32; 4]> {
+        self.bindings_for(UniformFrequency::Object)
+            .zip(offsets.offsets())
+            .filter(|(binding, _)| binding.is_in_pass(pass))
+            .map(|(_, offset)| *offset)
+            .collect()
+    }
+
+    /// The frequencies with a bind group, which are the frequencies of the bound component types
+    /// and always include [`UniformFrequency::Object`].
+    fn frequencies(&self) -> Vec<UniformFrequency> {
+        [UniformFrequency::Frame, UniformFrequency::View]
+            .into_iter()
+            .filter(|frequency| self.bindings_for(*frequency).next().is_some())
+            .chain([UniformFrequency::Object])
+            .collect()
+    }
+
+    /// Returns the bind group of the component types bound with the `frequency` and in the
+    /// `pass`, or in all passes without one, reusing the bind group of the same uniform `buffers`
+    /// of a recent frame.
+    fn cached_bind_group(
+        &mut self,
+        render_device: &RenderDevice,
+        frequency: UniformFrequency,
+        pass: Option<Cow<'static, str>>,
+        buffers: &[(Buffer, u64)],
+        frames_in_flight: usize,
+        statistics: &RenderStatistics,
+    ) -> BindGroup {
+        let buffers = self
+            .bindings
+            .iter()
+            .zip(buffers)
+            .filter(|(binding, _)| {
+                binding.frequency == frequency
+                    && pass.as_ref().map_or(true, |pass| binding.is_in_pass(pass))
+            })
+            .map(|(_, (buffer, size))| (buffer, *size))
+            .collect::<SmallVec<[_; 4]>>();
+        let ids = buffers
+            .iter()
+            .map(|(buffer, _)| buffer.id())
+            .collect::<SmallVec<[BufferId; 4]>>();
+        let is_same_group =
+            |cached: &CachedBindGroup| cached.frequency == frequency && cached.pass == pass;
+        if let Some(cached) = self
+            .cached_bind_groups
+            .iter()
+            .find(|cached| is_same_group(cached) && cached.buffers == ids)
+        {
+            return cached.bind_group.clone();
+        }
+
+        let layout = match &pass {
+            Some(pass) => self.layout_for_pass(render_device, pass),
+            None => self.layout_for(render_device, frequency),
+        };
+        let _span = info_span!(
+            target: "bevy_render::uniforms",
+            "create_bind_group",
+            pipeline = type_name::<P>()
+        )
+        .entered();
+        let entries = buffers
+            .into_iter()
+            .enumerate()
+            .map(|(index, (buffer, size))| BindGroupEntry {
+                binding: index as u32,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: BufferSize::new(size),
+                }),
+            })
+            .collect::<Vec<_>>();
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("uniform_component_bind_group"),
+            layout: &layout,
+            entries: &entries,
+        });
+        // the bind groups of reallocated buffers are dropped once the frames moved past them
+        let mut same_group = self
+            .cached_bind_groups
+            .iter()
+            .enumerate()
+            .filter(|(_, cached)| is_same_group(cached))
+            .map(|(index, _)| index);
+        let mut destroyed = 0;
+        if let Some(oldest) = same_group.next() {
+            if same_group.count() + 1 >= frames_in_flight {
+                self.cached_bind_groups.remove(oldest);
+                destroyed += 1;
+            }
+        }
+        statistics.record_bind_groups(1, destroyed);
+        self.cached_bind_groups.push(CachedBindGroup {
+            frequency,
+            pass,
+            buffers: ids,
+            bind_group: bind_group.clone(),
+        });
+        bind_group
+    }
+
+    /// Returns the dynamic offsets of the entities with all of the component types bound with the
+    /// given `frequency`.
+    fn entity_offsets(&self, frequency: UniformFrequency) -> Vec<(Entity, SmallVec<[u32; 4]>)> {
+        let first = match self.bindings_for(frequency).next() {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+        first
+            .offsets
+            .keys()
+            .filter_map(|entity| {
+                let offsets = self
+                    .bindings_for(frequency)
+                    .map(|binding| binding.offsets.get(entity).copied())
+                    .collect::<Option<_>>()?;
+                Some((*entity, offsets))
+            })
+            .collect()
+    }
+
+    /// Returns the dynamic offsets of the uniforms bound per frame, or `None` if not all of them
+    /// have been prepared. The uniforms of each component type have to be shared by all entities
+    /// with the component.
+    fn collect_frame_offsets(
+        &self,
+    ) -> Result<Option<SmallVec<[u32; 4]>>, UniformComponentBindingError> {
+        let mut frame_offsets = SmallVec::new();
+        for binding in self.bindings_for(UniformFrequency::Frame) {
+            let mut offsets = binding.offsets.values().copied();
+            let offset = match offsets.next() {
+                Some(offset) => offset,
+                None => return Ok(None),
+            };
+            if offsets.any(|other| other != offset) {
+                return Err(UniformComponentBindingError::FrameUniformsDiffer(
+                    binding.component_type,
+                ));
+            }
+            frame_offsets.push(offset);
+        }
+        Ok(Some(frame_offsets))
+    }
+}
+
+/// Defines the shader def of each binding as its index.
+fn shader_defs<'a>(
+    bindings: impl Iterator<Item = &'a UniformComponentBinding> + 'a,
+) -> impl Iterator<Item = String> + 'a {
+    bindings
+        .enumerate()
+        .map(|(index, binding)| format!("{} {}", binding.shader_def, index))
+}
+
+/// Names the layout entries of the `bindings` after the bound component types, which are expected
+/// to match the names of the uniform variables in shaders.
+fn entry_names<'a>(
+    bindings: impl Iterator<Item = &'a UniformComponentBinding> + 'a,
+) -> impl Iterator<Item = (u32, &'a str)> + 'a {
+    bindings
+        .enumerate()
+        .map(|(index, binding)| (index as u32, &*binding.name))
+}
+
+fn layout_entries<'a>(
+    bindings: impl Iterator<Item = &'a UniformComponentBinding>,
+) -> Vec<BindGroupLayoutEntry> {
+    bindings
+        .enumerate()
+        .map(|(index, binding)| BindGroupLayoutEntry {
+            binding: index as u32,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: BufferSize::new(binding.size),
+            },
+            count: None,
+        })
+        .collect()
+}
+
+/// The dynamic offsets of the uniforms of an entity in the bind group of the
+/// [`UniformComponentBindings`] of the pipeline `P`, in binding order.
+#[derive(Component)]
+pub struct UniformComponentOffsets<P: Send + Sync + 'static> {
+    offsets: SmallVec<[u32; 4]>,
+    marker: PhantomData<fn() -> P>,
+}
+
+impl<P: Send + Sync + 'static> UniformComponentOffsets<P> {
+    #[inline]
+    pub fn offsets(&self) -> &[u32] {
+        &self.offsets
+    }
+}
+
+/// This system collects the uniform buffer and the dynamic offsets of the component type `C`
+/// bound for the pipeline `P`.
+fn collect_uniform_component_offsets<P: Send + Sync + 'static, C: Component + AsStd140>(
+    mut bindings: ResMut<UniformComponentBindings<P>>,
+    uniforms: Option<Res<ComponentUniforms<C>>>,
+    indices: Query<(Entity, &DynamicUniformIndex<C>)>,
+) {
+    let buffer = uniforms.and_then(|uniforms| uniforms.uniform_buffer().cloned());
+    bindings.collect::<C>(
+        buffer,
+        indices
+            .iter()
+            .map(|(entity, index)| (entity, index.index())),
+    );
+}
+
+/// This system creates the bind groups of the bound component uniforms and inserts the
+/// [`UniformComponentOffsets`] and [`UniformComponentViewOffsets`] of the entities with all of the
+/// component types bound per entity or per view.
+fn queue_uniform_component_bindings<P: Send + Sync + 'static>(
+    mut commands: Commands,
+    mut bindings: ResMut<UniformComponentBindings<P>>,
+    render_device: Res<RenderDevice>,
+    frames_in_flight: Res<FramesInFlight>,
+    statistics: Res<RenderStatistics>,
+) {
+    let frames_in_flight = frames_in_flight.count();
+    let frequencies = bindings.frequencies();
+    for frequency in &frequencies {
+        bindings.layout_for(&render_device, *frequency);
+    }
+    for pass in bindings.passes() {
+        bindings.layout_for_pass(&render_device, &pass);
+    }
+    let buffers = bindings
+        .bindings
+        .iter()
+        .map(|binding| Some((binding.buffer.clone()?, binding.size)))
+        .collect::<Option<Vec<_>>>();
+    match buffers {
+        Some(buffers) => {
+            for frequency in frequencies {
+                let bind_group = bindings.cached_bind_group(
+                    &render_device,
+                    frequency,
+                    None,
+                    &buffers,
+                    frames_in_flight,
+                    &statistics,
+                );
+                bindings.bind_groups.insert(frequency, bind_group);
+            }
+            let passes = bindings.pass_layouts.keys().cloned().collect::<Vec<_>>();
+            for pass in passes {
+                let bind_group = bindings.cached_bind_group(
+                    &render_device,
+                    UniformFrequency::Object,
+                    Some(pass.clone()),
+                    &buffers,
+                    frames_in_flight,
+                    &statistics,
+                );
+                bindings.pass_bind_groups.insert(pass, bind_group);
+            }
+        }
+        None => {
+            bindings.bind_groups.clear();
+            bindings.pass_bind_groups.clear();
+        }
+    }
+
+    bindings.frame_offsets = match bindings.collect_frame_offsets() {
+        Ok(frame_offsets) => frame_offsets,
+        Err(err) => {
+            error!("{}", err);
+            None
+        }
+    };
+    let view_offsets = bindings
+        .entity_offsets(UniformFrequency::View)
+        .into_iter()
+        .map(|(entity, offsets)| {
+            let offsets = UniformComponentViewOffsets::<P> {
+                offsets,
+                marker: PhantomData,
+            };
+            (entity, (offsets,))
+        })
+        .collect::<Vec<_>>();
+    commands.insert_or_spawn_batch(view_offsets);
+    let offsets = bindings
+        .entity_offsets(UniformFrequency::Object)
+        .into_iter()
+        .map(|(entity, offsets)| {
+            let offsets = UniformComponentOffsets::<P> {
+                offsets,
+                marker: PhantomData,
+            };
+            (entity, (offsets,))
+        })
+        .collect::<Vec<_>>();
+    commands.insert_or_spawn_batch(offsets);
+}
+
+/// Sets the bind group of the [`UniformComponentBindings`] of the pipeline `P` at the index `I`,
+/// with the dynamic offsets of the entity.
+pub struct SetUniformComponentBindGroup<P, const I: usize>(PhantomData<fn() -> P>);
+
+impl<P: Send + Sync + 'static, const I: usize> EntityRenderCommand
+    for SetUniformComponentBindGroup<P, I>
+{
+    type Param = (
+        SRes<UniformComponentBindings<P>>,
+        SQuery<Read<UniformComponentOffsets<P>>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (bindings, offsets): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (bind_group, offsets) = match (bindings.into_inner().bind_group(), offsets.get(item)) {
+            (Some(bind_group), Ok(offsets)) => (bind_group, offsets),
+            _ => return RenderCommandResult::Failure,
+        };
+        pass.set_bind_group(I, bind_group, offsets.offsets());
+        RenderCommandResult::Success
+    }
+}
+
+/// A pass of a pipeline with its own [`UniformComponentBindings`], see
+/// [`SetUniformComponentPassBindGroup`].
+pub trait UniformComponentPass: Send + Sync + 'static {
+    /// The name of the pass, as passed to [`UniformComponentBindings::bind_in_passes`].
+    const NAME: &'static str;
+}
+
+/// Sets the bind group of the pass `N` of the [`UniformComponentBindings`] of the pipeline `P` at
+/// the index `I`, with the dynamic offsets of the entity in the pass.
+pub struct SetUniformComponentPassBindGroup<P, N, const I: usize>(PhantomData<fn() -> (P, N)>);
+
+impl<P: Send + Sync + 'static, N: UniformComponentPass, const I: usize> EntityRenderCommand
+    for SetUniformComponentPassBindGroup<P, N, I>
+{
+    type Param = (
+        SRes<UniformComponentBindings<P>>,
+        SQuery<Read<UniformComponentOffsets<P>>>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (bindings, offsets): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let bindings = bindings.into_inner();
+        let (bind_group, offsets) = match (bindings.bind_group_for_pass(N::NAME), offsets.get(item))
+        {
+            (Some(bind_group), Ok(offsets)) => (bind_group, offsets),
+            _ => return RenderCommandResult::Failure,
+        };
+        pass.set_bind_group(I, bind_group, &bindings.offsets_in_pass(N::NAME, offsets));
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        collect_uniform_component_offsets, UniformComponentBindingError, UniformComponentBindings,
+        UniformComponentOffsets,
+    };
+    use crate::{
+        render_component::{DynamicUniformIndex, UniformFrequency},
+        render_resource::{std140::AsStd140, BindingType, BufferSize},
+    };
+
+    use bevy_ecs::{
+        component::Component,
+        schedule::{Stage, SystemStage},
+        world::World,
+    };
+    use bevy_math::Vec4;
+    use std::{any::type_name, fmt::Debug, marker::PhantomData};
+
+    #[derive(Component, AsStd140, Clone)]
+    struct BaseColor {
+        color: [f32; 4],
+    }
+
+    #[derive(Component, AsStd140, Clone)]
+    struct Wind {
+        strength: f32,
+        frequency: f32,
+    }
+
+    #[derive(Component, AsStd140, Clone)]
+    struct Gust {
+        strength: f32,
+    }
+
+    #[derive(Component, AsStd140, Clone)]
+    struct Tint {
+        color: Vec4,
+    }
+
+    struct FoliagePipeline;
+
+    #[test]
+    fn component_uniforms_are_merged_into_one_bind_group() {
+        let mut bindings = UniformComponentBindings::<FoliagePipeline>::default();
+        bindings.bind::<BaseColor>("base_color").unwrap();
+        bindings.bind::<Wind>("wind").unwrap();
+        assert_eq!(
+            bindings.shader_defs().collect::<Vec<_>>(),
+            ["BASE_COLOR 0", "WIND 1"]
+        );
+        let entries = bindings
+            .layout_entries()
+            .into_iter()
+            .map(|entry| match entry.ty {
+                BindingType::Buffer {
+                    has_dynamic_offset: true,
+                    min_binding_size,
+                    ..
+                } => (entry.binding, min_binding_size),
+                ty => panic!("unexpected binding type {:?}", ty),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (0, BufferSize::new(BaseColor::std140_size_static() as u64)),
+                (1, BufferSize::new(Wind::std140_size_static() as u64)),
+            ]
+        );
+
+        assert_eq!(
+            bindings.bind::<Wind>("gust"),
+            Err(UniformComponentBindingError::AlreadyBound(
+                type_name::<Wind>()
+            ))
+        );
+        assert_eq!(
+            bindings.bind::<Gust>("wind speed"),
+            Err(UniformComponentBindingError::InvalidName(
+                "wind speed".to_string()
+            ))
+        );
+        // the error names both component types
+        let err = bindings.bind::<Gust>("Wind").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the uniforms of `{}` and `{}` are both bound as `Wind`",
+                type_name::<Wind>(),
+                type_name::<Gust>()
+            )
+        );
+        assert_eq!(bindings.names().collect::<Vec<_>>(), ["base_color", "wind"]);
+    }
+
+    #[test]
+    fn component_uniforms_can_be_restricted_to_passes() {
+        let mut bindings = UniformComponentBindings::<FoliagePipeline>::default();
+        bindings
+            .bind_in_passes::<BaseColor, _>("base_color", ["main"])
+            .unwrap();
+        bindings.bind::<Wind>("wind").unwrap();
+        bindings
+            .bind_in_passes::<Gust, _>("gust", ["main", "shadow"])
+            .unwrap();
+        assert_eq!(bindings.passes(), ["main", "shadow"]);
+
+        // all uniforms are bound outside of passes
+        assert_eq!(
+            bindings.shader_defs().collect::<Vec<_>>(),
+            ["BASE_COLOR 0", "WIND 1", "GUST 2"]
+        );
+        assert_eq!(
+            bindings.shader_defs_in_pass("main").collect::<Vec<_>>(),
+            ["BASE_COLOR 0", "WIND 1", "GUST 2"]
+        );
+        // the uniforms of the shadow pass are renumbered
+        assert_eq!(
+            bindings.names_in_pass("shadow").collect::<Vec<_>>(),
+            ["wind", "gust"]
+        );
+        assert_eq!(
+            bindings.shader_defs_in_pass("shadow").collect::<Vec<_>>(),
+            ["WIND 0", "GUST 1"]
+        );
+        let entries = bindings
+            .layout_entries_in_pass("shadow")
+            .into_iter()
+            .map(|entry| (entry.binding, entry.ty))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (0, bindings.layout_entries()[1].ty),
+                (1, bindings.layout_entries()[2].ty),
+            ]
+        );
+        // unnamed passes only bind the unrestricted uniforms
+        assert_eq!(
+            bindings.names_in_pass("prepass").collect::<Vec<_>>(),
+            ["wind"]
+        );
+
+        let offsets = UniformComponentOffsets::<FoliagePipeline> {
+            offsets: [0, 256, 512].into_iter().collect(),
+            marker: PhantomData,
+        };
+        assert_eq!(bindings.offsets_in_pass("shadow", &offsets)[..], [256, 512]);
+        assert_eq!(
+            bindings.offsets_in_pass("main", &offsets)[..],
+            [0, 256, 512]
+        );
+
+        assert_eq!(
+            bindings.bind_in_passes::<Tint, &str>("tint", []),
+            Err(UniformComponentBindingError::NoPasses(type_name::<Tint>()))
+        );
+    }
+
+    #[test]
+    fn only_entities_with_all_bound_components_get_offsets() {
+        let mut bindings = UniformComponentBindings::<FoliagePipeline>::default();
+        bindings.bind::<BaseColor>("base_color").unwrap();
+        bindings.bind::<Wind>("wind").unwrap();
+        let mut world = World::new();
+        world.insert_resource(bindings);
+        let mut collect = SystemStage::parallel()
+            .with_system(collect_uniform_component_offsets::<FoliagePipeline, BaseColor>)
+            .with_system(collect_uniform_component_offsets::<FoliagePipeline, Wind>);
+        let base_color = |index| DynamicUniformIndex::<BaseColor> {
+            index,
+            marker: PhantomData,
+        };
+        let wind = |index| DynamicUniformIndex::<Wind> {
+            index,
+            marker: PhantomData,
+        };
+        let entity = world.spawn().insert(base_color(256)).insert(wind(512)).id();
+        world.spawn().insert(base_color(0));
+        collect.run(&mut world);
+
+        let bindings = world.resource::<UniformComponentBindings<FoliagePipeline>>();
+        assert_eq!(
+            bindings.entity_offsets(UniformFrequency::Object),
+            [(entity, [256, 512].into_iter().collect())]
+        );
+        // the uniform buffers aren't prepared without a `UniformComponentPlugin`
+        assert!(bindings
+            .bindings
+            .iter()
+            .all(|binding| binding.buffer.is_none()));
+    }
+
+    #[test]
+    fn component_uniforms_are_grouped_by_frequency() {
+        let mut bindings = UniformComponentBindings::<FoliagePipeline>::default();
+        bindings.bind::<BaseColor>("base_color").unwrap();
+        bindings
+            .bind_with_frequency::<Wind>("wind", UniformFrequency::Frame)
+            .unwrap();
+        bindings
+            .bind_with_frequency::<Gust>("gust", UniformFrequency::View)
+            .unwrap();
+        assert_eq!(
+            bindings.frequencies(),
+            [
+                UniformFrequency::Frame,
+                UniformFrequency::View,
+                UniformFrequency::Object
+            ]
+        );
+        // each bind group is numbered from zero
+        assert_eq!(
+            bindings.shader_defs().collect::<Vec<_>>(),
+            ["WIND 0", "GUST 0", "BASE_COLOR 0"]
+        );
+        assert_eq!(bindings.layout_entries().len(), 1);
+        let frame_entries = bindings.layout_entries_for(UniformFrequency::Frame);
+        assert_eq!(frame_entries.len(), 1);
+        assert!(matches!(
+            frame_entries[0].ty,
+            BindingType::Buffer { min_binding_size, .. }
+                if min_binding_size == BufferSize::new(Wind::std140_size_static() as u64)
+        ));
+        assert_eq!(
+            bindings.bind_filtered::<Tint>(
+                "tint".into(),
+                Some(vec!["shadow".into()]),
+                UniformFrequency::View
+            ),
+            Err(UniformComponentBindingError::FrequencyWithPasses(
+                type_name::<Tint>()
+            ))
+        );
+
+        let mut world = World::new();
+        world.insert_resource(bindings);
+        let mut collect = SystemStage::parallel()
+            .with_system(collect_uniform_component_offsets::<FoliagePipeline, Wind>)
+            .with_system(collect_uniform_component_offsets::<FoliagePipeline, Gust>)
+            .with_system(collect_uniform_component_offsets::<FoliagePipeline, BaseColor>);
+        let wind = |index| DynamicUniformIndex::<Wind> {
+            index,
+            marker: PhantomData,
+        };
+        world.spawn().insert(wind(256));
+        let view = world
+            .spawn()
+            .insert(DynamicUniformIndex::<Gust> {
+                index: 512,
+                marker: PhantomData,
+            })
+            .id();
+        let entity = world
+            .spawn()
+            .insert(DynamicUniformIndex::<BaseColor> {
+                index: 768,
+                marker: PhantomData,
+            })
+            .id();
+        collect.run(&mut world);
+        let bindings = world.resource::<UniformComponentBindings<FoliagePipeline>>();
+        assert_eq!(
+            bindings.collect_frame_offsets(),
+            Ok(Some([256].into_iter().collect()))
+        );
+        assert_eq!(
+            bindings.entity_offsets(UniformFrequency::View),
+            [(view, [512].into_iter().collect())]
+        );
+        assert_eq!(
+            bindings.entity_offsets(UniformFrequency::Object),
+            [(entity, [768].into_iter().collect())]
+        );
+
+        // the uniforms bound per frame have to be shared by all entities
+        world.spawn().insert(wind(0));
+        collect.run(&mut world);
+        let bindings = world.resource::<UniformComponentBindings<FoliagePipeline>>();
+        assert_eq!(
+            bindings.collect_frame_offsets(),
+            Err(UniformComponentBindingError::FrameUniformsDiffer(
+                type_name::<Wind>()
+            ))
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{
+    diagnostic::RenderStatistics,
+    render_component::DynamicUniformIndex,
+    render_resource::{std140::AsStd140, DynamicUniformVec},
+    renderer::{FramesInFlight, RenderDevice, RenderQueue, RenderResourceLimits},
+    RenderApp, RenderStage,
+};
+use bevy_app::{App, Plugin};
+use bevy_asset::{Asset, AssetEvent, Assets, Handle};
+use bevy_ecs::prelude::*;
+use bevy_utils::{tracing::info_span, HashMap, HashSet};
+use std::{any::type_name, marker::PhantomData, ops::Deref};
+
+/// This plugin prepares assets of the corresponding type for the GPU by transforming them into
+/// uniforms, e.g. materials shared by many entities.
+///
+/// Every asset is stored once in the [`AssetUniforms`] resource, no matter how many entities use
+/// it, and is only uploaded again when it is modified. A [`DynamicUniformIndex`] referencing the
+/// uniform of its asset is inserted for every entity with a `Handle<A>`, which has to be extracted
+/// with an [`ExtractComponentPlugin`](super::ExtractComponentPlugin). Components that differ
+/// between entities should use the [`UniformComponentPlugin`](super::UniformComponentPlugin)
+/// instead.
+pub struct UniformAssetPlugin<A>(PhantomData<fn() -> A>);
+
+impl<A> Default for UniformAssetPlugin<A> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<A: Asset + AsStd140 + Clone> Plugin for UniformAssetPlugin<A> {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            let asset_uniforms = AssetUniforms::<A>::new(
+                render_app.world.resource::<RenderResourceLimits>(),
+                render_app.world.resource::<FramesInFlight>(),
+            );
+            render_app
+                .init_resource::<ExtractedUniformAssets<A>>()
+                .insert_resource(asset_uniforms)
+                .add_system_to_stage(RenderStage::Extract, extract_uniform_assets::<A>)
+                .add_system_to_stage(RenderStage::Prepare, prepare_uniform_assets::<A>);
+        }
+    }
+}
+
+/// The assets that were created, modified or removed since the last frame.
+pub struct ExtractedUniformAssets<A: Asset> {
+    extracted: Vec<(Handle<A>, A)>,
+    removed: Vec<Handle<A>>,
+}
+
+impl<A: Asset> Default for ExtractedUniformAssets<A> {
+    fn default() -> Self {
+        Self {
+            extracted: Default::default(),
+            removed: Default::default(),
+        }
+    }
+}
+
+/// Stores the uniforms of all assets of the asset type.
+pub struct AssetUniforms<A: Asset + AsStd140> {
+    uniforms: DynamicUniformVec<A>,
+    assets: Vec<(Handle<A>, A)>,
+    indices: HashMap<Handle<A>, usize>,
+    entity_count: usize,
+}
+
+impl<A: Asset + AsStd140> AssetUniforms<A> {
+    /// Creates the uniforms with slots that are aligned according to the `limits` of the device,
+    /// and a uniform buffer for each of the `frames` in flight, which are written in turn whenever
+    /// an asset changed.
+    pub fn new(limits: &RenderResourceLimits, frames: &FramesInFlight) -> Self {
+        Self {
+            uniforms: DynamicUniformVec::new(limits).with_frames_in_flight(frames),
+            assets: Default::default(),
+            indices: Default::default(),
+            entity_count: 0,
+        }
+    }
+}
+
+impl<A: Asset + AsStd140> Default for AssetUniforms<A> {
+    fn default() -> Self {
+        Self::new(&RenderResourceLimits::default(), &FramesInFlight::default())
+    }
+}
+
+impl<A: Asset + AsStd140> Deref for AssetUniforms<A> {
+    type Target = DynamicUniformVec<A>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.uniforms
+    }
+}
+
+impl<A: Asset + AsStd140 + Clone> AssetUniforms<A> {
+    #[inline]
+    pub fn uniforms(&self) -> &DynamicUniformVec<A> {
+        &self.uniforms
+    }
+
+    /// Returns the dynamic offset of the uniform of the asset.
+    pub fn offset(&self, handle: &Handle<A>) -> Option<u32> {
+        let index = *self.indices.get(handle)?;
+        Some((index * self.uniforms.item_size()) as u32)
+    }
+
+    /// Returns the number of asset uniforms, which are shared by the entities using the assets.
+    #[inline]
+    pub fn asset_count(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// Returns the number of entities that referenced an asset uniform in the current frame.
+    #[inline]
+    pub fn entity_count(&self) -> usize {
+        self.entity_count
+    }
+
+    /// Applies the changes to the assets and returns the number of uniforms that have to be
+    /// uploaded again.
+    pub(super) fn update(&mut self, extracted: ExtractedUniformAssets<A>) -> usize {
+        let _span = info_span!(
+            target: "bevy_render::uniforms",
+            "gather_uniforms",
+            uniform_type = type_name::<A>()
+        )
+        .entered();
+        let mut changed = 0;
+        for handle in extracted.removed {
+            if let Some(index) = self.indices.remove(&handle) {
+                self.assets.swap_remove(index);
+                // the last asset moved into the slot of the removed one
+                if let Some((moved, _)) = self.assets.get(index) {
+                    self.indices.insert(moved.clone_weak(), index);
+                    changed += 1;
+                }
+            }
+        }
+        for (handle, asset) in extracted.extracted {
+            match self.indices.get(&handle) {
+                Some(&index) => self.assets[index].1 = asset,
+                None => {
+                    self.indices.insert(handle.clone_weak(), self.assets.len());
+                    self.assets.push((handle, asset));
+                }
+            }
+            changed += 1;
+        }
+        changed
+    }
+}
+
+/// This system extracts the assets that changed since the last frame.
+fn extract_uniform_assets<A: Asset + Clone>(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<A>>,
+    assets: Res<Assets<A>>,
+) {
+    let mut changed_assets = HashSet::default();
+    let mut removed = Vec::new();
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                changed_assets.insert(handle);
+            }
+            AssetEvent::Removed { handle } => {
+                changed_assets.remove(handle);
+                removed.push(handle.clone_weak());
+            }
+        }
+    }
+
+    let extracted = changed_assets
+        .drain()
+        .filter_map(|handle| Some((handle.clone_weak(), assets.get(handle)?.clone())))
+        .collect();
+    commands.insert_resource(ExtractedUniformAssets { extracted, removed });
+}
+
+/// This system uploads the uniforms of the assets that changed and inserts the
+/// [`DynamicUniformIndex`] of their asset for all entities using them.
+fn prepare_uniform_assets<A: Asset + AsStd140 + Clone>(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    statistics: Res<RenderStatistics>,
+    mut extracted_assets: ResMut<ExtractedUniformAssets<A>>,
+    mut asset_uniforms: ResMut<AssetUniforms<A>>,
+    handles: Query<(Entity, &Handle<A>)>,
+) {
+    let extracted_assets = std::mem::take(&mut *extracted_assets);
+    if asset_uniforms.update(extracted_assets) > 0 {
+        let AssetUniforms {
+            uniforms, assets, ..
+        } = &mut *asset_uniforms;
+        let _span = info_span!(
+            target: "bevy_render::uniforms",
+            "write_uniforms",
+            uniform_type = type_name::<A>()
+        )
+        .entered();
+        uniforms.clear();
+        for (_, asset) in assets.iter() {
+            uniforms.push(asset.clone());
+        }
+        // only the uniforms of the changed assets are written
+        statistics.record_uniform_writes(uniforms.write_buffer(&render_device, &render_queue));
+    }
+
+    let entities = handles
+        .iter()
+        .filter_map(|(entity, handle)| {
+            let index = DynamicUniformIndex::<Handle<A>> {
+                index: asset_uniforms.offset(handle)?,
+                marker: PhantomData,
+            };
+            Some((entity, (index,)))
+        })
+        .collect::<Vec<_>>();
+    asset_uniforms.entity_count = entities.len();
+    statistics.record_asset_uniforms(asset_uniforms.asset_count() as u64, entities.len() as u64);
+    commands.insert_or_spawn_batch(entities);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssetUniforms, ExtractedUniformAssets};
+    use crate::render_resource::std140::AsStd140;
+    use bevy_asset::{Handle, HandleId};
+    use bevy_reflect::TypeUuid;
+
+    #[derive(AsStd140, Clone, TypeUuid)]
+    #[uuid = "0e4b6c2a-7d19-4f3e-a5c8-2b9d1f6e8a37"]
+    struct ColorMaterial {
+        brightness: f32,
+    }
+
+    fn extracted(
+        extracted: Vec<(Handle<ColorMaterial>, ColorMaterial)>,
+        removed: Vec<Handle<ColorMaterial>>,
+    ) -> ExtractedUniformAssets<ColorMaterial> {
+        ExtractedUniformAssets { extracted, removed }
+    }
+
+    #[test]
+    fn shared_assets_are_uploaded_once_per_change() {
+        let mut asset_uniforms = AssetUniforms::<ColorMaterial>::default();
+        let handle = Handle::<ColorMaterial>::weak(HandleId::random::<ColorMaterial>());
+        let material = |brightness| ColorMaterial { brightness };
+        // the number of uniforms uploaded by `prepare_uniform_assets`
+        let frame = |asset_uniforms: &mut AssetUniforms<_>, changed, removed| {
+            let uploads = asset_uniforms.update(extracted(changed, removed));
+            (uploads, asset_uniforms.asset_count())
+        };
+
+        assert_eq!(
+            frame(
+                &mut asset_uniforms,
+                vec![(handle.clone_weak(), material(0.0))],
+                vec![]
+            ),
+            (1, 1)
+        );
+        // unchanged materials aren't uploaded again
+        assert_eq!(frame(&mut asset_uniforms, vec![], vec![]), (0, 1));
+        // every mutation is a single upload, no matter how many entities share the material
+        for i in 1..=10 {
+            let changed = vec![(handle.clone_weak(), material(i as f32))];
+            assert_eq!(frame(&mut asset_uniforms, changed, vec![]), (1, 1));
+        }
+
+        // the last material moves into the slot of a removed one
+        let other = Handle::<ColorMaterial>::weak(HandleId::random::<ColorMaterial>());
+        frame(
+            &mut asset_uniforms,
+            vec![(other.clone_weak(), material(0.0))],
+            vec![],
+        );
+        assert_eq!(asset_uniforms.offset(&other), Some(256));
+        assert_eq!(
+            frame(&mut asset_uniforms, vec![], vec![handle.clone_weak()]),
+            (1, 1)
+        );
+        assert_eq!(asset_uniforms.offset(&handle), None);
+        assert_eq!(asset_uniforms.offset(&other), Some(0));
+    }
+}
@@ -0,0 +1,490 @@
+use crate::{
+    render_graph::{Node, NodeRunError, RenderGraphContext},
+    render_resource::{
+        std140::AsStd140, std430::AsStd430, BindGroup, BindGroupLayout, BindGroupLayoutDescriptor,
+        BindGroupLayoutEntry, BindingType, BufferBindingType, BufferSize, CachedComputePipelineId,
+        ComputePassDescriptor, ComputePipelineDescriptor, PipelineCache, Shader, ShaderStages,
+    },
+    renderer::{RenderContext, RenderDevice},
+};
+use bevy_asset::Handle;
+use bevy_ecs::{prelude::*, query::QueryState};
+use std::borrow::Cow;
+use thiserror::Error;
+use wgpu::CommandEncoder;
+
+/// The buffer bindings of a compute shader, derived from the uniform and storage types it reads
+/// and writes, like the [`UniformComponentBindings`](crate::render_component::UniformComponentBindings)
+/// of render pipelines.
+///
+/// The types are bound in the order they are added to a single bind group visible to the compute
+/// stage. The shader def of each binding is its upper case name, defined as its binding index, and
+/// `WORKGROUP_SIZE_X`, `WORKGROUP_SIZE_Y` and `WORKGROUP_SIZE_Z` are defined as the workgroup size,
+/// so WGSL shaders can refer to them with `#{PARTICLES}` or `workgroup_size(#{WORKGROUP_SIZE_X})`.
+#[derive(Clone, Debug)]
+pub struct ComputeBindings {
+    bindings: Vec<ComputeBinding>,
+    workgroup_size: [u32; 3],
+}
+
+#[derive(Clone, Debug)]
+struct ComputeBinding {
+    name: Cow<'static, str>,
+    shader_def: String,
+    ty: BufferBindingType,
+    size: u64,
+}
+
+/// The error returned when binding a type to a [`ComputeBindings`] fails.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ComputeBindingError {
+    #[error("'{0}' is not a valid binding name, names have to be identifiers")]
+    InvalidName(String),
+    #[error("the name '{0}' is already bound")]
+    NameCollision(String),
+}
+
+impl ComputeBindings {
+    pub fn new(workgroup_size: [u32; 3]) -> Self {
+        Self {
+            bindings: Vec::new(),
+            workgroup_size,
+        }
+    }
+
+    /// Binds a uniform buffer holding a single `T` under the `name`.
+    pub fn uniform<T: AsStd140>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+    ) -> Result<&mut Self, ComputeBindingError> {
+        self.bind(
+            name.into(),
+            BufferBindingType::Uniform,
+            T::std140_size_static() as u64,
+        )
+    }
+
+    /// Binds a storage buffer holding an array of `T`, like the values of a
+    /// [`StorageBuffer`](crate::render_resource::StorageBuffer), under the `name`.
+    pub fn storage<T: AsStd430>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        read_only: bool,
+    ) -> Result<&mut Self, ComputeBindingError> {
+        self.bind(
+            name.into(),
+            BufferBindingType::Storage { read_only },
+            T::std430_size_static() as u64,
+        )
+    }
+
+    fn bind(
+        &mut self,
+        name: Cow<'static, str>,
+        ty: BufferBindingType,
+        size: u64,
+    ) -> Result<&mut Self, ComputeBindingError> {
+        let mut chars = name.chars();
+        let is_identifier = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(ComputeBindingError::InvalidName(name.into_owned()));
+        }
+        let shader_def = name.to_ascii_uppercase();
+        if shader_def.starts_with("WORKGROUP_SIZE_")
+            || self
+                .bindings
+                .iter()
+                .any(|binding| binding.shader_def == shader_def)
+        {
+            return Err(ComputeBindingError::NameCollision(name.into_owned()));
+        }
+        self.bindings.push(ComputeBinding {
+            name,
+            shader_def,
+            ty,
+            size,
+        });
+        Ok(self)
+    }
+
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// The names of the bound types, in binding order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.bindings.iter().map(|binding| &*binding.name)
+    }
+
+    /// The shader defs of the bindings and the workgroup size.
+    pub fn shader_defs(&self) -> impl Iterator<Item = String> + '_ {
+        let [x, y, z] = self.workgroup_size;
+        self.bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| format!("{} {}", binding.shader_def, index))
+            .chain([
+                format!("WORKGROUP_SIZE_X {}", x),
+                format!("WORKGROUP_SIZE_Y {}", y),
+                format!("WORKGROUP_SIZE_Z {}", z),
+            ])
+    }
+
+    /// The entries of the bind group layout, which require buffers holding at least one value of
+    /// the bound types.
+    pub fn layout_entries(&self) -> Vec<BindGroupLayoutEntry> {
+        self.bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| BindGroupLayoutEntry {
+                binding: index as u32,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: binding.ty,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(binding.size),
+                },
+                count: None,
+            })
+            .collect()
+    }
+
+    /// Creates the bind group layout, with its entries named after the bound types.
+    pub fn layout(&self, render_device: &RenderDevice) -> BindGroupLayout {
+        render_device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("compute_bindings_layout"),
+                entries: &self.layout_entries(),
+            })
+            .with_entry_names(
+                self.names()
+                    .enumerate()
+                    .map(|(index, name)| (index as u32, name)),
+            )
+    }
+
+    /// Describes a compute pipeline running the `entry_point` of the `shader` with these bindings
+    /// in the given `layout`, which is usually created by [`Self::layout`].
+    pub fn pipeline_descriptor(
+        &self,
+        layout: BindGroupLayout,
+        shader: Handle<Shader>,
+        entry_point: impl Into<Cow<'static, str>>,
+    ) -> ComputePipelineDescriptor {
+        ComputePipelineDescriptor {
+            label: Some("compute_bindings_pipeline".into()),
+            layout: Some(vec![layout]),
+            shader,
+            shader_defs: self.shader_defs().collect(),
+            entry_point: entry_point.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a dispatch of the `pipeline` covering every invocation in `size` with workgroups
+    /// of the workgroup size of these bindings.
+    pub fn dispatch(&self, pipeline: CachedComputePipelineId, size: [u32; 3]) -> ComputeDispatch {
+        ComputeDispatch::for_size(pipeline, size, self.workgroup_size)
+    }
+}
+
+/// A dispatch of a compute pipeline, executed by the [`ComputeDispatchNode`].
+///
+/// Dispatches are either spawned as components on render world entities every frame (usually in
+/// [`RenderStage::Queue`](crate::RenderStage::Queue)) to run once per entity, or inserted as a
+/// resource into the render world to run every frame. The bind groups are bound in order, starting
+/// at group `0`.
+#[derive(Component, Clone)]
+pub struct ComputeDispatch {
+    pub pipeline: CachedComputePipelineId,
+    pub bind_groups: Vec<BindGroup>,
+    pub workgroups: [u32; 3],
+}
+
+impl ComputeDispatch {
+    pub fn new(pipeline: CachedComputePipelineId, workgroups: [u32; 3]) -> Self {
+        Self {
+            pipeline,
+            bind_groups: Vec::new(),
+            workgroups,
+        }
+    }
+
+    /// Creates a dispatch covering every invocation in `size`, for a compute shader with the given
+    /// `workgroup_size`. Partially covered workgroups are dispatched as well.
+    pub fn for_size(
+        pipeline: CachedComputePipelineId,
+        size: [u32; 3],
+        workgroup_size: [u32; 3],
+    ) -> Self {
+        let workgroups = [
+            workgroup_count(size[0], workgroup_size[0]),
+            workgroup_count(size[1], workgroup_size[1]),
+            workgroup_count(size[2], workgroup_size[2]),
+        ];
+        Self::new(pipeline, workgroups)
+    }
+
+    pub fn with_bind_group(mut self, bind_group: BindGroup) -> Self {
+        self.bind_groups.push(bind_group);
+        self
+    }
+}
+
+fn workgroup_count(size: u32, workgroup_size: u32) -> u32 {
+    let workgroup_size = workgroup_size.max(1);
+    size / workgroup_size + (size % workgroup_size != 0) as u32
+}
+
+/// Runs the [`ComputeDispatch`] resource and all [`ComputeDispatch`] components of the render
+/// world in a single compute pass. Dispatches whose pipeline is not ready yet are skipped.
+///
+/// The core pipeline adds this node to the main render graph under [`Self::NAME`], running before
+/// the main passes.
+pub struct ComputeDispatchNode {
+    query: QueryState<&'static ComputeDispatch>,
+}
+
+impl ComputeDispatchNode {
+    pub const NAME: &'static str = "compute_dispatch";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for ComputeDispatchNode {
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let dispatches = world
+            .get_resource::<ComputeDispatch>()
+            .into_iter()
+            .chain(self.query.iter_manual(world));
+        encode_dispatches(
+            world.resource::<PipelineCache>(),
+            dispatches,
+            &mut render_context.command_encoder,
+        );
+
+        Ok(())
+    }
+}
+
+/// Records the `dispatches` whose pipeline is ready into a single compute pass.
+fn encode_dispatches<'a>(
+    pipeline_cache: &PipelineCache,
+    dispatches: impl Iterator<Item = &'a ComputeDispatch>,
+    command_encoder: &mut CommandEncoder,
+) {
+    let mut dispatches = dispatches
+        .filter_map(|dispatch| {
+            pipeline_cache
+                .get_compute_pipeline(dispatch.pipeline)
+                .map(|pipeline| (dispatch, pipeline))
+        })
+        .peekable();
+    if dispatches.peek().is_none() {
+        return;
+    }
+
+    let mut pass = command_encoder.begin_compute_pass(&ComputePassDescriptor {
+        label: Some("compute_dispatch"),
+    });
+    for (dispatch, pipeline) in dispatches {
+        pass.set_pipeline(pipeline);
+        for (index, bind_group) in dispatch.bind_groups.iter().enumerate() {
+            pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        let [x, y, z] = dispatch.workgroups;
+        pass.dispatch(x, y, z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{workgroup_count, ComputeBindingError, ComputeBindings};
+    use crate::render_resource::{
+        std140::AsStd140, std430::AsStd430, BindGroupLayoutEntry, BindingType, BufferBindingType,
+        BufferSize, CachedComputePipelineId, ProcessedShader, Shader, ShaderProcessor,
+        ShaderStages,
+    };
+    use bevy_math::Vec3;
+    use bevy_utils::HashMap;
+
+    #[test]
+    fn workgroup_count_covers_size() {
+        assert_eq!(workgroup_count(1280, 8), 160);
+        assert_eq!(workgroup_count(1281, 8), 161);
+        assert_eq!(workgroup_count(1, 64), 1);
+        assert_eq!(workgroup_count(0, 64), 0);
+        assert_eq!(workgroup_count(u32::MAX, 64), u32::MAX / 64 + 1);
+        assert_eq!(workgroup_count(u32::MAX, 1), u32::MAX);
+    }
+
+    #[derive(AsStd140)]
+    struct Simulation {
+        gravity: Vec3,
+        delta_time: f32,
+    }
+
+    #[derive(AsStd430)]
+    struct Particle {
+        position: Vec3,
+        lifetime: f32,
+        velocity: Vec3,
+    }
+
+    fn particle_bindings() -> ComputeBindings {
+        let mut bindings = ComputeBindings::new([64, 1, 1]);
+        bindings
+            .uniform::<Simulation>("simulation")
+            .unwrap()
+            .storage::<Particle>("particles", false)
+            .unwrap();
+        bindings
+    }
+
+    #[test]
+    fn layout_entries_follow_the_bound_types() {
+        let bindings = particle_bindings();
+        assert_eq!(
+            bindings.layout_entries(),
+            [
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(16),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        // the particle is padded to the 16 byte alignment of its vec3 fields
+                        min_binding_size: BufferSize::new(32),
+                    },
+                    count: None,
+                },
+            ]
+        );
+        assert_eq!(
+            bindings.names().collect::<Vec<_>>(),
+            ["simulation", "particles"]
+        );
+        assert_eq!(
+            bindings.shader_defs().collect::<Vec<_>>(),
+            [
+                "SIMULATION 0",
+                "PARTICLES 1",
+                "WORKGROUP_SIZE_X 64",
+                "WORKGROUP_SIZE_Y 1",
+                "WORKGROUP_SIZE_Z 1",
+            ]
+        );
+
+        // 1000 particles need a 16th, partially covered workgroup
+        let dispatch = bindings.dispatch(CachedComputePipelineId::INVALID, [1000, 1, 1]);
+        assert_eq!(dispatch.workgroups, [16, 1, 1]);
+    }
+
+    #[test]
+    fn invalid_and_duplicate_names_are_rejected() {
+        let mut bindings = particle_bindings();
+        assert_eq!(
+            bindings.uniform::<Simulation>("2d").unwrap_err(),
+            ComputeBindingError::InvalidName("2d".to_string())
+        );
+        assert_eq!(
+            bindings.storage::<Particle>("Particles", true).unwrap_err(),
+            ComputeBindingError::NameCollision("Particles".to_string())
+        );
+        assert_eq!(
+            bindings
+                .uniform::<Simulation>("workgroup_size_x")
+                .unwrap_err(),
+            ComputeBindingError::NameCollision("workgroup_size_x".to_string())
+        );
+        assert_eq!(bindings.layout_entries().len(), 2);
+    }
+
+    const SIMULATE_SHADER: &str = r"
+struct Simulation {
+    gravity: vec3<f32>;
+    delta_time: f32;
+};
+
+struct Particle {
+    position: vec3<f32>;
+    lifetime: f32;
+    velocity: vec3<f32>;
+};
+
+struct Particles {
+    data: array<Particle>;
+};
+
+[[group(0), binding(#{SIMULATION})]]
+var<uniform> simulation: Simulation;
+[[group(0), binding(#{PARTICLES})]]
+var<storage, read_write> particles: Particles;
+
+[[stage(compute), workgroup_size(#{WORKGROUP_SIZE_X}, #{WORKGROUP_SIZE_Y}, #{WORKGROUP_SIZE_Z})]]
+fn simulate([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x < arrayLength(&particles.data)) {
+        let particle = &particles.data[id.x];
+        (*particle).velocity = (*particle).velocity + simulation.gravity * simulation.delta_time;
+        (*particle).position = (*particle).position + (*particle).velocity * simulation.delta_time;
+    }
+}
+";
+
+    #[test]
+    fn shader_defs_bind_the_shader_to_the_layout() {
+        let bindings = particle_bindings();
+        let shader_defs = bindings.shader_defs().collect::<Vec<_>>();
+        let processed = ShaderProcessor::default()
+            .process(
+                &Shader::from_wgsl(SIMULATE_SHADER),
+                &shader_defs,
+                &HashMap::default(),
+                &HashMap::default(),
+            )
+            .unwrap();
+        assert!(matches!(processed, ProcessedShader::Wgsl(_)));
+        let reflection = processed.reflect().unwrap();
+
+        let used = reflection
+            .bindings_used_by("simulate")
+            .unwrap()
+            .into_iter()
+            .map(|binding| (binding.name.unwrap(), binding.group, binding.binding))
+            .collect::<Vec<_>>();
+        let entries = bindings
+            .names()
+            .zip(bindings.layout_entries())
+            .map(|(name, entry)| (name.to_string(), 0, entry.binding))
+            .collect::<Vec<_>>();
+        assert_eq!(used, entries);
+        assert_eq!(
+            reflection.module.entry_points[0].workgroup_size,
+            bindings.workgroup_size()
+        );
+    }
+}
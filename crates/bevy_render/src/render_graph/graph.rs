@@ -6,7 +6,7 @@ use crate::{
     renderer::RenderContext,
 };
 use bevy_ecs::prelude::World;
-use bevy_utils::HashMap;
+use bevy_utils::{HashMap, HashSet};
 use std::{borrow::Cow, fmt::Debug};
 
 use super::EdgeExistence;
@@ -433,6 +433,49 @@ impl RenderGraph {
         Ok(())
     }
 
+    /// Returns whether the `before` node is guaranteed to run before the `after` node, because
+    /// they are connected by a path of edges.
+    pub fn runs_before(
+        &self,
+        before: impl Into<NodeLabel>,
+        after: impl Into<NodeLabel>,
+    ) -> Result<bool, RenderGraphError> {
+        let before = self.get_node_id(before)?;
+        let after = self.get_node_id(after)?;
+
+        let mut visited = HashSet::default();
+        let mut stack = vec![before];
+        while let Some(node) = stack.pop() {
+            for edge in self.get_node_state(node)?.edges.output_edges() {
+                let next = edge.get_input_node();
+                if next == after {
+                    return Ok(true);
+                }
+                if visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Verifies that the `producer` node, which renders to a texture, runs before the `consumer`
+    /// node, which samples it. Otherwise the consumer may sample the texture of the previous
+    /// frame, or one that is only partially rendered.
+    pub fn validate_render_target_order(
+        &self,
+        producer: impl Into<NodeLabel>,
+        consumer: impl Into<NodeLabel>,
+    ) -> Result<(), RenderGraphError> {
+        let producer = producer.into();
+        let consumer = consumer.into();
+        if self.runs_before(&producer, &consumer)? {
+            Ok(())
+        } else {
+            Err(RenderGraphError::RenderTargetOrder { producer, consumer })
+        }
+    }
+
     /// Checks whether the `edge` already exists in the graph.
     pub fn has_edge(&self, edge: &Edge) -> bool {
         let output_node_state = self.get_node_state(edge.get_output_node());
@@ -708,6 +751,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_render_target_order() {
+        let mut graph = RenderGraph::default();
+        graph.add_node("A", TestNode::new(0, 1));
+        graph.add_node("B", TestNode::new(1, 0));
+        graph.add_node("C", TestNode::new(0, 0));
+        graph.add_node("D", TestNode::new(0, 0));
+
+        graph.add_slot_edge("A", 0, "B", 0).unwrap();
+        graph.add_node_edge("B", "C").unwrap();
+
+        assert_eq!(graph.runs_before("A", "C"), Ok(true));
+        assert_eq!(graph.runs_before("C", "A"), Ok(false));
+        assert_eq!(graph.validate_render_target_order("A", "C"), Ok(()));
+        assert_eq!(
+            graph.validate_render_target_order("A", "D"),
+            Err(RenderGraphError::RenderTargetOrder {
+                producer: "A".into(),
+                consumer: "D".into(),
+            }),
+            "D may run before A"
+        );
+        assert_eq!(
+            graph.validate_render_target_order("A", "E"),
+            Err(RenderGraphError::InvalidNode("E".into()))
+        );
+    }
+
     #[test]
     fn test_slot_already_occupied() {
         let mut graph = RenderGraph::default();
@@ -1,9 +1,11 @@
+mod compute_dispatch;
 mod context;
 mod edge;
 mod graph;
 mod node;
 mod node_slot;
 
+pub use compute_dispatch::*;
 pub use context::*;
 pub use edge::*;
 pub use graph::*;
@@ -43,4 +45,9 @@ pub enum RenderGraphError {
         input_slot: usize,
         occupied_by_node: NodeId,
     },
+    #[error("node {producer:?} renders to a texture sampled by node {consumer:?}, but doesn't run before it")]
+    RenderTargetOrder {
+        producer: NodeLabel,
+        consumer: NodeLabel,
+    },
 }
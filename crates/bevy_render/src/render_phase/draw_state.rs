@@ -1,10 +1,12 @@
 use crate::{
+    camera::ExtractedCamera,
     prelude::Color,
     render_resource::{
-        BindGroup, BindGroupId, Buffer, BufferId, BufferSlice, RenderPipeline, RenderPipelineId,
-        ShaderStages,
+        BindGroup, BindGroupId, Buffer, BufferId, BufferSlice, CachedRenderPipelineId,
+        RenderPipeline, RenderPipelineId, ShaderStages,
     },
 };
+use bevy_asset::HandleId;
 use bevy_utils::tracing::trace;
 use std::ops::Range;
 use wgpu::{IndexFormat, RenderPass};
@@ -87,6 +89,19 @@ impl DrawState {
     }
 }
 
+/// The state bound by a draw, which phase items can be sorted by.
+///
+/// The [`TrackedRenderPass`] skips setting a pipeline or bind group that is already set, so
+/// sorting draws by their state only changes the state between draws of different groups, instead
+/// of between almost every draw when the draws are in query iteration order.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct DrawStateKey {
+    pub pipeline: CachedRenderPipelineId,
+    /// The bind group of e.g. the material of the draw.
+    pub bind_group: Option<BindGroupId>,
+    pub mesh: Option<HandleId>,
+}
+
 /// A [`RenderPass`], which tracks the current pipeline state to ensure all draw calls are valid.
 /// It is used to set the current [`RenderPipeline`], [`BindGroups`](BindGroup) and buffers.
 /// After all requirements are specified, draw calls can be issued.
@@ -337,6 +352,32 @@ impl<'a> TrackedRenderPass<'a> {
             .set_viewport(x, y, width, height, min_depth, max_depth);
     }
 
+    /// Sets the viewport and scissor rect of `camera`, see [`ExtractedCamera::pass_viewport`] and
+    /// [`ExtractedCamera::pass_scissor_rect`].
+    ///
+    /// Both are dynamic state of the render pass, so this can be called between draws (e.g. to
+    /// render several cameras into one pass) without specializing any pipeline again.
+    pub fn set_camera_viewport(&mut self, camera: &ExtractedCamera) {
+        if let Some(viewport) = camera.pass_viewport() {
+            self.set_viewport(
+                viewport.physical_position.x as f32,
+                viewport.physical_position.y as f32,
+                viewport.physical_size.x as f32,
+                viewport.physical_size.y as f32,
+                viewport.depth.start,
+                viewport.depth.end,
+            );
+        }
+        if let Some(scissor_rect) = camera.pass_scissor_rect() {
+            self.set_scissor_rect(
+                scissor_rect.physical_position.x,
+                scissor_rect.physical_position.y,
+                scissor_rect.physical_size.x,
+                scissor_rect.physical_size.y,
+            );
+        }
+    }
+
     /// Insert a single debug marker.
     ///
     /// This is a GPU debugging feature. This has no effect on the rendering itself.
@@ -195,4 +195,77 @@ mod tests {
         ];
         assert_eq!(&*render_phase.items, items_batched);
     }
+
+    #[test]
+    fn draws_are_grouped_by_state() {
+        use crate::render_resource::{BindGroupId, CachedRenderPipelineId};
+        use bevy_asset::HandleId;
+        use bevy_reflect::Uuid;
+
+        struct TestPhaseItem {
+            state: DrawStateKey,
+            entity: Entity,
+        }
+        impl PhaseItem for TestPhaseItem {
+            type SortKey = (DrawStateKey, Entity);
+
+            fn sort_key(&self) -> Self::SortKey {
+                (self.state, self.entity)
+            }
+
+            fn draw_function(&self) -> DrawFunctionId {
+                unimplemented!();
+            }
+        }
+
+        // 4 pipelines with 3 materials each, drawing 2 meshes, in query iteration order
+        let draws = |order: &mut dyn Iterator<Item = u32>| {
+            let mut render_phase = RenderPhase::<TestPhaseItem>::default();
+            for draw in order {
+                render_phase.add(TestPhaseItem {
+                    state: DrawStateKey {
+                        pipeline: CachedRenderPipelineId(draw as usize % 4),
+                        bind_group: Some(BindGroupId(Uuid::from_u128((draw as u128 / 4) % 3))),
+                        mesh: Some(HandleId::new(Uuid::nil(), (draw as u64 / 12) % 2)),
+                    },
+                    entity: Entity::from_raw(draw),
+                });
+            }
+            render_phase.sort();
+            render_phase.items
+        };
+        let items = draws(&mut (0..240).map(|draw| draw * 7 % 240));
+
+        // the state only changes between groups, like in a `TrackedRenderPass`
+        let (mut pipeline_changes, mut bind_group_changes, mut state_changes) = (0, 0, 0);
+        let mut previous: Option<DrawStateKey> = None;
+        for item in &items {
+            let state = item.state;
+            if previous.map(|previous| previous.pipeline) != Some(state.pipeline) {
+                pipeline_changes += 1;
+            }
+            if previous.map(|previous| (previous.pipeline, previous.bind_group))
+                != Some((state.pipeline, state.bind_group))
+            {
+                bind_group_changes += 1;
+            }
+            if previous != Some(state) {
+                state_changes += 1;
+            }
+            previous = Some(state);
+        }
+        assert_eq!(items.len(), 240);
+        assert_eq!(pipeline_changes, 4);
+        assert_eq!(bind_group_changes, 4 * 3);
+        assert_eq!(state_changes, 4 * 3 * 2);
+
+        // draws of the same group are ordered by entity, no matter the order they were added in
+        let entities =
+            |items: &[TestPhaseItem]| items.iter().map(|item| item.entity).collect::<Vec<_>>();
+        assert!(items
+            .windows(2)
+            .filter(|pair| pair[0].state == pair[1].state)
+            .all(|pair| pair[0].entity < pair[1].entity));
+        assert_eq!(entities(&items), entities(&draws(&mut (0..240).rev())));
+    }
 }
@@ -0,0 +1,416 @@
+use crate::{
+    diagnostic::RenderStatistics,
+    render_resource::{
+        BindGroup, BindGroupLayout, BindGroupLayoutId, Buffer, BufferId, FrameArena, LastUsed,
+        Sampler, SamplerId, TextureView, TextureViewId,
+    },
+    renderer::RenderDevice,
+};
+use bevy_ecs::system::{Local, Res, ResMut};
+use bevy_utils::{hashbrown::hash_map::RawEntryMut, HashMap};
+use std::hash::{BuildHasher, Hash, Hasher};
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindingResource, BufferAddress, BufferBinding, BufferSize,
+};
+
+/// Identifies a resource referenced by a cached bind group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceId {
+    BindGroupLayout(BindGroupLayoutId),
+    Buffer(BufferId),
+    TextureView(TextureViewId),
+    Sampler(SamplerId),
+}
+
+impl From<BindGroupLayoutId> for ResourceId {
+    fn from(id: BindGroupLayoutId) -> Self {
+        ResourceId::BindGroupLayout(id)
+    }
+}
+
+impl From<BufferId> for ResourceId {
+    fn from(id: BufferId) -> Self {
+        ResourceId::Buffer(id)
+    }
+}
+
+impl From<TextureViewId> for ResourceId {
+    fn from(id: TextureViewId) -> Self {
+        ResourceId::TextureView(id)
+    }
+}
+
+impl From<SamplerId> for ResourceId {
+    fn from(id: SamplerId) -> Self {
+        ResourceId::Sampler(id)
+    }
+}
+
+/// A resource bound by a [`CachedBindGroupEntry`].
+#[derive(Debug, Clone, Copy)]
+pub enum CachedBindingResource<'a> {
+    /// The range of the `buffer` starting at `offset`, which extends to the end of the buffer if
+    /// `size` is `None`.
+    Buffer {
+        buffer: &'a Buffer,
+        offset: BufferAddress,
+        size: Option<BufferSize>,
+    },
+    TextureView(&'a TextureView),
+    Sampler(&'a Sampler),
+}
+
+impl<'a> CachedBindingResource<'a> {
+    /// Binds the whole `buffer`.
+    pub fn whole_buffer(buffer: &'a Buffer) -> Self {
+        CachedBindingResource::Buffer {
+            buffer,
+            offset: 0,
+            size: None,
+        }
+    }
+
+    fn key(&self, binding: u32) -> BindGroupEntryKey<ResourceId> {
+        let (resource, offset, size) = match *self {
+            CachedBindingResource::Buffer {
+                buffer,
+                offset,
+                size,
+            } => (buffer.id().into(), offset, size),
+            CachedBindingResource::TextureView(texture_view) => (texture_view.id().into(), 0, None),
+            CachedBindingResource::Sampler(sampler) => (sampler.id().into(), 0, None),
+        };
+        BindGroupEntryKey {
+            binding,
+            resource,
+            offset,
+            size,
+        }
+    }
+
+    fn binding_resource(&self) -> BindingResource<'a> {
+        match *self {
+            CachedBindingResource::Buffer {
+                buffer,
+                offset,
+                size,
+            } => BindingResource::Buffer(BufferBinding {
+                buffer,
+                offset,
+                size,
+            }),
+            CachedBindingResource::TextureView(texture_view) => {
+                BindingResource::TextureView(texture_view)
+            }
+            CachedBindingResource::Sampler(sampler) => BindingResource::Sampler(sampler),
+        }
+    }
+}
+
+/// An entry of a bind group created by the [`BindGroupCache`].
+#[derive(Debug, Clone, Copy)]
+pub struct CachedBindGroupEntry<'a> {
+    pub binding: u32,
+    pub resource: CachedBindingResource<'a>,
+}
+
+/// How often the bind groups of a [`BindGroupCache`] were shared. The counters add up since the
+/// cache was created; the [`RenderStatistics`] report the bind groups created and freed per frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BindGroupCacheStats {
+    /// The number of bind groups in the cache.
+    pub bind_group_count: usize,
+    /// The number of requested bind groups that were already cached.
+    pub hits: usize,
+    /// The number of requested bind groups that had to be created.
+    pub misses: usize,
+    /// The number of bind groups that were invalidated or freed after not being used for a while.
+    pub freed: usize,
+}
+
+/// Shares bind groups between everything binding the same resources with the same layout, e.g.
+/// entities using the same material.
+///
+/// Bind groups are identified by their layout and the buffer ranges, texture views and samplers
+/// they bind. Recreating a resource gives it a new id, so bind groups referencing a texture that
+/// was reloaded or a buffer that was reallocated aren't used anymore; they are freed after not
+/// being used for a while. Destroyed resources can also be removed right away with
+/// [`BindGroupCache::invalidate`].
+///
+/// The frames are counted by the evictions in the [`Cleanup`](crate::RenderStage::Cleanup) stage
+/// of the render app, so a bind group requested in any earlier stage counts as used.
+#[derive(Default)]
+pub struct BindGroupCache {
+    cache: Cache<ResourceId, BindGroup>,
+}
+
+impl BindGroupCache {
+    /// Returns the bind group of the `layout` binding the `entries`, which is only created if no
+    /// such bind group is cached yet. The key of the bind group is assembled in the `frame_arena`,
+    /// so looking up cached bind groups doesn't allocate.
+    pub fn get(
+        &mut self,
+        render_device: &RenderDevice,
+        frame_arena: &FrameArena,
+        layout: &BindGroupLayout,
+        entries: &[CachedBindGroupEntry],
+    ) -> &BindGroup {
+        let entry_keys = frame_arena.alloc_slice_fill_iter(
+            entries
+                .iter()
+                .map(|entry| entry.resource.key(entry.binding)),
+        );
+        self.cache.get(layout.id().into(), entry_keys, || {
+            let entries = entries
+                .iter()
+                .map(|entry| BindGroupEntry {
+                    binding: entry.binding,
+                    resource: entry.resource.binding_resource(),
+                })
+                .collect::<Vec<_>>();
+            render_device.create_bind_group(&BindGroupDescriptor {
+                label: Some("cached_bind_group"),
+                layout,
+                entries: &entries,
+            })
+        })
+    }
+
+    /// Removes the bind groups referencing the `resource`, e.g. because it was destroyed.
+    pub fn invalidate(&mut self, resource: impl Into<ResourceId>) {
+        self.cache.invalidate(&resource.into());
+    }
+
+    pub fn stats(&self) -> BindGroupCacheStats {
+        self.cache.stats()
+    }
+
+    /// Frees the bind groups that weren't used for a while.
+    pub(crate) fn evict_system(
+        mut bind_group_cache: ResMut<Self>,
+        statistics: Res<RenderStatistics>,
+        mut previous_stats: Local<BindGroupCacheStats>,
+    ) {
+        bind_group_cache.cache.evict();
+        let stats = bind_group_cache.stats();
+        statistics.record_bind_groups(
+            (stats.misses - previous_stats.misses) as u64,
+            (stats.freed - previous_stats.freed) as u64,
+        );
+        *previous_stats = stats;
+    }
+}
+
+/// The binding index, resource and buffer range of an entry of a bind group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BindGroupEntryKey<R> {
+    binding: u32,
+    resource: R,
+    offset: BufferAddress,
+    size: Option<BufferSize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BindGroupKey<R> {
+    layout: R,
+    entries: Vec<BindGroupEntryKey<R>>,
+}
+
+impl<R: Hash> Hash for BindGroupKey<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_key(&self.layout, &self.entries, state);
+    }
+}
+
+/// Hashes the parts of a [`BindGroupKey`], so that keys can be looked up without creating them.
+fn hash_key<R: Hash, H: Hasher>(layout: &R, entries: &[BindGroupEntryKey<R>], state: &mut H) {
+    layout.hash(state);
+    entries.hash(state);
+}
+
+impl<R: PartialEq> BindGroupKey<R> {
+    fn references(&self, resource: &R) -> bool {
+        self.layout == *resource || self.entries.iter().any(|entry| entry.resource == *resource)
+    }
+}
+
+struct CachedBindGroup<B> {
+    bind_group: B,
+    /// The last frame the bind group was requested in.
+    last_used: LastUsed,
+}
+
+/// The bind groups of a [`BindGroupCache`] by their key, generic over the resource ids and bind
+/// groups so that tests can identify them with numbers.
+struct Cache<R, B> {
+    bind_groups: HashMap<BindGroupKey<R>, CachedBindGroup<B>>,
+    frame: u64,
+    hits: usize,
+    misses: usize,
+    freed: usize,
+}
+
+impl<R, B> Default for Cache<R, B> {
+    fn default() -> Self {
+        Self {
+            bind_groups: HashMap::default(),
+            frame: 0,
+            hits: 0,
+            misses: 0,
+            freed: 0,
+        }
+    }
+}
+
+impl<R: Copy + Eq + Hash, B> Cache<R, B> {
+    fn get(
+        &mut self,
+        layout: R,
+        entries: &[BindGroupEntryKey<R>],
+        create: impl FnOnce() -> B,
+    ) -> &B {
+        let frame = self.frame;
+        let mut hasher = self.bind_groups.hasher().build_hasher();
+        hash_key(&layout, entries, &mut hasher);
+        let hash = hasher.finish();
+        let entry = self
+            .bind_groups
+            .raw_entry_mut()
+            .from_hash(hash, |key| key.layout == layout && key.entries == entries);
+        let cached = match entry {
+            RawEntryMut::Occupied(entry) => {
+                self.hits += 1;
+                entry.into_mut()
+            }
+            RawEntryMut::Vacant(entry) => {
+                self.misses += 1;
+                let key = BindGroupKey {
+                    layout,
+                    entries: entries.to_vec(),
+                };
+                let cached = CachedBindGroup {
+                    bind_group: create(),
+                    last_used: LastUsed::new(frame),
+                };
+                entry.insert_hashed_nocheck(hash, key, cached).1
+            }
+        };
+        cached.last_used.set(frame);
+        &cached.bind_group
+    }
+
+    fn invalidate(&mut self, resource: &R) {
+        let len = self.bind_groups.len();
+        self.bind_groups.retain(|key, _| !key.references(resource));
+        self.freed += len - self.bind_groups.len();
+    }
+
+    fn evict(&mut self) {
+        let frame = self.frame;
+        let len = self.bind_groups.len();
+        self.bind_groups
+            .retain(|_, cached| !cached.last_used.is_unused(frame));
+        self.freed += len - self.bind_groups.len();
+        self.frame += 1;
+    }
+
+    fn stats(&self) -> BindGroupCacheStats {
+        BindGroupCacheStats {
+            bind_group_count: self.bind_groups.len(),
+            hits: self.hits,
+            misses: self.misses,
+            freed: self.freed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BindGroupEntryKey, Cache};
+    use crate::{
+        counting_allocator::allocations,
+        render_resource::{FrameArena, MAX_UNUSED_FRAMES},
+    };
+    use wgpu::BufferSize;
+
+    /// The entries of a material with a uniform buffer, a texture and a sampler, identified by
+    /// numbers.
+    fn material_entries(texture: u32) -> [BindGroupEntryKey<u32>; 3] {
+        let entry = |binding, resource, size| BindGroupEntryKey {
+            binding,
+            resource,
+            offset: 0,
+            size: BufferSize::new(size),
+        };
+        [entry(0, 1, 64), entry(1, texture, 0), entry(2, 3, 0)]
+    }
+
+    #[test]
+    fn entities_sharing_a_material_share_its_bind_group() {
+        let mut cache = Cache::<u32, ()>::default();
+        let mut created = 0;
+        for _entity in 0..100 {
+            cache.get(0, &material_entries(2), || created += 1);
+        }
+        assert_eq!(created, 1);
+        let stats = cache.stats();
+        assert_eq!(stats.bind_group_count, 1);
+        assert_eq!(stats.hits, 99);
+        assert_eq!(stats.misses, 1);
+
+        // a reloaded texture gets a new id, so the bind group is recreated
+        cache.get(0, &material_entries(4), || created += 1);
+        assert_eq!(created, 2);
+        assert_eq!(cache.stats().bind_group_count, 2);
+        cache.invalidate(&2);
+        assert_eq!(cache.stats().bind_group_count, 1);
+        assert_eq!(cache.stats().freed, 1);
+
+        // other buffer ranges of the same buffer aren't shared
+        let mut entries = material_entries(4);
+        entries[0].offset = 256;
+        cache.get(0, &entries, || created += 1);
+        assert_eq!(created, 3);
+        // neither are bind groups with other layouts
+        cache.get(5, &material_entries(4), || created += 1);
+        assert_eq!(created, 4);
+    }
+
+    #[test]
+    fn unused_bind_groups_are_freed() {
+        let mut cache = Cache::<u32, ()>::default();
+        cache.get(0, &material_entries(2), || ());
+        for _ in 0..=MAX_UNUSED_FRAMES {
+            cache.get(0, &material_entries(4), || ());
+            cache.evict();
+        }
+        assert_eq!(cache.stats().bind_group_count, 1);
+        assert_eq!(cache.stats().freed, 1);
+        let mut created = false;
+        cache.get(0, &material_entries(4), || created = true);
+        assert!(!created);
+    }
+
+    #[test]
+    fn steady_state_frames_do_not_allocate() {
+        let mut cache = Cache::<u32, ()>::default();
+        let mut arena = FrameArena::default();
+        let frame = |cache: &mut Cache<u32, ()>, arena: &mut FrameArena| {
+            for material in 0..1000 {
+                let entries = arena.alloc_slice_fill_iter(material_entries(material).into_iter());
+                cache.get(0, entries, || ());
+            }
+            cache.evict();
+            arena.reset();
+        };
+        // the first frame creates the bind groups and the chunks of the arena
+        frame(&mut cache, &mut arena);
+
+        let before = allocations();
+        for _ in 0..10 {
+            frame(&mut cache, &mut arena);
+        }
+        assert_eq!(allocations(), before);
+        assert_eq!(cache.stats().misses, 1000);
+    }
+}
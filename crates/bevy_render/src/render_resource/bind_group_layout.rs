@@ -8,6 +8,8 @@ pub struct BindGroupLayoutId(Uuid);
 pub struct BindGroupLayout {
     id: BindGroupLayoutId,
     value: Arc<wgpu::BindGroupLayout>,
+    entries: Option<Arc<[wgpu::BindGroupLayoutEntry]>>,
+    entry_names: Arc<[(u32, String)]>,
 }
 
 impl PartialEq for BindGroupLayout {
@@ -26,6 +28,44 @@ impl BindGroupLayout {
     pub fn value(&self) -> &wgpu::BindGroupLayout {
         &self.value
     }
+
+    /// The entries this layout was created with. This is [`None`] for layouts converted from a
+    /// raw [`wgpu::BindGroupLayout`].
+    #[inline]
+    pub fn entries(&self) -> Option<&[wgpu::BindGroupLayoutEntry]> {
+        self.entries.as_deref()
+    }
+
+    /// Returns the name given to the entry with the given `binding` by [`Self::with_entry_names`].
+    #[inline]
+    pub fn entry_name(&self, binding: u32) -> Option<&str> {
+        self.entry_names
+            .iter()
+            .find(|(named, _)| *named == binding)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// The `(binding, name)` pairs given by [`Self::with_entry_names`].
+    #[inline]
+    pub fn entry_names(&self) -> &[(u32, String)] {
+        &self.entry_names
+    }
+
+    /// Names the entries of this layout after the shader variables bound to them. Pipelines using
+    /// the layout then report shader bindings whose names don't match, and named entries none of
+    /// their shaders use, see [`BindingValidation`](super::BindingValidation).
+    pub fn with_entry_names<'a>(mut self, names: impl IntoIterator<Item = (u32, &'a str)>) -> Self {
+        self.entry_names = names
+            .into_iter()
+            .map(|(binding, name)| (binding, name.to_string()))
+            .collect();
+        self
+    }
+
+    pub(crate) fn with_entries(mut self, entries: &[wgpu::BindGroupLayoutEntry]) -> Self {
+        self.entries = Some(entries.into());
+        self
+    }
 }
 
 impl From<wgpu::BindGroupLayout> for BindGroupLayout {
@@ -33,6 +73,8 @@ impl From<wgpu::BindGroupLayout> for BindGroupLayout {
         BindGroupLayout {
             id: BindGroupLayoutId(Uuid::new_v4()),
             value: Arc::new(value),
+            entries: None,
+            entry_names: Arc::new([]),
         }
     }
 }
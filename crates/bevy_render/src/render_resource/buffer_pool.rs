@@ -0,0 +1,253 @@
+use crate::{
+    diagnostic::RenderStatistics,
+    render_resource::{Buffer, LastUsed},
+    renderer::{FramesInFlight, RenderDevice, RenderQueue},
+};
+use bevy_ecs::system::{Res, ResMut};
+use bevy_utils::tracing::info_span;
+use wgpu::{BufferAddress, BufferDescriptor, BufferUsages};
+
+/// The smallest buffer allocated by the [`BufferPool`].
+const MIN_POOLED_BUFFER_SIZE: BufferAddress = 256;
+
+/// The GPU memory held by a [`BufferPool`]. Memory that is allocated but neither in use nor in
+/// flight is kept for later frames, until the buffers weren't handed out for a while.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// The number of buffers allocated by the pool.
+    pub buffer_count: usize,
+    /// The total size of the buffers allocated by the pool in bytes.
+    pub bytes_allocated: BufferAddress,
+    /// The size of the buffers handed out in the current frame in bytes.
+    pub bytes_in_use: BufferAddress,
+    /// The size of the buffers handed out in previous frames that the GPU may still read in bytes.
+    pub bytes_in_flight: BufferAddress,
+}
+
+/// Hands out buffers that are only used for a single frame, like the instance data of a draw
+/// call that is uploaded again every frame.
+///
+/// Creating fresh buffers every frame churns through GPU memory. The pool instead recycles the
+/// buffers handed out in a frame once the GPU finished the frame, as signalled to the
+/// [`FramesInFlight`], so that they can be handed out again in a later frame without overwriting
+/// contents the GPU still reads. Buffer sizes are rounded up to powers of two, which lets a buffer
+/// be reused for slightly larger contents.
+///
+/// Buffers handed out by systems of the render app stay in use until its
+/// [`Cleanup`](crate::RenderStage::Cleanup) stage, which recycles them.
+#[derive(Default)]
+pub struct BufferPool {
+    pool: Pool<Buffer>,
+}
+
+impl BufferPool {
+    /// Returns a buffer of at least `size` bytes with the `usage`, which may be used until the
+    /// end of the current frame.
+    pub fn get(
+        &mut self,
+        render_device: &RenderDevice,
+        usage: BufferUsages,
+        size: BufferAddress,
+    ) -> &Buffer {
+        self.pool.get(usage, size, |size| {
+            render_device.create_buffer(&BufferDescriptor {
+                label: Some("pooled_buffer"),
+                size,
+                usage: usage | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        })
+    }
+
+    /// Returns a buffer with the `usage` holding the `contents`, which may be used until the end
+    /// of the current frame.
+    pub fn get_with_data(
+        &mut self,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        usage: BufferUsages,
+        contents: &[u8],
+    ) -> &Buffer {
+        let _span = info_span!(
+            target: "bevy_render::uniforms",
+            "write_instance_buffer",
+            bytes = contents.len()
+        )
+        .entered();
+        let buffer = self.get(render_device, usage, contents.len() as BufferAddress);
+        render_queue.write_buffer(buffer, 0, contents);
+        buffer
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        self.pool.stats()
+    }
+
+    /// Recycles the buffers of the frames the GPU finished. Buffers that weren't handed out for a
+    /// while are freed, so the pool shrinks again after a spike.
+    pub(crate) fn recycle_system(
+        mut buffer_pool: ResMut<Self>,
+        frames: Res<FramesInFlight>,
+        statistics: Res<RenderStatistics>,
+    ) {
+        statistics.record_instance_buffer_bytes(buffer_pool.stats().bytes_in_use);
+        buffer_pool
+            .pool
+            .recycle(frames.frame(), frames.completed_frames());
+    }
+}
+
+struct PooledBuffer<B> {
+    buffer: B,
+    usage: BufferUsages,
+    size: BufferAddress,
+    /// The last frame the buffer was handed out in, which is set when the frame is recycled.
+    last_used: LastUsed,
+}
+
+/// The buffers of a [`BufferPool`], which move from `in_use` to `in_flight` when their frame ends
+/// and to `free` once the GPU finished it. Tests pool plain numbers instead of buffers.
+struct Pool<B> {
+    free: Vec<PooledBuffer<B>>,
+    in_use: Vec<PooledBuffer<B>>,
+    /// The buffers of the frames the GPU may not have finished yet.
+    in_flight: Vec<PooledBuffer<B>>,
+}
+
+impl<B> Default for Pool<B> {
+    fn default() -> Self {
+        Self {
+            free: Vec::new(),
+            in_use: Vec::new(),
+            in_flight: Vec::new(),
+        }
+    }
+}
+
+impl<B> Pool<B> {
+    fn get(
+        &mut self,
+        usage: BufferUsages,
+        size: BufferAddress,
+        create: impl FnOnce(BufferAddress) -> B,
+    ) -> &B {
+        let size = size.max(MIN_POOLED_BUFFER_SIZE).next_power_of_two();
+        // prefer the most recently used buffers, so that the others can be freed
+        let position = self
+            .free
+            .iter()
+            .rposition(|pooled| pooled.usage == usage && pooled.size == size);
+        let pooled = match position {
+            Some(index) => self.free.swap_remove(index),
+            None => PooledBuffer {
+                buffer: create(size),
+                usage,
+                size,
+                last_used: LastUsed::default(),
+            },
+        };
+        self.in_use.push(pooled);
+        &self.in_use.last().unwrap().buffer
+    }
+
+    /// Ends the `frame`, after the GPU finished the first `completed_frames` frames.
+    fn recycle(&mut self, frame: u64, completed_frames: u64) {
+        for mut pooled in self.in_use.drain(..) {
+            pooled.last_used.set(frame);
+            self.in_flight.push(pooled);
+        }
+        let mut index = 0;
+        while index < self.in_flight.len() {
+            if self.in_flight[index].last_used.frame() < completed_frames {
+                self.free.push(self.in_flight.swap_remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        self.free
+            .retain(|pooled| !pooled.last_used.is_unused(frame));
+    }
+
+    fn stats(&self) -> BufferPoolStats {
+        let size = |buffers: &[PooledBuffer<B>]| -> BufferAddress {
+            buffers.iter().map(|pooled| pooled.size).sum()
+        };
+        BufferPoolStats {
+            buffer_count: self.free.len() + self.in_use.len() + self.in_flight.len(),
+            bytes_allocated: size(&self.free) + size(&self.in_use) + size(&self.in_flight),
+            bytes_in_use: size(&self.in_use),
+            bytes_in_flight: size(&self.in_flight),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pool, MIN_POOLED_BUFFER_SIZE};
+    use crate::render_resource::MAX_UNUSED_FRAMES;
+    use wgpu::BufferUsages;
+
+    #[test]
+    fn buffers_are_reused_across_frames() {
+        let mut pool = Pool::<()>::default();
+        let mut created = 0;
+        let mut stats = Vec::new();
+        for frame in 0..100 {
+            // a varying number of entities, each with its own instance buffer
+            for _ in 0..(5000 - frame % 3) {
+                pool.get(BufferUsages::VERTEX, 64, |_| created += 1);
+            }
+            stats.push(pool.stats());
+            // the GPU finishes every frame right away
+            pool.recycle(frame, frame + 1);
+        }
+        assert_eq!(created, 5000);
+        assert_eq!(stats[0].bytes_in_use, 5000 * MIN_POOLED_BUFFER_SIZE);
+        assert!(stats.iter().all(|stats| stats.buffer_count == 5000));
+
+        // buffers that aren't used anymore are freed after a while
+        for frame in 100..100 + MAX_UNUSED_FRAMES {
+            pool.get(BufferUsages::VERTEX, 64, |_| created += 1);
+            pool.recycle(frame, frame + 1);
+        }
+        assert_eq!(created, 5000);
+        assert_eq!(pool.stats().buffer_count, 1);
+        assert_eq!(pool.stats().bytes_in_use, 0);
+    }
+
+    #[test]
+    fn buffers_match_usage_and_size() {
+        let mut pool = Pool::<u64>::default();
+        assert_eq!(*pool.get(BufferUsages::VERTEX, 1000, |size| size), 1024);
+        pool.recycle(0, 1);
+        // slightly larger contents fit into the same buffer
+        assert_eq!(*pool.get(BufferUsages::VERTEX, 1020, |_| 0), 1024);
+        // buffers with a different usage or size aren't shared
+        assert_eq!(*pool.get(BufferUsages::INDEX, 1000, |_| 0), 0);
+        assert_eq!(*pool.get(BufferUsages::VERTEX, 1000, |_| 0), 0);
+        assert_eq!(pool.stats().buffer_count, 3);
+    }
+
+    #[test]
+    fn buffers_are_reused_once_their_frame_completed() {
+        let mut pool = Pool::<u32>::default();
+        let mut created = 0;
+        let mut create = |_: u64| {
+            created += 1;
+            created
+        };
+        // the GPU lags two frames behind, so every buffer of a frame in flight is still being read
+        let mut handed_out = Vec::new();
+        for frame in 0..6 {
+            handed_out.push(*pool.get(BufferUsages::VERTEX, 64, &mut create));
+            pool.recycle(frame, frame.saturating_sub(1));
+        }
+        assert_eq!(handed_out, [1, 2, 3, 1, 2, 3]);
+        assert_eq!(pool.stats().bytes_in_flight, 2 * MIN_POOLED_BUFFER_SIZE);
+
+        // once the GPU catches up, all buffers are free
+        pool.recycle(6, 6);
+        assert_eq!(pool.stats().bytes_in_flight, 0);
+        assert_eq!(pool.stats().buffer_count, 3);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{
+    render_resource::{internal::bytemuck, std140::AsStd140, Buffer, DynamicUniformVec},
+    renderer::{RenderDevice, RenderQueue},
+};
+use bevy_ecs::world::{Mut, World};
+use futures_lite::future;
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    ops::{Deref, Range},
+    pin::Pin,
+};
+use thiserror::Error;
+use wgpu::{BufferAddress, BufferAsyncError, BufferDescriptor, BufferUsages};
+
+#[derive(Error, Debug)]
+pub enum ReadbackError {
+    #[error("the buffer was reallocated before its contents were copied")]
+    Reallocated,
+    #[error("the range {0:?} can't be copied, its start and end have to be multiples of 4")]
+    UnalignedRange(Range<BufferAddress>),
+    #[error("the {size} bytes that were read back can't hold a `{type_name}`")]
+    TooSmall {
+        type_name: &'static str,
+        size: usize,
+    },
+    #[error(transparent)]
+    Map(#[from] BufferAsyncError),
+}
+
+type ReadbackCallback = Box<dyn FnOnce(Result<Vec<u8>, ReadbackError>) + Send + Sync>;
+
+/// Resolves the buffer a requested range belongs to when it is copied. Returns `None` if it isn't
+/// the buffer the range belonged to when the readback was requested anymore.
+type BufferSource = Box<dyn Fn(&World) -> Option<Buffer> + Send + Sync>;
+
+struct ReadbackRequest {
+    source: BufferSource,
+    range: Range<BufferAddress>,
+    callback: ReadbackCallback,
+}
+
+struct PendingReadback {
+    staging_buffer: Buffer,
+    /// Resolves once the staging buffer is mapped. The future is only polled through a `&mut`, the
+    /// mutex makes it `Sync`.
+    mapped: Mutex<Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>>,
+    callback: ReadbackCallback,
+}
+
+/// Reads the contents of buffers back from the GPU, e.g. to check that the uniforms of a
+/// component were uploaded as expected while debugging.
+///
+/// The requested ranges are copied at the end of the frame, after all uniforms were written, and
+/// the bytes are passed to the callback of the request in a later frame, once the GPU finished the
+/// copy. A range whose buffer was reallocated until then, e.g. because more entities were
+/// spawned, is reported as [`ReadbackError::Reallocated`] instead of reading stale contents.
+#[derive(Default)]
+pub struct BufferReadback {
+    requests: Vec<ReadbackRequest>,
+    pending: Vec<PendingReadback>,
+}
+
+impl BufferReadback {
+    /// Reads back the `range` of the `buffer`.
+    pub fn read_buffer(
+        &mut self,
+        buffer: &Buffer,
+        range: Range<BufferAddress>,
+        callback: impl FnOnce(Result<Vec<u8>, ReadbackError>) + Send + Sync + 'static,
+    ) {
+        let source = buffer.clone();
+        self.request(
+            Box::new(move |_: &World| Some(source.clone())),
+            range,
+            Box::new(callback),
+        );
+    }
+
+    /// Reads back the uniform at the dynamic `offset` of the `uniforms`, which are a resource that
+    /// derefs to a [`DynamicUniformVec`], like the `ComponentUniforms` of a component type.
+    ///
+    /// Nothing is read if the uniform buffer hasn't been created yet.
+    pub fn read_uniform<R, T>(
+        &mut self,
+        uniforms: &R,
+        offset: u32,
+        callback: impl FnOnce(Result<T, ReadbackError>) + Send + Sync + 'static,
+    ) where
+        R: Deref<Target = DynamicUniformVec<T>> + Send + Sync + 'static,
+        T: AsStd140,
+    {
+        let buffer_id = match uniforms.uniform_buffer() {
+            Some(buffer) => buffer.id(),
+            None => return,
+        };
+        self.request(
+            Box::new(move |world: &World| {
+                let buffer = world.get_resource::<R>()?.uniform_buffer()?;
+                (buffer.id() == buffer_id).then(|| buffer.clone())
+            }),
+            uniform_range::<T>(offset),
+            Box::new(move |bytes| callback(bytes.and_then(|bytes| decode_std140(&bytes)))),
+        );
+    }
+
+    fn request(
+        &mut self,
+        source: BufferSource,
+        range: Range<BufferAddress>,
+        callback: ReadbackCallback,
+    ) {
+        if range.start % wgpu::COPY_BUFFER_ALIGNMENT != 0
+            || range.end % wgpu::COPY_BUFFER_ALIGNMENT != 0
+        {
+            callback(Err(ReadbackError::UnalignedRange(range)));
+            return;
+        }
+        self.requests.push(ReadbackRequest {
+            source,
+            range,
+            callback,
+        });
+    }
+
+    /// Copies the requested ranges into mappable buffers and passes the contents of the finished
+    /// copies to their callbacks, at the start of the [`Cleanup`](crate::RenderStage::Cleanup)
+    /// stage.
+    pub(crate) fn readback_system(world: &mut World) {
+        world.resource_scope(|world, mut readback: Mut<Self>| {
+            let render_device = world.resource::<RenderDevice>();
+            let render_queue = world.resource::<RenderQueue>();
+            readback.copy(world, render_device, render_queue);
+            readback.deliver();
+        });
+    }
+
+    /// Resolves the buffers of the requested ranges. The requests whose buffer was reallocated are
+    /// reported as [`ReadbackError::Reallocated`].
+    fn resolve_requests(&mut self, world: &World) -> Vec<(Buffer, ReadbackRequest)> {
+        self.requests
+            .drain(..)
+            .filter_map(|request| match (request.source)(world) {
+                Some(buffer) => Some((buffer, request)),
+                None => {
+                    (request.callback)(Err(ReadbackError::Reallocated));
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn copy(&mut self, world: &World, render_device: &RenderDevice, render_queue: &RenderQueue) {
+        let requests = self.resolve_requests(world);
+        if requests.is_empty() {
+            return;
+        }
+        let mut encoder = render_device.create_command_encoder(&Default::default());
+        let mut copied = Vec::new();
+        for (buffer, request) in requests {
+            let size = request.range.end - request.range.start;
+            let staging_buffer = render_device.create_buffer(&BufferDescriptor {
+                label: Some("readback_buffer"),
+                size,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            encoder.copy_buffer_to_buffer(&buffer, request.range.start, &staging_buffer, 0, size);
+            copied.push((staging_buffer, request.callback));
+        }
+        render_queue.submit([encoder.finish()]);
+
+        for (staging_buffer, callback) in copied {
+            let mapped = staging_buffer.slice(..).map_async(wgpu::MapMode::Read);
+            self.pending.push(PendingReadback {
+                staging_buffer,
+                mapped: Mutex::new(Box::pin(mapped)),
+                callback,
+            });
+        }
+    }
+
+    /// Passes the contents of the finished copies to their callbacks. The device is polled at the
+    /// start of every frame, which completes the mapping.
+    fn deliver(&mut self) {
+        let mut index = 0;
+        while index < self.pending.len() {
+            let mapped = self.pending[index].mapped.get_mut();
+            let result = match future::block_on(future::poll_once(mapped)) {
+                Some(result) => result,
+                None => {
+                    index += 1;
+                    continue;
+                }
+            };
+            let pending = self.pending.swap_remove(index);
+            let bytes = result.map_err(ReadbackError::from).map(|()| {
+                let slice = pending.staging_buffer.slice(..);
+                let bytes = slice.get_mapped_range().to_vec();
+                pending.staging_buffer.unmap();
+                bytes
+            });
+            (pending.callback)(bytes);
+        }
+    }
+}
+
+/// Returns the range of the uniform of type `T` at the dynamic `offset`. The copy has to end at a
+/// multiple of 4, which is within the slot of the uniform.
+fn uniform_range<T: AsStd140>(offset: u32) -> Range<BufferAddress> {
+    let start = offset as BufferAddress;
+    let size = T::std140_size_static() as BufferAddress;
+    start..start + (size + 3) / 4 * 4
+}
+
+/// Decodes a value from the start of its `std140` representation in the `bytes`.
+pub fn decode_std140<T: AsStd140>(bytes: &[u8]) -> Result<T, ReadbackError> {
+    let mut value = <T::Output as bytemuck::Zeroable>::zeroed();
+    let value_bytes = bytemuck::bytes_of_mut(&mut value);
+    if bytes.len() < value_bytes.len() {
+        return Err(ReadbackError::TooSmall {
+            type_name: std::any::type_name::<T>(),
+            size: bytes.len(),
+        });
+    }
+    value_bytes.copy_from_slice(&bytes[..value_bytes.len()]);
+    Ok(T::from_std140(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_std140, uniform_range, BufferReadback, ReadbackError};
+    use crate::render_resource::std140::{AsStd140, Std140};
+    use bevy_ecs::world::World;
+    use bevy_math::Vec4;
+    use parking_lot::Mutex;
+    use std::{ops::Range, sync::Arc};
+
+    #[derive(AsStd140, Debug, PartialEq)]
+    struct Pattern {
+        value: Vec4,
+        index: u32,
+    }
+
+    #[test]
+    fn values_are_decoded_from_their_std140_bytes() {
+        let pattern = Pattern {
+            value: Vec4::new(1.0, 2.0, 3.0, 4.0),
+            index: 7,
+        };
+        let mut bytes = pattern.as_std140().as_bytes().to_vec();
+        // the slot of a dynamic uniform is padded
+        bytes.resize(256, 0xff);
+        assert_eq!(decode_std140::<Pattern>(&bytes).unwrap(), pattern);
+        assert!(matches!(
+            decode_std140::<Pattern>(&bytes[..8]),
+            Err(ReadbackError::TooSmall { size: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn requests_are_checked_before_copying() {
+        let world = World::new();
+        let mut readback = BufferReadback::default();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let request = |readback: &mut BufferReadback, range| {
+            let errors = errors.clone();
+            readback.request(
+                // the buffer of the range was reallocated
+                Box::new(|_: &World| None),
+                range,
+                Box::new(move |result| errors.lock().push(result.unwrap_err())),
+            );
+        };
+
+        request(&mut readback, 2..6);
+        assert!(matches!(
+            errors.lock()[..],
+            [ReadbackError::UnalignedRange(Range { start: 2, end: 6 })]
+        ));
+        request(&mut readback, 16..32);
+        assert_eq!(readback.requests.len(), 1);
+        assert!(readback.resolve_requests(&world).is_empty());
+        assert!(matches!(errors.lock()[1], ReadbackError::Reallocated));
+
+        // a uniform is copied from its dynamic offset
+        let size = Pattern::std140_size_static() as u64;
+        assert_eq!(uniform_range::<Pattern>(256), 256..256 + size);
+        assert_eq!(uniform_range::<f32>(4), 4..8);
+    }
+}
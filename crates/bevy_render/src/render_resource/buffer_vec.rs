@@ -6,12 +6,64 @@ use bevy_core::{cast_slice, Pod};
 use copyless::VecHelper;
 use wgpu::BufferUsages;
 
+/// Controls how the capacity of a [`BufferVec`] follows the number of its values.
+///
+/// Growing the buffer by a factor and only shrinking it after it has been underused for a while
+/// avoids reallocating it every frame when the number of values fluctuates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferGrowthPolicy {
+    /// The factor the capacity at least grows by when the buffer is too small. Defaults to `2.0`.
+    pub growth_factor: f32,
+    /// The buffer shrinks once less than this fraction of its capacity has been used for
+    /// `shrink_delay` consecutive writes. Defaults to `0.25`, `0.0` disables shrinking.
+    pub shrink_threshold: f32,
+    /// The number of consecutive underused writes after which the buffer shrinks. Defaults to
+    /// `60`.
+    pub shrink_delay: u32,
+}
+
+impl Default for BufferGrowthPolicy {
+    fn default() -> Self {
+        Self {
+            growth_factor: 2.0,
+            shrink_threshold: 0.25,
+            shrink_delay: 60,
+        }
+    }
+}
+
+impl BufferGrowthPolicy {
+    /// Returns the capacity of a buffer of `capacity` values that has to store `len` values.
+    /// `underused_writes` counts the consecutive writes that used less of the buffer than the
+    /// `shrink_threshold`.
+    pub fn next_capacity(&self, capacity: usize, len: usize, underused_writes: &mut u32) -> usize {
+        let grown = || ((len as f32 * self.growth_factor).ceil() as usize).max(len);
+        if len > capacity {
+            *underused_writes = 0;
+            return grown().max((capacity as f32 * self.growth_factor).ceil() as usize);
+        }
+        if (len as f32) < capacity as f32 * self.shrink_threshold {
+            *underused_writes += 1;
+            if *underused_writes >= self.shrink_delay {
+                *underused_writes = 0;
+                // keep room to grow again without reallocating right away
+                return grown().max(1);
+            }
+        } else {
+            *underused_writes = 0;
+        }
+        capacity
+    }
+}
+
 pub struct BufferVec<T: Pod> {
     values: Vec<T>,
     buffer: Option<Buffer>,
     capacity: usize,
     item_size: usize,
     buffer_usage: BufferUsages,
+    growth_policy: BufferGrowthPolicy,
+    underused_writes: u32,
 }
 
 impl<T: Pod> Default for BufferVec<T> {
@@ -22,6 +74,8 @@ impl<T: Pod> Default for BufferVec<T> {
             capacity: 0,
             buffer_usage: BufferUsages::all(),
             item_size: std::mem::size_of::<T>(),
+            growth_policy: BufferGrowthPolicy::default(),
+            underused_writes: 0,
         }
     }
 }
@@ -34,6 +88,13 @@ impl<T: Pod> BufferVec<T> {
         }
     }
 
+    /// Sets how the capacity of the buffer follows the number of values written to it by
+    /// [`BufferVec::write_buffer`].
+    pub fn with_growth_policy(mut self, growth_policy: BufferGrowthPolicy) -> Self {
+        self.growth_policy = growth_policy;
+        self
+    }
+
     #[inline]
     pub fn buffer(&self) -> Option<&Buffer> {
         self.buffer.as_ref()
@@ -62,22 +123,35 @@ impl<T: Pod> BufferVec<T> {
 
     pub fn reserve(&mut self, capacity: usize, device: &RenderDevice) {
         if capacity > self.capacity {
-            self.capacity = capacity;
-            let size = self.item_size * capacity;
-            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
-                label: None,
-                size: size as wgpu::BufferAddress,
-                usage: BufferUsages::COPY_DST | self.buffer_usage,
-                mapped_at_creation: false,
-            }));
+            self.reallocate(capacity, device);
         }
     }
 
+    fn reallocate(&mut self, capacity: usize, device: &RenderDevice) {
+        self.capacity = capacity;
+        let size = self.item_size * capacity;
+        self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: size as wgpu::BufferAddress,
+            usage: BufferUsages::COPY_DST | self.buffer_usage,
+            mapped_at_creation: false,
+        }));
+    }
+
+    /// Writes the values to the buffer, which is reallocated according to the
+    /// [`BufferGrowthPolicy`] first if it is too small or has been too large for a while.
     pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) {
         if self.values.is_empty() {
             return;
         }
-        self.reserve(self.values.len(), device);
+        let capacity = self.growth_policy.next_capacity(
+            self.capacity,
+            self.values.len(),
+            &mut self.underused_writes,
+        );
+        if capacity != self.capacity {
+            self.reallocate(capacity, device);
+        }
         if let Some(buffer) = &self.buffer {
             let range = 0..self.item_size * self.values.len();
             let bytes: &[u8] = cast_slice(&self.values);
@@ -89,3 +163,57 @@ impl<T: Pod> BufferVec<T> {
         self.values.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::BufferGrowthPolicy;
+
+    /// Returns the capacities of a buffer holding each of the `lens` in turn.
+    fn capacities(policy: BufferGrowthPolicy, lens: impl IntoIterator<Item = usize>) -> Vec<usize> {
+        let mut capacity = 0;
+        let mut underused_writes = 0;
+        lens.into_iter()
+            .map(|len| {
+                capacity = policy.next_capacity(capacity, len, &mut underused_writes);
+                capacity
+            })
+            .collect()
+    }
+
+    #[test]
+    fn buffers_grow_geometrically() {
+        let policy = BufferGrowthPolicy::default();
+        assert_eq!(
+            capacities(policy, [10, 11, 21, 20, 41, 200]),
+            vec![20, 20, 42, 42, 42, 400]
+        );
+
+        // fluctuating lengths don't reallocate the buffer
+        let lens = (0..100).map(|frame| 100 + frame % 7);
+        assert!(capacities(policy, lens)
+            .iter()
+            .all(|capacity| *capacity == 200));
+    }
+
+    #[test]
+    fn buffers_shrink_after_sustained_low_usage() {
+        let policy = BufferGrowthPolicy {
+            growth_factor: 1.5,
+            shrink_threshold: 0.5,
+            shrink_delay: 3,
+        };
+        assert_eq!(
+            capacities(policy, [100, 40, 40, 80, 40, 40, 40, 40]),
+            vec![150, 150, 150, 150, 150, 150, 60, 60]
+        );
+
+        // shrinking can be disabled
+        let policy = BufferGrowthPolicy {
+            shrink_threshold: 0.0,
+            ..policy
+        };
+        assert!(capacities(policy, [100, 1, 1, 1, 1, 1])
+            .iter()
+            .all(|capacity| *capacity == 150));
+    }
+}
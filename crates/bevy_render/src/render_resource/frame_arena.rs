@@ -0,0 +1,178 @@
+use bevy_ecs::system::ResMut;
+use parking_lot::Mutex;
+use std::{
+    alloc::{self, Layout},
+    mem,
+    ptr::NonNull,
+};
+
+/// The size of the first chunk allocated by a [`FrameArena`] in bytes.
+const MIN_CHUNK_SIZE: usize = 16 * 1024;
+/// The largest alignment of values allocated in a [`FrameArena`].
+const CHUNK_ALIGN: usize = 16;
+
+/// A bump allocator for temporary values that are only needed during the current frame, like the
+/// keys assembled to look up cached bind groups.
+///
+/// Allocating only bumps an offset into a chunk of memory, and all values are freed at once when
+/// the arena is reset at the end of the frame. The chunks allocated in a frame are merged into a
+/// single chunk by the reset, so frames that don't need more memory than the previous ones don't
+/// allocate at all.
+///
+/// Only [`Copy`] values can be allocated, since they are never dropped. Allocating only needs a
+/// shared reference, so systems of the render app running in parallel can use the arena at once.
+#[derive(Default)]
+pub struct FrameArena {
+    state: Mutex<ArenaState>,
+}
+
+impl FrameArena {
+    /// Returns a slice of `len` copies of the `value`, which lives until the end of the frame.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_fill_copy<T: Copy>(&self, len: usize, value: T) -> &mut [T] {
+        self.alloc_slice_fill_iter((0..len).map(|_| value))
+    }
+
+    /// Returns a slice of the values of the `iter`, which lives until the end of the frame.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_fill_iter<T: Copy>(
+        &self,
+        iter: impl ExactSizeIterator<Item = T>,
+    ) -> &mut [T] {
+        assert!(
+            mem::align_of::<T>() <= CHUNK_ALIGN,
+            "{} is aligned to more than {} bytes",
+            std::any::type_name::<T>(),
+            CHUNK_ALIGN
+        );
+        let len = iter.len();
+        if len == 0 || mem::size_of::<T>() == 0 {
+            return &mut [];
+        }
+        let layout = Layout::array::<T>(len).unwrap();
+        let ptr = self.state.lock().alloc(layout).cast::<T>();
+        // only the written values are exposed, in case the iterator's length was wrong
+        let mut written = 0;
+        for value in iter.take(len) {
+            // SAFETY: the allocation has room for `len` values of `T` and isn't handed out again
+            // until the arena is reset, which requires `&mut self`
+            unsafe { ptr.as_ptr().add(written).write(value) };
+            written += 1;
+        }
+        // SAFETY: the first `written` values are initialized and `T` is `Copy`, so they don't
+        // need to be dropped
+        unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), written) }
+    }
+
+    /// The total size of the chunks of the arena in bytes.
+    pub fn capacity(&self) -> usize {
+        self.state
+            .lock()
+            .chunks
+            .iter()
+            .map(|chunk| chunk.layout.size())
+            .sum()
+    }
+
+    /// Frees all values allocated in the arena.
+    pub fn reset(&mut self) {
+        let state = self.state.get_mut();
+        if state.chunks.len() > 1 {
+            let size = state.chunks.iter().map(|chunk| chunk.layout.size()).sum();
+            state.chunks.clear();
+            state.chunks.push(Chunk::new(size));
+        }
+        state.used = 0;
+    }
+
+    /// Frees the temporary values of this frame.
+    pub(crate) fn reset_system(mut frame_arena: ResMut<Self>) {
+        frame_arena.reset();
+    }
+}
+
+#[derive(Default)]
+struct ArenaState {
+    chunks: Vec<Chunk>,
+    /// The number of bytes used of the last chunk.
+    used: usize,
+}
+
+impl ArenaState {
+    fn alloc(&mut self, layout: Layout) -> NonNull<u8> {
+        let start = (self.used + layout.align() - 1) & !(layout.align() - 1);
+        let start = match self.chunks.last() {
+            Some(chunk) if start + layout.size() <= chunk.layout.size() => start,
+            last => {
+                let previous_size = last.map_or(0, |chunk| chunk.layout.size());
+                let size = (previous_size * 2).max(layout.size()).max(MIN_CHUNK_SIZE);
+                self.chunks.push(Chunk::new(size));
+                0
+            }
+        };
+        self.used = start + layout.size();
+        let chunk = self.chunks.last().unwrap();
+        // SAFETY: the allocation fits into the chunk
+        unsafe { NonNull::new_unchecked(chunk.ptr.as_ptr().add(start)) }
+    }
+}
+
+struct Chunk {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+// SAFETY: the chunk owns its memory
+unsafe impl Send for Chunk {}
+
+impl Chunk {
+    fn new(size: usize) -> Self {
+        let layout = Layout::from_size_align(size, CHUNK_ALIGN).unwrap();
+        // SAFETY: the size is at least `MIN_CHUNK_SIZE`
+        let ptr = unsafe { alloc::alloc(layout) };
+        match NonNull::new(ptr) {
+            Some(ptr) => Self { ptr, layout },
+            None => alloc::handle_alloc_error(layout),
+        }
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        // SAFETY: the memory was allocated with the same layout
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameArena, MIN_CHUNK_SIZE};
+    use crate::counting_allocator::allocations;
+
+    #[test]
+    fn values_are_bump_allocated() {
+        let mut arena = FrameArena::default();
+        let bytes = arena.alloc_slice_fill_copy(3, 1u8);
+        let words = arena.alloc_slice_fill_iter([1u64, 2, 3].into_iter());
+        bytes[0] = 2;
+        assert_eq!(bytes, [2, 1, 1]);
+        assert_eq!(words, [1, 2, 3]);
+        assert_eq!(words.as_ptr() as usize % 8, 0);
+        assert!(arena.alloc_slice_fill_copy(0, 0u32).is_empty());
+        assert_eq!(arena.capacity(), MIN_CHUNK_SIZE);
+
+        // a frame that didn't fit into the chunk leaves a single larger chunk behind
+        arena.alloc_slice_fill_copy(MIN_CHUNK_SIZE, 0u8);
+        assert_eq!(arena.capacity(), 3 * MIN_CHUNK_SIZE);
+        arena.reset();
+        assert_eq!(arena.capacity(), 3 * MIN_CHUNK_SIZE);
+
+        let before = allocations();
+        for _frame in 0..10 {
+            arena.alloc_slice_fill_copy(2 * MIN_CHUNK_SIZE, 0u8);
+            arena.alloc_slice_fill_copy(100, 0u64);
+            arena.reset();
+        }
+        assert_eq!(allocations(), before);
+    }
+}
@@ -0,0 +1,54 @@
+/// The number of frames a cached GPU resource is kept without being used. Resources that are only
+/// needed every few frames, like the bind groups of an effect that is toggled on and off, stay
+/// cached, while the resources of a despawned spike of entities are freed within a second.
+pub(crate) const MAX_UNUSED_FRAMES: u64 = 60;
+
+/// The last frame a cached resource was used in, which decides when it is freed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct LastUsed(u64);
+
+impl LastUsed {
+    #[inline]
+    pub(crate) fn new(frame: u64) -> Self {
+        Self(frame)
+    }
+
+    #[inline]
+    pub(crate) fn frame(&self) -> u64 {
+        self.0
+    }
+
+    /// Marks the resource as used in the `frame`.
+    #[inline]
+    pub(crate) fn set(&mut self, frame: u64) {
+        self.0 = self.0.max(frame);
+    }
+
+    /// Whether the resource wasn't used in the [`MAX_UNUSED_FRAMES`] frames up to the `frame`, so
+    /// that it should be freed.
+    #[inline]
+    pub(crate) fn is_unused(&self, frame: u64) -> bool {
+        frame.saturating_sub(self.0) >= MAX_UNUSED_FRAMES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LastUsed, MAX_UNUSED_FRAMES};
+
+    #[test]
+    fn resources_are_unused_after_max_unused_frames() {
+        let mut last_used = LastUsed::new(10);
+        assert!(!last_used.is_unused(10));
+        assert!(!last_used.is_unused(10 + MAX_UNUSED_FRAMES - 1));
+        assert!(last_used.is_unused(10 + MAX_UNUSED_FRAMES));
+        // a resource used in a later frame than the current one isn't unused either
+        assert!(!last_used.is_unused(5));
+
+        last_used.set(50);
+        assert!(!last_used.is_unused(10 + MAX_UNUSED_FRAMES));
+        // using it in an earlier frame doesn't make it expire sooner
+        last_used.set(20);
+        assert_eq!(last_used.frame(), 50);
+    }
+}
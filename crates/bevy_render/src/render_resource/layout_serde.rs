@@ -0,0 +1,176 @@
+use crate::render_resource::{AsVertexFormats, VertexBufferLayout};
+use serde::{Deserialize, Serialize};
+use wgpu::{VertexAttribute, VertexFormat, VertexStepMode};
+
+/// Serializes the [`VertexBufferLayout`] of a type implementing [`AsVertexFormats`] as JSON, e.g.
+/// for external tools generating shaders that read the type as vertex or instance data.
+///
+/// The layout can be read back with [`import_layout_json`].
+pub fn export_layout_json<T: AsVertexFormats>(step_mode: VertexStepMode) -> String {
+    serde_json::to_string_pretty(&VertexBufferLayout::from_type::<T>(step_mode))
+        .expect("vertex buffer layouts can always be serialized")
+}
+
+/// Reads a [`VertexBufferLayout`] written by [`export_layout_json`].
+pub fn import_layout_json(json: &str) -> Result<VertexBufferLayout, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "VertexFormat")]
+pub(crate) enum VertexFormatDef {
+    Uint8x2,
+    Uint8x4,
+    Sint8x2,
+    Sint8x4,
+    Unorm8x2,
+    Unorm8x4,
+    Snorm8x2,
+    Snorm8x4,
+    Uint16x2,
+    Uint16x4,
+    Sint16x2,
+    Sint16x4,
+    Unorm16x2,
+    Unorm16x4,
+    Snorm16x2,
+    Snorm16x4,
+    Float16x2,
+    Float16x4,
+    Float32,
+    Float32x2,
+    Float32x3,
+    Float32x4,
+    Uint32,
+    Uint32x2,
+    Uint32x3,
+    Uint32x4,
+    Sint32,
+    Sint32x2,
+    Sint32x3,
+    Sint32x4,
+    Float64,
+    Float64x2,
+    Float64x3,
+    Float64x4,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "VertexStepMode")]
+pub(crate) enum VertexStepModeDef {
+    Vertex,
+    Instance,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "VertexAttribute")]
+pub(crate) struct VertexAttributeDef {
+    #[serde(with = "VertexFormatDef")]
+    format: VertexFormat,
+    offset: u64,
+    shader_location: u32,
+}
+
+/// Serializes the attributes of a [`VertexBufferLayout`] through [`VertexAttributeDef`].
+pub(crate) mod vertex_attributes {
+    use super::VertexAttributeDef;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use wgpu::VertexAttribute;
+
+    #[derive(Serialize, Deserialize)]
+    struct Attribute(#[serde(with = "VertexAttributeDef")] VertexAttribute);
+
+    pub fn serialize<S: Serializer>(
+        attributes: &[VertexAttribute],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(attributes.iter().copied().map(Attribute))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<VertexAttribute>, D::Error> {
+        let attributes = Vec::<Attribute>::deserialize(deserializer)?;
+        Ok(attributes
+            .into_iter()
+            .map(|attribute| attribute.0)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_layout_json, import_layout_json};
+    use crate::render_resource::{
+        CompactColor, ReflectedBinding, ReflectedVertexInput, VertexBufferLayout, VertexDataLayout,
+    };
+    use bevy_math::Mat4;
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::fmt::Debug;
+    use wgpu::{VertexFormat, VertexStepMode};
+
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: T) {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn every_vertex_format_round_trips() {
+        use VertexFormat::*;
+        let formats = [
+            Uint8x2, Uint8x4, Sint8x2, Sint8x4, Unorm8x2, Unorm8x4, Snorm8x2, Snorm8x4, Uint16x2,
+            Uint16x4, Sint16x2, Sint16x4, Unorm16x2, Unorm16x4, Snorm16x2, Snorm16x4, Float16x2,
+            Float16x4, Float32, Float32x2, Float32x3, Float32x4, Uint32, Uint32x2, Uint32x3,
+            Uint32x4, Sint32, Sint32x2, Sint32x3, Sint32x4, Float64, Float64x2, Float64x3,
+            Float64x4,
+        ];
+        for step_mode in [VertexStepMode::Vertex, VertexStepMode::Instance] {
+            round_trip(VertexBufferLayout::from_vertex_formats(step_mode, formats));
+            for format in formats {
+                round_trip(VertexBufferLayout::from_vertex_formats(step_mode, [format]));
+            }
+        }
+        round_trip(VertexBufferLayout::default());
+    }
+
+    #[test]
+    fn exported_layouts_can_be_imported() {
+        let json = export_layout_json::<Mat4>(VertexStepMode::Instance);
+        assert_eq!(
+            import_layout_json(&json).unwrap(),
+            VertexBufferLayout::from_type::<Mat4>(VertexStepMode::Instance)
+        );
+
+        let json = export_layout_json::<CompactColor>(VertexStepMode::Vertex);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "array_stride": 4,
+                "step_mode": "Vertex",
+                "attributes": [{
+                    "format": "Unorm8x4",
+                    "offset": 0,
+                    "shader_location": 0,
+                }],
+            })
+        );
+
+        assert!(import_layout_json(r#"{ "array_stride": 4 }"#).is_err());
+    }
+
+    #[test]
+    fn reflected_interfaces_round_trip() {
+        for data_layout in [VertexDataLayout::Interleaved, VertexDataLayout::Separate] {
+            round_trip(data_layout);
+        }
+        for name in [None, Some("material".to_string())] {
+            round_trip(ReflectedBinding {
+                name: name.clone(),
+                group: 1,
+                binding: 2,
+            });
+            round_trip(ReflectedVertexInput { name, location: 3 });
+        }
+    }
+}
@@ -1,26 +1,51 @@
 mod bind_group;
+mod bind_group_cache;
 mod bind_group_layout;
 mod buffer;
+mod buffer_pool;
+mod buffer_readback;
 mod buffer_vec;
+mod frame_arena;
+mod last_used;
+#[cfg(feature = "serialize")]
+mod layout_serde;
 mod pipeline;
 mod pipeline_cache;
 mod pipeline_specializer;
 mod shader;
+mod shader_disk_cache;
 mod storage_buffer;
 mod texture;
+mod uniform_allocator;
+mod uniform_registry;
 mod uniform_vec;
+mod vertex_formats;
 
 pub use bind_group::*;
+pub use bind_group_cache::*;
 pub use bind_group_layout::*;
 pub use buffer::*;
+pub use buffer_pool::*;
+pub use buffer_readback::*;
 pub use buffer_vec::*;
+pub use frame_arena::*;
+#[cfg(feature = "serialize")]
+pub use layout_serde::*;
 pub use pipeline::*;
 pub use pipeline_cache::*;
 pub use pipeline_specializer::*;
 pub use shader::*;
+pub use shader_disk_cache::*;
 pub use storage_buffer::*;
 pub use texture::*;
+pub use uniform_allocator::*;
+pub use uniform_registry::*;
 pub use uniform_vec::*;
+pub use vertex_formats::*;
+
+pub(crate) use last_used::LastUsed;
+#[cfg(test)]
+pub(crate) use last_used::MAX_UNUSED_FRAMES;
 
 // TODO: decide where re-exports should go
 pub use wgpu::{
@@ -34,14 +59,14 @@ pub use wgpu::{
     FrontFace, ImageCopyBuffer, ImageCopyBufferBase, ImageCopyTexture, ImageCopyTextureBase,
     ImageDataLayout, ImageSubresourceRange, IndexFormat, Limits as WgpuLimits, LoadOp, MapMode,
     MultisampleState, Operations, Origin3d, PipelineLayout, PipelineLayoutDescriptor, PolygonMode,
-    PrimitiveState, PrimitiveTopology, RenderPassColorAttachment, RenderPassDepthStencilAttachment,
-    RenderPassDescriptor, RenderPipelineDescriptor as RawRenderPipelineDescriptor,
-    SamplerBindingType, SamplerDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource,
-    ShaderStages, StencilFaceState, StencilOperation, StencilState, StorageTextureAccess,
-    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
-    TextureUsages, TextureViewDescriptor, TextureViewDimension, VertexAttribute,
-    VertexBufferLayout as RawVertexBufferLayout, VertexFormat, VertexState as RawVertexState,
-    VertexStepMode,
+    PrimitiveState, PrimitiveTopology, PushConstantRange, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor,
+    RenderPipelineDescriptor as RawRenderPipelineDescriptor, SamplerBindingType, SamplerDescriptor,
+    ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilFaceState,
+    StencilOperation, StencilState, StorageTextureAccess, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor,
+    TextureViewDimension, VertexAttribute, VertexBufferLayout as RawVertexBufferLayout,
+    VertexFormat, VertexState as RawVertexState, VertexStepMode,
 };
 
 pub use bevy_crevice::*;
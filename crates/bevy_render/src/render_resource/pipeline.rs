@@ -1,10 +1,14 @@
-use crate::render_resource::{BindGroupLayout, Shader};
+use crate::render_resource::{
+    shader_def_name, BindGroupLayout, Shader, ShaderDefs, SpecializationConstant,
+};
 use bevy_asset::Handle;
 use bevy_reflect::Uuid;
+use bevy_utils::HashMap;
 use std::{borrow::Cow, ops::Deref, sync::Arc};
+use thiserror::Error;
 use wgpu::{
     BufferAddress, ColorTargetState, DepthStencilState, MultisampleState, PrimitiveState,
-    VertexAttribute, VertexFormat, VertexStepMode,
+    PushConstantRange, ShaderStages, VertexAttribute, VertexFormat, VertexStepMode,
 };
 
 /// A [`RenderPipeline`] identifier.
@@ -87,7 +91,7 @@ impl Deref for ComputePipeline {
 }
 
 /// Describes a render (graphics) pipeline.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct RenderPipelineDescriptor {
     /// Debug label of the pipeline. This will show up in graphics debuggers for easy identification.
     pub label: Option<Cow<'static, str>>,
@@ -103,28 +107,156 @@ pub struct RenderPipelineDescriptor {
     pub multisample: MultisampleState,
     /// The compiled fragment stage, its entry point, and the color targets.
     pub fragment: Option<FragmentState>,
+    /// The ranges of push constants used by the pipeline. Push constants are only available if
+    /// [`RenderDevice::supports_push_constants`](crate::renderer::RenderDevice::supports_push_constants)
+    /// returns `true` for their size. The
+    /// [`PushConstantComponentPlugin`](crate::render_component::PushConstantComponentPlugin)
+    /// declares them only if they are, and falls back to a uniform otherwise.
+    pub push_constant_ranges: Vec<PushConstantRange>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+impl RenderPipelineDescriptor {
+    /// Adds `shader_def` to the shader defs of the given `stages`.
+    ///
+    /// Shader modules are cached per stage, so a shader def which is only added to the stage using
+    /// it doesn't cause new permutations of the other stage to be compiled.
+    pub fn push_shader_def(
+        &mut self,
+        shader_def: impl Into<Cow<'static, str>>,
+        stages: ShaderStages,
+    ) {
+        let shader_def: Cow<'static, str> = shader_def.into();
+        if stages.contains(ShaderStages::VERTEX) {
+            self.vertex.shader_defs.push(shader_def.clone());
+        }
+        if let Some(fragment) = &mut self.fragment {
+            if stages.contains(ShaderStages::FRAGMENT) {
+                fragment.shader_defs.push(shader_def);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct VertexState {
     /// The compiled shader module for this stage.
     pub shader: Handle<Shader>,
-    pub shader_defs: Vec<String>,
+    pub shader_defs: ShaderDefs,
     /// The name of the entry point in the compiled shader. There must be a
     /// function with this name in the shader.
     pub entry_point: Cow<'static, str>,
+    /// Entry points replacing [`entry_point`](Self::entry_point) if their shader def is one of the
+    /// `shader_defs`. The first matching override is used.
+    pub entry_point_overrides: Vec<EntryPointOverride>,
+    /// Values for the SPIR-V specialization constants of the shader. Changing them requires a
+    /// new pipeline, but no new shader def permutation.
+    pub specialization_constants: Vec<SpecializationConstant>,
     /// The format of any vertex buffers used with this pipeline.
     pub buffers: Vec<VertexBufferLayout>,
+    /// Disables the warning about vertex attributes in [`buffers`](Self::buffers) which are not
+    /// read by the shader, for pipelines sharing a vertex layout with shaders using more inputs.
+    pub allow_unused_attributes: bool,
+}
+
+impl VertexState {
+    /// Checks that no two attributes of the vertex [`buffers`](Self::buffers) use the same shader
+    /// location.
+    pub fn validate_buffers(&self) -> Result<(), DuplicateShaderLocation> {
+        let mut locations = HashMap::default();
+        for (buffer, layout) in self.buffers.iter().enumerate() {
+            for (attribute, vertex_attribute) in layout.attributes.iter().enumerate() {
+                if let Some((first_buffer, first_attribute)) =
+                    locations.insert(vertex_attribute.shader_location, (buffer, attribute))
+                {
+                    return Err(DuplicateShaderLocation {
+                        shader_location: vertex_attribute.shader_location,
+                        first_buffer,
+                        first_attribute,
+                        second_buffer: buffer,
+                        second_attribute: attribute,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the entry point used for the current `shader_defs`.
+    pub fn resolved_entry_point(&self) -> &str {
+        resolve_entry_point(
+            &self.entry_point,
+            &self.entry_point_overrides,
+            &self.shader_defs,
+        )
+    }
+}
+
+/// Returned by [`VertexState::validate_buffers`] if two vertex attributes use the same shader
+/// location.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("shader location {shader_location} is used by attribute {first_attribute} of vertex buffer {first_buffer} and by attribute {second_attribute} of vertex buffer {second_buffer}")]
+pub struct DuplicateShaderLocation {
+    pub shader_location: u32,
+    pub first_buffer: usize,
+    pub first_attribute: usize,
+    pub second_buffer: usize,
+    pub second_attribute: usize,
+}
+
+/// Selects a different entry point of a shader stage whenever a shader def is enabled, e.g.
+/// `vs_skinned` instead of `vs_main` if the stage's shader defs contain `SKINNED`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntryPointOverride {
+    pub shader_def: Cow<'static, str>,
+    pub entry_point: Cow<'static, str>,
+}
+
+impl EntryPointOverride {
+    pub fn new(
+        shader_def: impl Into<Cow<'static, str>>,
+        entry_point: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            shader_def: shader_def.into(),
+            entry_point: entry_point.into(),
+        }
+    }
+}
+
+fn resolve_entry_point<'a>(
+    entry_point: &'a str,
+    overrides: &'a [EntryPointOverride],
+    shader_defs: &[Cow<'static, str>],
+) -> &'a str {
+    overrides
+        .iter()
+        .find(|entry_point_override| {
+            shader_defs
+                .iter()
+                .any(|shader_def| shader_def_name(shader_def) == entry_point_override.shader_def)
+        })
+        .map_or(entry_point, |entry_point_override| {
+            &entry_point_override.entry_point
+        })
 }
 
 /// Describes how the vertex buffer is interpreted.
 #[derive(Default, Clone, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct VertexBufferLayout {
     /// The stride, in bytes, between elements of this buffer.
     pub array_stride: BufferAddress,
     /// How often this vertex buffer is "stepped" forward.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(with = "crate::render_resource::layout_serde::VertexStepModeDef")
+    )]
     pub step_mode: VertexStepMode,
     /// The list of attributes which comprise a single vertex.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(with = "crate::render_resource::layout_serde::vertex_attributes")
+    )]
     pub attributes: Vec<VertexAttribute>,
 }
 
@@ -154,30 +286,806 @@ impl VertexBufferLayout {
             attributes,
         }
     }
+
+    /// Moves the shader locations of all attributes, so that they start at `first_location`.
+    /// Layouts created by [`VertexBufferLayout::from_vertex_formats`] start at location zero,
+    /// which collides with the locations of the other buffers of a pipeline (e.g. the mesh).
+    pub fn with_first_shader_location(mut self, first_location: u32) -> Self {
+        if let Some(start) = self
+            .attributes
+            .iter()
+            .map(|attribute| attribute.shader_location)
+            .min()
+        {
+            for attribute in &mut self.attributes {
+                attribute.shader_location = attribute.shader_location - start + first_location;
+            }
+        }
+        self
+    }
+}
+
+/// Builds a [`VertexBufferLayout`] by hand, e.g. for procedural meshes or custom instance data,
+/// keeping track of the offsets and shader locations of the attributes.
+///
+/// Every attribute is named, so that [`build`](Self::build) can report which attributes are
+/// inconsistent. The names are not part of the built layout.
+///
+/// ```
+/// # use bevy_render::render_resource::VertexBufferLayoutBuilder;
+/// # use wgpu::{VertexFormat, VertexStepMode};
+/// let layout = VertexBufferLayoutBuilder::new()
+///     .step_mode(VertexStepMode::Instance)
+///     .attribute("position", VertexFormat::Float32x3)
+///     .attribute("color", VertexFormat::Unorm8x4)
+///     .attribute_at("scale", VertexFormat::Float32, 16, 4)
+///     .build()
+///     .unwrap();
+/// assert_eq!(layout.array_stride, 20);
+/// assert_eq!(layout.attributes[1].offset, 12);
+/// assert_eq!(layout.attributes[1].shader_location, 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct VertexBufferLayoutBuilder {
+    step_mode: VertexStepMode,
+    array_stride: Option<BufferAddress>,
+    attributes: Vec<(Cow<'static, str>, VertexAttribute)>,
+}
+
+impl VertexBufferLayoutBuilder {
+    /// Creates a builder for a layout stepped per vertex without any attributes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how often the vertex buffer is stepped forward, which is per vertex by default.
+    pub fn step_mode(mut self, step_mode: VertexStepMode) -> Self {
+        self.step_mode = step_mode;
+        self
+    }
+
+    /// Sets the array stride, which is the end of the last attribute rounded up to the alignment
+    /// of vertex buffer strides by default.
+    pub fn array_stride(mut self, array_stride: BufferAddress) -> Self {
+        self.array_stride = Some(array_stride);
+        self
+    }
+
+    /// Adds an attribute directly behind the end of the previous attribute, at the shader location
+    /// following the highest location used so far.
+    pub fn attribute(self, name: impl Into<Cow<'static, str>>, format: VertexFormat) -> Self {
+        let offset = self
+            .attributes
+            .iter()
+            .map(|(_, attribute)| attribute.offset + attribute.format.size())
+            .max()
+            .unwrap_or(0);
+        let shader_location = self
+            .attributes
+            .iter()
+            .map(|(_, attribute)| attribute.shader_location + 1)
+            .max()
+            .unwrap_or(0);
+        self.attribute_at(name, format, offset, shader_location)
+    }
+
+    /// Adds an attribute at the given offset and shader location.
+    pub fn attribute_at(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        format: VertexFormat,
+        offset: BufferAddress,
+        shader_location: u32,
+    ) -> Self {
+        self.attributes.push((
+            name.into(),
+            VertexAttribute {
+                format,
+                offset,
+                shader_location,
+            },
+        ));
+        self
+    }
+
+    /// Builds the layout, checking that the names and shader locations of the attributes are
+    /// unique, that no attributes overlap, that the offsets and the array stride are aligned and
+    /// that all attributes fit into the array stride.
+    pub fn build(self) -> Result<VertexBufferLayout, VertexLayoutError> {
+        let end = self
+            .attributes
+            .iter()
+            .map(|(_, attribute)| attribute.offset + attribute.format.size())
+            .max()
+            .unwrap_or(0);
+        let array_stride = self
+            .array_stride
+            .unwrap_or_else(|| align_vertex_stride(end));
+        if array_stride % wgpu::VERTEX_STRIDE_ALIGNMENT != 0 {
+            return Err(VertexLayoutError::UnalignedStride { array_stride });
+        }
+
+        for (index, (name, attribute)) in self.attributes.iter().enumerate() {
+            let alignment = attribute.format.size().min(4);
+            if attribute.offset % alignment != 0 {
+                return Err(VertexLayoutError::UnalignedAttribute {
+                    name: name.clone(),
+                    offset: attribute.offset,
+                    alignment,
+                });
+            }
+            let end = attribute.offset + attribute.format.size();
+            if end > array_stride {
+                return Err(VertexLayoutError::AttributeExceedsStride {
+                    name: name.clone(),
+                    end,
+                    array_stride,
+                });
+            }
+            for (other_name, other) in &self.attributes[..index] {
+                if other_name == name {
+                    return Err(VertexLayoutError::DuplicateName { name: name.clone() });
+                }
+                if other.shader_location == attribute.shader_location {
+                    return Err(VertexLayoutError::DuplicateShaderLocation {
+                        shader_location: attribute.shader_location,
+                        first: other_name.clone(),
+                        second: name.clone(),
+                    });
+                }
+                let other_end = other.offset + other.format.size();
+                if attribute.offset < other_end && other.offset < end {
+                    return Err(VertexLayoutError::OverlappingAttributes {
+                        first: other_name.clone(),
+                        second: name.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(VertexBufferLayout {
+            array_stride,
+            step_mode: self.step_mode,
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(|(_, attribute)| attribute)
+                .collect(),
+        })
+    }
+}
+
+/// Returned by [`VertexBufferLayoutBuilder::build`] if the attributes of a layout are
+/// inconsistent. The variants contain the names of the involved attributes.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum VertexLayoutError {
+    #[error("the vertex buffer layout has several attributes named {name}")]
+    DuplicateName { name: Cow<'static, str> },
+    #[error("shader location {shader_location} is used by the attributes {first} and {second}")]
+    DuplicateShaderLocation {
+        shader_location: u32,
+        first: Cow<'static, str>,
+        second: Cow<'static, str>,
+    },
+    #[error("the attributes {first} and {second} overlap")]
+    OverlappingAttributes {
+        first: Cow<'static, str>,
+        second: Cow<'static, str>,
+    },
+    #[error("the attribute {name} ends at byte {end}, after the array stride of {array_stride}")]
+    AttributeExceedsStride {
+        name: Cow<'static, str>,
+        end: BufferAddress,
+        array_stride: BufferAddress,
+    },
+    #[error("the attribute {name} is at offset {offset}, which isn't a multiple of {alignment}")]
+    UnalignedAttribute {
+        name: Cow<'static, str>,
+        offset: BufferAddress,
+        alignment: BufferAddress,
+    },
+    #[error(
+        "the array stride of {array_stride} isn't a multiple of {}",
+        wgpu::VERTEX_STRIDE_ALIGNMENT
+    )]
+    UnalignedStride { array_stride: BufferAddress },
+}
+
+/// A [`VertexBufferLayout`] together with the name of the source of its data, e.g. `Mesh` or the
+/// type of the instance data. The name is used in the errors of [`merge_vertex_buffers`] and
+/// [`NamedVertexBufferLayout::merge`].
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct NamedVertexBufferLayout {
+    pub name: Cow<'static, str>,
+    pub layout: VertexBufferLayout,
+}
+
+impl NamedVertexBufferLayout {
+    pub fn new(name: impl Into<Cow<'static, str>>, layout: VertexBufferLayout) -> Self {
+        Self {
+            name: name.into(),
+            layout,
+        }
+    }
+
+    /// Interleaves the layout of `other` into this one, for a single buffer whose elements consist
+    /// of an element of this layout followed by an element of `other`.
+    ///
+    /// The attributes of `other` are moved behind the (4 byte aligned) end of this layout's
+    /// elements, and to the shader locations directly following the highest location of this
+    /// layout, in the order of their current locations.
+    pub fn merge(&self, other: &Self) -> Result<Self, VertexBufferMergeError> {
+        self.validate()?;
+        other.validate()?;
+        if self.layout.step_mode != other.layout.step_mode {
+            return Err(VertexBufferMergeError::IncompatibleStepModes {
+                first: self.name.clone(),
+                first_step_mode: self.layout.step_mode,
+                second: other.name.clone(),
+                second_step_mode: other.layout.step_mode,
+            });
+        }
+
+        let offset = align_vertex_stride(self.layout.array_stride);
+        let mut attributes = self.layout.attributes.clone();
+        attributes.extend(
+            other
+                .relocated_attributes(next_shader_location(&self.layout))
+                .map(|attribute| VertexAttribute {
+                    offset: attribute.offset + offset,
+                    ..attribute
+                }),
+        );
+
+        Ok(Self {
+            name: format!("{} + {}", self.name, other.name).into(),
+            layout: VertexBufferLayout {
+                array_stride: offset + align_vertex_stride(other.layout.array_stride),
+                step_mode: self.layout.step_mode,
+                attributes,
+            },
+        })
+    }
+
+    /// Checks that no two attributes of the layout share a shader location or overlap, and that
+    /// all attributes fit into the array stride.
+    pub fn validate(&self) -> Result<(), VertexBufferMergeError> {
+        let attributes = &self.layout.attributes;
+        for (index, attribute) in attributes.iter().enumerate() {
+            let end = attribute.offset + attribute.format.size();
+            if end > self.layout.array_stride {
+                return Err(VertexBufferMergeError::AttributeExceedsStride {
+                    layout: self.name.clone(),
+                    shader_location: attribute.shader_location,
+                    end,
+                    array_stride: self.layout.array_stride,
+                });
+            }
+            for other in &attributes[..index] {
+                if other.shader_location == attribute.shader_location {
+                    return Err(VertexBufferMergeError::DuplicateShaderLocation {
+                        layout: self.name.clone(),
+                        shader_location: attribute.shader_location,
+                    });
+                }
+                let other_end = other.offset + other.format.size();
+                if attribute.offset < other_end && other.offset < end {
+                    return Err(VertexBufferMergeError::OverlappingAttributes {
+                        layout: self.name.clone(),
+                        first_shader_location: other.shader_location,
+                        second_shader_location: attribute.shader_location,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the attributes moved to contiguous shader locations starting at `first_location`,
+    /// in the order of their current locations.
+    fn relocated_attributes(
+        &self,
+        first_location: u32,
+    ) -> impl Iterator<Item = VertexAttribute> + '_ {
+        let mut attributes = self.layout.attributes.iter().collect::<Vec<_>>();
+        attributes.sort_by_key(|attribute| attribute.shader_location);
+        attributes
+            .into_iter()
+            .zip(first_location..)
+            .map(|(attribute, shader_location)| VertexAttribute {
+                shader_location,
+                ..*attribute
+            })
+    }
+}
+
+/// Combines the layouts of several sources of vertex data into the vertex buffers of a
+/// [`VertexState`], one buffer per layout.
+///
+/// The shader locations of the first layout are kept (the mesh layout usually uses the locations
+/// expected by the shader), while the attributes of every following layout are moved to the
+/// contiguous locations following the highest location used so far.
+///
+/// To combine layouts describing a single buffer, use [`NamedVertexBufferLayout::merge`] instead.
+pub fn merge_vertex_buffers(
+    layouts: &[NamedVertexBufferLayout],
+) -> Result<Vec<VertexBufferLayout>, VertexBufferMergeError> {
+    let mut buffers: Vec<VertexBufferLayout> = Vec::with_capacity(layouts.len());
+    let mut next_location = 0;
+    for (index, layout) in layouts.iter().enumerate() {
+        layout.validate()?;
+        let buffer = if index == 0 {
+            layout.layout.clone()
+        } else {
+            VertexBufferLayout {
+                attributes: layout.relocated_attributes(next_location).collect(),
+                ..layout.layout.clone()
+            }
+        };
+        next_location = next_location.max(next_shader_location(&buffer));
+        buffers.push(buffer);
+    }
+    Ok(buffers)
+}
+
+fn next_shader_location(layout: &VertexBufferLayout) -> u32 {
+    layout
+        .attributes
+        .iter()
+        .map(|attribute| attribute.shader_location + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Rounds `stride` up to the alignment required for vertex buffer strides and attribute offsets.
+pub(crate) fn align_vertex_stride(stride: BufferAddress) -> BufferAddress {
+    let alignment = wgpu::VERTEX_STRIDE_ALIGNMENT;
+    (stride + alignment - 1) / alignment * alignment
+}
+
+/// Returned by [`merge_vertex_buffers`] and [`NamedVertexBufferLayout::merge`] if vertex buffer
+/// layouts can't be combined. The variants contain the names of the involved layouts.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum VertexBufferMergeError {
+    #[error("the vertex buffer layouts of {first} and {second} can't be interleaved, because {first} is stepped per {first_step_mode:?} and {second} per {second_step_mode:?}")]
+    IncompatibleStepModes {
+        first: Cow<'static, str>,
+        first_step_mode: VertexStepMode,
+        second: Cow<'static, str>,
+        second_step_mode: VertexStepMode,
+    },
+    #[error("shader location {shader_location} is used by several attributes of the vertex buffer layout of {layout}")]
+    DuplicateShaderLocation {
+        layout: Cow<'static, str>,
+        shader_location: u32,
+    },
+    #[error("the attributes at shader locations {first_shader_location} and {second_shader_location} of the vertex buffer layout of {layout} overlap")]
+    OverlappingAttributes {
+        layout: Cow<'static, str>,
+        first_shader_location: u32,
+        second_shader_location: u32,
+    },
+    #[error("the attribute at shader location {shader_location} of the vertex buffer layout of {layout} ends at byte {end}, after the array stride of {array_stride}")]
+    AttributeExceedsStride {
+        layout: Cow<'static, str>,
+        shader_location: u32,
+        end: BufferAddress,
+        array_stride: BufferAddress,
+    },
 }
 
 /// Describes the fragment process in a render pipeline.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct FragmentState {
     /// The compiled shader module for this stage.
     pub shader: Handle<Shader>,
-    pub shader_defs: Vec<String>,
+    pub shader_defs: ShaderDefs,
     /// The name of the entry point in the compiled shader. There must be a
     /// function with this name in the shader.
     pub entry_point: Cow<'static, str>,
+    /// Entry points replacing [`entry_point`](Self::entry_point) if their shader def is one of the
+    /// `shader_defs`. The first matching override is used.
+    pub entry_point_overrides: Vec<EntryPointOverride>,
+    /// Values for the SPIR-V specialization constants of the shader. Changing them requires a
+    /// new pipeline, but no new shader def permutation.
+    pub specialization_constants: Vec<SpecializationConstant>,
     /// The color state of the render targets.
     pub targets: Vec<ColorTargetState>,
 }
 
+impl FragmentState {
+    /// Returns the entry point used for the current `shader_defs`.
+    pub fn resolved_entry_point(&self) -> &str {
+        resolve_entry_point(
+            &self.entry_point,
+            &self.entry_point_overrides,
+            &self.shader_defs,
+        )
+    }
+}
+
 /// Describes a compute pipeline.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct ComputePipelineDescriptor {
     pub label: Option<Cow<'static, str>>,
     pub layout: Option<Vec<BindGroupLayout>>,
     /// The compiled shader module for this stage.
     pub shader: Handle<Shader>,
-    pub shader_defs: Vec<String>,
+    pub shader_defs: ShaderDefs,
     /// The name of the entry point in the compiled shader. There must be a
     /// function with this name in the shader.
     pub entry_point: Cow<'static, str>,
+    /// Entry points replacing [`entry_point`](Self::entry_point) if their shader def is one of the
+    /// `shader_defs`. The first matching override is used.
+    pub entry_point_overrides: Vec<EntryPointOverride>,
+    /// Values for the SPIR-V specialization constants of the shader. Changing them requires a
+    /// new pipeline, but no new shader def permutation.
+    pub specialization_constants: Vec<SpecializationConstant>,
+    /// The ranges of push constants used by the pipeline, see
+    /// [`RenderPipelineDescriptor::push_constant_ranges`].
+    pub push_constant_ranges: Vec<PushConstantRange>,
+}
+
+impl ComputePipelineDescriptor {
+    /// Returns the entry point used for the current `shader_defs`.
+    pub fn resolved_entry_point(&self) -> &str {
+        resolve_entry_point(
+            &self.entry_point,
+            &self.entry_point_overrides,
+            &self.shader_defs,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        merge_vertex_buffers, DuplicateShaderLocation, EntryPointOverride, NamedVertexBufferLayout,
+        VertexBufferLayout, VertexBufferLayoutBuilder, VertexBufferMergeError, VertexLayoutError,
+        VertexState,
+    };
+    use crate::{render_resource::ShaderDefs, test_util::pipeline_descriptor};
+    use bevy_asset::Handle;
+    use bevy_math::Mat4;
+    use wgpu::{ShaderStages, VertexAttribute, VertexFormat, VertexStepMode};
+
+    #[test]
+    fn push_stage_shader_defs() {
+        let mut descriptor = pipeline_descriptor();
+        descriptor.push_shader_def("SKINNED", ShaderStages::VERTEX_FRAGMENT);
+        descriptor.push_shader_def("NORMAL_MAP", ShaderStages::FRAGMENT);
+        descriptor.push_shader_def("MORPH_TARGETS", ShaderStages::VERTEX);
+
+        assert_eq!(*descriptor.vertex.shader_defs, ["SKINNED", "MORPH_TARGETS"]);
+        assert_eq!(
+            *descriptor.fragment.unwrap().shader_defs,
+            ["SKINNED", "NORMAL_MAP"]
+        );
+    }
+
+    #[test]
+    fn entry_point_overrides() {
+        let mut vertex = VertexState {
+            shader: Handle::default(),
+            shader_defs: ShaderDefs::from_iter(["MAX_JOINTS 64"]),
+            entry_point: "vs_main".into(),
+            entry_point_overrides: vec![
+                EntryPointOverride::new("SKINNED", "vs_skinned"),
+                EntryPointOverride::new("MORPHED", "vs_morphed"),
+            ],
+            specialization_constants: Vec::new(),
+            buffers: Vec::new(),
+            allow_unused_attributes: false,
+        };
+        assert_eq!(vertex.resolved_entry_point(), "vs_main");
+
+        vertex.shader_defs.push("MORPHED");
+        assert_eq!(vertex.resolved_entry_point(), "vs_morphed");
+
+        // the first matching override wins
+        vertex.shader_defs.push("SKINNED");
+        assert_eq!(vertex.resolved_entry_point(), "vs_skinned");
+    }
+
+    #[test]
+    fn duplicate_shader_locations() {
+        let mut descriptor = pipeline_descriptor();
+        let mesh = VertexBufferLayout::from_vertex_formats(
+            VertexStepMode::Vertex,
+            [
+                VertexFormat::Float32x3,
+                VertexFormat::Float32x3,
+                VertexFormat::Float32x2,
+            ],
+        );
+        let instance = VertexBufferLayout::from_type::<[f32; 8]>(VertexStepMode::Instance);
+        descriptor.vertex.buffers = vec![mesh, instance.clone()];
+        assert_eq!(
+            descriptor.vertex.validate_buffers(),
+            Err(DuplicateShaderLocation {
+                shader_location: 0,
+                first_buffer: 0,
+                first_attribute: 0,
+                second_buffer: 1,
+                second_attribute: 0,
+            })
+        );
+
+        descriptor.vertex.buffers[1] = instance.with_first_shader_location(3);
+        assert_eq!(descriptor.vertex.validate_buffers(), Ok(()));
+        assert_eq!(
+            descriptor.vertex.buffers[1].attributes[1].shader_location,
+            4
+        );
+    }
+
+    fn mesh_layout() -> NamedVertexBufferLayout {
+        NamedVertexBufferLayout::new(
+            "Mesh",
+            VertexBufferLayout::from_vertex_formats(
+                VertexStepMode::Vertex,
+                [
+                    VertexFormat::Float32x3,
+                    VertexFormat::Float32x3,
+                    VertexFormat::Float32x2,
+                ],
+            ),
+        )
+    }
+
+    #[test]
+    fn merge_named_layouts() {
+        let instance = NamedVertexBufferLayout::new(
+            "InstanceData",
+            VertexBufferLayout::from_type::<[f32; 8]>(VertexStepMode::Instance),
+        );
+        let buffers = merge_vertex_buffers(&[mesh_layout(), instance.clone()]).unwrap();
+        assert_eq!(buffers[0], mesh_layout().layout);
+        assert_eq!(
+            buffers[1]
+                .attributes
+                .iter()
+                .map(|attribute| (attribute.shader_location, attribute.offset))
+                .collect::<Vec<_>>(),
+            [(3, 0), (4, 16)]
+        );
+
+        let error = mesh_layout().merge(&instance).unwrap_err();
+        assert!(matches!(
+            error,
+            VertexBufferMergeError::IncompatibleStepModes { .. }
+        ));
+        let message = error.to_string();
+        assert!(message.contains("Mesh") && message.contains("InstanceData"));
+
+        let extra = NamedVertexBufferLayout::new(
+            "Extra",
+            VertexBufferLayout::from_vertex_formats(
+                VertexStepMode::Vertex,
+                [VertexFormat::Unorm8x2, VertexFormat::Float32],
+            ),
+        );
+        let merged = extra.merge(&mesh_layout()).unwrap();
+        assert_eq!(merged.name, "Extra + Mesh");
+        // the stride of `Extra` is padded from 6 to 8 bytes
+        assert_eq!(merged.layout.array_stride, 8 + 32);
+        assert_eq!(merged.layout.attributes[2].offset, 8);
+        assert_eq!(merged.layout.attributes[2].shader_location, 2);
+
+        let mut overlapping = mesh_layout();
+        overlapping.layout.attributes[1].offset = 8;
+        assert_eq!(
+            overlapping.merge(&extra),
+            Err(VertexBufferMergeError::OverlappingAttributes {
+                layout: "Mesh".into(),
+                first_shader_location: 0,
+                second_shader_location: 1,
+            })
+        );
+    }
+
+    /// A small xorshift generator, so that the randomized test is reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+
+        fn layout(&mut self, name: &'static str) -> NamedVertexBufferLayout {
+            const FORMATS: [VertexFormat; 6] = [
+                VertexFormat::Float32,
+                VertexFormat::Float32x3,
+                VertexFormat::Float32x4,
+                VertexFormat::Unorm8x2,
+                VertexFormat::Uint16x4,
+                VertexFormat::Sint8x4,
+            ];
+            let formats = (0..self.next(5))
+                .map(|_| FORMATS[self.next(FORMATS.len() as u64) as usize])
+                .collect::<Vec<_>>();
+            let step_mode = if self.next(4) == 0 {
+                VertexStepMode::Instance
+            } else {
+                VertexStepMode::Vertex
+            };
+            let mut layout = VertexBufferLayout::from_vertex_formats(step_mode, formats)
+                .with_first_shader_location(self.next(8) as u32);
+            // shuffle the shader locations
+            for _ in 0..layout.attributes.len() {
+                let a = self.next(layout.attributes.len() as u64) as usize;
+                let b = self.next(layout.attributes.len() as u64) as usize;
+                let location = layout.attributes[a].shader_location;
+                layout.attributes[a].shader_location = layout.attributes[b].shader_location;
+                layout.attributes[b].shader_location = location;
+            }
+            NamedVertexBufferLayout::new(name, layout)
+        }
+    }
+
+    fn assert_disjoint(attributes: &[&VertexAttribute]) {
+        for (index, attribute) in attributes.iter().enumerate() {
+            for other in &attributes[..index] {
+                assert_ne!(attribute.shader_location, other.shader_location);
+            }
+        }
+    }
+
+    #[test]
+    fn merged_layouts_never_overlap() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..1000 {
+            let first = rng.layout("First");
+            let second = rng.layout("Second");
+            let third = rng.layout("Third");
+
+            match first.merge(&second) {
+                Ok(merged) => {
+                    assert_eq!(merged.validate(), Ok(()));
+                    assert_eq!(merged.layout.array_stride % 4, 0);
+                    assert_eq!(
+                        merged.layout.attributes.len(),
+                        first.layout.attributes.len() + second.layout.attributes.len()
+                    );
+                }
+                Err(error) => {
+                    assert_ne!(first.layout.step_mode, second.layout.step_mode);
+                    assert!(matches!(
+                        error,
+                        VertexBufferMergeError::IncompatibleStepModes { .. }
+                    ));
+                }
+            }
+
+            let buffers = merge_vertex_buffers(&[first, second, third]).unwrap();
+            assert_disjoint(
+                &buffers
+                    .iter()
+                    .flat_map(|buffer| &buffer.attributes)
+                    .collect::<Vec<_>>(),
+            );
+            for buffer in &buffers {
+                assert_eq!(
+                    NamedVertexBufferLayout::new("Buffer", buffer.clone()).validate(),
+                    Ok(())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn built_layouts_match_generated_layouts() {
+        let built = VertexBufferLayoutBuilder::new()
+            .step_mode(VertexStepMode::Instance)
+            .attribute("x_axis", VertexFormat::Float32x4)
+            .attribute("y_axis", VertexFormat::Float32x4)
+            .attribute("z_axis", VertexFormat::Float32x4)
+            .attribute("w_axis", VertexFormat::Float32x4)
+            .build()
+            .unwrap();
+        assert_eq!(
+            built,
+            VertexBufferLayout::from_type::<Mat4>(VertexStepMode::Instance)
+        );
+
+        let built = VertexBufferLayoutBuilder::new()
+            .attribute("position", VertexFormat::Float32x3)
+            .attribute("normal", VertexFormat::Float32x3)
+            .attribute("uv", VertexFormat::Float32x2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            built,
+            VertexBufferLayout::from_vertex_formats(
+                VertexStepMode::Vertex,
+                [
+                    VertexFormat::Float32x3,
+                    VertexFormat::Float32x3,
+                    VertexFormat::Float32x2
+                ]
+            )
+        );
+
+        // attributes placed by hand move the following attributes
+        let built = VertexBufferLayoutBuilder::new()
+            .attribute_at("color", VertexFormat::Unorm8x4, 8, 3)
+            .attribute("scale", VertexFormat::Float32)
+            .attribute("flags", VertexFormat::Uint16x2)
+            .build()
+            .unwrap();
+        assert_eq!(built.array_stride, 20);
+        assert_eq!(
+            built.attributes[1..],
+            [
+                VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 12,
+                    shader_location: 4,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Uint16x2,
+                    offset: 16,
+                    shader_location: 5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn inconsistent_built_layouts_are_rejected() {
+        let builder =
+            || VertexBufferLayoutBuilder::new().attribute("position", VertexFormat::Float32x3);
+        assert_eq!(
+            builder()
+                .attribute("position", VertexFormat::Float32)
+                .build(),
+            Err(VertexLayoutError::DuplicateName {
+                name: "position".into()
+            })
+        );
+        assert_eq!(
+            builder()
+                .attribute_at("color", VertexFormat::Float32x4, 12, 0)
+                .build(),
+            Err(VertexLayoutError::DuplicateShaderLocation {
+                shader_location: 0,
+                first: "position".into(),
+                second: "color".into(),
+            })
+        );
+        assert_eq!(
+            builder()
+                .attribute_at("color", VertexFormat::Float32x4, 8, 1)
+                .build(),
+            Err(VertexLayoutError::OverlappingAttributes {
+                first: "position".into(),
+                second: "color".into(),
+            })
+        );
+        assert_eq!(
+            builder().array_stride(8).build(),
+            Err(VertexLayoutError::AttributeExceedsStride {
+                name: "position".into(),
+                end: 12,
+                array_stride: 8,
+            })
+        );
+        assert_eq!(
+            builder()
+                .attribute_at("color", VertexFormat::Unorm8x4, 14, 1)
+                .build(),
+            Err(VertexLayoutError::UnalignedAttribute {
+                name: "color".into(),
+                offset: 14,
+                alignment: 4,
+            })
+        );
+        assert_eq!(
+            builder().array_stride(14).build(),
+            Err(VertexLayoutError::UnalignedStride { array_stride: 14 })
+        );
+    }
 }
@@ -1,21 +1,33 @@
 use crate::{
+    diagnostic::RenderStatistics,
     render_resource::{
         AsModuleDescriptorError, BindGroupLayout, BindGroupLayoutId, ComputePipeline,
-        ComputePipelineDescriptor, ProcessShaderError, ProcessedShader,
+        ComputePipelineDescriptor, DuplicateShaderLocation, ProcessShaderError, ProcessedShader,
         RawComputePipelineDescriptor, RawFragmentState, RawRenderPipelineDescriptor,
-        RawVertexState, RenderPipeline, RenderPipelineDescriptor, Shader, ShaderImport,
-        ShaderProcessor, ShaderReflectError,
+        RawVertexState, ReflectedBinding, ReflectedVertexInput, RenderPipeline,
+        RenderPipelineDescriptor, Shader, ShaderDefs, ShaderDefsKey, ShaderDiskCache, ShaderImport,
+        ShaderModuleDescriptor, ShaderProcessor, ShaderReflectError, ShaderReflection,
+        ShaderSource, ShaderSourceMap, SpecializationConstant, VertexBufferLayout, VertexState,
     },
     renderer::RenderDevice,
     RenderWorld,
 };
-use bevy_asset::{AssetEvent, Assets, Handle};
+use bevy_asset::{AssetEvent, AssetServer, Assets, Handle};
 use bevy_ecs::event::EventReader;
 use bevy_ecs::system::{Res, ResMut};
-use bevy_utils::{default, tracing::error, Entry, HashMap, HashSet};
-use std::{hash::Hash, mem, ops::Deref, sync::Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use bevy_tasks::{Task, TaskPool};
+use bevy_utils::{
+    closest_name, default,
+    tracing::{error, warn},
+    Entry, HashMap, HashSet,
+};
+use std::{borrow::Cow, hash::Hash, mem, sync::Arc};
 use thiserror::Error;
-use wgpu::{PipelineLayoutDescriptor, ShaderModule, VertexBufferLayout as RawVertexBufferLayout};
+use wgpu::{
+    BindGroupLayoutEntry, PipelineLayoutDescriptor, PushConstantRange, ShaderModule, ShaderStages,
+    VertexAttribute, VertexBufferLayout as RawVertexBufferLayout, VertexFormat,
+};
 
 enum PipelineDescriptor {
     RenderPipelineDescriptor(Box<RenderPipelineDescriptor>),
@@ -30,8 +42,8 @@ pub enum Pipeline {
 
 type CachedPipelineId = usize;
 
-#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
-pub struct CachedRenderPipelineId(CachedPipelineId);
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct CachedRenderPipelineId(pub(crate) CachedPipelineId);
 
 impl CachedRenderPipelineId {
     pub const INVALID: Self = CachedRenderPipelineId(usize::MAX);
@@ -47,6 +59,9 @@ impl CachedComputePipelineId {
 struct CachedPipeline {
     descriptor: PipelineDescriptor,
     state: CachedPipelineState,
+    /// The last successfully created pipeline, kept while the pipeline is recreated (e.g. because
+    /// one of its shaders was hot reloaded) so rendering can continue if recreating it fails.
+    previous: Option<Pipeline>,
 }
 
 #[derive(Debug)]
@@ -68,10 +83,21 @@ impl CachedPipelineState {
     }
 }
 
+/// A processed and compiled shader, as stored in the [`ShaderCache`].
+#[derive(Clone)]
+struct CachedShaderModule {
+    module: Arc<ShaderModule>,
+    /// The reflected interface of the shader, used to validate pipelines against it. This is only
+    /// available unless [`BindingValidation::Disabled`] is set.
+    reflection: Option<Arc<ShaderReflection>>,
+}
+
 #[derive(Default)]
 pub struct ShaderData {
     pipelines: HashSet<CachedPipelineId>,
-    processed_shaders: HashMap<Vec<String>, Arc<ShaderModule>>,
+    processed_shaders: HashMap<(ShaderDefs, Vec<SpecializationConstant>), CachedShaderModule>,
+    /// The imports of the shader, including its resolved `# include`s.
+    imports: Vec<ShaderImport>,
     resolved_imports: HashMap<ShaderImport, Handle<Shader>>,
     dependents: HashSet<Handle<Shader>>,
 }
@@ -82,7 +108,105 @@ struct ShaderCache {
     shaders: HashMap<Handle<Shader>, Shader>,
     import_path_shaders: HashMap<ShaderImport, Handle<Shader>>,
     waiting_on_import: HashMap<ShaderImport, Vec<Handle<Shader>>>,
+    /// Paths of `# include`d shaders that still have to be loaded through the asset server.
+    includes_to_load: HashSet<String>,
+    /// Keeps the shaders loaded for `# include`s alive.
+    included_shaders: HashMap<String, Handle<Shader>>,
     processor: ShaderProcessor,
+    disk_cache: Option<ShaderDiskCache>,
+    compile_jobs: CompileJobs<ShaderJobKey, PreparedShader, PipelineCacheError>,
+    /// Whether shaders are reflected to validate the pipelines using them.
+    reflect: bool,
+}
+
+/// Identifies a shader permutation, so that pipelines requesting the same permutation while it
+/// compiles share a single [`CompileJobs`] job.
+type ShaderJobKey = (Handle<Shader>, ShaderDefs, Vec<SpecializationConstant>);
+
+/// A shader translated to a source wgpu accepts, ready to be turned into a [`ShaderModule`] on the
+/// render thread.
+struct PreparedShader {
+    source: ShaderSource<'static>,
+    reflection: Option<Arc<ShaderReflection>>,
+}
+
+/// The result of [`ShaderCache::prepare`].
+enum ShaderModuleSource {
+    /// The module was already created for the requested permutation.
+    Cached(CachedShaderModule),
+    /// The shader was translated, but its module still has to be created.
+    Prepared(PreparedShader),
+}
+
+/// Converts the source of a [`ShaderModuleDescriptor`] borrowing a [`ProcessedShader`] into an
+/// owned one, so that it can be sent back from the task translating it.
+fn into_owned_source(source: ShaderSource) -> ShaderSource<'static> {
+    match source {
+        ShaderSource::Wgsl(source) => ShaderSource::Wgsl(Cow::Owned(source.into_owned())),
+        ShaderSource::SpirV(source) => ShaderSource::SpirV(Cow::Owned(source.into_owned())),
+        #[allow(unreachable_patterns)]
+        _ => unreachable!("GLSL shaders are translated to WGSL or SPIR-V"),
+    }
+}
+
+/// Jobs computing values on a task pool, like translating shaders, deduplicated by their key.
+///
+/// Without a task pool, jobs run synchronously when they are requested.
+struct CompileJobs<K, T, E> {
+    #[cfg(not(target_arch = "wasm32"))]
+    task_pool: Option<TaskPool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: HashMap<K, Task<Result<T, E>>>,
+    #[cfg(target_arch = "wasm32")]
+    jobs: std::marker::PhantomData<(K, T, E)>,
+}
+
+impl<K, T, E> Default for CompileJobs<K, T, E> {
+    fn default() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            task_pool: None,
+            jobs: default(),
+        }
+    }
+}
+
+impl<K: Clone + Hash + Eq, T: Send + 'static, E: Send + 'static> CompileJobs<K, T, E> {
+    /// Returns the result of the job with the given `key`, or `None` while it is still running.
+    ///
+    /// If no job with the key is running, `prepare` is called on the current thread and the job it
+    /// returns is started, unless it fails. Requesting a running job again doesn't start another
+    /// one, so any number of requests for the same key result in a single job. Once the result was
+    /// returned, the job is forgotten, so the caller is expected to cache the result.
+    fn poll<J>(&mut self, key: &K, prepare: impl FnOnce() -> Result<J, E>) -> Option<Result<T, E>>
+    where
+        J: FnOnce() -> Result<T, E> + Send + 'static,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(task_pool) = &self.task_pool {
+            let job = match self.jobs.entry(key.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => match prepare() {
+                    Ok(job) => entry.insert(task_pool.spawn(async move { job() })),
+                    Err(err) => return Some(Err(err)),
+                },
+            };
+            let result = bevy_utils::futures::now_or_never(job)?;
+            self.jobs.remove(key);
+            return Some(result);
+        }
+
+        let _ = key;
+        Some(prepare().and_then(|job| job()))
+    }
+
+    /// Cancels the running jobs whose keys match `predicate`.
+    fn cancel(&mut self, predicate: impl Fn(&K) -> bool) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.jobs.retain(|key, _| !predicate(key));
+        #[cfg(target_arch = "wasm32")]
+        let _ = predicate;
+    }
 }
 
 impl ShaderCache {
@@ -91,15 +215,62 @@ impl ShaderCache {
         render_device: &RenderDevice,
         pipeline: CachedPipelineId,
         handle: &Handle<Shader>,
-        shader_defs: &[String],
-    ) -> Result<Arc<ShaderModule>, PipelineCacheError> {
+        shader_defs: &ShaderDefs,
+        specialization_constants: &[SpecializationConstant],
+    ) -> Result<CachedShaderModule, PipelineCacheError> {
+        let prepared =
+            match self.prepare(pipeline, handle, shader_defs, specialization_constants)? {
+                ShaderModuleSource::Cached(module) => return Ok(module),
+                ShaderModuleSource::Prepared(prepared) => prepared,
+            };
+
+        render_device
+            .wgpu_device()
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader_module = render_device.create_shader_module(&ShaderModuleDescriptor {
+            label: None,
+            source: prepared.source,
+        });
+        let error = render_device.wgpu_device().pop_error_scope();
+
+        // `now_or_never` will return Some if the future is ready and None otherwise.
+        // On native platforms, wgpu will yield the error immediatly while on wasm it may take longer since the browser APIs are asynchronous.
+        // So to keep the complexity of the ShaderCache low, we will only catch this error early on native platforms,
+        // and on wasm the error will be handled by wgpu and crash the application.
+        if let Some(Some(wgpu::Error::Validation { description, .. })) =
+            bevy_utils::futures::now_or_never(error)
+        {
+            return Err(PipelineCacheError::CreateShaderModule(description));
+        }
+
+        let module = CachedShaderModule {
+            module: Arc::new(shader_module),
+            reflection: prepared.reflection,
+        };
+        let key = (shader_defs.clone(), specialization_constants.to_vec());
+        let data = self.data.entry(handle.clone_weak()).or_default();
+        data.processed_shaders.insert(key, module.clone());
+        Ok(module)
+    }
+
+    /// Returns the module already created for the given shader permutation, or translates the
+    /// shader so that [`Self::get`] can create it. This records `pipeline` as using the shader, so
+    /// that it is queued again when the shader changes.
+    fn prepare(
+        &mut self,
+        pipeline: CachedPipelineId,
+        handle: &Handle<Shader>,
+        shader_defs: &ShaderDefs,
+        specialization_constants: &[SpecializationConstant],
+    ) -> Result<ShaderModuleSource, PipelineCacheError> {
         let shader = self
             .shaders
             .get(handle)
             .ok_or_else(|| PipelineCacheError::ShaderNotLoaded(handle.clone_weak()))?;
         let data = self.data.entry(handle.clone_weak()).or_default();
-        let n_asset_imports = shader
-            .imports()
+        let n_asset_imports = data
+            .imports
+            .iter()
             .filter(|import| matches!(import, ShaderImport::AssetPath(_)))
             .count();
         let n_resolved_asset_imports = data
@@ -113,50 +284,90 @@ impl ShaderCache {
 
         data.pipelines.insert(pipeline);
 
-        // PERF: this shader_defs clone isn't great. use raw_entry_mut when it stabilizes
-        let module = match data.processed_shaders.entry(shader_defs.to_vec()) {
-            Entry::Occupied(entry) => entry.into_mut(),
-            Entry::Vacant(entry) => {
-                let processed = self.processor.process(
-                    shader,
-                    shader_defs,
-                    &self.shaders,
-                    &self.import_path_shaders,
-                )?;
-                let module_descriptor = match processed.get_module_descriptor() {
-                    Ok(module_descriptor) => module_descriptor,
+        // PERF: these clones aren't great. use raw_entry_mut when it stabilizes
+        let key = (shader_defs.clone(), specialization_constants.to_vec());
+        if let Some(module) = data.processed_shaders.get(&key) {
+            return Ok(ShaderModuleSource::Cached(module.clone()));
+        }
+
+        let job_key = (handle.clone_weak(), key.0.clone(), key.1.clone());
+        let (processor, shaders, import_path_shaders, disk_cache, reflect) = (
+            &self.processor,
+            &self.shaders,
+            &self.import_path_shaders,
+            &self.disk_cache,
+            self.reflect,
+        );
+        let prepared = self.compile_jobs.poll(&job_key, || {
+            let shader_defs = shader_defs.to_strings();
+            let (processed, source_map) = processor.process_with_source_map(
+                shader,
+                &shader_defs,
+                shaders,
+                import_path_shaders,
+            )?;
+            let processed = processed.specialize(specialization_constants)?;
+            let path = shader.import_path().cloned();
+            let name = describe_shader(handle, path.as_ref());
+            let disk_cache = disk_cache.clone();
+            Ok(move || {
+                let module_descriptor = match &disk_cache {
+                    Some(disk_cache) => disk_cache.get_module_descriptor(&processed, &shader_defs),
+                    None => processed.get_module_descriptor(),
+                };
+                let source = match module_descriptor {
+                    Ok(module_descriptor) => into_owned_source(module_descriptor.source),
                     Err(err) => {
-                        return Err(PipelineCacheError::AsModuleDescriptorError(err, processed));
+                        return Err(PipelineCacheError::AsModuleDescriptorError(
+                            err,
+                            Box::new(ShaderErrorContext {
+                                source: processed,
+                                source_map,
+                                path,
+                                shader_defs,
+                            }),
+                        ));
                     }
                 };
+                let reflection = if reflect {
+                    match processed.reflect() {
+                        Ok(reflection) => Some(Arc::new(reflection)),
+                        Err(err) => {
+                            warn!(
+                                "failed to reflect {}, its entry points and bindings won't be \
+                                 validated: {}",
+                                name, err
+                            );
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                Ok(PreparedShader { source, reflection })
+            })
+        });
+        match prepared {
+            Some(prepared) => prepared.map(ShaderModuleSource::Prepared),
+            None => Err(PipelineCacheError::ShaderNotYetCompiled),
+        }
+    }
 
-                render_device
-                    .wgpu_device()
-                    .push_error_scope(wgpu::ErrorFilter::Validation);
-                let shader_module = render_device.create_shader_module(&module_descriptor);
-                let error = render_device.wgpu_device().pop_error_scope();
-
-                // `now_or_never` will return Some if the future is ready and None otherwise.
-                // On native platforms, wgpu will yield the error immediatly while on wasm it may take longer since the browser APIs are asynchronous.
-                // So to keep the complexity of the ShaderCache low, we will only catch this error early on native platforms,
-                // and on wasm the error will be handled by wgpu and crash the application.
-                if let Some(Some(wgpu::Error::Validation { description, .. })) =
-                    bevy_utils::futures::now_or_never(error)
-                {
-                    return Err(PipelineCacheError::CreateShaderModule(description));
-                }
-
-                entry.insert(Arc::new(shader_module))
-            }
-        };
-
-        Ok(module.clone())
+    /// Returns a human readable name for the shader with the given `handle`.
+    fn describe(&self, handle: &Handle<Shader>) -> String {
+        describe_shader(
+            handle,
+            self.shaders.get(handle).and_then(Shader::import_path),
+        )
     }
 
     fn clear(&mut self, handle: &Handle<Shader>) -> Vec<CachedPipelineId> {
         let mut shaders_to_clear = vec![handle.clone_weak()];
         let mut pipelines_to_queue = Vec::new();
         while let Some(handle) = shaders_to_clear.pop() {
+            // a job still compiling the previous version of the shader would be stale
+            self.compile_jobs
+                .cancel(|(job_handle, ..)| *job_handle == handle);
             if let Some(data) = self.data.get_mut(&handle) {
                 data.processed_shaders.clear();
                 pipelines_to_queue.extend(data.pipelines.iter().cloned());
@@ -185,7 +396,26 @@ impl ShaderCache {
             }
         }
 
-        for import in shader.imports() {
+        let mut imports = shader.imports().cloned().collect::<Vec<_>>();
+        for include in shader.includes() {
+            // invalid includes are reported when the shader is processed
+            if let Ok(import) = self.processor.resolve_include(
+                &self.import_path_shaders,
+                shader.import_path(),
+                include,
+            ) {
+                if let ShaderImport::AssetPath(path) = &import {
+                    if !self.included_shaders.contains_key(path) {
+                        self.includes_to_load.insert(path.clone());
+                    }
+                }
+                if !imports.contains(&import) {
+                    imports.push(import);
+                }
+            }
+        }
+
+        for import in &imports {
             if let Some(import_handle) = self.import_path_shaders.get(import) {
                 // resolve import because it is currently available
                 let data = self.data.entry(handle.clone_weak()).or_default();
@@ -200,6 +430,7 @@ impl ShaderCache {
             }
         }
 
+        self.data.entry(handle.clone_weak()).or_default().imports = imports;
         self.shaders.insert(handle.clone_weak(), shader);
         pipelines_to_queue
     }
@@ -218,7 +449,7 @@ impl ShaderCache {
 
 #[derive(Default)]
 struct LayoutCache {
-    layouts: HashMap<Vec<BindGroupLayoutId>, wgpu::PipelineLayout>,
+    layouts: HashMap<(Vec<BindGroupLayoutId>, Vec<PushConstantRange>), wgpu::PipelineLayout>,
 }
 
 impl LayoutCache {
@@ -226,8 +457,12 @@ impl LayoutCache {
         &mut self,
         render_device: &RenderDevice,
         bind_group_layouts: &[BindGroupLayout],
+        push_constant_ranges: &[PushConstantRange],
     ) -> &wgpu::PipelineLayout {
-        let key = bind_group_layouts.iter().map(|l| l.id()).collect();
+        let key = (
+            bind_group_layouts.iter().map(|l| l.id()).collect(),
+            push_constant_ranges.to_vec(),
+        );
         self.layouts.entry(key).or_insert_with(|| {
             let bind_group_layouts = bind_group_layouts
                 .iter()
@@ -235,10 +470,129 @@ impl LayoutCache {
                 .collect::<Vec<_>>();
             render_device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges,
                 ..default()
             })
         })
     }
+
+    /// Returns the layout for the given bind group layouts and push constant ranges, or [`None`]
+    /// to let the backend derive the layout from the shaders. Push constants always require an
+    /// explicit layout.
+    fn get_optional(
+        &mut self,
+        render_device: &RenderDevice,
+        bind_group_layouts: Option<&[BindGroupLayout]>,
+        push_constant_ranges: &[PushConstantRange],
+    ) -> Option<&wgpu::PipelineLayout> {
+        if bind_group_layouts.is_none() && push_constant_ranges.is_empty() {
+            return None;
+        }
+        Some(self.get(
+            render_device,
+            bind_group_layouts.unwrap_or_default(),
+            push_constant_ranges,
+        ))
+    }
+}
+
+/// Returns the number of bytes of push constants required by the given `ranges`.
+fn push_constants_size(ranges: &[PushConstantRange]) -> u32 {
+    ranges
+        .iter()
+        .map(|range| range.range.end)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Groups render pipelines by the inputs that usually tell specializations apart, so that equal
+/// descriptors queued for different specialization keys can share a single pipeline.
+///
+/// The shader defs are only compared by their [`ShaderDefsKey`]. Pipelines with the same key are
+/// compared by their whole descriptors, so a collision of the keys can't make them share a
+/// pipeline.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct RenderPipelineSpecializationKey {
+    pub vertex_shader: Handle<Shader>,
+    pub fragment_shader: Option<Handle<Shader>>,
+    pub vertex_shader_defs: ShaderDefsKey,
+    pub fragment_shader_defs: ShaderDefsKey,
+    pub vertex_buffers: Vec<VertexBufferLayout>,
+}
+
+impl RenderPipelineSpecializationKey {
+    pub fn new(descriptor: &RenderPipelineDescriptor) -> Self {
+        Self {
+            vertex_shader: descriptor.vertex.shader.clone_weak(),
+            fragment_shader: descriptor
+                .fragment
+                .as_ref()
+                .map(|fragment| fragment.shader.clone_weak()),
+            vertex_shader_defs: descriptor.vertex.shader_defs.key(),
+            fragment_shader_defs: descriptor.fragment.as_ref().map_or_else(
+                || ShaderDefs::new().key(),
+                |fragment| fragment.shader_defs.key(),
+            ),
+            vertex_buffers: descriptor.vertex.buffers.clone(),
+        }
+    }
+}
+
+/// The render pipelines queued in a [`PipelineCache`], by their
+/// [`RenderPipelineSpecializationKey`].
+#[derive(Default)]
+struct RenderPipelineSpecializations {
+    pipelines: HashMap<RenderPipelineSpecializationKey, Vec<CachedRenderPipelineId>>,
+}
+
+impl RenderPipelineSpecializations {
+    /// Returns a pipeline with a descriptor equal to `descriptor`, looking up the descriptors of
+    /// known pipelines with `get_descriptor`.
+    fn find<'a>(
+        &self,
+        key: &RenderPipelineSpecializationKey,
+        descriptor: &RenderPipelineDescriptor,
+        get_descriptor: impl Fn(CachedRenderPipelineId) -> &'a RenderPipelineDescriptor,
+    ) -> Option<CachedRenderPipelineId> {
+        self.pipelines
+            .get(key)?
+            .iter()
+            .copied()
+            .find(|id| get_descriptor(*id) == descriptor)
+    }
+
+    fn insert(&mut self, key: RenderPipelineSpecializationKey, id: CachedRenderPipelineId) {
+        self.pipelines.entry(key).or_default().push(id);
+    }
+}
+
+/// How strictly the shaders of pipelines are checked against their layouts, see
+/// [`PipelineCache::set_binding_validation`].
+///
+/// Shader bindings missing from the layout always fail the pipeline, since wgpu can't create it.
+/// Bindings that are named differently in the shader than in the layout (see
+/// [`BindGroupLayout::with_entry_names`]) and named layout entries none of the shaders of a
+/// pipeline use are likely typos, but don't keep the pipeline from working.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindingValidation {
+    /// Shaders aren't reflected, so neither their bindings nor their entry points or vertex inputs
+    /// are checked.
+    Disabled,
+    /// Mismatching names and unused named layout entries are logged as warnings.
+    Warn,
+    /// Mismatching names and unused named layout entries fail the pipeline.
+    Strict,
+}
+
+impl Default for BindingValidation {
+    /// Reflecting shaders takes time, so bindings are only validated in debug builds by default.
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            BindingValidation::Warn
+        } else {
+            BindingValidation::Disabled
+        }
+    }
 }
 
 pub struct PipelineCache {
@@ -247,19 +601,57 @@ pub struct PipelineCache {
     device: RenderDevice,
     pipelines: Vec<CachedPipeline>,
     waiting_pipelines: HashSet<CachedPipelineId>,
+    render_pipeline_specializations: RenderPipelineSpecializations,
+    validate_vertex_buffers: bool,
+    binding_validation: BindingValidation,
 }
 
 impl PipelineCache {
     pub fn new(device: RenderDevice) -> Self {
+        let binding_validation = BindingValidation::default();
         Self {
             device,
             layout_cache: default(),
-            shader_cache: default(),
+            shader_cache: ShaderCache {
+                reflect: binding_validation != BindingValidation::Disabled,
+                ..default()
+            },
             waiting_pipelines: default(),
             pipelines: default(),
+            render_pipeline_specializations: default(),
+            validate_vertex_buffers: cfg!(debug_assertions),
+            binding_validation,
         }
     }
 
+    /// Sets how strictly the shaders of pipelines are checked against their layouts. This only
+    /// affects shaders compiled afterwards.
+    pub fn set_binding_validation(&mut self, binding_validation: BindingValidation) {
+        self.binding_validation = binding_validation;
+        self.shader_cache.reflect = binding_validation != BindingValidation::Disabled;
+    }
+
+    /// Enables checking the vertex buffers of render pipelines for attributes sharing a shader
+    /// location (see [`VertexState::validate_buffers`]). This is enabled by default in debug
+    /// builds.
+    pub fn set_validate_vertex_buffers(&mut self, validate: bool) {
+        self.validate_vertex_buffers = validate;
+    }
+
+    /// Compiles shaders on the given `task_pool` instead of the render thread, so that a new shader
+    /// permutation doesn't stall the frame it first appears in. Until its shaders are compiled, a
+    /// pipeline isn't available and draws using it are skipped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_task_pool(&mut self, task_pool: TaskPool) {
+        self.shader_cache.compile_jobs.task_pool = Some(task_pool);
+    }
+
+    /// Stores translated shaders in the given `disk_cache`, so that they don't have to be
+    /// compiled again on the next run.
+    pub fn set_shader_disk_cache(&mut self, disk_cache: ShaderDiskCache) {
+        self.shader_cache.disk_cache = Some(disk_cache);
+    }
+
     #[inline]
     pub fn get_render_pipeline_state(&self, id: CachedRenderPipelineId) -> &CachedPipelineState {
         &self.pipelines[id.0].state
@@ -275,10 +667,28 @@ impl PipelineCache {
         &self,
         id: CachedRenderPipelineId,
     ) -> &RenderPipelineDescriptor {
-        match &self.pipelines[id.0].descriptor {
-            PipelineDescriptor::RenderPipelineDescriptor(descriptor) => descriptor,
-            PipelineDescriptor::ComputePipelineDescriptor(_) => unreachable!(),
-        }
+        render_pipeline_descriptor(&self.pipelines, id)
+    }
+
+    /// Returns the number of distinct render pipelines that were queued. Queueing a descriptor
+    /// equal to an already queued one returns the existing pipeline instead.
+    pub fn render_pipeline_count(&self) -> usize {
+        self.render_pipeline_specializations
+            .pipelines
+            .values()
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// Returns the keys of all queued render pipelines, together with the pipelines sharing them.
+    /// This is meant for diagnosing unexpectedly large numbers of pipeline specializations.
+    pub fn render_pipeline_specializations(
+        &self,
+    ) -> impl Iterator<Item = (&RenderPipelineSpecializationKey, &[CachedRenderPipelineId])> {
+        self.render_pipeline_specializations
+            .pipelines
+            .iter()
+            .map(|(key, ids)| (key, ids.as_slice()))
     }
 
     #[inline]
@@ -292,25 +702,27 @@ impl PipelineCache {
         }
     }
 
+    /// Returns the [`RenderPipeline`] for the given `id`, if it has been created. While a pipeline
+    /// is being recreated, the previously created version is returned.
     #[inline]
     pub fn get_render_pipeline(&self, id: CachedRenderPipelineId) -> Option<&RenderPipeline> {
-        if let CachedPipelineState::Ok(Pipeline::RenderPipeline(pipeline)) =
-            &self.pipelines[id.0].state
-        {
-            Some(pipeline)
-        } else {
-            None
+        let cached_pipeline = &self.pipelines[id.0];
+        match (&cached_pipeline.state, &cached_pipeline.previous) {
+            (CachedPipelineState::Ok(Pipeline::RenderPipeline(pipeline)), _)
+            | (_, Some(Pipeline::RenderPipeline(pipeline))) => Some(pipeline),
+            _ => None,
         }
     }
 
+    /// Returns the [`ComputePipeline`] for the given `id`, if it has been created. While a
+    /// pipeline is being recreated, the previously created version is returned.
     #[inline]
     pub fn get_compute_pipeline(&self, id: CachedComputePipelineId) -> Option<&ComputePipeline> {
-        if let CachedPipelineState::Ok(Pipeline::ComputePipeline(pipeline)) =
-            &self.pipelines[id.0].state
-        {
-            Some(pipeline)
-        } else {
-            None
+        let cached_pipeline = &self.pipelines[id.0];
+        match (&cached_pipeline.state, &cached_pipeline.previous) {
+            (CachedPipelineState::Ok(Pipeline::ComputePipeline(pipeline)), _)
+            | (_, Some(Pipeline::ComputePipeline(pipeline))) => Some(pipeline),
+            _ => None,
         }
     }
 
@@ -318,12 +730,25 @@ impl PipelineCache {
         &mut self,
         descriptor: RenderPipelineDescriptor,
     ) -> CachedRenderPipelineId {
+        let key = RenderPipelineSpecializationKey::new(&descriptor);
+        let pipelines = &self.pipelines;
+        if let Some(id) = self
+            .render_pipeline_specializations
+            .find(&key, &descriptor, |id| {
+                render_pipeline_descriptor(pipelines, id)
+            })
+        {
+            return id;
+        }
+
         let id = CachedRenderPipelineId(self.pipelines.len());
         self.pipelines.push(CachedPipeline {
             descriptor: PipelineDescriptor::RenderPipelineDescriptor(Box::new(descriptor)),
             state: CachedPipelineState::Queued,
+            previous: None,
         });
         self.waiting_pipelines.insert(id.0);
+        self.render_pipeline_specializations.insert(key, id);
         id
     }
 
@@ -335,25 +760,36 @@ impl PipelineCache {
         self.pipelines.push(CachedPipeline {
             descriptor: PipelineDescriptor::ComputePipelineDescriptor(Box::new(descriptor)),
             state: CachedPipelineState::Queued,
+            previous: None,
         });
         self.waiting_pipelines.insert(id.0);
         id
     }
 
-    fn set_shader(&mut self, handle: &Handle<Shader>, shader: &Shader) {
+    pub(crate) fn set_shader(&mut self, handle: &Handle<Shader>, shader: &Shader) {
         let pipelines_to_queue = self.shader_cache.set_shader(handle, shader.clone());
         for cached_pipeline in pipelines_to_queue {
-            self.pipelines[cached_pipeline].state = CachedPipelineState::Queued;
-            self.waiting_pipelines.insert(cached_pipeline);
+            self.requeue_pipeline(cached_pipeline);
         }
     }
 
     fn remove_shader(&mut self, shader: &Handle<Shader>) {
         let pipelines_to_queue = self.shader_cache.remove(shader);
         for cached_pipeline in pipelines_to_queue {
-            self.pipelines[cached_pipeline].state = CachedPipelineState::Queued;
-            self.waiting_pipelines.insert(cached_pipeline);
+            self.requeue_pipeline(cached_pipeline);
+        }
+    }
+
+    /// Queues an already created pipeline to be created again, keeping the current version around
+    /// until the new one is ready.
+    fn requeue_pipeline(&mut self, id: CachedPipelineId) {
+        let pipeline = &mut self.pipelines[id];
+        if let CachedPipelineState::Ok(previous) =
+            mem::replace(&mut pipeline.state, CachedPipelineState::Queued)
+        {
+            pipeline.previous = Some(previous);
         }
+        self.waiting_pipelines.insert(id);
     }
 
     fn process_render_pipeline(
@@ -366,12 +802,40 @@ impl PipelineCache {
             id,
             &descriptor.vertex.shader,
             &descriptor.vertex.shader_defs,
+            &descriptor.vertex.specialization_constants,
         ) {
             Ok(module) => module,
             Err(err) => {
                 return CachedPipelineState::Err(err);
             }
         };
+        let vertex_entry_point = descriptor.vertex.resolved_entry_point();
+        let mut used_bindings = UsedBindings::default();
+        if let Err(err) = self.validate_stage(
+            descriptor.layout.as_deref(),
+            &descriptor.vertex.shader,
+            &vertex_module,
+            vertex_entry_point,
+            ShaderStages::VERTEX,
+            &mut used_bindings,
+        ) {
+            return CachedPipelineState::Err(err);
+        }
+        if let Err(err) = self.validate_vertex_inputs(&descriptor.vertex, &vertex_module) {
+            return CachedPipelineState::Err(err);
+        }
+        if self.validate_vertex_buffers {
+            if let Err(err) = descriptor.vertex.validate_buffers() {
+                return CachedPipelineState::Err(PipelineCacheError::DuplicateShaderLocation {
+                    pipeline: descriptor
+                        .label
+                        .as_deref()
+                        .unwrap_or("unlabeled")
+                        .to_string(),
+                    error: err,
+                });
+            }
+        }
 
         let fragment_data = if let Some(fragment) = &descriptor.fragment {
             let fragment_module = match self.shader_cache.get(
@@ -379,17 +843,25 @@ impl PipelineCache {
                 id,
                 &fragment.shader,
                 &fragment.shader_defs,
+                &fragment.specialization_constants,
             ) {
                 Ok(module) => module,
                 Err(err) => {
                     return CachedPipelineState::Err(err);
                 }
             };
-            Some((
-                fragment_module,
-                fragment.entry_point.deref(),
-                &fragment.targets,
-            ))
+            let fragment_entry_point = fragment.resolved_entry_point();
+            if let Err(err) = self.validate_stage(
+                descriptor.layout.as_deref(),
+                &fragment.shader,
+                &fragment_module,
+                fragment_entry_point,
+                ShaderStages::FRAGMENT,
+                &mut used_bindings,
+            ) {
+                return CachedPipelineState::Err(err);
+            }
+            Some((fragment_module, fragment_entry_point, &fragment.targets))
         } else {
             None
         };
@@ -405,11 +877,24 @@ impl PipelineCache {
             })
             .collect::<Vec<_>>();
 
-        let layout = if let Some(layout) = &descriptor.layout {
-            Some(self.layout_cache.get(&self.device, layout))
-        } else {
-            None
-        };
+        if let Err(err) = self.validate_binding_names(
+            descriptor.label.as_deref(),
+            descriptor.layout.as_deref(),
+            used_bindings,
+        ) {
+            return CachedPipelineState::Err(err);
+        }
+        if let Err(err) = self.validate_push_constants(
+            descriptor.label.as_deref(),
+            &descriptor.push_constant_ranges,
+        ) {
+            return CachedPipelineState::Err(err);
+        }
+        let layout = self.layout_cache.get_optional(
+            &self.device,
+            descriptor.layout.as_deref(),
+            &descriptor.push_constant_ranges,
+        );
 
         let descriptor = RawRenderPipelineDescriptor {
             multiview: None,
@@ -420,14 +905,14 @@ impl PipelineCache {
             primitive: descriptor.primitive,
             vertex: RawVertexState {
                 buffers: &vertex_buffer_layouts,
-                entry_point: descriptor.vertex.entry_point.deref(),
-                module: &vertex_module,
+                entry_point: vertex_entry_point,
+                module: &vertex_module.module,
             },
             fragment: fragment_data
                 .as_ref()
                 .map(|(module, entry_point, targets)| RawFragmentState {
                     entry_point,
-                    module,
+                    module: &module.module,
                     targets,
                 }),
         };
@@ -437,6 +922,153 @@ impl PipelineCache {
         CachedPipelineState::Ok(Pipeline::RenderPipeline(pipeline))
     }
 
+    /// Checks that the shader `module` of a pipeline stage has the given `entry_point` and that its
+    /// bindings match the pipeline `layout`, collecting the bindings it uses into `used_bindings`.
+    /// This relies on shader reflection, so stages whose shader couldn't be reflected, or that were
+    /// compiled while [`BindingValidation::Disabled`] was set, aren't checked.
+    fn validate_stage(
+        &self,
+        layout: Option<&[BindGroupLayout]>,
+        shader: &Handle<Shader>,
+        module: &CachedShaderModule,
+        entry_point: &str,
+        stage: ShaderStages,
+        used_bindings: &mut UsedBindings,
+    ) -> Result<(), PipelineCacheError> {
+        let reflection = match &module.reflection {
+            Some(reflection) => reflection,
+            None => {
+                used_bindings.complete = false;
+                return Ok(());
+            }
+        };
+
+        let available = reflection
+            .entry_points()
+            .filter(|(_, entry_point_stage)| shader_stages(*entry_point_stage) == stage)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        if !available.contains(&entry_point) {
+            return Err(PipelineCacheError::MissingEntryPoint {
+                shader: self.shader_cache.describe(shader),
+                entry_point: entry_point.to_string(),
+                stage,
+                available: available.into_iter().map(String::from).collect(),
+            });
+        }
+
+        let bindings = match reflection.bindings_used_by(entry_point) {
+            Some(bindings) => bindings,
+            None => return Ok(()),
+        };
+        if let Some(layout) = layout {
+            let interfaces = layout_interfaces(layout);
+            validate_bindings(&interfaces, &bindings, stage, &mut used_bindings.mismatches)
+                .map_err(|error| PipelineCacheError::IncompatibleLayout {
+                    shader: self.shader_cache.describe(shader),
+                    entry_point: entry_point.to_string(),
+                    error: Box::new(error),
+                })?;
+        }
+        used_bindings.bindings.extend(bindings);
+
+        Ok(())
+    }
+
+    /// Reports the shader bindings of a pipeline that are named differently in its `layout`, and
+    /// the named entries of the layout none of its shaders use, as warnings or errors depending on
+    /// the [`BindingValidation`].
+    fn validate_binding_names(
+        &self,
+        label: Option<&str>,
+        layout: Option<&[BindGroupLayout]>,
+        used_bindings: UsedBindings,
+    ) -> Result<(), PipelineCacheError> {
+        let mut mismatches = used_bindings.mismatches;
+        // without the bindings of every stage, any entry might be used by the missing ones
+        if let (Some(layout), true) = (layout, used_bindings.complete) {
+            mismatches.extend(unused_layout_entries(
+                &layout_interfaces(layout),
+                &used_bindings.bindings,
+            ));
+        }
+
+        let pipeline = label.unwrap_or("unlabeled");
+        match self.binding_validation {
+            BindingValidation::Disabled => Ok(()),
+            BindingValidation::Warn => {
+                for mismatch in mismatches {
+                    warn!(
+                        "the pipeline '{}' may be misconfigured: {}",
+                        pipeline, mismatch
+                    );
+                }
+                Ok(())
+            }
+            BindingValidation::Strict => match mismatches.into_iter().next() {
+                Some(error) => Err(PipelineCacheError::MismatchedBindings {
+                    pipeline: pipeline.to_string(),
+                    error: Box::new(error),
+                }),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Checks that every input of the vertex shader is provided by the vertex buffers of the
+    /// pipeline with a matching format, and warns about vertex attributes the shader does not read
+    /// (unless [`VertexState::allow_unused_attributes`] is set). Like [`Self::validate_stage`],
+    /// this relies on shader reflection.
+    fn validate_vertex_inputs(
+        &self,
+        vertex: &VertexState,
+        module: &CachedShaderModule,
+    ) -> Result<(), PipelineCacheError> {
+        let entry_point = vertex.resolved_entry_point();
+        let inputs = match module
+            .reflection
+            .as_ref()
+            .and_then(|reflection| reflection.typed_vertex_inputs(entry_point))
+        {
+            Some(inputs) => inputs,
+            None => return Ok(()),
+        };
+
+        let unused = match validate_vertex_attributes(&inputs, &vertex.buffers) {
+            Ok(unused) => unused,
+            Err(VertexInputMismatch::MissingAttributes { inputs, attributes }) => {
+                return Err(PipelineCacheError::MissingVertexAttributes {
+                    shader: self.shader_cache.describe(&vertex.shader),
+                    entry_point: entry_point.to_string(),
+                    inputs,
+                    attributes,
+                });
+            }
+            Err(VertexInputMismatch::MismatchedFormat { input, format }) => {
+                return Err(PipelineCacheError::MismatchedVertexFormat {
+                    shader: self.shader_cache.describe(&vertex.shader),
+                    entry_point: entry_point.to_string(),
+                    input,
+                    format,
+                });
+            }
+        };
+
+        if !vertex.allow_unused_attributes {
+            for attribute in unused {
+                warn!(
+                    "the vertex attribute {:?} at location {} is not used by the entry point '{}' of the shader {}",
+                    attribute.format,
+                    attribute.shader_location,
+                    entry_point,
+                    self.shader_cache.describe(&vertex.shader)
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     fn process_compute_pipeline(
         &mut self,
         id: CachedPipelineId,
@@ -447,6 +1079,7 @@ impl PipelineCache {
             id,
             &descriptor.shader,
             &descriptor.shader_defs,
+            &descriptor.specialization_constants,
         ) {
             Ok(module) => module,
             Err(err) => {
@@ -454,17 +1087,43 @@ impl PipelineCache {
             }
         };
 
-        let layout = if let Some(layout) = &descriptor.layout {
-            Some(self.layout_cache.get(&self.device, layout))
-        } else {
-            None
-        };
+        let entry_point = descriptor.resolved_entry_point();
+        let mut used_bindings = UsedBindings::default();
+        if let Err(err) = self.validate_stage(
+            descriptor.layout.as_deref(),
+            &descriptor.shader,
+            &compute_module,
+            entry_point,
+            ShaderStages::COMPUTE,
+            &mut used_bindings,
+        ) {
+            return CachedPipelineState::Err(err);
+        }
+        if let Err(err) = self.validate_binding_names(
+            descriptor.label.as_deref(),
+            descriptor.layout.as_deref(),
+            used_bindings,
+        ) {
+            return CachedPipelineState::Err(err);
+        }
+
+        if let Err(err) = self.validate_push_constants(
+            descriptor.label.as_deref(),
+            &descriptor.push_constant_ranges,
+        ) {
+            return CachedPipelineState::Err(err);
+        }
+        let layout = self.layout_cache.get_optional(
+            &self.device,
+            descriptor.layout.as_deref(),
+            &descriptor.push_constant_ranges,
+        );
 
         let descriptor = RawComputePipelineDescriptor {
             label: descriptor.label.as_deref(),
             layout,
-            module: &compute_module,
-            entry_point: descriptor.entry_point.as_ref(),
+            module: &compute_module.module,
+            entry_point,
         };
 
         let pipeline = self.device.create_compute_pipeline(&descriptor);
@@ -472,9 +1131,30 @@ impl PipelineCache {
         CachedPipelineState::Ok(Pipeline::ComputePipeline(pipeline))
     }
 
-    pub fn process_queue(&mut self) {
+    /// Checks that the device supports the push constants used by a pipeline.
+    fn validate_push_constants(
+        &self,
+        label: Option<&str>,
+        push_constant_ranges: &[PushConstantRange],
+    ) -> Result<(), PipelineCacheError> {
+        let size = push_constants_size(push_constant_ranges);
+        if self.device.supports_push_constants(size) {
+            Ok(())
+        } else {
+            Err(PipelineCacheError::UnsupportedPushConstants {
+                pipeline: label.unwrap_or("unlabeled").to_string(),
+                size,
+                max_size: self.device.max_push_constant_size(),
+            })
+        }
+    }
+
+    /// Creates the queued pipelines whose shaders are available and returns the number of
+    /// pipelines that were created.
+    pub fn process_queue(&mut self) -> usize {
         let waiting_pipelines = mem::take(&mut self.waiting_pipelines);
         let mut pipelines = mem::take(&mut self.pipelines);
+        let mut created_count = 0;
 
         for id in waiting_pipelines {
             let pipeline = &mut pipelines[id];
@@ -484,20 +1164,31 @@ impl PipelineCache {
                 CachedPipelineState::Err(err) => {
                     match err {
                         PipelineCacheError::ShaderNotLoaded(_)
-                        | PipelineCacheError::ShaderImportNotYetAvailable => { /* retry */ }
+                        | PipelineCacheError::ShaderImportNotYetAvailable
+                        | PipelineCacheError::ShaderNotYetCompiled => { /* retry */ }
                         // shader could not be processed ... retrying won't help
                         PipelineCacheError::ProcessShaderError(err) => {
                             error!("failed to process shader: {}", err);
                             continue;
                         }
-                        PipelineCacheError::AsModuleDescriptorError(err, source) => {
-                            log_shader_error(source, err);
+                        PipelineCacheError::AsModuleDescriptorError(err, context) => {
+                            log_shader_error(context, err);
                             continue;
                         }
                         PipelineCacheError::CreateShaderModule(description) => {
                             error!("failed to create shader module: {}", description);
                             continue;
                         }
+                        PipelineCacheError::IncompatibleLayout { .. }
+                        | PipelineCacheError::MissingEntryPoint { .. }
+                        | PipelineCacheError::MissingVertexAttributes { .. }
+                        | PipelineCacheError::MismatchedVertexFormat { .. }
+                        | PipelineCacheError::DuplicateShaderLocation { .. }
+                        | PipelineCacheError::MismatchedBindings { .. }
+                        | PipelineCacheError::UnsupportedPushConstants { .. } => {
+                            error!("failed to create pipeline: {}", err);
+                            continue;
+                        }
                     }
                 }
             }
@@ -511,21 +1202,33 @@ impl PipelineCache {
                 }
             };
 
-            if let CachedPipelineState::Err(_) = pipeline.state {
-                self.waiting_pipelines.insert(id);
+            match pipeline.state {
+                CachedPipelineState::Ok(_) => {
+                    pipeline.previous = None;
+                    created_count += 1;
+                }
+                CachedPipelineState::Err(_) => {
+                    self.waiting_pipelines.insert(id);
+                }
+                CachedPipelineState::Queued => {}
             }
         }
 
         self.pipelines = pipelines;
+        created_count
     }
 
-    pub(crate) fn process_pipeline_queue_system(mut cache: ResMut<Self>) {
-        cache.process_queue();
+    pub(crate) fn process_pipeline_queue_system(
+        mut cache: ResMut<Self>,
+        statistics: Res<RenderStatistics>,
+    ) {
+        statistics.record_pipelines_specialized(cache.process_queue() as u64);
     }
 
     pub(crate) fn extract_shaders(
         mut world: ResMut<RenderWorld>,
         shaders: Res<Assets<Shader>>,
+        asset_server: Res<AssetServer>,
         mut events: EventReader<AssetEvent<Shader>>,
     ) {
         let mut cache = world.resource_mut::<Self>();
@@ -539,16 +1242,83 @@ impl PipelineCache {
                 AssetEvent::Removed { handle } => cache.remove_shader(handle),
             }
         }
+
+        let shader_cache = &mut cache.shader_cache;
+        for path in mem::take(&mut shader_cache.includes_to_load) {
+            let included = asset_server.load(path.as_str());
+            shader_cache.included_shaders.insert(path, included);
+        }
     }
 }
 
-fn log_shader_error(source: &ProcessedShader, error: &AsModuleDescriptorError) {
+/// Everything needed to explain why a shader failed to compile.
+#[derive(Debug)]
+pub struct ShaderErrorContext {
+    /// The shader source as it was passed to the shader compiler.
+    pub source: ProcessedShader,
+    /// Relates the lines of `source` to the original shader files.
+    pub source_map: ShaderSourceMap,
+    /// The import path (usually the asset path) of the shader that failed to compile.
+    pub path: Option<ShaderImport>,
+    /// The shader defs the shader was processed with.
+    pub shader_defs: Vec<String>,
+}
+
+impl ShaderErrorContext {
+    fn file_name(&self, fallback: &str) -> String {
+        self.path
+            .as_ref()
+            .map_or_else(|| fallback.to_string(), display_shader_import)
+    }
+
+    fn header(&self) -> String {
+        format!(
+            "failed to process shader {} with shader defs {:?}",
+            self.path
+                .as_ref()
+                .map_or_else(|| "<unnamed>".to_string(), display_shader_import),
+            self.shader_defs
+        )
+    }
+
+    /// Describes where the processed line containing `offset` came from.
+    fn origin_note(&self, source: &str, offset: usize) -> Option<String> {
+        self.source_map
+            .get_by_offset(source, offset)
+            .map(|origin| match &origin.file {
+                Some(file) => format!(
+                    "this is line {} of {}",
+                    origin.line,
+                    display_shader_import(file)
+                ),
+                None => format!("this is line {} of the unnamed shader", origin.line),
+            })
+    }
+}
+
+fn display_shader_import(import: &ShaderImport) -> String {
+    match import {
+        ShaderImport::AssetPath(path) => path.clone(),
+        ShaderImport::Custom(path) => path.clone(),
+    }
+}
+
+/// Returns a human readable name for the shader with the given `handle` and `import_path`.
+fn describe_shader(handle: &Handle<Shader>, import_path: Option<&ShaderImport>) -> String {
+    match import_path {
+        Some(import_path) => display_shader_import(import_path),
+        None => format!("{:?}", handle),
+    }
+}
+
+fn log_shader_error(context: &ShaderErrorContext, error: &AsModuleDescriptorError) {
     use codespan_reporting::{
         diagnostic::{Diagnostic, Label},
         files::SimpleFile,
         term,
     };
 
+    let source = &context.source;
     match error {
         AsModuleDescriptorError::ShaderReflectError(error) => match error {
             ShaderReflectError::WgslParse(error) => {
@@ -556,13 +1326,13 @@ fn log_shader_error(source: &ProcessedShader, error: &AsModuleDescriptorError) {
                     .get_wgsl_source()
                     .expect("non-wgsl source for wgsl error");
                 let msg = error.emit_to_string(source);
-                error!("failed to process shader:\n{}", msg);
+                error!("{}:\n{}", context.header(), msg);
             }
             ShaderReflectError::GlslParse(errors) => {
                 let source = source
                     .get_glsl_source()
                     .expect("non-glsl source for glsl error");
-                let files = SimpleFile::new("glsl", source);
+                let files = SimpleFile::new(context.file_name("glsl"), source);
                 let config = codespan_reporting::term::Config::default();
                 let mut writer = term::termcolor::Ansi::new(Vec::new());
 
@@ -570,7 +1340,14 @@ fn log_shader_error(source: &ProcessedShader, error: &AsModuleDescriptorError) {
                     let mut diagnostic = Diagnostic::error().with_message(err.kind.to_string());
 
                     if let Some(range) = err.meta.to_range() {
-                        diagnostic = diagnostic.with_labels(vec![Label::primary((), range)]);
+                        diagnostic = diagnostic
+                            .with_notes(
+                                context
+                                    .origin_note(source, range.start)
+                                    .into_iter()
+                                    .collect(),
+                            )
+                            .with_labels(vec![Label::primary((), range)]);
                     }
 
                     term::emit(&mut writer, &config, &files, &diagnostic)
@@ -580,55 +1357,70 @@ fn log_shader_error(source: &ProcessedShader, error: &AsModuleDescriptorError) {
                 let msg = writer.into_inner();
                 let msg = String::from_utf8_lossy(&msg);
 
-                error!("failed to process shader: \n{}", msg);
+                error!("{}: \n{}", context.header(), msg);
             }
             ShaderReflectError::SpirVParse(error) => {
-                error!("failed to process shader:\n{}", error);
+                error!("{}:\n{}", context.header(), error);
             }
             ShaderReflectError::Validation(error) => {
                 let (filename, source) = match source {
                     ProcessedShader::Wgsl(source) => ("wgsl", source.as_ref()),
                     ProcessedShader::Glsl(source, _) => ("glsl", source.as_ref()),
                     ProcessedShader::SpirV(_) => {
-                        error!("failed to process shader:\n{}", error);
+                        error!("{}:\n{}", context.header(), error);
                         return;
                     }
                 };
 
-                let files = SimpleFile::new(filename, source);
+                let files = SimpleFile::new(context.file_name(filename), source);
                 let config = term::Config::default();
                 let mut writer = term::termcolor::Ansi::new(Vec::new());
 
-                let diagnostic = Diagnostic::error()
-                    .with_message(error.to_string())
-                    .with_labels(
-                        error
-                            .spans()
-                            .map(|(span, desc)| {
-                                Label::primary((), span.to_range().unwrap())
-                                    .with_message(desc.to_owned())
-                            })
-                            .collect(),
-                    )
-                    .with_notes(
-                        ErrorSources::of(error)
-                            .map(|source| source.to_string())
-                            .collect(),
-                    );
+                let spans = error
+                    .spans()
+                    .filter_map(|(span, desc)| span.to_range().map(|range| (range, desc)))
+                    .collect::<Vec<_>>();
+                let diagnostic =
+                    Diagnostic::error()
+                        .with_message(error.to_string())
+                        .with_labels(
+                            spans
+                                .iter()
+                                .map(|(range, desc)| {
+                                    Label::primary((), range.clone()).with_message(desc.to_string())
+                                })
+                                .collect(),
+                        )
+                        .with_notes(
+                            ErrorSources::of(error)
+                                .map(|source| source.to_string())
+                                .chain(spans.iter().filter_map(|(range, _)| {
+                                    context.origin_note(source, range.start)
+                                }))
+                                .collect(),
+                        );
 
                 term::emit(&mut writer, &config, &files, &diagnostic).expect("cannot write error");
 
                 let msg = writer.into_inner();
                 let msg = String::from_utf8_lossy(&msg);
 
-                error!("failed to process shader: \n{}", msg);
+                error!("{}: \n{}", context.header(), msg);
             }
         },
         AsModuleDescriptorError::WgslConversion(error) => {
-            error!("failed to convert shader to wgsl: \n{}", error);
+            error!(
+                "{}: failed to convert shader to wgsl: \n{}",
+                context.header(),
+                error
+            );
         }
         AsModuleDescriptorError::SpirVConversion(error) => {
-            error!("failed to convert shader to spirv: \n{}", error);
+            error!(
+                "{}: failed to convert shader to spirv: \n{}",
+                context.header(),
+                error
+            );
         }
     }
 }
@@ -642,11 +1434,392 @@ pub enum PipelineCacheError {
     #[error(transparent)]
     ProcessShaderError(#[from] ProcessShaderError),
     #[error("{0}")]
-    AsModuleDescriptorError(AsModuleDescriptorError, ProcessedShader),
+    AsModuleDescriptorError(AsModuleDescriptorError, Box<ShaderErrorContext>),
     #[error("Shader import not yet available.")]
     ShaderImportNotYetAvailable,
+    #[error("Shader is still being compiled.")]
+    ShaderNotYetCompiled,
     #[error("Could not create shader module: {0}")]
     CreateShaderModule(String),
+    #[error("The entry point '{entry_point}' of the shader {shader} does not match the pipeline layout: {error}")]
+    IncompatibleLayout {
+        shader: String,
+        entry_point: String,
+        error: Box<BindingValidationError>,
+    },
+    #[error("The shader {shader} has no {stage:?} entry point named '{entry_point}'. Available entry points: {available:?}")]
+    MissingEntryPoint {
+        shader: String,
+        entry_point: String,
+        stage: ShaderStages,
+        available: Vec<String>,
+    },
+    #[error("The entry point '{entry_point}' of the shader {shader} reads vertex inputs {inputs:?}, but the vertex buffers only provide the shader locations {attributes:?}")]
+    MissingVertexAttributes {
+        shader: String,
+        entry_point: String,
+        inputs: Vec<String>,
+        attributes: Vec<u32>,
+    },
+    #[error("The vertex input {input} of the entry point '{entry_point}' of the shader {shader} can't be read from a vertex attribute of the format {format:?}")]
+    MismatchedVertexFormat {
+        shader: String,
+        entry_point: String,
+        input: String,
+        format: VertexFormat,
+    },
+    #[error("The pipeline '{pipeline}' uses {size} bytes of push constants, but the device only supports {max_size} bytes")]
+    UnsupportedPushConstants {
+        pipeline: String,
+        size: u32,
+        max_size: u32,
+    },
+    #[error("The vertex buffers of the pipeline '{pipeline}' are invalid: {error}")]
+    DuplicateShaderLocation {
+        pipeline: String,
+        error: DuplicateShaderLocation,
+    },
+    #[error("The shaders of the pipeline '{pipeline}' don't match its layout: {error}")]
+    MismatchedBindings {
+        pipeline: String,
+        error: Box<BindingValidationError>,
+    },
+}
+
+/// Describes how a shader's resource bindings disagree with the layout of a pipeline using it.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BindingValidationError {
+    #[error("the shader uses binding {binding} ('{name}') of bind group {group}, but the layout only has {group_count} bind groups{suggestion}")]
+    MissingBindGroup {
+        name: String,
+        group: u32,
+        binding: u32,
+        group_count: usize,
+        suggestion: BindingSuggestion,
+    },
+    #[error("the shader uses binding {binding} ('{name}') of bind group {group}, which is not part of the layout. The bind group has the bindings {available:?}{suggestion}")]
+    MissingBinding {
+        name: String,
+        group: u32,
+        binding: u32,
+        available: Vec<u32>,
+        suggestion: BindingSuggestion,
+    },
+    #[error("binding {binding} of bind group {group} is named '{name}' in the shader, but '{layout_name}' in the layout{suggestion}")]
+    MismatchedName {
+        name: String,
+        layout_name: String,
+        group: u32,
+        binding: u32,
+        suggestion: BindingSuggestion,
+    },
+    #[error("binding {binding} ('{layout_name}') of bind group {group} is not used by any shader of the pipeline{suggestion}")]
+    UnusedEntry {
+        layout_name: String,
+        group: u32,
+        binding: u32,
+        suggestion: BindingSuggestion,
+    },
+    #[error(
+        "binding {binding} ('{name}') of bind group {group} is not visible to the {stage:?} stage"
+    )]
+    NotVisible {
+        name: String,
+        group: u32,
+        binding: u32,
+        stage: ShaderStages,
+    },
+}
+
+/// Describes how the inputs of a vertex shader disagree with the vertex buffers of a pipeline,
+/// as returned by [`validate_vertex_attributes`].
+#[derive(Debug, PartialEq, Eq)]
+enum VertexInputMismatch {
+    /// Some inputs aren't provided by any attribute. Contains all inputs and the shader locations
+    /// of all attributes.
+    MissingAttributes {
+        inputs: Vec<String>,
+        attributes: Vec<u32>,
+    },
+    /// The attribute read by the `input` has a format holding a different kind of scalars.
+    MismatchedFormat { input: String, format: VertexFormat },
+}
+
+/// Checks that every vertex input of a shader is provided by an attribute of the vertex `buffers`
+/// with a format of the same kind of scalars, and returns the attributes no input reads.
+fn validate_vertex_attributes<'a>(
+    inputs: &[(ReflectedVertexInput, Option<naga::ScalarKind>)],
+    buffers: &'a [VertexBufferLayout],
+) -> Result<Vec<&'a VertexAttribute>, VertexInputMismatch> {
+    let mut attributes = buffers
+        .iter()
+        .flat_map(|buffer| buffer.attributes.iter())
+        .collect::<Vec<_>>();
+    attributes.sort_unstable_by_key(|attribute| attribute.shader_location);
+    let attribute_at = |location| {
+        attributes
+            .iter()
+            .find(|attribute| attribute.shader_location == location)
+    };
+
+    if inputs
+        .iter()
+        .any(|(input, _)| attribute_at(input.location).is_none())
+    {
+        return Err(VertexInputMismatch::MissingAttributes {
+            inputs: inputs
+                .iter()
+                .map(|(input, _)| display_vertex_input(input))
+                .collect(),
+            attributes: attributes
+                .iter()
+                .map(|attribute| attribute.shader_location)
+                .collect(),
+        });
+    }
+    for (input, kind) in inputs {
+        let format = attribute_at(input.location).unwrap().format;
+        if kind.map_or(false, |kind| kind != vertex_format_kind(format)) {
+            return Err(VertexInputMismatch::MismatchedFormat {
+                input: display_vertex_input(input),
+                format,
+            });
+        }
+    }
+
+    Ok(attributes
+        .into_iter()
+        .filter(|attribute| {
+            !inputs
+                .iter()
+                .any(|(input, _)| input.location == attribute.shader_location)
+        })
+        .collect())
+}
+
+/// Returns the kind of the scalars a shader reads from a vertex attribute of `format`.
+fn vertex_format_kind(format: VertexFormat) -> naga::ScalarKind {
+    use VertexFormat::*;
+    match format {
+        Uint8x2 | Uint8x4 | Uint16x2 | Uint16x4 | Uint32 | Uint32x2 | Uint32x3 | Uint32x4 => {
+            naga::ScalarKind::Uint
+        }
+        Sint8x2 | Sint8x4 | Sint16x2 | Sint16x4 | Sint32 | Sint32x2 | Sint32x3 | Sint32x4 => {
+            naga::ScalarKind::Sint
+        }
+        // normalized integers are read as floats
+        _ => naga::ScalarKind::Float,
+    }
+}
+
+fn display_vertex_input(input: &ReflectedVertexInput) -> String {
+    match &input.name {
+        Some(name) => format!("{} ({})", input.location, name),
+        None => input.location.to_string(),
+    }
+}
+
+fn shader_stages(stage: naga::ShaderStage) -> ShaderStages {
+    match stage {
+        naga::ShaderStage::Vertex => ShaderStages::VERTEX,
+        naga::ShaderStage::Fragment => ShaderStages::FRAGMENT,
+        naga::ShaderStage::Compute => ShaderStages::COMPUTE,
+    }
+}
+
+/// A binding suggested in place of a misspelled one, displayed as a "did you mean" sentence
+/// appended to a [`BindingValidationError`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BindingSuggestion(Option<(String, u32, u32)>);
+
+impl BindingSuggestion {
+    /// The name, bind group and binding of the suggested binding.
+    pub fn binding(&self) -> Option<(&str, u32, u32)> {
+        self.0
+            .as_ref()
+            .map(|(name, group, binding)| (name.as_str(), *group, *binding))
+    }
+
+    /// Suggests the binding whose name is closest to `name`, if any is close enough.
+    fn closest<'a>(
+        name: &str,
+        mut candidates: impl Iterator<Item = (&'a str, u32, u32)> + Clone,
+    ) -> Self {
+        Self(
+            closest_name(name, candidates.clone().map(|(candidate, _, _)| candidate)).and_then(
+                |closest| {
+                    candidates
+                        .find(|(candidate, _, _)| *candidate == closest)
+                        .map(|(candidate, group, binding)| (candidate.to_string(), group, binding))
+                },
+            ),
+        )
+    }
+}
+
+impl std::fmt::Display for BindingSuggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some((name, group, binding)) => write!(
+                f,
+                ". Did you mean '{}' (binding {} of bind group {})?",
+                name, binding, group
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The parts of a [`BindGroupLayout`] the bindings of shaders are validated against.
+struct LayoutInterface<'a> {
+    /// The entries of the layout, which are unknown for layouts converted from raw wgpu layouts.
+    entries: Option<&'a [BindGroupLayoutEntry]>,
+    names: &'a [(u32, String)],
+}
+
+impl<'a> LayoutInterface<'a> {
+    fn name(&self, binding: u32) -> Option<&'a str> {
+        self.names
+            .iter()
+            .find(|(named, _)| *named == binding)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+fn layout_interfaces(layout: &[BindGroupLayout]) -> Vec<LayoutInterface> {
+    layout
+        .iter()
+        .map(|bind_group_layout| LayoutInterface {
+            entries: bind_group_layout.entries(),
+            names: bind_group_layout.entry_names(),
+        })
+        .collect()
+}
+
+/// The named entries of all bind groups of a layout, as `(name, group, binding)`.
+fn named_entries<'a>(
+    layout: &'a [LayoutInterface<'a>],
+) -> impl Iterator<Item = (&'a str, u32, u32)> + Clone {
+    layout.iter().enumerate().flat_map(|(group, interface)| {
+        interface
+            .names
+            .iter()
+            .map(move |(binding, name)| (name.as_str(), group as u32, *binding))
+    })
+}
+
+/// The bindings used by the shaders of a pipeline, collected while validating its stages.
+struct UsedBindings {
+    bindings: Vec<ReflectedBinding>,
+    /// The bindings that are named differently in the layout.
+    mismatches: Vec<BindingValidationError>,
+    /// Whether the bindings of all stages are known, which requires their shaders to be reflected.
+    complete: bool,
+}
+
+impl Default for UsedBindings {
+    fn default() -> Self {
+        Self {
+            bindings: Vec::new(),
+            mismatches: Vec::new(),
+            complete: true,
+        }
+    }
+}
+
+/// Checks that every resource binding used by a shader `stage` is part of the pipeline `layout`
+/// and visible to the stage. Bindings that are named differently in the layout are pushed to
+/// `mismatches`, since they still work.
+fn validate_bindings(
+    layout: &[LayoutInterface],
+    bindings: &[ReflectedBinding],
+    stage: ShaderStages,
+    mismatches: &mut Vec<BindingValidationError>,
+) -> Result<(), BindingValidationError> {
+    for binding in bindings {
+        let name = binding
+            .name
+            .clone()
+            .unwrap_or_else(|| "<unnamed>".to_string());
+        let suggestion = || match &binding.name {
+            Some(name) => BindingSuggestion::closest(name, named_entries(layout)),
+            None => BindingSuggestion::default(),
+        };
+        let interface = layout.get(binding.group as usize).ok_or_else(|| {
+            BindingValidationError::MissingBindGroup {
+                name: name.clone(),
+                group: binding.group,
+                binding: binding.binding,
+                group_count: layout.len(),
+                suggestion: suggestion(),
+            }
+        })?;
+        // layouts converted from raw wgpu layouts can't be validated
+        let entries = match interface.entries {
+            Some(entries) => entries,
+            None => continue,
+        };
+        let entry = entries
+            .iter()
+            .find(|entry| entry.binding == binding.binding)
+            .ok_or_else(|| BindingValidationError::MissingBinding {
+                name: name.clone(),
+                group: binding.group,
+                binding: binding.binding,
+                available: entries.iter().map(|entry| entry.binding).collect(),
+                suggestion: suggestion(),
+            })?;
+        if !entry.visibility.contains(stage) {
+            return Err(BindingValidationError::NotVisible {
+                name,
+                group: binding.group,
+                binding: binding.binding,
+                stage,
+            });
+        }
+        if let (Some(name), Some(layout_name)) = (&binding.name, interface.name(binding.binding)) {
+            if name != layout_name {
+                mismatches.push(BindingValidationError::MismatchedName {
+                    name: name.clone(),
+                    layout_name: layout_name.to_string(),
+                    group: binding.group,
+                    binding: binding.binding,
+                    suggestion: suggestion(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the named entries of the `layout` none of the `used` bindings refer to, suggesting the
+/// used bindings with the closest names.
+fn unused_layout_entries(
+    layout: &[LayoutInterface],
+    used: &[ReflectedBinding],
+) -> Vec<BindingValidationError> {
+    let used_names = used.iter().filter_map(|binding| {
+        binding
+            .name
+            .as_deref()
+            .map(|name| (name, binding.group, binding.binding))
+    });
+    named_entries(layout)
+        .filter(|(_, group, binding)| {
+            !used
+                .iter()
+                .any(|used| used.group == *group && used.binding == *binding)
+        })
+        .map(
+            |(layout_name, group, binding)| BindingValidationError::UnusedEntry {
+                layout_name: layout_name.to_string(),
+                group,
+                binding,
+                suggestion: BindingSuggestion::closest(layout_name, used_names.clone()),
+            },
+        )
+        .collect()
 }
 
 struct ErrorSources<'a> {
@@ -670,3 +1843,424 @@ impl<'a> Iterator for ErrorSources<'a> {
         current
     }
 }
+
+fn render_pipeline_descriptor(
+    pipelines: &[CachedPipeline],
+    id: CachedRenderPipelineId,
+) -> &RenderPipelineDescriptor {
+    match &pipelines[id.0].descriptor {
+        PipelineDescriptor::RenderPipelineDescriptor(descriptor) => descriptor,
+        PipelineDescriptor::ComputePipelineDescriptor(_) => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        push_constants_size, unused_layout_entries, validate_bindings, BindingSuggestion,
+        BindingValidationError, CachedRenderPipelineId, CompileJobs, LayoutInterface,
+        PipelineCacheError, RenderPipelineSpecializationKey, RenderPipelineSpecializations,
+        ShaderCache, ShaderModuleSource, VertexInputMismatch,
+    };
+    use crate::{
+        render_resource::{
+            ProcessedShader, RenderPipelineDescriptor, Shader, ShaderDefs, ShaderImport,
+            VertexBufferLayout,
+        },
+        test_util::pipeline_descriptor,
+    };
+    use bevy_asset::{Handle, HandleUntyped};
+    use bevy_reflect::TypeUuid;
+    use std::borrow::Cow;
+    use wgpu::{
+        BindGroupLayoutEntry, BindingType, BufferBindingType, PushConstantRange, ShaderStages,
+        VertexFormat, VertexStepMode,
+    };
+
+    #[test]
+    fn push_constant_ranges_size() {
+        assert_eq!(push_constants_size(&[]), 0);
+        assert_eq!(
+            push_constants_size(&[
+                PushConstantRange {
+                    stages: ShaderStages::VERTEX,
+                    range: 0..16,
+                },
+                PushConstantRange {
+                    stages: ShaderStages::FRAGMENT,
+                    range: 16..64,
+                },
+            ]),
+            64
+        );
+    }
+
+    fn uniform_entry(binding: u32) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    #[test]
+    fn binding_names_are_validated_both_ways() {
+        #[rustfmt::skip]
+        const FRAGMENT: &str = r"
+struct Color {
+    value: vec4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> materail: Color;
+[[group(0), binding(1)]]
+var<uniform> lights: Color;
+
+[[stage(fragment)]]
+fn fragment() -> [[location(0)]] vec4<f32> {
+    return materail.value + lights.value;
+}
+";
+        let used = ProcessedShader::Wgsl(Cow::Borrowed(FRAGMENT))
+            .reflect()
+            .unwrap()
+            .bindings_used_by("fragment")
+            .unwrap();
+
+        let entries = [uniform_entry(0), uniform_entry(1), uniform_entry(2)];
+        let names = [
+            (0, "material".to_string()),
+            (1, "lights".to_string()),
+            (2, "shadows".to_string()),
+        ];
+        let layout = [LayoutInterface {
+            entries: Some(&entries),
+            names: &names,
+        }];
+        let mut mismatches = Vec::new();
+        validate_bindings(&layout, &used, ShaderStages::FRAGMENT, &mut mismatches).unwrap();
+        assert_eq!(
+            mismatches,
+            [BindingValidationError::MismatchedName {
+                name: "materail".to_string(),
+                layout_name: "material".to_string(),
+                group: 0,
+                binding: 0,
+                suggestion: BindingSuggestion(Some(("material".to_string(), 0, 0))),
+            }]
+        );
+        assert_eq!(
+            unused_layout_entries(&layout, &used),
+            [BindingValidationError::UnusedEntry {
+                layout_name: "shadows".to_string(),
+                group: 0,
+                binding: 2,
+                suggestion: BindingSuggestion::default(),
+            }]
+        );
+
+        // bindings missing from the layout fail, suggesting entries with the same name elsewhere
+        let entries = [uniform_entry(0)];
+        let names = [(0, "lights".to_string())];
+        let layout = [LayoutInterface {
+            entries: Some(&entries),
+            names: &names,
+        }];
+        let error =
+            validate_bindings(&layout, &used, ShaderStages::FRAGMENT, &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            error,
+            BindingValidationError::MissingBinding {
+                name: "lights".to_string(),
+                group: 0,
+                binding: 1,
+                available: vec![0],
+                suggestion: BindingSuggestion(Some(("lights".to_string(), 0, 0))),
+            }
+        );
+        assert!(error
+            .to_string()
+            .ends_with("Did you mean 'lights' (binding 0 of bind group 0)?"));
+    }
+
+    #[test]
+    fn vertex_inputs_are_validated_against_attributes() {
+        #[rustfmt::skip]
+        const VERTEX: &str = r"
+[[stage(vertex)]]
+fn vertex(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] joint_indices: vec4<u32>,
+) -> [[builtin(position)]] vec4<f32> {
+    return vec4<f32>(position, f32(joint_indices.x));
+}
+";
+        let inputs = ProcessedShader::Wgsl(Cow::Borrowed(VERTEX))
+            .reflect()
+            .unwrap()
+            .typed_vertex_inputs("vertex")
+            .unwrap();
+        let validate = |formats: &[VertexFormat]| {
+            let buffers = [VertexBufferLayout::from_vertex_formats(
+                VertexStepMode::Vertex,
+                formats.iter().copied(),
+            )];
+            super::validate_vertex_attributes(&inputs, &buffers).map(|unused| {
+                unused
+                    .iter()
+                    .map(|attribute| (attribute.shader_location, attribute.format))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            validate(&[VertexFormat::Float32x3, VertexFormat::Uint8x4]),
+            Ok(vec![])
+        );
+        // unused attributes are returned to be warned about
+        assert_eq!(
+            validate(&[
+                VertexFormat::Float32x3,
+                VertexFormat::Uint16x4,
+                VertexFormat::Unorm8x4
+            ]),
+            Ok(vec![(2, VertexFormat::Unorm8x4)])
+        );
+        assert_eq!(
+            validate(&[VertexFormat::Float32x3]),
+            Err(VertexInputMismatch::MissingAttributes {
+                inputs: vec!["0 (position)".to_string(), "1 (joint_indices)".to_string()],
+                attributes: vec![0],
+            })
+        );
+        // normalized integers are read as floats, not as integers
+        assert_eq!(
+            validate(&[VertexFormat::Float32x3, VertexFormat::Unorm8x4]),
+            Err(VertexInputMismatch::MismatchedFormat {
+                input: "1 (joint_indices)".to_string(),
+                format: VertexFormat::Unorm8x4,
+            })
+        );
+        assert_eq!(
+            validate(&[VertexFormat::Sint32x3, VertexFormat::Uint8x4]),
+            Err(VertexInputMismatch::MismatchedFormat {
+                input: "0 (position)".to_string(),
+                format: VertexFormat::Sint32x3,
+            })
+        );
+    }
+
+    fn descriptor(shader_defs: &[&'static str]) -> RenderPipelineDescriptor {
+        let mut descriptor = pipeline_descriptor();
+        for shader_def in shader_defs {
+            descriptor.push_shader_def(*shader_def, ShaderStages::VERTEX_FRAGMENT);
+        }
+        descriptor
+    }
+
+    #[test]
+    fn equal_descriptors_share_pipelines() {
+        let mut specializations = RenderPipelineSpecializations::default();
+        let mut descriptors = Vec::new();
+        let mut ids = Vec::new();
+
+        // e.g. one material per entity, each with one of two sets of shader defs
+        for entity in 0..1000 {
+            let descriptor = if entity % 2 == 0 {
+                descriptor(&["A", "B"])
+            } else {
+                descriptor(&["B"])
+            };
+            let key = RenderPipelineSpecializationKey::new(&descriptor);
+            let found = specializations.find(&key, &descriptor, |id: CachedRenderPipelineId| {
+                &descriptors[id.0]
+            });
+            let id = found.unwrap_or_else(|| {
+                let id = CachedRenderPipelineId(descriptors.len());
+                descriptors.push(descriptor);
+                specializations.insert(key, id);
+                id
+            });
+            ids.push(id);
+        }
+
+        assert_eq!(descriptors.len(), 2);
+        assert_eq!(specializations.pipelines.len(), 2);
+        assert_eq!(ids[0], ids[998]);
+        assert_eq!(ids[1], ids[999]);
+        assert_ne!(ids[0], ids[1]);
+
+        // the order of the shader defs only matters for the descriptor, not the key
+        assert_eq!(
+            RenderPipelineSpecializationKey::new(&descriptor(&["B", "A"])),
+            RenderPipelineSpecializationKey::new(&descriptor(&["A", "B"])),
+        );
+    }
+
+    #[test]
+    fn modified_import_requeues_dependent_pipelines() {
+        let mut cache = ShaderCache::default();
+        let main_handle = Handle::<Shader>::default();
+        let import_handle = HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 1).typed::<Shader>();
+
+        assert!(cache
+            .set_shader(
+                &main_handle,
+                Shader::from_wgsl("#import FOO\nfn main() { }")
+            )
+            .is_empty());
+        assert!(cache
+            .set_shader(
+                &import_handle,
+                Shader::from_wgsl("fn foo() { }").with_import_path("FOO")
+            )
+            .is_empty());
+
+        // pretend a pipeline was created from the main shader
+        cache
+            .data
+            .get_mut(&main_handle)
+            .unwrap()
+            .pipelines
+            .insert(7);
+
+        let requeued = cache.set_shader(
+            &import_handle,
+            Shader::from_wgsl("fn foo() { let x = 1; }").with_import_path("FOO"),
+        );
+        assert_eq!(requeued, vec![7]);
+
+        let requeued = cache.set_shader(
+            &main_handle,
+            Shader::from_wgsl("#import FOO\nfn main() { }"),
+        );
+        assert_eq!(requeued, vec![7]);
+    }
+
+    #[test]
+    fn includes_resolve_to_generated_shaders_of_the_same_cache() {
+        let mut cache = ShaderCache::default();
+        let generated_handle = Handle::<Shader>::default();
+        let main_handle = HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 1).typed::<Shader>();
+        let main = Shader::from_glsl(
+            "#version 450\n#include \"generated.glsl\"\nvoid main() { }",
+            naga::ShaderStage::Fragment,
+        );
+
+        // without the generated shader, the include is loaded from the assets
+        let mut other_cache = ShaderCache::default();
+        other_cache.set_shader(&main_handle, main.clone());
+        assert_eq!(
+            other_cache.includes_to_load,
+            ["generated.glsl".to_string()].into_iter().collect()
+        );
+
+        // like the shaders created by `Shader::from_glsl_struct`
+        let generated =
+            Shader::from_glsl("struct Generated { float x; };", naga::ShaderStage::Vertex)
+                .with_import_path("generated.glsl");
+        cache.set_shader(&generated_handle, generated);
+        cache.set_shader(&main_handle, main);
+        assert!(cache.includes_to_load.is_empty());
+        assert_eq!(
+            cache.data[&main_handle].resolved_imports
+                [&ShaderImport::Custom("generated.glsl".to_string())],
+            generated_handle
+        );
+    }
+
+    #[test]
+    fn broken_shaders_requeue_their_pipelines_without_retrying() {
+        #[rustfmt::skip]
+        const SHADER: &str = r"
+[[stage(vertex)]]
+fn vertex() -> [[builtin(position)]] vec4<f32> {
+    return vec4<f32>(0.0);
+}
+";
+        let mut shader_cache = ShaderCache::default();
+        let shader = Handle::<Shader>::default();
+        let defs = ShaderDefs::new();
+        shader_cache.set_shader(&shader, Shader::from_wgsl(SHADER));
+        assert!(matches!(
+            shader_cache.prepare(0, &shader, &defs, &[]),
+            Ok(ShaderModuleSource::Prepared(_))
+        ));
+
+        // e.g. a hot reloaded shader with a typo
+        assert_eq!(
+            shader_cache.set_shader(&shader, Shader::from_wgsl("fn vertex( {")),
+            vec![0]
+        );
+        // unlike a shader that is still loading, retrying this wouldn't help, so the pipeline
+        // cache logs it once and keeps the previous pipeline
+        assert!(matches!(
+            shader_cache.prepare(0, &shader, &defs, &[]),
+            Err(PipelineCacheError::AsModuleDescriptorError(..))
+        ));
+
+        // fixing the shader queues the pipeline again
+        assert_eq!(
+            shader_cache.set_shader(&shader, Shader::from_wgsl(SHADER)),
+            vec![0]
+        );
+        assert!(matches!(
+            shader_cache.prepare(0, &shader, &defs, &[]),
+            Ok(ShaderModuleSource::Prepared(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn simultaneous_requests_share_one_compile_job() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc,
+        };
+
+        let mut jobs = CompileJobs::<&str, u32, ()> {
+            task_pool: Some(bevy_tasks::TaskPool::new()),
+            ..Default::default()
+        };
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let (finish, finished) = mpsc::channel::<()>();
+        let finished = std::sync::Mutex::new(Some(finished));
+
+        // e.g. many entities appearing with the same new shader def combination in one frame
+        for _ in 0..100 {
+            let result = jobs.poll(&"A B", || {
+                spawned.fetch_add(1, Ordering::SeqCst);
+                let finished = finished.lock().unwrap().take().unwrap();
+                Ok(move || {
+                    finished.recv().unwrap();
+                    Ok(7)
+                })
+            });
+            assert_eq!(result, None);
+        }
+        assert_eq!(spawned.load(Ordering::SeqCst), 1);
+
+        finish.send(()).unwrap();
+        let result = loop {
+            if let Some(result) = jobs.poll(&"A B", || -> Result<fn() -> Result<u32, ()>, ()> {
+                panic!("the job is still running")
+            }) {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(result, Ok(7));
+        assert!(jobs.jobs.is_empty());
+
+        // other keys get their own job, which fails before being spawned
+        assert_eq!(
+            jobs.poll(&"A", || -> Result<fn() -> Result<u32, ()>, ()> { Err(()) }),
+            Some(Err(()))
+        );
+        assert!(jobs.jobs.is_empty());
+    }
+}
@@ -3,16 +3,40 @@ use crate::{
     mesh::{InnerMeshVertexBufferLayout, MeshVertexBufferLayout, MissingVertexAttributeError},
     render_resource::{
         CachedRenderPipelineId, ComputePipelineDescriptor, PipelineCache, RenderPipelineDescriptor,
-        VertexBufferLayout,
+        VertexBufferLayout, VertexBufferMergeError,
     },
 };
 use bevy_utils::{
-    default, hashbrown::hash_map::RawEntryMut, tracing::error, Entry, HashMap, PreHashMap,
-    PreHashMapExt,
+    default,
+    hashbrown::hash_map::RawEntryMut,
+    tracing::{error, field, info_span, Span},
+    Entry, FixedState, HashMap, PreHashMap, PreHashMapExt,
+};
+use std::{
+    any::type_name,
+    fmt::Debug,
+    hash::{BuildHasher, Hash, Hasher},
 };
-use std::{fmt::Debug, hash::Hash};
 use thiserror::Error;
 
+/// Creates the span around the specialization of a pipeline of the type `S` for the `key`, with
+/// the hash of the key to tell the specializations apart. The key is only hashed if a subscriber
+/// is interested in the span.
+fn specialization_span<S>(key: &impl Hash) -> Span {
+    let span = info_span!(
+        target: "bevy_render::uniforms",
+        "specialize_pipeline",
+        pipeline = type_name::<S>(),
+        key_hash = field::Empty
+    );
+    if !span.is_disabled() {
+        let mut hasher = FixedState.build_hasher();
+        key.hash(&mut hasher);
+        span.record("key_hash", &hasher.finish());
+    }
+    span
+}
+
 pub trait SpecializedRenderPipeline {
     type Key: Clone + Hash + PartialEq + Eq;
     fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor;
@@ -35,11 +59,24 @@ impl<S: SpecializedRenderPipeline> SpecializedRenderPipelines<S> {
         specialize_pipeline: &S,
         key: S::Key,
     ) -> CachedRenderPipelineId {
-        *self.cache.entry(key.clone()).or_insert_with(|| {
-            let descriptor = specialize_pipeline.specialize(key);
+        self.specialize_with(specialize_pipeline, key, |descriptor| {
             cache.queue_render_pipeline(descriptor)
         })
     }
+
+    /// Like [`Self::specialize`], but passes the descriptor of a new specialization to `queue`
+    /// instead of a [`PipelineCache`].
+    pub(crate) fn specialize_with(
+        &mut self,
+        specialize_pipeline: &S,
+        key: S::Key,
+        queue: impl FnOnce(RenderPipelineDescriptor) -> CachedRenderPipelineId,
+    ) -> CachedRenderPipelineId {
+        *self.cache.entry(key.clone()).or_insert_with(|| {
+            let _span = specialization_span::<S>(&key).entered();
+            queue(specialize_pipeline.specialize(key))
+        })
+    }
 }
 
 pub trait SpecializedComputePipeline {
@@ -65,6 +102,7 @@ impl<S: SpecializedComputePipeline> SpecializedComputePipelines<S> {
         key: S::Key,
     ) -> CachedComputePipelineId {
         *self.cache.entry(key.clone()).or_insert_with(|| {
+            let _span = specialization_span::<S>(&key).entered();
             let descriptor = specialize_pipeline.specialize(key);
             cache.queue_compute_pipeline(descriptor)
         })
@@ -110,12 +148,12 @@ impl<S: SpecializedMeshPipeline> SpecializedMeshPipelines<S> {
         match map.entry(key.clone()) {
             Entry::Occupied(entry) => Ok(*entry.into_mut()),
             Entry::Vacant(entry) => {
+                let _span = specialization_span::<S>(&key).entered();
                 let descriptor = specialize_pipeline
                     .specialize(key.clone(), layout)
                     .map_err(|mut err| {
+                        if let SpecializedMeshPipelineError::MissingVertexAttribute(err) = &mut err
                         {
-                            let SpecializedMeshPipelineError::MissingVertexAttribute(err) =
-                                &mut err;
                             err.pipeline_type = Some(std::any::type_name::<S>());
                         }
                         err
@@ -157,4 +195,6 @@ impl<S: SpecializedMeshPipeline> SpecializedMeshPipelines<S> {
 pub enum SpecializedMeshPipelineError {
     #[error(transparent)]
     MissingVertexAttribute(#[from] MissingVertexAttributeError),
+    #[error(transparent)]
+    VertexBufferMerge(#[from] VertexBufferMergeError),
 }
@@ -1,12 +1,21 @@
+use crate::render_resource::shader_disk_cache::Fnv1a;
 use bevy_asset::{AssetLoader, Handle, LoadContext, LoadedAsset};
+use bevy_crevice::glsl::GlslStruct;
 use bevy_reflect::{TypeUuid, Uuid};
 use bevy_utils::{tracing::error, BoxedFuture, HashMap};
 use naga::back::wgsl::WriterFlags;
 use naga::{valid::ModuleInfo, Module};
 use once_cell::sync::Lazy;
 use regex::Regex;
+use smallvec::SmallVec;
 use std::{
-    borrow::Cow, collections::HashSet, marker::Copy, ops::Deref, path::PathBuf, str::FromStr,
+    borrow::Cow,
+    collections::HashSet,
+    hash::{Hash, Hasher},
+    marker::Copy,
+    ops::Deref,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
 };
 use thiserror::Error;
 use wgpu::{util::make_spirv, ShaderModuleDescriptor, ShaderSource};
@@ -40,6 +49,7 @@ pub struct Shader {
     source: Source,
     import_path: Option<ShaderImport>,
     imports: Vec<ShaderImport>,
+    includes: Vec<String>,
 }
 
 impl Shader {
@@ -48,6 +58,7 @@ impl Shader {
         let shader_imports = SHADER_IMPORT_PROCESSOR.get_imports_from_str(&source);
         Shader {
             imports: shader_imports.imports,
+            includes: shader_imports.includes,
             import_path: shader_imports.import_path,
             source: Source::Wgsl(source),
         }
@@ -58,6 +69,7 @@ impl Shader {
         let shader_imports = SHADER_IMPORT_PROCESSOR.get_imports_from_str(&source);
         Shader {
             imports: shader_imports.imports,
+            includes: shader_imports.includes,
             import_path: shader_imports.import_path,
             source: Source::Glsl(source, stage),
         }
@@ -66,11 +78,66 @@ impl Shader {
     pub fn from_spirv(source: impl Into<Cow<'static, [u8]>>) -> Shader {
         Shader {
             imports: Vec::new(),
+            includes: Vec::new(),
             import_path: None,
             source: Source::SpirV(source.into()),
         }
     }
 
+    /// Creates a shader from SPIR-V binaries that were compiled ahead of time, one for each set of
+    /// shader defs the shader will be used with. Shader defs cannot be applied to a compiled
+    /// binary, so the variant is selected by the shader defs of the pipeline using it instead.
+    pub fn from_spirv_variants<D, S>(variants: impl IntoIterator<Item = (D, S)>) -> Shader
+    where
+        D: IntoIterator<Item = String>,
+        S: Into<Cow<'static, [u8]>>,
+    {
+        Shader {
+            imports: Vec::new(),
+            includes: Vec::new(),
+            import_path: None,
+            source: Source::PrecompiledSpirV(
+                variants
+                    .into_iter()
+                    .map(|(shader_defs, source)| (spirv_variant_key(shader_defs), source.into()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Creates a GLSL shader containing the [`GlslStruct::glsl_definition`] of `T`, which other GLSL
+    /// shaders include with `#include "<path>"`, where the path is given by deriving `GlslStruct`
+    /// with `#[glsl(include_path = "<path>")]`.
+    ///
+    /// Unlike other includes, the path is not relative to the including shader and isn't loaded
+    /// through the [`AssetServer`](bevy_asset::AssetServer). Instead, the [`PipelineCache`](crate::render_resource::PipelineCache) of
+    /// each app resolves the path to the generated shader it was given. This requires creating the
+    /// shader before loading shaders including it, e.g. when building a plugin:
+    ///
+    /// ```ignore
+    /// let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+    /// shaders.set_untracked(
+    ///     STANDARD_MATERIAL_GLSL_HANDLE,
+    ///     Shader::from_glsl_struct::<StandardMaterialUniformData>(),
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` has no include path.
+    pub fn from_glsl_struct<T: GlslStruct>() -> Shader {
+        let include_path = T::INCLUDE_PATH.unwrap_or_else(|| {
+            panic!(
+                "{} needs a `#[glsl(include_path = \"...\")]` attribute to be included",
+                T::NAME
+            )
+        });
+        // the stage doesn't matter, since the definition is only used in other shaders
+        let mut shader = Shader::from_glsl(T::glsl_definition(), naga::ShaderStage::Vertex);
+        shader.import_path = Some(ShaderImport::Custom(include_path.to_string()));
+        shader
+    }
+
     pub fn set_import_path<P: Into<String>>(&mut self, import_path: P) {
         self.import_path = Some(ShaderImport::Custom(import_path.into()));
     }
@@ -89,6 +156,12 @@ impl Shader {
     pub fn imports(&self) -> impl ExactSizeIterator<Item = &ShaderImport> {
         self.imports.iter()
     }
+
+    /// Returns the paths of the `# include` directives of this shader, as they are written. They
+    /// are resolved by [`ShaderProcessor::resolve_include`].
+    pub fn includes(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.includes.iter().map(String::as_str)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -96,11 +169,20 @@ pub enum Source {
     Wgsl(Cow<'static, str>),
     Glsl(Cow<'static, str>, naga::ShaderStage),
     SpirV(Cow<'static, [u8]>),
+    /// SPIR-V binaries keyed by the sorted and deduplicated shader defs they were compiled with.
+    PrecompiledSpirV(HashMap<Vec<String>, Cow<'static, [u8]>>),
     // TODO: consider the following
-    // PrecompiledSpirVMacros(HashMap<HashSet<String>, Vec<u32>>)
     // NagaModule(Module) ... Module impls Serialize/Deserialize
 }
 
+/// Sorts and deduplicates `shader_defs`, producing the key of a [`Source::PrecompiledSpirV`] variant.
+fn spirv_variant_key(shader_defs: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut key = shader_defs.into_iter().collect::<Vec<_>>();
+    key.sort_unstable();
+    key.dedup();
+    key
+}
+
 /// A processed [Shader]. This cannot contain preprocessor directions. It must be "ready to compile"
 #[derive(PartialEq, Eq, Debug)]
 pub enum ProcessedShader {
@@ -109,7 +191,67 @@ pub enum ProcessedShader {
     SpirV(Cow<'static, [u8]>),
 }
 
+/// The value of a SPIR-V specialization constant, set when a pipeline is created instead of
+/// requiring a separate shader def permutation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpecializationConstant {
+    /// The `constant_id` of the constant in the shader.
+    pub id: u32,
+    pub value: SpecializationConstantValue,
+}
+
+impl SpecializationConstant {
+    pub fn new(id: u32, value: impl Into<SpecializationConstantValue>) -> Self {
+        Self {
+            id,
+            value: value.into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpecializationConstantValue {
+    Bool(bool),
+    U32(u32),
+    I32(i32),
+}
+
+impl From<bool> for SpecializationConstantValue {
+    fn from(value: bool) -> Self {
+        SpecializationConstantValue::Bool(value)
+    }
+}
+
+impl From<u32> for SpecializationConstantValue {
+    fn from(value: u32) -> Self {
+        SpecializationConstantValue::U32(value)
+    }
+}
+
+impl From<i32> for SpecializationConstantValue {
+    fn from(value: i32) -> Self {
+        SpecializationConstantValue::I32(value)
+    }
+}
+
 impl ProcessedShader {
+    /// Sets the values of the given specialization `constants` in this shader. Only SPIR-V shaders
+    /// support specialization constants. Every constant must be declared by the shader.
+    pub fn specialize(
+        self,
+        constants: &[SpecializationConstant],
+    ) -> Result<ProcessedShader, ProcessShaderError> {
+        if constants.is_empty() {
+            return Ok(self);
+        }
+        match self {
+            ProcessedShader::SpirV(source) => Ok(ProcessedShader::SpirV(Cow::Owned(
+                specialize_spirv(&source, constants)?,
+            ))),
+            _ => Err(ProcessShaderError::ShaderFormatDoesNotSupportSpecializationConstants),
+        }
+    }
+
     pub fn get_wgsl_source(&self) -> Option<&str> {
         if let ProcessedShader::Wgsl(source) = self {
             Some(source)
@@ -196,7 +338,128 @@ pub struct ShaderReflection {
     pub module_info: ModuleInfo,
 }
 
+/// A resource binding declared by a shader, as reported by [`ShaderReflection::bindings`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectedBinding {
+    pub name: Option<String>,
+    pub group: u32,
+    pub binding: u32,
+}
+
+/// An input of a vertex entry point, as reported by [`ShaderReflection::vertex_inputs`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectedVertexInput {
+    pub name: Option<String>,
+    pub location: u32,
+}
+
 impl ShaderReflection {
+    /// Returns the names and stages of the entry points of this shader.
+    pub fn entry_points(&self) -> impl Iterator<Item = (&str, naga::ShaderStage)> {
+        self.module
+            .entry_points
+            .iter()
+            .map(|entry_point| (entry_point.name.as_str(), entry_point.stage))
+    }
+
+    /// Returns every resource binding (uniforms, storage buffers, textures and samplers) declared
+    /// by this shader, sorted by group and binding.
+    pub fn bindings(&self) -> Vec<ReflectedBinding> {
+        self.collect_bindings(|_| true)
+    }
+
+    /// Returns the resource bindings actually used by the entry point named `entry_point`, sorted
+    /// by group and binding. Returns [`None`] if there is no such entry point.
+    pub fn bindings_used_by(&self, entry_point: &str) -> Option<Vec<ReflectedBinding>> {
+        let index = self
+            .module
+            .entry_points
+            .iter()
+            .position(|candidate| candidate.name == entry_point)?;
+        let function_info = self.module_info.get_entry_point(index);
+        Some(self.collect_bindings(|handle| !function_info[handle].is_empty()))
+    }
+
+    fn collect_bindings(
+        &self,
+        mut filter: impl FnMut(naga::Handle<naga::GlobalVariable>) -> bool,
+    ) -> Vec<ReflectedBinding> {
+        let mut bindings = self
+            .module
+            .global_variables
+            .iter()
+            .filter(|(handle, _)| filter(*handle))
+            .filter_map(|(_, variable)| {
+                variable.binding.as_ref().map(|binding| ReflectedBinding {
+                    name: variable.name.clone(),
+                    group: binding.group,
+                    binding: binding.binding,
+                })
+            })
+            .collect::<Vec<_>>();
+        bindings.sort_by_key(|binding| (binding.group, binding.binding));
+        bindings
+    }
+
+    /// Returns the location-bound inputs of the vertex entry point named `entry_point`, sorted by
+    /// location. Returns [`None`] if there is no such vertex entry point.
+    pub fn vertex_inputs(&self, entry_point: &str) -> Option<Vec<ReflectedVertexInput>> {
+        let inputs = self.typed_vertex_inputs(entry_point)?;
+        Some(inputs.into_iter().map(|(input, _)| input).collect())
+    }
+
+    /// Like [`ShaderReflection::vertex_inputs`], but also returns the kind of the scalars each
+    /// input reads, which has to match the format of the vertex attribute at its location. The
+    /// kind is [`None`] for inputs that aren't scalars or vectors.
+    pub(crate) fn typed_vertex_inputs(
+        &self,
+        entry_point: &str,
+    ) -> Option<Vec<(ReflectedVertexInput, Option<naga::ScalarKind>)>> {
+        let scalar_kind = |ty: naga::Handle<naga::Type>| match self.module.types[ty].inner {
+            naga::TypeInner::Scalar { kind, .. } | naga::TypeInner::Vector { kind, .. } => {
+                Some(kind)
+            }
+            _ => None,
+        };
+        let entry_point = self.module.entry_points.iter().find(|candidate| {
+            candidate.stage == naga::ShaderStage::Vertex && candidate.name == entry_point
+        })?;
+
+        let mut inputs = Vec::new();
+        for argument in &entry_point.function.arguments {
+            match &argument.binding {
+                Some(naga::Binding::Location { location, .. }) => {
+                    let input = ReflectedVertexInput {
+                        name: argument.name.clone(),
+                        location: *location,
+                    };
+                    inputs.push((input, scalar_kind(argument.ty)));
+                }
+                Some(naga::Binding::BuiltIn(_)) => {}
+                // inputs grouped in a struct carry their bindings on the struct members
+                None => {
+                    if let naga::TypeInner::Struct { members, .. } =
+                        &self.module.types[argument.ty].inner
+                    {
+                        for member in members {
+                            if let Some(naga::Binding::Location { location, .. }) = member.binding {
+                                let input = ReflectedVertexInput {
+                                    name: member.name.clone(),
+                                    location,
+                                };
+                                inputs.push((input, scalar_kind(member.ty)));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        inputs.sort_by_key(|(input, _)| input.location);
+        Some(inputs)
+    }
+
     pub fn get_spirv(&self) -> Result<Vec<u32>, naga::back::spv::Error> {
         naga::back::spv::write_vec(
             &self.module,
@@ -240,20 +503,24 @@ impl AssetLoader for ShaderLoader {
                 _ => panic!("unhandled extension: {}", ext),
             };
 
-            let shader_imports = SHADER_IMPORT_PROCESSOR.get_imports(&shader);
-            if shader_imports.import_path.is_some() {
-                shader.import_path = shader_imports.import_path;
-            } else {
+            if shader.import_path.is_none() {
                 shader.import_path = Some(ShaderImport::AssetPath(
                     load_context.path().to_string_lossy().to_string(),
                 ));
             }
+            // `# include`s are loaded by the `PipelineCache`, which knows about generated includes
+            let dependencies = shader
+                .imports
+                .iter()
+                .filter_map(|import| match import {
+                    ShaderImport::AssetPath(asset_path) => Some(asset_path.clone()),
+                    ShaderImport::Custom(_) => None,
+                })
+                .collect::<Vec<_>>();
             let mut asset = LoadedAsset::new(shader);
-            for import in shader_imports.imports {
-                if let ShaderImport::AssetPath(asset_path) = import {
-                    let path = PathBuf::from_str(&asset_path)?;
-                    asset.add_dependency(path.into());
-                }
+            for asset_path in dependencies {
+                let path = PathBuf::from_str(&asset_path)?;
+                asset.add_dependency(path.into());
             }
 
             load_context.set_default_asset(asset);
@@ -268,25 +535,183 @@ impl AssetLoader for ShaderLoader {
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum ProcessShaderError {
-    #[error("Too many '# endif' lines. Each endif should be preceded by an if statement.")]
-    TooManyEndIfs,
     #[error(
-        "Not enough '# endif' lines. Each if statement should be followed by an endif statement."
+        "Too many '# endif' lines (line {0}). Each endif should be preceded by an if statement."
     )]
-    NotEnoughEndIfs,
+    TooManyEndIfs(usize),
+    #[error(
+        "Not enough '# endif' lines. The if statement on line {0} should be followed by an endif statement."
+    )]
+    NotEnoughEndIfs(usize),
+    #[error("'# else' on line {0} is not preceded by an if statement.")]
+    ElseWithoutIf(usize),
+    #[error("'# elif' on line {0} is not preceded by an if statement.")]
+    ElifWithoutIf(usize),
+    #[error("'# elif' on line {0} follows an '# else' of the same if statement.")]
+    ElifAfterElse(usize),
+    #[error("Multiple '# else' lines for the same if statement (line {0}).")]
+    MultipleElses(usize),
+    #[error("The shader def '{0}' used on line {1} is not defined or has no value.")]
+    MissingShaderDefValue(String, usize),
     #[error("This Shader's format does not support processing shader defs.")]
     ShaderFormatDoesNotSupportShaderDefs,
+    #[error("This precompiled Shader has no variant for the shader defs {0:?}.")]
+    MissingSpirVVariant(Vec<String>),
     #[error("This Shader's formatdoes not support imports.")]
     ShaderFormatDoesNotSupportImports,
+    #[error(
+        "The include \"{include}\" of the shader '{import_path}' is relative, but shaders with a \
+         custom import path have no directory to resolve it from."
+    )]
+    RelativeIncludeFromCustomImport {
+        include: String,
+        import_path: String,
+    },
     #[error("Unresolved import: {0:?}.")]
     UnresolvedImport(ShaderImport),
     #[error("The shader import {0:?} does not match the source file type. Support for this might be added in the future.")]
     MismatchedImportFormat(ShaderImport),
+    #[error(
+        "Shader imports are nested deeper than {}. Import chain: {0:?}",
+        MAX_SHADER_IMPORT_DEPTH
+    )]
+    ImportDepthExceeded(Vec<ShaderImport>),
+    #[error("This Shader's format does not support specialization constants.")]
+    ShaderFormatDoesNotSupportSpecializationConstants,
+    #[error("The shader does not declare a specialization constant with the id {0}.")]
+    MissingSpecializationConstant(u32),
+    #[error("The value of the specialization constant {0} does not match its type in the shader.")]
+    SpecializationConstantTypeMismatch(u32),
+    #[error("The SpirV shader is malformed.")]
+    InvalidSpirV,
+}
+
+const SPIRV_MAGIC_NUMBER: u32 = 0x0723_0203;
+const SPIRV_HEADER_WORDS: usize = 5;
+const SPIRV_OP_TYPE_INT: u32 = 21;
+const SPIRV_OP_TYPE_FLOAT: u32 = 22;
+const SPIRV_OP_DECORATE: u32 = 71;
+const SPIRV_OP_SPEC_CONSTANT_TRUE: u32 = 48;
+const SPIRV_OP_SPEC_CONSTANT_FALSE: u32 = 49;
+const SPIRV_OP_SPEC_CONSTANT: u32 = 50;
+const SPIRV_DECORATION_SPEC_ID: u32 = 1;
+
+/// Patches the default values of the specialization constants declared in a SPIR-V module, which
+/// is what the driver uses when no other value is provided at pipeline creation.
+fn specialize_spirv(
+    source: &[u8],
+    constants: &[SpecializationConstant],
+) -> Result<Vec<u8>, ProcessShaderError> {
+    if source.len() % 4 != 0 || source.len() < SPIRV_HEADER_WORDS * 4 {
+        return Err(ProcessShaderError::InvalidSpirV);
+    }
+    let mut words = source
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect::<Vec<_>>();
+    if words[0] != SPIRV_MAGIC_NUMBER {
+        return Err(ProcessShaderError::InvalidSpirV);
+    }
+
+    // maps result ids to the `constant_id` they are decorated with
+    let mut spec_ids = HashMap::default();
+    // maps the result ids of numeric types to their `(opcode, width, signedness)`, where the
+    // signedness of floats is always 0
+    let mut numeric_types = HashMap::default();
+    let mut instructions = Vec::new();
+    let mut offset = SPIRV_HEADER_WORDS;
+    while offset < words.len() {
+        let word_count = (words[offset] >> 16) as usize;
+        let opcode = words[offset] & 0xffff;
+        if word_count == 0 || offset + word_count > words.len() {
+            return Err(ProcessShaderError::InvalidSpirV);
+        }
+        if opcode == SPIRV_OP_DECORATE
+            && word_count == 4
+            && words[offset + 2] == SPIRV_DECORATION_SPEC_ID
+        {
+            spec_ids.insert(words[offset + 1], words[offset + 3]);
+        }
+        if opcode == SPIRV_OP_TYPE_INT && word_count == 4 {
+            numeric_types.insert(
+                words[offset + 1],
+                (opcode, words[offset + 2], words[offset + 3]),
+            );
+        }
+        if opcode == SPIRV_OP_TYPE_FLOAT && word_count == 3 {
+            numeric_types.insert(words[offset + 1], (opcode, words[offset + 2], 0));
+        }
+        instructions.push((offset, word_count, opcode));
+        offset += word_count;
+    }
+
+    let mut specialized = HashSet::new();
+    for (offset, word_count, opcode) in instructions {
+        let is_spec_constant = matches!(
+            opcode,
+            SPIRV_OP_SPEC_CONSTANT_TRUE | SPIRV_OP_SPEC_CONSTANT_FALSE | SPIRV_OP_SPEC_CONSTANT
+        );
+        if !is_spec_constant || word_count < 3 {
+            continue;
+        }
+        let id = match spec_ids.get(&words[offset + 2]) {
+            Some(id) => *id,
+            None => continue,
+        };
+        let constant = match constants.iter().find(|constant| constant.id == id) {
+            Some(constant) => constant,
+            None => continue,
+        };
+
+        let result_type = numeric_types.get(&words[offset + 1]).copied();
+        match (opcode, constant.value) {
+            (
+                SPIRV_OP_SPEC_CONSTANT_TRUE | SPIRV_OP_SPEC_CONSTANT_FALSE,
+                SpecializationConstantValue::Bool(value),
+            ) => {
+                let opcode = if value {
+                    SPIRV_OP_SPEC_CONSTANT_TRUE
+                } else {
+                    SPIRV_OP_SPEC_CONSTANT_FALSE
+                };
+                words[offset] = (word_count as u32) << 16 | opcode;
+            }
+            // 32 bit constants have exactly one literal word
+            (SPIRV_OP_SPEC_CONSTANT, SpecializationConstantValue::U32(value))
+                if word_count == 4 && result_type == Some((SPIRV_OP_TYPE_INT, 32, 0)) =>
+            {
+                words[offset + 3] = value;
+            }
+            (SPIRV_OP_SPEC_CONSTANT, SpecializationConstantValue::I32(value))
+                if word_count == 4 && result_type == Some((SPIRV_OP_TYPE_INT, 32, 1)) =>
+            {
+                words[offset + 3] = value as u32;
+            }
+            _ => return Err(ProcessShaderError::SpecializationConstantTypeMismatch(id)),
+        }
+        specialized.insert(id);
+    }
+
+    if let Some(missing) = constants
+        .iter()
+        .find(|constant| !specialized.contains(&constant.id))
+    {
+        return Err(ProcessShaderError::MissingSpecializationConstant(
+            missing.id,
+        ));
+    }
+
+    Ok(words.iter().flat_map(|word| word.to_le_bytes()).collect())
 }
 
+/// The maximum nesting depth of `# import` and `# include` directives. Deeper chains are almost
+/// certainly caused by a cycle.
+pub const MAX_SHADER_IMPORT_DEPTH: usize = 32;
+
 pub struct ShaderImportProcessor {
     import_asset_path_regex: Regex,
     import_custom_path_regex: Regex,
+    include_regex: Regex,
     define_import_path_regex: Regex,
 }
 
@@ -301,6 +726,7 @@ impl Default for ShaderImportProcessor {
         Self {
             import_asset_path_regex: Regex::new(r#"^\s*#\s*import\s+"(.+)""#).unwrap(),
             import_custom_path_regex: Regex::new(r"^\s*#\s*import\s+(.+)").unwrap(),
+            include_regex: Regex::new(r#"^\s*#\s*include\s+"(.+)""#).unwrap(),
             define_import_path_regex: Regex::new(r"^\s*#\s*define_import_path\s+(.+)").unwrap(),
         }
     }
@@ -309,6 +735,7 @@ impl Default for ShaderImportProcessor {
 #[derive(Default)]
 pub struct ShaderImports {
     imports: Vec<ShaderImport>,
+    includes: Vec<String>,
     import_path: Option<ShaderImport>,
 }
 
@@ -317,14 +744,19 @@ impl ShaderImportProcessor {
         match &shader.source {
             Source::Wgsl(source) => self.get_imports_from_str(source),
             Source::Glsl(source, _stage) => self.get_imports_from_str(source),
-            Source::SpirV(_source) => ShaderImports::default(),
+            Source::SpirV(_) | Source::PrecompiledSpirV(_) => ShaderImports::default(),
         }
     }
 
     pub fn get_imports_from_str(&self, shader: &str) -> ShaderImports {
         let mut shader_imports = ShaderImports::default();
         for line in shader.lines() {
-            if let Some(cap) = self.import_asset_path_regex.captures(line) {
+            if let Some(cap) = self.include_regex.captures(line) {
+                let include = cap.get(1).unwrap().as_str();
+                if !shader_imports.includes.iter().any(|path| path == include) {
+                    shader_imports.includes.push(include.to_string());
+                }
+            } else if let Some(cap) = self.import_asset_path_regex.captures(line) {
                 let import = cap.get(1).unwrap();
                 shader_imports
                     .imports
@@ -342,36 +774,395 @@ impl ShaderImportProcessor {
 
         shader_imports
     }
+
+    /// Resolves the path of an `# include` directive relative to the directory of the `including`
+    /// shader. Shaders that were not loaded from an asset path resolve includes relative to the
+    /// asset root.
+    ///
+    /// Shaders with a [`ShaderImport::Custom`] import path have no directory, so they can't use
+    /// includes starting with `./` or `../`.
+    pub fn resolve_include(
+        &self,
+        including: Option<&ShaderImport>,
+        include: &str,
+    ) -> Result<ShaderImport, ProcessShaderError> {
+        let path = match including {
+            Some(ShaderImport::AssetPath(including_path)) => Path::new(including_path)
+                .parent()
+                .map_or_else(|| PathBuf::from(include), |parent| parent.join(include)),
+            Some(ShaderImport::Custom(import_path))
+                if matches!(
+                    Path::new(include).components().next(),
+                    Some(Component::CurDir | Component::ParentDir)
+                ) =>
+            {
+                return Err(ProcessShaderError::RelativeIncludeFromCustomImport {
+                    include: include.to_string(),
+                    import_path: import_path.clone(),
+                });
+            }
+            _ => PathBuf::from(include),
+        };
+
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+        }
+
+        Ok(ShaderImport::AssetPath(
+            normalized.to_string_lossy().to_string(),
+        ))
+    }
 }
 
 pub static SHADER_IMPORT_PROCESSOR: Lazy<ShaderImportProcessor> =
     Lazy::new(ShaderImportProcessor::default);
 
 pub struct ShaderProcessor {
+    version_regex: Regex,
     ifdef_regex: Regex,
     ifndef_regex: Regex,
+    elif_regex: Regex,
     else_regex: Regex,
     endif_regex: Regex,
+    def_value_regex: Regex,
 }
 
 impl Default for ShaderProcessor {
     fn default() -> Self {
         Self {
+            version_regex: Regex::new(r"^\s*#\s*version\s").unwrap(),
             ifdef_regex: Regex::new(r"^\s*#\s*ifdef\s*([\w|\d|_]+)").unwrap(),
             ifndef_regex: Regex::new(r"^\s*#\s*ifndef\s*([\w|\d|_]+)").unwrap(),
+            elif_regex: Regex::new(
+                r"^\s*#\s*elif\s+(?:defined\s*\(\s*([\w|\d|_]+)\s*\)|([\w|\d|_]+))",
+            )
+            .unwrap(),
             else_regex: Regex::new(r"^\s*#\s*else").unwrap(),
             endif_regex: Regex::new(r"^\s*#\s*endif").unwrap(),
+            def_value_regex: Regex::new(r"#\{\s*([\w|\d|_]+)\s*\}").unwrap(),
+        }
+    }
+}
+
+/// The state of a single `# ifdef` / `# ifndef` block while processing a shader.
+struct ConditionalScope {
+    /// Whether lines in the current branch of this block are emitted.
+    active: bool,
+    /// Whether one of the branches of this block has already been selected.
+    branch_taken: bool,
+    /// Whether this block's `# else` has been reached.
+    seen_else: bool,
+    /// The line of the directive that opened this block.
+    line: usize,
+}
+
+impl ConditionalScope {
+    fn new(parent_active: bool, condition: bool, line: usize) -> Self {
+        Self {
+            active: parent_active && condition,
+            branch_taken: condition,
+            seen_else: false,
+            line,
+        }
+    }
+}
+
+/// Tracks the imports encountered while processing a shader and all of its imports.
+#[derive(Default)]
+struct ProcessState {
+    /// Shaders that have already been pulled in through `# include`. Each is only included once.
+    included: HashSet<ShaderImport>,
+    /// The imports currently being processed, outermost first.
+    chain: Vec<ShaderImport>,
+    /// The origin of every line written so far, in output order.
+    source_map: ShaderSourceMap,
+}
+
+/// The file and line a line of a [`ProcessedShader`] originates from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLine {
+    /// The import path of the shader containing the line. This is [`None`] for shaders that were
+    /// neither loaded as an asset nor given an import path.
+    pub file: Option<ShaderImport>,
+    /// The line number within that file, starting at 1.
+    pub line: usize,
+}
+
+/// Maps the lines of a [`ProcessedShader`] back to the shader sources they were taken from. This
+/// lets error messages point at the original files, even though processing removes conditional
+/// blocks, inlines imports and inserts generated lines.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShaderSourceMap {
+    lines: Vec<Option<SourceLine>>,
+}
+
+impl ShaderSourceMap {
+    /// Returns the origin of the given line of the processed source, starting at 1. Returns
+    /// [`None`] for lines that were generated during processing.
+    pub fn get(&self, processed_line: usize) -> Option<&SourceLine> {
+        processed_line
+            .checked_sub(1)
+            .and_then(|index| self.lines.get(index))
+            .and_then(Option::as_ref)
+    }
+
+    /// Returns the origin of the line containing the given byte offset of the processed `source`.
+    pub fn get_by_offset(&self, source: &str, offset: usize) -> Option<&SourceLine> {
+        let offset = offset.min(source.len());
+        let processed_line = source.as_bytes()[..offset]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count()
+            + 1;
+        self.get(processed_line)
+    }
+}
+
+/// The shader defs of a shader stage, e.g. `["VERTEX_TANGENTS", "SKINNED"]`.
+///
+/// Pipelines rarely use more than a few shader defs, which are stored inline without allocating.
+/// Shader defs known at compile time are borrowed, only computed ones like `format!("MAX_LIGHTS
+/// {}", max_lights)` need to own their string.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefs(SmallVec<[Cow<'static, str>; 4]>);
+
+impl ShaderDefs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, shader_def: impl Into<Cow<'static, str>>) {
+        self.0.push(shader_def.into());
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Returns owned copies of the shader defs, as used by the [`ShaderProcessor`].
+    pub fn to_strings(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|shader_def| shader_def.to_string())
+            .collect()
+    }
+}
+
+impl Deref for ShaderDefs {
+    type Target = [Cow<'static, str>];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S: Into<Cow<'static, str>>> FromIterator<S> for ShaderDefs {
+    fn from_iter<I: IntoIterator<Item = S>>(shader_defs: I) -> Self {
+        Self(shader_defs.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<S: Into<Cow<'static, str>>> Extend<S> for ShaderDefs {
+    fn extend<I: IntoIterator<Item = S>>(&mut self, shader_defs: I) {
+        self.0.extend(shader_defs.into_iter().map(Into::into));
+    }
+}
+
+impl From<Vec<String>> for ShaderDefs {
+    fn from(shader_defs: Vec<String>) -> Self {
+        shader_defs.into_iter().collect()
+    }
+}
+
+impl ShaderDefs {
+    /// Returns the [`ShaderDefsKey`] of the shader defs.
+    pub fn key(&self) -> ShaderDefsKey {
+        ShaderDefsKey::new(self.iter().map(|shader_def| &**shader_def))
+    }
+
+    /// Returns the shader defs sorted and without duplicates, together with their key.
+    pub fn sorted(&self) -> SortedShaderDefs {
+        let mut shader_defs = self.to_strings();
+        shader_defs.sort_unstable();
+        shader_defs.dedup();
+        SortedShaderDefs {
+            key: ShaderDefsKey::from_sorted(shader_defs.iter().map(String::as_str)),
+            shader_defs,
+        }
+    }
+}
+
+/// A hash of a set of shader defs, which doesn't depend on the order of the shader defs or
+/// duplicates. It is computed with a stable hash function, so the key of a set of shader defs is
+/// the same in every run, e.g. for the [`ShaderDiskCache`](super::ShaderDiskCache).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderDefsKey(pub u64);
+
+impl ShaderDefsKey {
+    pub fn new<'a>(shader_defs: impl IntoIterator<Item = &'a str>) -> Self {
+        // pipelines rarely have more shader defs than this, so sorting them doesn't allocate
+        let mut shader_defs = shader_defs.into_iter().collect::<SmallVec<[&str; 16]>>();
+        shader_defs.sort_unstable();
+        shader_defs.dedup();
+        Self::from_sorted(shader_defs)
+    }
+
+    fn from_sorted<'a>(shader_defs: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut hash = Fnv1a::default();
+        for shader_def in shader_defs {
+            hash.write(&(shader_def.len() as u64).to_le_bytes());
+            hash.write(shader_def.as_bytes());
         }
+        Self(hash.0)
+    }
+}
+
+/// Shader defs sorted and without duplicates, as returned by [`ShaderDefs::sorted`].
+///
+/// They are hashed by their [`ShaderDefsKey`] only. Equal keys are checked by comparing the shader
+/// defs, so a hash collision can't make two different sets of shader defs equal.
+#[derive(Clone, Debug)]
+pub struct SortedShaderDefs {
+    key: ShaderDefsKey,
+    shader_defs: Vec<String>,
+}
+
+impl SortedShaderDefs {
+    #[inline]
+    pub fn key(&self) -> ShaderDefsKey {
+        self.key
+    }
+
+    #[inline]
+    pub fn shader_defs(&self) -> &[String] {
+        &self.shader_defs
+    }
+}
+
+impl PartialEq for SortedShaderDefs {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.shader_defs == other.shader_defs
     }
 }
 
+impl Eq for SortedShaderDefs {}
+
+impl Hash for SortedShaderDefs {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl Default for SortedShaderDefs {
+    fn default() -> Self {
+        ShaderDefs::new().sorted()
+    }
+}
+
+/// Returns the name of a shader def. A shader def can carry a value, separated from its name by
+/// whitespace (e.g. `"MAX_LIGHTS 8"`).
+pub fn shader_def_name(shader_def: &str) -> &str {
+    shader_def.split_whitespace().next().unwrap_or(shader_def)
+}
+
+/// Returns the value of a shader def, which follows its name after whitespace, see
+/// [`shader_def_name`].
+pub fn shader_def_value(shader_def: &str) -> Option<&str> {
+    let shader_def = shader_def.trim();
+    let value = shader_def[shader_def_name(shader_def).len()..].trim_start();
+    (!value.is_empty()).then(|| value)
+}
+
 impl ShaderProcessor {
+    /// Resolves the conditional blocks and imports of the given `shader` for a set of `shader_defs`.
+    ///
+    /// GLSL shaders additionally receive a `#define` line for every shader def, inserted directly
+    /// after the `#version` directive. Shader defs carrying a value (`"MAX_LIGHTS 8"`) are defined
+    /// with that value, so it can be used in the shader source (`vec4 lights[MAX_LIGHTS];`).
+    /// In WGSL shaders, the value is substituted for every occurrence of `#{MAX_LIGHTS}`.
     pub fn process(
         &self,
         shader: &Shader,
         shader_defs: &[String],
         shaders: &HashMap<Handle<Shader>, Shader>,
         import_handles: &HashMap<ShaderImport, Handle<Shader>>,
+    ) -> Result<ProcessedShader, ProcessShaderError> {
+        self.process_with_source_map(shader, shader_defs, shaders, import_handles)
+            .map(|(processed, _source_map)| processed)
+    }
+
+    /// Same as [`ShaderProcessor::process`], but also returns a [`ShaderSourceMap`] relating the
+    /// lines of the processed shader to the files they came from.
+    pub fn process_with_source_map(
+        &self,
+        shader: &Shader,
+        shader_defs: &[String],
+        shaders: &HashMap<Handle<Shader>, Shader>,
+        import_handles: &HashMap<ShaderImport, Handle<Shader>>,
+    ) -> Result<(ProcessedShader, ShaderSourceMap), ProcessShaderError> {
+        let mut state = ProcessState::default();
+        let processed =
+            self.process_with_state(shader, shader_defs, shaders, import_handles, &mut state)?;
+        let mut source_map = state.source_map;
+
+        let processed = match processed {
+            ProcessedShader::Glsl(source, stage) if !shader_defs.is_empty() => {
+                let (source, first_define) = self.insert_glsl_defines(&source, shader_defs);
+                source_map.lines.splice(
+                    first_define..first_define,
+                    std::iter::repeat(None).take(shader_defs.len()),
+                );
+                ProcessedShader::Glsl(source.into(), stage)
+            }
+            processed => processed,
+        };
+
+        Ok((processed, source_map))
+    }
+
+    /// Inserts a `#define` for each shader def after the `#version` directive, which has to stay
+    /// the first directive of a GLSL shader. Also returns the index of the first inserted line.
+    fn insert_glsl_defines(&self, source: &str, shader_defs: &[String]) -> (String, usize) {
+        let mut defines = String::new();
+        for shader_def in shader_defs {
+            defines.push_str("#define ");
+            defines.push_str(shader_def);
+            defines.push('\n');
+        }
+
+        let mut final_string = String::with_capacity(source.len() + defines.len());
+        let mut first_define = None;
+        for (index, line) in source.lines().enumerate() {
+            final_string.push_str(line);
+            final_string.push('\n');
+            if first_define.is_none() && self.version_regex.is_match(line) {
+                final_string.push_str(&defines);
+                first_define = Some(index + 1);
+            }
+        }
+
+        if first_define.is_none() {
+            final_string.insert_str(0, &defines);
+        }
+
+        (final_string, first_define.unwrap_or(0))
+    }
+
+    fn process_with_state(
+        &self,
+        shader: &Shader,
+        shader_defs: &[String],
+        shaders: &HashMap<Handle<Shader>, Shader>,
+        import_handles: &HashMap<ShaderImport, Handle<Shader>>,
+        state: &mut ProcessState,
     ) -> Result<ProcessedShader, ProcessShaderError> {
         let shader_str = match &shader.source {
             Source::Wgsl(source) => source.deref(),
@@ -383,33 +1174,76 @@ impl ShaderProcessor {
                     return Err(ProcessShaderError::ShaderFormatDoesNotSupportShaderDefs);
                 }
             }
+            Source::PrecompiledSpirV(variants) => {
+                let key = spirv_variant_key(shader_defs.iter().cloned());
+                return match variants.get(&key) {
+                    Some(source) => Ok(ProcessedShader::SpirV(source.clone())),
+                    None => Err(ProcessShaderError::MissingSpirVVariant(key)),
+                };
+            }
         };
 
-        let shader_defs_unique = HashSet::<String>::from_iter(shader_defs.iter().cloned());
-        let mut scopes = vec![true];
+        let shader_defs_unique =
+            HashSet::<&str>::from_iter(shader_defs.iter().map(|def| shader_def_name(def)));
+        let mut scopes: Vec<ConditionalScope> = Vec::new();
         let mut final_string = String::new();
-        for line in shader_str.lines() {
+        for (line_index, line) in shader_str.lines().enumerate() {
+            let line_number = line_index + 1;
+            let active = scopes.last().map_or(true, |scope| scope.active);
             if let Some(cap) = self.ifdef_regex.captures(line) {
                 let def = cap.get(1).unwrap();
-                scopes.push(*scopes.last().unwrap() && shader_defs_unique.contains(def.as_str()));
+                let condition = shader_defs_unique.contains(def.as_str());
+                scopes.push(ConditionalScope::new(active, condition, line_number));
             } else if let Some(cap) = self.ifndef_regex.captures(line) {
                 let def = cap.get(1).unwrap();
-                scopes.push(*scopes.last().unwrap() && !shader_defs_unique.contains(def.as_str()));
-            } else if self.else_regex.is_match(line) {
-                let mut is_parent_scope_truthy = true;
-                if scopes.len() > 1 {
-                    is_parent_scope_truthy = scopes[scopes.len() - 2];
+                let condition = !shader_defs_unique.contains(def.as_str());
+                scopes.push(ConditionalScope::new(active, condition, line_number));
+            } else if let Some(cap) = self.elif_regex.captures(line) {
+                let def = cap.get(1).or_else(|| cap.get(2)).unwrap();
+                let is_parent_scope_truthy = scopes.len() < 2 || scopes[scopes.len() - 2].active;
+                let scope = scopes
+                    .last_mut()
+                    .ok_or(ProcessShaderError::ElifWithoutIf(line_number))?;
+                if scope.seen_else {
+                    return Err(ProcessShaderError::ElifAfterElse(line_number));
                 }
-                if let Some(last) = scopes.last_mut() {
-                    *last = is_parent_scope_truthy && !*last;
+                let condition = !scope.branch_taken && shader_defs_unique.contains(def.as_str());
+                scope.active = is_parent_scope_truthy && condition;
+                scope.branch_taken |= condition;
+            } else if self.else_regex.is_match(line) {
+                let is_parent_scope_truthy = scopes.len() < 2 || scopes[scopes.len() - 2].active;
+                let scope = scopes
+                    .last_mut()
+                    .ok_or(ProcessShaderError::ElseWithoutIf(line_number))?;
+                if scope.seen_else {
+                    return Err(ProcessShaderError::MultipleElses(line_number));
                 }
+                scope.seen_else = true;
+                scope.active = is_parent_scope_truthy && !scope.branch_taken;
+                scope.branch_taken = true;
             } else if self.endif_regex.is_match(line) {
-                scopes.pop();
-                if scopes.is_empty() {
-                    return Err(ProcessShaderError::TooManyEndIfs);
+                if scopes.pop().is_none() {
+                    return Err(ProcessShaderError::TooManyEndIfs(line_number));
                 }
-            } else if *scopes.last().unwrap() {
-                if let Some(cap) = SHADER_IMPORT_PROCESSOR
+            } else if active {
+                if let Some(cap) = SHADER_IMPORT_PROCESSOR.include_regex.captures(line) {
+                    let import = self.resolve_include(
+                        import_handles,
+                        shader.import_path(),
+                        cap.get(1).unwrap().as_str(),
+                    )?;
+                    if state.included.insert(import.clone()) {
+                        self.apply_import(
+                            import_handles,
+                            shaders,
+                            &import,
+                            shader,
+                            shader_defs,
+                            &mut final_string,
+                            state,
+                        )?;
+                    }
+                } else if let Some(cap) = SHADER_IMPORT_PROCESSOR
                     .import_asset_path_regex
                     .captures(line)
                 {
@@ -421,6 +1255,7 @@ impl ShaderProcessor {
                         shader,
                         shader_defs,
                         &mut final_string,
+                        state,
                     )?;
                 } else if let Some(cap) = SHADER_IMPORT_PROCESSOR
                     .import_custom_path_regex
@@ -434,6 +1269,7 @@ impl ShaderProcessor {
                         shader,
                         shader_defs,
                         &mut final_string,
+                        state,
                     )?;
                 } else if SHADER_IMPORT_PROCESSOR
                     .define_import_path_regex
@@ -441,14 +1277,27 @@ impl ShaderProcessor {
                 {
                     // ignore import path lines
                 } else {
-                    final_string.push_str(line);
+                    if matches!(shader.source, Source::Wgsl(_)) && line.contains("#{") {
+                        self.substitute_def_values(
+                            line,
+                            line_number,
+                            shader_defs,
+                            &mut final_string,
+                        )?;
+                    } else {
+                        final_string.push_str(line);
+                    }
                     final_string.push('\n');
+                    state.source_map.lines.push(Some(SourceLine {
+                        file: shader.import_path().cloned(),
+                        line: line_number,
+                    }));
                 }
             }
         }
 
-        if scopes.len() != 1 {
-            return Err(ProcessShaderError::NotEnoughEndIfs);
+        if let Some(scope) = scopes.last() {
+            return Err(ProcessShaderError::NotEnoughEndIfs(scope.line));
         }
 
         let processed_source = Cow::from(final_string);
@@ -456,12 +1305,57 @@ impl ShaderProcessor {
         match &shader.source {
             Source::Wgsl(_source) => Ok(ProcessedShader::Wgsl(processed_source)),
             Source::Glsl(_source, stage) => Ok(ProcessedShader::Glsl(processed_source, *stage)),
-            Source::SpirV(_source) => {
+            Source::SpirV(_) | Source::PrecompiledSpirV(_) => {
                 unreachable!("SpirV has early return");
             }
         }
     }
 
+    /// Replaces every `#{NAME}` in a WGSL `line` with the value of the shader def `NAME`. WGSL has no
+    /// preprocessor of its own, so this is how shader def values make it into WGSL shaders.
+    fn substitute_def_values(
+        &self,
+        line: &str,
+        line_number: usize,
+        shader_defs: &[String],
+        final_string: &mut String,
+    ) -> Result<(), ProcessShaderError> {
+        let mut last_end = 0;
+        for cap in self.def_value_regex.captures_iter(line) {
+            let whole = cap.get(0).unwrap();
+            let name = cap.get(1).unwrap().as_str();
+            let value = shader_defs
+                .iter()
+                .find(|def| shader_def_name(def) == name)
+                .and_then(|def| shader_def_value(def))
+                .ok_or_else(|| {
+                    ProcessShaderError::MissingShaderDefValue(name.to_string(), line_number)
+                })?;
+            final_string.push_str(&line[last_end..whole.start()]);
+            final_string.push_str(value);
+            last_end = whole.end();
+        }
+        final_string.push_str(&line[last_end..]);
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    /// Resolves the path of an `# include` directive of the `including` shader. Paths of shaders
+    /// created by [`Shader::from_glsl_struct`] found in `import_handles` are used as-is, any other
+    /// path is resolved by [`ShaderImportProcessor::resolve_include`].
+    pub fn resolve_include(
+        &self,
+        import_handles: &HashMap<ShaderImport, Handle<Shader>>,
+        including: Option<&ShaderImport>,
+        include: &str,
+    ) -> Result<ShaderImport, ProcessShaderError> {
+        let generated = ShaderImport::Custom(include.to_string());
+        if import_handles.contains_key(&generated) {
+            return Ok(generated);
+        }
+        SHADER_IMPORT_PROCESSOR.resolve_include(including, include)
+    }
+
     fn apply_import(
         &self,
         import_handles: &HashMap<ShaderImport, Handle<Shader>>,
@@ -470,13 +1364,19 @@ impl ShaderProcessor {
         shader: &Shader,
         shader_defs: &[String],
         final_string: &mut String,
+        state: &mut ProcessState,
     ) -> Result<(), ProcessShaderError> {
         let imported_shader = import_handles
             .get(import)
             .and_then(|handle| shaders.get(handle))
             .ok_or_else(|| ProcessShaderError::UnresolvedImport(import.clone()))?;
+        state.chain.push(import.clone());
+        if state.chain.len() > MAX_SHADER_IMPORT_DEPTH {
+            return Err(ProcessShaderError::ImportDepthExceeded(state.chain.clone()));
+        }
         let imported_processed =
-            self.process(imported_shader, shader_defs, shaders, import_handles)?;
+            self.process_with_state(imported_shader, shader_defs, shaders, import_handles, state)?;
+        state.chain.pop();
 
         match &shader.source {
             Source::Wgsl(_) => {
@@ -493,7 +1393,7 @@ impl ShaderProcessor {
                     return Err(ProcessShaderError::MismatchedImportFormat(import.clone()));
                 }
             }
-            Source::SpirV(_) => {
+            Source::SpirV(_) | Source::PrecompiledSpirV(_) => {
                 return Err(ProcessShaderError::ShaderFormatDoesNotSupportImports);
             }
         }
@@ -506,10 +1406,116 @@ impl ShaderProcessor {
 mod tests {
     use bevy_asset::{Handle, HandleUntyped};
     use bevy_reflect::TypeUuid;
-    use bevy_utils::HashMap;
+    use bevy_utils::{HashMap, HashSet};
     use naga::ShaderStage;
 
-    use crate::render_resource::{ProcessShaderError, Shader, ShaderImport, ShaderProcessor};
+    use crate::counting_allocator::allocations;
+    use crate::render_resource::{
+        CachedRenderPipelineId, ProcessShaderError, ProcessedShader, ReflectedBinding,
+        ReflectedVertexInput, RenderPipelineDescriptor, Shader, ShaderDefs, ShaderDefsKey,
+        ShaderImport, ShaderProcessor, ShaderStages, SortedShaderDefs, SourceLine,
+        SpecializationConstant, SpecializedRenderPipeline, SpecializedRenderPipelines,
+        MAX_SHADER_IMPORT_DEPTH,
+    };
+    use crate::test_util::pipeline_descriptor;
+
+    #[test]
+    fn shader_defs_keys() {
+        let key =
+            |shader_defs: &[&'static str]| ShaderDefs::from_iter(shader_defs.iter().copied()).key();
+        // the order and duplicates don't matter
+        assert_eq!(key(&["A", "B", "C"]), key(&["C", "A", "B"]));
+        assert_eq!(key(&["A", "B"]), key(&["B", "A", "B"]));
+        assert_ne!(key(&["A", "B"]), key(&["A"]));
+        assert_ne!(key(&["A"]), key(&[]));
+        // shader defs aren't simply concatenated
+        assert_ne!(key(&["AB"]), key(&["A", "B"]));
+        assert_ne!(key(&["MAX_LIGHTS 8"]), key(&["MAX_LIGHTS 16"]));
+        // the key is the same in every run
+        assert_eq!(key(&[]), ShaderDefsKey(0xcbf2_9ce4_8422_2325));
+
+        let sorted = ShaderDefs::from_iter(["B", "A", "B"]).sorted();
+        assert_eq!(sorted.shader_defs(), ["A", "B"]);
+        assert_eq!(sorted.key(), key(&["A", "B"]));
+        assert_eq!(sorted, ShaderDefs::from_iter(["A", "B"]).sorted());
+
+        // colliding keys don't make different shader defs equal
+        let collision = SortedShaderDefs {
+            key: sorted.key(),
+            shader_defs: vec!["C".to_string()],
+        };
+        assert_ne!(sorted, collision);
+    }
+
+    #[test]
+    fn few_static_shader_defs_do_not_allocate() {
+        let before = allocations();
+        for entity in 0..10_000 {
+            let mut shader_defs = ShaderDefs::new();
+            shader_defs.push("VERTEX_TANGENTS");
+            if entity % 2 == 0 {
+                shader_defs.push("SKINNED");
+            }
+            shader_defs.extend(["NO_ARRAY_TEXTURES_SUPPORT", "STANDARDMATERIAL_NORMAL_MAP"]);
+            assert!(shader_defs.len() >= 3);
+            // neither does the key specialized pipelines are looked up with
+            assert_ne!(shader_defs.key(), ShaderDefsKey(0));
+        }
+        assert_eq!(allocations() - before, 0);
+
+        // computed shader defs and more than four shader defs allocate
+        let mut shader_defs = ShaderDefs::from_iter(["A", "B", "C", "D"]);
+        let before = allocations();
+        shader_defs.push(format!("MAX_LIGHTS {}", 8));
+        assert_ne!(allocations() - before, 0);
+        assert_eq!(*shader_defs, ["A", "B", "C", "D", "MAX_LIGHTS 8"]);
+    }
+
+    /// Specializes pipelines for the shader defs of materials, like the material pipelines do.
+    struct MaterialPipeline;
+
+    impl SpecializedRenderPipeline for MaterialPipeline {
+        type Key = (bool, bool);
+
+        fn specialize(&self, (skinned, normal_map): Self::Key) -> RenderPipelineDescriptor {
+            let mut descriptor = pipeline_descriptor();
+            if skinned {
+                descriptor.push_shader_def("SKINNED", ShaderStages::VERTEX_FRAGMENT);
+            }
+            if normal_map {
+                descriptor.push_shader_def("STANDARDMATERIAL_NORMAL_MAP", ShaderStages::FRAGMENT);
+            }
+            descriptor
+        }
+    }
+
+    #[test]
+    fn queueing_a_prepared_frame_does_not_allocate() {
+        let mut pipelines = SpecializedRenderPipelines::<MaterialPipeline>::default();
+        let mut descriptors = Vec::new();
+        let mut queued = Vec::with_capacity(10_000);
+        let mut frame = |descriptors: &mut Vec<_>, queued: &mut Vec<_>| {
+            queued.clear();
+            for entity in 0..10_000 {
+                let key = (entity % 2 == 0, entity % 3 == 0);
+                queued.push(
+                    pipelines.specialize_with(&MaterialPipeline, key, |descriptor| {
+                        descriptors.push(descriptor);
+                        CachedRenderPipelineId(descriptors.len() - 1)
+                    }),
+                );
+            }
+        };
+
+        // the first frame specializes the pipelines of the four materials
+        frame(&mut descriptors, &mut queued);
+        let before = allocations();
+        frame(&mut descriptors, &mut queued);
+        assert_eq!(allocations() - before, 0);
+        assert_eq!(descriptors.len(), 4);
+        assert_eq!(queued.iter().collect::<HashSet<_>>().len(), 4);
+    }
+
     #[rustfmt::skip]
 const WGSL: &str = r"
 struct View {
@@ -754,47 +1760,177 @@ fn vertex(
 }
 ";
         let processor = ShaderProcessor::default();
-        let result = processor
-            .process(
-                &Shader::from_wgsl(WGSL_ELSE),
+        let result = processor
+            .process(
+                &Shader::from_wgsl(WGSL_ELSE),
+                &[],
+                &HashMap::default(),
+                &HashMap::default(),
+            )
+            .unwrap();
+        assert_eq!(result.get_wgsl_source().unwrap(), EXPECTED);
+    }
+
+    #[test]
+    fn process_shader_def_unclosed() {
+        #[rustfmt::skip]
+        const INPUT: &str = r"
+#ifdef FOO
+";
+        let processor = ShaderProcessor::default();
+        let result = processor.process(
+            &Shader::from_wgsl(INPUT),
+            &[],
+            &HashMap::default(),
+            &HashMap::default(),
+        );
+        assert_eq!(result, Err(ProcessShaderError::NotEnoughEndIfs(2)));
+    }
+
+    #[test]
+    fn process_shader_def_too_closed() {
+        #[rustfmt::skip]
+        const INPUT: &str = r"
+#endif
+";
+        let processor = ShaderProcessor::default();
+        let result = processor.process(
+            &Shader::from_wgsl(INPUT),
+            &[],
+            &HashMap::default(),
+            &HashMap::default(),
+        );
+        assert_eq!(result, Err(ProcessShaderError::TooManyEndIfs(2)));
+    }
+
+    #[test]
+    fn process_shader_def_elif() {
+        #[rustfmt::skip]
+        const INPUT: &str = r"
+#ifdef FOO
+fn foo() { }
+#elif defined(BAR)
+fn bar() { }
+#elif BAZ
+fn baz() { }
+#else
+fn fallback() { }
+#endif
+";
+        let processor = ShaderProcessor::default();
+        let process = |shader_defs: &[String]| {
+            let result = processor
+                .process(
+                    &Shader::from_wgsl(INPUT),
+                    shader_defs,
+                    &HashMap::default(),
+                    &HashMap::default(),
+                )
+                .unwrap();
+            result.get_wgsl_source().unwrap().to_string()
+        };
+
+        assert_eq!(process(&[]), "\nfn fallback() { }\n");
+        assert_eq!(
+            process(&["FOO".to_string(), "BAR".to_string()]),
+            "\nfn foo() { }\n"
+        );
+        assert_eq!(process(&["BAR".to_string()]), "\nfn bar() { }\n");
+        assert_eq!(
+            process(&["BAR".to_string(), "BAZ".to_string()]),
+            "\nfn bar() { }\n"
+        );
+        assert_eq!(process(&["BAZ".to_string()]), "\nfn baz() { }\n");
+    }
+
+    #[test]
+    fn process_nested_shader_def_elif() {
+        #[rustfmt::skip]
+        const INPUT: &str = r"
+#ifndef OUTER
+fn no_outer() { }
+#elif defined(INNER)
+#ifdef DEEP
+fn deep() { }
+#else
+fn inner() { }
+#endif
+#endif
+";
+        let processor = ShaderProcessor::default();
+        let process = |shader_defs: &[String]| {
+            let result = processor
+                .process(
+                    &Shader::from_wgsl(INPUT),
+                    shader_defs,
+                    &HashMap::default(),
+                    &HashMap::default(),
+                )
+                .unwrap();
+            result.get_wgsl_source().unwrap().to_string()
+        };
+
+        assert_eq!(process(&[]), "\nfn no_outer() { }\n");
+        assert_eq!(process(&["OUTER".to_string()]), "\n");
+        assert_eq!(
+            process(&["OUTER".to_string(), "INNER".to_string()]),
+            "\nfn inner() { }\n"
+        );
+        assert_eq!(
+            process(&["OUTER".to_string(), "INNER".to_string(), "DEEP".to_string()]),
+            "\nfn deep() { }\n"
+        );
+        assert_eq!(process(&["DEEP".to_string()]), "\nfn no_outer() { }\n");
+    }
+
+    #[test]
+    fn process_shader_def_unbalanced_else() {
+        let processor = ShaderProcessor::default();
+        let process = |input: &'static str| {
+            processor.process(
+                &Shader::from_wgsl(input),
                 &[],
                 &HashMap::default(),
                 &HashMap::default(),
             )
-            .unwrap();
-        assert_eq!(result.get_wgsl_source().unwrap(), EXPECTED);
-    }
+        };
 
-    #[test]
-    fn process_shader_def_unclosed() {
-        #[rustfmt::skip]
-        const INPUT: &str = r"
-#ifdef FOO
-";
-        let processor = ShaderProcessor::default();
-        let result = processor.process(
-            &Shader::from_wgsl(INPUT),
-            &[],
-            &HashMap::default(),
-            &HashMap::default(),
+        assert_eq!(
+            process("fn foo() { }\n#else\n"),
+            Err(ProcessShaderError::ElseWithoutIf(2))
+        );
+        assert_eq!(
+            process("#elif defined(FOO)\n"),
+            Err(ProcessShaderError::ElifWithoutIf(1))
+        );
+        assert_eq!(
+            process("#ifdef FOO\n#else\n#elif BAR\n#endif\n"),
+            Err(ProcessShaderError::ElifAfterElse(3))
+        );
+        assert_eq!(
+            process("#ifdef FOO\n#else\n#else\n#endif\n"),
+            Err(ProcessShaderError::MultipleElses(3))
         );
-        assert_eq!(result, Err(ProcessShaderError::NotEnoughEndIfs));
     }
 
     #[test]
-    fn process_shader_def_too_closed() {
+    fn process_shader_unknown_directive() {
         #[rustfmt::skip]
         const INPUT: &str = r"
-#endif
+#version 450
+#extension GL_EXT_samplerless_texture_functions : enable
+void foo() { }
 ";
         let processor = ShaderProcessor::default();
-        let result = processor.process(
-            &Shader::from_wgsl(INPUT),
-            &[],
-            &HashMap::default(),
-            &HashMap::default(),
-        );
-        assert_eq!(result, Err(ProcessShaderError::TooManyEndIfs));
+        let result = processor
+            .process(
+                &Shader::from_glsl(INPUT, ShaderStage::Vertex),
+                &[],
+                &HashMap::default(),
+                &HashMap::default(),
+            )
+            .unwrap();
+        assert_eq!(result.get_glsl_source().unwrap(), INPUT);
     }
 
     #[test]
@@ -888,6 +2024,343 @@ void bar() { }
         assert_eq!(result.get_glsl_source().unwrap(), EXPECTED);
     }
 
+    #[test]
+    fn process_glsl_shader_def_values() {
+        #[rustfmt::skip]
+        const INPUT: &str = r"#version 450
+layout(location = 0) out vec4 o_Target;
+layout(set = 0, binding = 0) uniform Lights {
+    vec4 colors[MAX_LIGHTS];
+};
+void main() {
+#ifdef RED
+    o_Target = vec4(1.0, 0.0, 0.0, 1.0);
+#else
+    o_Target = colors[0];
+#endif
+}
+";
+        #[rustfmt::skip]
+        const EXPECTED: &str = r"#version 450
+#define MAX_LIGHTS 8
+#define RED
+layout(location = 0) out vec4 o_Target;
+layout(set = 0, binding = 0) uniform Lights {
+    vec4 colors[MAX_LIGHTS];
+};
+void main() {
+    o_Target = vec4(1.0, 0.0, 0.0, 1.0);
+}
+";
+        let processor = ShaderProcessor::default();
+        let result = processor
+            .process(
+                &Shader::from_glsl(INPUT, ShaderStage::Fragment),
+                &["MAX_LIGHTS 8".to_string(), "RED".to_string()],
+                &HashMap::default(),
+                &HashMap::default(),
+            )
+            .unwrap();
+        assert_eq!(result.get_glsl_source().unwrap(), EXPECTED);
+        assert!(result.reflect().is_ok());
+
+        let result = processor
+            .process(
+                &Shader::from_glsl(INPUT, ShaderStage::Fragment),
+                &["MAX_LIGHTS 4".to_string()],
+                &HashMap::default(),
+                &HashMap::default(),
+            )
+            .unwrap();
+        assert!(result
+            .get_glsl_source()
+            .unwrap()
+            .starts_with("#version 450\n#define MAX_LIGHTS 4\n"));
+        assert!(result.reflect().is_ok());
+    }
+
+    #[test]
+    fn process_wgsl_shader_def_values() {
+        #[rustfmt::skip]
+        const INPUT: &str = r"
+struct Lights {
+    colors: array<vec4<f32>, #{MAX_LIGHTS}>;
+    intensity: f32;
+};
+let SCALE: f32 = #{ SCALE };
+";
+        #[rustfmt::skip]
+        const EXPECTED: &str = r"
+struct Lights {
+    colors: array<vec4<f32>, 8u>;
+    intensity: f32;
+};
+let SCALE: f32 = 0.5;
+";
+        let processor = ShaderProcessor::default();
+        let result = processor
+            .process(
+                &Shader::from_wgsl(INPUT),
+                &["MAX_LIGHTS 8u".to_string(), "  SCALE\t0.5 ".to_string()],
+                &HashMap::default(),
+                &HashMap::default(),
+            )
+            .unwrap();
+        assert_eq!(result.get_wgsl_source().unwrap(), EXPECTED);
+
+        let result = processor.process(
+            &Shader::from_wgsl(INPUT),
+            &["MAX_LIGHTS".to_string()],
+            &HashMap::default(),
+            &HashMap::default(),
+        );
+        assert_eq!(
+            result,
+            Err(ProcessShaderError::MissingShaderDefValue(
+                "MAX_LIGHTS".to_string(),
+                3
+            ))
+        );
+    }
+
+    #[test]
+    fn process_precompiled_spirv_variants() {
+        let shader = Shader::from_spirv_variants([
+            (vec![], vec![0u8, 1, 2, 3]),
+            (
+                vec!["SKINNED".to_string(), "VERTEX_TANGENTS".to_string()],
+                vec![4u8, 5, 6, 7],
+            ),
+        ]);
+        let processor = ShaderProcessor::default();
+        let process = |shader_defs: &[String]| {
+            processor.process(
+                &shader,
+                shader_defs,
+                &HashMap::default(),
+                &HashMap::default(),
+            )
+        };
+
+        assert_eq!(
+            process(&[]),
+            Ok(ProcessedShader::SpirV(vec![0u8, 1, 2, 3].into()))
+        );
+        assert_eq!(
+            process(&["VERTEX_TANGENTS".to_string(), "SKINNED".to_string()]),
+            Ok(ProcessedShader::SpirV(vec![4u8, 5, 6, 7].into()))
+        );
+        assert_eq!(
+            process(&["SKINNED".to_string()]),
+            Err(ProcessShaderError::MissingSpirVVariant(vec![
+                "SKINNED".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn specialize_spirv_constants() {
+        let spirv = |words: &[u32]| -> ProcessedShader {
+            ProcessedShader::SpirV(words.iter().flat_map(|word| word.to_le_bytes()).collect())
+        };
+        let module = |bool_opcode: u32, uint_value: u32| {
+            spirv(&[
+                // header
+                0x0723_0203,
+                0x0001_0000,
+                0,
+                9,
+                0,
+                // OpDecorate %5 SpecId 3
+                4 << 16 | 71,
+                5,
+                1,
+                3,
+                // OpDecorate %6 SpecId 7
+                4 << 16 | 71,
+                6,
+                1,
+                7,
+                // OpDecorate %8 SpecId 9
+                4 << 16 | 71,
+                8,
+                1,
+                9,
+                // OpTypeBool %2
+                2 << 16 | 20,
+                2,
+                // OpTypeInt %3 32 0
+                4 << 16 | 21,
+                3,
+                32,
+                0,
+                // OpTypeFloat %4 32
+                3 << 16 | 22,
+                4,
+                32,
+                // OpSpecConstantTrue / OpSpecConstantFalse %bool %5
+                3 << 16 | bool_opcode,
+                2,
+                5,
+                // OpSpecConstant %uint %6 uint_value
+                4 << 16 | 50,
+                3,
+                6,
+                uint_value,
+                // OpSpecConstant %float %8 1.0
+                4 << 16 | 50,
+                4,
+                8,
+                1.0f32.to_bits(),
+            ])
+        };
+
+        assert_eq!(
+            module(48, 16).specialize(&[
+                SpecializationConstant::new(3, false),
+                SpecializationConstant::new(7, 64u32),
+            ]),
+            Ok(module(49, 64))
+        );
+        assert_eq!(
+            module(48, 16).specialize(&[SpecializationConstant::new(11, true)]),
+            Err(ProcessShaderError::MissingSpecializationConstant(11))
+        );
+        assert_eq!(
+            module(48, 16).specialize(&[SpecializationConstant::new(3, 1u32)]),
+            Err(ProcessShaderError::SpecializationConstantTypeMismatch(3))
+        );
+        // the signedness and kind of numeric constants have to match as well
+        assert_eq!(
+            module(48, 16).specialize(&[SpecializationConstant::new(7, -1i32)]),
+            Err(ProcessShaderError::SpecializationConstantTypeMismatch(7))
+        );
+        assert_eq!(
+            module(48, 16).specialize(&[SpecializationConstant::new(9, 2u32)]),
+            Err(ProcessShaderError::SpecializationConstantTypeMismatch(9))
+        );
+        assert_eq!(
+            ProcessedShader::Wgsl("".into()).specialize(&[SpecializationConstant::new(3, true)]),
+            Err(ProcessShaderError::ShaderFormatDoesNotSupportSpecializationConstants)
+        );
+    }
+
+    #[test]
+    fn reflect_bindings_and_vertex_inputs() {
+        let processor = ShaderProcessor::default();
+        let result = processor
+            .process(
+                &Shader::from_wgsl(WGSL),
+                &["TEXTURE".to_string()],
+                &HashMap::default(),
+                &HashMap::default(),
+            )
+            .unwrap();
+        let reflection = result.reflect().unwrap();
+
+        assert_eq!(
+            reflection.entry_points().collect::<Vec<_>>(),
+            vec![("vertex", ShaderStage::Vertex)]
+        );
+        assert_eq!(
+            reflection.bindings(),
+            vec![
+                ReflectedBinding {
+                    name: Some("view".to_string()),
+                    group: 0,
+                    binding: 0,
+                },
+                ReflectedBinding {
+                    name: Some("sprite_texture".to_string()),
+                    group: 1,
+                    binding: 0,
+                },
+            ]
+        );
+        assert_eq!(
+            reflection.vertex_inputs("vertex"),
+            Some(vec![
+                ReflectedVertexInput {
+                    name: Some("vertex_position".to_string()),
+                    location: 0,
+                },
+                ReflectedVertexInput {
+                    name: Some("vertex_uv".to_string()),
+                    location: 1,
+                },
+            ])
+        );
+        assert_eq!(reflection.vertex_inputs("fragment"), None);
+        // the texture is declared, but not used by the vertex entry point
+        assert_eq!(
+            reflection.bindings_used_by("vertex"),
+            Some(vec![ReflectedBinding {
+                name: Some("view".to_string()),
+                group: 0,
+                binding: 0,
+            }])
+        );
+        assert_eq!(reflection.bindings_used_by("fragment"), None);
+    }
+
+    #[test]
+    fn reflect_spirv_entry_points_and_bindings() {
+        // compiled from `test_shaders/sprite.wgsl` by naga, with debug names
+        let shader = Shader::from_spirv(&include_bytes!("test_shaders/sprite.spv")[..]);
+        let result = ShaderProcessor::default()
+            .process(&shader, &[], &HashMap::default(), &HashMap::default())
+            .unwrap();
+        let reflection = result.reflect().unwrap();
+
+        assert_eq!(
+            reflection.entry_points().collect::<Vec<_>>(),
+            vec![
+                ("vertex", ShaderStage::Vertex),
+                ("fragment", ShaderStage::Fragment)
+            ]
+        );
+        assert_eq!(
+            reflection.bindings(),
+            vec![
+                ReflectedBinding {
+                    name: Some("view".to_string()),
+                    group: 0,
+                    binding: 0,
+                },
+                ReflectedBinding {
+                    name: Some("sprite_texture".to_string()),
+                    group: 1,
+                    binding: 0,
+                },
+                ReflectedBinding {
+                    name: Some("sprite_sampler".to_string()),
+                    group: 1,
+                    binding: 1,
+                },
+            ]
+        );
+        // the arguments of the entry points aren't named in the SPIR-V
+        assert_eq!(
+            reflection.vertex_inputs("vertex"),
+            Some(vec![
+                ReflectedVertexInput {
+                    name: None,
+                    location: 0,
+                },
+                ReflectedVertexInput {
+                    name: None,
+                    location: 1,
+                },
+            ])
+        );
+        assert_eq!(
+            reflection
+                .bindings_used_by("fragment")
+                .map(|bindings| bindings.len()),
+            Some(2)
+        );
+    }
+
     #[test]
     fn process_nested_shader_def_outer_defined_inner_not() {
         #[rustfmt::skip]
@@ -1234,6 +2707,250 @@ fn in_main() { }
         assert_eq!(result.get_wgsl_source().unwrap(), EXPECTED);
     }
 
+    #[test]
+    fn process_include_relative_once() {
+        #[rustfmt::skip]
+        const COMMON: &str = r"
+fn common() { }
+";
+        #[rustfmt::skip]
+        const LIGHTING: &str = r#"
+#include "../common.wgsl"
+fn lighting() { }
+"#;
+        #[rustfmt::skip]
+        const INPUT: &str = r#"
+#include "lighting/lighting.wgsl"
+#include "common.wgsl"
+fn main() { }
+"#;
+        #[rustfmt::skip]
+        const EXPECTED: &str = r"
+
+
+fn common() { }
+fn lighting() { }
+fn main() { }
+";
+        let processor = ShaderProcessor::default();
+        let mut shaders = HashMap::default();
+        let mut import_handles = HashMap::default();
+        {
+            let common_handle = Handle::<Shader>::default();
+            shaders.insert(common_handle.clone_weak(), Shader::from_wgsl(COMMON));
+            import_handles.insert(
+                ShaderImport::AssetPath("shaders/common.wgsl".to_string()),
+                common_handle.clone_weak(),
+            );
+        }
+        {
+            let lighting_handle = HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 1).typed();
+            let mut lighting = Shader::from_wgsl(LIGHTING);
+            lighting.import_path = Some(ShaderImport::AssetPath(
+                "shaders/lighting/lighting.wgsl".to_string(),
+            ));
+            shaders.insert(lighting_handle.clone_weak(), lighting);
+            import_handles.insert(
+                ShaderImport::AssetPath("shaders/lighting/lighting.wgsl".to_string()),
+                lighting_handle.clone_weak(),
+            );
+        }
+        let mut shader = Shader::from_wgsl(INPUT);
+        shader.import_path = Some(ShaderImport::AssetPath("shaders/main.wgsl".to_string()));
+        assert_eq!(
+            shader.includes().collect::<Vec<_>>(),
+            vec!["lighting/lighting.wgsl", "common.wgsl"]
+        );
+        assert_eq!(
+            processor.resolve_include(&import_handles, shader.import_path(), "common.wgsl"),
+            Ok(ShaderImport::AssetPath("shaders/common.wgsl".to_string()))
+        );
+
+        let result = processor
+            .process(&shader, &[], &shaders, &import_handles)
+            .unwrap();
+        assert_eq!(result.get_wgsl_source().unwrap(), EXPECTED);
+    }
+
+    #[test]
+    fn process_relative_include_from_custom_import() {
+        #[rustfmt::skip]
+        const INPUT: &str = r#"
+#define_import_path my_crate::lighting
+#include "../common.wgsl"
+fn lighting() { }
+"#;
+        let processor = ShaderProcessor::default();
+        let shader = Shader::from_wgsl(INPUT);
+        assert!(shader.imports().next().is_none());
+
+        let result = processor.process(&shader, &[], &HashMap::default(), &HashMap::default());
+        assert_eq!(
+            result,
+            Err(ProcessShaderError::RelativeIncludeFromCustomImport {
+                include: "../common.wgsl".to_string(),
+                import_path: "my_crate::lighting".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn include_generated_glsl_struct() {
+        use crate::render_resource::glsl::GlslStruct;
+        use bevy_math::Vec4;
+
+        #[allow(dead_code)]
+        #[derive(GlslStruct)]
+        #[glsl(include_path = "test/generated_material.glsl")]
+        struct GeneratedMaterial {
+            color: Vec4,
+            roughness: f32,
+        }
+
+        const INPUT: &str = r#"
+#version 450
+#include "test/generated_material.glsl"
+layout(location = 0) out vec4 o_Target;
+layout(set = 0, binding = 0) uniform GeneratedMaterial_block {
+    GeneratedMaterial material;
+};
+void main() {
+    o_Target = material.color * material.roughness;
+}
+"#;
+        let processor = ShaderProcessor::default();
+        let mut shaders = HashMap::default();
+        let mut import_handles = HashMap::default();
+        let mut shader = Shader::from_glsl(INPUT, ShaderStage::Fragment);
+        shader.import_path = Some(ShaderImport::AssetPath("shaders/main.frag".to_string()));
+        // without the generated shader, the include is an ordinary relative path
+        assert_eq!(
+            processor.resolve_include(
+                &import_handles,
+                shader.import_path(),
+                "test/generated_material.glsl"
+            ),
+            Ok(ShaderImport::AssetPath(
+                "shaders/test/generated_material.glsl".to_string()
+            ))
+        );
+
+        let generated = Shader::from_glsl_struct::<GeneratedMaterial>();
+        let generated_path = ShaderImport::Custom("test/generated_material.glsl".to_string());
+        assert_eq!(generated.import_path(), Some(&generated_path));
+        {
+            let generated_handle = Handle::<Shader>::default();
+            shaders.insert(generated_handle.clone_weak(), generated);
+            import_handles.insert(generated_path.clone(), generated_handle.clone_weak());
+        }
+
+        // the generated path isn't relative to the including shader
+        assert_eq!(
+            processor.resolve_include(
+                &import_handles,
+                shader.import_path(),
+                "test/generated_material.glsl"
+            ),
+            Ok(generated_path)
+        );
+
+        let result = processor
+            .process(&shader, &[], &shaders, &import_handles)
+            .unwrap();
+        assert!(result
+            .get_glsl_source()
+            .unwrap()
+            .contains(&GeneratedMaterial::glsl_definition()));
+        assert!(result.reflect().is_ok());
+    }
+
+    #[test]
+    fn process_source_map() {
+        #[rustfmt::skip]
+        const FOO: &str = r"void foo() { }
+";
+        #[rustfmt::skip]
+        const INPUT: &str = r"#version 450
+#import FOO
+#ifdef B
+void b() { }
+#endif
+void main() { }
+";
+        let processor = ShaderProcessor::default();
+        let mut shaders = HashMap::default();
+        let mut import_handles = HashMap::default();
+        let foo_handle = Handle::<Shader>::default();
+        shaders.insert(
+            foo_handle.clone_weak(),
+            Shader::from_glsl(FOO, ShaderStage::Fragment).with_import_path("FOO"),
+        );
+        import_handles.insert(
+            ShaderImport::Custom("FOO".to_string()),
+            foo_handle.clone_weak(),
+        );
+        let mut shader = Shader::from_glsl(INPUT, ShaderStage::Fragment);
+        shader.import_path = Some(ShaderImport::AssetPath("shaders/main.frag".to_string()));
+
+        let (result, source_map) = processor
+            .process_with_source_map(&shader, &["A".to_string()], &shaders, &import_handles)
+            .unwrap();
+        let source = result.get_glsl_source().unwrap();
+        assert_eq!(
+            source,
+            "#version 450\n#define A\nvoid foo() { }\nvoid main() { }\n"
+        );
+
+        let main_line = |line| {
+            Some(SourceLine {
+                file: Some(ShaderImport::AssetPath("shaders/main.frag".to_string())),
+                line,
+            })
+        };
+        assert_eq!(source_map.get(1).cloned(), main_line(1));
+        assert_eq!(source_map.get(2), None);
+        assert_eq!(
+            source_map.get(3).cloned(),
+            Some(SourceLine {
+                file: Some(ShaderImport::Custom("FOO".to_string())),
+                line: 1,
+            })
+        );
+        assert_eq!(source_map.get(4).cloned(), main_line(6));
+        assert_eq!(source_map.get(5), None);
+        assert_eq!(
+            source_map
+                .get_by_offset(source, source.find("main").unwrap())
+                .cloned(),
+            main_line(6)
+        );
+    }
+
+    #[test]
+    fn process_import_cycle() {
+        #[rustfmt::skip]
+        const FOO: &str = r"
+#import FOO
+";
+        let processor = ShaderProcessor::default();
+        let mut shaders = HashMap::default();
+        let mut import_handles = HashMap::default();
+        let foo_handle = Handle::<Shader>::default();
+        shaders.insert(foo_handle.clone_weak(), Shader::from_wgsl(FOO));
+        import_handles.insert(
+            ShaderImport::Custom("FOO".to_string()),
+            foo_handle.clone_weak(),
+        );
+        let result = processor.process(&Shader::from_wgsl(FOO), &[], &shaders, &import_handles);
+        assert_eq!(
+            result,
+            Err(ProcessShaderError::ImportDepthExceeded(vec![
+                ShaderImport::Custom("FOO".to_string());
+                MAX_SHADER_IMPORT_DEPTH + 1
+            ]))
+        );
+    }
+
     #[test]
     fn process_import_in_ifdef() {
         #[rustfmt::skip]
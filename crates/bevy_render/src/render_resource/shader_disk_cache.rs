@@ -0,0 +1,302 @@
+use crate::render_resource::{AsModuleDescriptorError, ProcessedShader, ShaderDefsKey};
+use bevy_utils::tracing::debug;
+use naga::ShaderStage;
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+use wgpu::{ShaderModuleDescriptor, ShaderSource};
+
+/// Identifies the cache file layout. Bump this whenever the layout or the contents of cache
+/// entries change, and whenever naga is upgraded, so that stale entries are recompiled instead of
+/// being loaded.
+const SHADER_DISK_CACHE_VERSION: u32 = 2;
+const SHADER_DISK_CACHE_MAGIC: &[u8; 8] = b"BEVYSHDR";
+const SHADER_DISK_CACHE_HEADER_LEN: usize = SHADER_DISK_CACHE_MAGIC.len() + 4 + 8;
+
+/// Persists the SPIR-V compiled from GLSL shaders on disk, so that each permutation of a shader
+/// only has to be compiled once instead of on every startup.
+///
+/// Entries are keyed by a hash of the preprocessed shader source, its stage and its sorted shader
+/// defs. Entries that can't be read, are corrupted or were written by another version of the cache
+/// are ignored and replaced by a freshly compiled shader.
+///
+/// To enable the cache, insert this as a resource before adding the `RenderPlugin`.
+#[derive(Debug, Clone)]
+pub struct ShaderDiskCache {
+    directory: PathBuf,
+}
+
+impl ShaderDiskCache {
+    /// Creates a cache storing its entries in `directory`, which is created if it does not exist.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Computes the cache key of a shader `source` of the given `stage`, compiled with
+    /// `shader_defs`. The order of the shader defs does not matter.
+    pub fn key(source: &str, stage: ShaderStage, shader_defs: &[String]) -> u64 {
+        let shader_defs = ShaderDefsKey::new(shader_defs.iter().map(String::as_str));
+
+        // This has to be stable across runs and compiler versions, which rules out `DefaultHasher`.
+        let mut hash = Fnv1a::default();
+        hash.write(&SHADER_DISK_CACHE_VERSION.to_le_bytes());
+        hash.write(&[stage as u8]);
+        hash.write(&(source.len() as u64).to_le_bytes());
+        hash.write(source.as_bytes());
+        hash.write(&shader_defs.0.to_le_bytes());
+        hash.0
+    }
+
+    /// Returns the cached entry for `key`, or calls `compile` and stores its result in the cache.
+    pub fn get_or_compile<E>(
+        &self,
+        key: u64,
+        compile: impl FnOnce() -> Result<Vec<u32>, E>,
+    ) -> Result<Vec<u32>, E> {
+        if let Some(cached) = self.load(key) {
+            return Ok(cached);
+        }
+        let compiled = compile()?;
+        self.store(key, &compiled);
+        Ok(compiled)
+    }
+
+    /// Creates the [`ShaderModuleDescriptor`] of a `processed` shader like
+    /// [`ProcessedShader::get_module_descriptor`], but compiles GLSL shaders to SPIR-V, and only
+    /// if they are not yet in the cache.
+    pub fn get_module_descriptor<'a>(
+        &self,
+        processed: &'a ProcessedShader,
+        shader_defs: &[String],
+    ) -> Result<ShaderModuleDescriptor<'a>, AsModuleDescriptorError> {
+        let (source, stage) = match processed {
+            ProcessedShader::Glsl(source, stage) => (source, *stage),
+            _ => return processed.get_module_descriptor(),
+        };
+
+        let key = Self::key(source, stage, shader_defs);
+        let spirv = self.get_or_compile(key, || {
+            let reflection = processed.reflect()?;
+            Ok::<_, AsModuleDescriptorError>(reflection.get_spirv()?)
+        })?;
+
+        Ok(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::SpirV(Cow::Owned(spirv)),
+        })
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        self.directory.join(format!("{:016x}.shader", key))
+    }
+
+    fn load(&self, key: u64) -> Option<Vec<u32>> {
+        let bytes = fs::read(self.entry_path(key)).ok()?;
+        if bytes.len() < SHADER_DISK_CACHE_HEADER_LEN
+            || &bytes[..SHADER_DISK_CACHE_MAGIC.len()] != SHADER_DISK_CACHE_MAGIC
+        {
+            debug!("ignoring invalid shader cache entry {:016x}", key);
+            return None;
+        }
+
+        let (version, rest) = bytes[SHADER_DISK_CACHE_MAGIC.len()..].split_at(4);
+        let (len, payload) = rest.split_at(8);
+        if u32::from_le_bytes(version.try_into().ok()?) != SHADER_DISK_CACHE_VERSION {
+            debug!("ignoring outdated shader cache entry {:016x}", key);
+            return None;
+        }
+        if u64::from_le_bytes(len.try_into().ok()?) != payload.len() as u64
+            || payload.len() % 4 != 0
+        {
+            debug!("ignoring truncated shader cache entry {:016x}", key);
+            return None;
+        }
+
+        Some(
+            payload
+                .chunks_exact(4)
+                .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    fn store(&self, key: u64, compiled: &[u32]) {
+        let len = compiled.len() * 4;
+        let mut bytes = Vec::with_capacity(SHADER_DISK_CACHE_HEADER_LEN + len);
+        bytes.extend_from_slice(SHADER_DISK_CACHE_MAGIC);
+        bytes.extend_from_slice(&SHADER_DISK_CACHE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(len as u64).to_le_bytes());
+        for word in compiled {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+
+        // write to a temporary file first, so that an interrupted write never leaves a partial
+        // entry behind
+        let path = self.entry_path(key);
+        let temp_path = path.with_extension("tmp");
+        let result = fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(&temp_path, &bytes))
+            .and_then(|_| fs::rename(&temp_path, &path));
+        if let Err(err) = result {
+            debug!("failed to write shader cache entry {:?}: {}", path, err);
+        }
+    }
+}
+
+/// The 64 bit FNV-1a hash function.
+pub(crate) struct Fnv1a(pub(crate) u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShaderDiskCache;
+    use crate::render_resource::ProcessedShader;
+    use naga::ShaderStage;
+    use std::{cell::Cell, convert::Infallible, fs};
+    use wgpu::ShaderSource;
+
+    fn temp_cache(name: &str) -> ShaderDiskCache {
+        let directory = std::env::temp_dir().join(format!(
+            "bevy_shader_disk_cache_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        ShaderDiskCache::new(directory)
+    }
+
+    #[test]
+    fn second_compile_is_cached() {
+        let cache = temp_cache("second_compile_is_cached");
+        let key = ShaderDiskCache::key(
+            "void main() {}",
+            ShaderStage::Fragment,
+            &["B".to_string(), "A".to_string()],
+        );
+        let compiled = Cell::new(0);
+        let compile = || {
+            compiled.set(compiled.get() + 1);
+            Ok::<_, Infallible>(vec![0x0723_0203, 42])
+        };
+
+        assert_eq!(
+            cache.get_or_compile(key, compile).unwrap(),
+            [0x0723_0203, 42]
+        );
+        assert_eq!(
+            cache.get_or_compile(key, compile).unwrap(),
+            [0x0723_0203, 42]
+        );
+        assert_eq!(compiled.get(), 1);
+
+        // the order of the shader defs doesn't matter
+        assert_eq!(
+            key,
+            ShaderDiskCache::key(
+                "void main() {}",
+                ShaderStage::Fragment,
+                &["A".to_string(), "B".to_string()]
+            )
+        );
+        assert_ne!(
+            key,
+            ShaderDiskCache::key("void main() {}", ShaderStage::Fragment, &["A".to_string()])
+        );
+
+        let _ = fs::remove_dir_all(cache.directory());
+    }
+
+    #[test]
+    fn corrupted_entry_is_recompiled() {
+        let cache = temp_cache("corrupted_entry_is_recompiled");
+        let key = ShaderDiskCache::key("void main() {}", ShaderStage::Vertex, &[]);
+        fs::create_dir_all(cache.directory()).unwrap();
+        fs::write(cache.entry_path(key), b"BEVYSHDR\x01").unwrap();
+
+        let compiled = Cell::new(0);
+        let compile = || {
+            compiled.set(compiled.get() + 1);
+            Ok::<_, Infallible>(vec![0x0723_0203, 42])
+        };
+        assert_eq!(
+            cache.get_or_compile(key, compile).unwrap(),
+            [0x0723_0203, 42]
+        );
+        assert_eq!(
+            cache.get_or_compile(key, compile).unwrap(),
+            [0x0723_0203, 42]
+        );
+        assert_eq!(compiled.get(), 1);
+
+        // entries written by another version of the cache are ignored
+        let mut bytes = fs::read(cache.entry_path(key)).unwrap();
+        bytes[8] = bytes[8].wrapping_add(1);
+        fs::write(cache.entry_path(key), bytes).unwrap();
+        assert_eq!(
+            cache.get_or_compile(key, compile).unwrap(),
+            [0x0723_0203, 42]
+        );
+        assert_eq!(compiled.get(), 2);
+
+        let _ = fs::remove_dir_all(cache.directory());
+    }
+
+    #[test]
+    fn glsl_shaders_are_cached_as_spirv() {
+        const FRAGMENT: &str = r"
+#version 450
+layout(location = 0) out vec4 o_Target;
+void main() {
+    o_Target = vec4(1.0, 0.0, 1.0, 1.0);
+}
+";
+        let cache = temp_cache("glsl_shaders_are_cached_as_spirv");
+        let processed = ProcessedShader::Glsl(FRAGMENT.into(), ShaderStage::Fragment);
+        let shader_defs = ["A".to_string()];
+
+        let compiled = match cache.get_module_descriptor(&processed, &shader_defs) {
+            Ok(descriptor) => match descriptor.source {
+                ShaderSource::SpirV(spirv) => spirv.into_owned(),
+                _ => panic!("expected the shader to be compiled to SPIR-V"),
+            },
+            Err(err) => panic!("failed to compile the shader: {}", err),
+        };
+        let key = ShaderDiskCache::key(FRAGMENT, ShaderStage::Fragment, &shader_defs);
+        assert_eq!(cache.load(key).as_deref(), Some(&compiled[..]));
+
+        // the cached entry is a valid module with the entry point of the shader
+        let bytes = compiled
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+        let module =
+            naga::front::spv::parse_u8_slice(&bytes, &naga::front::spv::Options::default())
+                .unwrap();
+        assert_eq!(module.entry_points.len(), 1);
+        assert_eq!(module.entry_points[0].stage, ShaderStage::Fragment);
+
+        let _ = fs::remove_dir_all(cache.directory());
+    }
+}
@@ -0,0 +1,423 @@
+use crate::{
+    render_resource::Buffer,
+    renderer::{FramesInFlight, RenderDevice, RenderQueue, RenderResourceLimits},
+};
+use bevy_ecs::system::{Res, ResMut};
+use parking_lot::Mutex;
+use std::{fmt, num::NonZeroU64, ops::Range, sync::Arc};
+use wgpu::{BindingResource, BufferAddress, BufferBinding, BufferDescriptor, BufferUsages};
+
+/// Configures the [`UniformAllocator`], which is read from the app world when the `RenderPlugin`
+/// is built.
+#[derive(Debug, Clone)]
+pub struct UniformAllocatorSettings {
+    /// The size of the buffers the uniforms are allocated from in bytes. Uniforms that are larger
+    /// than a page get a page of their own.
+    pub page_size: BufferAddress,
+}
+
+impl Default for UniformAllocatorSettings {
+    fn default() -> Self {
+        Self { page_size: 1 << 20 }
+    }
+}
+
+/// How full the pages of a [`UniformAllocator`] are. The difference between the allocated bytes and
+/// the bytes in use is either fragmented or left for new allocations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UniformAllocatorStats {
+    /// The number of buffers allocated by the allocator.
+    pub page_count: usize,
+    /// The total size of the pages in bytes.
+    pub bytes_allocated: BufferAddress,
+    /// The size of the live allocations, including their padding, in bytes.
+    pub bytes_in_use: BufferAddress,
+    /// The number of live allocations.
+    pub allocation_count: usize,
+}
+
+/// Allocates small uniforms that live for a while, like the uniforms of materials, from a few
+/// large buffers instead of creating a buffer for each of them.
+///
+/// Devices allocate memory in pages, so tiny buffers waste most of their memory, and some drivers
+/// limit the number of allocations. The allocations are aligned to the
+/// `min_uniform_buffer_offset_alignment` of the device and bound with their offset and size, see
+/// [`UniformAllocation::binding`]. Dropping the last clone of an allocation frees its range once
+/// the GPU finished the frames that may still read it.
+///
+/// Pages are never freed, since materials usually live for the whole app and the freed ranges of
+/// the others are reused by new allocations.
+pub struct UniformAllocator {
+    pages: Pages,
+    buffers: Vec<Buffer>,
+    /// The ranges of the dropped allocations, which are freed by the
+    /// [`UniformAllocator::recycle_system`].
+    dropped: Arc<Mutex<Vec<(usize, Range<BufferAddress>)>>>,
+    /// The ranges that were dropped in a frame the GPU may not have finished yet.
+    in_flight: Vec<(u64, usize, Range<BufferAddress>)>,
+}
+
+impl UniformAllocator {
+    /// Creates an allocator with pages of `page_size` bytes, with allocations that are aligned
+    /// according to the `limits` of the device.
+    pub fn new(limits: &RenderResourceLimits, page_size: BufferAddress) -> Self {
+        Self {
+            pages: Pages::new(
+                page_size,
+                limits.min_uniform_buffer_offset_alignment as BufferAddress,
+            ),
+            buffers: Vec::new(),
+            dropped: Default::default(),
+            in_flight: Vec::new(),
+        }
+    }
+
+    /// Allocates a range of at least `size` bytes, which is freed once it is dropped.
+    pub fn allocate(
+        &mut self,
+        render_device: &RenderDevice,
+        size: BufferAddress,
+    ) -> UniformAllocation {
+        let (page, range) = self.pages.allocate(size);
+        if page == self.buffers.len() {
+            self.buffers
+                .push(render_device.create_buffer(&BufferDescriptor {
+                    label: Some("uniform_allocator_page"),
+                    size: self.pages.pages[page].size,
+                    usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }));
+        }
+        UniformAllocation(Arc::new(Allocation {
+            buffer: self.buffers[page].clone(),
+            page,
+            range,
+            size,
+            dropped: self.dropped.clone(),
+        }))
+    }
+
+    /// Allocates a range holding the `contents`, see [`UniformAllocator::allocate`].
+    pub fn allocate_with_data(
+        &mut self,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        contents: &[u8],
+    ) -> UniformAllocation {
+        let allocation = self.allocate(render_device, contents.len() as BufferAddress);
+        allocation.write(render_queue, contents);
+        allocation
+    }
+
+    pub fn stats(&self) -> UniformAllocatorStats {
+        self.pages.stats()
+    }
+
+    /// Frees the ranges of the dropped allocations once the GPU finished the frame they were
+    /// dropped in.
+    pub(crate) fn recycle_system(mut allocator: ResMut<Self>, frames: Res<FramesInFlight>) {
+        allocator.recycle(frames.frame(), frames.completed_frames());
+    }
+
+    fn recycle(&mut self, frame: u64, completed_frames: u64) {
+        for (page, range) in self.dropped.lock().drain(..) {
+            self.in_flight.push((frame, page, range));
+        }
+        let pages = &mut self.pages;
+        self.in_flight.retain(|(dropped_in, page, range)| {
+            let done = *dropped_in < completed_frames;
+            if done {
+                pages.free(*page, range.clone());
+            }
+            !done
+        });
+    }
+}
+
+/// A range of a buffer of the [`UniformAllocator`], which is freed once the last clone is dropped.
+#[derive(Clone)]
+pub struct UniformAllocation(Arc<Allocation>);
+
+struct Allocation {
+    buffer: Buffer,
+    page: usize,
+    /// The allocated range, which is padded to the alignment.
+    range: Range<BufferAddress>,
+    /// The requested size.
+    size: BufferAddress,
+    dropped: Arc<Mutex<Vec<(usize, Range<BufferAddress>)>>>,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.dropped.lock().push((self.page, self.range.clone()));
+    }
+}
+
+impl UniformAllocation {
+    /// Returns the buffer the allocation is a part of.
+    #[inline]
+    pub fn buffer(&self) -> &Buffer {
+        &self.0.buffer
+    }
+
+    /// Returns the offset of the allocation in its buffer.
+    #[inline]
+    pub fn offset(&self) -> BufferAddress {
+        self.0.range.start
+    }
+
+    /// Returns the size that was requested for the allocation.
+    #[inline]
+    pub fn size(&self) -> BufferAddress {
+        self.0.size
+    }
+
+    /// Binds the range of the allocation.
+    #[inline]
+    pub fn binding(&self) -> BindingResource {
+        BindingResource::Buffer(BufferBinding {
+            buffer: &self.0.buffer,
+            offset: self.0.range.start,
+            size: NonZeroU64::new(self.0.size),
+        })
+    }
+
+    /// Writes the `contents` to the start of the allocation.
+    pub fn write(&self, render_queue: &RenderQueue, contents: &[u8]) {
+        assert!(
+            contents.len() as BufferAddress <= self.0.size,
+            "the contents don't fit into the uniform allocation"
+        );
+        render_queue.write_buffer(&self.0.buffer, self.0.range.start, contents);
+    }
+}
+
+impl fmt::Debug for UniformAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UniformAllocation")
+            .field("buffer", &self.0.buffer.id())
+            .field("offset", &self.offset())
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
+struct Page {
+    size: BufferAddress,
+    /// The free ranges of the page, sorted by their start and never adjacent to each other.
+    free: Vec<Range<BufferAddress>>,
+}
+
+impl Page {
+    fn new(size: BufferAddress) -> Self {
+        Self {
+            size,
+            free: vec![0..size],
+        }
+    }
+
+    /// Takes `size` bytes from the start of the first free range that is large enough.
+    fn allocate(&mut self, size: BufferAddress) -> Option<Range<BufferAddress>> {
+        let index = self
+            .free
+            .iter()
+            .position(|free| free.end - free.start >= size)?;
+        let free = &mut self.free[index];
+        let range = free.start..free.start + size;
+        free.start = range.end;
+        if free.is_empty() {
+            self.free.remove(index);
+        }
+        Some(range)
+    }
+
+    /// Returns the `range` to the free ranges, merging it with its neighbors.
+    fn free(&mut self, mut range: Range<BufferAddress>) {
+        let index = self.free.partition_point(|free| free.start < range.start);
+        debug_assert!(index == 0 || self.free[index - 1].end <= range.start);
+        debug_assert!(index == self.free.len() || range.end <= self.free[index].start);
+        if index < self.free.len() && self.free[index].start == range.end {
+            range.end = self.free.remove(index).end;
+        }
+        match index.checked_sub(1).map(|index| &mut self.free[index]) {
+            Some(previous) if previous.end == range.start => previous.end = range.end,
+            _ => self.free.insert(index, range),
+        }
+    }
+
+    fn free_bytes(&self) -> BufferAddress {
+        self.free.iter().map(|free| free.end - free.start).sum()
+    }
+}
+
+/// The free ranges of the pages of a [`UniformAllocator`], whose buffers are stored at the same
+/// indices.
+struct Pages {
+    page_size: BufferAddress,
+    alignment: BufferAddress,
+    pages: Vec<Page>,
+    allocation_count: usize,
+}
+
+impl Pages {
+    fn new(page_size: BufferAddress, alignment: BufferAddress) -> Self {
+        let alignment = alignment.max(1);
+        Self {
+            // pages hold whole aligned allocations
+            page_size: (page_size.max(alignment) + alignment - 1) / alignment * alignment,
+            alignment,
+            pages: Vec::new(),
+            allocation_count: 0,
+        }
+    }
+
+    /// Returns the page and range of an allocation of at least `size` bytes. A new page is added
+    /// if no page has enough room.
+    fn allocate(&mut self, size: BufferAddress) -> (usize, Range<BufferAddress>) {
+        let size = (size.max(1) + self.alignment - 1) / self.alignment * self.alignment;
+        self.allocation_count += 1;
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(range) = page.allocate(size) {
+                return (index, range);
+            }
+        }
+        let mut page = Page::new(size.max(self.page_size));
+        let range = page.allocate(size).unwrap();
+        self.pages.push(page);
+        (self.pages.len() - 1, range)
+    }
+
+    fn free(&mut self, page: usize, range: Range<BufferAddress>) {
+        self.allocation_count -= 1;
+        self.pages[page].free(range);
+    }
+
+    fn stats(&self) -> UniformAllocatorStats {
+        let bytes_allocated = self.pages.iter().map(|page| page.size).sum();
+        let bytes_free: BufferAddress = self.pages.iter().map(Page::free_bytes).sum();
+        UniformAllocatorStats {
+            page_count: self.pages.len(),
+            bytes_allocated,
+            bytes_in_use: bytes_allocated - bytes_free,
+            allocation_count: self.allocation_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Page, Pages, UniformAllocator};
+    use crate::renderer::RenderResourceLimits;
+    use std::ops::Range;
+
+    #[test]
+    fn freed_ranges_are_coalesced() {
+        let mut page = Page::new(1024);
+        let ranges = (0..4)
+            .map(|_| page.allocate(256).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, [0..256, 256..512, 512..768, 768..1024]);
+        assert_eq!(page.allocate(1), None);
+
+        page.free(ranges[1].clone());
+        page.free(ranges[3].clone());
+        assert_eq!(page.free, [256..512, 768..1024]);
+        // a range that doesn't fit into any of the holes is only freed up by merging them
+        assert_eq!(page.allocate(512), None);
+        page.free(ranges[2].clone());
+        assert_eq!(page.free, [256..1024]);
+        page.free(ranges[0].clone());
+        assert_eq!(page.free, [0..1024]);
+    }
+
+    #[test]
+    fn allocations_are_aligned_and_oversized_ones_get_their_own_page() {
+        let mut pages = Pages::new(4096, 256);
+        assert_eq!(pages.allocate(80), (0, 0..256));
+        assert_eq!(pages.allocate(257), (0, 256..768));
+        assert_eq!(pages.allocate(10_000), (1, 0..10_240));
+        assert_eq!(pages.allocate(0), (0, 768..1024));
+        let stats = pages.stats();
+        assert_eq!(stats.page_count, 2);
+        assert_eq!(stats.bytes_allocated, 4096 + 10_240);
+        assert_eq!(stats.bytes_in_use, 1024 + 10_240);
+        assert_eq!(stats.allocation_count, 4);
+    }
+
+    #[test]
+    fn dropped_ranges_are_freed_once_their_frame_completed() {
+        let mut allocator = UniformAllocator::new(&RenderResourceLimits::default(), 4096);
+        let (page, range) = allocator.pages.allocate(100);
+        allocator.dropped.lock().push((page, range));
+
+        // the GPU may still read the uniform in the frame it was dropped in
+        allocator.recycle(0, 0);
+        assert_eq!(allocator.stats().allocation_count, 1);
+        allocator.recycle(1, 0);
+        assert_eq!(allocator.stats().bytes_in_use, 256);
+        allocator.recycle(2, 1);
+        assert_eq!(allocator.stats().bytes_in_use, 0);
+        assert_eq!(allocator.stats().allocation_count, 0);
+        assert!(allocator.in_flight.is_empty());
+    }
+
+    #[test]
+    fn random_allocations_never_overlap_or_leak() {
+        let mut pages = Pages::new(64 * 1024, 256);
+        let mut live: Vec<(usize, Range<u64>)> = Vec::new();
+        // a xorshift generator, so that failures can be reproduced
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for step in 0..20_000 {
+            // the number of live allocations drifts up and down, so pages get full and empty
+            let allocate =
+                live.is_empty() || random() % 100 < if step % 5000 < 2500 { 60 } else { 40 };
+            if allocate {
+                let size = match random() % 10 {
+                    // the occasional allocation that is larger than a page
+                    0 => 64 * 1024 + random() % 4096,
+                    1..=3 => random() % 4096,
+                    _ => random() % 300,
+                };
+                let (page, range) = pages.allocate(size);
+                assert_eq!(range.start % 256, 0);
+                assert!(range.end - range.start >= size);
+                assert!(range.end <= pages.pages[page].size);
+                live.push((page, range));
+            } else {
+                let index = random() as usize % live.len();
+                let (page, range) = live.swap_remove(index);
+                pages.free(page, range);
+            }
+
+            if step % 1000 == 0 {
+                let mut sorted = live.clone();
+                sorted.sort_by_key(|(page, range)| (*page, range.start));
+                for pair in sorted.windows(2) {
+                    let ((page, first), (next_page, next)) = (&pair[0], &pair[1]);
+                    assert!(page != next_page || first.end <= next.start);
+                }
+                let in_use = live
+                    .iter()
+                    .map(|(_, range)| range.end - range.start)
+                    .sum::<u64>();
+                assert_eq!(pages.stats().bytes_in_use, in_use);
+                assert_eq!(pages.stats().allocation_count, live.len());
+            }
+        }
+
+        // everything is free again once the allocations are gone
+        for (page, range) in live.drain(..) {
+            pages.free(page, range);
+        }
+        assert_eq!(pages.stats().bytes_in_use, 0);
+        assert_eq!(pages.stats().allocation_count, 0);
+        assert!(pages.pages.iter().all(|page| page.free == [0..page.size]));
+    }
+}
@@ -0,0 +1,446 @@
+use crate::{
+    render_resource::{
+        editor::{EditableUniform, FieldEditorInfo},
+        layout::UniformLayoutDescription,
+        std140::{AsStd140, Std140},
+        AsVertexFormats, ShaderDefs, VertexBufferLayout,
+    },
+    texture::Image,
+};
+use bevy_asset::Handle;
+use bevy_ecs::{
+    system::BoxedSystem,
+    world::{Mut, World},
+};
+use bevy_utils::HashMap;
+use std::{
+    any::{type_name, Any, TypeId},
+    marker::PhantomData,
+};
+use thiserror::Error;
+use wgpu::VertexStepMode;
+
+/// The error returned by the accessors of a [`UniformTypeRegistration`] when they are passed a
+/// value of another type.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("expected a value of the uniform type `{expected}`")]
+pub struct UniformTypeMismatch {
+    pub expected: &'static str,
+}
+
+/// The uniform types known to the renderer, which lets render graph code handle the uniforms of
+/// types it doesn't know statically, like the materials of plugins.
+///
+/// Plugins add their types to the registry of the render app with
+/// [`register`](UniformTypeRegistry::register), which returns a builder for the optional
+/// accessors of the type:
+///
+/// ```ignore
+/// render_app
+///     .world
+///     .resource_mut::<UniformTypeRegistry>()
+///     .register::<CustomMaterialUniformData>()
+///     .editable()
+///     .shader_defs(|material, shader_defs| {
+///         if material.emissive.w > 0.0 {
+///             shader_defs.push("EMISSIVE");
+///         }
+///     });
+/// ```
+#[derive(Default)]
+pub struct UniformTypeRegistry {
+    registrations: HashMap<TypeId, UniformTypeRegistration>,
+    names: HashMap<&'static str, TypeId>,
+}
+
+impl UniformTypeRegistry {
+    /// Registers the uniform type `T`, or returns its existing registration to add accessors.
+    pub fn register<T: AsStd140 + Send + Sync + 'static>(&mut self) -> RegisterUniformType<T> {
+        let registration = self
+            .registrations
+            .entry(TypeId::of::<T>())
+            .or_insert_with(UniformTypeRegistration::new::<T>);
+        self.names.insert(registration.name, registration.type_id);
+        RegisterUniformType {
+            registration,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the registration of the type with the given [`TypeId`].
+    pub fn get(&self, type_id: TypeId) -> Option<&UniformTypeRegistration> {
+        self.registrations.get(&type_id)
+    }
+
+    /// Returns the registration of the type with the given [`type_name`].
+    pub fn get_with_name(&self, name: &str) -> Option<&UniformTypeRegistration> {
+        self.get(*self.names.get(name)?)
+    }
+
+    /// Iterates over the registrations of all types, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &UniformTypeRegistration> {
+        self.registrations.values()
+    }
+
+    /// Runs the prepare systems of all types registered with one, e.g. by
+    /// [`UniformComponentPlugin::registry_driven`](crate::render_component::UniformComponentPlugin::registry_driven),
+    /// at the start of the [`Prepare`](crate::RenderStage::Prepare) stage.
+    pub(crate) fn prepare_system(world: &mut World) {
+        world.resource_scope(|world, mut registry: Mut<Self>| {
+            for registration in registry.registrations.values_mut() {
+                if let Some(prepare) = &mut registration.prepare {
+                    if !prepare.initialized {
+                        prepare.system.initialize(world);
+                        prepare.initialized = true;
+                    }
+                    prepare.system.run((), world);
+                    prepare.system.apply_buffers(world);
+                }
+            }
+        });
+    }
+}
+
+/// A type registered in the [`UniformTypeRegistry`], with type-erased accessors for its values.
+///
+/// The accessors return a [`UniformTypeMismatch`] when passed a value of another type.
+pub struct UniformTypeRegistration {
+    name: &'static str,
+    type_id: TypeId,
+    describe_std140_layout: fn() -> UniformLayoutDescription,
+    write_std140: fn(&dyn Any, &mut Vec<u8>) -> bool,
+    vertex_buffer_layout: Option<VertexBufferLayout>,
+    editor_fields: &'static [FieldEditorInfo],
+    set_field: Option<fn(&mut dyn Any, usize, &[f32]) -> Option<bool>>,
+    shader_defs: Option<Box<dyn Fn(&dyn Any, &mut ShaderDefs) -> bool + Send + Sync>>,
+    textures: Option<Box<dyn Fn(&dyn Any, &mut Vec<Handle<Image>>) -> bool + Send + Sync>>,
+    prepare: Option<PrepareSystem>,
+}
+
+struct PrepareSystem {
+    system: BoxedSystem,
+    initialized: bool,
+}
+
+impl UniformTypeRegistration {
+    fn new<T: AsStd140 + 'static>() -> Self {
+        Self {
+            name: type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            describe_std140_layout: T::describe_std140_layout,
+            write_std140: |value, bytes| match value.downcast_ref::<T>() {
+                Some(value) => {
+                    bytes.extend_from_slice(value.as_std140().as_bytes());
+                    true
+                }
+                None => false,
+            },
+            vertex_buffer_layout: None,
+            editor_fields: &[],
+            set_field: None,
+            shader_defs: None,
+            textures: None,
+            prepare: None,
+        }
+    }
+
+    /// Returns the [`type_name`] of the type.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Returns the `std140` layout of the type, see [`AsStd140::describe_std140_layout`].
+    pub fn describe_std140_layout(&self) -> UniformLayoutDescription {
+        (self.describe_std140_layout)()
+    }
+
+    /// Appends the `std140` bytes of the value to `bytes`, see [`AsStd140::as_std140`].
+    pub fn write_std140(
+        &self,
+        value: &dyn Any,
+        bytes: &mut Vec<u8>,
+    ) -> Result<(), UniformTypeMismatch> {
+        self.check((self.write_std140)(value, bytes))
+    }
+
+    /// Returns the layout of vertex buffers containing the type, if the type was registered with
+    /// [`RegisterUniformType::vertex_buffer`].
+    #[inline]
+    pub fn vertex_buffer_layout(&self) -> Option<&VertexBufferLayout> {
+        self.vertex_buffer_layout.as_ref()
+    }
+
+    /// Returns the editable fields of the type, which are empty unless the type was registered
+    /// with [`RegisterUniformType::editable`].
+    #[inline]
+    pub fn editor_fields(&self) -> &'static [FieldEditorInfo] {
+        self.editor_fields
+    }
+
+    /// Writes an editable field of the value, see [`EditableUniform::set_field`].
+    ///
+    /// Returns `Ok(false)` if the field wasn't written, e.g. because the type wasn't registered as
+    /// editable.
+    pub fn set_field(
+        &self,
+        value: &mut dyn Any,
+        index: usize,
+        components: &[f32],
+    ) -> Result<bool, UniformTypeMismatch> {
+        match self.set_field {
+            Some(set_field) => set_field(value, index, components).ok_or_else(|| self.mismatch()),
+            None => self.check(self.is_type_of(value)).map(|_| false),
+        }
+    }
+
+    /// Adds the shader defs of the value to `shader_defs`, if the type was registered with
+    /// [`RegisterUniformType::shader_defs`].
+    pub fn shader_defs(
+        &self,
+        value: &dyn Any,
+        shader_defs: &mut ShaderDefs,
+    ) -> Result<(), UniformTypeMismatch> {
+        match &self.shader_defs {
+            Some(collect) => self.check(collect(value, shader_defs)),
+            None => self.check(self.is_type_of(value)),
+        }
+    }
+
+    /// Adds the textures of the value to `textures`, if the type was registered with
+    /// [`RegisterUniformType::textures`].
+    pub fn textures(
+        &self,
+        value: &dyn Any,
+        textures: &mut Vec<Handle<Image>>,
+    ) -> Result<(), UniformTypeMismatch> {
+        match &self.textures {
+            Some(collect) => self.check(collect(value, textures)),
+            None => self.check(self.is_type_of(value)),
+        }
+    }
+
+    fn is_type_of(&self, value: &dyn Any) -> bool {
+        Any::type_id(value) == self.type_id
+    }
+
+    fn check(&self, matches: bool) -> Result<(), UniformTypeMismatch> {
+        if matches {
+            Ok(())
+        } else {
+            Err(self.mismatch())
+        }
+    }
+
+    fn mismatch(&self) -> UniformTypeMismatch {
+        UniformTypeMismatch {
+            expected: self.name,
+        }
+    }
+}
+
+/// Adds optional accessors to the registration of the uniform type `T`, returned by
+/// [`UniformTypeRegistry::register`].
+pub struct RegisterUniformType<'a, T> {
+    registration: &'a mut UniformTypeRegistration,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: 'static> RegisterUniformType<'a, T> {
+    /// Stores the layout of vertex buffers containing the type, using the given step mode.
+    pub fn vertex_buffer(self, step_mode: VertexStepMode) -> Self
+    where
+        T: AsVertexFormats,
+    {
+        self.registration.vertex_buffer_layout =
+            Some(VertexBufferLayout::from_type::<T>(step_mode));
+        self
+    }
+
+    /// Exposes the [`EditableUniform`] fields of the type.
+    pub fn editable(self) -> Self
+    where
+        T: EditableUniform,
+    {
+        self.registration.editor_fields = T::EDITOR_FIELDS;
+        self.registration.set_field = Some(|value, index, components| {
+            value
+                .downcast_mut::<T>()
+                .map(|value| value.set_field(index, components))
+        });
+        self
+    }
+
+    /// Collects the shader defs of values of the type with the given function.
+    pub fn shader_defs(self, collect: fn(&T, &mut ShaderDefs)) -> Self {
+        self.registration.shader_defs = Some(Box::new(move |value, shader_defs| {
+            value
+                .downcast_ref::<T>()
+                .map(|value| collect(value, shader_defs))
+                .is_some()
+        }));
+        self
+    }
+
+    /// Collects the textures used by values of the type with the given function.
+    pub fn textures(self, collect: fn(&T, &mut Vec<Handle<Image>>)) -> Self {
+        self.registration.textures = Some(Box::new(move |value, textures| {
+            value
+                .downcast_ref::<T>()
+                .map(|value| collect(value, textures))
+                .is_some()
+        }));
+        self
+    }
+
+    /// Runs the system as part of [`UniformTypeRegistry::prepare_system`], which prepares the
+    /// uniforms of all registered types.
+    pub(crate) fn prepare_with(self, system: BoxedSystem) -> Self {
+        self.registration.prepare = Some(PrepareSystem {
+            system,
+            initialized: false,
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UniformTypeMismatch, UniformTypeRegistry};
+    use crate::{
+        render_resource::{
+            editor::EditableUniform,
+            std140::{AsStd140, Std140},
+            ShaderDefs, VertexBufferLayout,
+        },
+        texture::Image,
+    };
+    use bevy_asset::HandleUntyped;
+    use bevy_math::{Mat4, Vec4};
+    use bevy_reflect::TypeUuid;
+    use std::any::{type_name, TypeId};
+    use wgpu::VertexStepMode;
+
+    #[derive(AsStd140, EditableUniform, Clone, Debug, PartialEq)]
+    struct TestMaterial {
+        #[uniform(semantic = "color")]
+        color: Vec4,
+        #[uniform(min = 0.0, max = 1.0, semantic = "factor")]
+        roughness: f32,
+        #[uniform(skip)]
+        textured: u32,
+    }
+
+    const TEXTURE: HandleUntyped = HandleUntyped::weak_from_u64(Image::TYPE_UUID, 1);
+
+    fn registry() -> UniformTypeRegistry {
+        let mut registry = UniformTypeRegistry::default();
+        registry
+            .register::<TestMaterial>()
+            .editable()
+            .shader_defs(|material, shader_defs| {
+                if material.textured != 0 {
+                    shader_defs.push("TEXTURED");
+                }
+            })
+            .textures(|material, textures| {
+                if material.textured != 0 {
+                    textures.push(TEXTURE.typed());
+                }
+            });
+        registry
+            .register::<Mat4>()
+            .vertex_buffer(VertexStepMode::Instance);
+        registry
+    }
+
+    #[test]
+    fn erased_accessors_match_direct_calls() {
+        let registry = registry();
+        assert_eq!(registry.iter().count(), 2);
+
+        let mut material = TestMaterial {
+            color: Vec4::new(1.0, 0.5, 0.25, 1.0),
+            roughness: 0.5,
+            textured: 1,
+        };
+        let registration = registry.get(TypeId::of::<TestMaterial>()).unwrap();
+        assert_eq!(registration.name(), type_name::<TestMaterial>());
+        let mut bytes = Vec::new();
+        registration.write_std140(&material, &mut bytes).unwrap();
+        assert_eq!(bytes, material.as_std140().as_bytes());
+        assert_eq!(
+            registration.describe_std140_layout().to_string(),
+            TestMaterial::describe_std140_layout().to_string()
+        );
+        assert!(registration.vertex_buffer_layout().is_none());
+
+        assert_eq!(registration.editor_fields(), TestMaterial::EDITOR_FIELDS);
+        let mut expected = material.clone();
+        assert!(expected.set_field(1, &[0.75]));
+        assert_eq!(registration.set_field(&mut material, 1, &[0.75]), Ok(true));
+        assert_eq!(registration.set_field(&mut material, 2, &[0.75]), Ok(false));
+        assert_eq!(material, expected);
+
+        let mut shader_defs = ShaderDefs::new();
+        let mut textures = Vec::new();
+        registration
+            .shader_defs(&material, &mut shader_defs)
+            .unwrap();
+        registration.textures(&material, &mut textures).unwrap();
+        assert_eq!(
+            shader_defs,
+            ["TEXTURED"].into_iter().collect::<ShaderDefs>()
+        );
+        assert_eq!(textures, [TEXTURE.typed::<Image>()]);
+
+        let transform = Mat4::from_scale([1.0, 2.0, 3.0].into());
+        let registration = registry.get_with_name(type_name::<Mat4>()).unwrap();
+        bytes.clear();
+        registration.write_std140(&transform, &mut bytes).unwrap();
+        assert_eq!(bytes, transform.as_std140().as_bytes());
+        assert_eq!(
+            registration.vertex_buffer_layout(),
+            Some(&VertexBufferLayout::from_type::<Mat4>(
+                VertexStepMode::Instance
+            ))
+        );
+        assert!(registration.editor_fields().is_empty());
+        assert_eq!(
+            registration.set_field(&mut transform.clone(), 0, &[1.0]),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn accessors_reject_other_types() {
+        let registry = registry();
+        let registration = registry.get(TypeId::of::<Mat4>()).unwrap();
+        let mismatch = Err(UniformTypeMismatch {
+            expected: type_name::<Mat4>(),
+        });
+        assert_eq!(
+            registration.write_std140(&1.0f32, &mut Vec::new()),
+            mismatch
+        );
+        assert_eq!(
+            registration.shader_defs(&Vec4::ONE, &mut ShaderDefs::new()),
+            mismatch
+        );
+
+        let registration = registry.get(TypeId::of::<TestMaterial>()).unwrap();
+        let mut matrix = Mat4::IDENTITY;
+        assert_eq!(
+            registration.set_field(&mut matrix, 0, &[1.0]),
+            Err(UniformTypeMismatch {
+                expected: type_name::<TestMaterial>(),
+            })
+        );
+        assert!(registry.get_with_name("Unregistered").is_none());
+    }
+}
@@ -1,33 +1,104 @@
 use crate::{
-    render_resource::std140::{self, AsStd140, DynamicUniform, Std140},
-    render_resource::Buffer,
-    renderer::{RenderDevice, RenderQueue},
+    render_resource::std140::{AsStd140, Std140},
+    render_resource::{Buffer, BufferGrowthPolicy},
+    renderer::{
+        FramesInFlight, RenderDevice, RenderQueue, RenderResourceLimits, UniformBindingSizeError,
+    },
 };
-use std::num::NonZeroU64;
+use bevy_tasks::TaskPool;
+use bevy_utils::tracing::error;
+use std::{num::NonZeroU64, ops::Range};
 use wgpu::{BindingResource, BufferBinding, BufferDescriptor, BufferUsages};
 
+/// The uploads done by [`UniformVec::write_buffer`], e.g. for the
+/// [`RenderStatistics`](crate::diagnostic::RenderStatistics).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UniformWrites {
+    /// The number of bytes written to the uniform buffer.
+    pub bytes: u64,
+    /// The number of writes issued to the queue.
+    pub writes: u64,
+    /// The number of uploaded values that changed since the last upload.
+    pub values: u64,
+}
+
+/// Stores values of type `T` in a uniform buffer.
+///
+/// [`UniformVec::write_buffer`] only uploads the values that changed since the last upload, so
+/// static values don't have to be copied to the GPU every frame, even though they are pushed
+/// again every frame. The capacity of the uniform buffer follows the number of values according to
+/// a [`BufferGrowthPolicy`], so the buffer shrinks again after e.g. many entities despawned.
+///
+/// A vec that [skips unchanged values](UniformVec::skip_unchanged) compares each value with the
+/// value pushed to its slot before the last [`UniformVec::clear`] instead, and only encodes the
+/// values that differ.
 pub struct UniformVec<T: AsStd140> {
     values: Vec<T>,
+    /// The values of the last upload, if unchanged values are skipped.
+    previous_values: Vec<T>,
+    same_value: Option<fn(&T, &T) -> bool>,
+    /// The bytes of the values that were written in the current frame.
     scratch: Vec<u8>,
+    /// The contents of the uniform buffer, which are compared with the `scratch` to find the
+    /// changed values.
+    uploaded: Vec<u8>,
+    /// The changed ranges of the `scratch`, which are kept around to reuse their allocation.
+    changed_ranges: Vec<Range<usize>>,
     uniform_buffer: Option<Buffer>,
     capacity: usize,
     item_size: usize,
+    force_upload: bool,
+    growth_policy: BufferGrowthPolicy,
+    underused_writes: u32,
+    /// Whether [`UniformVec::write_buffer`] logged that the values don't fit into a binding.
+    size_error_logged: bool,
 }
 
 impl<T: AsStd140> Default for UniformVec<T> {
     fn default() -> Self {
         Self {
             values: Vec::new(),
+            previous_values: Vec::new(),
+            same_value: None,
             scratch: Vec::new(),
+            uploaded: Vec::new(),
+            changed_ranges: Vec::new(),
             uniform_buffer: None,
             capacity: 0,
             item_size: (T::std140_size_static() + <T as AsStd140>::Output::ALIGNMENT - 1)
                 & !(<T as AsStd140>::Output::ALIGNMENT - 1),
+            force_upload: false,
+            growth_policy: BufferGrowthPolicy::default(),
+            underused_writes: 0,
+            size_error_logged: false,
         }
     }
 }
 
 impl<T: AsStd140> UniformVec<T> {
+    /// Sets how the capacity of the uniform buffer follows the number of values written to it by
+    /// [`UniformVec::write_buffer`].
+    pub fn with_growth_policy(mut self, growth_policy: BufferGrowthPolicy) -> Self {
+        self.growth_policy = growth_policy;
+        self
+    }
+
+    /// Only encodes and uploads the values that differ from the value in their slot at the last
+    /// upload. This is cheaper than comparing the encoded bytes of all values with the uniform
+    /// buffer, which is done otherwise.
+    pub fn skip_unchanged(self) -> Self
+    where
+        T: PartialEq,
+    {
+        self.with_value_comparison(T::eq)
+    }
+
+    /// Like [`UniformVec::skip_unchanged`], comparing the values with `same_value`.
+    pub(crate) fn with_value_comparison(mut self, same_value: fn(&T, &T) -> bool) -> Self {
+        self.same_value = Some(same_value);
+        self
+    }
+
     #[inline]
     pub fn uniform_buffer(&self) -> Option<&Buffer> {
         self.uniform_buffer.as_ref()
@@ -67,37 +138,179 @@ impl<T: AsStd140> UniformVec<T> {
         &mut self.values[index]
     }
 
-    pub fn reserve(&mut self, capacity: usize, device: &RenderDevice) -> bool {
+    /// Reallocates the uniform buffer if it can't hold `capacity` values, and returns whether it
+    /// did. Returns an error without creating a buffer if a value doesn't fit into a uniform
+    /// buffer binding of the `device`. Such values have to be stored in a storage buffer instead.
+    pub fn reserve(
+        &mut self,
+        capacity: usize,
+        device: &RenderDevice,
+    ) -> Result<bool, UniformBindingSizeError> {
         if capacity > self.capacity {
-            self.capacity = capacity;
-            let size = self.item_size * capacity;
-            self.scratch.resize(size, 0);
-            self.uniform_buffer = Some(device.create_buffer(&BufferDescriptor {
-                label: None,
-                size: size as wgpu::BufferAddress,
-                usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
-                mapped_at_creation: false,
-            }));
-            true
+            self.reallocate(capacity, device)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn reallocate(
+        &mut self,
+        capacity: usize,
+        device: &RenderDevice,
+    ) -> Result<(), UniformBindingSizeError> {
+        self.resize(capacity, &device.limits())?;
+        self.uniform_buffer = Some(device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (self.item_size * capacity) as wgpu::BufferAddress,
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        }));
+        Ok(())
+    }
+
+    /// Prepares the copies of the values for a new uniform buffer with room for `capacity` values.
+    /// Returns an error without changing anything if a value doesn't fit into a uniform buffer
+    /// binding with the `limits`.
+    fn resize(
+        &mut self,
+        capacity: usize,
+        limits: &wgpu::Limits,
+    ) -> Result<(), UniformBindingSizeError> {
+        self.validate_binding(limits)?;
+        self.capacity = capacity;
+        let size = self.item_size * capacity;
+        self.scratch.resize(size, 0);
+        if self.same_value.is_some() {
+            // none of the previous values are in the new buffer
+            self.previous_values.clear();
+        } else {
+            // new buffers are zeroed
+            self.uploaded.clear();
+            self.uploaded.resize(size, 0);
+        }
+        Ok(())
+    }
+
+    /// Returns the capacity the uniform buffer has to be reallocated with to hold the values, if it
+    /// is too small or has been underused for a while.
+    fn next_capacity(&mut self) -> Option<usize> {
+        // writes without values count as underused as well, so the buffer is freed up even if
+        // all entities despawned
+        let capacity = self.growth_policy.next_capacity(
+            self.capacity,
+            self.values.len(),
+            &mut self.underused_writes,
+        );
+        if capacity != self.capacity {
+            Some(capacity)
         } else {
-            false
+            None
         }
     }
 
-    pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) {
+    /// Returns an error if a value doesn't fit into a uniform buffer binding with the `limits`.
+    fn validate_binding(&self, limits: &wgpu::Limits) -> Result<(), UniformBindingSizeError> {
+        RenderResourceLimits::from(limits)
+            .validate_uniform_binding(std::any::type_name::<T>(), self.item_size)
+    }
+
+    /// Uploads the values that changed since the last upload to the uniform buffer, which is
+    /// reallocated if it is too small or has been underused for a while. If the values don't fit
+    /// into a uniform buffer binding, nothing is uploaded and the error of
+    /// [`UniformVec::reserve`] is logged once.
+    pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) -> UniformWrites {
+        let item_size = self.item_size;
+        self.write_buffer_with(device, queue, |values, bytes| {
+            write_std140(values, item_size, bytes);
+        })
+    }
+
+    /// Like [`UniformVec::write_buffer`], but encodes many values in parallel on the
+    /// `task_pool`.
+    pub fn write_buffer_parallel(
+        &mut self,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        task_pool: &TaskPool,
+    ) -> UniformWrites
+    where
+        T: Sync,
+    {
+        let item_size = self.item_size;
+        self.write_buffer_with(device, queue, |values, bytes| {
+            write_std140_parallel(values, item_size, bytes, task_pool);
+        })
+    }
+
+    fn write_buffer_with(
+        &mut self,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        encode: impl FnOnce(&[T], &mut [u8]),
+    ) -> UniformWrites {
+        if let Some(capacity) = self.next_capacity() {
+            if let Err(err) = self.reallocate(capacity, device) {
+                if !std::mem::replace(&mut self.size_error_logged, true) {
+                    error!("{}", err);
+                }
+                return UniformWrites::default();
+            }
+        }
+        let uniform_buffer = match &self.uniform_buffer {
+            Some(uniform_buffer) => uniform_buffer.clone(),
+            None => return UniformWrites::default(),
+        };
+        self.upload(encode, &mut |offset, bytes| {
+            queue.write_buffer(&uniform_buffer, offset as wgpu::BufferAddress, bytes);
+        })
+    }
+
+    /// Encodes the values that changed since the last upload and passes them to `write` together
+    /// with their offset in the uniform buffer.
+    fn upload(
+        &mut self,
+        encode: impl FnOnce(&[T], &mut [u8]),
+        write: &mut dyn FnMut(usize, &[u8]),
+    ) -> UniformWrites {
         if self.values.is_empty() {
-            return;
+            return UniformWrites::default();
         }
-        self.reserve(self.values.len(), device);
-        if let Some(uniform_buffer) = &self.uniform_buffer {
-            let range = 0..self.item_size * self.values.len();
-            let mut writer = std140::Writer::new(&mut self.scratch[range.clone()]);
-            writer.write(self.values.as_slice()).unwrap();
-            queue.write_buffer(uniform_buffer, 0, &self.scratch[range]);
+        if let Some(same_value) = self.same_value {
+            return upload_changed_values(
+                &self.values,
+                &self.previous_values,
+                same_value,
+                &mut self.scratch,
+                self.item_size,
+                std::mem::take(&mut self.force_upload),
+                &mut self.changed_ranges,
+                write,
+            );
         }
+        let len = self.item_size * self.values.len();
+        encode(&self.values, &mut self.scratch[..len]);
+        upload_changes(
+            &mut self.uploaded[..len],
+            &self.scratch[..len],
+            self.item_size,
+            std::mem::take(&mut self.force_upload),
+            &mut self.changed_ranges,
+            write,
+        )
+    }
+
+    /// Uploads all values with the next [`UniformVec::write_buffer`], even if they didn't
+    /// change. This is only needed if the contents of the uniform buffer were lost or overwritten.
+    pub fn force_upload(&mut self) {
+        self.force_upload = true;
     }
 
     pub fn clear(&mut self) {
+        if self.same_value.is_some() {
+            // the values are compared with the values of the last upload
+            std::mem::swap(&mut self.values, &mut self.previous_values);
+        }
         self.values.clear();
     }
 
@@ -106,61 +319,729 @@ impl<T: AsStd140> UniformVec<T> {
     }
 }
 
+/// Stores values of type `T` in a uniform buffer, which is bound with a dynamic offset to select
+/// the value of e.g. an entity, as returned by [`DynamicUniformVec::push`].
+///
+/// Every value takes up a slot aligned to the `min_uniform_buffer_offset_alignment` of the
+/// [`RenderResourceLimits`] the vec was created with. The default limits align the slots to 256
+/// bytes, which is the largest alignment a device may require, so their offsets are valid on all
+/// devices. The values are pushed again every frame, which frees the slots of despawned entities.
+///
+/// A vec created [`with_frames_in_flight`](DynamicUniformVec::with_frames_in_flight) keeps a
+/// uniform buffer per frame in flight and moves on to the next one with every
+/// [`DynamicUniformVec::clear`], so the values of the current frame never overwrite the values the
+/// GPU still reads for a previous frame. It must then be cleared exactly once per frame.
 pub struct DynamicUniformVec<T: AsStd140> {
-    uniform_vec: UniformVec<DynamicUniform<T>>,
+    ring: Vec<UniformVec<T>>,
+    /// The index of the uniform vec of the current frame in the `ring`.
+    current: usize,
 }
 
 impl<T: AsStd140> Default for DynamicUniformVec<T> {
     fn default() -> Self {
-        Self {
-            uniform_vec: Default::default(),
-        }
+        Self::new(&RenderResourceLimits::default())
     }
 }
 
 impl<T: AsStd140> DynamicUniformVec<T> {
+    /// Creates a vec with slots that are aligned according to the `limits` of the device.
+    pub fn new(limits: &RenderResourceLimits) -> Self {
+        let mut dynamic_uniform_vec = Self {
+            ring: Vec::new(),
+            current: 0,
+        };
+        dynamic_uniform_vec.resize_ring(
+            1,
+            limits.uniform_slot_size(T::std140_size_static(), <T as AsStd140>::Output::ALIGNMENT),
+        );
+        dynamic_uniform_vec
+    }
+
+    /// Keeps a uniform buffer for each of the frames in flight, see [`FramesInFlight`].
+    pub fn with_frames_in_flight(mut self, frames: &FramesInFlight) -> Self {
+        self.resize_ring(frames.count(), self.item_size());
+        self
+    }
+
+    /// Only encodes and uploads the values that changed, see [`UniformVec::skip_unchanged`].
+    pub fn skip_unchanged(self) -> Self
+    where
+        T: PartialEq,
+    {
+        self.with_value_comparison(T::eq)
+    }
+
+    pub(crate) fn with_value_comparison(mut self, same_value: fn(&T, &T) -> bool) -> Self {
+        for uniform_vec in &mut self.ring {
+            uniform_vec.same_value = Some(same_value);
+        }
+        self
+    }
+
+    fn resize_ring(&mut self, len: usize, item_size: usize) {
+        let same_value = self
+            .ring
+            .first()
+            .and_then(|uniform_vec| uniform_vec.same_value);
+        self.ring.resize_with(len, || UniformVec {
+            item_size,
+            same_value,
+            ..Default::default()
+        });
+    }
+
+    #[inline]
+    fn current(&self) -> &UniformVec<T> {
+        &self.ring[self.current]
+    }
+
+    #[inline]
+    fn current_mut(&mut self) -> &mut UniformVec<T> {
+        &mut self.ring[self.current]
+    }
+
+    /// Returns the uniform buffer of the current frame.
     #[inline]
     pub fn uniform_buffer(&self) -> Option<&Buffer> {
-        self.uniform_vec.uniform_buffer()
+        self.current().uniform_buffer()
     }
 
     #[inline]
     pub fn binding(&self) -> Option<BindingResource> {
-        self.uniform_vec.binding()
+        self.current().binding()
     }
 
     #[inline]
     pub fn len(&self) -> usize {
-        self.uniform_vec.len()
+        self.current().len()
     }
 
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.uniform_vec.is_empty()
+        self.current().is_empty()
     }
 
     #[inline]
     pub fn capacity(&self) -> usize {
-        self.uniform_vec.capacity()
+        self.current().capacity()
     }
 
+    /// Returns the number of uniform buffers, one for each frame in flight.
+    #[inline]
+    pub fn frames_in_flight(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns the size of the slot of each value in bytes.
+    #[inline]
+    pub fn item_size(&self) -> usize {
+        self.current().item_size
+    }
+
+    /// Adds the `value` and returns its dynamic offset.
     #[inline]
     pub fn push(&mut self, value: T) -> u32 {
-        (self.uniform_vec.push(DynamicUniform(value)) * self.uniform_vec.item_size) as u32
+        let item_size = self.item_size();
+        (self.current_mut().push(value) * item_size) as u32
+    }
+
+    /// Reserves room for `capacity` values in the uniform buffer of the current frame, see
+    /// [`UniformVec::reserve`].
+    #[inline]
+    pub fn reserve(
+        &mut self,
+        capacity: usize,
+        device: &RenderDevice,
+    ) -> Result<(), UniformBindingSizeError> {
+        self.current_mut().reserve(capacity, device).map(|_| ())
     }
 
+    /// Uploads the values that changed since the uniform buffer of the current frame was last
+    /// written, see [`UniformVec::write_buffer`].
     #[inline]
-    pub fn reserve(&mut self, capacity: usize, device: &RenderDevice) {
-        self.uniform_vec.reserve(capacity, device);
+    pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) -> UniformWrites {
+        self.current_mut().write_buffer(device, queue)
     }
 
+    /// See [`UniformVec::write_buffer_parallel`].
     #[inline]
-    pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) {
-        self.uniform_vec.write_buffer(device, queue);
+    pub fn write_buffer_parallel(
+        &mut self,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        task_pool: &TaskPool,
+    ) -> UniformWrites
+    where
+        T: Sync,
+    {
+        self.current_mut()
+            .write_buffer_parallel(device, queue, task_pool)
+    }
+
+    /// Uploads all values to every uniform buffer the next time it is written.
+    #[inline]
+    pub fn force_upload(&mut self) {
+        for uniform_vec in &mut self.ring {
+            uniform_vec.force_upload();
+        }
     }
 
+    /// Removes all values and moves on to the uniform buffer of the next frame.
     #[inline]
     pub fn clear(&mut self) {
-        self.uniform_vec.clear();
+        self.current = (self.current + 1) % self.ring.len();
+        self.current_mut().clear();
+    }
+}
+
+/// Encodes the `values` that differ from the `previous_values` in their slot into the `scratch`
+/// and passes the encoded ranges, coalesced with [`coalesce_ranges`], to `write`. All values are
+/// encoded if `force_upload` is set. The `ranges` are only used to store the changed ranges
+/// without allocating.
+#[allow(clippy::too_many_arguments)]
+fn upload_changed_values<T: AsStd140>(
+    values: &[T],
+    previous_values: &[T],
+    same_value: fn(&T, &T) -> bool,
+    scratch: &mut [u8],
+    item_size: usize,
+    force_upload: bool,
+    ranges: &mut Vec<Range<usize>>,
+    write: &mut dyn FnMut(usize, &[u8]),
+) -> UniformWrites {
+    let mut changed_count = 0;
+    let changed = values.iter().enumerate().filter(|(index, value)| {
+        force_upload
+            || !previous_values
+                .get(*index)
+                .map_or(false, |previous| same_value(previous, value))
+    });
+    // the unchanged slots between the changed ones still hold the bytes of the last upload
+    let encoded = changed.map(|(index, value)| {
+        changed_count += 1;
+        let slot = index * item_size..(index + 1) * item_size;
+        write_std140(
+            std::slice::from_ref(value),
+            item_size,
+            &mut scratch[slot.clone()],
+        );
+        slot
+    });
+    coalesce_ranges(encoded, MAX_COALESCED_GAP, ranges);
+    let mut writes = UniformWrites {
+        values: changed_count as u64,
+        ..Default::default()
+    };
+    for range in ranges.drain(..) {
+        write(range.start, &scratch[range.clone()]);
+        writes.bytes += range.len() as u64;
+        writes.writes += 1;
+    }
+    writes
+}
+
+/// The number of values encoded by each task of [`write_std140_parallel`].
+const VALUES_PER_TASK: usize = 1024;
+
+/// Writes the std140 representation of each of the `values` to the start of its slot of
+/// `item_size` bytes in the `bytes`.
+fn write_std140<T: AsStd140>(values: &[T], item_size: usize, bytes: &mut [u8]) {
+    for (value, slot) in values.iter().zip(bytes.chunks_mut(item_size)) {
+        let value = value.as_std140();
+        let value = value.as_bytes();
+        slot[..value.len()].copy_from_slice(value);
+    }
+}
+
+/// Writes the `values` into the `bytes`, taking up a slot of `item_size` bytes each. Chunks of the
+/// values are written into their own slices of the `bytes` in parallel on the `task_pool`. Few
+/// values are written on the current thread instead.
+pub fn write_std140_parallel<T: AsStd140 + Sync>(
+    values: &[T],
+    item_size: usize,
+    bytes: &mut [u8],
+    task_pool: &TaskPool,
+) {
+    if values.len() <= VALUES_PER_TASK {
+        write_std140(values, item_size, bytes);
+        return;
+    }
+    task_pool.scope(|scope| {
+        let chunks = values
+            .chunks(VALUES_PER_TASK)
+            .zip(bytes.chunks_mut(VALUES_PER_TASK * item_size));
+        for (values, bytes) in chunks {
+            scope.spawn(async move {
+                write_std140(values, item_size, bytes);
+            });
+        }
+    });
+}
+
+/// Unchanged bytes between two changed ranges up to this size are uploaded as well, since copying
+/// a few more bytes is cheaper than issuing another write.
+pub const MAX_COALESCED_GAP: usize = 1024;
+
+/// Passes the ranges of the `current` bytes that differ from the `uploaded` bytes to `write` and
+/// copies them to the `uploaded` bytes. Everything is written if `force_upload` is set. The
+/// `ranges` are only used to store the changed ranges without allocating.
+fn upload_changes(
+    uploaded: &mut [u8],
+    current: &[u8],
+    item_size: usize,
+    force_upload: bool,
+    ranges: &mut Vec<Range<usize>>,
+    write: &mut dyn FnMut(usize, &[u8]),
+) -> UniformWrites {
+    let changed_count = if force_upload {
+        ranges.clear();
+        ranges.push(0..current.len());
+        current.len() / item_size
+    } else {
+        changed_ranges(uploaded, current, item_size, ranges)
+    };
+    let mut writes = UniformWrites {
+        values: changed_count as u64,
+        ..Default::default()
+    };
+    for range in ranges.drain(..) {
+        write(range.start, &current[range.clone()]);
+        writes.bytes += range.len() as u64;
+        writes.writes += 1;
+        uploaded[range.clone()].copy_from_slice(&current[range]);
+    }
+    writes
+}
+
+/// Replaces the `ranges` with the byte ranges of the items of `item_size` bytes that differ
+/// between `previous` and `current`, coalesced with [`coalesce_ranges`], and returns the number of
+/// differing items.
+fn changed_ranges(
+    previous: &[u8],
+    current: &[u8],
+    item_size: usize,
+    ranges: &mut Vec<Range<usize>>,
+) -> usize {
+    let mut changed_count = 0;
+    let items = previous.chunks(item_size).zip(current.chunks(item_size));
+    let changed = items
+        .enumerate()
+        .filter(|(_, (previous, current))| previous != current)
+        .map(|(index, (_, current))| {
+            changed_count += 1;
+            index * item_size..index * item_size + current.len()
+        });
+    coalesce_ranges(changed, MAX_COALESCED_GAP, ranges);
+    changed_count
+}
+
+/// Replaces the `coalesced` ranges with the sorted and non-overlapping `ranges`, merging the
+/// ranges that are at most `max_gap` apart, so that they can be written with as few writes as
+/// possible.
+pub fn coalesce_ranges(
+    ranges: impl IntoIterator<Item = Range<usize>>,
+    max_gap: usize,
+    coalesced: &mut Vec<Range<usize>>,
+) {
+    coalesced.clear();
+    for range in ranges {
+        match coalesced.last_mut() {
+            Some(last) if range.start - last.end <= max_gap => last.end = range.end,
+            _ => coalesced.push(range),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        upload_changed_values, upload_changes, write_std140_parallel, DynamicUniformVec,
+        UniformVec, UniformWrites, MAX_COALESCED_GAP,
+    };
+    use crate::{
+        counting_allocator::allocations,
+        diagnostic::RenderStatistics,
+        render_resource::{
+            std140::{self, AsStd140, Std140},
+            BufferGrowthPolicy,
+        },
+        renderer::{FramesInFlight, RenderResourceLimits},
+    };
+    use bevy_math::{Mat4, Vec4};
+    use bevy_tasks::TaskPool;
+    use std::ops::Range;
+
+    fn changed_ranges(
+        previous: &[u8],
+        current: &[u8],
+        item_size: usize,
+    ) -> (Vec<Range<usize>>, usize) {
+        let mut ranges = Vec::new();
+        let changed_count = super::changed_ranges(previous, current, item_size, &mut ranges);
+        (ranges, changed_count)
+    }
+
+    fn coalesce_ranges(
+        ranges: impl IntoIterator<Item = Range<usize>>,
+        max_gap: usize,
+    ) -> Vec<Range<usize>> {
+        let mut coalesced = Vec::new();
+        super::coalesce_ranges(ranges, max_gap, &mut coalesced);
+        coalesced
+    }
+
+    #[test]
+    fn only_changed_items_are_uploaded() {
+        let item_size = 16;
+        let uploaded = (0..10_000u32)
+            .flat_map(|entity| [entity.to_le_bytes(); 4].concat())
+            .collect::<Vec<_>>();
+
+        // static values don't cause any writes
+        assert_eq!(changed_ranges(&uploaded, &uploaded, item_size), (vec![], 0));
+
+        let mut current = uploaded.clone();
+        current[4242 * item_size + 3] = 255;
+        assert_eq!(
+            changed_ranges(&uploaded, &current, item_size),
+            (vec![4242 * item_size..4243 * item_size], 1)
+        );
+
+        // nearby changes are uploaded with a single write
+        current[4243 * item_size] = 255;
+        current[4245 * item_size] = 255;
+        current[9999 * item_size] = 255;
+        assert_eq!(
+            changed_ranges(&uploaded, &current, item_size),
+            (
+                vec![
+                    4242 * item_size..4246 * item_size,
+                    9999 * item_size..10_000 * item_size
+                ],
+                4
+            )
+        );
+    }
+
+    #[test]
+    fn ranges_are_coalesced() {
+        assert!(coalesce_ranges([], 0).is_empty());
+        assert_eq!(coalesce_ranges([0..4, 4..8, 12..16], 0), vec![0..8, 12..16]);
+        assert_eq!(coalesce_ranges([0..4, 4..8, 12..16], 4), vec![0..16]);
+        assert_eq!(
+            coalesce_ranges([0..256, 1536..1792, 2048..2304], MAX_COALESCED_GAP),
+            vec![0..256, 1536..2304]
+        );
+
+        // every changed dynamic uniform of a scattered subset of 10k entities
+        let item_size = 256;
+        let changed = (0..10_000)
+            .step_by(3)
+            .map(|i| i * item_size..(i + 1) * item_size);
+        assert_eq!(changed.clone().count(), 3334);
+        assert_eq!(
+            coalesce_ranges(changed, MAX_COALESCED_GAP),
+            vec![0..10_000 * item_size]
+        );
+    }
+
+    #[test]
+    fn oversized_uniforms_are_not_allocated() {
+        let limits = wgpu::Limits::downlevel_defaults();
+        // 19200 bytes don't fit into the 16384 bytes of a uniform binding on all devices
+        let err = UniformVec::<[Mat4; 300]>::default()
+            .validate_binding(&limits)
+            .unwrap_err();
+        assert_eq!((err.size, err.max_size), (19200, 16384));
+        assert!(UniformVec::<Mat4>::default()
+            .validate_binding(&limits)
+            .is_ok());
+    }
+
+    #[test]
+    fn oversized_uniforms_are_not_uploaded() {
+        let limits = wgpu::Limits::downlevel_defaults();
+        let mut uniforms = UniformVec::<[Mat4; 300]>::default();
+        uniforms.push([Mat4::IDENTITY; 300]);
+        assert!(uniforms.resize(1, &limits).is_err());
+
+        // no buffer is created, so the values aren't uploaded
+        assert_eq!(uniforms.capacity(), 0);
+        assert!(uniforms.scratch.is_empty());
+        assert!(uniforms.binding().is_none());
+    }
+
+    #[derive(AsStd140, Clone, PartialEq)]
+    struct EntityUniform {
+        value: f32,
+    }
+
+    #[test]
+    fn unchanged_values_are_not_encoded() {
+        let item_size = 256;
+        let previous = (0..10_000)
+            .map(|i| EntityUniform { value: i as f32 })
+            .collect::<Vec<_>>();
+        let mut values = previous.clone();
+        let mut scratch = vec![0; values.len() * item_size];
+        let mut ranges = Vec::new();
+        let mut upload =
+            |values: &[EntityUniform], previous: &[EntityUniform], scratch: &mut [u8]| {
+                let mut written = Vec::new();
+                let writes = upload_changed_values(
+                    values,
+                    previous,
+                    EntityUniform::eq,
+                    scratch,
+                    item_size,
+                    false,
+                    &mut ranges,
+                    &mut |offset, bytes| written.push(offset..offset + bytes.len()),
+                );
+                (writes, written)
+            };
+
+        // every value is encoded into a new buffer
+        let (writes, written) = upload(&values, &[], &mut scratch);
+        assert_eq!((writes.values, writes.bytes), (10_000, 10_000 * 256));
+        assert_eq!(written, [0..10_000 * item_size]);
+        assert_eq!(
+            upload(&values, &previous, &mut scratch),
+            (UniformWrites::default(), vec![])
+        );
+
+        // the slots of the unchanged values aren't touched
+        scratch.fill(0xff);
+        values[4242].value = -1.0;
+        let (writes, written) = upload(&values, &previous, &mut scratch);
+        assert_eq!((writes.values, writes.bytes, writes.writes), (1, 256, 1));
+        let slot = 4242 * item_size..4243 * item_size;
+        assert_eq!(written, [slot.clone()]);
+        assert_eq!(scratch[slot.start..slot.start + 4], (-1.0f32).to_ne_bytes());
+        assert!(scratch[..slot.start].iter().all(|byte| *byte == 0xff));
+        assert!(scratch[slot.end..].iter().all(|byte| *byte == 0xff));
+    }
+
+    #[test]
+    fn each_frame_in_flight_has_its_own_uniform_buffer() {
+        let mut uniforms = DynamicUniformVec::<EntityUniform>::default()
+            .with_frames_in_flight(&FramesInFlight::new(3));
+        assert_eq!(uniforms.frames_in_flight(), 3);
+        let mut frames = Vec::new();
+        for frame in 0..6 {
+            uniforms.clear();
+            for i in 0..=frame {
+                // the offsets don't depend on the frame
+                assert_eq!(uniforms.push(EntityUniform { value: i as f32 }), i * 256);
+            }
+            frames.push(uniforms.current);
+        }
+        assert_eq!(frames, [1, 2, 0, 1, 2, 0]);
+        // the values of the previous frames are kept until their buffer is used again
+        let lens = uniforms
+            .ring
+            .iter()
+            .map(|ring| ring.len())
+            .collect::<Vec<_>>();
+        assert_eq!(lens, [6, 4, 5]);
+        assert!(uniforms.ring.iter().all(|ring| ring.item_size == 256));
+    }
+
+    #[test]
+    fn dynamic_offsets_follow_the_device_alignment() {
+        let values = (0..3)
+            .map(|i| TransformUniform {
+                transform: Mat4::IDENTITY,
+                color: Vec4::splat(i as f32 + 1.0),
+            })
+            .collect::<Vec<_>>();
+        // the values take up 80 bytes
+        for (alignment, item_size) in [(64, 128), (256, 256), (1024, 1024)] {
+            let limits = RenderResourceLimits {
+                min_uniform_buffer_offset_alignment: alignment,
+                ..Default::default()
+            };
+            let mut uniforms = DynamicUniformVec::<TransformUniform>::new(&limits);
+            assert_eq!(uniforms.item_size(), item_size);
+            let offsets = values
+                .iter()
+                .map(|value| uniforms.push(value.clone()))
+                .collect::<Vec<_>>();
+            assert_eq!(offsets, [0, item_size as u32, 2 * item_size as u32]);
+
+            // each value is written to the slot at its offset
+            let mut bytes = vec![0; values.len() * item_size];
+            write_std140_parallel(&values, item_size, &mut bytes, &TaskPool::new());
+            for (value, offset) in values.iter().zip(offsets) {
+                let value = value.as_std140();
+                let value = value.as_bytes();
+                let offset = offset as usize;
+                assert_eq!(&bytes[offset..offset + value.len()], value);
+            }
+        }
+    }
+
+    #[test]
+    fn capacity_follows_spawned_entities() {
+        let limits = wgpu::Limits::default();
+        let mut uniforms = UniformVec::<EntityUniform>::default();
+        let mut capacities = Vec::new();
+        // 1k entities are spawned and despawned again and again
+        for _ in 0..10 {
+            for len in [1000, 0] {
+                let mut reallocations = 0;
+                for _frame in 0..BufferGrowthPolicy::default().shrink_delay {
+                    uniforms.clear();
+                    for i in 0..len {
+                        uniforms.push(EntityUniform { value: i as f32 });
+                    }
+                    if let Some(capacity) = uniforms.next_capacity() {
+                        uniforms.resize(capacity, &limits).unwrap();
+                        reallocations += 1;
+                    }
+                }
+                capacities.push((uniforms.capacity(), reallocations));
+            }
+        }
+        // the uniform buffer is reallocated smaller after the entities despawned and doesn't keep
+        // growing
+        assert!(capacities
+            .chunks(2)
+            .all(|capacities| capacities == [(2000, 1), (1, 1)]));
+    }
+
+    #[derive(AsStd140, Clone)]
+    struct TransformUniform {
+        transform: Mat4,
+        color: Vec4,
+    }
+
+    #[test]
+    fn parallel_writes_match_serial_writes() {
+        let values = (0..5000)
+            .map(|i| TransformUniform {
+                transform: Mat4::from_translation([i as f32, 0.0, 0.0].into()),
+                color: Vec4::splat(i as f32),
+            })
+            .collect::<Vec<_>>();
+        let item_size = TransformUniform::std140_size_static();
+        assert_eq!(item_size, 80);
+        assert_eq!(<TransformUniform as AsStd140>::Output::ALIGNMENT, 16);
+
+        let mut serial = vec![0; values.len() * item_size];
+        std140::Writer::new(&mut serial[..])
+            .write(values.as_slice())
+            .unwrap();
+        let mut parallel = vec![0; values.len() * item_size];
+        write_std140_parallel(&values, item_size, &mut parallel, &TaskPool::new());
+        assert!(serial == parallel);
+    }
+
+    #[test]
+    fn static_scenes_stop_uploading_after_the_first_frame() {
+        let item_size = 256;
+        let statistics = RenderStatistics::default();
+        // a fresh uniform buffer is zeroed
+        let mut uploaded = vec![0; 1000 * item_size];
+        let current = (0..1000u32)
+            .flat_map(|entity| [entity.to_le_bytes(); 64].concat())
+            .collect::<Vec<_>>();
+        let mut ranges = Vec::new();
+        let mut upload = |uploaded: &mut [u8], current: &[u8], force_upload| {
+            upload_changes(
+                uploaded,
+                current,
+                item_size,
+                force_upload,
+                &mut ranges,
+                &mut |_, _| {},
+            )
+        };
+        let mut frame_statistics = Vec::new();
+        for _frame in 0..10 {
+            let writes = upload(&mut uploaded, &current, false);
+            statistics.record_uniform_writes(writes);
+            frame_statistics.push(statistics.take());
+        }
+        // only the first entity is zero, and thus already uploaded
+        assert_eq!(frame_statistics[0].dirty_uniforms, 999);
+        assert_eq!(frame_statistics[0].uniform_bytes_written, 999 * 256);
+        assert_eq!(frame_statistics[0].uniform_buffer_writes, 1);
+        assert!(frame_statistics[1..]
+            .iter()
+            .all(|statistics| *statistics == Default::default()));
+
+        let writes = upload(&mut uploaded, &current, true);
+        assert_eq!(
+            writes,
+            UniformWrites {
+                bytes: 1000 * 256,
+                writes: 1,
+                values: 1000,
+            }
+        );
+    }
+
+    #[test]
+    fn each_uniform_buffer_in_flight_is_written_once() {
+        let limits = wgpu::Limits::default();
+        let statistics = RenderStatistics::default();
+        let mut uniforms = DynamicUniformVec::<EntityUniform>::default()
+            .with_frames_in_flight(&FramesInFlight::default());
+        let frame = |uniforms: &mut DynamicUniformVec<EntityUniform>| {
+            // e.g. the uniform of a static view
+            uniforms.clear();
+            uniforms.push(EntityUniform { value: 1.0 });
+            let uniforms = uniforms.current_mut();
+            if let Some(capacity) = uniforms.next_capacity() {
+                uniforms.resize(capacity, &limits).unwrap();
+            }
+            let item_size = uniforms.item_size;
+            statistics.record_uniform_writes(uniforms.upload(
+                |values, bytes| super::write_std140(values, item_size, bytes),
+                &mut |_, _| {},
+            ));
+            statistics.take()
+        };
+
+        for _ in 0..FramesInFlight::default().count() {
+            let frame = frame(&mut uniforms);
+            assert_eq!((frame.dirty_uniforms, frame.uniform_buffer_writes), (1, 1));
+        }
+        assert_eq!(frame(&mut uniforms).uniform_buffer_writes, 0);
+    }
+
+    #[test]
+    fn uploading_changes_does_not_allocate() {
+        let item_size = 256;
+        let mut uploaded = vec![0; 1000 * item_size];
+        let mut current = uploaded.clone();
+        let mut ranges = Vec::new();
+        let mut written = 0;
+        let mut upload = |uploaded: &mut [u8], current: &[u8]| {
+            upload_changes(
+                uploaded,
+                current,
+                item_size,
+                false,
+                &mut ranges,
+                &mut |_, bytes| written += bytes.len(),
+            )
+        };
+        // the first frame with scattered changes allocates the ranges
+        for entity in (0..1000).step_by(100) {
+            current[entity * item_size] = 1;
+        }
+        upload(&mut uploaded, &current);
+
+        let before = allocations();
+        for frame in 2..10 {
+            for entity in (0..1000).step_by(100) {
+                current[entity * item_size] = frame;
+            }
+            assert_eq!(upload(&mut uploaded, &current).writes, 10);
+        }
+        assert_eq!(allocations(), before);
+        assert_eq!(written, 9 * 10 * item_size);
     }
 }
@@ -0,0 +1,1187 @@
+use crate::{
+    color::Color,
+    mesh::HalfFloat,
+    render_phase::TrackedRenderPass,
+    render_resource::{
+        align_vertex_stride, Buffer, BufferDescriptor, BufferGrowthPolicy, BufferUsages,
+        VertexBufferLayout,
+    },
+    renderer::{RenderDevice, RenderQueue},
+};
+use bevy_core::{Pod, Zeroable};
+use bevy_math::{IVec2, IVec3, IVec4, Mat4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+use thiserror::Error;
+use wgpu::{VertexAttribute, VertexFormat, VertexStepMode};
+
+/// Rust types which can be uploaded as vertex or instance attributes, described by the
+/// [`VertexFormats`](VertexFormat) they occupy.
+///
+/// Types too large for a single attribute, like `[f32; 8]` or [`Mat4`], span multiple consecutive
+/// attributes. The formats always describe the memory layout of the type, so the type can be
+/// written to a vertex buffer with [`cast_slice`](bevy_core::cast_slice).
+///
+/// Integer types map to integer formats (e.g. `[u16; 4]` to [`VertexFormat::Uint16x4`]), which a
+/// shader reads as `vec4<u32>` / `uvec4`. Whether integer data is read as integers or as
+/// normalized floats is controlled by the format rather than the Rust type, so to read the same
+/// data as normalized floats, pass [`VertexFormat::Unorm16x4`] to
+/// [`VertexBufferLayout::from_vertex_formats`] instead.
+pub trait AsVertexFormats {
+    fn as_vertex_formats() -> &'static [VertexFormat];
+}
+
+macro_rules! impl_as_vertex_formats {
+    ($($ty:ty => [$($format:ident),+]),+ $(,)?) => {
+        $(
+            impl AsVertexFormats for $ty {
+                fn as_vertex_formats() -> &'static [VertexFormat] {
+                    &[$(VertexFormat::$format),+]
+                }
+            }
+        )+
+    };
+}
+
+impl_as_vertex_formats!(
+    f32 => [Float32],
+    [f32; 1] => [Float32],
+    [f32; 2] => [Float32x2],
+    [f32; 3] => [Float32x3],
+    [f32; 4] => [Float32x4],
+    [f32; 8] => [Float32x4, Float32x4],
+    [f32; 12] => [Float32x4, Float32x4, Float32x4],
+    [f32; 16] => [Float32x4, Float32x4, Float32x4, Float32x4],
+    Vec2 => [Float32x2],
+    Vec3 => [Float32x3],
+    Vec4 => [Float32x4],
+    Mat4 => [Float32x4, Float32x4, Float32x4, Float32x4],
+    u32 => [Uint32],
+    [u32; 2] => [Uint32x2],
+    [u32; 3] => [Uint32x3],
+    [u32; 4] => [Uint32x4],
+    i32 => [Sint32],
+    [i32; 2] => [Sint32x2],
+    [i32; 3] => [Sint32x3],
+    [i32; 4] => [Sint32x4],
+    [u16; 2] => [Uint16x2],
+    [u16; 4] => [Uint16x4],
+    [i16; 2] => [Sint16x2],
+    [i16; 4] => [Sint16x4],
+    [u8; 2] => [Uint8x2],
+    [u8; 4] => [Uint8x4],
+    [i8; 2] => [Sint8x2],
+    [i8; 4] => [Sint8x4],
+    UVec2 => [Uint32x2],
+    UVec3 => [Uint32x3],
+    UVec4 => [Uint32x4],
+    IVec2 => [Sint32x2],
+    IVec3 => [Sint32x3],
+    IVec4 => [Sint32x4],
+);
+
+/// A [`Color`] packed into four bytes, which shaders read as a linear RGBA `vec4<f32>` through
+/// [`VertexFormat::Unorm8x4`].
+///
+/// This uses a quarter of the memory of the color's `[f32; 4]` representation, which adds up for
+/// per-instance colors of many instances, at the cost of 8 bits of precision per channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct CompactColor(pub u32);
+
+// SAFETY: `CompactColor` is a transparent wrapper around a `u32`
+unsafe impl Zeroable for CompactColor {}
+unsafe impl Pod for CompactColor {}
+
+impl From<Color> for CompactColor {
+    fn from(color: Color) -> Self {
+        Self(color.as_linear_rgba_u32())
+    }
+}
+
+impl From<CompactColor> for Color {
+    fn from(color: CompactColor) -> Self {
+        let [red, green, blue, alpha] = color.0.to_le_bytes();
+        Color::rgba_linear(
+            red as f32 / 255.0,
+            green as f32 / 255.0,
+            blue as f32 / 255.0,
+            alpha as f32 / 255.0,
+        )
+    }
+}
+
+impl_as_vertex_formats!(CompactColor => [Unorm8x4]);
+
+impl VertexBufferLayout {
+    /// Creates a new densely packed [`VertexBufferLayout`] for a type implementing
+    /// [`AsVertexFormats`]. See [`VertexBufferLayout::from_vertex_formats`].
+    pub fn from_type<T: AsVertexFormats>(step_mode: VertexStepMode) -> Self {
+        Self::from_vertex_formats(step_mode, T::as_vertex_formats().iter().copied())
+    }
+}
+
+/// How the fields of vertex or instance data are laid out in vertex buffers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum VertexDataLayout {
+    #[default]
+    /// All fields are interleaved in a single buffer, whose stride is the size of all fields.
+    Interleaved,
+    /// Every field is tightly packed into its own buffer, whose stride is the size of the field.
+    /// This allows updating fields which change at different frequencies separately.
+    Separate,
+}
+
+impl VertexBufferLayout {
+    /// Creates the densely packed layouts of the vertex buffers holding data with the given
+    /// `fields`, each described by the formats of its attributes (e.g. through
+    /// [`AsVertexFormats::as_vertex_formats`]).
+    ///
+    /// Returns a single layout for [`VertexDataLayout::Interleaved`] and one layout per field
+    /// for [`VertexDataLayout::Separate`]. Either way, the attributes use consecutive shader
+    /// locations starting at zero, so shaders don't depend on the chosen layout. The strides are
+    /// rounded up to the alignment vertex buffers require, e.g. a buffer of a single
+    /// [`VertexFormat::Uint8x2`] field has a stride of 4 bytes. The interleaved attributes are
+    /// aligned to their size up to 4 bytes as well, so a [`VertexFormat::Unorm8x4`] following a
+    /// [`VertexFormat::Uint8x2`] starts at offset 4.
+    pub fn from_fields<'a>(
+        step_mode: VertexStepMode,
+        data_layout: VertexDataLayout,
+        fields: impl IntoIterator<Item = &'a [VertexFormat]>,
+    ) -> Vec<VertexBufferLayout> {
+        let mut layouts = match data_layout {
+            VertexDataLayout::Interleaved => {
+                let mut offset = 0;
+                let attributes = fields
+                    .into_iter()
+                    .flatten()
+                    .enumerate()
+                    .map(|(shader_location, &format)| {
+                        let alignment = attribute_alignment(format);
+                        offset = (offset + alignment - 1) / alignment * alignment;
+                        let attribute = VertexAttribute {
+                            format,
+                            offset,
+                            shader_location: shader_location as u32,
+                        };
+                        offset += format.size();
+                        attribute
+                    })
+                    .collect();
+                vec![VertexBufferLayout {
+                    array_stride: offset,
+                    step_mode,
+                    attributes,
+                }]
+            }
+            VertexDataLayout::Separate => {
+                let mut first_location = 0;
+                fields
+                    .into_iter()
+                    .map(|formats| {
+                        let layout = VertexBufferLayout::from_vertex_formats(
+                            step_mode,
+                            formats.iter().copied(),
+                        )
+                        .with_first_shader_location(first_location);
+                        first_location += formats.len() as u32;
+                        layout
+                    })
+                    .collect()
+            }
+        };
+        for layout in &mut layouts {
+            layout.array_stride = align_vertex_stride(layout.array_stride);
+        }
+        layouts
+    }
+}
+
+/// Returns the alignment of an attribute of `format` in an interleaved vertex buffer, which is the
+/// size of the format, but at most 4 bytes.
+fn attribute_alignment(format: VertexFormat) -> u64 {
+    format.size().min(4)
+}
+
+/// Pads the interleaved vertex data in `buffer` to the offset of the next attribute of `format`.
+/// Every vertex starts at an offset aligned to 4 bytes, so the attribute is aligned within the
+/// vertex as well.
+fn pad_to_attribute(buffer: &mut Vec<u8>, format: VertexFormat) {
+    let alignment = attribute_alignment(format) as usize;
+    buffer.resize((buffer.len() + alignment - 1) / alignment * alignment, 0);
+}
+
+/// The contents of the vertex buffers described by [`VertexBufferLayout::from_fields`].
+///
+/// The fields are either pushed as the bytes of their declared formats with
+/// [`VertexDataBuffers::push`], or converted to them with [`VertexDataBuffers::push_as_formats`].
+/// The latter allows declaring e.g. [`VertexFormat::Float16x4`] for a per-instance color, which
+/// halves its size, while still gathering it from `f32`s.
+///
+/// The contents are uploaded with [`VertexDataBuffers::write_buffers`] and bound for draws with
+/// [`VertexDataBuffers::set_vertex_buffers`].
+#[derive(Clone, Debug)]
+pub struct VertexDataBuffers {
+    data_layout: VertexDataLayout,
+    field_formats: Vec<Vec<VertexFormat>>,
+    field_sizes: Vec<usize>,
+    buffers: Vec<Vec<u8>>,
+    len: usize,
+    gpu_buffers: Vec<GpuVertexData>,
+}
+
+/// The vertex buffer holding the contents of one of the [`VertexDataBuffers`].
+#[derive(Clone, Debug, Default)]
+struct GpuVertexData {
+    buffer: Option<Buffer>,
+    capacity: usize,
+    underused_writes: u32,
+    /// The number of bytes written to the buffer.
+    len: usize,
+}
+
+impl VertexDataBuffers {
+    pub fn new<'a>(
+        data_layout: VertexDataLayout,
+        fields: impl IntoIterator<Item = &'a [VertexFormat]>,
+    ) -> Self {
+        let field_formats = fields
+            .into_iter()
+            .map(|formats| formats.to_vec())
+            .collect::<Vec<_>>();
+        let field_sizes = field_formats
+            .iter()
+            .map(|formats| formats.iter().map(|format| format.size() as usize).sum())
+            .collect::<Vec<_>>();
+        let buffer_count = match data_layout {
+            VertexDataLayout::Interleaved => 1,
+            VertexDataLayout::Separate => field_sizes.len(),
+        };
+        Self {
+            data_layout,
+            field_formats,
+            field_sizes,
+            buffers: vec![Vec::new(); buffer_count],
+            len: 0,
+            gpu_buffers: vec![GpuVertexData::default(); buffer_count],
+        }
+    }
+
+    /// Pads the data of the last vertex or instance to the stride of its buffer.
+    fn finish_push(&mut self) {
+        for buffer in &mut self.buffers {
+            let stride_end = align_vertex_stride(buffer.len() as u64) as usize;
+            buffer.resize(stride_end, 0);
+        }
+        self.len += 1;
+    }
+
+    /// Appends the data of a single vertex or instance, given as one value per attribute of its
+    /// fields. Every value is converted to the declared format of its attribute with
+    /// [`WriteAsFormat`], so e.g. `f32`s are packed as half precision floats for the
+    /// `Float16` formats. Fields spanning multiple attributes, like a [`Mat4`], take a value
+    /// per attribute, e.g. the columns of the matrix.
+    ///
+    /// Nothing is appended if a value can't be converted to the format of its attribute.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of values doesn't match the number of attributes of the fields.
+    pub fn push_as_formats(
+        &mut self,
+        attributes: &[&dyn WriteAsFormat],
+    ) -> Result<(), UnsupportedVertexFormat> {
+        let attribute_count = self.field_formats.iter().map(Vec::len).sum::<usize>();
+        assert_eq!(
+            attributes.len(),
+            attribute_count,
+            "expected {} attributes of vertex data",
+            attribute_count
+        );
+        let lengths = self.buffers.iter().map(Vec::len).collect::<Vec<_>>();
+        let mut attributes = attributes.iter();
+        for (index, formats) in self.field_formats.iter().enumerate() {
+            let buffer = match self.data_layout {
+                VertexDataLayout::Interleaved => 0,
+                VertexDataLayout::Separate => index,
+            };
+            for format in formats {
+                let value = attributes.next().unwrap();
+                if self.data_layout == VertexDataLayout::Interleaved {
+                    pad_to_attribute(&mut self.buffers[buffer], *format);
+                }
+                if let Err(err) = value.write_as_format(*format, &mut self.buffers[buffer]) {
+                    for (buffer, length) in self.buffers.iter_mut().zip(&lengths) {
+                        buffer.truncate(*length);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        self.finish_push();
+        Ok(())
+    }
+
+    /// Appends the data of a single vertex or instance, given as the bytes of each of its fields.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number or the sizes of the fields don't match the fields of the buffers.
+    pub fn push(&mut self, fields: &[&[u8]]) {
+        assert_eq!(
+            fields.len(),
+            self.field_sizes.len(),
+            "expected {} fields of vertex data",
+            self.field_sizes.len()
+        );
+        for (index, (field, size)) in fields.iter().zip(&self.field_sizes).enumerate() {
+            assert_eq!(
+                field.len(),
+                *size,
+                "field {} of the vertex data has the wrong size",
+                index
+            );
+            match self.data_layout {
+                VertexDataLayout::Interleaved => {
+                    let mut bytes = *field;
+                    for format in &self.field_formats[index] {
+                        let (attribute, rest) = bytes.split_at(format.size() as usize);
+                        pad_to_attribute(&mut self.buffers[0], *format);
+                        self.buffers[0].extend_from_slice(attribute);
+                        bytes = rest;
+                    }
+                }
+                VertexDataLayout::Separate => self.buffers[index].extend_from_slice(field),
+            }
+        }
+        self.finish_push();
+    }
+
+    pub fn data_layout(&self) -> VertexDataLayout {
+        self.data_layout
+    }
+
+    /// Returns the number of vertices or instances pushed since the buffers were last cleared.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the contents of the buffers, in the order of the layouts of
+    /// [`VertexBufferLayout::from_fields`]. Every buffer is bound to its own vertex buffer slot.
+    pub fn buffers(&self) -> &[Vec<u8>] {
+        &self.buffers
+    }
+
+    /// Uploads the contents of the buffers to their vertex buffers, which are reallocated
+    /// according to the default [`BufferGrowthPolicy`] if they are too small.
+    pub fn write_buffers(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
+        let growth_policy = BufferGrowthPolicy::default();
+        for (data, gpu_data) in self.buffers.iter().zip(&mut self.gpu_buffers) {
+            gpu_data.len = data.len();
+            if data.is_empty() {
+                continue;
+            }
+            let capacity = growth_policy.next_capacity(
+                gpu_data.capacity,
+                data.len(),
+                &mut gpu_data.underused_writes,
+            );
+            if capacity != gpu_data.capacity || gpu_data.buffer.is_none() {
+                gpu_data.capacity = capacity;
+                gpu_data.buffer = Some(render_device.create_buffer(&BufferDescriptor {
+                    label: Some("vertex_data_buffer"),
+                    size: capacity as u64,
+                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }));
+            }
+            render_queue.write_buffer(gpu_data.buffer.as_ref().unwrap(), 0, data);
+        }
+    }
+
+    /// Binds the vertex buffers written by [`VertexDataBuffers::write_buffers`] to consecutive
+    /// slots starting at `first_slot`, matching the layouts of [`VertexBufferLayout::from_fields`]
+    /// declared at the same position of the pipeline. Returns `false` without binding any buffer
+    /// if nothing was written.
+    pub fn set_vertex_buffers<'a>(
+        &'a self,
+        pass: &mut TrackedRenderPass<'a>,
+        first_slot: usize,
+    ) -> bool {
+        let written = |gpu_data: &GpuVertexData| gpu_data.buffer.is_some() && gpu_data.len > 0;
+        if !self.gpu_buffers.iter().all(written) {
+            return false;
+        }
+        for (index, gpu_data) in self.gpu_buffers.iter().enumerate() {
+            let buffer = gpu_data.buffer.as_ref().unwrap();
+            pass.set_vertex_buffer(first_slot + index, buffer.slice(..gpu_data.len as u64));
+        }
+        true
+    }
+
+    pub fn clear(&mut self) {
+        for buffer in &mut self.buffers {
+            buffer.clear();
+        }
+        self.len = 0;
+    }
+}
+
+/// Writes a value as vertex data of an explicitly chosen [`VertexFormat`], converting it if the
+/// format differs from the one given by [`AsVertexFormats`]. This allows e.g. uploading floats as
+/// [`VertexFormat::Float16x2`] or [`VertexFormat::Unorm8x4`] to save bandwidth.
+///
+/// The layout passed to the pipeline has to declare the same format for the attribute.
+///
+/// Values are always written in little-endian byte order, which is what GPU APIs expect,
+/// regardless of the byte order of the target. [`ReadFromFormat`] reads them back with the same
+/// convention.
+pub trait WriteAsFormat {
+    /// Appends the value converted to `format` to `buffer`. Exactly
+    /// [`format.size()`](VertexFormat::size) bytes are written on success.
+    fn write_as_format(
+        &self,
+        format: VertexFormat,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), UnsupportedVertexFormat>;
+}
+
+/// Reads a value back from vertex data of the given [`VertexFormat`], e.g. written by
+/// [`WriteAsFormat`]. The data is read in little-endian byte order.
+pub trait ReadFromFormat: Sized {
+    /// Reads the value from `bytes`, which have to be exactly
+    /// [`format.size()`](VertexFormat::size) bytes long.
+    fn read_from_format(
+        format: VertexFormat,
+        bytes: &[u8],
+    ) -> Result<Self, UnsupportedVertexFormat>;
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("values of type {ty} can't be converted to or from {format:?}")]
+pub struct UnsupportedVertexFormat {
+    pub ty: &'static str,
+    pub format: VertexFormat,
+}
+
+/// Writes float components as `format`, which has to have as many components. Normalized formats
+/// clamp the components to their range.
+fn write_floats<T>(
+    components: &[f32],
+    format: VertexFormat,
+    buffer: &mut Vec<u8>,
+) -> Result<(), UnsupportedVertexFormat> {
+    use VertexFormat::*;
+    if float_component_count(format) != components.len() {
+        return Err(UnsupportedVertexFormat {
+            ty: std::any::type_name::<T>(),
+            format,
+        });
+    }
+
+    for &component in components {
+        match format {
+            Float32 | Float32x2 | Float32x3 | Float32x4 => {
+                buffer.extend_from_slice(&component.to_le_bytes());
+            }
+            Float16x2 | Float16x4 => {
+                buffer.extend_from_slice(&HalfFloat::from_f32(component).0.to_le_bytes());
+            }
+            Unorm8x2 | Unorm8x4 => buffer.push((component.clamp(0.0, 1.0) * 255.0).round() as u8),
+            Snorm8x2 | Snorm8x4 => {
+                buffer.push(((component.clamp(-1.0, 1.0) * 127.0).round() as i8) as u8);
+            }
+            Unorm16x2 | Unorm16x4 => buffer.extend_from_slice(
+                &((component.clamp(0.0, 1.0) * 65535.0).round() as u16).to_le_bytes(),
+            ),
+            Snorm16x2 | Snorm16x4 => buffer.extend_from_slice(
+                &((component.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_le_bytes(),
+            ),
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of float components of `format`, or zero if it doesn't hold floats.
+fn float_component_count(format: VertexFormat) -> usize {
+    use VertexFormat::*;
+    match format {
+        Float32 => 1,
+        Float32x2 | Float16x2 | Unorm8x2 | Snorm8x2 | Unorm16x2 | Snorm16x2 => 2,
+        Float32x3 => 3,
+        Float32x4 | Float16x4 | Unorm8x4 | Snorm8x4 | Unorm16x4 | Snorm16x4 => 4,
+        _ => 0,
+    }
+}
+
+/// Reads the `N` float components of `format` from `bytes`. The inverse of [`write_floats`], up
+/// to the precision of the format.
+fn read_floats<T, const N: usize>(
+    format: VertexFormat,
+    bytes: &[u8],
+) -> Result<[f32; N], UnsupportedVertexFormat> {
+    use VertexFormat::*;
+    if float_component_count(format) != N || bytes.len() as u64 != format.size() {
+        return Err(UnsupportedVertexFormat {
+            ty: std::any::type_name::<T>(),
+            format,
+        });
+    }
+
+    let component_size = bytes.len() / N;
+    let mut components = [0.0; N];
+    for (component, bytes) in components
+        .iter_mut()
+        .zip(bytes.chunks_exact(component_size))
+    {
+        *component = match format {
+            Float32 | Float32x2 | Float32x3 | Float32x4 => {
+                f32::from_le_bytes(bytes.try_into().unwrap())
+            }
+            Float16x2 | Float16x4 => {
+                HalfFloat(u16::from_le_bytes(bytes.try_into().unwrap())).to_f32()
+            }
+            Unorm8x2 | Unorm8x4 => bytes[0] as f32 / 255.0,
+            Snorm8x2 | Snorm8x4 => (bytes[0] as i8 as f32 / 127.0).max(-1.0),
+            Unorm16x2 | Unorm16x4 => u16::from_le_bytes(bytes.try_into().unwrap()) as f32 / 65535.0,
+            Snorm16x2 | Snorm16x4 => {
+                (i16::from_le_bytes(bytes.try_into().unwrap()) as f32 / 32767.0).max(-1.0)
+            }
+            _ => unreachable!(),
+        };
+    }
+    Ok(components)
+}
+
+macro_rules! impl_write_floats_as_format {
+    ($($ty:ty => |$value:ident| $components:expr),+ $(,)?) => {
+        $(
+            impl WriteAsFormat for $ty {
+                fn write_as_format(
+                    &self,
+                    format: VertexFormat,
+                    buffer: &mut Vec<u8>,
+                ) -> Result<(), UnsupportedVertexFormat> {
+                    let $value = self;
+                    write_floats::<$ty>(&$components, format, buffer)
+                }
+            }
+        )+
+    };
+}
+
+impl_write_floats_as_format!(
+    f32 => |value| [*value],
+    [f32; 2] => |value| *value,
+    [f32; 3] => |value| *value,
+    [f32; 4] => |value| *value,
+    Vec2 => |value| value.to_array(),
+    Vec3 => |value| value.to_array(),
+    Vec4 => |value| value.to_array(),
+    Color => |value| value.as_linear_rgba_f32(),
+);
+
+macro_rules! impl_read_floats_from_format {
+    ($($ty:ty => |$components:ident: [f32; $n:literal]| $value:expr),+ $(,)?) => {
+        $(
+            impl ReadFromFormat for $ty {
+                fn read_from_format(
+                    format: VertexFormat,
+                    bytes: &[u8],
+                ) -> Result<Self, UnsupportedVertexFormat> {
+                    let $components = read_floats::<$ty, $n>(format, bytes)?;
+                    Ok($value)
+                }
+            }
+        )+
+    };
+}
+
+impl_read_floats_from_format!(
+    f32 => |components: [f32; 1]| components[0],
+    [f32; 2] => |components: [f32; 2]| components,
+    [f32; 3] => |components: [f32; 3]| components,
+    [f32; 4] => |components: [f32; 4]| components,
+    Vec2 => |components: [f32; 2]| Vec2::from(components),
+    Vec3 => |components: [f32; 3]| Vec3::from(components),
+    Vec4 => |components: [f32; 4]| Vec4::from(components),
+    Color => |components: [f32; 4]| {
+        let [red, green, blue, alpha] = components;
+        Color::rgba_linear(red, green, blue, alpha)
+    },
+);
+
+impl WriteAsFormat for u32 {
+    fn write_as_format(
+        &self,
+        format: VertexFormat,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), UnsupportedVertexFormat> {
+        match format {
+            // the four bytes of the value are read as separate components
+            VertexFormat::Uint32 | VertexFormat::Uint8x4 | VertexFormat::Unorm8x4 => {
+                buffer.extend_from_slice(&self.to_le_bytes());
+                Ok(())
+            }
+            _ => Err(UnsupportedVertexFormat { ty: "u32", format }),
+        }
+    }
+}
+
+impl ReadFromFormat for u32 {
+    fn read_from_format(
+        format: VertexFormat,
+        bytes: &[u8],
+    ) -> Result<Self, UnsupportedVertexFormat> {
+        match format {
+            VertexFormat::Uint32 | VertexFormat::Uint8x4 | VertexFormat::Unorm8x4
+                if bytes.len() == 4 =>
+            {
+                Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            _ => Err(UnsupportedVertexFormat { ty: "u32", format }),
+        }
+    }
+}
+
+/// Small integer arrays are written tightly packed, as the integer formats with the same
+/// component type and count, or as the normalized formats of the same size, which shaders read
+/// as floats. Buffers can't contain 8 or 16 bit integers in WGSL, so using these as uniforms
+/// requires packing them into `u32`s manually.
+macro_rules! impl_integers_as_format {
+    ($([$component:ident; $n:literal] => [$($format:ident),+]),+ $(,)?) => {
+        $(
+            impl WriteAsFormat for [$component; $n] {
+                fn write_as_format(
+                    &self,
+                    format: VertexFormat,
+                    buffer: &mut Vec<u8>,
+                ) -> Result<(), UnsupportedVertexFormat> {
+                    match format {
+                        $(VertexFormat::$format)|+ => {
+                            for component in self {
+                                buffer.extend_from_slice(&component.to_le_bytes());
+                            }
+                            Ok(())
+                        }
+                        _ => Err(UnsupportedVertexFormat {
+                            ty: std::any::type_name::<[$component; $n]>(),
+                            format,
+                        }),
+                    }
+                }
+            }
+
+            impl ReadFromFormat for [$component; $n] {
+                fn read_from_format(
+                    format: VertexFormat,
+                    bytes: &[u8],
+                ) -> Result<Self, UnsupportedVertexFormat> {
+                    let mut value = [0; $n];
+                    let component_size = std::mem::size_of::<$component>();
+                    match format {
+                        $(VertexFormat::$format)|+
+                            if bytes.len() == std::mem::size_of::<[$component; $n]>() =>
+                        {
+                            for (component, bytes) in
+                                value.iter_mut().zip(bytes.chunks_exact(component_size))
+                            {
+                                *component = $component::from_le_bytes(bytes.try_into().unwrap());
+                            }
+                            Ok(value)
+                        }
+                        _ => Err(UnsupportedVertexFormat {
+                            ty: std::any::type_name::<[$component; $n]>(),
+                            format,
+                        }),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_integers_as_format!(
+    [u8; 2] => [Uint8x2, Unorm8x2],
+    [u8; 4] => [Uint8x4, Unorm8x4],
+    [i8; 2] => [Sint8x2, Snorm8x2],
+    [i8; 4] => [Sint8x4, Snorm8x4],
+    [u16; 2] => [Uint16x2, Unorm16x2],
+    [u16; 4] => [Uint16x4, Unorm16x4],
+    [i16; 2] => [Sint16x2, Snorm16x2],
+    [i16; 4] => [Sint16x4, Snorm16x4],
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AsVertexFormats, CompactColor, ReadFromFormat, UnsupportedVertexFormat, VertexDataBuffers,
+        VertexDataLayout, WriteAsFormat,
+    };
+    use crate::{color::Color, mesh::HalfFloat, render_resource::VertexBufferLayout};
+    use bevy_math::{Mat4, UVec4, Vec2};
+    use wgpu::{VertexAttribute, VertexFormat, VertexStepMode};
+
+    #[test]
+    fn float_array_spans_multiple_attributes() {
+        let layout = VertexBufferLayout::from_type::<[f32; 8]>(VertexStepMode::Instance);
+        assert_eq!(layout.array_stride, 32);
+        assert_eq!(
+            layout.attributes,
+            vec![
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 16,
+                    shader_location: 1,
+                },
+            ]
+        );
+        // the bytes written for the type match the declared formats
+        assert_eq!(
+            bevy_core::cast_slice::<_, u8>(&[[0.0f32; 8]]).len() as u64,
+            layout.array_stride
+        );
+    }
+
+    #[test]
+    fn formats_match_type_sizes() {
+        fn size<T: AsVertexFormats>() -> u64 {
+            T::as_vertex_formats()
+                .iter()
+                .map(|format| format.size())
+                .sum()
+        }
+
+        assert_eq!(size::<f32>(), 4);
+        assert_eq!(size::<[f32; 3]>(), 12);
+        assert_eq!(size::<[f32; 12]>(), 48);
+        assert_eq!(size::<Mat4>(), std::mem::size_of::<Mat4>() as u64);
+        assert_eq!(size::<[u16; 4]>(), 8);
+        assert_eq!(size::<UVec4>(), 16);
+    }
+
+    #[test]
+    fn integer_attributes_are_not_normalized() {
+        // joint indices of a skinned mesh, read as `vec4<u32>` by the shader
+        let layout = VertexBufferLayout::from_type::<[u16; 4]>(VertexStepMode::Vertex);
+        assert_eq!(layout.attributes[0].format, VertexFormat::Uint16x4);
+        assert_eq!(layout.array_stride, 8);
+
+        let joints = [[1u16, 2, 3, 65535], [4, 5, 6, 7]];
+        let bytes: &[u8] = bevy_core::cast_slice(&joints);
+        assert_eq!(bytes.len() as u64, 2 * layout.array_stride);
+        assert_eq!(&bytes[6..8], &65535u16.to_ne_bytes());
+        assert_eq!(
+            <u32 as AsVertexFormats>::as_vertex_formats(),
+            [VertexFormat::Uint32]
+        );
+    }
+
+    #[test]
+    fn compact_color() {
+        let full = VertexBufferLayout::from_type::<[f32; 4]>(VertexStepMode::Instance);
+        let compact = VertexBufferLayout::from_type::<CompactColor>(VertexStepMode::Instance);
+        assert_eq!(full.array_stride, 16);
+        assert_eq!(compact.array_stride, 4);
+        assert_eq!(compact.attributes[0].format, VertexFormat::Unorm8x4);
+
+        for color in [
+            Color::rgba_linear(0.0, 0.25, 0.5, 1.0),
+            Color::rgba_linear(0.1, 0.2, 0.3, 0.4),
+            Color::rgba(0.9, 0.7, 0.01, 0.5),
+        ] {
+            let round_tripped = Color::from(CompactColor::from(color)).as_linear_rgba_f32();
+            for (channel, expected) in round_tripped.into_iter().zip(color.as_linear_rgba_f32()) {
+                assert!((channel - expected).abs() <= 1.0 / 255.0);
+            }
+        }
+    }
+
+    #[test]
+    fn write_as_overridden_format() {
+        let mut buffer = Vec::new();
+        [0.5f32, -2.0]
+            .write_as_format(VertexFormat::Float16x2, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer.len() as u64, VertexFormat::Float16x2.size());
+        assert_eq!(buffer[..2], HalfFloat::from_f32(0.5).0.to_le_bytes());
+
+        buffer.clear();
+        Color::rgba_linear(1.0, 0.0, 0.5, 1.0)
+            .write_as_format(VertexFormat::Unorm8x4, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, [255, 0, 128, 255]);
+
+        buffer.clear();
+        0x0403_0201u32
+            .write_as_format(VertexFormat::Unorm8x4, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, [1, 2, 3, 4]);
+
+        // the number of components has to match
+        assert_eq!(
+            1.0f32.write_as_format(VertexFormat::Float16x2, &mut buffer),
+            Err(UnsupportedVertexFormat {
+                ty: "f32",
+                format: VertexFormat::Float16x2
+            })
+        );
+    }
+
+    #[test]
+    fn interleaved_and_separate_layouts_match() {
+        let fields = [
+            Mat4::as_vertex_formats(),
+            CompactColor::as_vertex_formats(),
+            <[f32; 2]>::as_vertex_formats(),
+        ];
+        let instances = [
+            (Mat4::IDENTITY, CompactColor(0xff00_00ff), [1.0f32, 2.0]),
+            (
+                Mat4::from_scale(bevy_math::Vec3::splat(2.0)),
+                CompactColor(0x1234_5678),
+                [-3.0, 0.5],
+            ),
+        ];
+
+        // reads the bytes of every attribute of every instance through the layouts
+        let read = |data_layout| {
+            let layouts =
+                VertexBufferLayout::from_fields(VertexStepMode::Instance, data_layout, fields);
+            let mut buffers = VertexDataBuffers::new(data_layout, fields);
+            for (transform, color, uv) in &instances {
+                buffers.push(&[
+                    bevy_core::bytes_of(transform),
+                    bevy_core::bytes_of(color),
+                    bevy_core::bytes_of(uv),
+                ]);
+            }
+            assert_eq!(layouts.len(), buffers.buffers().len());
+
+            let mut values = Vec::new();
+            for instance in 0..instances.len() as u64 {
+                for (layout, buffer) in layouts.iter().zip(buffers.buffers()) {
+                    assert_eq!(
+                        buffer.len() as u64,
+                        instances.len() as u64 * layout.array_stride
+                    );
+                    for attribute in &layout.attributes {
+                        let start = (instance * layout.array_stride + attribute.offset) as usize;
+                        let end = start + attribute.format.size() as usize;
+                        values.push((
+                            instance,
+                            attribute.shader_location,
+                            buffer[start..end].to_vec(),
+                        ));
+                    }
+                }
+            }
+            values.sort();
+            values
+        };
+
+        let interleaved = read(VertexDataLayout::Interleaved);
+        let separate = read(VertexDataLayout::Separate);
+        assert_eq!(interleaved.len(), 2 * 6);
+        assert_eq!(interleaved, separate);
+
+        let layouts = VertexBufferLayout::from_fields(
+            VertexStepMode::Instance,
+            VertexDataLayout::Separate,
+            fields,
+        );
+        assert_eq!(
+            layouts
+                .iter()
+                .map(|layout| layout.array_stride)
+                .collect::<Vec<_>>(),
+            [64, 4, 8]
+        );
+        assert_eq!(layouts[1].attributes[0].shader_location, 4);
+        assert_eq!(layouts[2].attributes[0].shader_location, 5);
+    }
+
+    #[test]
+    fn strides_are_aligned() {
+        let fields = [
+            <[u8; 2]>::as_vertex_formats(),
+            CompactColor::as_vertex_formats(),
+        ];
+        let strides = |data_layout| {
+            VertexBufferLayout::from_fields(VertexStepMode::Instance, data_layout, fields)
+                .iter()
+                .map(|layout| layout.array_stride)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(strides(VertexDataLayout::Interleaved), [8]);
+        assert_eq!(strides(VertexDataLayout::Separate), [4, 4]);
+        // the color is aligned to 4 bytes after the two byte field
+        let layouts = VertexBufferLayout::from_fields(
+            VertexStepMode::Instance,
+            VertexDataLayout::Interleaved,
+            fields,
+        );
+        let offsets = layouts[0]
+            .attributes
+            .iter()
+            .map(|attribute| attribute.offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 4]);
+
+        let mut buffers = VertexDataBuffers::new(VertexDataLayout::Separate, fields);
+        for instance in 1..=2u8 {
+            buffers.push(&[&[instance, instance], &[instance; 4]]);
+        }
+        assert_eq!(buffers.len(), 2);
+        // the two byte field is padded to the stride of its buffer
+        assert_eq!(buffers.buffers()[0], [1, 1, 0, 0, 2, 2, 0, 0]);
+        assert_eq!(buffers.buffers()[1], [1, 1, 1, 1, 2, 2, 2, 2]);
+
+        let mut buffers = VertexDataBuffers::new(VertexDataLayout::Interleaved, fields);
+        buffers
+            .push_as_formats(&[&[1u8, 1], &0x0202_0202u32])
+            .unwrap();
+        buffers.push(&[&[3, 3], &[4; 4]]);
+        assert_eq!(
+            buffers.buffers()[0],
+            [1, 1, 0, 0, 2, 2, 2, 2, 3, 3, 0, 0, 4, 4, 4, 4]
+        );
+        buffers.clear();
+        assert!(buffers.is_empty());
+    }
+
+    #[test]
+    fn half_precision_fields_round_trip() {
+        let fields = [
+            <[f32; 4]>::as_vertex_formats(),
+            &[VertexFormat::Float16x4][..],
+            &[VertexFormat::Float16x2][..],
+        ];
+        let layouts = VertexBufferLayout::from_fields(
+            VertexStepMode::Instance,
+            VertexDataLayout::Interleaved,
+            fields,
+        );
+        assert_eq!(layouts[0].array_stride, 16 + 8 + 4);
+        assert_eq!(layouts[0].attributes[1].offset, 16);
+        assert_eq!(layouts[0].attributes[2].offset, 24);
+
+        let values = [
+            [0.1f32, 1.0 / 3.0, -0.0078125, 1.0],
+            [123.456, -2.5, 65504.0, 0.0],
+        ];
+        let mut buffers = VertexDataBuffers::new(VertexDataLayout::Interleaved, fields);
+        for value in &values {
+            buffers
+                .push_as_formats(&[value, value, &[value[0], value[1]]])
+                .unwrap();
+        }
+        let buffer = &buffers.buffers()[0];
+        assert_eq!(buffer.len() as u64, 2 * layouts[0].array_stride);
+
+        // half precision numbers have 10 explicit mantissa bits, so rounding to the nearest one
+        // is off by at most half of the distance to the next one
+        let half_epsilon = 1.0 / 1024.0;
+        let assert_close = |read: &[f32], expected: &[f32]| {
+            for (read, expected) in read.iter().zip(expected) {
+                assert!(
+                    (read - expected).abs() <= expected.abs() * half_epsilon / 2.0,
+                    "{} isn't close to {}",
+                    read,
+                    expected
+                );
+            }
+        };
+        for (instance, value) in buffer.chunks_exact(28).zip(&values) {
+            let full = <[f32; 4]>::read_from_format(VertexFormat::Float32x4, &instance[..16]);
+            assert_eq!(full.unwrap(), *value);
+            let half = <[f32; 4]>::read_from_format(VertexFormat::Float16x4, &instance[16..24]);
+            assert_close(&half.unwrap(), value);
+            let half = <[f32; 2]>::read_from_format(VertexFormat::Float16x2, &instance[24..]);
+            assert_close(&half.unwrap(), &value[..2]);
+        }
+
+        // a value which doesn't match its format leaves the buffers unchanged
+        assert_eq!(
+            buffers.push_as_formats(&[&values[0], &values[0], &1.0f32]),
+            Err(UnsupportedVertexFormat {
+                ty: "f32",
+                format: VertexFormat::Float16x2
+            })
+        );
+        assert_eq!(buffers.buffers()[0].len(), 2 * 28);
+    }
+
+    #[test]
+    fn half_precision_shrinks_large_batches() {
+        const INSTANCES: usize = 100_000;
+        let full = [
+            Mat4::as_vertex_formats(),
+            <[f32; 4]>::as_vertex_formats(),
+            <[f32; 2]>::as_vertex_formats(),
+        ];
+        let half = [
+            Mat4::as_vertex_formats(),
+            &[VertexFormat::Float16x4][..],
+            &[VertexFormat::Float16x2][..],
+        ];
+
+        let gather = |fields: [&[VertexFormat]; 3]| {
+            let mut buffers = VertexDataBuffers::new(VertexDataLayout::Separate, fields);
+            for instance in 0..INSTANCES {
+                let offset = instance as f32 / INSTANCES as f32;
+                let transform = Mat4::from_translation(bevy_math::Vec3::splat(offset));
+                let color = [offset, 1.0 - offset, 0.5, 1.0];
+                buffers
+                    .push_as_formats(&[
+                        &transform.x_axis,
+                        &transform.y_axis,
+                        &transform.z_axis,
+                        &transform.w_axis,
+                        &color,
+                        &[offset, -offset],
+                    ])
+                    .unwrap();
+            }
+            let layouts = VertexBufferLayout::from_fields(
+                VertexStepMode::Instance,
+                buffers.data_layout(),
+                fields,
+            );
+            for (layout, buffer) in layouts.iter().zip(buffers.buffers()) {
+                assert_eq!(buffer.len() as u64, INSTANCES as u64 * layout.array_stride);
+            }
+            buffers
+                .buffers()
+                .iter()
+                .map(|buffer| buffer.len())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            gather(full),
+            [INSTANCES * 64, INSTANCES * 16, INSTANCES * 8]
+        );
+        assert_eq!(gather(half), [INSTANCES * 64, INSTANCES * 8, INSTANCES * 4]);
+    }
+
+    #[test]
+    fn little_endian_byte_patterns() {
+        let write = |value: &dyn WriteAsFormat, format| {
+            let mut buffer = Vec::new();
+            value.write_as_format(format, &mut buffer).unwrap();
+            buffer
+        };
+        assert_eq!(
+            write(&1.0f32, VertexFormat::Float32),
+            [0x00, 0x00, 0x80, 0x3f]
+        );
+        assert_eq!(
+            write(&[1.0f32, -2.0], VertexFormat::Float16x2),
+            [0x00, 0x3c, 0x00, 0xc0]
+        );
+        assert_eq!(
+            write(&Vec2::new(-1.0, 1.0), VertexFormat::Snorm16x2),
+            [0x01, 0x80, 0xff, 0x7f]
+        );
+        assert_eq!(
+            write(&0x1234_5678u32, VertexFormat::Uint32),
+            [0x78, 0x56, 0x34, 0x12]
+        );
+
+        assert_eq!(
+            f32::read_from_format(VertexFormat::Float32, &[0x00, 0x00, 0x80, 0x3f]),
+            Ok(1.0)
+        );
+        assert_eq!(
+            <[f32; 2]>::read_from_format(VertexFormat::Float16x2, &[0x00, 0x3c, 0x00, 0xc0]),
+            Ok([1.0, -2.0])
+        );
+        assert_eq!(
+            Vec2::read_from_format(VertexFormat::Snorm16x2, &[0x01, 0x80, 0xff, 0x7f]),
+            Ok(Vec2::new(-1.0, 1.0))
+        );
+        assert_eq!(
+            u32::read_from_format(VertexFormat::Uint32, &[0x78, 0x56, 0x34, 0x12]),
+            Ok(0x1234_5678)
+        );
+        assert_eq!(
+            <[f32; 4]>::read_from_format(VertexFormat::Unorm8x4, &[0, 51, 255, 255]),
+            Ok([0.0, 0.2, 1.0, 1.0])
+        );
+
+        // the size of the bytes has to match the format
+        assert_eq!(
+            f32::read_from_format(VertexFormat::Float32, &[0x00, 0x00, 0x80]),
+            Err(UnsupportedVertexFormat {
+                ty: "f32",
+                format: VertexFormat::Float32
+            })
+        );
+    }
+
+    #[test]
+    fn small_integers_are_tightly_packed() {
+        fn write<T: WriteAsFormat>(value: T, format: VertexFormat) -> Vec<u8> {
+            let mut buffer = Vec::new();
+            value.write_as_format(format, &mut buffer).unwrap();
+            assert_eq!(buffer.len() as u64, format.size());
+            buffer
+        }
+
+        assert_eq!(write([1u8, 255], VertexFormat::Uint8x2), [1, 255]);
+        assert_eq!(write([1u8, 2, 3, 4], VertexFormat::Unorm8x4), [1, 2, 3, 4]);
+        assert_eq!(write([-1i8, 127], VertexFormat::Sint8x2), [0xff, 0x7f]);
+        assert_eq!(
+            write([-128i8, 0, 1, -2], VertexFormat::Snorm8x4),
+            [0x80, 0, 1, 0xfe]
+        );
+        assert_eq!(
+            write([0x0102u16, 0xfffe], VertexFormat::Uint16x2),
+            [0x02, 0x01, 0xfe, 0xff]
+        );
+        assert_eq!(
+            write([1u16, 2, 3, 0x8000], VertexFormat::Unorm16x4),
+            [1, 0, 2, 0, 3, 0, 0x00, 0x80]
+        );
+        assert_eq!(
+            write([-2i16, 0x0304], VertexFormat::Snorm16x2),
+            [0xfe, 0xff, 0x04, 0x03]
+        );
+        assert_eq!(
+            write([-1i16, 1, -32768, 32767], VertexFormat::Sint16x4),
+            [0xff, 0xff, 1, 0, 0x00, 0x80, 0xff, 0x7f]
+        );
+
+        assert_eq!(
+            <[i16; 2]>::read_from_format(VertexFormat::Snorm16x2, &[0xfe, 0xff, 0x04, 0x03]),
+            Ok([-2, 0x0304])
+        );
+        assert_eq!(
+            <[u8; 4]>::read_from_format(VertexFormat::Uint8x4, &[1, 2, 3, 4]),
+            Ok([1, 2, 3, 4])
+        );
+
+        // the signedness of the format has to match the type
+        let mut buffer = Vec::new();
+        assert_eq!(
+            [1u16, 2].write_as_format(VertexFormat::Sint16x2, &mut buffer),
+            Err(UnsupportedVertexFormat {
+                ty: "[u16; 2]",
+                format: VertexFormat::Sint16x2
+            })
+        );
+        assert!(buffer.is_empty());
+    }
+}
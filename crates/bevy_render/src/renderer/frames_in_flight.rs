@@ -0,0 +1,183 @@
+use crate::renderer::{RenderDevice, RenderQueue};
+use bevy_ecs::system::{Res, ResMut};
+use bevy_utils::tracing::info_span;
+use futures_lite::future;
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// Tracks the frames that were submitted to the GPU but haven't finished executing yet.
+///
+/// Buffers that are written every frame, like the [`DynamicUniformVec`]s of per-frame uniforms,
+/// keep a slot per frame in flight. The CPU fills the slot of the current frame while the GPU still
+/// reads the slots of the previous frames. Before a frame starts, the renderer waits until the GPU
+/// finished the frame that last used its slot.
+///
+/// This is a resource of the render world, see
+/// [`WgpuSettings::frames_in_flight`](crate::settings::WgpuSettings::frames_in_flight).
+///
+/// [`DynamicUniformVec`]: crate::render_resource::DynamicUniformVec
+pub struct FramesInFlight {
+    count: usize,
+    frame: u64,
+    /// The number of frames the GPU finished, which is increased by the completion signals.
+    completed: Arc<AtomicU64>,
+    /// The completion futures of the submitted frames, which call the completion signals once
+    /// they resolve after the device was polled.
+    pending: Mutex<Vec<Pin<Box<dyn Future<Output = ()> + Send>>>>,
+}
+
+impl FramesInFlight {
+    /// Creates the tracking for `count` frames in flight, with at least one frame.
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: count.max(1),
+            frame: 0,
+            completed: Default::default(),
+            pending: Default::default(),
+        }
+    }
+
+    /// Returns the number of frames that may be in flight, which is the number of slots of buffers
+    /// that are written every frame.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the index of the current frame.
+    #[inline]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns the slot of the current frame in buffers with a slot per frame in flight.
+    #[inline]
+    pub fn slot(&self) -> usize {
+        (self.frame % self.count as u64) as usize
+    }
+
+    /// Returns the number of frames the GPU finished executing.
+    #[inline]
+    pub fn completed_frames(&self) -> u64 {
+        self.completed.load(Ordering::Acquire)
+    }
+
+    /// Returns whether the GPU finished the frame that last used the slot of the current frame, so
+    /// that the slot can be written.
+    pub fn slot_available(&self) -> bool {
+        // the frame `frame - count` used the same slot
+        self.frame < self.count as u64 || self.completed_frames() > self.frame - self.count as u64
+    }
+
+    /// Returns the signal that the GPU finished the current frame, which is called once the work
+    /// submitted in the frame is done.
+    pub fn completion_signal(&self) -> impl FnOnce() + Send + 'static {
+        let completed = self.completed.clone();
+        let frame = self.frame;
+        move || {
+            // the frames may complete out of order on some backends
+            completed.fetch_max(frame + 1, Ordering::AcqRel);
+        }
+    }
+
+    /// Waits for the slot of the current frame to become available at the start of the
+    /// [`Prepare`](crate::RenderStage::Prepare) stage.
+    pub(crate) fn wait_system(frames: Res<Self>, render_device: Res<RenderDevice>) {
+        render_device.poll(wgpu::Maintain::Poll);
+        frames.poll_pending();
+        if !frames.slot_available() {
+            let _span = info_span!("wait_for_frame_in_flight").entered();
+            // waiting for all submitted work signals the completion of every frame
+            render_device.poll(wgpu::Maintain::Wait);
+            frames.poll_pending();
+        }
+    }
+
+    /// Registers the completion signal of the current frame after it was submitted.
+    pub(crate) fn submit(&self, render_queue: &RenderQueue) {
+        let done = render_queue.on_submitted_work_done();
+        let signal = self.completion_signal();
+        self.pending.lock().push(Box::pin(async move {
+            done.await;
+            signal();
+        }));
+    }
+
+    /// Polls the completion futures of the submitted frames and drops the resolved ones.
+    fn poll_pending(&self) {
+        self.pending
+            .lock()
+            .retain_mut(|done| future::block_on(future::poll_once(done)).is_none());
+    }
+
+    /// Moves on to the next frame at the end of the [`Cleanup`](crate::RenderStage::Cleanup)
+    /// stage.
+    pub(crate) fn advance_system(mut frames: ResMut<Self>) {
+        frames.frame += 1;
+    }
+}
+
+impl Default for FramesInFlight {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FramesInFlight;
+
+    #[test]
+    fn slots_are_recycled_once_their_frame_completed() {
+        let mut frames = FramesInFlight::new(3);
+        let mut signals = Vec::new();
+        let mut slots = Vec::new();
+        // the GPU doesn't finish any frame, so only the first three can be recorded
+        while frames.slot_available() {
+            slots.push(frames.slot());
+            signals.push(frames.completion_signal());
+            frames.frame += 1;
+        }
+        assert_eq!(slots, [0, 1, 2]);
+        assert_eq!(frames.slot(), 0);
+
+        // frames are executed in order, so the completion of the second frame frees up the slot
+        // of the first one as well, even if its own signal arrives later
+        let mut signals = signals.into_iter();
+        let (first, second) = (signals.next().unwrap(), signals.next().unwrap());
+        second();
+        assert_eq!(frames.completed_frames(), 2);
+        assert!(frames.slot_available());
+        first();
+        assert_eq!(frames.completed_frames(), 2);
+
+        frames.frame += 1;
+        assert_eq!(frames.slot(), 1);
+        assert!(frames.slot_available());
+        frames.frame += 1;
+        assert_eq!(frames.slot(), 2);
+        assert!(!frames.slot_available());
+        signals.next().unwrap()();
+        assert!(frames.slot_available());
+    }
+
+    #[test]
+    fn a_single_frame_in_flight_waits_for_every_frame() {
+        let mut frames = FramesInFlight::new(0);
+        assert_eq!(frames.count(), 1);
+        assert!(frames.slot_available());
+        let signal = frames.completion_signal();
+        frames.frame += 1;
+        assert_eq!(frames.slot(), 0);
+        assert!(!frames.slot_available());
+        signal();
+        assert!(frames.slot_available());
+    }
+}
@@ -1,9 +1,13 @@
+mod frames_in_flight;
 mod graph_runner;
 mod render_device;
+mod render_resource_limits;
 
 use bevy_utils::tracing::{error, info, info_span};
+pub use frames_in_flight::*;
 pub use graph_runner::*;
 pub use render_device::*;
+pub use render_resource_limits::*;
 
 use crate::{
     render_graph::RenderGraph,
@@ -43,6 +47,9 @@ pub fn render_system(world: &mut World) {
 
         panic!("Error running render graph: {}", e);
     }
+    world
+        .resource::<FramesInFlight>()
+        .submit(world.resource::<RenderQueue>());
 
     {
         let _span = info_span!("present_frames").entered();
@@ -14,6 +14,16 @@ pub struct RenderDevice {
     device: Arc<wgpu::Device>,
 }
 
+/// Returns the maximum number of bytes of push constants of a device with the given `features` and
+/// `limits`, see [`RenderDevice::max_push_constant_size`].
+pub(crate) fn max_push_constant_size(features: wgpu::Features, limits: &wgpu::Limits) -> u32 {
+    if features.contains(wgpu::Features::PUSH_CONSTANTS) {
+        limits.max_push_constant_size
+    } else {
+        0
+    }
+}
+
 impl From<Arc<wgpu::Device>> for RenderDevice {
     fn from(device: Arc<wgpu::Device>) -> Self {
         Self { device }
@@ -37,6 +47,18 @@ impl RenderDevice {
         self.device.limits()
     }
 
+    /// Returns the maximum number of bytes of push constants pipelines can use, which is `0` if the
+    /// device doesn't support [`wgpu::Features::PUSH_CONSTANTS`].
+    pub fn max_push_constant_size(&self) -> u32 {
+        max_push_constant_size(self.features(), &self.limits())
+    }
+
+    /// Returns `true` if pipelines can use `size` bytes of push constants. Otherwise, per-draw
+    /// data has to be passed by other means, like a uniform buffer.
+    pub fn supports_push_constants(&self, size: u32) -> bool {
+        size <= self.max_push_constant_size()
+    }
+
     /// Creates a [`ShaderModule`](wgpu::ShaderModule) from either SPIR-V or WGSL source code.
     #[inline]
     pub fn create_shader_module(&self, desc: &wgpu::ShaderModuleDescriptor) -> wgpu::ShaderModule {
@@ -82,7 +104,7 @@ impl RenderDevice {
         &self,
         desc: &wgpu::BindGroupLayoutDescriptor,
     ) -> BindGroupLayout {
-        BindGroupLayout::from(self.device.create_bind_group_layout(desc))
+        BindGroupLayout::from(self.device.create_bind_group_layout(desc)).with_entries(desc.entries)
     }
 
     /// Creates a [`PipelineLayout`](wgpu::PipelineLayout).
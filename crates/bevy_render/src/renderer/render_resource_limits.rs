@@ -0,0 +1,125 @@
+use thiserror::Error;
+
+/// The limits of the [`RenderDevice`](super::RenderDevice) that determine how uniforms are laid out
+/// in buffers, queried when the renderer is initialized.
+///
+/// This is a resource of both the app world and the render world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderResourceLimits {
+    /// The alignment of dynamic offsets into uniform buffers, which is the minimum size of the slot
+    /// of each value in a [`DynamicUniformVec`](crate::render_resource::DynamicUniformVec).
+    pub min_uniform_buffer_offset_alignment: u32,
+    /// The maximum size of a uniform buffer binding in bytes.
+    pub max_uniform_buffer_binding_size: u32,
+}
+
+/// The limits are the largest alignment and smallest binding size that devices may have, so they
+/// are valid on all devices.
+impl Default for RenderResourceLimits {
+    fn default() -> Self {
+        Self::from(&wgpu::Limits::downlevel_webgl2_defaults())
+    }
+}
+
+impl From<&wgpu::Limits> for RenderResourceLimits {
+    fn from(limits: &wgpu::Limits) -> Self {
+        Self {
+            min_uniform_buffer_offset_alignment: limits.min_uniform_buffer_offset_alignment,
+            max_uniform_buffer_binding_size: limits.max_uniform_buffer_binding_size,
+        }
+    }
+}
+
+impl RenderResourceLimits {
+    /// Returns the size of the slot of a uniform of `size` bytes that is aligned to `alignment`
+    /// bytes in its std140 layout, so that the slots start at valid dynamic offsets.
+    pub fn uniform_slot_size(&self, size: usize, alignment: usize) -> usize {
+        let alignment = alignment.max(self.min_uniform_buffer_offset_alignment as usize);
+        // an empty uniform still needs a slot of its own
+        let size = size.max(1);
+        (size + alignment - 1) / alignment * alignment
+    }
+
+    /// Returns the dynamic offset of the slot at `index` in a buffer of slots of `slot_size` bytes.
+    #[inline]
+    pub fn uniform_offset(&self, index: usize, slot_size: usize) -> u32 {
+        (index * slot_size) as u32
+    }
+
+    /// Returns an error naming the uniform `name` if its `size` bytes don't fit into a uniform
+    /// buffer binding.
+    pub fn validate_uniform_binding(
+        &self,
+        name: &str,
+        size: usize,
+    ) -> Result<(), UniformBindingSizeError> {
+        if size > self.max_uniform_buffer_binding_size as usize {
+            return Err(UniformBindingSizeError {
+                name: name.to_string(),
+                size,
+                max_size: self.max_uniform_buffer_binding_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("the uniform `{name}` takes up {size} bytes, but uniform buffer bindings can only hold {max_size} bytes on this device")]
+pub struct UniformBindingSizeError {
+    pub name: String,
+    pub size: usize,
+    pub max_size: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenderResourceLimits;
+
+    fn limits(alignment: u32) -> RenderResourceLimits {
+        RenderResourceLimits {
+            min_uniform_buffer_offset_alignment: alignment,
+            max_uniform_buffer_binding_size: 16384,
+        }
+    }
+
+    #[test]
+    fn uniform_slots_start_at_aligned_offsets() {
+        // sizes and std140 alignments of e.g. a `f32`, a `vec3<f32>` and structs with matrices
+        let uniforms = [(4, 4), (12, 16), (128, 16), (300, 16)];
+        for alignment in [32, 64, 256, 1024] {
+            let limits = limits(alignment);
+            for (size, uniform_alignment) in uniforms {
+                let slot_size = limits.uniform_slot_size(size, uniform_alignment);
+                assert!(slot_size >= size);
+                assert!(slot_size < size + alignment as usize);
+                for index in 0..4 {
+                    let offset = limits.uniform_offset(index, slot_size);
+                    assert_eq!(offset % alignment, 0);
+                    assert_eq!(offset as usize % uniform_alignment, 0);
+                }
+            }
+        }
+
+        assert_eq!(limits(256).uniform_slot_size(300, 16), 512);
+        assert_eq!(limits(1024).uniform_slot_size(4, 4), 1024);
+        assert_eq!(limits(1024).uniform_slot_size(1025, 16), 2048);
+        assert_eq!(limits(1024).uniform_offset(3, 2048), 6144);
+        assert_eq!(limits(64).uniform_slot_size(0, 4), 64);
+        // the alignment of the uniform itself is respected as well
+        assert_eq!(limits(32).uniform_slot_size(4, 64), 64);
+    }
+
+    #[test]
+    fn oversized_uniforms_are_reported_by_name() {
+        let limits = limits(256);
+        assert!(limits.validate_uniform_binding("Lights", 16384).is_ok());
+        let err = limits
+            .validate_uniform_binding("Lights", 16400)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the uniform `Lights` takes up 16400 bytes, but uniform buffer bindings can only hold 16384 bytes on this device"
+        );
+    }
+}
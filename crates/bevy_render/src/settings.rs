@@ -31,6 +31,10 @@ pub struct WgpuSettings {
     pub limits: WgpuLimits,
     /// The constraints on limits allowed regardless of what the adapter/backend supports
     pub constrained_limits: Option<WgpuLimits>,
+    /// The number of frames the CPU may prepare while the GPU still renders the previous ones.
+    /// Buffers that are written every frame keep a copy per frame in flight, so more frames use
+    /// more memory but stall less often.
+    pub frames_in_flight: usize,
 }
 
 impl Default for WgpuSettings {
@@ -68,6 +72,7 @@ impl Default for WgpuSettings {
             disabled_features: None,
             limits,
             constrained_limits: None,
+            frames_in_flight: 2,
         }
     }
 }
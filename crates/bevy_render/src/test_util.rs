@@ -0,0 +1,33 @@
+//! Fixtures shared by the tests of several modules.
+
+use crate::render_resource::{FragmentState, RenderPipelineDescriptor, ShaderDefs, VertexState};
+use bevy_asset::Handle;
+
+/// A descriptor without vertex buffers and fragment targets.
+pub(crate) fn pipeline_descriptor() -> RenderPipelineDescriptor {
+    RenderPipelineDescriptor {
+        label: None,
+        layout: None,
+        push_constant_ranges: Vec::new(),
+        vertex: VertexState {
+            shader: Handle::default(),
+            shader_defs: ShaderDefs::new(),
+            entry_point: "vertex".into(),
+            entry_point_overrides: Vec::new(),
+            specialization_constants: Vec::new(),
+            buffers: Vec::new(),
+            allow_unused_attributes: false,
+        },
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        fragment: Some(FragmentState {
+            shader: Handle::default(),
+            shader_defs: ShaderDefs::new(),
+            entry_point: "fragment".into(),
+            entry_point_overrides: Vec::new(),
+            specialization_constants: Vec::new(),
+            targets: Vec::new(),
+        }),
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{
+    render_asset::RenderAssets,
+    renderer::{RenderDevice, RenderQueue},
+    texture::{create_gpu_image, BevyDefault, GpuImage, Image},
+};
+use bevy_asset::Handle;
+use bevy_ecs::world::{FromWorld, World};
+use wgpu::{Extent3d, TextureDimension, TextureFormat};
+
+/// Selects which of the [`FallbackTextures`] is bound in place of a texture that hasn't been
+/// loaded yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FallbackTexture {
+    #[default]
+    /// An opaque white texture, which leaves colors and factors multiplied by it unchanged.
+    White,
+    /// An opaque black texture.
+    Black,
+    /// A normal map texture pointing straight out of the surface.
+    FlatNormal,
+}
+
+impl FallbackTexture {
+    /// Returns the 1x1 [`Image`] of this fallback texture.
+    pub fn image(self) -> Image {
+        let (pixel, format) = match self {
+            FallbackTexture::White => ([255, 255, 255, 255], TextureFormat::bevy_default()),
+            FallbackTexture::Black => ([0, 0, 0, 255], TextureFormat::bevy_default()),
+            // normal maps are stored in linear space
+            FallbackTexture::FlatNormal => ([128, 128, 255, 255], TextureFormat::Rgba8Unorm),
+        };
+        Image::new_fill(Extent3d::default(), TextureDimension::D2, &pixel, format)
+    }
+}
+
+/// The 1x1 textures bound by materials in place of textures that haven't finished loading yet.
+///
+/// This is a resource of the render world. Binding a fallback instead of waiting for the texture
+/// lets a material render during the first frames after it was spawned. Materials that were
+/// prepared with a fallback are prepared again once all of their textures are loaded, see
+/// [`RenderAsset::prepare_fallback_asset`](crate::render_asset::RenderAsset::prepare_fallback_asset).
+pub struct FallbackTextures {
+    white: GpuImage,
+    black: GpuImage,
+    flat_normal: GpuImage,
+}
+
+impl FromWorld for FallbackTextures {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let render_queue = world.resource::<RenderQueue>();
+        let create = |fallback: FallbackTexture| {
+            create_gpu_image(&fallback.image(), render_device, render_queue)
+        };
+        Self {
+            white: create(FallbackTexture::White),
+            black: create(FallbackTexture::Black),
+            flat_normal: create(FallbackTexture::FlatNormal),
+        }
+    }
+}
+
+impl FallbackTextures {
+    pub fn get(&self, fallback: FallbackTexture) -> &GpuImage {
+        match fallback {
+            FallbackTexture::White => &self.white,
+            FallbackTexture::Black => &self.black,
+            FallbackTexture::FlatNormal => &self.flat_normal,
+        }
+    }
+
+    /// Returns the [`GpuImage`] of `handle`, or the `fallback` texture if the image hasn't been
+    /// prepared yet.
+    pub fn get_or_fallback<'a>(
+        &'a self,
+        gpu_images: &'a RenderAssets<Image>,
+        handle: &Handle<Image>,
+        fallback: FallbackTexture,
+    ) -> &'a GpuImage {
+        gpu_images.get(handle).unwrap_or_else(|| self.get(fallback))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FallbackTexture;
+    use wgpu::{Extent3d, TextureFormat};
+
+    #[test]
+    fn fallback_images_are_single_pixels() {
+        for (fallback, pixel) in [
+            (FallbackTexture::White, [255u8, 255, 255, 255]),
+            (FallbackTexture::Black, [0, 0, 0, 255]),
+            (FallbackTexture::FlatNormal, [128, 128, 255, 255]),
+        ] {
+            let image = fallback.image();
+            assert_eq!(image.texture_descriptor.size, Extent3d::default());
+            assert_eq!(image.data, pixel);
+        }
+        assert_eq!(
+            FallbackTexture::FlatNormal
+                .image()
+                .texture_descriptor
+                .format,
+            TextureFormat::Rgba8Unorm
+        );
+    }
+}
@@ -6,6 +6,7 @@ use super::dds::*;
 use super::ktx2::*;
 
 use super::image_texture_conversion::image_to_texture;
+use super::mip_level_data_size;
 use crate::{
     render_asset::{PrepareAssetError, RenderAsset},
     render_resource::{Sampler, Texture, TextureView},
@@ -19,7 +20,7 @@ use bevy_reflect::TypeUuid;
 use thiserror::Error;
 use wgpu::{
     Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, TextureDimension, TextureFormat,
-    TextureViewDescriptor,
+    TextureUsages, TextureViewDescriptor, TextureViewDimension,
 };
 
 pub const TEXTURE_ASSET_INDEX: u64 = 0;
@@ -110,6 +111,9 @@ pub struct Image {
     // TODO: this nesting makes accessing Image metadata verbose. Either flatten out descriptor or add accessors
     pub texture_descriptor: wgpu::TextureDescriptor<'static>,
     pub sampler_descriptor: wgpu::SamplerDescriptor<'static>,
+    /// The descriptor of the view the texture is bound with. If `None`, the view dimension is
+    /// inferred from the `texture_descriptor`, see [`Image::texture_view_dimension`].
+    pub texture_view_descriptor: Option<wgpu::TextureViewDescriptor<'static>>,
 }
 
 impl Default for Image {
@@ -132,6 +136,7 @@ impl Default for Image {
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             },
             sampler_descriptor: wgpu::SamplerDescriptor::default(),
+            texture_view_descriptor: None,
         }
     }
 }
@@ -149,7 +154,7 @@ impl Image {
         format: TextureFormat,
     ) -> Self {
         debug_assert_eq!(
-            size.volume() * format.pixel_size(),
+            mip_level_data_size(size, dimension, format, 0),
             data.len(),
             "Pixel data, size and format have to match",
         );
@@ -196,6 +201,79 @@ impl Image {
         value
     }
 
+    /// Creates a new 2D image a camera can render to by setting it as its
+    /// [`RenderTarget`](crate::camera::RenderTarget), and which can be bound as a texture of a
+    /// material afterwards. The image data is initialized with zeroes.
+    ///
+    /// The render graph node of the camera has to run before the nodes sampling the image, which
+    /// can be checked with
+    /// [`RenderGraph::validate_render_target_order`](crate::render_graph::RenderGraph::validate_render_target_order).
+    pub fn new_render_target(size: Extent3d, format: TextureFormat) -> Self {
+        let mut image = Image::default();
+        image.texture_descriptor.format = format;
+        image.ensure_usage(TextureUsages::RENDER_ATTACHMENT);
+        // the default image is a white pixel, which resizing would keep
+        image.data.clear();
+        image.resize(size);
+        image
+    }
+
+    /// Adds the `required` usages to the texture descriptor, for images created by the engine
+    /// which are bound in ways their creator didn't anticipate. Returns the usages that were
+    /// missing.
+    ///
+    /// Images created by users should be created with the right usages instead, which materials
+    /// verify with [`GpuImage::check_usage`].
+    pub fn ensure_usage(&mut self, required: TextureUsages) -> TextureUsages {
+        let missing = required - self.texture_descriptor.usage;
+        self.texture_descriptor.usage |= missing;
+        missing
+    }
+
+    /// Creates a new 3D image from raw binary data, which contains `depth` slices of
+    /// `width` * `height` pixels each. This is primarily for use with the `texture_3d` shader
+    /// uniform type, e.g. for color grading lookup tables or volumetric data.
+    ///
+    /// # Panics
+    /// Panics if the length of the `data`, the size and the size of the `format` do not match.
+    pub fn new_3d(
+        width: u32,
+        height: u32,
+        depth: u32,
+        data: Vec<u8>,
+        format: TextureFormat,
+    ) -> Self {
+        Self::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: depth,
+            },
+            TextureDimension::D3,
+            data,
+            format,
+        )
+    }
+
+    /// Returns the layout of the first mip level of the `data` of the image, whose rows and slices
+    /// or layers are tightly packed. The rows of block-compressed formats are rows of blocks.
+    pub fn data_layout(&self) -> ImageDataLayout {
+        let size = self.texture_descriptor.size;
+        let format_info = self.texture_descriptor.format.describe();
+        let (block_width, block_height) = format_info.block_dimensions;
+        let blocks_wide = (size.width + block_width as u32 - 1) / block_width as u32;
+        let blocks_high = (size.height + block_height as u32 - 1) / block_height as u32;
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(blocks_wide * format_info.block_size as u32),
+            rows_per_image: if size.depth_or_array_layers > 1 {
+                std::num::NonZeroU32::new(blocks_high)
+            } else {
+                None
+            },
+        }
+    }
+
     /// Returns the aspect ratio (height/width) of a 2D image.
     pub fn aspect_2d(&self) -> f32 {
         self.texture_descriptor.size.height as f32 / self.texture_descriptor.size.width as f32
@@ -255,6 +333,47 @@ impl Image {
         });
     }
 
+    /// Flags a 2D array texture with six layers as a cube map, so that it is bound as a cube
+    /// texture, like `texture_cube<f32>` in WGSL. The layers are the +X, -X, +Y, -Y, +Z and -Z
+    /// faces of the cube. Use [`Image::reinterpret_stacked_2d_as_array`] first to turn six
+    /// vertically stacked faces into layers.
+    ///
+    /// # Panics
+    /// Panics if the texture is not 2D, doesn't have exactly six layers or its layers are not
+    /// square.
+    pub fn reinterpret_as_cube(&mut self) {
+        let size = self.texture_descriptor.size;
+        assert!(self.texture_descriptor.dimension == TextureDimension::D2);
+        assert_eq!(size.depth_or_array_layers, 6, "Cube maps need six layers");
+        assert_eq!(size.width, size.height, "Cube map faces must be square");
+
+        self.texture_view_descriptor = Some(TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..Default::default()
+        });
+    }
+
+    /// Returns whether the image uses an sRGB format, whose colors are converted to linear space
+    /// when sampled.
+    pub fn is_srgb(&self) -> bool {
+        self.texture_descriptor.format.describe().srgb
+    }
+
+    /// Returns the dimension of the view this image is bound with.
+    pub fn texture_view_dimension(&self) -> TextureViewDimension {
+        self.texture_view_descriptor
+            .as_ref()
+            .and_then(|descriptor| descriptor.dimension)
+            .unwrap_or(match self.texture_descriptor.dimension {
+                TextureDimension::D1 => TextureViewDimension::D1,
+                TextureDimension::D2 if self.texture_descriptor.size.depth_or_array_layers > 1 => {
+                    TextureViewDimension::D2Array
+                }
+                TextureDimension::D2 => TextureViewDimension::D2,
+                TextureDimension::D3 => TextureViewDimension::D3,
+            })
+    }
+
     /// Convert a texture from a format to another
     /// Only a few formats are supported as input and output:
     /// - `TextureFormat::R8Unorm`
@@ -538,11 +657,136 @@ impl TextureFormatPixelInfo for TextureFormat {
 pub struct GpuImage {
     pub texture: Texture,
     pub texture_view: TextureView,
+    pub texture_view_dimension: TextureViewDimension,
     pub texture_format: TextureFormat,
+    pub texture_usage: TextureUsages,
     pub sampler: Sampler,
     pub size: Size,
 }
 
+impl GpuImage {
+    /// Returns whether the texture uses an sRGB format, whose colors are converted to linear space
+    /// when sampled.
+    pub fn is_srgb(&self) -> bool {
+        self.texture_format.describe().srgb
+    }
+
+    /// Checks that the image can be bound to the `field` of a material, whose binding expects
+    /// textures of the `expected` view dimension.
+    pub fn check_view_dimension(
+        &self,
+        field: &'static str,
+        expected: TextureViewDimension,
+    ) -> Result<(), ImageBindingError> {
+        if self.texture_view_dimension == expected {
+            Ok(())
+        } else {
+            Err(ImageBindingError::ViewDimension {
+                field,
+                expected,
+                found: self.texture_view_dimension,
+            })
+        }
+    }
+
+    /// Checks that the image can be bound to the `field` of a material as a storage texture.
+    pub fn check_storage_format(&self, field: &'static str) -> Result<(), ImageBindingError> {
+        check_storage_format(field, self.texture_format)
+    }
+
+    /// Checks that the image can be bound to the `field` of a material as a storage texture, and
+    /// was created with the [`TextureUsages::STORAGE_BINDING`] usage this requires.
+    pub fn check_storage_binding(&self, field: &'static str) -> Result<(), ImageBindingError> {
+        self.check_storage_format(field)?;
+        self.check_usage(field, TextureUsages::STORAGE_BINDING)
+    }
+
+    /// Checks that the image was created with the `required` usages of the `field` of a material
+    /// it is bound to, e.g. [`TextureUsages::TEXTURE_BINDING`] for sampled textures.
+    pub fn check_usage(
+        &self,
+        field: &'static str,
+        required: TextureUsages,
+    ) -> Result<(), ImageBindingError> {
+        check_usage(field, self.texture_usage, required)
+    }
+
+    /// Returns the `{FIELD}_SRGB` shader def if the texture uses an sRGB format, which lets
+    /// shaders bound to the `field` skip their own color space conversion.
+    pub fn srgb_shader_def(&self, field: &str) -> Option<String> {
+        self.is_srgb().then(|| srgb_shader_def(field))
+    }
+}
+
+/// Checks that textures of the `format` can be bound to the `field` of a material as a storage
+/// texture, which doesn't support sRGB formats and only some of the others on all devices.
+pub fn check_storage_format(
+    field: &'static str,
+    format: TextureFormat,
+) -> Result<(), ImageBindingError> {
+    let format_info = format.describe();
+    if format_info.srgb {
+        Err(ImageBindingError::SrgbStorageTexture { field, format })
+    } else if !format_info
+        .guaranteed_format_features
+        .allowed_usages
+        .contains(TextureUsages::STORAGE_BINDING)
+    {
+        Err(ImageBindingError::UnsupportedStorageFormat { field, format })
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that textures with the `usage` can be bound to the `field` of a material, whose binding
+/// requires the `required` usages.
+pub fn check_usage(
+    field: &'static str,
+    usage: TextureUsages,
+    required: TextureUsages,
+) -> Result<(), ImageBindingError> {
+    let missing = required - usage;
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(ImageBindingError::MissingUsage { field, missing })
+    }
+}
+
+/// Returns the shader def that marks textures bound to the `field` as sRGB textures, e.g.
+/// `BASE_COLOR_TEXTURE_SRGB` for the `base_color_texture` field.
+pub fn srgb_shader_def(field: &str) -> String {
+    format!("{}_SRGB", field.to_uppercase())
+}
+
+/// An error that occurs when an image can't be bound to a field of a material.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ImageBindingError {
+    #[error(
+        "field `{field}` expects a {expected:?} texture, but the image is a {found:?} texture"
+    )]
+    ViewDimension {
+        field: &'static str,
+        expected: TextureViewDimension,
+        found: TextureViewDimension,
+    },
+    #[error("field `{field}` is a storage texture, which can't use the sRGB format {format:?}")]
+    SrgbStorageTexture {
+        field: &'static str,
+        format: TextureFormat,
+    },
+    #[error("field `{field}` is a storage texture, which doesn't support the format {format:?} on all devices")]
+    UnsupportedStorageFormat {
+        field: &'static str,
+        format: TextureFormat,
+    },
+    #[error("field `{field}` requires the image to be created with the {missing:?} usages")]
+    MissingUsage {
+        field: &'static str,
+        missing: TextureUsages,
+    },
+}
+
 impl RenderAsset for Image {
     type ExtractedAsset = Image;
     type PreparedAsset = GpuImage;
@@ -558,55 +802,53 @@ impl RenderAsset for Image {
         image: Self::ExtractedAsset,
         (render_device, render_queue): &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
-        let texture = if image.texture_descriptor.mip_level_count > 1 || image.is_compressed() {
-            render_device.create_texture_with_data(
-                render_queue,
-                &image.texture_descriptor,
-                &image.data,
-            )
-        } else {
-            let texture = render_device.create_texture(&image.texture_descriptor);
-            let format_size = image.texture_descriptor.format.pixel_size();
-            render_queue.write_texture(
-                ImageCopyTexture {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &image.data,
-                ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(
-                        std::num::NonZeroU32::new(
-                            image.texture_descriptor.size.width * format_size as u32,
-                        )
-                        .unwrap(),
-                    ),
-                    rows_per_image: if image.texture_descriptor.size.depth_or_array_layers > 1 {
-                        std::num::NonZeroU32::new(image.texture_descriptor.size.height)
-                    } else {
-                        None
-                    },
-                },
-                image.texture_descriptor.size,
-            );
-            texture
-        };
+        Ok(create_gpu_image(&image, render_device, render_queue))
+    }
+}
 
-        let texture_view = texture.create_view(&TextureViewDescriptor::default());
-        let size = Size::new(
-            image.texture_descriptor.size.width as f32,
-            image.texture_descriptor.size.height as f32,
+/// Uploads the `image` to the GPU.
+pub(crate) fn create_gpu_image(
+    image: &Image,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) -> GpuImage {
+    let texture = if image.texture_descriptor.mip_level_count > 1 || image.is_compressed() {
+        render_device.create_texture_with_data(render_queue, &image.texture_descriptor, &image.data)
+    } else {
+        let texture = render_device.create_texture(&image.texture_descriptor);
+        render_queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &image.data,
+            image.data_layout(),
+            image.texture_descriptor.size,
         );
-        let sampler = render_device.create_sampler(&image.sampler_descriptor);
-        Ok(GpuImage {
-            texture,
-            texture_view,
-            texture_format: image.texture_descriptor.format,
-            sampler,
-            size,
-        })
+        texture
+    };
+
+    let texture_view = texture.create_view(
+        image
+            .texture_view_descriptor
+            .as_ref()
+            .unwrap_or(&TextureViewDescriptor::default()),
+    );
+    let size = Size::new(
+        image.texture_descriptor.size.width as f32,
+        image.texture_descriptor.size.height as f32,
+    );
+    let sampler = render_device.create_sampler(&image.sampler_descriptor);
+    GpuImage {
+        texture,
+        texture_view,
+        texture_view_dimension: image.texture_view_dimension(),
+        texture_format: image.texture_descriptor.format,
+        texture_usage: image.texture_descriptor.usage,
+        sampler,
+        size,
     }
 }
 
@@ -729,4 +971,135 @@ mod test {
         let image = Image::default();
         assert_eq!(Vec2::new(1.0, 1.0), image.size());
     }
+
+    #[test]
+    fn render_target_can_be_bound() {
+        let size = Extent3d {
+            width: 64,
+            height: 32,
+            depth_or_array_layers: 1,
+        };
+        let image = Image::new_render_target(size, TextureFormat::Bgra8UnormSrgb);
+        assert!(image
+            .texture_descriptor
+            .usage
+            .contains(TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING));
+        assert_eq!(image.texture_descriptor.size, size);
+        assert_eq!(image.data.len(), 64 * 32 * 4);
+        assert!(image.data.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn texture_usages() {
+        let mut image = Image::default();
+        assert_eq!(
+            check_usage(
+                "storage",
+                image.texture_descriptor.usage,
+                TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING
+            ),
+            Err(ImageBindingError::MissingUsage {
+                field: "storage",
+                missing: TextureUsages::STORAGE_BINDING,
+            })
+        );
+
+        assert_eq!(
+            image.ensure_usage(TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING),
+            TextureUsages::STORAGE_BINDING
+        );
+        assert_eq!(
+            check_usage(
+                "storage",
+                image.texture_descriptor.usage,
+                TextureUsages::STORAGE_BINDING
+            ),
+            Ok(())
+        );
+        assert!(image
+            .ensure_usage(TextureUsages::STORAGE_BINDING)
+            .is_empty());
+    }
+
+    #[test]
+    fn cube_view_dimension() {
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: 4,
+                height: 4 * 6,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 255],
+            TextureFormat::Rgba8Unorm,
+        );
+        assert_eq!(image.texture_view_dimension(), TextureViewDimension::D2);
+        image.reinterpret_stacked_2d_as_array(6);
+        assert_eq!(
+            image.texture_view_dimension(),
+            TextureViewDimension::D2Array
+        );
+        image.reinterpret_as_cube();
+        assert_eq!(image.texture_view_dimension(), TextureViewDimension::Cube);
+    }
+
+    #[test]
+    fn volume_descriptor() {
+        let data = (0..4 * 4 * 4 * 4).map(|i| i as u8).collect();
+        let image = Image::new_3d(4, 4, 4, data, TextureFormat::Rgba8Unorm);
+        assert_eq!(image.texture_descriptor.dimension, TextureDimension::D3);
+        assert_eq!(
+            image.texture_descriptor.size,
+            Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 4,
+            }
+        );
+        assert_eq!(image.texture_view_dimension(), TextureViewDimension::D3);
+
+        // each row is 4 pixels of 4 bytes and each slice 4 rows
+        let layout = image.data_layout();
+        assert_eq!(layout.offset, 0);
+        assert_eq!(layout.bytes_per_row.map(|bytes| bytes.get()), Some(16));
+        assert_eq!(layout.rows_per_image.map(|rows| rows.get()), Some(4));
+        let slice_size = 16 * 4;
+        assert_eq!(image.data[slice_size * 3 + 16 * 2 + 4], 228);
+    }
+
+    #[test]
+    fn srgb_bindings() {
+        let image = Image::default();
+        assert!(image.is_srgb());
+        assert_eq!(
+            check_storage_format("output", image.texture_descriptor.format),
+            Err(ImageBindingError::SrgbStorageTexture {
+                field: "output",
+                format: image.texture_descriptor.format,
+            })
+        );
+        assert_eq!(
+            check_storage_format("output", TextureFormat::Rgba8Unorm),
+            Ok(())
+        );
+        // besides sRGB, only some formats can be used for storage textures
+        assert_eq!(
+            check_storage_format("output", TextureFormat::Bgra8Unorm),
+            Err(ImageBindingError::UnsupportedStorageFormat {
+                field: "output",
+                format: TextureFormat::Bgra8Unorm,
+            })
+        );
+        assert_eq!(
+            srgb_shader_def("base_color_texture"),
+            "BASE_COLOR_TEXTURE_SRGB"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn cube_needs_six_layers() {
+        let mut image = Image::default();
+        image.reinterpret_as_cube();
+    }
 }
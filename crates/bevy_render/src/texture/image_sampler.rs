@@ -0,0 +1,261 @@
+use crate::{
+    render_asset::{PrepareAssetError, RenderAsset},
+    render_resource::Sampler,
+    renderer::RenderDevice,
+};
+use bevy_ecs::system::{lifetimeless::SRes, SystemParamItem};
+use bevy_reflect::TypeUuid;
+use bevy_utils::{tracing::warn, HashMap};
+use std::num::NonZeroU8;
+use wgpu::{AddressMode, FilterMode, SamplerDescriptor};
+
+/// A sampler asset, which configures how a texture is sampled independently of the
+/// [`Image`](super::Image) it is bound with.
+///
+/// Materials that don't set a sampler for a texture use the
+/// [`sampler_descriptor`](super::Image::sampler_descriptor) of its [`Image`](super::Image).
+///
+/// ```
+/// # use bevy_render::{render_resource::{AddressMode, FilterMode}, texture::ImageSampler};
+/// // repeats the texture with linear filtering
+/// let albedo_sampler = ImageSampler::new(AddressMode::Repeat, FilterMode::Linear);
+/// // clamps to the edge of the texture with nearest filtering
+/// let lightmap_sampler = ImageSampler::new(AddressMode::ClampToEdge, FilterMode::Nearest);
+/// ```
+#[derive(Debug, Clone, Default, TypeUuid)]
+#[uuid = "4bc3b9c6-0e7e-4fb4-8a38-6e8e0a3c5d21"]
+pub struct ImageSampler {
+    pub descriptor: SamplerDescriptor<'static>,
+}
+
+impl From<SamplerDescriptor<'static>> for ImageSampler {
+    fn from(descriptor: SamplerDescriptor<'static>) -> Self {
+        Self { descriptor }
+    }
+}
+
+impl ImageSampler {
+    /// Creates a sampler using the `address_mode` along all axes and the `filter_mode` for
+    /// magnification, minification and between mipmaps.
+    pub fn new(address_mode: AddressMode, filter_mode: FilterMode) -> Self {
+        Self {
+            descriptor: SamplerDescriptor {
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                address_mode_w: address_mode,
+                mag_filter: filter_mode,
+                min_filter: filter_mode,
+                mipmap_filter: filter_mode,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Limits the mip levels used when sampling to the range from `lod_min_clamp` to
+    /// `lod_max_clamp`. The mip levels of an image can be generated with
+    /// [`Image::generate_mipmaps`](super::Image::generate_mipmaps).
+    pub fn with_lod_clamp(mut self, lod_min_clamp: f32, lod_max_clamp: f32) -> Self {
+        self.descriptor.lod_min_clamp = lod_min_clamp;
+        self.descriptor.lod_max_clamp = lod_max_clamp;
+        self
+    }
+
+    /// Enables anisotropic filtering with up to `anisotropy_clamp` samples, which keeps textures on
+    /// surfaces viewed at oblique angles sharp. See [`valid_anisotropy_clamp`] for the supported
+    /// values.
+    pub fn with_anisotropy(mut self, anisotropy_clamp: u8) -> Self {
+        self.descriptor.anisotropy_clamp = valid_anisotropy_clamp(anisotropy_clamp);
+        self
+    }
+}
+
+/// The largest `anisotropy_clamp` of samplers.
+pub const MAX_ANISOTROPY_CLAMP: u8 = 16;
+
+/// Returns the anisotropy clamp passed to the backend for the requested `anisotropy_clamp`, which
+/// has to be a power of two of at most [`MAX_ANISOTROPY_CLAMP`]. Other values are rounded down to
+/// the next supported value with a warning, values below 2 disable anisotropic filtering.
+pub fn valid_anisotropy_clamp(anisotropy_clamp: u8) -> Option<NonZeroU8> {
+    if anisotropy_clamp < 2 {
+        return None;
+    }
+    let valid = (1 << (u8::BITS - 1 - anisotropy_clamp.leading_zeros())).min(MAX_ANISOTROPY_CLAMP);
+    if valid != anisotropy_clamp {
+        warn!(
+            "the anisotropy clamp of samplers must be a power of two of at most {}, using {} instead of {}",
+            MAX_ANISOTROPY_CLAMP, valid, anisotropy_clamp
+        );
+    }
+    NonZeroU8::new(valid)
+}
+
+/// Makes the `anisotropy_clamp` of a sampler descriptor that was set directly valid.
+fn validate_anisotropy_clamp(
+    mut descriptor: SamplerDescriptor<'static>,
+) -> SamplerDescriptor<'static> {
+    descriptor.anisotropy_clamp = descriptor
+        .anisotropy_clamp
+        .and_then(|anisotropy_clamp| valid_anisotropy_clamp(anisotropy_clamp.get()));
+    descriptor
+}
+
+impl RenderAsset for ImageSampler {
+    type ExtractedAsset = SamplerDescriptor<'static>;
+    type PreparedAsset = Sampler;
+    type Param = SRes<RenderDevice>;
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.descriptor.clone()
+    }
+
+    fn prepare_asset(
+        descriptor: Self::ExtractedAsset,
+        render_device: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        Ok(render_device.create_sampler(&validate_anisotropy_clamp(descriptor)))
+    }
+}
+
+/// A lightweight alternative to [`ImageSampler`] assets for materials that sample all of their
+/// textures the same way, e.g. with nearest filtering for pixel art.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerOverride {
+    pub address_mode: AddressMode,
+    pub filter_mode: FilterMode,
+    /// Enables anisotropic filtering, see [`ImageSampler::with_anisotropy`].
+    pub anisotropy_clamp: Option<u8>,
+}
+
+impl SamplerOverride {
+    pub fn new(address_mode: AddressMode, filter_mode: FilterMode) -> Self {
+        Self {
+            address_mode,
+            filter_mode,
+            anisotropy_clamp: None,
+        }
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy_clamp: u8) -> Self {
+        self.anisotropy_clamp = Some(anisotropy_clamp);
+        self
+    }
+
+    /// Returns the descriptor of the sampler, see [`ImageSampler::new`].
+    pub fn descriptor(&self) -> SamplerDescriptor<'static> {
+        let sampler = ImageSampler::new(self.address_mode, self.filter_mode);
+        match self.anisotropy_clamp {
+            Some(anisotropy_clamp) => sampler.with_anisotropy(anisotropy_clamp).descriptor,
+            None => sampler.descriptor,
+        }
+    }
+}
+
+/// The samplers of [`SamplerOverride`]s. Materials using the same override share its sampler.
+///
+/// The samplers are never freed, as there is one for each distinct override and materials rarely
+/// use more than a handful of them.
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerOverride, Sampler>,
+}
+
+impl SamplerCache {
+    /// Returns the sampler of the `sampler_override`, which is created the first time the override
+    /// is used.
+    pub fn get(
+        &mut self,
+        render_device: &RenderDevice,
+        sampler_override: SamplerOverride,
+    ) -> &Sampler {
+        get_or_create(&mut self.samplers, sampler_override, |descriptor| {
+            render_device.create_sampler(descriptor)
+        })
+    }
+}
+
+/// Returns the value of the `sampler_override` in the `cache`, which is created from the
+/// descriptor of the override the first time it is used.
+fn get_or_create<T>(
+    cache: &mut HashMap<SamplerOverride, T>,
+    sampler_override: SamplerOverride,
+    create: impl FnOnce(&SamplerDescriptor<'static>) -> T,
+) -> &T {
+    cache
+        .entry(sampler_override)
+        .or_insert_with(|| create(&sampler_override.descriptor()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        get_or_create, valid_anisotropy_clamp, validate_anisotropy_clamp, ImageSampler,
+        SamplerOverride,
+    };
+    use bevy_utils::HashMap;
+    use std::num::NonZeroU8;
+    use wgpu::{AddressMode, FilterMode, SamplerDescriptor};
+
+    #[test]
+    fn identical_overrides_share_samplers() {
+        // the samplers are stood in for by the index of their creation
+        let mut cache = HashMap::default();
+        let mut created = Vec::new();
+        let mut get = |sampler_override| {
+            *get_or_create(&mut cache, sampler_override, |descriptor| {
+                created.push(descriptor.clone());
+                created.len() - 1
+            })
+        };
+        let pixel_art = SamplerOverride::new(AddressMode::ClampToEdge, FilterMode::Nearest);
+        let tiling = SamplerOverride::new(AddressMode::Repeat, FilterMode::Linear);
+        let pixel_art_sampler = get(pixel_art);
+        let tiling_sampler = get(tiling);
+        assert_ne!(pixel_art_sampler, tiling_sampler);
+        assert_eq!(get(pixel_art), pixel_art_sampler);
+        assert_eq!(
+            get(SamplerOverride::new(
+                AddressMode::ClampToEdge,
+                FilterMode::Nearest
+            )),
+            0
+        );
+        assert_eq!(created, [pixel_art.descriptor(), tiling.descriptor()]);
+
+        let descriptor = pixel_art.descriptor();
+        assert_eq!(descriptor.address_mode_u, AddressMode::ClampToEdge);
+        assert_eq!(descriptor.address_mode_w, AddressMode::ClampToEdge);
+        assert_eq!(descriptor.mag_filter, FilterMode::Nearest);
+        assert_eq!(descriptor.mipmap_filter, FilterMode::Nearest);
+        assert_ne!(descriptor, tiling.descriptor());
+    }
+
+    #[test]
+    fn anisotropy_clamps() {
+        assert_eq!(valid_anisotropy_clamp(0), None);
+        assert_eq!(valid_anisotropy_clamp(1), None);
+        assert_eq!(valid_anisotropy_clamp(8), NonZeroU8::new(8));
+        // unsupported values are rounded down
+        assert_eq!(valid_anisotropy_clamp(12), NonZeroU8::new(8));
+        assert_eq!(valid_anisotropy_clamp(255), NonZeroU8::new(16));
+
+        let sampler = ImageSampler::new(AddressMode::Repeat, FilterMode::Linear).with_anisotropy(4);
+        assert_eq!(sampler.descriptor.anisotropy_clamp, NonZeroU8::new(4));
+        let descriptor = SamplerDescriptor {
+            anisotropy_clamp: NonZeroU8::new(32),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_anisotropy_clamp(descriptor).anisotropy_clamp,
+            NonZeroU8::new(16)
+        );
+
+        // overrides with different anisotropy don't share samplers
+        let linear = SamplerOverride::new(AddressMode::Repeat, FilterMode::Linear);
+        let anisotropic = linear.with_anisotropy(16);
+        assert_ne!(linear, anisotropic);
+        assert_eq!(
+            anisotropic.descriptor().anisotropy_clamp,
+            NonZeroU8::new(16)
+        );
+    }
+}
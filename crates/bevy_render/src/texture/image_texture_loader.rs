@@ -1,12 +1,12 @@
 use anyhow::Result;
 use bevy_asset::{AssetLoader, LoadContext, LoadedAsset};
 use bevy_ecs::prelude::{FromWorld, World};
-use bevy_utils::BoxedFuture;
+use bevy_utils::{tracing::warn, BoxedFuture};
 use thiserror::Error;
 
 use crate::{
     renderer::RenderDevice,
-    texture::{Image, ImageType, TextureError},
+    texture::{Image, ImageType, MipmapSettings, TextureError},
 };
 
 use super::CompressedImageFormats;
@@ -15,6 +15,7 @@ use super::CompressedImageFormats;
 #[derive(Clone)]
 pub struct ImageTextureLoader {
     supported_compressed_formats: CompressedImageFormats,
+    generate_mipmaps: bool,
 }
 
 const FILE_EXTENSIONS: &[&str] = &[
@@ -46,7 +47,7 @@ impl AssetLoader for ImageTextureLoader {
             // use the file extension for the image type
             let ext = load_context.path().extension().unwrap().to_str().unwrap();
 
-            let dyn_img = Image::from_buffer(
+            let mut dyn_img = Image::from_buffer(
                 bytes,
                 ImageType::Extension(ext),
                 self.supported_compressed_formats,
@@ -57,6 +58,16 @@ impl AssetLoader for ImageTextureLoader {
                 path: format!("{}", load_context.path().display()),
             })?;
 
+            if self.generate_mipmaps && dyn_img.texture_descriptor.mip_level_count == 1 {
+                if let Err(err) = dyn_img.generate_mipmaps() {
+                    warn!(
+                        "Can't generate mipmaps for image {}: {}",
+                        load_context.path().display(),
+                        err
+                    );
+                }
+            }
+
             load_context.set_default_asset(LoadedAsset::new(dyn_img));
             Ok(())
         })
@@ -73,6 +84,9 @@ impl FromWorld for ImageTextureLoader {
             supported_compressed_formats: CompressedImageFormats::from_features(
                 world.resource::<RenderDevice>().features(),
             ),
+            generate_mipmaps: world
+                .get_resource::<MipmapSettings>()
+                .map_or(false, |settings| settings.generate_on_load),
         }
     }
 }
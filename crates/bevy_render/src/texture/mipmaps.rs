@@ -0,0 +1,241 @@
+use crate::{
+    color::SrgbColorSpace,
+    texture::{Image, TextureError, TextureFormatPixelInfo},
+};
+use wgpu::{Extent3d, TextureDimension, TextureFormat};
+
+/// Controls whether mipmaps are generated for images loaded from files.
+///
+/// Insert this as a resource before adding the `RenderPlugin`. Mipmaps of single images can be
+/// generated with [`Image::generate_mipmaps`] instead.
+#[derive(Debug, Clone, Default)]
+pub struct MipmapSettings {
+    /// Generates mipmaps for loaded images that don't contain any, as long as their format is
+    /// supported by [`Image::generate_mipmaps`]. Defaults to `false`.
+    pub generate_on_load: bool,
+}
+
+/// Returns the number of mip levels of a full mip chain for a texture of the given `size` and
+/// `dimension`, down to a size of 1 pixel.
+pub fn mip_level_count(size: Extent3d, dimension: TextureDimension) -> u32 {
+    let max_size = match dimension {
+        TextureDimension::D1 => size.width,
+        TextureDimension::D2 => size.width.max(size.height),
+        TextureDimension::D3 => size.width.max(size.height).max(size.depth_or_array_layers),
+    };
+    u32::BITS - max_size.max(1).leading_zeros()
+}
+
+/// Returns the size in bytes of the data of the `mip_level` of a texture. Block-compressed formats
+/// store blocks of pixels, so the size of the mip level is rounded up to whole blocks.
+pub fn mip_level_data_size(
+    size: Extent3d,
+    dimension: TextureDimension,
+    format: TextureFormat,
+    mip_level: u32,
+) -> usize {
+    let format_info = format.describe();
+    let (block_width, block_height) = format_info.block_dimensions;
+    let mip_size = size.mip_level_size(mip_level, dimension == TextureDimension::D3);
+    let blocks_wide = (mip_size.width + block_width as u32 - 1) / block_width as u32;
+    let blocks_high = (mip_size.height + block_height as u32 - 1) / block_height as u32;
+    blocks_wide as usize
+        * blocks_high as usize
+        * mip_size.depth_or_array_layers as usize
+        * format_info.block_size as usize
+}
+
+impl Image {
+    /// Returns the size in bytes the `data` of the image should have, including all of its mip
+    /// levels.
+    pub fn expected_data_size(&self) -> usize {
+        let descriptor = &self.texture_descriptor;
+        (0..descriptor.mip_level_count)
+            .map(|mip_level| {
+                mip_level_data_size(
+                    descriptor.size,
+                    descriptor.dimension,
+                    descriptor.format,
+                    mip_level,
+                )
+            })
+            .sum()
+    }
+
+    /// Generates the full mip chain of a 2D image by repeatedly downsampling it with a box filter.
+    /// Every layer of an array texture gets its own mip chain. Colors of sRGB images are averaged
+    /// in linear space.
+    ///
+    /// Samplers only use the mipmaps if their `mipmap_filter` and LOD clamps allow it, see
+    /// [`ImageSampler`](super::ImageSampler).
+    ///
+    /// Only uncompressed formats with 8 bits per channel are supported.
+    pub fn generate_mipmaps(&mut self) -> Result<(), TextureError> {
+        let format = self.texture_descriptor.format;
+        let srgb = match format {
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Bgra8UnormSrgb => true,
+            TextureFormat::R8Unorm
+            | TextureFormat::Rg8Unorm
+            | TextureFormat::Rgba8Unorm
+            | TextureFormat::Bgra8Unorm => false,
+            _ => {
+                return Err(TextureError::UnsupportedTextureFormat(format!(
+                    "{:?}",
+                    format
+                )))
+            }
+        };
+        if self.texture_descriptor.dimension != TextureDimension::D2
+            || self.texture_descriptor.mip_level_count != 1
+        {
+            return Err(TextureError::InvalidData(
+                "mipmaps can only be generated for 2D images without mipmaps".to_string(),
+            ));
+        }
+
+        let size = self.texture_descriptor.size;
+        let channels = format.pixel_size();
+        let mip_level_count = mip_level_count(size, TextureDimension::D2);
+        let layer_len = size.width as usize * size.height as usize * channels;
+
+        // the mips of each layer follow the layer, which is the order wgpu expects the data in
+        let mut data = Vec::with_capacity(self.data.len() * 4 / 3 + channels);
+        for layer in self.data.chunks_exact(layer_len) {
+            data.extend_from_slice(layer);
+            let mut mip_start = data.len() - layer.len();
+            let (mut width, mut height) = (size.width as usize, size.height as usize);
+            for _ in 1..mip_level_count {
+                let next_mip_start = data.len();
+                let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+                for y in 0..next_height {
+                    for x in 0..next_width {
+                        for channel in 0..channels {
+                            let is_color = srgb && channel < 3;
+                            let mut sum = 0.0;
+                            let mut count = 0.0;
+                            for source_y in (y * 2)..(y * 2 + 2).min(height) {
+                                for source_x in (x * 2)..(x * 2 + 2).min(width) {
+                                    let index = (source_y * width + source_x) * channels + channel;
+                                    let value = data[mip_start + index] as f32 / 255.0;
+                                    sum += if is_color {
+                                        value.nonlinear_to_linear_srgb()
+                                    } else {
+                                        value
+                                    };
+                                    count += 1.0;
+                                }
+                            }
+                            let mut average = sum / count;
+                            if is_color {
+                                average = average.linear_to_nonlinear_srgb();
+                            }
+                            data.push((average * 255.0).round() as u8);
+                        }
+                    }
+                }
+                mip_start = next_mip_start;
+                width = next_width;
+                height = next_height;
+            }
+        }
+
+        self.data = data;
+        self.texture_descriptor.mip_level_count = mip_level_count;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mip_level_count, mip_level_data_size};
+    use crate::texture::Image;
+    use wgpu::{Extent3d, TextureDimension, TextureFormat};
+
+    fn size(width: u32, height: u32, depth_or_array_layers: u32) -> Extent3d {
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers,
+        }
+    }
+
+    #[test]
+    fn mip_level_counts() {
+        assert_eq!(mip_level_count(size(1, 1, 1), TextureDimension::D2), 1);
+        assert_eq!(mip_level_count(size(256, 256, 1), TextureDimension::D2), 9);
+        assert_eq!(mip_level_count(size(255, 16, 1), TextureDimension::D2), 8);
+        assert_eq!(mip_level_count(size(4, 1024, 6), TextureDimension::D2), 11);
+        // array layers are not downsampled, but the depth of 3D textures is
+        assert_eq!(mip_level_count(size(4, 4, 64), TextureDimension::D2), 3);
+        assert_eq!(mip_level_count(size(4, 4, 64), TextureDimension::D3), 7);
+    }
+
+    #[test]
+    fn compressed_mip_level_data_sizes() {
+        let bc7 = TextureFormat::Bc7RgbaUnorm;
+        // 4x4 blocks of 16 bytes, partial blocks are rounded up
+        assert_eq!(
+            mip_level_data_size(size(10, 6, 1), TextureDimension::D2, bc7, 0),
+            3 * 2 * 16
+        );
+        assert_eq!(
+            mip_level_data_size(size(10, 6, 1), TextureDimension::D2, bc7, 1),
+            2 * 16
+        );
+        // the smallest mip levels still take up a whole block
+        assert_eq!(
+            mip_level_data_size(size(10, 6, 1), TextureDimension::D2, bc7, 3),
+            16
+        );
+        // 8 byte blocks for each layer
+        assert_eq!(
+            mip_level_data_size(
+                size(8, 8, 6),
+                TextureDimension::D2,
+                TextureFormat::Bc1RgbaUnorm,
+                0
+            ),
+            2 * 2 * 6 * 8
+        );
+
+        let mut image = Image::new(size(10, 6, 1), TextureDimension::D2, vec![0; 6 * 16], bc7);
+        image.texture_descriptor.mip_level_count =
+            mip_level_count(image.texture_descriptor.size, TextureDimension::D2);
+        assert_eq!(image.texture_descriptor.mip_level_count, 4);
+        image.data.resize((6 + 2 + 1 + 1) * 16, 0);
+        assert_eq!(image.expected_data_size(), image.data.len());
+    }
+
+    #[test]
+    fn generate_mipmaps() {
+        let data = vec![
+            0, 0, 0, 255, 255, 255, 255, 255, 8, 8, 8, 255, 16, 16, 16, 255, //
+            0, 0, 0, 255, 255, 255, 255, 255, 32, 32, 32, 255, 40, 40, 40, 255,
+        ];
+        let mut image = Image::new(
+            size(4, 2, 1),
+            TextureDimension::D2,
+            data.clone(),
+            TextureFormat::Rgba8Unorm,
+        );
+        image.generate_mipmaps().unwrap();
+        assert_eq!(image.texture_descriptor.mip_level_count, 3);
+        assert_eq!(image.data.len(), (8 + 2 + 1) * 4);
+        assert_eq!(image.data[..32], data);
+        assert_eq!(image.data[32..40], [128, 128, 128, 255, 24, 24, 24, 255]);
+        assert_eq!(image.data[40..], [76, 76, 76, 255]);
+
+        // colors of sRGB images are averaged in linear space
+        let mut image = Image::new(
+            size(2, 1, 1),
+            TextureDimension::D2,
+            vec![0, 0, 0, 0, 255, 255, 255, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        image.generate_mipmaps().unwrap();
+        assert_eq!(image.data[8..], [188, 188, 188, 128]);
+
+        // images can only get mipmaps once
+        assert!(image.generate_mipmaps().is_err());
+    }
+}
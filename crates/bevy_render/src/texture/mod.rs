@@ -2,13 +2,16 @@
 mod basis;
 #[cfg(feature = "dds")]
 mod dds;
+mod fallback_textures;
 #[cfg(feature = "hdr")]
 mod hdr_texture_loader;
 #[allow(clippy::module_inception)]
 mod image;
+mod image_sampler;
 mod image_texture_loader;
 #[cfg(feature = "ktx2")]
 mod ktx2;
+mod mipmaps;
 mod texture_cache;
 
 pub(crate) mod image_texture_conversion;
@@ -18,10 +21,13 @@ pub use self::image::*;
 pub use self::ktx2::*;
 #[cfg(feature = "dds")]
 pub use dds::*;
+pub use fallback_textures::*;
 #[cfg(feature = "hdr")]
 pub use hdr_texture_loader::*;
+pub use image_sampler::*;
 
 pub use image_texture_loader::*;
+pub use mipmaps::*;
 pub use texture_cache::*;
 
 use crate::{
@@ -58,7 +64,11 @@ impl Plugin for ImagePlugin {
         app.add_plugin(RenderAssetPlugin::<Image>::with_prepare_asset_label(
             PrepareAssetLabel::PreAssetPrepare,
         ))
-        .add_asset::<Image>();
+        .add_plugin(RenderAssetPlugin::<ImageSampler>::with_prepare_asset_label(
+            PrepareAssetLabel::PreAssetPrepare,
+        ))
+        .add_asset::<Image>()
+        .add_asset::<ImageSampler>();
         app.world
             .resource_mut::<Assets<Image>>()
             .set_untracked(DEFAULT_IMAGE_HANDLE, Image::default());
@@ -66,6 +76,8 @@ impl Plugin for ImagePlugin {
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
                 .init_resource::<TextureCache>()
+                .init_resource::<FallbackTextures>()
+                .init_resource::<SamplerCache>()
                 .add_system_to_stage(RenderStage::Cleanup, update_texture_cache_system);
         }
     }
@@ -3,33 +3,43 @@ pub mod window;
 
 pub use visibility::*;
 use wgpu::{
-    Color, Extent3d, Operations, RenderPassColorAttachment, TextureDescriptor, TextureDimension,
-    TextureFormat, TextureUsages,
+    BindingType, Color, Extent3d, Operations, RenderPassColorAttachment, SamplerBindingType,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureViewDimension,
 };
 pub use window::*;
 
 use crate::{
     camera::ExtractedCamera,
+    diagnostic::RenderStatistics,
     prelude::Image,
     render_asset::RenderAssets,
     render_resource::{std140::AsStd140, DynamicUniformVec, Texture, TextureView},
-    renderer::{RenderDevice, RenderQueue},
+    renderer::{FramesInFlight, RenderDevice, RenderQueue, RenderResourceLimits},
     texture::{BevyDefault, TextureCache},
     RenderApp, RenderStage,
 };
 use bevy_app::{App, Plugin};
 use bevy_ecs::prelude::*;
-use bevy_math::{Mat4, Vec3};
+use bevy_math::{Mat4, Vec2, Vec3};
 use bevy_transform::components::GlobalTransform;
+use bevy_utils::tracing::warn;
+use thiserror::Error;
 
 pub struct ViewPlugin;
 
 impl Plugin for ViewPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Msaa>().add_plugin(VisibilityPlugin);
+        let depth_format = app
+            .world
+            .get_resource::<ViewDepthFormat>()
+            .copied()
+            .unwrap_or_default();
 
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
+                .insert_resource(depth_format)
                 .init_resource::<ViewUniforms>()
                 .add_system_to_stage(RenderStage::Extract, extract_msaa)
                 .add_system_to_stage(RenderStage::Prepare, prepare_view_uniforms)
@@ -60,6 +70,11 @@ pub struct Msaa {
     /// Note that WGPU currently only supports 1 or 4 samples.
     /// Ultimately we plan on supporting whatever is natively supported on a given device.
     /// Check out this issue for more info: <https://github.com/gfx-rs/wgpu/issues/1832>
+    ///
+    /// This can be changed at runtime. Pipelines are specialized for the sample count (e.g. through
+    /// `MeshPipelineKey::from_msaa_samples`), so they are recreated for the new count, together
+    /// with the view's render targets. Unsupported sample counts are replaced by 4, or by 1 if they
+    /// are 0, when the resource is extracted.
     pub samples: u32,
 }
 
@@ -69,9 +84,37 @@ impl Default for Msaa {
     }
 }
 
+/// The format of the depth textures of views. Defaults to [`TextureFormat::Depth32Float`].
+///
+/// Pipelines can only use stencil operations if this is a format with a stencil aspect, like
+/// [`TextureFormat::Depth24PlusStencil8`]. The format can't be changed at runtime, so this resource
+/// has to be inserted before adding the `RenderPlugin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewDepthFormat(pub TextureFormat);
+
+impl ViewDepthFormat {
+    /// Returns `true` if the format has a stencil aspect.
+    pub fn has_stencil(&self) -> bool {
+        matches!(self.0, TextureFormat::Depth24PlusStencil8)
+    }
+}
+
+impl Default for ViewDepthFormat {
+    fn default() -> Self {
+        Self(TextureFormat::Depth32Float)
+    }
+}
+
 pub fn extract_msaa(mut commands: Commands, msaa: Res<Msaa>) {
     // NOTE: windows.is_changed() handles cases where a window was resized
-    commands.insert_resource(msaa.clone());
+    let samples = if msaa.samples <= 1 { 1 } else { 4 };
+    if samples != msaa.samples && msaa.is_changed() {
+        warn!(
+            "{} MSAA samples are not supported, using {} samples instead",
+            msaa.samples, samples
+        );
+    }
+    commands.insert_resource(Msaa { samples });
 }
 
 #[derive(Component)]
@@ -95,13 +138,24 @@ pub struct ViewUniform {
     far: f32,
     width: f32,
     height: f32,
+    // the top left corner of the camera viewport in the render target, in physical pixels
+    viewport_origin: Vec2,
 }
 
-#[derive(Default)]
 pub struct ViewUniforms {
     pub uniforms: DynamicUniformVec<ViewUniform>,
 }
 
+impl FromWorld for ViewUniforms {
+    fn from_world(world: &mut World) -> Self {
+        let limits = world.resource::<RenderResourceLimits>();
+        let frames = world.resource::<FramesInFlight>();
+        Self {
+            uniforms: DynamicUniformVec::new(limits).with_frames_in_flight(frames),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct ViewUniformOffset {
     pub offset: u32,
@@ -134,18 +188,72 @@ impl ViewTarget {
 #[derive(Component)]
 pub struct ViewDepthTexture {
     pub texture: Texture,
+    /// The view used as the depth attachment of render passes.
     pub view: TextureView,
+    /// The view of only the depth aspect of the texture, which can be bound to be sampled by
+    /// shaders, see [`ViewDepthTexture::sampled_view`].
+    pub depth_view: TextureView,
+    pub format: ViewDepthFormat,
+    pub sample_count: u32,
+}
+
+impl ViewDepthTexture {
+    /// Returns the given stencil `ops` if the texture has a stencil aspect, or [`None`] otherwise.
+    pub fn stencil_ops(&self, ops: Operations<u32>) -> Option<Operations<u32>> {
+        self.format.has_stencil().then(|| ops)
+    }
+
+    /// Returns the view to bind the depth texture with, for effects like soft particles that
+    /// sample the depth of the scene. The texture should only be sampled by passes running after
+    /// the main pass.
+    ///
+    /// Depth textures can't be resolved, so this fails if the texture is multisampled.
+    pub fn sampled_view(&self) -> Result<&TextureView, DepthTextureBindingError> {
+        if self.sample_count > 1 {
+            Err(DepthTextureBindingError::Multisampled(self.sample_count))
+        } else {
+            Ok(&self.depth_view)
+        }
+    }
+
+    /// The [`BindingType`] of bind group layout entries binding the
+    /// [`sampled_view`](ViewDepthTexture::sampled_view). In shaders this is a `texture_depth_2d`.
+    pub fn texture_binding_type() -> BindingType {
+        BindingType::Texture {
+            sample_type: TextureSampleType::Depth,
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        }
+    }
+
+    /// The [`BindingType`] of the sampler to sample the depth texture with. Comparison samplers
+    /// (`sampler_comparison` in shaders) compare the depth against a reference value like shadow
+    /// maps do, otherwise the depth is read directly and the sampler must not filter.
+    pub fn sampler_binding_type(comparison: bool) -> BindingType {
+        BindingType::Sampler(if comparison {
+            SamplerBindingType::Comparison
+        } else {
+            SamplerBindingType::NonFiltering
+        })
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DepthTextureBindingError {
+    #[error("the view depth texture has {0} samples and can't be bound as a texture, disable MSAA to sample it")]
+    Multisampled(u32),
 }
 
 fn prepare_view_uniforms(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
+    statistics: Res<RenderStatistics>,
     mut view_uniforms: ResMut<ViewUniforms>,
-    views: Query<(Entity, &ExtractedView)>,
+    views: Query<(Entity, &ExtractedView, Option<&ExtractedCamera>)>,
 ) {
     view_uniforms.uniforms.clear();
-    for (entity, camera) in views.iter() {
+    for (entity, camera, extracted_camera) in views.iter() {
         let projection = camera.projection;
         let view = camera.transform.compute_matrix();
         let inverse_view = view.inverse();
@@ -160,15 +268,20 @@ fn prepare_view_uniforms(
                 far: camera.far,
                 width: camera.width as f32,
                 height: camera.height as f32,
+                viewport_origin: extracted_camera
+                    .and_then(|camera| camera.viewport.as_ref())
+                    .map_or(Vec2::ZERO, |viewport| viewport.physical_position.as_vec2()),
             }),
         };
 
         commands.entity(entity).insert(view_uniforms);
     }
 
-    view_uniforms
-        .uniforms
-        .write_buffer(&render_device, &render_queue);
+    statistics.record_uniform_writes(
+        view_uniforms
+            .uniforms
+            .write_buffer(&render_device, &render_queue),
+    );
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -213,3 +326,44 @@ fn prepare_view_targets(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_msaa, Msaa, ViewDepthTexture};
+    use bevy_ecs::{
+        schedule::{Stage, SystemStage},
+        world::World,
+    };
+    use wgpu::{BindingType, SamplerBindingType, TextureSampleType};
+
+    #[test]
+    fn unsupported_msaa_samples_are_replaced() {
+        let mut world = World::new();
+        let mut extract = SystemStage::single(extract_msaa);
+        for (samples, extracted) in [(0, 1), (1, 1), (2, 4), (4, 4), (8, 4)] {
+            world.insert_resource(Msaa { samples });
+            extract.run(&mut world);
+            assert_eq!(world.resource::<Msaa>().samples, extracted);
+        }
+    }
+
+    #[test]
+    fn depth_texture_binding_types() {
+        assert!(matches!(
+            ViewDepthTexture::texture_binding_type(),
+            BindingType::Texture {
+                sample_type: TextureSampleType::Depth,
+                multisampled: false,
+                ..
+            }
+        ));
+        assert!(matches!(
+            ViewDepthTexture::sampler_binding_type(true),
+            BindingType::Sampler(SamplerBindingType::Comparison)
+        ));
+        assert!(matches!(
+            ViewDepthTexture::sampler_binding_type(false),
+            BindingType::Sampler(SamplerBindingType::NonFiltering)
+        ));
+    }
+}
@@ -1,17 +1,23 @@
 use bevy_app::{App, Plugin};
 use bevy_asset::{load_internal_asset, AssetServer, Assets, Handle, HandleUntyped};
-use bevy_ecs::system::{lifetimeless::SRes, SystemParamItem};
+use bevy_ecs::system::{
+    lifetimeless::{SRes, SResMut},
+    SystemParamItem,
+};
 use bevy_math::Vec4;
 use bevy_reflect::TypeUuid;
 use bevy_render::{
     color::Color,
     prelude::Shader,
-    render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+    render_asset::{
+        DependentRenderAsset, PrepareAssetError, RenderAsset, RenderAssetDependencyPlugin,
+        RenderAssets,
+    },
     render_resource::{
         std140::{AsStd140, Std140},
         *,
     },
-    renderer::RenderDevice,
+    renderer::{RenderDevice, RenderQueue},
     texture::Image,
 };
 
@@ -32,7 +38,8 @@ impl Plugin for ColorMaterialPlugin {
             Shader::from_wgsl
         );
 
-        app.add_plugin(Material2dPlugin::<ColorMaterial>::default());
+        app.add_plugin(Material2dPlugin::<ColorMaterial>::default())
+            .add_plugin(RenderAssetDependencyPlugin::<ColorMaterial, Image>::default());
 
         app.world
             .resource_mut::<Assets<ColorMaterial>>()
@@ -101,8 +108,8 @@ pub struct ColorMaterialUniformData {
 /// The GPU representation of a [`ColorMaterial`].
 #[derive(Debug, Clone)]
 pub struct GpuColorMaterial {
-    /// A buffer containing the [`ColorMaterialUniformData`] of the material.
-    pub buffer: Buffer,
+    /// The range of a uniform buffer containing the [`ColorMaterialUniformData`] of the material.
+    pub uniform: UniformAllocation,
     /// The bind group specifying how the [`ColorMaterialUniformData`] and
     /// the texture of the material are bound.
     pub bind_group: BindGroup,
@@ -115,6 +122,8 @@ impl RenderAsset for ColorMaterial {
     type PreparedAsset = GpuColorMaterial;
     type Param = (
         SRes<RenderDevice>,
+        SRes<RenderQueue>,
+        SResMut<UniformAllocator>,
         SRes<Material2dPipeline<ColorMaterial>>,
         SRes<RenderAssets<Image>>,
     );
@@ -125,8 +134,9 @@ impl RenderAsset for ColorMaterial {
 
     fn prepare_asset(
         material: Self::ExtractedAsset,
-        (render_device, color_pipeline, gpu_images): &mut SystemParamItem<Self::Param>,
+        param: &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let (render_device, render_queue, uniform_allocator, color_pipeline, gpu_images) = param;
         let (texture_view, sampler) = if let Some(result) = color_pipeline
             .mesh2d_pipeline
             .get_image_texture(gpu_images, &material.texture)
@@ -147,16 +157,16 @@ impl RenderAsset for ColorMaterial {
         };
         let value_std140 = value.as_std140();
 
-        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("color_material_uniform_buffer"),
-            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-            contents: value_std140.as_bytes(),
-        });
+        let uniform = uniform_allocator.allocate_with_data(
+            render_device,
+            render_queue,
+            value_std140.as_bytes(),
+        );
         let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
             entries: &[
                 BindGroupEntry {
                     binding: 0,
-                    resource: buffer.as_entire_binding(),
+                    resource: uniform.binding(),
                 },
                 BindGroupEntry {
                     binding: 1,
@@ -172,7 +182,7 @@ impl RenderAsset for ColorMaterial {
         });
 
         Ok(GpuColorMaterial {
-            buffer,
+            uniform,
             bind_group,
             flags,
             texture: material.texture,
@@ -180,6 +190,12 @@ impl RenderAsset for ColorMaterial {
     }
 }
 
+impl DependentRenderAsset<Image> for ColorMaterial {
+    fn dependencies(&self) -> Vec<Handle<Image>> {
+        self.texture.iter().cloned().collect()
+    }
+}
+
 impl Material2d for ColorMaterial {
     fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
         Some(COLOR_MATERIAL_SHADER_HANDLE.typed())
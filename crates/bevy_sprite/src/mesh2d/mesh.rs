@@ -58,7 +58,7 @@ impl Plugin for Mesh2dRenderPlugin {
             Shader::from_wgsl
         );
 
-        app.add_plugin(UniformComponentPlugin::<Mesh2dUniform>::default());
+        app.add_plugin(UniformComponentPlugin::<Mesh2dUniform>::default().skip_unchanged());
 
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
@@ -71,7 +71,7 @@ impl Plugin for Mesh2dRenderPlugin {
     }
 }
 
-#[derive(Component, AsStd140, Clone)]
+#[derive(Component, AsStd140, Clone, PartialEq)]
 pub struct Mesh2dUniform {
     pub transform: Mat4,
     pub inverse_transpose_model: Mat4,
@@ -193,7 +193,9 @@ impl FromWorld for Mesh2dPipeline {
             GpuImage {
                 texture,
                 texture_view,
+                texture_view_dimension: TextureViewDimension::D2,
                 texture_format: image.texture_descriptor.format,
+                texture_usage: image.texture_descriptor.usage,
                 sampler,
                 size: Size::new(
                     image.texture_descriptor.size.width as f32,
@@ -236,6 +238,7 @@ bitflags::bitflags! {
         const NONE                        = 0;
         const MSAA_RESERVED_BITS          = Mesh2dPipelineKey::MSAA_MASK_BITS << Mesh2dPipelineKey::MSAA_SHIFT_BITS;
         const PRIMITIVE_TOPOLOGY_RESERVED_BITS = Mesh2dPipelineKey::PRIMITIVE_TOPOLOGY_MASK_BITS << Mesh2dPipelineKey::PRIMITIVE_TOPOLOGY_SHIFT_BITS;
+        const STRIP_INDEX_FORMAT_RESERVED_BITS = Mesh2dPipelineKey::STRIP_INDEX_FORMAT_MASK_BITS << Mesh2dPipelineKey::STRIP_INDEX_FORMAT_SHIFT_BITS;
     }
 }
 
@@ -244,6 +247,8 @@ impl Mesh2dPipelineKey {
     const MSAA_SHIFT_BITS: u32 = 32 - 6;
     const PRIMITIVE_TOPOLOGY_MASK_BITS: u32 = 0b111;
     const PRIMITIVE_TOPOLOGY_SHIFT_BITS: u32 = Self::MSAA_SHIFT_BITS - 3;
+    const STRIP_INDEX_FORMAT_MASK_BITS: u32 = 0b11;
+    const STRIP_INDEX_FORMAT_SHIFT_BITS: u32 = Self::PRIMITIVE_TOPOLOGY_SHIFT_BITS - 2;
 
     pub fn from_msaa_samples(msaa_samples: u32) -> Self {
         let msaa_bits = ((msaa_samples - 1) & Self::MSAA_MASK_BITS) << Self::MSAA_SHIFT_BITS;
@@ -273,6 +278,25 @@ impl Mesh2dPipelineKey {
             _ => PrimitiveTopology::default(),
         }
     }
+
+    pub fn from_strip_index_format(strip_index_format: Option<IndexFormat>) -> Self {
+        let strip_index_format_bits = match strip_index_format {
+            None => 0,
+            Some(IndexFormat::Uint16) => 1,
+            Some(IndexFormat::Uint32) => 2,
+        } << Self::STRIP_INDEX_FORMAT_SHIFT_BITS;
+        Self::from_bits(strip_index_format_bits).unwrap()
+    }
+
+    pub fn strip_index_format(&self) -> Option<IndexFormat> {
+        let strip_index_format_bits =
+            (self.bits >> Self::STRIP_INDEX_FORMAT_SHIFT_BITS) & Self::STRIP_INDEX_FORMAT_MASK_BITS;
+        match strip_index_format_bits {
+            1 => Some(IndexFormat::Uint16),
+            2 => Some(IndexFormat::Uint32),
+            _ => None,
+        }
+    }
 }
 
 impl SpecializedMeshPipeline for Mesh2dPipeline {
@@ -289,14 +313,14 @@ impl SpecializedMeshPipeline for Mesh2dPipeline {
             Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
         ];
 
-        let mut shader_defs = Vec::new();
+        let mut shader_defs = ShaderDefs::new();
         if layout.contains(Mesh::ATTRIBUTE_TANGENT) {
-            shader_defs.push(String::from("VERTEX_TANGENTS"));
+            shader_defs.push("VERTEX_TANGENTS");
             vertex_attributes.push(Mesh::ATTRIBUTE_TANGENT.at_shader_location(3));
         }
 
         #[cfg(feature = "webgl")]
-        shader_defs.push(String::from("NO_ARRAY_TEXTURES_SUPPORT"));
+        shader_defs.push("NO_ARRAY_TEXTURES_SUPPORT");
 
         let vertex_buffer_layout = layout.get_layout(&vertex_attributes)?;
 
@@ -304,13 +328,18 @@ impl SpecializedMeshPipeline for Mesh2dPipeline {
             vertex: VertexState {
                 shader: MESH2D_SHADER_HANDLE.typed::<Shader>(),
                 entry_point: "vertex".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 shader_defs: shader_defs.clone(),
                 buffers: vec![vertex_buffer_layout],
+                allow_unused_attributes: false,
             },
             fragment: Some(FragmentState {
                 shader: MESH2D_SHADER_HANDLE.typed::<Shader>(),
                 shader_defs,
                 entry_point: "fragment".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 targets: vec![ColorTargetState {
                     format: TextureFormat::bevy_default(),
                     blend: Some(BlendState::ALPHA_BLENDING),
@@ -318,6 +347,7 @@ impl SpecializedMeshPipeline for Mesh2dPipeline {
                 }],
             }),
             layout: Some(vec![self.view_layout.clone(), self.mesh_layout.clone()]),
+            push_constant_ranges: Vec::new(),
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
                 cull_mode: Some(Face::Back),
@@ -325,7 +355,7 @@ impl SpecializedMeshPipeline for Mesh2dPipeline {
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
                 topology: key.primitive_topology(),
-                strip_index_format: None,
+                strip_index_format: key.strip_index_format(),
             },
             depth_stencil: None,
             multisample: MultisampleState {
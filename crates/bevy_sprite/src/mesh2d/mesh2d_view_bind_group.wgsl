@@ -10,4 +10,5 @@ struct View {
     far: f32;
     width: f32;
     height: f32;
+    viewport_origin: vec2<f32>;
 };
@@ -128,22 +128,27 @@ impl SpecializedRenderPipeline for SpritePipeline {
         let vertex_layout =
             VertexBufferLayout::from_vertex_formats(VertexStepMode::Vertex, formats);
 
-        let mut shader_defs = Vec::new();
+        let mut shader_defs = ShaderDefs::new();
         if key.contains(SpritePipelineKey::COLORED) {
-            shader_defs.push("COLORED".to_string());
+            shader_defs.push("COLORED");
         }
 
         RenderPipelineDescriptor {
             vertex: VertexState {
                 shader: SPRITE_SHADER_HANDLE.typed::<Shader>(),
                 entry_point: "vertex".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 shader_defs: shader_defs.clone(),
                 buffers: vec![vertex_layout],
+                allow_unused_attributes: false,
             },
             fragment: Some(FragmentState {
                 shader: SPRITE_SHADER_HANDLE.typed::<Shader>(),
                 shader_defs,
                 entry_point: "fragment".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 targets: vec![ColorTargetState {
                     format: TextureFormat::bevy_default(),
                     blend: Some(BlendState::ALPHA_BLENDING),
@@ -151,6 +156,7 @@ impl SpecializedRenderPipeline for SpritePipeline {
                 }],
             }),
             layout: Some(vec![self.view_layout.clone(), self.material_layout.clone()]),
+            push_constant_ranges: Vec::new(),
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
                 cull_mode: None,
@@ -77,19 +77,24 @@ impl SpecializedRenderPipeline for UiPipeline {
                 VertexFormat::Uint32,
             ],
         );
-        let shader_defs = Vec::new();
+        let shader_defs = ShaderDefs::new();
 
         RenderPipelineDescriptor {
             vertex: VertexState {
                 shader: super::UI_SHADER_HANDLE.typed::<Shader>(),
                 entry_point: "vertex".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 shader_defs: shader_defs.clone(),
                 buffers: vec![vertex_layout],
+                allow_unused_attributes: false,
             },
             fragment: Some(FragmentState {
                 shader: super::UI_SHADER_HANDLE.typed::<Shader>(),
                 shader_defs,
                 entry_point: "fragment".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
                 targets: vec![ColorTargetState {
                     format: TextureFormat::bevy_default(),
                     blend: Some(BlendState::ALPHA_BLENDING),
@@ -97,6 +102,7 @@ impl SpecializedRenderPipeline for UiPipeline {
                 }],
             }),
             layout: Some(vec![self.view_layout.clone(), self.image_layout.clone()]),
+            push_constant_ranges: Vec::new(),
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
                 cull_mode: None,
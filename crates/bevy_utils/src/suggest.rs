@@ -0,0 +1,43 @@
+/// Returns the candidate closest to `name`, if it is close enough to likely be what was meant. This
+/// is used to suggest names in error messages about misspelled ones.
+/// ```
+/// use bevy_utils::closest_name;
+///
+/// let fields = ["base_color", "roughness", "metallic"];
+/// assert_eq!(closest_name("roughnes", fields), Some("roughness"));
+/// assert_eq!(closest_name("emissive", fields), None);
+/// ```
+pub fn closest_name<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (name.chars().count() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between the strings, counted in chars.
+/// ```
+/// use bevy_utils::edit_distance;
+///
+/// assert_eq!(edit_distance("kitten", "sitting"), 3);
+/// assert_eq!(edit_distance("", "abc"), 3);
+/// ```
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // the distances between the processed prefix of `a` and each prefix of `b`
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + (a_char != *b_char) as usize;
+            diagonal = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(diagonal + 1);
+        }
+    }
+    distances[b.len()]
+}
@@ -0,0 +1,219 @@
+//! Renders lines and points with a custom [`Material`]. The primitive topology is taken from the
+//! mesh, unless the material declares its own.
+
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{FaceCulling, MaterialPipeline},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::PrimitiveTopology,
+        render_asset::{PrepareAssetError, RenderAsset},
+        render_resource::{
+            std140::{AsStd140, Std140},
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages, ShaderStages,
+        },
+        renderer::RenderDevice,
+    },
+};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(MaterialPlugin::<LineMaterial>::default())
+        .add_startup_system(setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+) {
+    // the axes of the coordinate system, as pairs of vertices
+    commands.spawn().insert_bundle(MaterialMeshBundle {
+        mesh: meshes.add(Mesh::from(LineList {
+            lines: vec![
+                (Vec3::ZERO, Vec3::X),
+                (Vec3::ZERO, Vec3::Y),
+                (Vec3::ZERO, Vec3::Z),
+            ],
+        })),
+        material: materials.add(LineMaterial {
+            color: Color::WHITE,
+            topology: None,
+        }),
+        ..default()
+    });
+
+    // a spiral, as a single connected strip of vertices
+    let spiral = meshes.add(Mesh::from(LineStrip {
+        points: (0..100)
+            .map(|i| {
+                let t = i as f32 * 0.1;
+                Vec3::new(t.cos() * 0.5, t * 0.1, t.sin() * 0.5)
+            })
+            .collect(),
+    }));
+    commands.spawn().insert_bundle(MaterialMeshBundle {
+        mesh: spiral.clone(),
+        transform: Transform::from_xyz(1.0, 0.0, 0.0),
+        material: materials.add(LineMaterial {
+            color: Color::GREEN,
+            topology: None,
+        }),
+        ..default()
+    });
+
+    // the same spiral, but the material only renders the vertices
+    commands.spawn().insert_bundle(MaterialMeshBundle {
+        mesh: spiral,
+        transform: Transform::from_xyz(-1.0, 0.0, 0.0),
+        material: materials.add(LineMaterial {
+            color: Color::RED,
+            topology: Some(PrimitiveTopology::PointList),
+        }),
+        ..default()
+    });
+
+    // camera
+    commands.spawn_bundle(PerspectiveCameraBundle {
+        transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "050ce6ac-080a-4d8c-b6b5-b5bab7560d8f"]
+pub struct LineMaterial {
+    color: Color,
+    /// Overrides the primitive topology of the mesh
+    topology: Option<PrimitiveTopology>,
+}
+
+#[derive(Clone)]
+pub struct GpuLineMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+    topology: Option<PrimitiveTopology>,
+}
+
+impl RenderAsset for LineMaterial {
+    type ExtractedAsset = LineMaterial;
+    type PreparedAsset = GpuLineMaterial;
+    type Param = (SRes<RenderDevice>, SRes<MaterialPipeline<Self>>);
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        extracted_asset: Self::ExtractedAsset,
+        (render_device, material_pipeline): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let color = Vec4::from_slice(&extracted_asset.color.as_linear_rgba_f32());
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            contents: color.as_std140().as_bytes(),
+            label: None,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: None,
+            layout: &material_pipeline.material_layout,
+        });
+
+        Ok(GpuLineMaterial {
+            _buffer: buffer,
+            bind_group,
+            topology: extracted_asset.topology,
+        })
+    }
+}
+
+impl Material for LineMaterial {
+    fn fragment_shader(asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(asset_server.load("shaders/line_material.wgsl"))
+    }
+
+    fn bind_group(render_asset: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &render_asset.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(Vec4::std140_size_static() as u64),
+                },
+                count: None,
+            }],
+            label: None,
+        })
+    }
+
+    fn primitive_topology(
+        render_asset: &<Self as RenderAsset>::PreparedAsset,
+    ) -> Option<PrimitiveTopology> {
+        render_asset.topology
+    }
+
+    // Lines have no faces, so there is nothing to cull
+    fn face_culling(_render_asset: &<Self as RenderAsset>::PreparedAsset) -> FaceCulling {
+        FaceCulling::NONE
+    }
+}
+
+/// A list of lines with a start and end position
+#[derive(Debug, Clone)]
+pub struct LineList {
+    pub lines: Vec<(Vec3, Vec3)>,
+}
+
+impl From<LineList> for Mesh {
+    fn from(line: LineList) -> Self {
+        let positions: Vec<_> = line
+            .lines
+            .into_iter()
+            .flat_map(|(start, end)| [start.to_array(), end.to_array()])
+            .collect();
+        line_mesh(PrimitiveTopology::LineList, positions)
+    }
+}
+
+/// A list of points that will have a line drawn between each consecutive point
+#[derive(Debug, Clone)]
+pub struct LineStrip {
+    pub points: Vec<Vec3>,
+}
+
+impl From<LineStrip> for Mesh {
+    fn from(line: LineStrip) -> Self {
+        let positions: Vec<_> = line
+            .points
+            .into_iter()
+            .map(|point| point.to_array())
+            .collect();
+        line_mesh(PrimitiveTopology::LineStrip, positions)
+    }
+}
+
+fn line_mesh(primitive_topology: PrimitiveTopology, positions: Vec<[f32; 3]>) -> Mesh {
+    // the mesh pipeline requires normals and uvs, even though they aren't used to render lines
+    let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+
+    let mut mesh = Mesh::new(primitive_topology);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
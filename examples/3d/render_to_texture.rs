@@ -7,9 +7,7 @@ use bevy::{
         camera::{ActiveCamera, Camera, CameraTypePlugin, RenderTarget},
         render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotValue},
         render_phase::RenderPhase,
-        render_resource::{
-            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
-        },
+        render_resource::{Extent3d, TextureFormat},
         renderer::RenderContext,
         view::RenderLayers,
         RenderApp, RenderStage,
@@ -53,6 +51,12 @@ fn main() {
     graph
         .add_node_edge(FIRST_PASS_DRIVER, node::MAIN_PASS_DRIVER)
         .unwrap();
+
+    // The main pass samples the texture the first pass renders to, which it would otherwise
+    // sample before it is rendered.
+    graph
+        .validate_render_target_order(FIRST_PASS_DRIVER, node::MAIN_PASS_DRIVER)
+        .unwrap();
     app.run();
 }
 
@@ -122,23 +126,7 @@ fn setup(
     };
 
     // This is the texture that will be rendered to.
-    let mut image = Image {
-        texture_descriptor: TextureDescriptor {
-            label: None,
-            size,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Bgra8UnormSrgb,
-            mip_level_count: 1,
-            sample_count: 1,
-            usage: TextureUsages::TEXTURE_BINDING
-                | TextureUsages::COPY_DST
-                | TextureUsages::RENDER_ATTACHMENT,
-        },
-        ..default()
-    };
-
-    // fill image.data with zeroes
-    image.resize(size);
+    let image = Image::new_render_target(size, TextureFormat::Bgra8UnormSrgb);
 
     let image_handle = images.add(image);
 
@@ -0,0 +1,559 @@
+//! Draws 10,000 cubes with a handful of draw calls, by batching all visible entities that draw the
+//! same mesh with the same material into one instanced draw.
+//!
+//! Unlike the `shader_instancing` example, every cube is its own entity with its own
+//! [`Transform`] and is frustum culled on its own. The material of a batch is bound once, only the
+//! transforms of the entities are stored per instance. Entities with a [`ColorOverride`] drop out
+//! of their batch and are drawn individually.
+
+use bevy::{
+    core_pipeline::Opaque3d,
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshViewBindGroup},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::{
+            FallbackVertexBuffer, GpuBufferInfo, MeshVertexBufferLayout,
+            FALLBACK_VERTEX_BUFFER_SLOT,
+        },
+        render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets},
+        render_component::{ExtractComponent, ExtractComponentPlugin},
+        render_phase::{
+            AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+            SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::{
+            std140::{AsStd140, Std140},
+            *,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        view::{ComputedVisibility, Msaa, Visibility},
+        RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+use bytemuck::{Pod, Zeroable};
+use std::hash::Hash;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(AutomaticInstancingPlugin)
+        .add_startup_system(setup)
+        .add_system(rotate_camera)
+        .run();
+}
+
+/// The material of automatically instanced entities.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "2c4f7a9e-5b1d-4e8a-9c3f-6d0b8e2a7f15"]
+pub struct InstancedMaterial {
+    color: Color,
+}
+
+/// Overrides the color of the [`InstancedMaterial`] of an entity, which is then drawn on its own.
+#[derive(Component, Clone, Copy)]
+struct ColorOverride(Color);
+
+impl ExtractComponent for ColorOverride {
+    type Query = &'static ColorOverride;
+    type Filter = ();
+
+    fn extract_component(item: bevy::ecs::query::QueryItem<Self::Query>) -> Self {
+        *item
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<InstancedMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let cube = meshes.add(Mesh::from(shape::Cube { size: 0.5 }));
+    let materials = [Color::RED, Color::GREEN, Color::BLUE]
+        .map(|color| materials.add(InstancedMaterial { color }));
+    for x in 0..100 {
+        for z in 0..100 {
+            let mut cube = commands.spawn_bundle((
+                cube.clone(),
+                materials[(x / 10 + z / 10) % materials.len()].clone(),
+                Transform::from_xyz(x as f32 - 49.5, 0.25, z as f32 - 49.5),
+                GlobalTransform::default(),
+                Visibility::default(),
+                ComputedVisibility::default(),
+            ));
+            if (x + z * 7) % 101 == 0 {
+                cube.insert(ColorOverride(Color::WHITE));
+            }
+        }
+    }
+
+    // the ground uses the regular material path
+    commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Plane { size: 110.0 })),
+        material: standard_materials.add(Color::rgb(0.3, 0.3, 0.3).into()),
+        ..default()
+    });
+    commands.spawn_bundle(DirectionalLightBundle::default());
+
+    commands.spawn_bundle(PerspectiveCameraBundle {
+        transform: Transform::from_xyz(0.0, 40.0, 70.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+fn rotate_camera(mut camera: Query<&mut Transform, With<Camera>>, time: Res<Time>) {
+    for mut transform in camera.iter_mut() {
+        transform.rotate_around(
+            Vec3::ZERO,
+            Quat::from_rotation_y(10f32.to_radians() * time.delta_seconds()),
+        );
+        transform.look_at(Vec3::ZERO, Vec3::Y);
+    }
+}
+
+pub struct AutomaticInstancingPlugin;
+
+impl Plugin for AutomaticInstancingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<InstancedMaterial>()
+            .add_plugin(ExtractComponentPlugin::<Handle<InstancedMaterial>>::default())
+            .add_plugin(ExtractComponentPlugin::<ColorOverride>::default())
+            .add_plugin(RenderAssetPlugin::<InstancedMaterial>::default());
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawInstanceBatch>()
+            .init_resource::<InstancingPipeline>()
+            .init_resource::<SpecializedMeshPipelines<InstancingPipeline>>()
+            .init_resource::<BufferGrowthPolicy>()
+            .init_resource::<InstanceBuffers>()
+            .add_system_to_stage(RenderStage::Prepare, prepare_instance_batches)
+            .add_system_to_stage(RenderStage::Queue, queue_instance_batches);
+    }
+}
+
+pub struct GpuInstancedMaterial {
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for InstancedMaterial {
+    type ExtractedAsset = InstancedMaterial;
+    type PreparedAsset = GpuInstancedMaterial;
+    type Param = (SRes<RenderDevice>, SRes<InstancingPipeline>);
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        extracted_asset: Self::ExtractedAsset,
+        (render_device, instancing_pipeline): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let color = Vec4::from_slice(&extracted_asset.color.as_linear_rgba_f32());
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            contents: color.as_std140().as_bytes(),
+            label: None,
+            usage: BufferUsages::UNIFORM,
+        });
+        Ok(GpuInstancedMaterial {
+            bind_group: instancing_pipeline.material_bind_group(render_device, &buffer),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct InstanceData {
+    model: Mat4,
+}
+
+/// The instances drawn by the entity this is inserted on, and the material they are drawn with.
+#[derive(Component)]
+pub struct InstanceBatch {
+    buffer: Buffer,
+    length: u32,
+    material_bind_group: BindGroup,
+}
+
+/// The instance buffers of the batches, which are kept across frames and grow and shrink
+/// according to the [`BufferGrowthPolicy`] resource.
+#[derive(Default)]
+struct InstanceBuffers(HashMap<(Handle<Mesh>, Handle<InstancedMaterial>), BufferVec<InstanceData>>);
+
+/// A draw call of [`prepare_instance_batches`], drawing the instances at the `instances` indices.
+#[derive(Debug, PartialEq)]
+struct InstanceDraw<K> {
+    /// The entity the draw is queued for.
+    entity: Entity,
+    /// The key of the batch, or `None` if the instance is drawn on its own.
+    key: Option<K>,
+    instances: Vec<usize>,
+}
+
+/// Batches the `instances` into draw calls. Instances with the same key are drawn by one instanced
+/// draw queued for the first of them, instances without a key are drawn individually.
+fn batch_instances<K: Clone + Eq + Hash>(
+    instances: impl IntoIterator<Item = (Entity, Option<K>)>,
+) -> Vec<InstanceDraw<K>> {
+    let mut draws = Vec::new();
+    let mut batches = HashMap::default();
+    for (index, (entity, key)) in instances.into_iter().enumerate() {
+        let draw = match key {
+            Some(key) => *batches.entry(key.clone()).or_insert_with(|| {
+                draws.push(InstanceDraw {
+                    entity,
+                    key: Some(key),
+                    instances: Vec::new(),
+                });
+                draws.len() - 1
+            }),
+            None => {
+                draws.push(InstanceDraw {
+                    entity,
+                    key: None,
+                    instances: Vec::new(),
+                });
+                draws.len() - 1
+            }
+        };
+        draws[draw].instances.push(index);
+    }
+    draws
+}
+
+/// Groups the visible entities by their mesh and material. All entities drawing the same mesh
+/// also use the same pipeline, since its key only depends on the mesh. Every group is drawn by its
+/// first entity.
+#[allow(clippy::too_many_arguments)]
+fn prepare_instance_batches(
+    mut commands: Commands,
+    // only visible entities are extracted with a mesh uniform
+    instances: Query<(
+        Entity,
+        &Handle<Mesh>,
+        &MeshUniform,
+        &Handle<InstancedMaterial>,
+        Option<&ColorOverride>,
+    )>,
+    materials: Res<RenderAssets<InstancedMaterial>>,
+    instancing_pipeline: Res<InstancingPipeline>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    growth_policy: Res<BufferGrowthPolicy>,
+    mut instance_buffers: ResMut<InstanceBuffers>,
+    mut buffer_pool: ResMut<BufferPool>,
+    mut frame: Local<u32>,
+) {
+    // sorting by entity keeps the order of the instances stable across frames
+    let mut instances = instances.iter().collect::<Vec<_>>();
+    instances.sort_unstable_by_key(|(entity, ..)| *entity);
+    let draws = batch_instances(instances.iter().map(
+        |(entity, mesh, _, material, color_override)| {
+            let key = (mesh.clone_weak(), material.clone_weak());
+            (*entity, color_override.is_none().then(|| key))
+        },
+    ));
+
+    let instance_buffers = &mut instance_buffers.0;
+    // the buffers of batches that aren't drawn anymore are freed
+    instance_buffers.retain(|key, _| draws.iter().any(|draw| draw.key.as_ref() == Some(key)));
+    for draw in &draws {
+        let key = match &draw.key {
+            Some(key) => key,
+            None => {
+                let (_, _, mesh_uniform, _, color_override) = instances[draw.instances[0]];
+                let instance = InstanceData {
+                    model: mesh_uniform.transform,
+                };
+                // the overridden color can't be bound once for the whole batch
+                let color = Vec4::from_slice(&color_override.unwrap().0.as_linear_rgba_f32());
+                let uniform_buffer = buffer_pool.get_with_data(
+                    &render_device,
+                    &render_queue,
+                    BufferUsages::UNIFORM,
+                    color.as_std140().as_bytes(),
+                );
+                let material_bind_group =
+                    instancing_pipeline.material_bind_group(&render_device, uniform_buffer);
+                let buffer = buffer_pool.get_with_data(
+                    &render_device,
+                    &render_queue,
+                    BufferUsages::VERTEX,
+                    bytemuck::bytes_of(&instance),
+                );
+                commands.entity(draw.entity).insert(InstanceBatch {
+                    buffer: buffer.clone(),
+                    length: 1,
+                    material_bind_group,
+                });
+                continue;
+            }
+        };
+        let material = match materials.get(&key.1) {
+            Some(material) => material,
+            None => continue,
+        };
+        let instance_buffer = instance_buffers.entry(key.clone()).or_insert_with(|| {
+            BufferVec::new(BufferUsages::VERTEX).with_growth_policy(*growth_policy)
+        });
+        instance_buffer.clear();
+        for &index in &draw.instances {
+            let (_, _, mesh_uniform, ..) = instances[index];
+            instance_buffer.push(InstanceData {
+                model: mesh_uniform.transform,
+            });
+        }
+        instance_buffer.write_buffer(&render_device, &render_queue);
+        commands.entity(draw.entity).insert(InstanceBatch {
+            buffer: instance_buffer.buffer().unwrap().clone(),
+            length: instance_buffer.len() as u32,
+            material_bind_group: material.bind_group.clone(),
+        });
+    }
+
+    *frame += 1;
+    if *frame % 300 == 1 {
+        info!(
+            "drawing {} visible entities with {} draw calls",
+            instances.len(),
+            draws.len()
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_instance_batches(
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    instancing_pipeline: Res<InstancingPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<InstancingPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    batches: Query<(Entity, &Handle<Mesh>), With<InstanceBatch>>,
+    mut views: Query<&mut RenderPhase<Opaque3d>>,
+) {
+    let draw_instance_batch = opaque_3d_draw_functions
+        .read()
+        .get_id::<DrawInstanceBatch>()
+        .unwrap();
+
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples);
+
+    for mut opaque_phase in views.iter_mut() {
+        for (entity, mesh_handle) in batches.iter() {
+            if let Some(mesh) = meshes.get(mesh_handle) {
+                let key =
+                    msaa_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                let pipeline = pipelines
+                    .specialize(&mut pipeline_cache, &instancing_pipeline, key, &mesh.layout)
+                    .unwrap();
+                opaque_phase.add(Opaque3d {
+                    distance: 0.0,
+                    pipeline,
+                    material_bind_group: None,
+                    mesh: Some(mesh_handle.id),
+                    entity,
+                    draw_function: draw_instance_batch,
+                });
+            }
+        }
+    }
+}
+
+pub struct InstancingPipeline {
+    shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+    material_layout: BindGroupLayout,
+}
+
+impl FromWorld for InstancingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        let shader = asset_server.load("shaders/automatic_instancing.wgsl");
+        let mesh_pipeline = world.resource::<MeshPipeline>().clone();
+        let render_device = world.resource::<RenderDevice>();
+        let material_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(Vec4::std140_size_static() as u64),
+                },
+                count: None,
+            }],
+            label: Some("instanced_material_layout"),
+        });
+
+        InstancingPipeline {
+            shader,
+            mesh_pipeline,
+            material_layout,
+        }
+    }
+}
+
+impl InstancingPipeline {
+    /// Binds the `buffer` holding the color of a material.
+    fn material_bind_group(&self, render_device: &RenderDevice, buffer: &Buffer) -> BindGroup {
+        render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: BufferSize::new(Vec4::std140_size_static() as u64),
+                }),
+            }],
+            label: Some("instanced_material_bind_group"),
+            layout: &self.material_layout,
+        })
+    }
+}
+
+/// The vertex buffer slot of the instance data, which follows the mesh and the buffer the mesh
+/// pipeline reads missing attributes from.
+const INSTANCE_DATA_SLOT: usize = FALLBACK_VERTEX_BUFFER_SLOT + 1;
+
+impl SpecializedMeshPipeline for InstancingPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+        descriptor.vertex.shader = self.shader.clone();
+        // the model matrix of each instance follows the attributes of the mesh pipeline. The attributes
+        // the mesh lacks are read from the fallback buffer, so they count as well
+        let first_location = descriptor
+            .vertex
+            .buffers
+            .iter()
+            .flat_map(|buffer| &buffer.attributes)
+            .map(|attribute| attribute.shader_location + 1)
+            .max()
+            .unwrap_or(0);
+        // meshes with all attributes have no fallback layout, but the fallback buffer is bound
+        // anyway, so that the instance data always uses the same slot
+        descriptor
+            .vertex
+            .buffers
+            .resize_with(INSTANCE_DATA_SLOT, || VertexBufferLayout {
+                array_stride: 0,
+                step_mode: VertexStepMode::Vertex,
+                attributes: Vec::new(),
+            });
+        descriptor.vertex.buffers.push(
+            VertexBufferLayout::from_vertex_formats(
+                VertexStepMode::Instance,
+                [VertexFormat::Float32x4; 4],
+            )
+            .with_first_shader_location(first_location),
+        );
+        descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
+        // the instances don't need the mesh bind group, their transforms are in the instance data
+        descriptor.layout = Some(vec![
+            self.mesh_pipeline.view_layout.clone(),
+            self.material_layout.clone(),
+        ]);
+
+        Ok(descriptor)
+    }
+}
+
+type DrawInstanceBatch = (SetItemPipeline, SetMeshViewBindGroup<0>, DrawInstances);
+
+pub struct DrawInstances;
+impl EntityRenderCommand for DrawInstances {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SRes<FallbackVertexBuffer>,
+        SQuery<Read<Handle<Mesh>>>,
+        SQuery<Read<InstanceBatch>>,
+    );
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, fallback_vertex_buffer, mesh_query, batch_query): SystemParamItem<
+            'w,
+            '_,
+            Self::Param,
+        >,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let mesh_handle = mesh_query.get(item).unwrap();
+        let batch = batch_query.get_inner(item).unwrap();
+
+        let gpu_mesh = match meshes.into_inner().get(mesh_handle) {
+            Some(gpu_mesh) => gpu_mesh,
+            None => return RenderCommandResult::Failure,
+        };
+
+        pass.set_bind_group(1, &batch.material_bind_group, &[]);
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(
+            FALLBACK_VERTEX_BUFFER_SLOT,
+            fallback_vertex_buffer.into_inner().buffer.slice(..),
+        );
+        pass.set_vertex_buffer(INSTANCE_DATA_SLOT, batch.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..batch.length);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, 0..batch.length);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{batch_instances, InstanceDraw};
+    use bevy::prelude::Entity;
+
+    #[test]
+    fn homogeneous_instances_are_drawn_once() {
+        let entities = (0..1000).map(Entity::from_raw).collect::<Vec<_>>();
+        let draws = batch_instances(entities.iter().map(|entity| (*entity, Some("cube"))));
+        assert_eq!(
+            draws,
+            vec![InstanceDraw {
+                entity: entities[0],
+                key: Some("cube"),
+                instances: (0..1000).collect(),
+            }]
+        );
+    }
+
+    #[test]
+    fn instances_without_key_are_drawn_individually() {
+        let keys = [Some("red"), None, Some("green"), Some("red"), None];
+        let draws = batch_instances((0..).map(Entity::from_raw).zip(keys));
+        let draws = draws
+            .iter()
+            .map(|draw| (draw.entity.id(), draw.key, draw.instances.as_slice()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            draws,
+            [
+                (0, Some("red"), &[0, 3][..]),
+                (1, None, &[1][..]),
+                (2, Some("green"), &[2][..]),
+                (4, None, &[4][..]),
+            ]
+        );
+    }
+}
@@ -40,8 +40,8 @@ fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
         &[0, 0, 0, 255],
         TextureFormat::Rgba8Unorm,
     );
-    image.texture_descriptor.usage =
-        TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+    // the compute shader writes to the image, which the sprite then samples
+    image.ensure_usage(TextureUsages::STORAGE_BINDING);
     let image = images.add(image);
 
     commands.spawn_bundle(SpriteBundle {
@@ -91,6 +91,9 @@ fn queue_bind_group(
     render_device: Res<RenderDevice>,
 ) {
     let view = &gpu_images[&game_of_life_image.0];
+    // the node dispatching the compute shader expects the bind group to exist
+    view.check_storage_binding("texture")
+        .unwrap_or_else(|err| panic!("can't bind the game of life image: {}", err));
     let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
         label: None,
         layout: &pipeline.texture_bind_group_layout,
@@ -133,16 +136,22 @@ impl FromWorld for GameOfLifePipeline {
         let init_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             label: None,
             layout: Some(vec![texture_bind_group_layout.clone()]),
+            push_constant_ranges: Vec::new(),
             shader: shader.clone(),
-            shader_defs: vec![],
+            shader_defs: ShaderDefs::new(),
             entry_point: Cow::from("init"),
+            entry_point_overrides: Vec::new(),
+            specialization_constants: Vec::new(),
         });
         let update_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             label: None,
             layout: Some(vec![texture_bind_group_layout.clone()]),
+            push_constant_ranges: Vec::new(),
             shader,
-            shader_defs: vec![],
+            shader_defs: ShaderDefs::new(),
             entry_point: Cow::from("update"),
+            entry_point_overrides: Vec::new(),
+            specialization_constants: Vec::new(),
         });
 
         GameOfLifePipeline {
@@ -0,0 +1,218 @@
+//! A skybox material sampling a cube map texture.
+
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::MaterialPipeline,
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_asset::{
+            DependentRenderAsset, PrepareAssetError, RenderAsset, RenderAssetDependencyPlugin,
+            RenderAssets,
+        },
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            Extent3d, RenderPipelineDescriptor, SamplerBindingType, ShaderStages,
+            SpecializedMeshPipelineError, TextureDimension, TextureFormat, TextureSampleType,
+            TextureViewDimension,
+        },
+        renderer::RenderDevice,
+    },
+};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(MaterialPlugin::<SkyboxMaterial>::default())
+        .add_plugin(RenderAssetDependencyPlugin::<SkyboxMaterial, Image>::default())
+        .add_startup_system(setup)
+        .add_system(rotate_camera)
+        .run();
+}
+
+#[derive(Component)]
+struct MainCamera;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut skybox_materials: ResMut<Assets<SkyboxMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // A cube map with a different color on each face. The faces are stacked vertically, in the
+    // order +X, -X, +Y, -Y, +Z and -Z.
+    let face_colors: [[u8; 4]; 6] = [
+        [255, 64, 64, 255],
+        [64, 255, 255, 255],
+        [64, 255, 64, 255],
+        [255, 64, 255, 255],
+        [64, 64, 255, 255],
+        [255, 255, 64, 255],
+    ];
+    let face_size = 16;
+    let data = face_colors
+        .iter()
+        .flat_map(|color| color.repeat(face_size * face_size))
+        .collect();
+    let mut environment = Image::new(
+        Extent3d {
+            width: face_size as u32,
+            height: face_size as u32 * 6,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    environment.reinterpret_stacked_2d_as_array(6);
+    environment.reinterpret_as_cube();
+
+    // the skybox is a large cube surrounding the scene, which is seen from the inside
+    commands.spawn().insert_bundle(MaterialMeshBundle {
+        mesh: meshes.add(Mesh::from(shape::Cube { size: 100.0 })),
+        material: skybox_materials.add(SkyboxMaterial {
+            environment: images.add(environment),
+        }),
+        ..default()
+    });
+
+    commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 8,
+        })),
+        material: standard_materials.add(Color::WHITE.into()),
+        ..default()
+    });
+    commands.spawn_bundle(PointLightBundle {
+        transform: Transform::from_xyz(4.0, 8.0, 4.0),
+        ..default()
+    });
+
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_xyz(4.0, 2.5, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        })
+        .insert(MainCamera);
+}
+
+fn rotate_camera(mut camera: Query<&mut Transform, With<MainCamera>>, time: Res<Time>) {
+    let cam_transform = camera.single_mut().into_inner();
+
+    cam_transform.rotate_around(
+        Vec3::ZERO,
+        Quat::from_axis_angle(Vec3::Y, 45f32.to_radians() * time.delta_seconds()),
+    );
+    cam_transform.look_at(Vec3::ZERO, Vec3::Y);
+}
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "9ad5a3f0-1d6c-4d9e-b2a4-3f8d1c0e7b52"]
+pub struct SkyboxMaterial {
+    environment: Handle<Image>,
+}
+
+#[derive(Clone)]
+pub struct GpuSkyboxMaterial {
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for SkyboxMaterial {
+    type ExtractedAsset = SkyboxMaterial;
+    type PreparedAsset = GpuSkyboxMaterial;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<RenderAssets<Image>>,
+        SRes<MaterialPipeline<Self>>,
+    );
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        extracted_asset: Self::ExtractedAsset,
+        (render_device, gpu_images, material_pipeline): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let gpu_image = match gpu_images.get(&extracted_asset.environment) {
+            Some(gpu_image) => gpu_image,
+            // if the image isn't loaded yet, try next frame
+            None => return Err(PrepareAssetError::RetryNextUpdate(extracted_asset)),
+        };
+        // binding a texture that isn't a cube map would fail to create the bind group
+        if let Err(err) = gpu_image.check_view_dimension("environment", TextureViewDimension::Cube)
+        {
+            error!("{}", err);
+            return Err(PrepareAssetError::RetryNextUpdate(extracted_asset));
+        }
+
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&gpu_image.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&gpu_image.sampler),
+                },
+            ],
+            label: None,
+            layout: &material_pipeline.material_layout,
+        });
+
+        Ok(GpuSkyboxMaterial { bind_group })
+    }
+}
+
+impl DependentRenderAsset<Image> for SkyboxMaterial {
+    fn dependencies(&self) -> Vec<Handle<Image>> {
+        vec![self.environment.clone()]
+    }
+}
+
+impl Material for SkyboxMaterial {
+    fn fragment_shader(asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(asset_server.load("shaders/cube_map_material.wgsl"))
+    }
+
+    fn bind_group(render_asset: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &render_asset.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: None,
+        })
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // the skybox is seen from the inside, so its front faces must not be culled
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
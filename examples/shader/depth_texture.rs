@@ -0,0 +1,267 @@
+//! Samples the depth texture of the main pass in a later pass, which shows it in the bottom right
+//! corner of the window.
+
+use bevy::{
+    core_pipeline::draw_3d_graph,
+    prelude::*,
+    render::{
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        texture::BevyDefault,
+        view::{ExtractedView, ViewDepthTexture, ViewTarget},
+        RenderApp, RenderStage,
+    },
+};
+
+const DEPTH_PREVIEW_PASS: &str = "depth_preview_pass";
+
+fn main() {
+    let mut app = App::new();
+    // depth textures can't be resolved, so they can only be sampled without MSAA
+    app.insert_resource(Msaa { samples: 1 })
+        .add_plugins(DefaultPlugins)
+        .add_startup_system(setup)
+        .add_system(rotate);
+
+    let render_app = app.sub_app_mut(RenderApp);
+    render_app
+        .init_resource::<DepthPreviewPipeline>()
+        .add_system_to_stage(RenderStage::Queue, queue_depth_preview_bind_groups);
+    let depth_preview_node = DepthPreviewNode::new(&mut render_app.world);
+
+    let mut graph = render_app.world.resource_mut::<RenderGraph>();
+    let draw_3d_graph = graph.get_sub_graph_mut(draw_3d_graph::NAME).unwrap();
+    draw_3d_graph.add_node(DEPTH_PREVIEW_PASS, depth_preview_node);
+    let input_node_id = draw_3d_graph.input_node().unwrap().id;
+    draw_3d_graph
+        .add_slot_edge(
+            input_node_id,
+            draw_3d_graph::input::VIEW_ENTITY,
+            DEPTH_PREVIEW_PASS,
+            DepthPreviewNode::IN_VIEW,
+        )
+        .unwrap();
+    // the depth texture is rendered by the main pass
+    draw_3d_graph
+        .add_node_edge(draw_3d_graph::node::MAIN_PASS, DEPTH_PREVIEW_PASS)
+        .unwrap();
+    draw_3d_graph
+        .validate_render_target_order(draw_3d_graph::node::MAIN_PASS, DEPTH_PREVIEW_PASS)
+        .unwrap();
+
+    app.run();
+}
+
+#[derive(Component)]
+struct Rotates;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Plane { size: 10.0 })),
+        material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+        ..default()
+    });
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+            material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
+            transform: Transform::from_xyz(0.0, 0.5, 0.0),
+            ..default()
+        })
+        .insert(Rotates);
+    commands.spawn_bundle(PointLightBundle {
+        transform: Transform::from_xyz(4.0, 8.0, 4.0),
+        ..default()
+    });
+    commands.spawn_bundle(PerspectiveCameraBundle {
+        transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+fn rotate(mut query: Query<&mut Transform, With<Rotates>>, time: Res<Time>) {
+    for mut transform in query.iter_mut() {
+        transform.rotate(Quat::from_rotation_y(time.delta_seconds()));
+    }
+}
+
+struct DepthPreviewPipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    pipeline: CachedRenderPipelineId,
+}
+
+impl FromWorld for DepthPreviewPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("depth_preview_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: ViewDepthTexture::texture_binding_type(),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: ViewDepthTexture::sampler_binding_type(false),
+                    count: None,
+                },
+            ],
+        });
+        // depth textures can't be filtered unless they are compared against a reference value
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+
+        let shader = world
+            .resource::<AssetServer>()
+            .load("shaders/depth_preview.wgsl");
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some("depth_preview_pipeline".into()),
+            layout: Some(vec![layout.clone()]),
+            vertex: VertexState {
+                shader: shader.clone(),
+                shader_defs: ShaderDefs::new(),
+                entry_point: "vertex".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
+                buffers: Vec::new(),
+                allow_unused_attributes: false,
+            },
+            fragment: Some(FragmentState {
+                shader,
+                shader_defs: ShaderDefs::new(),
+                entry_point: "fragment".into(),
+                entry_point_overrides: Vec::new(),
+                specialization_constants: Vec::new(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: Vec::new(),
+        });
+
+        Self {
+            layout,
+            sampler,
+            pipeline,
+        }
+    }
+}
+
+#[derive(Component)]
+struct DepthPreviewBindGroup(BindGroup);
+
+fn queue_depth_preview_bind_groups(
+    mut commands: Commands,
+    pipeline: Res<DepthPreviewPipeline>,
+    render_device: Res<RenderDevice>,
+    views: Query<(Entity, &ViewDepthTexture)>,
+) {
+    for (entity, depth) in views.iter() {
+        let depth_view = match depth.sampled_view() {
+            Ok(depth_view) => depth_view,
+            Err(err) => {
+                error!("{}", err);
+                continue;
+            }
+        };
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("depth_preview_bind_group"),
+            layout: &pipeline.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(depth_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&pipeline.sampler),
+                },
+            ],
+        });
+        commands
+            .entity(entity)
+            .insert(DepthPreviewBindGroup(bind_group));
+    }
+}
+
+struct DepthPreviewNode {
+    query: QueryState<(
+        &'static ViewTarget,
+        &'static ExtractedView,
+        &'static DepthPreviewBindGroup,
+    )>,
+}
+
+impl DepthPreviewNode {
+    const IN_VIEW: &'static str = "view";
+
+    fn new(world: &mut World) -> Self {
+        Self {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for DepthPreviewNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
+        let (target, view, bind_group) = match self.query.get_manual(world, view_entity) {
+            Ok(query) => query,
+            Err(_) => return Ok(()),
+        };
+        let pipeline_id = world.resource::<DepthPreviewPipeline>().pipeline;
+        let pipeline = match world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline_id)
+        {
+            Some(pipeline) => pipeline,
+            None => return Ok(()),
+        };
+
+        let mut render_pass =
+            render_context
+                .command_encoder
+                .begin_render_pass(&RenderPassDescriptor {
+                    label: Some("depth_preview_pass"),
+                    color_attachments: &[target.get_color_attachment(Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    })],
+                    depth_stencil_attachment: None,
+                });
+        // the preview covers a quarter of the width and height of the view
+        let (width, height) = (view.width as f32 / 4.0, view.height as f32 / 4.0);
+        render_pass.set_viewport(width * 3.0, height * 3.0, width, height, 0.0, 1.0);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group.0, &[]);
+        render_pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
@@ -11,7 +11,7 @@ use bevy::{
         render_component::{ExtractComponent, ExtractComponentPlugin},
         render_phase::{AddRenderCommand, DrawFunctions, RenderPhase, SetItemPipeline},
         render_resource::{
-            PipelineCache, RenderPipelineDescriptor, SpecializedMeshPipeline,
+            PipelineCache, RenderPipelineDescriptor, ShaderStages, SpecializedMeshPipeline,
             SpecializedMeshPipelineError, SpecializedMeshPipelines,
         },
         view::ExtractedView,
@@ -107,16 +107,20 @@ impl SpecializedMeshPipeline for IsRedPipeline {
         (is_red, pbr_pipeline_key): Self::Key,
         layout: &MeshVertexBufferLayout,
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
-        let mut shader_defs = Vec::new();
-        if is_red.0 {
-            shader_defs.push("IS_RED".to_string());
-        }
         let mut descriptor = self.mesh_pipeline.specialize(pbr_pipeline_key, layout)?;
         descriptor.vertex.shader = self.shader.clone();
-        descriptor.vertex.shader_defs = shader_defs.clone();
+        descriptor.vertex.shader_defs.clear();
         let fragment = descriptor.fragment.as_mut().unwrap();
         fragment.shader = self.shader.clone();
-        fragment.shader_defs = shader_defs;
+        fragment.shader_defs.clear();
+        if is_red.0 {
+            // only the fragment shader depends on IS_RED, so the vertex shader is shared by both
+            // pipelines
+            descriptor.push_shader_def("IS_RED", ShaderStages::FRAGMENT);
+        }
+        // shader defs can carry a value, which WGSL shaders read with `#{BRIGHTNESS}`. Both stages
+        // are processed from the same source, so both need the value.
+        descriptor.push_shader_def("BRIGHTNESS 0.8", ShaderStages::VERTEX_FRAGMENT);
         descriptor.layout = Some(vec![
             self.mesh_pipeline.view_layout.clone(),
             self.mesh_pipeline.mesh_layout.clone(),
@@ -5,7 +5,10 @@ use bevy::{
     pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
     prelude::*,
     render::{
-        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        mesh::{
+            FallbackVertexBuffer, GpuBufferInfo, MeshVertexBufferLayout,
+            FALLBACK_VERTEX_BUFFER_SLOT,
+        },
         render_asset::RenderAssets,
         render_component::{ExtractComponent, ExtractComponentPlugin},
         render_phase::{
@@ -13,7 +16,7 @@ use bevy::{
             SetItemPipeline, TrackedRenderPass,
         },
         render_resource::*,
-        renderer::RenderDevice,
+        renderer::{RenderDevice, RenderQueue},
         view::{ComputedVisibility, ExtractedView, Msaa, NoFrustumCulling, Visibility},
         RenderApp, RenderStage,
     },
@@ -147,15 +150,19 @@ fn prepare_instance_buffers(
     mut commands: Commands,
     query: Query<(Entity, &InstanceMaterialData)>,
     render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut buffer_pool: ResMut<BufferPool>,
 ) {
     for (entity, instance_data) in query.iter() {
-        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("instance data buffer"),
-            contents: bytemuck::cast_slice(instance_data.as_slice()),
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-        });
+        // the instance data is uploaded every frame, so reuse the buffers of the previous frames
+        let buffer = buffer_pool.get_with_data(
+            &render_device,
+            &render_queue,
+            BufferUsages::VERTEX,
+            bytemuck::cast_slice(instance_data.as_slice()),
+        );
         commands.entity(entity).insert(InstanceBuffer {
-            buffer,
+            buffer: buffer.clone(),
             length: instance_data.len(),
         });
     }
@@ -182,6 +189,10 @@ impl FromWorld for CustomPipeline {
     }
 }
 
+/// The vertex buffer slot of the instance data, which follows the mesh and the buffer the mesh
+/// pipeline reads missing attributes from.
+const INSTANCE_DATA_SLOT: usize = FALLBACK_VERTEX_BUFFER_SLOT + 1;
+
 impl SpecializedMeshPipeline for CustomPipeline {
     type Key = MeshPipelineKey;
 
@@ -192,22 +203,34 @@ impl SpecializedMeshPipeline for CustomPipeline {
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
         let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
         descriptor.vertex.shader = self.shader.clone();
-        descriptor.vertex.buffers.push(VertexBufferLayout {
-            array_stride: std::mem::size_of::<InstanceData>() as u64,
-            step_mode: VertexStepMode::Instance,
-            attributes: vec![
-                VertexAttribute {
-                    format: VertexFormat::Float32x4,
-                    offset: 0,
-                    shader_location: 3, // shader locations 0-2 are taken up by Position, Normal and UV attributes
-                },
-                VertexAttribute {
-                    format: VertexFormat::Float32x4,
-                    offset: VertexFormat::Float32x4.size(),
-                    shader_location: 4,
-                },
-            ],
-        });
+        // shader locations 0-2 are taken up by the Position, Normal and UV attributes of the mesh
+        // pipeline, so the instance attributes are moved to locations 3 and 4. The attributes
+        // the mesh lacks are read from the fallback buffer, so they count as well
+        let first_location = descriptor
+            .vertex
+            .buffers
+            .iter()
+            .flat_map(|buffer| &buffer.attributes)
+            .map(|attribute| attribute.shader_location + 1)
+            .max()
+            .unwrap_or(0);
+        // meshes with all attributes have no fallback layout, but the fallback buffer is bound
+        // anyway, so that the instance data always uses the same slot
+        descriptor
+            .vertex
+            .buffers
+            .resize_with(INSTANCE_DATA_SLOT, || VertexBufferLayout {
+                array_stride: 0,
+                step_mode: VertexStepMode::Vertex,
+                attributes: Vec::new(),
+            });
+        descriptor.vertex.buffers.push(
+            VertexBufferLayout::from_vertex_formats(
+                VertexStepMode::Instance,
+                [VertexFormat::Float32x4, VertexFormat::Float32x4],
+            )
+            .with_first_shader_location(first_location),
+        );
         descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
         descriptor.layout = Some(vec![
             self.mesh_pipeline.view_layout.clone(),
@@ -229,6 +252,7 @@ pub struct DrawMeshInstanced;
 impl EntityRenderCommand for DrawMeshInstanced {
     type Param = (
         SRes<RenderAssets<Mesh>>,
+        SRes<FallbackVertexBuffer>,
         SQuery<Read<Handle<Mesh>>>,
         SQuery<Read<InstanceBuffer>>,
     );
@@ -236,7 +260,11 @@ impl EntityRenderCommand for DrawMeshInstanced {
     fn render<'w>(
         _view: Entity,
         item: Entity,
-        (meshes, mesh_query, instance_buffer_query): SystemParamItem<'w, '_, Self::Param>,
+        (meshes, fallback_vertex_buffer, mesh_query, instance_buffer_query): SystemParamItem<
+            'w,
+            '_,
+            Self::Param,
+        >,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
         let mesh_handle = mesh_query.get(item).unwrap();
@@ -248,7 +276,11 @@ impl EntityRenderCommand for DrawMeshInstanced {
         };
 
         pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
-        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+        pass.set_vertex_buffer(
+            FALLBACK_VERTEX_BUFFER_SLOT,
+            fallback_vertex_buffer.into_inner().buffer.slice(..),
+        );
+        pass.set_vertex_buffer(INSTANCE_DATA_SLOT, instance_buffer.buffer.slice(..));
 
         match &gpu_mesh.buffer_info {
             GpuBufferInfo::Indexed {
@@ -0,0 +1,225 @@
+//! Binds the uniforms of two component types in a single bind group: a base material that every
+//! pillar has, and an add-on component with the parameters of a wind effect.
+
+use bevy::{
+    core_pipeline::Opaque3d,
+    ecs::{query::QueryItem, system::lifetimeless::Read},
+    pbr::{
+        DrawMesh, MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup,
+        SetMeshViewBindGroup,
+    },
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_asset::RenderAssets,
+        render_component::{
+            BindUniformComponentPlugin, ExtractComponent, ExtractComponentPlugin,
+            SetUniformComponentBindGroup, UniformComponentBindings, UniformComponentPlugin,
+        },
+        render_phase::{AddRenderCommand, DrawFunctions, RenderPhase, SetItemPipeline},
+        render_resource::{std140::AsStd140, *},
+        renderer::RenderDevice,
+        view::ExtractedView,
+        RenderApp, RenderStage,
+    },
+};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WindyMaterialPlugin)
+        .add_startup_system(setup)
+        .add_system(blow)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let mesh = meshes.add(Mesh::from(shape::Box::new(0.3, 2.0, 0.3)));
+    let pillars = [
+        (Color::rgb(0.8, 0.3, 0.2), 0.0),
+        (Color::rgb(0.3, 0.8, 0.2), 0.15),
+        (Color::rgb(0.2, 0.3, 0.8), 0.4),
+    ];
+    for (i, (color, strength)) in pillars.into_iter().enumerate() {
+        commands.spawn_bundle((
+            mesh.clone(),
+            Transform::from_xyz(i as f32 - 1.0, 1.0, 0.0),
+            GlobalTransform::default(),
+            Visibility::default(),
+            ComputedVisibility::default(),
+            BaseMaterial { color },
+            // a strength of zero keeps the first pillar still
+            WindParams {
+                strength,
+                frequency: 1.5,
+                phase: 0.0,
+            },
+        ));
+    }
+
+    commands.spawn_bundle(PerspectiveCameraBundle {
+        transform: Transform::from_xyz(0.0, 1.5, 5.0).looking_at(Vec3::Y, Vec3::Y),
+        ..default()
+    });
+}
+
+/// The color of a pillar.
+#[derive(Component, AsStd140, Clone)]
+struct BaseMaterial {
+    color: Color,
+}
+
+/// Sways the top of a pillar back and forth.
+#[derive(Component, AsStd140, Clone)]
+struct WindParams {
+    strength: f32,
+    frequency: f32,
+    phase: f32,
+}
+
+impl ExtractComponent for BaseMaterial {
+    type Query = Read<Self>;
+    type Filter = ();
+
+    fn extract_component(item: QueryItem<Self::Query>) -> Self {
+        item.clone()
+    }
+}
+
+impl ExtractComponent for WindParams {
+    type Query = Read<Self>;
+    type Filter = ();
+
+    fn extract_component(item: QueryItem<Self::Query>) -> Self {
+        item.clone()
+    }
+}
+
+fn blow(time: Res<Time>, mut winds: Query<&mut WindParams>) {
+    for mut wind in winds.iter_mut() {
+        wind.phase = time.seconds_since_startup() as f32 * wind.frequency;
+    }
+}
+
+pub struct WindyMaterialPlugin;
+
+impl Plugin for WindyMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        // each component type is prepared on its own, and bound under its name
+        app.add_plugin(ExtractComponentPlugin::<BaseMaterial>::default())
+            .add_plugin(ExtractComponentPlugin::<WindParams>::default())
+            .add_plugin(UniformComponentPlugin::<BaseMaterial>::default())
+            .add_plugin(UniformComponentPlugin::<WindParams>::default());
+        app.add_plugin(BindUniformComponentPlugin::<WindyPipeline, BaseMaterial>::new("material"));
+        app.add_plugin(BindUniformComponentPlugin::<WindyPipeline, WindParams>::new("wind"));
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawWindy>()
+            .init_resource::<WindyPipeline>()
+            .init_resource::<SpecializedMeshPipelines<WindyPipeline>>()
+            .add_system_to_stage(RenderStage::Queue, queue_windy);
+    }
+}
+
+// add each entity with a mesh and a `BaseMaterial` to every view's `Opaque3d` render phase
+#[allow(clippy::too_many_arguments)]
+fn queue_windy(
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    windy_pipeline: Res<WindyPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<WindyPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    render_meshes: Res<RenderAssets<Mesh>>,
+    material_meshes: Query<(Entity, &MeshUniform, &Handle<Mesh>), With<BaseMaterial>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Opaque3d>)>,
+) {
+    let draw_windy = opaque_3d_draw_functions
+        .read()
+        .get_id::<DrawWindy>()
+        .unwrap();
+
+    let key = MeshPipelineKey::from_msaa_samples(msaa.samples)
+        | MeshPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleList);
+
+    for (view, mut opaque_phase) in views.iter_mut() {
+        let view_row_2 = view.transform.compute_matrix().inverse().row(2);
+        for (entity, mesh_uniform, mesh_handle) in material_meshes.iter() {
+            if let Some(mesh) = render_meshes.get(mesh_handle) {
+                let pipeline = pipelines
+                    .specialize(&mut pipeline_cache, &windy_pipeline, key, &mesh.layout)
+                    .unwrap();
+                opaque_phase.add(Opaque3d {
+                    entity,
+                    pipeline,
+                    draw_function: draw_windy,
+                    material_bind_group: None,
+                    mesh: Some(mesh_handle.id),
+                    distance: -view_row_2.dot(mesh_uniform.transform.col(3)),
+                });
+            }
+        }
+    }
+}
+
+pub struct WindyPipeline {
+    shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+    uniforms_layout: BindGroupLayout,
+    shader_defs: Vec<String>,
+}
+
+impl FromWorld for WindyPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        let shader = asset_server.load("shaders/uniform_components.wgsl");
+
+        // the layout and shader defs cover all component types bound for this pipeline
+        let render_device = world.resource::<RenderDevice>().clone();
+        let mut bindings = world.resource_mut::<UniformComponentBindings<WindyPipeline>>();
+        let uniforms_layout = bindings.layout(&render_device);
+        let shader_defs = bindings.shader_defs().collect();
+
+        WindyPipeline {
+            shader,
+            mesh_pipeline: world.resource::<MeshPipeline>().clone(),
+            uniforms_layout,
+            shader_defs,
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for WindyPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+        descriptor.vertex.shader = self.shader.clone();
+        descriptor
+            .vertex
+            .shader_defs
+            .extend(self.shader_defs.iter().cloned());
+        let fragment = descriptor.fragment.as_mut().unwrap();
+        fragment.shader = self.shader.clone();
+        fragment
+            .shader_defs
+            .extend(self.shader_defs.iter().cloned());
+        descriptor.layout = Some(vec![
+            self.mesh_pipeline.view_layout.clone(),
+            self.mesh_pipeline.mesh_layout.clone(),
+            self.uniforms_layout.clone(),
+        ]);
+        Ok(descriptor)
+    }
+}
+
+type DrawWindy = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    SetUniformComponentBindGroup<WindyPipeline, 2>,
+    DrawMesh,
+);